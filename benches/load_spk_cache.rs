@@ -0,0 +1,62 @@
+//! Loading a wallet with a large lookahead, with and without its persisted SPK cache.
+//!
+//! The wallet has 2-of-2 multisig descriptors, whose script pubkeys are slow to derive, with
+//! [`REVEALED`] addresses revealed on each keychain and a lookahead of [`LOOKAHEAD`].
+
+use bdk_chain::Merge;
+use bdk_wallet::{ChangeSet, KeychainKind, Wallet};
+use bitcoin::Network;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+const REVEALED: u32 = 2_000;
+const LOOKAHEAD: u32 = 1_000;
+
+const TPUB_A: &str = "tpubDDVNqmq75GNPWQ9UNKfP43UwjaHU4GYfoPavojQbfpyfZp2KetWgjGBRRAy4tYCrAA6SB11mhQAkqxjh1VtQHyKwT4oYxpwLaGHvoKmtxZf";
+const TPUB_B: &str = "tpubDDnGNapGEY6AZAdQbfRJgMg9fvz8pUBrLwvyvUqEgcUfgzM6zc2eVK4vY9x9L5FJWdX8WumXuLEDV5zDZnTfbn87vLe9XceCFwTu9so9Kks";
+
+/// The aggregate changeset of a wallet with [`REVEALED`] addresses on each keychain, and the SPK
+/// cache of its revealed and lookahead script pubkeys.
+fn persisted_changeset() -> ChangeSet {
+    let mut wallet = Wallet::create(
+        format!("wsh(multi(2,{TPUB_A}/0/*,{TPUB_B}/0/*))"),
+        format!("wsh(multi(2,{TPUB_A}/1/*,{TPUB_B}/1/*))"),
+    )
+    .network(Network::Testnet)
+    .lookahead(LOOKAHEAD)
+    .use_spk_cache(true)
+    .create_wallet_no_persist()
+    .unwrap();
+    let mut changeset = wallet.take_staged().unwrap();
+    for keychain in [KeychainKind::External, KeychainKind::Internal] {
+        let _ = wallet.reveal_addresses_to(keychain, REVEALED - 1);
+    }
+    changeset.merge(wallet.take_staged().unwrap());
+    changeset
+}
+
+fn bench_load_spk_cache(c: &mut Criterion) {
+    let changeset = persisted_changeset();
+    let mut group = c.benchmark_group("load_wsh_multi_2000_revealed");
+    group.sample_size(10);
+
+    for (name, use_spk_cache) in [("derived", false), ("spk_cache", true)] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || changeset.clone(),
+                |changeset| {
+                    Wallet::load()
+                        .lookahead(LOOKAHEAD)
+                        .use_spk_cache(use_spk_cache)
+                        .load_wallet_no_persist(changeset)
+                        .unwrap()
+                        .unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_load_spk_cache);
+criterion_main!(benches);
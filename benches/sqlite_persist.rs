@@ -0,0 +1,120 @@
+//! Persisting a large changeset, as after a full scan, to SQLite.
+//!
+//! Compares the batched [`ChangeSet::persist_to_sqlite`] with the row by row writers of
+//! `bdk_chain`, with the default pragmas and with [`SqlitePragmas::fast`].
+
+use bdk_chain::rusqlite::Connection;
+use bdk_chain::{keychain_txout, tx_graph, BlockId, ConfirmationBlockTime, DescriptorId};
+use bdk_wallet::{ChangeSet, SqlitePragmas};
+use bitcoin::hashes::Hash;
+use bitcoin::{
+    absolute, transaction, Amount, OutPoint, ScriptBuf, Transaction as BitcoinTx, TxIn, TxOut,
+    WPubkeyHash,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use tempfile::TempDir;
+
+const TX_COUNT: u32 = 50_000;
+
+/// A changeset of `count` transactions, each with an anchor, a last seen time and a cached script
+/// pubkey.
+fn synthetic_changeset(count: u32) -> ChangeSet {
+    let did = DescriptorId::from_byte_array([0x42; 32]);
+    let mut tx_graph = tx_graph::ChangeSet::<ConfirmationBlockTime>::default();
+    let mut indexer = keychain_txout::ChangeSet::default();
+    for i in 0..count {
+        let spk = ScriptBuf::new_p2wpkh(&WPubkeyHash::hash(&i.to_le_bytes()));
+        let tx = BitcoinTx {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Hash::hash(&i.to_be_bytes()), 0),
+                ..Default::default()
+            }],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(10_000),
+                    script_pubkey: spk.clone(),
+                },
+                TxOut {
+                    value: Amount::from_sat(20_000),
+                    script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::hash(&i.to_be_bytes())),
+                },
+            ],
+        };
+        let txid = tx.compute_txid();
+        tx_graph.txs.insert(tx.into());
+        tx_graph.anchors.insert((
+            ConfirmationBlockTime {
+                block_id: BlockId {
+                    height: i,
+                    hash: Hash::hash(&i.to_le_bytes()),
+                },
+                confirmation_time: 1_700_000_000 + u64::from(i),
+            },
+            txid,
+        ));
+        tx_graph.last_seen.insert(txid, 1_700_000_000);
+        indexer.spk_cache.entry(did).or_default().insert(i, spk);
+    }
+    indexer.last_revealed.insert(did, count - 1);
+    ChangeSet {
+        tx_graph,
+        indexer,
+        ..Default::default()
+    }
+}
+
+/// A new wallet database in a temporary directory.
+fn setup(pragmas: SqlitePragmas) -> (TempDir, Connection) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut conn = Connection::open(dir.path().join("wallet.sqlite")).unwrap();
+    pragmas.apply(&conn).unwrap();
+    let db_tx = conn.transaction().unwrap();
+    ChangeSet::init_sqlite_tables(&db_tx).unwrap();
+    db_tx.commit().unwrap();
+    (dir, conn)
+}
+
+fn bench_sqlite_persist(c: &mut Criterion) {
+    let changeset = synthetic_changeset(TX_COUNT);
+    let mut group = c.benchmark_group("sqlite_persist_50k_txs");
+    group.sample_size(10);
+
+    let variants = [
+        ("default_pragmas", SqlitePragmas::default()),
+        ("fast_pragmas", SqlitePragmas::fast()),
+    ];
+    for (name, pragmas) in variants {
+        group.bench_function(format!("row_by_row/{name}"), |b| {
+            b.iter_batched(
+                || setup(pragmas),
+                |(dir, mut conn)| {
+                    let db_tx = conn.transaction().unwrap();
+                    changeset.local_chain.persist_to_sqlite(&db_tx).unwrap();
+                    changeset.tx_graph.persist_to_sqlite(&db_tx).unwrap();
+                    changeset.indexer.persist_to_sqlite(&db_tx).unwrap();
+                    db_tx.commit().unwrap();
+                    (dir, conn)
+                },
+                BatchSize::PerIteration,
+            )
+        });
+        group.bench_function(format!("batched/{name}"), |b| {
+            b.iter_batched(
+                || setup(pragmas),
+                |(dir, mut conn)| {
+                    let db_tx = conn.transaction().unwrap();
+                    changeset.persist_to_sqlite(&db_tx).unwrap();
+                    db_tx.commit().unwrap();
+                    (dir, conn)
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sqlite_persist);
+criterion_main!(benches);
@@ -5,20 +5,20 @@ use bdk_bitcoind_rpc::{
 use bdk_wallet::rusqlite::Connection;
 use bdk_wallet::{
     bitcoin::{Block, Network},
-    KeychainKind, Wallet,
+    AutoPersist, KeychainKind, Wallet, WalletEvent,
 };
 use clap::{self, Parser};
 use std::{
     path::PathBuf,
     sync::{mpsc::sync_channel, Arc},
     thread::spawn,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 /// Bitcoind RPC example using `bdk_wallet::Wallet`.
 ///
 /// This syncs the chain block-by-block and prints the current balance, transaction count and UTXO
-/// count.
+/// count. Changes to the wallet's transactions and chain reorgs are printed as they're applied.
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 #[clap(propagate_version = true)]
@@ -83,6 +83,11 @@ enum Emission {
     Mempool(MempoolEvent),
 }
 
+/// Number of staged transactions after which the wallet is persisted while syncing.
+const PERSIST_MAX_STAGED_TXS: usize = 100;
+/// Maximum time the wallet changes stay unpersisted while syncing.
+const PERSIST_MAX_INTERVAL: Duration = Duration::from_secs(10);
+
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
@@ -129,6 +134,11 @@ fn main() -> anyhow::Result<()> {
         wallet_tip.height()
     );
 
+    // Persisting after every block is slow, so the changes are persisted in batches instead.
+    let mut wallet = AutoPersist::new(wallet, db)
+        .max_staged_txs(PERSIST_MAX_STAGED_TXS)
+        .max_interval(PERSIST_MAX_INTERVAL);
+
     let (sender, receiver) = sync_channel::<Emission>(21);
 
     let signal_sender = sender.clone();
@@ -167,24 +177,40 @@ fn main() -> anyhow::Result<()> {
                 let hash = block_emission.block_hash();
                 let connected_to = block_emission.connected_to();
                 let start_apply_block = Instant::now();
-                wallet.apply_block_connected_to(&block_emission.block, height, connected_to)?;
-                wallet.persist(&mut db)?;
+                let events = wallet.update(|wallet| {
+                    wallet.apply_block_connected_to_events(
+                        &block_emission.block,
+                        height,
+                        connected_to,
+                    )
+                })??;
                 let elapsed = start_apply_block.elapsed().as_secs_f32();
                 println!("Applied block {hash} at height {height} in {elapsed}s");
+                print_events(&events);
             }
             Emission::Mempool(event) => {
                 let start_apply_mempool = Instant::now();
-                wallet.apply_evicted_txs(event.evicted);
-                wallet.apply_unconfirmed_txs(event.update);
-                wallet.persist(&mut db)?;
+                let events = wallet.update(|wallet| {
+                    let mut events = wallet.apply_evicted_txs_events(event.evicted);
+                    events.extend(wallet.apply_unconfirmed_txs_events(event.update));
+                    events
+                })?;
+                // The wallet is synced, so the changes are persisted right away. Nothing is
+                // written when there are no changes.
+                if !wallet.flush()? {
+                    println!("No wallet changes to persist");
+                }
                 println!(
                     "Applied unconfirmed transactions in {}s",
                     start_apply_mempool.elapsed().as_secs_f32()
                 );
+                print_events(&events);
                 break;
             }
         }
     }
+    // Also done when the wallet is dropped, but errors would be ignored.
+    wallet.flush()?;
     let wallet_tip_end = wallet.latest_checkpoint();
     let balance = wallet.balance();
     println!(
@@ -206,3 +232,42 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Print the changes to the wallet's transactions, and chain reorgs.
+fn print_events(events: &[WalletEvent]) {
+    for event in events {
+        match event {
+            WalletEvent::ReorgDetected {
+                depth,
+                old_tip,
+                new_tip,
+            } => println!(
+                "Reorg of depth {depth}: tip {}:{} replaced by {}:{}",
+                old_tip.height, old_tip.hash, new_tip.height, new_tip.hash
+            ),
+            WalletEvent::TxConfirmed {
+                txid, block_time, ..
+            } => println!(
+                "Transaction {txid} confirmed at height {}",
+                block_time.block_id.height
+            ),
+            WalletEvent::TxUnconfirmed {
+                txid,
+                old_block_time: Some(old_block_time),
+                ..
+            } => println!(
+                "Transaction {txid} no longer confirmed at height {}",
+                old_block_time.block_id.height
+            ),
+            WalletEvent::TxUnconfirmed { txid, .. } => {
+                println!("Transaction {txid} seen in the mempool")
+            }
+            WalletEvent::TxReplaced { txid, .. } => println!("Transaction {txid} replaced"),
+            WalletEvent::TxDropped { txid, .. } => {
+                println!("Transaction {txid} evicted from the mempool")
+            }
+            // the chain tip is printed with each block
+            _ => {}
+        }
+    }
+}
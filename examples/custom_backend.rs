@@ -0,0 +1,168 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+extern crate bdk_wallet;
+
+use core::error::Error;
+use std::collections::BTreeMap;
+
+use bdk_wallet::bitcoin::hashes::Hash;
+use bdk_wallet::bitcoin::{
+    absolute, constants, transaction, Amount, BlockHash, Network, OutPoint, ScriptBuf, Transaction,
+    TxIn, TxOut,
+};
+use bdk_wallet::chain::{BlockId, ConfirmationBlockTime};
+use bdk_wallet::{KeychainKind, Wallet};
+
+const EXTERNAL_DESC: &str = "wpkh(tprv8ZgxMBicQKsPdy6LMhUtFHAgpocR8GC6QmwMSFpZs7h6Eziw3SpThFfczTDh5rW2krkqffa11UpX3XkeTTB2FvzZKWXqPY54Y6Rq4AQ5R8L/84'/1'/0'/0/*)";
+const INTERNAL_DESC: &str = "wpkh(tprv8ZgxMBicQKsPdy6LMhUtFHAgpocR8GC6QmwMSFpZs7h6Eziw3SpThFfczTDh5rW2krkqffa11UpX3XkeTTB2FvzZKWXqPY54Y6Rq4AQ5R8L/84'/1'/0'/1/*)";
+const STOP_GAP: u32 = 20;
+
+/// Height and time of the block confirming a transaction
+type Confirmation = (u32, u64);
+
+/// A minimal chain source: an in-memory index of the blocks of the chain, and of the
+/// transactions paying to each script pubkey.
+///
+/// A real backend, e.g. a custom indexer or a compact block filter client, answers the same
+/// queries over the network.
+#[derive(Default)]
+struct Indexer {
+    blocks: Vec<BlockId>,
+    /// Transactions by script pubkey, with their confirmation if any
+    history: BTreeMap<ScriptBuf, Vec<(Transaction, Option<Confirmation>)>>,
+}
+
+impl Indexer {
+    fn new() -> Self {
+        let genesis = constants::genesis_block(Network::Regtest).block_hash();
+        Self {
+            blocks: vec![BlockId {
+                height: 0,
+                hash: genesis,
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Mine a block confirming `txs`.
+    fn mine(&mut self, txs: impl IntoIterator<Item = Transaction>) {
+        let height = self.blocks.len() as u32;
+        self.blocks.push(BlockId {
+            height,
+            hash: BlockHash::from_byte_array([height as u8; 32]),
+        });
+        for tx in txs {
+            self.index(tx, Some((height, 1_700_000_000 + height as u64 * 600)));
+        }
+    }
+
+    /// Add `tx` to the mempool.
+    fn broadcast(&mut self, tx: Transaction) {
+        self.index(tx, None);
+    }
+
+    fn index(&mut self, tx: Transaction, confirmation: Option<Confirmation>) {
+        for txout in &tx.output {
+            self.history
+                .entry(txout.script_pubkey.clone())
+                .or_default()
+                .push((tx.clone(), confirmation));
+        }
+    }
+}
+
+/// Sync `wallet` with `indexer`, returning the number of transactions found.
+fn sync(wallet: &mut Wallet, indexer: &Indexer) -> Result<usize, Box<dyn Error>> {
+    let mut builder = wallet.update_builder();
+
+    // Every block above the wallet's tip is new. A backend able to detect reorgs would also
+    // replace the blocks of the wallet's chain that are no longer in the best chain.
+    let tip_height = wallet.latest_checkpoint().height();
+    builder.extend_chain(indexer.blocks.iter().copied().skip(tip_height as usize + 1))?;
+
+    let mut found = 0;
+    for keychain in [KeychainKind::External, KeychainKind::Internal] {
+        let mut unused = 0;
+        for (index, spk) in wallet.unbounded_spk_iter(keychain) {
+            let Some(history) = indexer.history.get(&spk) else {
+                unused += 1;
+                if unused == STOP_GAP {
+                    break;
+                }
+                continue;
+            };
+            unused = 0;
+            builder.set_last_active_index(keychain, index);
+            for (tx, confirmation) in history {
+                found += 1;
+                match confirmation {
+                    Some((height, time)) => {
+                        let anchor = ConfirmationBlockTime {
+                            block_id: indexer.blocks[*height as usize],
+                            confirmation_time: *time,
+                        };
+                        builder.add_tx(tx.clone(), anchor);
+                    }
+                    None => {
+                        builder.add_unconfirmed(tx.clone(), 1_700_000_000);
+                    }
+                }
+            }
+        }
+    }
+
+    // The update is only built if the anchors point to blocks of the chain, so a bug in the
+    // backend can't leave transactions confirmed in unknown blocks.
+    wallet.apply_update(builder.build()?)?;
+    Ok(found)
+}
+
+fn pay_to(script_pubkey: ScriptBuf, sats: u64, nonce: u32) -> Transaction {
+    Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Hash::all_zeros(), nonce),
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(sats),
+            script_pubkey,
+        }],
+    }
+}
+
+/// This example syncs a wallet with a custom chain source, using the
+/// [`UpdateBuilder`](bdk_wallet::UpdateBuilder) to turn the data it returns into an update.
+#[allow(clippy::print_stdout)]
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut wallet = Wallet::create(EXTERNAL_DESC, INTERNAL_DESC)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()?;
+    let mut indexer = Indexer::new();
+
+    let first = wallet.peek_address(KeychainKind::External, 0);
+    let third = wallet.peek_address(KeychainKind::External, 2);
+    indexer.mine([pay_to(first.script_pubkey(), 50_000, 0)]);
+    indexer.mine([pay_to(third.script_pubkey(), 20_000, 1)]);
+    indexer.broadcast(pay_to(first.script_pubkey(), 5_000, 2));
+
+    let found = sync(&mut wallet, &indexer)?;
+    println!("Found {found} transactions");
+    println!("Chain tip: {}", wallet.latest_checkpoint().height());
+    println!("Balance: {}", wallet.balance());
+    println!(
+        "Next unused address: {}",
+        wallet.next_unused_address(KeychainKind::External)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,232 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2025 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Address derivation without a wallet
+//!
+//! This module contains [`AddressIterator`], which derives the addresses of a descriptor without
+//! requiring any chain state or persistence.
+
+use core::ops::RangeBounds;
+
+use bdk_chain::SpkIterator;
+use bitcoin::{Address, Network};
+
+use super::{check_wallet_descriptor, DescriptorError, ExtendedDescriptor, IntoWalletDescriptor};
+use crate::wallet::utils::SecpCtx;
+
+/// An iterator over the `(index, address)` pairs of a descriptor.
+///
+/// The descriptor goes through the same checks a [`Wallet`](crate::Wallet) applies to its
+/// descriptors, so the addresses yielded are the same as the ones returned by
+/// [`Wallet::peek_address`](crate::Wallet::peek_address) for the same descriptor and network.
+///
+/// Non-wildcard descriptors only yield a single address at index 0.
+///
+/// ## Example
+///
+/// ```
+/// # use bdk_wallet::descriptor::AddressIterator;
+/// # use bdk_wallet::bitcoin::Network;
+/// let descriptor = "wpkh(tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq/0/*)";
+/// let mut addresses = AddressIterator::new_with_range(descriptor, Network::Testnet, 10..20)?;
+/// let (index, address) = addresses.next().expect("must derive an address");
+/// assert_eq!(index, 10);
+/// assert_eq!(address.to_string(), "tb1qhcrdamm04yt2mc3q7da6zsy5599y5eprv5rvfy");
+/// assert_eq!(addresses.count(), 9);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct AddressIterator {
+    spk_iter: SpkIterator<ExtendedDescriptor>,
+    network: Network,
+}
+
+impl AddressIterator {
+    /// Create an iterator over all the addresses of `descriptor` on `network`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `descriptor` is invalid, does not match `network`, is a multipath descriptor or
+    /// otherwise cannot be used by a [`Wallet`](crate::Wallet).
+    pub fn new<D: IntoWalletDescriptor>(
+        descriptor: D,
+        network: Network,
+    ) -> Result<Self, DescriptorError> {
+        Self::new_with_range(descriptor, network, ..)
+    }
+
+    /// Create an iterator over the addresses of `descriptor` on `network` whose derivation
+    /// index is within `range`.
+    ///
+    /// Refer to [`new`](Self::new) for more.
+    pub fn new_with_range<D, R>(
+        descriptor: D,
+        network: Network,
+        range: R,
+    ) -> Result<Self, DescriptorError>
+    where
+        D: IntoWalletDescriptor,
+        R: RangeBounds<u32>,
+    {
+        let secp = SecpCtx::new();
+        let (descriptor, _) = descriptor.into_wallet_descriptor(&secp, network.into())?;
+        check_wallet_descriptor(&descriptor)?;
+
+        Ok(Self {
+            spk_iter: SpkIterator::new_with_range(descriptor, range),
+            network,
+        })
+    }
+
+    /// Get the descriptor the addresses are derived from.
+    pub fn descriptor(&self) -> &ExtendedDescriptor {
+        self.spk_iter.descriptor()
+    }
+
+    /// Get the [`Network`] of the derived addresses.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+}
+
+impl Iterator for AddressIterator {
+    type Item = (u32, Address);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, spk) = self.spk_iter.next()?;
+        let address = Address::from_script(&spk, self.network).expect("must have address form");
+        Some((index, address))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let (index, spk) = self.spk_iter.nth(n)?;
+        let address = Address::from_script(&spk, self.network).expect("must have address form");
+        Some((index, address))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    use crate::test_utils::*;
+    use crate::{KeychainKind, Wallet};
+
+    #[test]
+    fn test_matches_wallet_peek_address() {
+        let descriptors = [
+            get_test_pkh(),
+            get_test_wpkh(),
+            get_test_single_sig_csv(),
+            get_test_tr_single_sig(),
+            get_test_tr_with_taptree(),
+            get_test_tr_single_sig_xprv_and_change_desc().0,
+            get_test_wpkh_and_change_desc().0,
+        ];
+        for descriptor in descriptors {
+            let wallet = Wallet::create_single(descriptor)
+                .network(Network::Regtest)
+                .create_wallet_no_persist()
+                .unwrap();
+            let addresses = AddressIterator::new(descriptor, Network::Regtest)
+                .unwrap()
+                .take(5)
+                .collect::<Vec<_>>();
+            let expected_len = if wallet
+                .public_descriptor(KeychainKind::External)
+                .has_wildcard()
+            {
+                5
+            } else {
+                1
+            };
+            assert_eq!(addresses.len(), expected_len);
+            for (index, address) in addresses {
+                assert_eq!(
+                    address,
+                    wallet.peek_address(KeychainKind::External, index).address
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        let (descriptor, _) = get_test_tr_single_sig_xprv_and_change_desc();
+        let wallet = Wallet::create_single(descriptor)
+            .network(Network::Signet)
+            .create_wallet_no_persist()
+            .unwrap();
+
+        let addresses = AddressIterator::new_with_range(descriptor, Network::Signet, 5..=7)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(addresses.len(), 3);
+        for (index, address) in addresses {
+            assert_eq!(
+                address,
+                wallet.peek_address(KeychainKind::External, index).address
+            );
+        }
+
+        let mut addresses = AddressIterator::new(descriptor, Network::Signet).unwrap();
+        assert_eq!(
+            addresses.nth(100),
+            Some((
+                100,
+                wallet.peek_address(KeychainKind::External, 100).address
+            ))
+        );
+    }
+
+    #[test]
+    fn test_non_wildcard() {
+        let descriptor = get_test_wpkh();
+        let addresses = AddressIterator::new(descriptor, Network::Regtest)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[0].0, 0);
+    }
+
+    #[test]
+    fn test_invalid_descriptors() {
+        // multipath descriptors must be split first
+        let two_path = "wpkh([9a6a2580/84'/1'/0']tpubDDnGNapGEY6AZAdQbfRJgMg9fvz8pUBrLwvyvUqEgcUfgzM6zc2eVK4vY9x9L5FJWdX8WumXuLEDV5zDZnTfbn87vLe9XceCFwTu9so9Kks/<0;1>/*)";
+        assert!(matches!(
+            AddressIterator::new(two_path, Network::Testnet),
+            Err(DescriptorError::Miniscript(_))
+        ));
+
+        // hardened derivation steps can't be derived from an xpub
+        let hardened = "wpkh(tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq/0'/*)";
+        assert!(matches!(
+            AddressIterator::new(hardened, Network::Testnet),
+            Err(DescriptorError::HardenedDerivationXpub)
+        ));
+
+        // the network of the keys must match
+        let mainnet = "wpkh(xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8/0/*)";
+        assert!(matches!(
+            AddressIterator::new(mainnet, Network::Testnet),
+            Err(DescriptorError::Key(_))
+        ));
+
+        // and so must the checksum
+        let bad_checksum = "wpkh(tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq/0/*)#aaaaaaaa";
+        assert!(matches!(
+            AddressIterator::new(bad_checksum, Network::Testnet),
+            Err(DescriptorError::InvalidDescriptorChecksum)
+        ));
+    }
+}
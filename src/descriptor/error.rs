@@ -44,6 +44,28 @@ pub enum Error {
     Hex(bitcoin::hex::HexToBytesError),
     /// The provided wallet descriptors are identical
     ExternalAndInternalAreTheSame,
+    /// Error while decoding extended keys with SLIP-0132 version bytes
+    Slip132(crate::keys::slip132::Error),
+    /// An extended public key has hardened derivation steps and its extended private key isn't
+    /// available to apply them, see [`rebase_hardened`](crate::descriptor::rebase_hardened)
+    HardenedStepWithoutPrivateKey {
+        /// The extended public key
+        key: alloc::string::String,
+        /// Position of the first hardened step in the derivation path of the key
+        index: usize,
+        /// The first hardened step
+        step: bitcoin::bip32::ChildNumber,
+    },
+    /// An extended key has a hardened wildcard, which can't be derived without the private key
+    HardenedWildcard {
+        /// The extended public key
+        key: alloc::string::String,
+    },
+    /// The extended keys of the descriptor don't all belong to the same network kind
+    MixedNetworks,
+    /// The descriptor contains secret keys, which a [watch-only](crate::CreateParams::watch_only)
+    /// wallet doesn't allow
+    SecretKeysNotAllowed,
 }
 
 impl From<crate::keys::KeyError> for Error {
@@ -84,6 +106,23 @@ impl fmt::Display for Error {
             Self::ExternalAndInternalAreTheSame => {
                 write!(f, "External and internal descriptors are the same")
             }
+            Self::Slip132(err) => write!(f, "SLIP-0132 error: {err}"),
+            Self::HardenedStepWithoutPrivateKey { key, index, step } => write!(
+                f,
+                "The private key of `{key}` is needed to derive its hardened step {step} at position {index}"
+            ),
+            Self::HardenedWildcard { key } => write!(
+                f,
+                "The hardened wildcard of `{key}` can't be derived without the private key"
+            ),
+            Self::MixedNetworks => write!(
+                f,
+                "The descriptor mixes extended keys of different network kinds"
+            ),
+            Self::SecretKeysNotAllowed => write!(
+                f,
+                "The descriptor contains secret keys, which a watch-only wallet doesn't allow"
+            ),
         }
     }
 }
@@ -120,6 +159,12 @@ impl From<bitcoin::hex::HexToBytesError> for Error {
     }
 }
 
+impl From<crate::keys::slip132::Error> for Error {
+    fn from(err: crate::keys::slip132::Error) -> Self {
+        Error::Slip132(err)
+    }
+}
+
 impl From<crate::descriptor::policy::PolicyError> for Error {
     fn from(err: crate::descriptor::policy::PolicyError) -> Self {
         Error::Policy(err)
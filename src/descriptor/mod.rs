@@ -36,18 +36,23 @@ use miniscript::{ForEachKey, MiniscriptKey, TranslatePk};
 
 use crate::descriptor::policy::BuildSatisfaction;
 
+mod address_iter;
 pub mod checksum;
 #[doc(hidden)]
 pub mod dsl;
 pub mod error;
 pub mod policy;
+mod rebase;
 pub mod template;
 
+pub use self::address_iter::AddressIterator;
 pub use self::checksum::calc_checksum;
 pub use self::error::Error as DescriptorError;
 pub use self::policy::Policy;
+pub use self::rebase::rebase_hardened;
+pub(crate) use self::rebase::rebase_keys;
 use self::template::DescriptorTemplateOut;
-use crate::keys::{IntoDescriptorKey, KeyError};
+use crate::keys::{slip132, IntoDescriptorKey, KeyError};
 use crate::wallet::{signer::SignersContainer, utils::SecpCtx};
 
 /// Alias for a [`Descriptor`] that can contain extended keys using [`DescriptorPublicKey`]
@@ -98,8 +103,16 @@ impl IntoWalletDescriptor for &str {
             None => self,
         };
 
-        ExtendedDescriptor::parse_descriptor(secp, descriptor)?
-            .into_wallet_descriptor(secp, network_kind)
+        // convert extended keys with SLIP-0132 version bytes (e.g. `zpub`) to the standard ones
+        match slip132::replace_descriptor_keys(descriptor)? {
+            Some((descriptor, key_script_types)) => {
+                let (descriptor, keymap) = ExtendedDescriptor::parse_descriptor(secp, &descriptor)?;
+                slip132::check_script_types(descriptor.desc_type(), &key_script_types)?;
+                (descriptor, keymap).into_wallet_descriptor(secp, network_kind)
+            }
+            None => ExtendedDescriptor::parse_descriptor(secp, descriptor)?
+                .into_wallet_descriptor(secp, network_kind),
+        }
     }
 }
 
@@ -197,6 +210,8 @@ impl IntoWalletDescriptor for (ExtendedDescriptor, KeyMap) {
             }
         }
 
+        check_network_kinds(&self.0)?;
+
         // Check the `network_kind` for the keys.
         use miniscript::TranslateErr;
         match self.0.translate_pk(&mut Translator {
@@ -326,6 +341,8 @@ pub(crate) fn check_wallet_descriptor(
         return Err(DescriptorError::HardenedDerivationXpub);
     }
 
+    check_network_kinds(descriptor)?;
+
     if descriptor.is_multipath() {
         return Err(DescriptorError::Miniscript(
             miniscript::Error::BadDescriptor(
@@ -341,6 +358,70 @@ pub(crate) fn check_wallet_descriptor(
     Ok(())
 }
 
+/// Make sure all the extended keys of `descriptor` belong to the same network kind.
+fn check_network_kinds(descriptor: &ExtendedDescriptor) -> Result<(), DescriptorError> {
+    let mut network_kind = None;
+    let mixed = descriptor.for_any_key(|k| {
+        let key_network_kind = match k {
+            DescriptorPublicKey::XPub(xpub) => xpub.xkey.network,
+            DescriptorPublicKey::MultiXPub(multi_xpub) => multi_xpub.xkey.network,
+            DescriptorPublicKey::Single(_) => return false,
+        };
+        *network_kind.get_or_insert(key_network_kind) != key_network_kind
+    });
+    if mixed {
+        return Err(DescriptorError::MixedNetworks);
+    }
+
+    Ok(())
+}
+
+/// Merge the `external` and `internal` descriptors into a multipath descriptor with `<0;1>`
+/// steps, if they only differ by their extended keys deriving from `0` and `1` respectively.
+pub(crate) fn merge_multipath(
+    external: &ExtendedDescriptor,
+    internal: &ExtendedDescriptor,
+) -> Option<ExtendedDescriptor> {
+    struct Merger;
+
+    impl miniscript::Translator<DescriptorPublicKey, DescriptorPublicKey, core::convert::Infallible>
+        for Merger
+    {
+        fn pk(
+            &mut self,
+            pk: &DescriptorPublicKey,
+        ) -> Result<DescriptorPublicKey, core::convert::Infallible> {
+            let DescriptorPublicKey::XPub(xpub) = pk else {
+                return Ok(pk.clone());
+            };
+            let Some((ChildNumber::Normal { index: 0 }, parent)) =
+                xpub.derivation_path.as_ref().split_last()
+            else {
+                return Ok(pk.clone());
+            };
+            let parent = DerivationPath::from(parent);
+            let paths = [0, 1].map(|index| parent.child(ChildNumber::Normal { index }));
+            Ok(DescriptorPublicKey::MultiXPub(DescriptorMultiXKey {
+                origin: xpub.origin.clone(),
+                xkey: xpub.xkey,
+                derivation_paths: miniscript::descriptor::DerivPaths::new(paths.to_vec())
+                    .expect("two paths"),
+                wildcard: xpub.wildcard,
+            }))
+        }
+
+        miniscript::translate_hash_clone!(
+            DescriptorPublicKey,
+            DescriptorPublicKey,
+            core::convert::Infallible
+        );
+    }
+
+    let merged = external.translate_pk(&mut Merger).ok()?;
+    let singles = merged.clone().into_single_descriptors().ok()?;
+    (singles.len() == 2 && &singles[0] == external && &singles[1] == internal).then_some(merged)
+}
+
 #[doc(hidden)]
 /// Used internally mainly by the `descriptor!()` and `fragment!()` macros.
 pub trait CheckMiniscript<Ctx: miniscript::ScriptContext> {
@@ -424,20 +505,38 @@ pub(crate) trait DescriptorMeta {
 }
 
 impl DescriptorMeta for ExtendedDescriptor {
+    // Both matches are exhaustive on purpose, so that new descriptor types have to be classified
+    // explicitly.
     fn is_witness(&self) -> bool {
-        matches!(
-            self.desc_type(),
+        match self.desc_type() {
             DescriptorType::Wpkh
-                | DescriptorType::ShWpkh
-                | DescriptorType::Wsh
-                | DescriptorType::ShWsh
-                | DescriptorType::ShWshSortedMulti
-                | DescriptorType::WshSortedMulti
-        )
+            | DescriptorType::ShWpkh
+            | DescriptorType::Wsh
+            | DescriptorType::ShWsh
+            | DescriptorType::ShWshSortedMulti
+            | DescriptorType::WshSortedMulti => true,
+            DescriptorType::Bare
+            | DescriptorType::Sh
+            | DescriptorType::Pkh
+            | DescriptorType::ShSortedMulti
+            | DescriptorType::Tr => false,
+        }
     }
 
     fn is_taproot(&self) -> bool {
-        self.desc_type() == DescriptorType::Tr
+        match self.desc_type() {
+            DescriptorType::Tr => true,
+            DescriptorType::Bare
+            | DescriptorType::Sh
+            | DescriptorType::Pkh
+            | DescriptorType::Wpkh
+            | DescriptorType::ShWpkh
+            | DescriptorType::Wsh
+            | DescriptorType::ShWsh
+            | DescriptorType::ShSortedMulti
+            | DescriptorType::ShWshSortedMulti
+            | DescriptorType::WshSortedMulti => false,
+        }
     }
 
     fn get_extended_keys(&self) -> Vec<DescriptorXKey<Xpub>> {
@@ -890,6 +989,123 @@ mod test {
         assert_eq!(wallet_desc, wallet_desc2)
     }
 
+    #[test]
+    fn test_descriptor_from_str_with_slip132_keys() {
+        let secp = Secp256k1::new();
+
+        // BIP84 test vectors
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let xpub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let (desc, _) = format!("wpkh([73c5da0a/84'/0'/0']{zpub}/0/*)")
+            .into_wallet_descriptor(&secp, NetworkKind::Main)
+            .unwrap();
+        let (expected, _) = format!("wpkh([73c5da0a/84'/0'/0']{xpub}/0/*)")
+            .into_wallet_descriptor(&secp, NetworkKind::Main)
+            .unwrap();
+        assert_eq!(desc, expected);
+        assert_eq!(
+            desc.at_derivation_index(0)
+                .unwrap()
+                .address(bitcoin::Network::Bitcoin)
+                .unwrap()
+                .to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+
+        // the checksum is the one of the original descriptor
+        let original = format!("wpkh({zpub}/0/*)");
+        let checksum = calc_checksum(&original).unwrap();
+        assert!(format!("{original}#{checksum}")
+            .into_wallet_descriptor(&secp, NetworkKind::Main)
+            .is_ok());
+
+        let (desc, keymap) = "wpkh(zprvAWgYBBk7JR8Gjrh4UJQ2uJdG1r3WNRRfURiABBE3RvMXYSrRJL62XuezvGdPvG6GFBZduosCc1YP5wixPox7zhZLfiUm8aunE96BBa4Kei5/84'/0'/0'/0/*)"
+            .into_wallet_descriptor(&secp, NetworkKind::Main)
+            .unwrap();
+        assert_eq!(desc, expected);
+        assert_eq!(keymap.len(), 1);
+
+        let xpub = bip32::Xpub::from_str(xpub).unwrap();
+        let tpub = bip32::Xpub::from_str("tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq").unwrap();
+        for (descriptor, network_kind) in [
+            (
+                format!(
+                    "sh(wpkh({}/0/*))",
+                    slip132::encode_xpub(&xpub, slip132::ScriptType::P2shP2wpkh)
+                ),
+                NetworkKind::Main,
+            ),
+            (
+                format!(
+                    "sh(wpkh({}/0/*))",
+                    slip132::encode_xpub(&tpub, slip132::ScriptType::P2shP2wpkh)
+                ),
+                NetworkKind::Test,
+            ),
+            (
+                format!(
+                    "wpkh({}/0/*)",
+                    slip132::encode_xpub(&tpub, slip132::ScriptType::P2wpkh)
+                ),
+                NetworkKind::Test,
+            ),
+            (
+                format!(
+                    "sh(wsh(multi(1,{}/0/*,{}/1/*)))",
+                    slip132::encode_xpub(&xpub, slip132::ScriptType::P2shP2wsh),
+                    slip132::encode_xpub(&xpub, slip132::ScriptType::P2shP2wsh)
+                ),
+                NetworkKind::Main,
+            ),
+            // some wallets export `zpub`s for multisig too
+            (
+                format!(
+                    "wsh(multi(1,{}/0/*,{}/1/*))",
+                    slip132::encode_xpub(&tpub, slip132::ScriptType::P2wsh),
+                    slip132::encode_xpub(&tpub, slip132::ScriptType::P2wpkh)
+                ),
+                NetworkKind::Test,
+            ),
+        ] {
+            let (desc, _) = descriptor
+                .into_wallet_descriptor(&secp, network_kind)
+                .unwrap();
+            assert_eq!(
+                slip132::replace_descriptor_keys(&desc.to_string()).unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_descriptor_from_str_with_slip132_key_mismatch() {
+        let secp = Secp256k1::new();
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+
+        for (descriptor, descriptor_type) in [
+            (format!("pkh({zpub}/0/*)"), slip132::ScriptType::Legacy),
+            (
+                format!("sh(wpkh({zpub}/0/*))"),
+                slip132::ScriptType::P2shP2wpkh,
+            ),
+            (format!("tr({zpub}/0/*)"), slip132::ScriptType::P2tr),
+        ] {
+            assert_matches!(
+                descriptor.into_wallet_descriptor(&secp, NetworkKind::Main),
+                Err(DescriptorError::Slip132(slip132::Error::ScriptTypeMismatch {
+                    key: slip132::ScriptType::P2wpkh,
+                    descriptor,
+                })) if descriptor == descriptor_type
+            );
+        }
+
+        // the network kind is checked as usual
+        assert_matches!(
+            format!("wpkh({zpub}/0/*)").into_wallet_descriptor(&secp, NetworkKind::Test),
+            Err(DescriptorError::Key(_))
+        );
+    }
+
     #[test]
     fn test_check_wallet_descriptor() {
         let secp = Secp256k1::new();
@@ -924,6 +1140,21 @@ mod test {
         let result = check_wallet_descriptor(&descriptor);
 
         assert!(result.is_err());
+
+        // Keys of different network kinds.
+        let descriptor = ExtendedDescriptor::from_str("wsh(multi(2,xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*,tpubD6NzVbkrYhZ4XHndKkuB8FifXm8r5FQHwrN6oZuWCz13qb93rtgKvD4PQsqC4HP4yhV3tA2fqr2RbY5mNXfM7RxXUoeABoDtsFUq2zJq6YK/0/*))").unwrap();
+        assert_matches!(
+            check_wallet_descriptor(&descriptor),
+            Err(DescriptorError::MixedNetworks)
+        );
+        for network_kind in [NetworkKind::Main, NetworkKind::Test] {
+            assert_matches!(
+                descriptor
+                    .to_string()
+                    .into_wallet_descriptor(&secp, network_kind),
+                Err(DescriptorError::MixedNetworks)
+            );
+        }
     }
 
     #[test]
@@ -1031,4 +1262,55 @@ mod test {
             "should return None rather than panicking on hardened derivation in PSBT key origins"
         );
     }
+
+    #[test]
+    fn test_descriptor_classification() {
+        let pk = "02b4632d08485ff1df2db55b9dafd23347d1c47a457072a1e87be26896549a8737";
+        let pk2 = "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd";
+        let cases = [
+            (format!("pk({pk})"), DescriptorType::Bare, false, false),
+            (format!("sh(pk({pk}))"), DescriptorType::Sh, false, false),
+            (format!("pkh({pk})"), DescriptorType::Pkh, false, false),
+            (format!("wpkh({pk})"), DescriptorType::Wpkh, true, false),
+            (format!("wsh(pk({pk}))"), DescriptorType::Wsh, true, false),
+            (
+                format!("sh(wsh(pk({pk})))"),
+                DescriptorType::ShWsh,
+                true,
+                false,
+            ),
+            (
+                format!("sh(wpkh({pk}))"),
+                DescriptorType::ShWpkh,
+                true,
+                false,
+            ),
+            (
+                format!("sh(sortedmulti(1,{pk},{pk2}))"),
+                DescriptorType::ShSortedMulti,
+                false,
+                false,
+            ),
+            (
+                format!("wsh(sortedmulti(1,{pk},{pk2}))"),
+                DescriptorType::WshSortedMulti,
+                true,
+                false,
+            ),
+            (
+                format!("sh(wsh(sortedmulti(1,{pk},{pk2})))"),
+                DescriptorType::ShWshSortedMulti,
+                true,
+                false,
+            ),
+            (format!("tr({pk})"), DescriptorType::Tr, false, true),
+        ];
+
+        for (desc, desc_type, is_witness, is_taproot) in cases {
+            let descriptor = ExtendedDescriptor::from_str(&desc).unwrap();
+            assert_eq!(descriptor.desc_type(), desc_type, "{desc}");
+            assert_eq!(descriptor.is_witness(), is_witness, "{desc}");
+            assert_eq!(descriptor.is_taproot(), is_taproot, "{desc}");
+        }
+    }
 }
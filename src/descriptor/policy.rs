@@ -47,7 +47,7 @@ use bitcoin::{
     hashes::{hash160, ripemd160, sha256},
     key::XOnlyPublicKey,
     psbt::{self, Psbt},
-    relative, PublicKey, Sequence,
+    relative, PublicKey, Sequence, Weight,
 };
 use miniscript::descriptor::{
     DescriptorPublicKey, ShInner, SinglePub, SinglePubKey, SortedMultiVec, WshInner,
@@ -506,6 +506,60 @@ impl Condition {
     }
 }
 
+/// Weight of an ECDSA signature push in a witness: a 72 bytes signature, the sighash byte and the
+/// length prefix.
+const ECDSA_SIGNATURE_WEIGHT: u64 = 1 + 72 + 1;
+/// Weight of a Schnorr signature push in a witness, with a sighash byte and the length prefix.
+const SCHNORR_SIGNATURE_WEIGHT: u64 = 1 + 64 + 1;
+/// Weight of a 32 bytes preimage push in a witness.
+const PREIMAGE_WEIGHT: u64 = 1 + 32;
+
+/// One way of satisfying a spending policy, see [`Policy::spend_paths`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SpendPath {
+    /// The children selected for each node of the policy tree, which can be passed to
+    /// [`TxBuilder::policy_path`](crate::wallet::tx_builder::TxBuilder::policy_path)
+    pub policy_path: BTreeMap<String, Vec<usize>>,
+    /// The keys that must sign
+    pub signers: Vec<PkOrF>,
+    /// The timelocks that must be satisfied
+    pub condition: Condition,
+    /// Estimated weight of the signatures and preimages satisfying the path
+    ///
+    /// This counts the witness elements of the satisfied items only, not the ones dissatisfying
+    /// the unused branches of the script, and assumes ECDSA signatures for multisigs.
+    pub satisfaction_weight: Weight,
+}
+
+impl SpendPath {
+    fn leaf(signers: Vec<PkOrF>, condition: Condition, satisfaction_weight: u64) -> Self {
+        SpendPath {
+            policy_path: BTreeMap::new(),
+            signers,
+            condition,
+            satisfaction_weight: Weight::from_wu(satisfaction_weight),
+        }
+    }
+
+    fn merge(mut self, other: &SpendPath) -> Result<Self, PolicyError> {
+        self.policy_path.extend(other.policy_path.clone());
+        self.signers.extend(other.signers.iter().cloned());
+        self.condition = self.condition.merge(&other.condition)?;
+        self.satisfaction_weight += other.satisfaction_weight;
+        Ok(self)
+    }
+}
+
+/// A timelock that must be satisfied to spend with a [`SpendPath`], see [`Policy::timelocks`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Timelock {
+    /// Absolute timelock (`after`), the height or time from which the path can be spent
+    Absolute(absolute::LockTime),
+    /// Relative timelock (`older`), the blocks or time after the confirmation of the output
+    /// from which the path can be spent
+    Relative(Sequence),
+}
+
 /// Errors that can happen while extracting and manipulating policies
 #[derive(Debug, PartialEq, Eq)]
 pub enum PolicyError {
@@ -557,6 +611,28 @@ impl Policy {
         }
     }
 
+    /// Returns whether `self` and `other` have the same spending conditions, regardless of how
+    /// much they are satisfied or can be satisfied by the wallet.
+    pub(crate) fn same_conditions(&self, other: &Policy) -> bool {
+        match (&self.item, &other.item) {
+            (
+                SatisfiableItem::Thresh { items, threshold },
+                SatisfiableItem::Thresh {
+                    items: other_items,
+                    threshold: other_threshold,
+                },
+            ) => {
+                threshold == other_threshold
+                    && items.len() == other_items.len()
+                    && items
+                        .iter()
+                        .zip(other_items)
+                        .all(|(item, other_item)| item.same_conditions(other_item))
+            }
+            (item, other_item) => item == other_item,
+        }
+    }
+
     fn make_and(a: Option<Policy>, b: Option<Policy>) -> Result<Option<Policy>, PolicyError> {
         match (a, b) {
             (None, None) => Ok(None),
@@ -739,6 +815,97 @@ impl Policy {
             _ => Ok(Condition::default()),
         }
     }
+
+    /// Enumerate the ways of satisfying the policy
+    ///
+    /// Every path lists the keys that must sign and the timelocks that must be satisfied, e.g. a
+    /// 2-of-3 multisig has three paths, one for each pair of keys. Paths combining timelocks of
+    /// different units can't be satisfied and are left out.
+    ///
+    /// Note that the number of paths grows quickly with the number of thresholds of the policy.
+    pub fn spend_paths(&self) -> Vec<SpendPath> {
+        let default = Condition::default();
+        match &self.item {
+            SatisfiableItem::EcdsaSignature(key) => vec![SpendPath::leaf(
+                vec![key.clone()],
+                default,
+                ECDSA_SIGNATURE_WEIGHT,
+            )],
+            SatisfiableItem::SchnorrSignature(key) => vec![SpendPath::leaf(
+                vec![key.clone()],
+                default,
+                SCHNORR_SIGNATURE_WEIGHT,
+            )],
+            SatisfiableItem::Sha256Preimage { .. }
+            | SatisfiableItem::Hash256Preimage { .. }
+            | SatisfiableItem::Ripemd160Preimage { .. }
+            | SatisfiableItem::Hash160Preimage { .. } => {
+                vec![SpendPath::leaf(vec![], default, PREIMAGE_WEIGHT)]
+            }
+            SatisfiableItem::AbsoluteTimelock { .. } | SatisfiableItem::RelativeTimelock { .. } => {
+                let condition = self.get_condition(&BTreeMap::new()).unwrap_or_default();
+                vec![SpendPath::leaf(vec![], condition, 0)]
+            }
+            SatisfiableItem::Multisig { keys, threshold } => {
+                let indexes = (0..keys.len()).collect::<Vec<_>>();
+                let mut selections = combinations(&indexes, *threshold);
+                selections.sort();
+                selections
+                    .into_iter()
+                    .map(|selected| {
+                        let signers = selected.iter().map(|&i| keys[i].clone()).collect();
+                        // the extra element is the dummy consumed by `OP_CHECKMULTISIG`
+                        let weight = 1 + ECDSA_SIGNATURE_WEIGHT * *threshold as u64;
+                        let mut path = SpendPath::leaf(signers, default, weight);
+                        path.policy_path.insert(self.id.clone(), selected);
+                        path
+                    })
+                    .collect()
+            }
+            SatisfiableItem::Thresh { items, threshold } => {
+                let item_paths = items.iter().map(Policy::spend_paths).collect::<Vec<_>>();
+                let indexes = (0..items.len()).collect::<Vec<_>>();
+                let mut selections = combinations(&indexes, *threshold);
+                selections.sort();
+                selections
+                    .into_iter()
+                    .flat_map(|selected| {
+                        let mut root = SpendPath::leaf(vec![], default, 0);
+                        root.policy_path.insert(self.id.clone(), selected.clone());
+                        selected.iter().fold(vec![root], |paths, &index| {
+                            paths
+                                .iter()
+                                .flat_map(|path| {
+                                    item_paths[index]
+                                        .iter()
+                                        .filter_map(|item_path| path.clone().merge(item_path).ok())
+                                })
+                                .collect()
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns the timelocks of the spend paths of the policy
+    ///
+    /// Every [`SpendPath`] requiring timelocks is listed once for each of them, with its absolute
+    /// timelock first. Paths without timelocks are left out. This can be used to show when the
+    /// paths of a wallet, e.g. the recovery path of an inheritance wallet, become spendable.
+    pub fn timelocks(&self) -> Vec<(SpendPath, Timelock)> {
+        self.spend_paths()
+            .into_iter()
+            .flat_map(|path| {
+                let Condition { csv, timelock } = path.condition;
+                let timelocks = timelock
+                    .map(Timelock::Absolute)
+                    .into_iter()
+                    .chain(csv.map(Timelock::Relative));
+                timelocks.map(move |timelock| (path.clone(), timelock))
+            })
+            .collect()
+    }
 }
 
 impl From<SatisfiableItem> for Policy {
@@ -1597,6 +1764,143 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_spend_paths() {
+        let secp = Secp256k1::new();
+
+        let key = |tprv| setup_keys(tprv, PATH, &secp).1;
+        let [f0, f1, f2] = [TPRV0_STR, TPRV1_STR, ALICE_TPRV_STR].map(|tprv| {
+            PkOrF::Fingerprint(setup_keys::<miniscript::Segwitv0>(tprv, PATH, &secp).2)
+        });
+        let extract = |desc: (Descriptor<DescriptorPublicKey>, _, _)| {
+            let (wallet_desc, keymap) = desc
+                .into_wallet_descriptor(&secp, NetworkKind::Test)
+                .unwrap();
+            let signers_container = SignersContainer::build(keymap, &wallet_desc, &secp);
+            wallet_desc
+                .extract_policy(&signers_container, BuildSatisfaction::None, &secp)
+                .unwrap()
+                .unwrap()
+        };
+
+        // A 2-of-3 multisig can be spent by any pair of keys.
+        let policy = extract(
+            descriptor!(wsh(multi(
+                2,
+                key(TPRV0_STR),
+                key(TPRV1_STR),
+                key(ALICE_TPRV_STR)
+            )))
+            .unwrap(),
+        );
+        let paths = policy.spend_paths();
+        let signers = paths.iter().map(|p| p.signers.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            signers,
+            vec![
+                vec![f0.clone(), f1.clone()],
+                vec![f0.clone(), f2.clone()],
+                vec![f1.clone(), f2]
+            ]
+        );
+        for (path, selected) in paths.iter().zip([[0, 1], [0, 2], [1, 2]]) {
+            assert_eq!(path.policy_path[&policy.id], selected);
+            assert!(path.condition.is_null());
+            assert_eq!(path.satisfaction_weight, Weight::from_wu(1 + 2 * 74));
+            assert_eq!(policy.get_condition(&path.policy_path), Ok(path.condition));
+        }
+
+        // The path going through the timelock requires it.
+        let sequence = 50;
+        let policy = extract(
+            descriptor!(wsh(or_d(
+                multi(2, key(TPRV0_STR), key(TPRV1_STR)),
+                and_v(v: pk(key(TPRV0_STR)), older(sequence))
+            )))
+            .unwrap(),
+        );
+        let paths = policy.spend_paths();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].signers, vec![f0.clone(), f1]);
+        assert!(paths[0].condition.is_null());
+        assert_eq!(paths[1].signers, vec![f0.clone()]);
+        assert_eq!(paths[1].condition.csv, Some(Sequence(sequence)));
+        assert_eq!(paths[1].satisfaction_weight, Weight::from_wu(74));
+        for path in &paths {
+            assert_eq!(policy.get_condition(&path.policy_path), Ok(path.condition));
+        }
+
+        // Timelocks of different units can't be combined.
+        let policy = extract(
+            descriptor!(wsh(or_d(
+                pk(key(TPRV0_STR)),
+                and_v(
+                    v: after(100),
+                    and_v(v: after(500_000_100), pk(key(TPRV1_STR)))
+                )
+            )))
+            .unwrap(),
+        );
+        let paths = policy.spend_paths();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].signers, vec![f0]);
+    }
+
+    #[test]
+    fn test_timelocks() {
+        let secp = Secp256k1::new();
+
+        let key = |tprv| setup_keys(tprv, PATH, &secp).1;
+        let extract = |desc: (Descriptor<DescriptorPublicKey>, _, _)| {
+            let (wallet_desc, keymap) = desc
+                .into_wallet_descriptor(&secp, NetworkKind::Test)
+                .unwrap();
+            let signers_container = SignersContainer::build(keymap, &wallet_desc, &secp);
+            wallet_desc
+                .extract_policy(&signers_container, BuildSatisfaction::None, &secp)
+                .unwrap()
+                .unwrap()
+        };
+
+        // Only the recovery path is timelocked.
+        let policy = extract(
+            descriptor!(wsh(or_d(
+                pk(key(TPRV0_STR)),
+                and_v(v: pk(key(TPRV1_STR)), older(144))
+            )))
+            .unwrap(),
+        );
+        let timelocks = policy.timelocks();
+        assert_eq!(timelocks.len(), 1);
+        assert_eq!(timelocks[0].0, policy.spend_paths()[1]);
+        assert_eq!(timelocks[0].1, Timelock::Relative(Sequence(144)));
+
+        // A path with both kinds of timelocks is listed for each of them.
+        let policy = extract(
+            descriptor!(wsh(and_v(
+                v: pk(key(TPRV0_STR)),
+                and_v(v: after(800_000), older(6))
+            )))
+            .unwrap(),
+        );
+        let timelocks = policy.timelocks();
+        let paths = policy.spend_paths();
+        assert_eq!(
+            timelocks,
+            vec![
+                (
+                    paths[0].clone(),
+                    Timelock::Absolute(absolute::LockTime::from_height(800_000).unwrap())
+                ),
+                (paths[0].clone(), Timelock::Relative(Sequence(6))),
+            ]
+        );
+
+        // A policy without timelocks has none.
+        let policy = extract(descriptor!(wpkh(key(TPRV0_STR))).unwrap());
+        assert!(policy.timelocks().is_empty());
+    }
+
     const ALICE_TPRV_STR:&str = "tprv8ZgxMBicQKsPf6T5X327efHnvJDr45Xnb8W4JifNWtEoqXu9MRYS4v1oYe6DFcMVETxy5w3bqpubYRqvcVTqovG1LifFcVUuJcbwJwrhYzP";
     const BOB_TPRV_STR:&str = "tprv8ZgxMBicQKsPeinZ155cJAn117KYhbaN6MV3WeG6sWhxWzcvX1eg1awd4C9GpUN1ncLEM2rzEvunAg3GizdZD4QPPCkisTz99tXXB4wZArp";
     const CAROL_TPRV_STR:&str = "tprv8ZgxMBicQKsPdC3CicFifuLCEyVVdXVUNYorxUWj3iGZ6nimnLAYAY9SYB7ib8rKzRxrCKFcEytCt6szwd2GHnGPRCBLAEAoSVDefSNk4Bt";
@@ -0,0 +1,376 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2025 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Hardened derivation steps rebasing
+//!
+//! A [`Wallet`](crate::Wallet) only accepts extended public keys without hardened derivation
+//! steps, since it must be able to derive its addresses without the private keys. This module
+//! contains [`rebase_hardened`], which applies the hardened steps of the keys whose extended
+//! private key is known, turning e.g. `wpkh(xpub/84'/0'/0'/0/*)` into the account-level
+//! `wpkh([fingerprint/84'/0'/0']xpub/0/*)`.
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, KeySource, Xpriv, Xpub};
+use bitcoin::Network;
+use miniscript::descriptor::{
+    DescriptorPublicKey, DescriptorSecretKey, DescriptorXKey, KeyMap, Wildcard,
+};
+use miniscript::{TranslatePk, Translator};
+
+use super::{check_wallet_descriptor, DescriptorError, ExtendedDescriptor, IntoWalletDescriptor};
+use crate::wallet::utils::SecpCtx;
+
+/// Apply the hardened derivation steps of the extended keys of `descriptor` using their
+/// extended private keys, returning a descriptor that can be used by a
+/// [`Wallet`](crate::Wallet) along with its keymap.
+///
+/// Each rebased key gets the key origin of the derived key, e.g. `xprv/84'/0'/0'/0/*` becomes
+/// `[fingerprint/84'/0'/0']xpub/0/*`, where `xpub` is the account-level key, and the keymap holds
+/// the matching account-level extended private key.
+///
+/// Descriptor strings containing extended private keys are already rebased when parsed. This is
+/// mostly useful for public descriptors paired with a keymap, which can also be done while
+/// creating a wallet with [`CreateParams::rebase_hardened`](crate::CreateParams::rebase_hardened).
+///
+/// # Errors
+///
+/// Fails with [`DescriptorError::HardenedStepWithoutPrivateKey`] if the extended private key of a
+/// key with hardened derivation steps isn't available, and with
+/// [`DescriptorError::HardenedWildcard`] for keys with a hardened wildcard, since every address
+/// would need the private key to be derived.
+///
+/// ## Example
+///
+/// ```
+/// # use bdk_wallet::descriptor::rebase_hardened;
+/// # use bdk_wallet::bitcoin::Network;
+/// let descriptor = "wpkh(xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu/84'/0'/0'/0/*)";
+/// let (descriptor, keymap) = rebase_hardened(descriptor, Network::Bitcoin)?;
+/// assert_eq!(
+///     descriptor.to_string(),
+///     "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)#wc3n3van"
+/// );
+/// assert_eq!(keymap.len(), 1);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub fn rebase_hardened<D: IntoWalletDescriptor>(
+    descriptor: D,
+    network: Network,
+) -> Result<(ExtendedDescriptor, KeyMap), DescriptorError> {
+    let secp = SecpCtx::new();
+    let (descriptor, keymap) = descriptor.into_wallet_descriptor(&secp, network.into())?;
+    let (descriptor, keymap) = rebase_keys(&secp, descriptor, keymap)?;
+    check_wallet_descriptor(&descriptor)?;
+    Ok((descriptor, keymap))
+}
+
+/// Rebase the keys of `descriptor` with hardened derivation steps, refer to [`rebase_hardened`].
+pub(crate) fn rebase_keys(
+    secp: &SecpCtx,
+    descriptor: ExtendedDescriptor,
+    mut keymap: KeyMap,
+) -> Result<(ExtendedDescriptor, KeyMap), DescriptorError> {
+    struct Rebaser<'a> {
+        secp: &'a SecpCtx,
+        keymap: &'a KeyMap,
+        rebased: Vec<(
+            DescriptorPublicKey,
+            DescriptorPublicKey,
+            DescriptorSecretKey,
+        )>,
+    }
+
+    impl Rebaser<'_> {
+        /// Find the extended private key of `xpub` in the keymap.
+        fn find_xprv(
+            &self,
+            pk: &DescriptorPublicKey,
+            xpub: &DescriptorXKey<Xpub>,
+        ) -> Option<DescriptorXKey<Xpriv>> {
+            let matches = |xprv: &DescriptorXKey<Xpriv>| {
+                Xpub::from_priv(self.secp, &xprv.xkey) == xpub.xkey
+                    && xprv.derivation_path == xpub.derivation_path
+                    && xprv.wildcard == xpub.wildcard
+            };
+            if let Some(DescriptorSecretKey::XPrv(xprv)) = self.keymap.get(pk) {
+                return Some(xprv.clone());
+            }
+            self.keymap.values().find_map(|secret| match secret {
+                DescriptorSecretKey::XPrv(xprv) if matches(xprv) => Some(xprv.clone()),
+                _ => None,
+            })
+        }
+    }
+
+    impl Translator<DescriptorPublicKey, DescriptorPublicKey, DescriptorError> for Rebaser<'_> {
+        fn pk(&mut self, pk: &DescriptorPublicKey) -> Result<DescriptorPublicKey, DescriptorError> {
+            let DescriptorPublicKey::XPub(xpub) = pk else {
+                return Ok(pk.clone());
+            };
+            if xpub.wildcard == Wildcard::Hardened {
+                return Err(DescriptorError::HardenedWildcard {
+                    key: pk.to_string(),
+                });
+            }
+            let Some(last_hardened) = xpub
+                .derivation_path
+                .into_iter()
+                .rposition(ChildNumber::is_hardened)
+            else {
+                return Ok(pk.clone());
+            };
+            let Some(xprv) = self.find_xprv(pk, xpub) else {
+                let index = xpub
+                    .derivation_path
+                    .into_iter()
+                    .position(ChildNumber::is_hardened)
+                    .expect("there is a hardened step");
+                return Err(DescriptorError::HardenedStepWithoutPrivateKey {
+                    key: pk.to_string(),
+                    index,
+                    step: xpub.derivation_path[index],
+                });
+            };
+
+            let hardened_path = DerivationPath::from(&xpub.derivation_path[..=last_hardened]);
+            let derivation_path = DerivationPath::from(&xpub.derivation_path[last_hardened + 1..]);
+            let account_xprv = xprv.xkey.derive_priv(self.secp, &hardened_path)?;
+            let origin: KeySource = match &xpub.origin {
+                Some((fingerprint, path)) => (*fingerprint, path.extend(&hardened_path)),
+                None => (xpub.xkey.fingerprint(), hardened_path),
+            };
+
+            let rebased_pk = DescriptorPublicKey::XPub(DescriptorXKey {
+                origin: Some(origin.clone()),
+                xkey: Xpub::from_priv(self.secp, &account_xprv),
+                derivation_path: derivation_path.clone(),
+                wildcard: xpub.wildcard,
+            });
+            let rebased_sk = DescriptorSecretKey::XPrv(DescriptorXKey {
+                origin: Some(origin),
+                xkey: account_xprv,
+                derivation_path,
+                wildcard: xpub.wildcard,
+            });
+            self.rebased
+                .push((pk.clone(), rebased_pk.clone(), rebased_sk));
+            Ok(rebased_pk)
+        }
+
+        miniscript::translate_hash_clone!(
+            DescriptorPublicKey,
+            DescriptorPublicKey,
+            DescriptorError
+        );
+    }
+
+    use miniscript::TranslateErr;
+    let mut rebaser = Rebaser {
+        secp,
+        keymap: &keymap,
+        rebased: Vec::new(),
+    };
+    let descriptor = match descriptor.translate_pk(&mut rebaser) {
+        Ok(descriptor) => descriptor,
+        Err(TranslateErr::TranslatorErr(e)) => return Err(e),
+        Err(TranslateErr::OuterError(e)) => return Err(e.into()),
+    };
+    let rebased = rebaser.rebased;
+    for (pk, rebased_pk, rebased_sk) in rebased {
+        keymap.remove(&pk);
+        keymap.insert(rebased_pk, rebased_sk);
+    }
+
+    // keys parsed from descriptor strings only have the hardened steps applied to their public
+    // key, replace their private key with the account-level one as well
+    let keymap = keymap
+        .into_iter()
+        .map(|(pk, sk)| match (&pk, sk) {
+            (DescriptorPublicKey::XPub(xpub), DescriptorSecretKey::XPrv(xprv)) => {
+                let sk = rebase_xprv(secp, xpub, xprv);
+                (pk, sk)
+            }
+            (_, sk) => (pk, sk),
+        })
+        .collect();
+
+    Ok((descriptor, keymap))
+}
+
+/// Replace `xprv` with the extended private key of the already rebased `xpub`, if they match.
+fn rebase_xprv(
+    secp: &SecpCtx,
+    xpub: &DescriptorXKey<Xpub>,
+    xprv: DescriptorXKey<Xpriv>,
+) -> DescriptorSecretKey {
+    // the keymap is user-supplied, the private key could have a shorter path than its public key
+    let Some(hardened_len) = xprv
+        .derivation_path
+        .len()
+        .checked_sub(xpub.derivation_path.len())
+    else {
+        return DescriptorSecretKey::XPrv(xprv);
+    };
+    let rebased = xpub.origin.is_some()
+        && hardened_len > 0
+        && xprv.derivation_path[hardened_len..] == xpub.derivation_path[..];
+    if !rebased {
+        return DescriptorSecretKey::XPrv(xprv);
+    }
+    let hardened_path = &xprv.derivation_path[..hardened_len];
+    match xprv.xkey.derive_priv(secp, &hardened_path) {
+        Ok(account_xprv) if Xpub::from_priv(secp, &account_xprv) == xpub.xkey => {
+            DescriptorSecretKey::XPrv(DescriptorXKey {
+                origin: xpub.origin.clone(),
+                xkey: account_xprv,
+                derivation_path: xpub.derivation_path.clone(),
+                wildcard: xprv.wildcard,
+            })
+        }
+        _ => DescriptorSecretKey::XPrv(xprv),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    use core::str::FromStr;
+
+    const XPRV: &str = "xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu";
+    const ACCOUNT_XPUB: &str = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+
+    #[test]
+    fn test_rebase_xprv_descriptor() {
+        let (descriptor, keymap) =
+            rebase_hardened(format!("wpkh({XPRV}/84'/0'/0'/0/*)"), Network::Bitcoin).unwrap();
+        let expected = format!("wpkh([73c5da0a/84'/0'/0']{ACCOUNT_XPUB}/0/*)");
+        assert_eq!(descriptor.to_string().split_once('#').unwrap().0, expected);
+
+        // the keymap holds the account-level key
+        let (pk, sk) = keymap.iter().next().unwrap();
+        assert_eq!(
+            pk.to_string(),
+            format!("[73c5da0a/84'/0'/0']{ACCOUNT_XPUB}/0/*")
+        );
+        match sk {
+            DescriptorSecretKey::XPrv(xprv) => {
+                assert_eq!(xprv.derivation_path.to_string(), "0");
+                assert_eq!(xprv.origin.as_ref().unwrap().1.to_string(), "84'/0'/0'");
+            }
+            _ => panic!("must be an extended private key"),
+        }
+    }
+
+    #[test]
+    fn test_rebase_public_descriptor_with_keymap() {
+        let secp = SecpCtx::new();
+        let master = Xpriv::from_str(XPRV).unwrap();
+        let path = DerivationPath::from_str("m/84'/0'/0'/0").unwrap();
+        let pk = DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: None,
+            xkey: Xpub::from_priv(&secp, &master),
+            derivation_path: path.clone(),
+            wildcard: Wildcard::Unhardened,
+        });
+        let sk = DescriptorSecretKey::XPrv(DescriptorXKey {
+            origin: None,
+            xkey: master,
+            derivation_path: path,
+            wildcard: Wildcard::Unhardened,
+        });
+        let descriptor = ExtendedDescriptor::new_wpkh(pk.clone()).unwrap();
+        assert_eq!(
+            check_wallet_descriptor(&descriptor),
+            Err(DescriptorError::HardenedDerivationXpub)
+        );
+
+        let keymap = KeyMap::from_iter([(pk.clone(), sk)]);
+        let (rebased, rebased_keymap) =
+            rebase_hardened((descriptor, keymap), Network::Bitcoin).unwrap();
+        assert_eq!(
+            rebased.to_string().split_once('#').unwrap().0,
+            format!("wpkh([73c5da0a/84'/0'/0']{ACCOUNT_XPUB}/0/*)")
+        );
+        assert!(!rebased_keymap.contains_key(&pk));
+        assert_eq!(rebased_keymap.len(), 1);
+    }
+
+    #[test]
+    fn test_rebase_without_private_key() {
+        let secp = SecpCtx::new();
+        let xpub = Xpub::from_priv(&secp, &Xpriv::from_str(XPRV).unwrap());
+        let descriptor = format!("wpkh({xpub}/0/1'/2'/*)");
+        let err = rebase_hardened(descriptor.as_str(), Network::Bitcoin).unwrap_err();
+        assert_eq!(
+            err,
+            DescriptorError::HardenedStepWithoutPrivateKey {
+                key: format!("{xpub}/0/1'/2'/*"),
+                index: 1,
+                step: ChildNumber::from_hardened_idx(1).unwrap(),
+            }
+        );
+        assert!(err.to_string().contains("1'"));
+
+        let descriptor = format!("wpkh({XPRV}/0/*')");
+        assert!(matches!(
+            rebase_hardened(descriptor.as_str(), Network::Bitcoin),
+            Err(DescriptorError::HardenedWildcard { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rebase_keeps_other_keys() {
+        let descriptor = format!(
+            "wsh(multi(2,{XPRV}/48'/0'/0'/2'/0/*,{ACCOUNT_XPUB}/0/*,03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd))"
+        );
+        let (rebased, keymap) = rebase_hardened(descriptor.as_str(), Network::Bitcoin).unwrap();
+        let rebased = rebased.to_string();
+        assert!(rebased.contains("[73c5da0a/48'/0'/0'/2']"));
+        assert!(rebased.contains(&format!(",{ACCOUNT_XPUB}/0/*,")));
+        assert!(rebased
+            .contains(",03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)"));
+        assert_eq!(keymap.len(), 1);
+    }
+
+    #[test]
+    fn test_rebase_mismatched_keymap() {
+        // the private key has a shorter derivation path than its public key
+        let secp = SecpCtx::new();
+        let master = Xpriv::from_str(XPRV).unwrap();
+        let account_xprv = master
+            .derive_priv(&secp, &DerivationPath::from_str("m/84'/0'/0'").unwrap())
+            .unwrap();
+        let pk = DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: Some((
+                master.fingerprint(&secp),
+                DerivationPath::from_str("m/84'/0'/0'").unwrap(),
+            )),
+            xkey: Xpub::from_priv(&secp, &account_xprv),
+            derivation_path: DerivationPath::from_str("m/0").unwrap(),
+            wildcard: Wildcard::Unhardened,
+        });
+        let sk = DescriptorSecretKey::XPrv(DescriptorXKey {
+            origin: None,
+            xkey: master,
+            derivation_path: DerivationPath::master(),
+            wildcard: Wildcard::Unhardened,
+        });
+        let descriptor = ExtendedDescriptor::new_wpkh(pk.clone()).unwrap();
+        let keymap = KeyMap::from_iter([(pk.clone(), sk.clone())]);
+        let (rebased, rebased_keymap) =
+            rebase_hardened((descriptor.clone(), keymap), Network::Bitcoin).unwrap();
+        assert_eq!(rebased, descriptor);
+        assert_eq!(rebased_keymap.get(&pk), Some(&sk));
+    }
+}
@@ -243,6 +243,7 @@ impl<K: DerivableKey<Legacy>> DescriptorTemplate for Bip44<K> {
         P2Pkh(legacy::make_bipxx_private(
             44,
             self.0,
+            0,
             self.1,
             network_kind,
         )?)
@@ -297,6 +298,50 @@ impl<K: DerivableKey<Legacy>> DescriptorTemplate for Bip44Public<K> {
     }
 }
 
+/// BIP44 account template. Expands to `pkh(key/44'/{0,1}'/account'/{0,1}/*)`
+///
+/// This is the same as [`Bip44`], except that the hardened account level of the derivation
+/// path is set to the given account number instead of always being `0'`.
+///
+/// Since there are hardened derivation steps, this template requires a private derivable key
+/// (generally a `xprv`/`tprv`).
+///
+/// ## Example
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use bdk_wallet::bitcoin::{PrivateKey, Network};
+/// # use bdk_wallet::{Wallet, KeychainKind};
+/// use bdk_wallet::template::Bip44Account;
+///
+/// let key = bitcoin::bip32::Xpriv::from_str("tprv8ZgxMBicQKsPeZRHk4rTG6orPS2CRNFX3njhUXx5vj9qGog5ZMH4uGReDWN5kCkY3jmWEtWause41CDvBRXD1shKknAMKxT99o9qUTRVC6m")?;
+/// let mut wallet = Wallet::create(
+///     Bip44Account(key.clone(), 1, KeychainKind::External),
+///     Bip44Account(key, 1, KeychainKind::Internal),
+/// )
+/// .network(Network::Testnet)
+/// .create_wallet_no_persist()?;
+///
+/// assert_eq!(wallet.next_unused_address(KeychainKind::External).to_string(), "mxKHyX5knLY6jeSTfVqFnzbbY4aySfjYZt");
+/// assert_eq!(wallet.public_descriptor(KeychainKind::External).to_string(), "pkh([c55b303f/44'/1'/1']tpubDCuorCpzvYS2NA3cTMyMJr8dk1eLqFvHs5FNzXjMcqhkhnduVCgPheULLLDiexApvnfGkwt488ogY4bStkwqnoVacWFfTAoF1Y96QmFJgHF/0/*)#5hdjnpqr");
+/// # Ok::<_, Box<dyn core::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Bip44Account<K: DerivableKey<Legacy>>(pub K, pub u32, pub KeychainKind);
+
+impl<K: DerivableKey<Legacy>> DescriptorTemplate for Bip44Account<K> {
+    fn build(self, network_kind: NetworkKind) -> Result<DescriptorTemplateOut, DescriptorError> {
+        P2Pkh(legacy::make_bipxx_private(
+            44,
+            self.0,
+            self.1,
+            self.2,
+            network_kind,
+        )?)
+        .build(network_kind)
+    }
+}
+
 /// BIP49 template. Expands to `sh(wpkh(key/49'/{0,1}'/0'/{0,1}/*))`
 ///
 /// Since there are hardened derivation steps, this template requires a private derivable key
@@ -332,6 +377,7 @@ impl<K: DerivableKey<Segwitv0>> DescriptorTemplate for Bip49<K> {
         P2Wpkh_P2Sh(segwit_v0::make_bipxx_private(
             49,
             self.0,
+            0,
             self.1,
             network_kind,
         )?)
@@ -386,6 +432,50 @@ impl<K: DerivableKey<Segwitv0>> DescriptorTemplate for Bip49Public<K> {
     }
 }
 
+/// BIP49 account template. Expands to `sh(wpkh(key/49'/{0,1}'/account'/{0,1}/*))`
+///
+/// This is the same as [`Bip49`], except that the hardened account level of the derivation
+/// path is set to the given account number instead of always being `0'`.
+///
+/// Since there are hardened derivation steps, this template requires a private derivable key
+/// (generally a `xprv`/`tprv`).
+///
+/// ## Example
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use bdk_wallet::bitcoin::{PrivateKey, Network};
+/// # use bdk_wallet::{Wallet, KeychainKind};
+/// use bdk_wallet::template::Bip49Account;
+///
+/// let key = bitcoin::bip32::Xpriv::from_str("tprv8ZgxMBicQKsPeZRHk4rTG6orPS2CRNFX3njhUXx5vj9qGog5ZMH4uGReDWN5kCkY3jmWEtWause41CDvBRXD1shKknAMKxT99o9qUTRVC6m")?;
+/// let mut wallet = Wallet::create(
+///     Bip49Account(key.clone(), 1, KeychainKind::External),
+///     Bip49Account(key, 1, KeychainKind::Internal),
+/// )
+/// .network(Network::Testnet)
+/// .create_wallet_no_persist()?;
+///
+/// assert_eq!(wallet.next_unused_address(KeychainKind::External).to_string(), "2MxqLJW2j1qAZVFsYMWZ5rY5H7Lq67EKkxq");
+/// assert_eq!(wallet.public_descriptor(KeychainKind::External).to_string(), "sh(wpkh([c55b303f/49'/1'/1']tpubDDYr4kdnZgjjVGuBLMAK3bJQofnyFDVCtX4CctHS51bymn5KNE28Sd7p7YFjKhQNcYrKWtay25ah9sUASJfJC2tdzxenkfWFxorGhsXmW62/0/*))#gv343s6f");
+/// # Ok::<_, Box<dyn core::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Bip49Account<K: DerivableKey<Segwitv0>>(pub K, pub u32, pub KeychainKind);
+
+impl<K: DerivableKey<Segwitv0>> DescriptorTemplate for Bip49Account<K> {
+    fn build(self, network_kind: NetworkKind) -> Result<DescriptorTemplateOut, DescriptorError> {
+        P2Wpkh_P2Sh(segwit_v0::make_bipxx_private(
+            49,
+            self.0,
+            self.1,
+            self.2,
+            network_kind,
+        )?)
+        .build(network_kind)
+    }
+}
+
 /// BIP84 template. Expands to `wpkh(key/84'/{0,1}'/0'/{0,1}/*)`
 ///
 /// Since there are hardened derivation steps, this template requires a private derivable key
@@ -421,6 +511,7 @@ impl<K: DerivableKey<Segwitv0>> DescriptorTemplate for Bip84<K> {
         P2Wpkh(segwit_v0::make_bipxx_private(
             84,
             self.0,
+            0,
             self.1,
             network_kind,
         )?)
@@ -475,6 +566,50 @@ impl<K: DerivableKey<Segwitv0>> DescriptorTemplate for Bip84Public<K> {
     }
 }
 
+/// BIP84 account template. Expands to `wpkh(key/84'/{0,1}'/account'/{0,1}/*)`
+///
+/// This is the same as [`Bip84`], except that the hardened account level of the derivation
+/// path is set to the given account number instead of always being `0'`.
+///
+/// Since there are hardened derivation steps, this template requires a private derivable key
+/// (generally a `xprv`/`tprv`).
+///
+/// ## Example
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use bdk_wallet::bitcoin::{PrivateKey, Network};
+/// # use bdk_wallet::{Wallet, KeychainKind};
+/// use bdk_wallet::template::Bip84Account;
+///
+/// let key = bitcoin::bip32::Xpriv::from_str("tprv8ZgxMBicQKsPeZRHk4rTG6orPS2CRNFX3njhUXx5vj9qGog5ZMH4uGReDWN5kCkY3jmWEtWause41CDvBRXD1shKknAMKxT99o9qUTRVC6m")?;
+/// let mut wallet = Wallet::create(
+///     Bip84Account(key.clone(), 1, KeychainKind::External),
+///     Bip84Account(key, 1, KeychainKind::Internal),
+/// )
+/// .network(Network::Testnet)
+/// .create_wallet_no_persist()?;
+///
+/// assert_eq!(wallet.next_unused_address(KeychainKind::External).to_string(), "tb1qssy006ph4vggput5699004ywnj20trtu8mpj37");
+/// assert_eq!(wallet.public_descriptor(KeychainKind::External).to_string(), "wpkh([c55b303f/84'/1'/1']tpubDDc5mum24Dekq4yaaRXY36HKFWchmoDDSoviZ6chNQzTofRLxc6T2UQX5DU5gF8DBbbq3sntJoqX6oJnPseLpRrgp2G7J7eHu8HNHEgY5hZ/0/*)#ev55aqez");
+/// # Ok::<_, Box<dyn core::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Bip84Account<K: DerivableKey<Segwitv0>>(pub K, pub u32, pub KeychainKind);
+
+impl<K: DerivableKey<Segwitv0>> DescriptorTemplate for Bip84Account<K> {
+    fn build(self, network_kind: NetworkKind) -> Result<DescriptorTemplateOut, DescriptorError> {
+        P2Wpkh(segwit_v0::make_bipxx_private(
+            84,
+            self.0,
+            self.1,
+            self.2,
+            network_kind,
+        )?)
+        .build(network_kind)
+    }
+}
+
 /// BIP86 template. Expands to `tr(key/86'/{0,1}'/0'/{0,1}/*)`
 ///
 /// Since there are hardened derivation steps, this template requires a private derivable key
@@ -510,6 +645,7 @@ impl<K: DerivableKey<Tap>> DescriptorTemplate for Bip86<K> {
         P2TR(segwit_v1::make_bipxx_private(
             86,
             self.0,
+            0,
             self.1,
             network_kind,
         )?)
@@ -564,6 +700,50 @@ impl<K: DerivableKey<Tap>> DescriptorTemplate for Bip86Public<K> {
     }
 }
 
+/// BIP86 account template. Expands to `tr(key/86'/{0,1}'/account'/{0,1}/*)`
+///
+/// This is the same as [`Bip86`], except that the hardened account level of the derivation
+/// path is set to the given account number instead of always being `0'`.
+///
+/// Since there are hardened derivation steps, this template requires a private derivable key
+/// (generally a `xprv`/`tprv`).
+///
+/// ## Example
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use bdk_wallet::bitcoin::{PrivateKey, Network};
+/// # use bdk_wallet::{Wallet, KeychainKind};
+/// use bdk_wallet::template::Bip86Account;
+///
+/// let key = bitcoin::bip32::Xpriv::from_str("tprv8ZgxMBicQKsPeZRHk4rTG6orPS2CRNFX3njhUXx5vj9qGog5ZMH4uGReDWN5kCkY3jmWEtWause41CDvBRXD1shKknAMKxT99o9qUTRVC6m")?;
+/// let mut wallet = Wallet::create(
+///     Bip86Account(key.clone(), 1, KeychainKind::External),
+///     Bip86Account(key, 1, KeychainKind::Internal),
+/// )
+/// .network(Network::Testnet)
+/// .create_wallet_no_persist()?;
+///
+/// assert_eq!(wallet.next_unused_address(KeychainKind::External).to_string(), "tb1p3jfapk9wsv5pq72xmcqefwpd9kk4l529h9v7lv3tpn6smcunky8qafwzyv");
+/// assert_eq!(wallet.public_descriptor(KeychainKind::External).to_string(), "tr([c55b303f/86'/1'/1']tpubDCiHofpEs47kyuf7A2QySYSDcPH24nHGXLx45oWqxjYcfSgFRRn6uTy2Y6Y2o3oazXvDWbAPqYPhVV5896J1qJjS38pyCfARaDVBWnc8qaB/0/*)#xtt82pk3");
+/// # Ok::<_, Box<dyn core::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Bip86Account<K: DerivableKey<Tap>>(pub K, pub u32, pub KeychainKind);
+
+impl<K: DerivableKey<Tap>> DescriptorTemplate for Bip86Account<K> {
+    fn build(self, network_kind: NetworkKind) -> Result<DescriptorTemplateOut, DescriptorError> {
+        P2TR(segwit_v1::make_bipxx_private(
+            86,
+            self.0,
+            self.1,
+            self.2,
+            network_kind,
+        )?)
+        .build(network_kind)
+    }
+}
+
 macro_rules! expand_make_bipxx {
     ( $mod_name:ident, $ctx:ty ) => {
         mod $mod_name {
@@ -572,6 +752,7 @@ macro_rules! expand_make_bipxx {
             pub(super) fn make_bipxx_private<K: DerivableKey<$ctx>>(
                 bip: u32,
                 key: K,
+                account: u32,
                 keychain: KeychainKind,
                 network_kind: NetworkKind,
             ) -> Result<impl IntoDescriptorKey<$ctx>, DescriptorError> {
@@ -586,7 +767,7 @@ macro_rules! expand_make_bipxx {
                         derivation_path.push(bip32::ChildNumber::from_hardened_idx(1)?);
                     }
                 }
-                derivation_path.push(bip32::ChildNumber::from_hardened_idx(0)?);
+                derivation_path.push(bip32::ChildNumber::from_hardened_idx(account)?);
 
                 match keychain {
                     KeychainKind::External => {
@@ -1046,6 +1227,63 @@ mod test {
         );
     }
 
+    // BIP86 account `tr(key/86'/{0,1}'/account'/{0,1}/*)`
+    // Account 0 uses the test vector from https://github.com/bitcoin/bips/blob/master/bip-0086.mediawiki
+    #[test]
+    fn test_bip86_account_template() {
+        use bitcoin::bip32::{DerivationPath, Xpub};
+        use bitcoin::secp256k1::Secp256k1;
+
+        let prvkey = bitcoin::bip32::Xpriv::from_str("xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu").unwrap();
+
+        let (desc, _, _) = Bip86Account(prvkey, 0, KeychainKind::External)
+            .build(NetworkKind::Main)
+            .unwrap();
+        assert_eq!(
+            desc.to_string(),
+            "tr([73c5da0a/86'/0'/0']xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*)#rg247h69"
+        );
+        check(
+            Bip86Account(prvkey, 0, KeychainKind::External).build(NetworkKind::Main),
+            false,
+            true,
+            false,
+            NetworkKind::Main,
+            &[
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+                "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh",
+                "bc1p0d0rhyynq0awa9m8cqrcr8f5nxqx3aw29w4ru5u9my3h0sfygnzs9khxz8",
+            ],
+        );
+        check(
+            Bip86Account(prvkey, 0, KeychainKind::Internal).build(NetworkKind::Main),
+            false,
+            true,
+            false,
+            NetworkKind::Main,
+            &["bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7"],
+        );
+
+        // Account 1 must match the key derived by hand at `m/86'/0'/1'`.
+        let secp = Secp256k1::new();
+        let path = DerivationPath::from_str("m/86'/0'/1'").unwrap();
+        let account_xpub = Xpub::from_priv(&secp, &prvkey.derive_priv(&secp, &path).unwrap());
+        let fingerprint = prvkey.fingerprint(&secp);
+        for (keychain, change) in [(KeychainKind::External, 0), (KeychainKind::Internal, 1)] {
+            let (desc, _, _) = Bip86Account(prvkey, 1, keychain)
+                .build(NetworkKind::Main)
+                .unwrap();
+            let expected = format!("tr([{fingerprint}/86'/0'/1']{account_xpub}/{change}/*)");
+            assert_eq!(desc.to_string().split_once('#').unwrap().0, expected);
+        }
+
+        // The coin type follows the network kind.
+        let (desc, _, _) = Bip86Account(prvkey, 1, KeychainKind::External)
+            .build(NetworkKind::Test)
+            .unwrap();
+        assert!(desc.to_string().starts_with("tr([73c5da0a/86'/1'/1']"));
+    }
+
     // BIP86 public `tr(key/{0,1}/*)`
     // Used addresses in test vector in https://github.com/bitcoin/bips/blob/master/bip-0086.mediawiki
     #[test]
@@ -18,17 +18,22 @@ use alloc::string::String;
 
 use bitcoin::{bip32, Network};
 use miniscript::ScriptContext;
+use zeroize::Zeroize;
 
 use super::{
-    any_network_kind, DerivableKey, DescriptorKey, ExtendedKey, GeneratableKey, GeneratedKey,
-    KeyError,
+    any_network_kind, DerivableKey, DescriptorKey, ExtendedKey, GeneratableKey, GenerateError,
+    GenerateOptions, GeneratedKey, KeyError,
 };
+use crate::descriptor::template::Bip86;
+use crate::types::KeychainKind;
+use crate::wallet::CreateParams;
 
 pub use bip39::{Error, Language, Mnemonic};
 
 type Seed = [u8; 64];
 
 /// Type describing entropy length (aka word count) in the mnemonic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WordCount {
     /// 12 words mnemonic (128 bits entropy)
     Words12 = 128,
@@ -142,11 +147,79 @@ impl<Ctx: ScriptContext> GeneratableKey<Ctx> for Mnemonic {
         (word_count, language): Self::Options,
         entropy: Self::Entropy,
     ) -> Result<GeneratedKey<Self, Ctx>, Self::Error> {
-        let entropy = &entropy[..(word_count as usize / 8)];
-        let mnemonic = Mnemonic::from_entropy_in(language, entropy)?;
+        let mut entropy = entropy;
+        let mnemonic = Mnemonic::from_entropy_in(language, &entropy[..(word_count as usize / 8)]);
+        entropy.zeroize();
 
-        Ok(GeneratedKey::new(mnemonic, any_network_kind()))
+        Ok(GeneratedKey::new(mnemonic?, any_network_kind()))
     }
+
+    fn required_entropy_len((word_count, _): &Self::Options) -> usize {
+        *word_count as usize / 8
+    }
+}
+
+/// Generate a mnemonic of `word_count` words in `language` from the entropy provided by the caller
+///
+/// Only the first bytes of `entropy` required by `word_count` are used, see
+/// [`GenerateOptions`] for more.
+pub fn generate_mnemonic_in(
+    word_count: WordCount,
+    language: Language,
+    entropy: &[u8],
+) -> Result<Mnemonic, GenerateError<Option<Error>>> {
+    let generated: GeneratedKey<Mnemonic, miniscript::Tap> =
+        GenerateOptions::new((word_count, language), entropy).generate()?;
+    Ok(generated.into_key())
+}
+
+/// Generate a new BIP86 taproot wallet from the entropy provided by the caller
+///
+/// Returns the [`CreateParams`] of a wallet using the [`Bip86`] templates for both keychains,
+/// ready to be created, and the English mnemonic to back it up. The mnemonic encodes as much of
+/// `entropy` as possible, up to 32 bytes: at least 16 bytes are required for a 12 words
+/// mnemonic, 32 bytes make it a 24 words one.
+///
+/// ## Example
+///
+/// ```
+/// # use bdk_wallet::bitcoin::Network;
+/// # use bdk_wallet::keys::generate_bip86_wallet;
+/// # use bdk_wallet::KeychainKind;
+/// let (params, mnemonic) = generate_bip86_wallet(Network::Bitcoin, &[0; 16])?;
+/// let wallet = params.create_wallet_no_persist()?;
+/// assert_eq!(mnemonic.word_count(), 12);
+/// assert_eq!(
+///     wallet.peek_address(KeychainKind::External, 0).to_string(),
+///     "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+/// );
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub fn generate_bip86_wallet(
+    network: Network,
+    entropy: &[u8],
+) -> Result<(CreateParams, Mnemonic), GenerateError<Option<Error>>> {
+    let word_count = match entropy.len() {
+        0..=15 => {
+            return Err(GenerateError::InsufficientEntropy {
+                required: WordCount::Words12 as usize / 8,
+                provided: entropy.len(),
+            })
+        }
+        16..=19 => WordCount::Words12,
+        20..=23 => WordCount::Words15,
+        24..=27 => WordCount::Words18,
+        28..=31 => WordCount::Words21,
+        _ => WordCount::Words24,
+    };
+    let mnemonic = generate_mnemonic_in(word_count, Language::English, entropy)?;
+    let params = CreateParams::new(
+        Bip86(mnemonic.clone(), KeychainKind::External),
+        Bip86(mnemonic.clone(), KeychainKind::Internal),
+    )
+    .network(network);
+
+    Ok((params, mnemonic))
 }
 
 #[cfg_attr(coverage_nightly, coverage(off))]
@@ -160,7 +233,11 @@ mod test {
     use bip39::{Language, Mnemonic};
     use bitcoin::bip32;
 
-    use crate::keys::{any_network_kind, GeneratableKey, GeneratedKey};
+    use crate::keys::{any_network_kind, GeneratableKey, GenerateError, GeneratedKey};
+    use crate::KeychainKind;
+
+    use super::{generate_bip86_wallet, generate_mnemonic_in};
+    use bitcoin::Network;
 
     #[test]
     fn test_keys_bip39_mnemonic() {
@@ -214,6 +291,64 @@ mod test {
         assert_eq!(generated_mnemonic.to_string(), "primary fetch primary fetch primary fetch primary fetch primary fetch primary fetch primary fetch primary fetch primary fetch primary fetch primary fetch primary foster");
     }
 
+    #[test]
+    fn test_keys_generate_mnemonic_in() {
+        let mnemonic = generate_mnemonic_in(
+            WordCount::Words12,
+            Language::English,
+            &crate::keys::test::TEST_ENTROPY[..16],
+        )
+        .unwrap();
+        assert_eq!(
+            mnemonic.to_string(),
+            "primary fetch primary fetch primary fetch primary fetch primary fetch primary fever"
+        );
+
+        assert_eq!(
+            generate_mnemonic_in(WordCount::Words24, Language::English, &[0xAA; 24]),
+            Err(GenerateError::InsufficientEntropy {
+                required: 32,
+                provided: 24
+            })
+        );
+    }
+
+    #[test]
+    fn test_generate_bip86_wallet() {
+        // BIP86 test vectors
+        let (params, mnemonic) = generate_bip86_wallet(Network::Bitcoin, &[0; 16]).unwrap();
+        assert_eq!(
+            mnemonic.to_string(),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        let wallet = params.create_wallet_no_persist().unwrap();
+        assert_eq!(
+            wallet.public_descriptor(KeychainKind::External).to_string(),
+            "tr([73c5da0a/86'/0'/0']xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*)#rg247h69"
+        );
+        assert_eq!(
+            wallet.peek_address(KeychainKind::External, 0).to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert_eq!(
+            wallet.peek_address(KeychainKind::Internal, 0).to_string(),
+            "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7"
+        );
+
+        // the mnemonic encodes up to 32 bytes of entropy
+        let (_, mnemonic) = generate_bip86_wallet(Network::Testnet, &[0xAA; 33]).unwrap();
+        assert_eq!(mnemonic.word_count(), 24);
+        let (_, mnemonic) = generate_bip86_wallet(Network::Testnet, &[0xAA; 21]).unwrap();
+        assert_eq!(mnemonic.word_count(), 15);
+        assert!(matches!(
+            generate_bip86_wallet(Network::Testnet, &[0xAA; 15]),
+            Err(GenerateError::InsufficientEntropy {
+                required: 16,
+                provided: 15
+            })
+        ));
+    }
+
     #[test]
     fn test_keys_generate_bip39_random() {
         let generated_mnemonic: GeneratedKey<_, miniscript::Segwitv0> =
@@ -0,0 +1,433 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2025 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Electrum seeds
+//!
+//! [Electrum seeds](https://electrum.readthedocs.io/en/latest/seedphrase.html) look like BIP39
+//! mnemonics, but they encode the kind of wallet they're meant for in a version number instead of
+//! a checksum and derive the BIP32 master key differently.
+//!
+//! Standard seeds derive `m/0/*` for receiving and `m/1/*` for change with P2PKH addresses,
+//! segwit seeds derive `m/0'/0/*` and `m/0'/1/*` with P2WPKH addresses, refer to
+//! [`ElectrumMnemonic::account_path`].
+//!
+//! ## Example
+//!
+//! ```
+//! # use core::str::FromStr;
+//! # use bdk_wallet::bitcoin::bip32::DerivationPath;
+//! # use bdk_wallet::bitcoin::Network;
+//! # use bdk_wallet::keys::electrum::ElectrumMnemonic;
+//! # use bdk_wallet::template::P2Wpkh;
+//! # use bdk_wallet::{KeychainKind, Wallet};
+//! let mnemonic = ElectrumMnemonic::parse(
+//!     "bitter grass shiver impose acquire brush forget axis eager alone wine silver",
+//! )?;
+//! let account_path = mnemonic.account_path();
+//! let mut wallet = Wallet::create(
+//!     P2Wpkh((mnemonic.clone(), account_path.extend([0.into()]))),
+//!     P2Wpkh((mnemonic, account_path.extend([1.into()]))),
+//! )
+//! .network(Network::Bitcoin)
+//! .create_wallet_no_persist()?;
+//!
+//! assert_eq!(
+//!     wallet.peek_address(KeychainKind::External, 0).to_string(),
+//!     "bc1q3g5tmkmlvxryhh843v4dz026avatc0zzr6h3af"
+//! );
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use bitcoin::bip32::{self, ChildNumber, DerivationPath};
+use bitcoin::hashes::{hmac, sha512, Hash, HashEngine};
+use bitcoin::{Network, NetworkKind};
+use miniscript::ScriptContext;
+
+use super::{any_network_kind, DerivableKey, DescriptorKey, ExtendedKey, KeyError};
+
+type Seed = [u8; 64];
+
+const PBKDF2_ROUNDS: u32 = 2048;
+
+/// The kind of wallet an Electrum seed is meant for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeedType {
+    /// Standard wallet with P2PKH addresses, seed version `01`.
+    Standard,
+    /// Segwit wallet with P2WPKH addresses, seed version `100`.
+    Segwit,
+}
+
+impl SeedType {
+    /// Get the seed type of the normalized `mnemonic`.
+    fn from_mnemonic(mnemonic: &str) -> Result<Self, Error> {
+        let mut engine = hmac::HmacEngine::<sha512::Hash>::new(b"Seed version");
+        engine.input(mnemonic.as_bytes());
+        let version = hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+
+        // the version is the prefix of the hex encoded hmac
+        match (version[0], version[1] >> 4) {
+            (0x01, _) => Ok(SeedType::Standard),
+            (0x10, 0x0) => Ok(SeedType::Segwit),
+            (0x10, 0x1 | 0x2) => Err(Error::TwoFactor),
+            _ => Err(Error::InvalidVersion),
+        }
+    }
+}
+
+/// Errors thrown while parsing Electrum seeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The seed or passphrase contains non-ASCII characters.
+    ///
+    /// Electrum normalizes those in ways that aren't supported here.
+    NonAscii,
+    /// The seed is an old-style (pre-2.0) Electrum seed, which isn't supported.
+    OldSeed,
+    /// The seed doesn't have a valid Electrum 2.0+ version number.
+    ///
+    /// This is the case for BIP39 mnemonics, but also for old-style (pre-2.0) seeds which aren't
+    /// supported.
+    InvalidVersion,
+    /// The seed is meant for a two-factor authentication wallet, which isn't supported.
+    TwoFactor,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NonAscii => write!(f, "Non-ASCII Electrum seeds are not supported"),
+            Self::OldSeed => write!(f, "Old-style (pre-2.0) Electrum seeds are not supported"),
+            Self::InvalidVersion => write!(
+                f,
+                "Not an Electrum 2.0+ seed (old-style pre-2.0 seeds are not supported)"
+            ),
+            Self::TwoFactor => write!(f, "Two-factor Electrum seeds are not supported"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<Error> for KeyError {
+    fn from(err: Error) -> Self {
+        KeyError::Message(err.to_string())
+    }
+}
+
+/// Normalize `text` the way Electrum does for ASCII text, i.e. lowercase it and collapse
+/// whitespace.
+fn normalize(text: &str) -> Result<String, Error> {
+    if !text.is_ascii() {
+        return Err(Error::NonAscii);
+    }
+    Ok(text
+        .split_ascii_whitespace()
+        .map(|word| word.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// PBKDF2-HMAC-SHA512 with a single output block, which is all an Electrum seed needs.
+fn pbkdf2_sha512(password: &[u8], salt: &[u8]) -> Seed {
+    let hmac_key = hmac::HmacEngine::<sha512::Hash>::new(password);
+
+    let mut engine = hmac_key.clone();
+    engine.input(salt);
+    engine.input(&1u32.to_be_bytes());
+    let mut u = hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+    let mut seed = u;
+    for _ in 1..PBKDF2_ROUNDS {
+        let mut engine = hmac_key.clone();
+        engine.input(&u);
+        u = hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+        seed.iter_mut().zip(u).for_each(|(s, u)| *s ^= u);
+    }
+    seed
+}
+
+/// An Electrum 2.0+ seed.
+///
+/// Only standard and segwit seeds with ASCII words are supported.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ElectrumMnemonic {
+    mnemonic: String,
+    seed_type: SeedType,
+}
+
+impl ElectrumMnemonic {
+    /// Parse an Electrum seed.
+    pub fn parse(mnemonic: &str) -> Result<Self, Error> {
+        let mnemonic = normalize(mnemonic)?;
+        // old-style seeds may also be stored as hex
+        if matches!(mnemonic.len(), 32 | 64) && mnemonic.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::OldSeed);
+        }
+        let seed_type = SeedType::from_mnemonic(&mnemonic)?;
+        Ok(Self {
+            mnemonic,
+            seed_type,
+        })
+    }
+
+    /// Get the kind of wallet the seed is meant for.
+    pub fn seed_type(&self) -> SeedType {
+        self.seed_type
+    }
+
+    /// Get the derivation path of the account Electrum derives addresses from.
+    ///
+    /// This is `m` for standard seeds and `m/0'` for segwit seeds, receiving addresses are derived
+    /// from `<account_path>/0/*` and change addresses from `<account_path>/1/*`.
+    pub fn account_path(&self) -> DerivationPath {
+        match self.seed_type {
+            SeedType::Standard => DerivationPath::master(),
+            SeedType::Segwit => [ChildNumber::Hardened { index: 0 }].as_ref().into(),
+        }
+    }
+
+    /// Get the BIP32 seed, optionally extended with `passphrase`.
+    pub fn to_seed(&self, passphrase: &str) -> Result<Seed, Error> {
+        let passphrase = normalize(passphrase)?;
+        let mut salt = String::from("electrum");
+        salt.push_str(&passphrase);
+        Ok(pbkdf2_sha512(self.mnemonic.as_bytes(), salt.as_bytes()))
+    }
+
+    /// Get the account extended private key Electrum shows for the seed, i.e. the key at
+    /// [`account_path`](Self::account_path).
+    pub fn account_xprv(
+        &self,
+        passphrase: &str,
+        network_kind: NetworkKind,
+    ) -> Result<bip32::Xpriv, KeyError> {
+        let secp = bitcoin::secp256k1::Secp256k1::signing_only();
+        let root = bip32::Xpriv::new_master(network_kind, &self.to_seed(passphrase)?)?;
+        Ok(root.derive_priv(&secp, &self.account_path())?)
+    }
+}
+
+impl FromStr for ElectrumMnemonic {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for ElectrumMnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.mnemonic)
+    }
+}
+
+impl fmt::Debug for ElectrumMnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ElectrumMnemonic")
+            .field("seed_type", &self.seed_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Type for an Electrum seed with an optional passphrase.
+pub type ElectrumMnemonicWithPassphrase = (ElectrumMnemonic, Option<String>);
+
+#[cfg_attr(docsrs, doc(cfg(feature = "electrum_seed")))]
+impl<Ctx: ScriptContext> DerivableKey<Ctx> for ElectrumMnemonicWithPassphrase {
+    fn into_extended_key(self) -> Result<ExtendedKey<Ctx>, KeyError> {
+        let (mnemonic, passphrase) = self;
+        let seed = mnemonic.to_seed(passphrase.as_deref().unwrap_or(""))?;
+        Ok(bip32::Xpriv::new_master(Network::Bitcoin, &seed[..])?.into())
+    }
+
+    fn into_descriptor_key(
+        self,
+        source: Option<bip32::KeySource>,
+        derivation_path: bip32::DerivationPath,
+    ) -> Result<DescriptorKey<Ctx>, KeyError> {
+        let descriptor_key = self
+            .into_extended_key()?
+            .into_descriptor_key(source, derivation_path)?;
+
+        // Electrum seeds don't encode the network kind either, see the BIP39 keys
+        Ok(descriptor_key.override_valid_network_kinds(any_network_kind()))
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "electrum_seed")))]
+impl<Ctx: ScriptContext> DerivableKey<Ctx> for ElectrumMnemonic {
+    fn into_extended_key(self) -> Result<ExtendedKey<Ctx>, KeyError> {
+        (self, None).into_extended_key()
+    }
+
+    fn into_descriptor_key(
+        self,
+        source: Option<bip32::KeySource>,
+        derivation_path: bip32::DerivationPath,
+    ) -> Result<DescriptorKey<Ctx>, KeyError> {
+        (self, None).into_descriptor_key(source, derivation_path)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    use bitcoin::hex::DisplayHex;
+
+    use crate::descriptor::{IntoWalletDescriptor, Segwitv0};
+    use crate::keys::IntoDescriptorKey;
+    use crate::template::{P2Pkh, P2Wpkh};
+    use crate::{KeychainKind, Wallet};
+
+    // test vectors from Electrum
+    const SEGWIT_SEED: &str =
+        "wild father tree among universe such mobile favorite target dynamic credit identify";
+    const STANDARD_WALLET_SEED: &str =
+        "cycle rocket west magnet parrot shuffle foot correct salt library feed song";
+    const SEGWIT_WALLET_SEED: &str =
+        "bitter grass shiver impose acquire brush forget axis eager alone wine silver";
+
+    #[test]
+    fn test_electrum_seed() {
+        let mnemonic = ElectrumMnemonic::parse(SEGWIT_SEED).unwrap();
+        assert_eq!(mnemonic.seed_type(), SeedType::Segwit);
+        assert_eq!(
+            mnemonic.to_seed("").unwrap().to_lower_hex_string(),
+            "aac2a6302e48577ab4b46f23dbae0774e2e62c796f797d0a1b5faeb528301e3064342dafb79069e7c4c6b8c38ae11d7a973bec0d4f70626f8cc5184a8d0b0756"
+        );
+        assert_eq!(
+            mnemonic
+                .to_seed("Did you ever hear the tragedy of Darth Plagueis the Wise?")
+                .unwrap()
+                .to_lower_hex_string(),
+            "4aa29f2aeb0127efb55138ab9e7be83b36750358751906f86c662b21a1ea1370f949e6d1a12fa56d3d93cadda93038c76ac8118597364e46f5156fde6183c82f"
+        );
+
+        // seeds and passphrases are normalized
+        let denormalized = ElectrumMnemonic::parse(
+            "  Wild father TREE among universe such\tmobile favorite target dynamic credit identify\n",
+        )
+        .unwrap();
+        assert_eq!(denormalized, mnemonic);
+        assert_eq!(denormalized.to_string(), SEGWIT_SEED);
+        assert_eq!(
+            mnemonic.to_seed("did you ever hear the tragedy of darth plagueis   the wise?"),
+            mnemonic.to_seed("Did you ever hear the tragedy of Darth Plagueis the Wise?")
+        );
+    }
+
+    #[test]
+    fn test_electrum_seed_wallets() {
+        let mnemonic = ElectrumMnemonic::parse(STANDARD_WALLET_SEED).unwrap();
+        assert_eq!(mnemonic.seed_type(), SeedType::Standard);
+        assert_eq!(mnemonic.account_path(), DerivationPath::master());
+        let xprv = mnemonic.account_xprv("", NetworkKind::Main).unwrap();
+        assert_eq!(
+            bip32::Xpub::from_priv(&bitcoin::secp256k1::Secp256k1::new(), &xprv).to_string(),
+            "xpub661MyMwAqRbcFWohJWt7PHsFEJfZAvw9ZxwQoDa4SoMgsDDM1T7WK3u9E4edkC4ugRnZ8E4xDZRpk8Rnts3Nbt97dPwT52CwBdDWroaZf8U"
+        );
+        let wallet = Wallet::create(
+            P2Pkh((mnemonic.clone(), DerivationPath::from(vec![0.into()]))),
+            P2Pkh((mnemonic, DerivationPath::from(vec![1.into()]))),
+        )
+        .network(Network::Bitcoin)
+        .create_wallet_no_persist()
+        .unwrap();
+        assert_eq!(
+            wallet.peek_address(KeychainKind::External, 0).to_string(),
+            "1NNkttn1YvVGdqBW4PR6zvc3Zx3H5owKRf"
+        );
+
+        let mnemonic = ElectrumMnemonic::parse(SEGWIT_WALLET_SEED).unwrap();
+        assert_eq!(mnemonic.seed_type(), SeedType::Segwit);
+        let xprv = mnemonic.account_xprv("", NetworkKind::Main).unwrap();
+        let xpub = bip32::Xpub::from_priv(&bitcoin::secp256k1::Secp256k1::new(), &xprv);
+        assert_eq!(
+            crate::keys::slip132::encode_xpub(&xpub, crate::keys::slip132::ScriptType::P2wpkh),
+            "zpub6nsHdRuY92FsMKdbn9BfjBCG6X8pyhCibNP6uDvpnw2cyrVhecvHRMa3Ne8kdJZxjxgwnpbHLkcR4bfnhHy6auHPJyDTQ3kianeuVLdkCYQ"
+        );
+        let wallet = Wallet::create(
+            P2Wpkh((
+                mnemonic.clone(),
+                DerivationPath::from_str("m/0'/0").unwrap(),
+            )),
+            P2Wpkh((mnemonic, DerivationPath::from_str("m/0'/1").unwrap())),
+        )
+        .network(Network::Bitcoin)
+        .create_wallet_no_persist()
+        .unwrap();
+        assert_eq!(
+            wallet.peek_address(KeychainKind::External, 0).to_string(),
+            "bc1q3g5tmkmlvxryhh843v4dz026avatc0zzr6h3af"
+        );
+    }
+
+    #[test]
+    fn test_electrum_seed_passphrase_descriptor() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let mnemonic = ElectrumMnemonic::parse(SEGWIT_SEED).unwrap();
+        let passphrase = "Did you ever hear the tragedy of Darth Plagueis the Wise?";
+        let path = DerivationPath::from_str("m/0'/0").unwrap();
+
+        let key: DescriptorKey<Segwitv0> = (mnemonic.clone(), Some(passphrase.to_string()))
+            .into_descriptor_key(None, path.clone())
+            .unwrap();
+        let (with_passphrase, _) = crate::descriptor!(wpkh(key))
+            .unwrap()
+            .into_wallet_descriptor(&secp, NetworkKind::Test)
+            .unwrap();
+        let key: DescriptorKey<Segwitv0> = (mnemonic, path).into_descriptor_key().unwrap();
+        let (without_passphrase, _) = crate::descriptor!(wpkh(key))
+            .unwrap()
+            .into_wallet_descriptor(&secp, NetworkKind::Test)
+            .unwrap();
+        assert_ne!(with_passphrase, without_passphrase);
+    }
+
+    #[test]
+    fn test_electrum_seed_invalid() {
+        // BIP39 mnemonics aren't Electrum seeds
+        assert_eq!(
+            ElectrumMnemonic::parse(
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+            ),
+            Err(Error::InvalidVersion)
+        );
+        assert_eq!(
+            ElectrumMnemonic::parse("electrum two factor seed 1931"),
+            Err(Error::TwoFactor)
+        );
+        assert_eq!(
+            ElectrumMnemonic::parse("electrum two factor segwit seed 6797"),
+            Err(Error::TwoFactor)
+        );
+        assert_eq!(
+            ElectrumMnemonic::parse("acb740e454c3134901d7c8f16497cc1c"),
+            Err(Error::OldSeed)
+        );
+        assert_eq!(
+            ElectrumMnemonic::parse("wïld father tree among universe such mobile favorite target dynamic credit identify"),
+            Err(Error::NonAscii)
+        );
+        assert_eq!(
+            ElectrumMnemonic::parse(SEGWIT_SEED)
+                .unwrap()
+                .to_seed("pässphrase"),
+            Err(Error::NonAscii)
+        );
+    }
+}
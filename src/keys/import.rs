@@ -0,0 +1,503 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2025 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Classify keys pasted by users
+//!
+//! This module contains [`classify_import`], which detects whether a string is a WIF private key
+//! or an extended key, with standard or [SLIP-0132](super::slip132) version bytes, and
+//! [`ImportedKey::suggested_descriptors`], which lists the descriptors the key is most likely
+//! used with. Together they are meant to back the "import wallet" screen of an application.
+//!
+//! ## Example
+//!
+//! ```
+//! # use bdk_wallet::bitcoin::Network;
+//! # use bdk_wallet::keys::classify_import;
+//! # use bdk_wallet::Wallet;
+//! let key = classify_import("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy")?;
+//! let suggestions = key.suggested_descriptors(Network::Testnet)?;
+//! assert!(suggestions[0].descriptor.starts_with("wpkh("));
+//!
+//! let wallet = Wallet::create_single(suggestions[0].descriptor.clone())
+//!     .network(Network::Testnet)
+//!     .create_wallet_no_persist()?;
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, Xpriv, Xpub};
+use bitcoin::{Network, NetworkKind, PrivateKey};
+use miniscript::{Legacy, Segwitv0, Tap};
+
+use super::slip132::{self, ScriptType};
+use super::DerivableKey;
+use crate::descriptor::template::*;
+use crate::descriptor::DescriptorError;
+use crate::KeychainKind;
+
+/// Errors thrown while classifying a key or building the descriptors suggested for it.
+#[derive(Debug)]
+pub enum Error {
+    /// The string is neither a WIF private key nor an extended key.
+    UnrecognizedKey,
+    /// The key is meant for a different network kind than the one requested.
+    NetworkMismatch {
+        /// The network kind of the key.
+        key: NetworkKind,
+        /// The requested network.
+        network: Network,
+    },
+    /// Error while building a descriptor.
+    Descriptor(DescriptorError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnrecognizedKey => write!(f, "Not a WIF private key or an extended key"),
+            Self::NetworkMismatch { key, network } => {
+                write!(f, "Key for {key:?} networks can't be used on {network}")
+            }
+            Self::Descriptor(err) => write!(f, "Descriptor error: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<DescriptorError> for Error {
+    fn from(err: DescriptorError) -> Self {
+        Error::Descriptor(err)
+    }
+}
+
+/// A key imported with [`classify_import`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportedKey {
+    /// A WIF private key, which carries its network kind and whether its public key is
+    /// compressed.
+    Wif(PrivateKey),
+    /// An extended private key.
+    Xpriv {
+        /// The key, with standard version bytes.
+        key: Xpriv,
+        /// The script type hinted by the version bytes of the key, [`ScriptType::Legacy`] for
+        /// `xprv`/`tprv`.
+        script_type: ScriptType,
+    },
+    /// An extended public key.
+    Xpub {
+        /// The key, with standard version bytes.
+        key: Xpub,
+        /// The script type hinted by the version bytes of the key, [`ScriptType::Legacy`] for
+        /// `xpub`/`tpub`.
+        script_type: ScriptType,
+    },
+}
+
+/// A descriptor suggested for an [`ImportedKey`], along with its change descriptor if the key
+/// can derive one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorSuggestion {
+    /// The descriptor, including any private key and its checksum.
+    pub descriptor: String,
+    /// The change descriptor, `None` for single keys.
+    pub change_descriptor: Option<String>,
+}
+
+/// Classify a key pasted by a user.
+///
+/// Detects WIF private keys and extended private or public keys with any of the version bytes
+/// registered in [SLIP-0132](super::slip132). Leading and trailing whitespace is ignored.
+pub fn classify_import(s: &str) -> Result<ImportedKey, Error> {
+    let s = s.trim();
+    if let Ok(key) = PrivateKey::from_wif(s) {
+        return Ok(ImportedKey::Wif(key));
+    }
+    if let Ok((key, script_type)) = slip132::decode(s) {
+        return Ok(ImportedKey::Xpub { key, script_type });
+    }
+    if let Ok((key, script_type)) = slip132::decode_xprv(s) {
+        return Ok(ImportedKey::Xpriv { key, script_type });
+    }
+    Err(Error::UnrecognizedKey)
+}
+
+impl ImportedKey {
+    /// Get the network kind the key is meant for.
+    pub fn network_kind(&self) -> NetworkKind {
+        match self {
+            ImportedKey::Wif(key) => key.network,
+            ImportedKey::Xpriv { key, .. } => key.network,
+            ImportedKey::Xpub { key, .. } => key.network,
+        }
+    }
+
+    /// Whether the key can be used to sign transactions.
+    pub fn is_private(&self) -> bool {
+        !matches!(self, ImportedKey::Xpub { .. })
+    }
+
+    /// Get the derivation depth of an extended key, `None` for WIF keys.
+    pub fn depth(&self) -> Option<u8> {
+        match self {
+            ImportedKey::Wif(_) => None,
+            ImportedKey::Xpriv { key, .. } => Some(key.depth),
+            ImportedKey::Xpub { key, .. } => Some(key.depth),
+        }
+    }
+
+    /// List the descriptors the key is most likely used with on `network`, most likely first.
+    ///
+    /// - WIF keys with a compressed public key are suggested in `wpkh()`, `tr()`, `sh(wpkh())`
+    ///   and `pkh()` descriptors, uncompressed ones only in `pkh()`.
+    /// - Master extended private keys are suggested with the BIP84, BIP86, BIP49 and BIP44
+    ///   templates.
+    /// - Any other extended key is assumed to be an account key, and is suggested in the same
+    ///   script types as WIF keys, deriving addresses at `/0/*` and change addresses at `/1/*`.
+    ///
+    /// SLIP-0132 version bytes narrow the suggestions down to the script type they are meant for.
+    /// No descriptor is suggested for the multisig `Ypub`/`Zpub` version bytes, since the other
+    /// keys of the multisig are needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::NetworkMismatch`] if the key is not meant for `network`.
+    pub fn suggested_descriptors(
+        &self,
+        network: Network,
+    ) -> Result<Vec<DescriptorSuggestion>, Error> {
+        let network_kind = NetworkKind::from(network);
+        if self.network_kind() != network_kind {
+            return Err(Error::NetworkMismatch {
+                key: self.network_kind(),
+                network,
+            });
+        }
+
+        let script_types: &[ScriptType] = match self {
+            ImportedKey::Wif(key) if !key.compressed => &[ScriptType::Legacy],
+            ImportedKey::Wif(_)
+            | ImportedKey::Xpriv {
+                script_type: ScriptType::Legacy,
+                ..
+            }
+            | ImportedKey::Xpub {
+                script_type: ScriptType::Legacy,
+                ..
+            } => &[
+                ScriptType::P2wpkh,
+                ScriptType::P2tr,
+                ScriptType::P2shP2wpkh,
+                ScriptType::Legacy,
+            ],
+            ImportedKey::Xpriv { script_type, .. } | ImportedKey::Xpub { script_type, .. } => {
+                match script_type {
+                    ScriptType::P2shP2wsh | ScriptType::P2wsh => &[],
+                    script_type => core::slice::from_ref(script_type),
+                }
+            }
+        };
+
+        script_types
+            .iter()
+            .map(|script_type| match self {
+                ImportedKey::Wif(key) => wif_suggestion(*key, *script_type, network_kind),
+                ImportedKey::Xpriv { key, .. } if key.depth == 0 => {
+                    master_suggestion(*key, *script_type, network_kind)
+                }
+                ImportedKey::Xpriv { key, .. } => {
+                    account_suggestion(*key, *script_type, network_kind)
+                }
+                ImportedKey::Xpub { key, .. } => {
+                    account_suggestion(*key, *script_type, network_kind)
+                }
+            })
+            .collect()
+    }
+}
+
+fn build<T: DescriptorTemplate>(template: T, network_kind: NetworkKind) -> Result<String, Error> {
+    let (descriptor, keymap, _) = template.build(network_kind)?;
+    Ok(descriptor.to_string_with_secret(&keymap))
+}
+
+fn wif_suggestion(
+    key: PrivateKey,
+    script_type: ScriptType,
+    network_kind: NetworkKind,
+) -> Result<DescriptorSuggestion, Error> {
+    let descriptor = match script_type {
+        ScriptType::P2wpkh => build(P2Wpkh(key), network_kind)?,
+        ScriptType::P2tr => build(P2TR(key), network_kind)?,
+        ScriptType::P2shP2wpkh => build(P2Wpkh_P2Sh(key), network_kind)?,
+        _ => build(P2Pkh(key), network_kind)?,
+    };
+    Ok(DescriptorSuggestion {
+        descriptor,
+        change_descriptor: None,
+    })
+}
+
+fn master_suggestion(
+    key: Xpriv,
+    script_type: ScriptType,
+    network_kind: NetworkKind,
+) -> Result<DescriptorSuggestion, Error> {
+    let build_keychain = |keychain| match script_type {
+        ScriptType::P2wpkh => build(Bip84(key, keychain), network_kind),
+        ScriptType::P2tr => build(Bip86(key, keychain), network_kind),
+        ScriptType::P2shP2wpkh => build(Bip49(key, keychain), network_kind),
+        _ => build(Bip44(key, keychain), network_kind),
+    };
+    Ok(DescriptorSuggestion {
+        descriptor: build_keychain(KeychainKind::External)?,
+        change_descriptor: Some(build_keychain(KeychainKind::Internal)?),
+    })
+}
+
+fn account_suggestion<K>(
+    key: K,
+    script_type: ScriptType,
+    network_kind: NetworkKind,
+) -> Result<DescriptorSuggestion, Error>
+where
+    K: DerivableKey<Legacy> + DerivableKey<Segwitv0> + DerivableKey<Tap> + Copy,
+{
+    let build_keychain = |keychain| {
+        let index = match keychain {
+            KeychainKind::External => 0,
+            KeychainKind::Internal => 1,
+        };
+        let path = DerivationPath::from(vec![
+            ChildNumber::from_normal_idx(index).expect("index is not hardened")
+        ]);
+        match script_type {
+            ScriptType::P2wpkh => build(P2Wpkh((key, path)), network_kind),
+            ScriptType::P2tr => build(P2TR((key, path)), network_kind),
+            ScriptType::P2shP2wpkh => build(P2Wpkh_P2Sh((key, path)), network_kind),
+            _ => build(P2Pkh((key, path)), network_kind),
+        }
+    };
+    Ok(DescriptorSuggestion {
+        descriptor: build_keychain(KeychainKind::External)?,
+        change_descriptor: Some(build_keychain(KeychainKind::Internal)?),
+    })
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    use alloc::string::ToString;
+    use core::str::FromStr;
+
+    use bitcoin::secp256k1::Secp256k1;
+
+    use crate::Wallet;
+
+    fn first_address(suggestion: &DescriptorSuggestion, network: Network) -> bitcoin::Address {
+        let params = match &suggestion.change_descriptor {
+            Some(change_descriptor) => {
+                Wallet::create(suggestion.descriptor.clone(), change_descriptor.clone())
+            }
+            None => Wallet::create_single(suggestion.descriptor.clone()),
+        };
+        let wallet = params.network(network).create_wallet_no_persist().unwrap();
+        wallet.peek_address(KeychainKind::External, 0).address
+    }
+
+    #[test]
+    fn test_classify_wif() {
+        let compressed = "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617";
+        let key = classify_import(&format!("  {compressed}\n")).unwrap();
+        assert!(matches!(&key, ImportedKey::Wif(k) if k.compressed));
+        assert_eq!(key.network_kind(), NetworkKind::Main);
+        assert!(key.is_private());
+        assert_eq!(key.depth(), None);
+
+        let suggestions = key.suggested_descriptors(Network::Bitcoin).unwrap();
+        let prefixes = suggestions
+            .iter()
+            .map(|s| s.descriptor.split(compressed).next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(prefixes, ["wpkh(", "tr(", "sh(wpkh(", "pkh("]);
+        assert!(suggestions.iter().all(|s| s.change_descriptor.is_none()));
+        for suggestion in &suggestions {
+            first_address(suggestion, Network::Bitcoin);
+        }
+
+        let uncompressed = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+        let key = classify_import(uncompressed).unwrap();
+        assert!(matches!(&key, ImportedKey::Wif(k) if !k.compressed));
+        let suggestions = key.suggested_descriptors(Network::Bitcoin).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert!(suggestions[0].descriptor.starts_with("pkh("));
+    }
+
+    #[test]
+    fn test_classify_master_xprv() {
+        let tprv = "tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS";
+        let xprv = Xpriv::from_str(tprv).unwrap();
+        let key = classify_import(tprv).unwrap();
+        assert_eq!(
+            key,
+            ImportedKey::Xpriv {
+                key: xprv,
+                script_type: ScriptType::Legacy
+            }
+        );
+        assert_eq!(key.depth(), Some(0));
+        assert_eq!(key.network_kind(), NetworkKind::Test);
+
+        let suggestions = key.suggested_descriptors(Network::Signet).unwrap();
+        let expected = [
+            (
+                build(Bip84(xprv, KeychainKind::External), NetworkKind::Test).unwrap(),
+                build(Bip84(xprv, KeychainKind::Internal), NetworkKind::Test).unwrap(),
+            ),
+            (
+                build(Bip86(xprv, KeychainKind::External), NetworkKind::Test).unwrap(),
+                build(Bip86(xprv, KeychainKind::Internal), NetworkKind::Test).unwrap(),
+            ),
+            (
+                build(Bip49(xprv, KeychainKind::External), NetworkKind::Test).unwrap(),
+                build(Bip49(xprv, KeychainKind::Internal), NetworkKind::Test).unwrap(),
+            ),
+            (
+                build(Bip44(xprv, KeychainKind::External), NetworkKind::Test).unwrap(),
+                build(Bip44(xprv, KeychainKind::Internal), NetworkKind::Test).unwrap(),
+            ),
+        ];
+        assert_eq!(suggestions.len(), expected.len());
+        for (suggestion, (descriptor, change_descriptor)) in suggestions.iter().zip(expected) {
+            assert_eq!(suggestion.descriptor, descriptor);
+            assert_eq!(suggestion.change_descriptor, Some(change_descriptor));
+        }
+        assert!(suggestions[0].descriptor.starts_with("wpkh(tprv"));
+        assert!(suggestions[0].descriptor.contains("/84'/1'/0'/0/*"));
+
+        // with SLIP-0132 version bytes only the matching template is suggested
+        let vprv = slip132::encode_xprv(&xprv, ScriptType::P2wpkh);
+        assert!(vprv.starts_with("vprv"));
+        let key = classify_import(&vprv).unwrap();
+        assert_eq!(
+            key,
+            ImportedKey::Xpriv {
+                key: xprv,
+                script_type: ScriptType::P2wpkh
+            }
+        );
+        assert_eq!(
+            key.suggested_descriptors(Network::Regtest).unwrap(),
+            suggestions[..1]
+        );
+    }
+
+    #[test]
+    fn test_classify_account_xpub() {
+        let secp = Secp256k1::new();
+        let master = Xpriv::from_str("xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu").unwrap();
+        let fingerprint = master.fingerprint(&secp);
+        let path = DerivationPath::from_str("m/84'/0'/0'").unwrap();
+        let account = Xpub::from_priv(&secp, &master.derive_priv(&secp, &path).unwrap());
+
+        let zpub = slip132::encode_xpub(&account, ScriptType::P2wpkh);
+        let key = classify_import(&zpub).unwrap();
+        assert_eq!(
+            key,
+            ImportedKey::Xpub {
+                key: account,
+                script_type: ScriptType::P2wpkh
+            }
+        );
+        assert!(!key.is_private());
+        assert_eq!(key.depth(), Some(3));
+
+        // the suggestion derives the same addresses as the BIP84 template
+        let suggestions = key.suggested_descriptors(Network::Bitcoin).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        let bip84 = DescriptorSuggestion {
+            descriptor: build(
+                Bip84Public(account, fingerprint, KeychainKind::External),
+                NetworkKind::Main,
+            )
+            .unwrap(),
+            change_descriptor: Some(
+                build(
+                    Bip84Public(account, fingerprint, KeychainKind::Internal),
+                    NetworkKind::Main,
+                )
+                .unwrap(),
+            ),
+        };
+        assert_eq!(
+            first_address(&suggestions[0], Network::Bitcoin),
+            first_address(&bip84, Network::Bitcoin)
+        );
+        assert!(suggestions[0]
+            .change_descriptor
+            .as_ref()
+            .unwrap()
+            .contains("/1/*"));
+
+        // a standard xpub could be used with any script type
+        let key = classify_import(&account.to_string()).unwrap();
+        let suggestions = key.suggested_descriptors(Network::Bitcoin).unwrap();
+        assert_eq!(suggestions.len(), 4);
+        assert!(suggestions[1].descriptor.starts_with("tr(xpub"));
+
+        // multisig keys can't be used on their own
+        let zpub_multisig = slip132::encode_xpub(&account, ScriptType::P2wsh);
+        let key = classify_import(&zpub_multisig).unwrap();
+        assert!(key
+            .suggested_descriptors(Network::Bitcoin)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_wrong_network() {
+        let key = classify_import("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        assert!(matches!(
+            key.suggested_descriptors(Network::Bitcoin),
+            Err(Error::NetworkMismatch {
+                key: NetworkKind::Test,
+                network: Network::Bitcoin
+            })
+        ));
+        assert!(key.suggested_descriptors(Network::Testnet4).is_ok());
+
+        let key = classify_import("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8").unwrap();
+        assert!(matches!(
+            key.suggested_descriptors(Network::Regtest),
+            Err(Error::NetworkMismatch {
+                key: NetworkKind::Main,
+                network: Network::Regtest
+            })
+        ));
+    }
+
+    #[test]
+    fn test_unrecognized() {
+        for s in [
+            "",
+            "not a key",
+            "tb1qhcrdamm04yt2mc3q7da6zsy5599y5eprv5rvfy",
+            "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98618",
+        ] {
+            assert!(matches!(classify_import(s), Err(Error::UnrecognizedKey)));
+        }
+    }
+}
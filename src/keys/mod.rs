@@ -34,6 +34,7 @@ use miniscript::{
     {Miniscript, Terminal},
 };
 use rand_core::{CryptoRng, RngCore};
+use zeroize::{Zeroize, Zeroizing};
 
 pub use miniscript::descriptor::{
     DescriptorPublicKey, DescriptorSecretKey, KeyMap, SinglePriv, SinglePub, SinglePubKey,
@@ -44,6 +45,19 @@ pub use miniscript::ScriptContext;
 #[cfg(feature = "keys-bip39")]
 #[cfg_attr(docsrs, doc(cfg(feature = "keys-bip39")))]
 pub mod bip39;
+#[cfg(feature = "keys-bip39")]
+#[cfg_attr(docsrs, doc(cfg(feature = "keys-bip39")))]
+pub use bip39::generate_bip86_wallet;
+#[cfg(feature = "electrum_seed")]
+#[cfg_attr(docsrs, doc(cfg(feature = "electrum_seed")))]
+pub mod electrum;
+pub mod import;
+pub mod slip132;
+#[cfg(feature = "slip39")]
+#[cfg_attr(docsrs, doc(cfg(feature = "slip39")))]
+pub mod slip39;
+
+pub use import::{classify_import, ImportedKey};
 
 /// Set of valid networks kinds for a key.
 pub type ValidNetworkKinds = HashSet<NetworkKind>;
@@ -629,6 +643,13 @@ pub trait GeneratableKey<Ctx: ScriptContext>: Sized {
         entropy: Self::Entropy,
     ) -> Result<GeneratedKey<Self, Ctx>, Self::Error>;
 
+    /// Number of bytes of entropy actually used to generate a key with the given options
+    ///
+    /// Defaults to the size of [`Self::Entropy`].
+    fn required_entropy_len(_options: &Self::Options) -> usize {
+        Self::Entropy::default().as_mut().len()
+    }
+
     /// Generate a key given the options with random entropy.
     ///
     /// Uses the thread-local random number generator.
@@ -694,6 +715,123 @@ where
 {
 }
 
+/// Options to generate a key from entropy provided by the caller
+///
+/// This allows using a source of randomness other than the thread-local random number generator,
+/// like a hardware RNG or dice rolls. The entropy is validated to be long enough for the key being
+/// generated, and zeroized once the options are dropped.
+///
+/// ## Example
+///
+/// ```
+/// # use bdk_wallet::bitcoin::bip32::Xpriv;
+/// # use bdk_wallet::bitcoin::NetworkKind;
+/// # use bdk_wallet::keys::{GenerateOptions, GeneratedKey, XprivGenerateOptions};
+/// let entropy = [0xAA; 32];
+/// let xprv: GeneratedKey<Xpriv, miniscript::Tap> = GenerateOptions::with_entropy(&entropy)
+///     .options(XprivGenerateOptions {
+///         network: NetworkKind::Test,
+///     })
+///     .generate()?;
+///
+/// // not enough entropy for a `Xpriv`
+/// let xprv: Result<GeneratedKey<Xpriv, miniscript::Tap>, _> =
+///     GenerateOptions::with_entropy(&entropy[..16]).generate();
+/// assert!(xprv.is_err());
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub struct GenerateOptions<O> {
+    options: O,
+    entropy: Zeroizing<Vec<u8>>,
+}
+
+impl<O: Default> GenerateOptions<O> {
+    /// Generate a key with the default options from `entropy`
+    pub fn with_entropy(entropy: &[u8]) -> Self {
+        Self::new(O::default(), entropy)
+    }
+}
+
+impl<O> GenerateOptions<O> {
+    /// Generate a key with the given `options` from `entropy`
+    pub fn new(options: O, entropy: &[u8]) -> Self {
+        GenerateOptions {
+            options,
+            entropy: Zeroizing::new(entropy.to_vec()),
+        }
+    }
+
+    /// Set the options of the key to generate
+    pub fn options(mut self, options: O) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Generate the key
+    ///
+    /// Only the first [`GeneratableKey::required_entropy_len`] bytes of the entropy are used.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`GenerateError::InsufficientEntropy`] if the entropy is too short for the key.
+    pub fn generate<Ctx, K>(self) -> Result<GeneratedKey<K, Ctx>, GenerateError<K::Error>>
+    where
+        Ctx: ScriptContext,
+        K: GeneratableKey<Ctx, Options = O>,
+    {
+        let required = K::required_entropy_len(&self.options);
+        if self.entropy.len() < required {
+            return Err(GenerateError::InsufficientEntropy {
+                required,
+                provided: self.entropy.len(),
+            });
+        }
+
+        let mut entropy = K::Entropy::default();
+        let len = entropy.as_mut().len().min(self.entropy.len());
+        entropy.as_mut()[..len].copy_from_slice(&self.entropy[..len]);
+        let generated = K::generate_with_entropy(self.options, entropy);
+        generated.map_err(GenerateError::Key)
+    }
+}
+
+impl<O: fmt::Debug> fmt::Debug for GenerateOptions<O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GenerateOptions")
+            .field("options", &self.options)
+            .field("entropy", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Errors thrown while generating a key with [`GenerateOptions`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum GenerateError<E> {
+    /// The entropy provided is too short for the key
+    InsufficientEntropy {
+        /// Number of bytes of entropy required
+        required: usize,
+        /// Number of bytes of entropy provided
+        provided: usize,
+    },
+    /// Error returned by the key generation
+    Key(E),
+}
+
+impl<E: fmt::Debug> fmt::Display for GenerateError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InsufficientEntropy { required, provided } => write!(
+                f,
+                "Insufficient entropy: {required} bytes are required, {provided} were provided"
+            ),
+            Self::Key(err) => write!(f, "Key generation error: {err:?}"),
+        }
+    }
+}
+
+impl<E: fmt::Debug> core::error::Error for GenerateError<E> {}
+
 impl<Ctx: ScriptContext> GeneratableKey<Ctx> for bip32::Xpriv {
     type Entropy = [u8; 32];
 
@@ -709,8 +847,10 @@ impl<Ctx: ScriptContext> GeneratableKey<Ctx> for bip32::Xpriv {
         } else {
             (NetworkKind::Test, test_network_kind())
         };
-        let xprv = bip32::Xpriv::new_master(kind, entropy.as_ref())?;
-        Ok(GeneratedKey::new(xprv, valid_networks))
+        let mut entropy = entropy;
+        let xprv = bip32::Xpriv::new_master(kind, entropy.as_ref());
+        entropy.zeroize();
+        Ok(GeneratedKey::new(xprv?, valid_networks))
     }
 }
 
@@ -759,7 +899,10 @@ impl<Ctx: ScriptContext> GeneratableKey<Ctx> for PrivateKey {
         options: Self::Options,
         entropy: Self::Entropy,
     ) -> Result<GeneratedKey<Self, Ctx>, Self::Error> {
-        let inner = secp256k1::SecretKey::from_slice(&entropy)?;
+        let mut entropy = entropy;
+        let inner = secp256k1::SecretKey::from_slice(&entropy);
+        entropy.zeroize();
+        let inner = inner?;
         let private_key = PrivateKey {
             compressed: options.compressed,
             network: options.network,
@@ -1089,6 +1232,42 @@ mod test {
         assert_eq!(generated_xprv.to_string(), "xprv9s21ZrQH143K4Xr1cJyqTvuL2FWR8eicgY9boWqMBv8MDVUZ65AXHnzBrK1nyomu6wdcabRgmGTaAKawvhAno1V5FowGpTLVx3jxzE5uk3Q");
     }
 
+    #[test]
+    fn test_keys_generate_options_with_entropy() {
+        // only the required bytes of entropy are used
+        let mut entropy = TEST_ENTROPY.to_vec();
+        entropy.extend([0x55; 32]);
+        let generated_xprv: GeneratedKey<bip32::Xpriv, miniscript::Segwitv0> =
+            GenerateOptions::with_entropy(&entropy).generate().unwrap();
+        assert_eq!(generated_xprv.valid_network_kinds, mainnet_network_kind());
+        assert_eq!(generated_xprv.to_string(), "xprv9s21ZrQH143K4Xr1cJyqTvuL2FWR8eicgY9boWqMBv8MDVUZ65AXHnzBrK1nyomu6wdcabRgmGTaAKawvhAno1V5FowGpTLVx3jxzE5uk3Q");
+
+        let generated_wif: GeneratedKey<bitcoin::PrivateKey, miniscript::Segwitv0> =
+            GenerateOptions::with_entropy(&TEST_ENTROPY)
+                .options(PrivateKeyGenerateOptions {
+                    network: NetworkKind::Test,
+                    ..Default::default()
+                })
+                .generate()
+                .unwrap();
+        assert_eq!(generated_wif.valid_network_kinds, test_network_kind());
+        assert_eq!(
+            generated_wif.inner.secret_bytes(),
+            TEST_ENTROPY,
+            "must be generated from the same entropy"
+        );
+
+        let generated = GenerateOptions::with_entropy(&TEST_ENTROPY[..31])
+            .generate::<miniscript::Segwitv0, bip32::Xpriv>();
+        assert!(matches!(
+            generated,
+            Err(GenerateError::InsufficientEntropy {
+                required: 32,
+                provided: 31
+            })
+        ));
+    }
+
     #[test]
     fn test_keys_generate_wif() {
         let generated_wif: GeneratedKey<_, miniscript::Segwitv0> =
@@ -0,0 +1,425 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2025 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! SLIP-0132 extended key encodings
+//!
+//! [SLIP-0132](https://github.com/satoshilabs/slips/blob/master/slip-0132.md) registers custom
+//! version bytes for extended keys that hint at the script type they are meant to be used with,
+//! e.g. `zpub` for native segwit or `ypub` for nested segwit. Taproot has no registered version
+//! bytes, so taproot keys use the standard `xpub`/`tpub` encoding.
+//!
+//! Descriptor strings containing SLIP-0132 keys are accepted by
+//! [`IntoWalletDescriptor`](crate::descriptor::IntoWalletDescriptor), which converts them to the
+//! standard encoding and checks that their version bytes match the script type of the
+//! descriptor.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use bitcoin::base58;
+use bitcoin::bip32::{self, Xpriv, Xpub};
+use bitcoin::NetworkKind;
+use miniscript::descriptor::DescriptorType;
+
+/// Errors thrown while decoding SLIP-0132 extended keys.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The key is not valid base58check.
+    Base58(base58::Error),
+    /// The key doesn't have the length of an extended key.
+    InvalidLength(usize),
+    /// The version bytes of the key are not registered in SLIP-0132.
+    UnknownVersion([u8; 4]),
+    /// The key is otherwise invalid.
+    Bip32(bip32::Error),
+    /// The version bytes of a key are meant for a different script type than the descriptor it is
+    /// used in, e.g. a `zpub` in a `pkh()` descriptor.
+    ScriptTypeMismatch {
+        /// The script type of the key.
+        key: ScriptType,
+        /// The script type of the descriptor.
+        descriptor: ScriptType,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base58(err) => write!(f, "Base58 error: {err}"),
+            Self::InvalidLength(len) => write!(f, "Invalid extended key length: {len}"),
+            Self::UnknownVersion(version) => write!(
+                f,
+                "Unknown extended key version: {}",
+                bitcoin::hex::DisplayHex::to_lower_hex_string(&version[..])
+            ),
+            Self::Bip32(err) => write!(f, "BIP32 error: {err}"),
+            Self::ScriptTypeMismatch { key, descriptor } => write!(
+                f,
+                "Extended key for {key:?} used in a {descriptor:?} descriptor"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<base58::Error> for Error {
+    fn from(err: base58::Error) -> Self {
+        Error::Base58(err)
+    }
+}
+
+impl From<bip32::Error> for Error {
+    fn from(err: bip32::Error) -> Self {
+        Error::Bip32(err)
+    }
+}
+
+/// The script type an extended key is meant to be used with, which selects its SLIP-0132 version
+/// bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptType {
+    /// Legacy P2PKH or P2SH (`xpub`/`tpub`).
+    Legacy,
+    /// P2WPKH nested in P2SH (`ypub`/`upub`).
+    P2shP2wpkh,
+    /// P2WSH nested in P2SH (`Ypub`/`Upub`).
+    P2shP2wsh,
+    /// Native P2WPKH (`zpub`/`vpub`).
+    P2wpkh,
+    /// Native P2WSH (`Zpub`/`Vpub`).
+    P2wsh,
+    /// Taproot (`xpub`/`tpub`, no SLIP-0132 version bytes are registered).
+    P2tr,
+}
+
+impl ScriptType {
+    /// Get the [`ScriptType`] of a descriptor of type `desc_type`.
+    pub fn from_descriptor_type(desc_type: DescriptorType) -> Self {
+        match desc_type {
+            DescriptorType::Bare
+            | DescriptorType::Sh
+            | DescriptorType::Pkh
+            | DescriptorType::ShSortedMulti => ScriptType::Legacy,
+            DescriptorType::ShWpkh => ScriptType::P2shP2wpkh,
+            DescriptorType::ShWsh | DescriptorType::ShWshSortedMulti => ScriptType::P2shP2wsh,
+            DescriptorType::Wpkh => ScriptType::P2wpkh,
+            DescriptorType::Wsh | DescriptorType::WshSortedMulti => ScriptType::P2wsh,
+            DescriptorType::Tr => ScriptType::P2tr,
+        }
+    }
+
+    /// Whether the two script types are meant for the same kind of outputs, i.e. both legacy,
+    /// nested segwit, native segwit or taproot.
+    fn is_compatible_with(&self, other: ScriptType) -> bool {
+        use ScriptType::*;
+        matches!(
+            (self, other),
+            (Legacy, Legacy)
+                | (P2shP2wpkh | P2shP2wsh, P2shP2wpkh | P2shP2wsh)
+                | (P2wpkh | P2wsh, P2wpkh | P2wsh)
+                | (P2tr, P2tr)
+        )
+    }
+
+    /// Version bytes of extended public keys on the given network kind.
+    fn xpub_version(&self, network_kind: NetworkKind) -> [u8; 4] {
+        match (self, network_kind) {
+            (ScriptType::Legacy | ScriptType::P2tr, NetworkKind::Main) => [0x04, 0x88, 0xb2, 0x1e],
+            (ScriptType::Legacy | ScriptType::P2tr, NetworkKind::Test) => [0x04, 0x35, 0x87, 0xcf],
+            (ScriptType::P2shP2wpkh, NetworkKind::Main) => [0x04, 0x9d, 0x7c, 0xb2],
+            (ScriptType::P2shP2wpkh, NetworkKind::Test) => [0x04, 0x4a, 0x52, 0x62],
+            (ScriptType::P2shP2wsh, NetworkKind::Main) => [0x02, 0x95, 0xb4, 0x3f],
+            (ScriptType::P2shP2wsh, NetworkKind::Test) => [0x02, 0x42, 0x89, 0xef],
+            (ScriptType::P2wpkh, NetworkKind::Main) => [0x04, 0xb2, 0x47, 0x46],
+            (ScriptType::P2wpkh, NetworkKind::Test) => [0x04, 0x5f, 0x1c, 0xf6],
+            (ScriptType::P2wsh, NetworkKind::Main) => [0x02, 0xaa, 0x7e, 0xd3],
+            (ScriptType::P2wsh, NetworkKind::Test) => [0x02, 0x57, 0x54, 0x83],
+        }
+    }
+
+    /// Version bytes of extended private keys on the given network kind.
+    fn xprv_version(&self, network_kind: NetworkKind) -> [u8; 4] {
+        match (self, network_kind) {
+            (ScriptType::Legacy | ScriptType::P2tr, NetworkKind::Main) => [0x04, 0x88, 0xad, 0xe4],
+            (ScriptType::Legacy | ScriptType::P2tr, NetworkKind::Test) => [0x04, 0x35, 0x83, 0x94],
+            (ScriptType::P2shP2wpkh, NetworkKind::Main) => [0x04, 0x9d, 0x78, 0x78],
+            (ScriptType::P2shP2wpkh, NetworkKind::Test) => [0x04, 0x4a, 0x4e, 0x28],
+            (ScriptType::P2shP2wsh, NetworkKind::Main) => [0x02, 0x95, 0xb0, 0x05],
+            (ScriptType::P2shP2wsh, NetworkKind::Test) => [0x02, 0x42, 0x85, 0xb5],
+            (ScriptType::P2wpkh, NetworkKind::Main) => [0x04, 0xb2, 0x43, 0x0c],
+            (ScriptType::P2wpkh, NetworkKind::Test) => [0x04, 0x5f, 0x18, 0xbc],
+            (ScriptType::P2wsh, NetworkKind::Main) => [0x02, 0xaa, 0x7a, 0x99],
+            (ScriptType::P2wsh, NetworkKind::Test) => [0x02, 0x57, 0x50, 0x48],
+        }
+    }
+}
+
+/// Script types with distinct version bytes, [`ScriptType::P2tr`] uses the ones of
+/// [`ScriptType::Legacy`].
+const DISTINCT_SCRIPT_TYPES: [ScriptType; 5] = [
+    ScriptType::Legacy,
+    ScriptType::P2shP2wpkh,
+    ScriptType::P2shP2wsh,
+    ScriptType::P2wpkh,
+    ScriptType::P2wsh,
+];
+
+/// Decode base58check `s` and replace its SLIP-0132 version bytes with the standard ones, using
+/// `version_of` to get the version bytes of a script type.
+fn decode_with(
+    s: &str,
+    version_of: fn(&ScriptType, NetworkKind) -> [u8; 4],
+) -> Result<([u8; 78], ScriptType), Error> {
+    let data = base58::decode_check(s)?;
+    let mut data: [u8; 78] = data
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::InvalidLength(data.len()))?;
+    let version: [u8; 4] = data[..4].try_into().expect("slice has length 4");
+    let (script_type, network_kind) = DISTINCT_SCRIPT_TYPES
+        .iter()
+        .flat_map(|script_type| {
+            [NetworkKind::Main, NetworkKind::Test].map(|network_kind| (*script_type, network_kind))
+        })
+        .find(|(script_type, network_kind)| version_of(script_type, *network_kind) == version)
+        .ok_or(Error::UnknownVersion(version))?;
+    data[..4].copy_from_slice(&version_of(&ScriptType::Legacy, network_kind));
+    Ok((data, script_type))
+}
+
+/// Decode an extended public key with any of the SLIP-0132 version bytes, e.g. a `zpub`.
+///
+/// Returns the key along with the script type its version bytes are meant for. Keys with the
+/// standard `xpub`/`tpub` version bytes are also accepted and return [`ScriptType::Legacy`],
+/// though they're also used for taproot and, by some wallets, for every script type.
+pub fn decode(s: &str) -> Result<(Xpub, ScriptType), Error> {
+    let (data, script_type) = decode_with(s, ScriptType::xpub_version)?;
+    Ok((Xpub::decode(&data)?, script_type))
+}
+
+/// Decode an extended private key with any of the SLIP-0132 version bytes, e.g. a `zprv`.
+///
+/// Refer to [`decode`] for more.
+pub fn decode_xprv(s: &str) -> Result<(Xpriv, ScriptType), Error> {
+    let (data, script_type) = decode_with(s, ScriptType::xprv_version)?;
+    Ok((Xpriv::decode(&data)?, script_type))
+}
+
+/// Length of base58 encoded extended keys.
+const ENCODED_LEN: usize = 111;
+
+/// Replace the extended keys with non-standard SLIP-0132 version bytes in `descriptor` with
+/// their standard encoding.
+///
+/// Returns `None` if there are no such keys, otherwise the converted descriptor along with the
+/// script types of the replaced keys.
+pub(crate) fn replace_descriptor_keys(
+    descriptor: &str,
+) -> Result<Option<(String, Vec<ScriptType>)>, Error> {
+    // extended keys are the only runs of 111 base58 characters a descriptor may contain
+    let is_base58 = |c: char| c.is_ascii_alphanumeric() && !matches!(c, '0' | 'O' | 'I' | 'l');
+    let mut converted = String::with_capacity(descriptor.len());
+    let mut script_types = Vec::new();
+    let mut rest = descriptor;
+    while let Some(start) = rest.find(is_base58) {
+        let len = rest[start..]
+            .find(|c| !is_base58(c))
+            .unwrap_or(rest.len() - start);
+        let token = &rest[start..start + len];
+        converted.push_str(&rest[..start]);
+        match token.get(1..4) {
+            Some("pub") if len == ENCODED_LEN && !matches!(&token[..1], "x" | "t") => {
+                let (xpub, script_type) = decode(token)?;
+                converted.push_str(&xpub.to_string());
+                script_types.push(script_type);
+            }
+            Some("prv") if len == ENCODED_LEN && !matches!(&token[..1], "x" | "t") => {
+                let (xprv, script_type) = decode_xprv(token)?;
+                converted.push_str(&xprv.to_string());
+                script_types.push(script_type);
+            }
+            _ => converted.push_str(token),
+        }
+        rest = &rest[start + len..];
+    }
+    converted.push_str(rest);
+
+    Ok((!script_types.is_empty()).then_some((converted, script_types)))
+}
+
+/// Check that the keys with `key_script_types` can be used in a descriptor of type `desc_type`.
+pub(crate) fn check_script_types(
+    desc_type: DescriptorType,
+    key_script_types: &[ScriptType],
+) -> Result<(), Error> {
+    let descriptor = ScriptType::from_descriptor_type(desc_type);
+    match key_script_types
+        .iter()
+        .find(|key| !key.is_compatible_with(descriptor))
+    {
+        Some(&key) => Err(Error::ScriptTypeMismatch { key, descriptor }),
+        None => Ok(()),
+    }
+}
+
+/// Encode `xpub` with the SLIP-0132 version bytes of `script_type`.
+pub fn encode_xpub(xpub: &Xpub, script_type: ScriptType) -> String {
+    let mut data = xpub.encode();
+    data[..4].copy_from_slice(&script_type.xpub_version(xpub.network));
+    base58::encode_check(&data)
+}
+
+/// Encode `xprv` with the SLIP-0132 version bytes of `script_type`.
+pub fn encode_xprv(xprv: &Xpriv, script_type: ScriptType) -> String {
+    let mut data = xprv.encode();
+    data[..4].copy_from_slice(&script_type.xprv_version(xprv.network));
+    base58::encode_check(&data)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    use core::str::FromStr;
+
+    #[test]
+    fn test_encode_bip84_vectors() {
+        // test vectors from BIP84
+        let xpub = Xpub::from_str("xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V").unwrap();
+        assert_eq!(
+            encode_xpub(&xpub, ScriptType::P2wpkh),
+            "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs"
+        );
+        let xprv = Xpriv::from_str("xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu").unwrap();
+        assert_eq!(
+            encode_xprv(&xprv, ScriptType::P2wpkh),
+            "zprvAWgYBBk7JR8Gjrh4UJQ2uJdG1r3WNRRfURiABBE3RvMXYSrRJL62XuezvGdPvG6GFBZduosCc1YP5wixPox7zhZLfiUm8aunE96BBa4Kei5"
+        );
+    }
+
+    #[test]
+    fn test_encode_standard() {
+        let xpub = Xpub::from_str("tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq").unwrap();
+        assert_eq!(encode_xpub(&xpub, ScriptType::Legacy), xpub.to_string());
+        assert_eq!(encode_xpub(&xpub, ScriptType::P2tr), xpub.to_string());
+
+        let xprv = Xpriv::from_str("tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS").unwrap();
+        assert_eq!(encode_xprv(&xprv, ScriptType::Legacy), xprv.to_string());
+    }
+
+    #[test]
+    fn test_encode_prefixes() {
+        let main = Xpub::from_str("xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V").unwrap();
+        let test = Xpub::from_str("tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq").unwrap();
+        for (script_type, main_prefix, test_prefix) in [
+            (ScriptType::Legacy, "xpub", "tpub"),
+            (ScriptType::P2shP2wpkh, "ypub", "upub"),
+            (ScriptType::P2shP2wsh, "Ypub", "Upub"),
+            (ScriptType::P2wpkh, "zpub", "vpub"),
+            (ScriptType::P2wsh, "Zpub", "Vpub"),
+            (ScriptType::P2tr, "xpub", "tpub"),
+        ] {
+            assert!(encode_xpub(&main, script_type).starts_with(main_prefix));
+            assert!(encode_xpub(&test, script_type).starts_with(test_prefix));
+        }
+    }
+
+    #[test]
+    fn test_decode_bip84_vectors() {
+        let (xpub, script_type) = decode("zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs").unwrap();
+        assert_eq!(script_type, ScriptType::P2wpkh);
+        assert_eq!(xpub.to_string(), "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V");
+
+        let (xprv, script_type) = decode_xprv("zprvAWgYBBk7JR8Gjrh4UJQ2uJdG1r3WNRRfURiABBE3RvMXYSrRJL62XuezvGdPvG6GFBZduosCc1YP5wixPox7zhZLfiUm8aunE96BBa4Kei5").unwrap();
+        assert_eq!(script_type, ScriptType::P2wpkh);
+        assert_eq!(xprv.to_string(), "xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu");
+    }
+
+    #[test]
+    fn test_decode_prefixes() {
+        let main = Xpub::from_str("xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V").unwrap();
+        let test = Xpub::from_str("tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq").unwrap();
+        for (xpub, script_type, prefix) in [
+            (main, ScriptType::Legacy, "xpub"),
+            (main, ScriptType::P2shP2wpkh, "ypub"),
+            (main, ScriptType::P2wpkh, "zpub"),
+            (test, ScriptType::Legacy, "tpub"),
+            (test, ScriptType::P2shP2wpkh, "upub"),
+            (test, ScriptType::P2wpkh, "vpub"),
+            (main, ScriptType::P2shP2wsh, "Ypub"),
+            (main, ScriptType::P2wsh, "Zpub"),
+            (test, ScriptType::P2shP2wsh, "Upub"),
+            (test, ScriptType::P2wsh, "Vpub"),
+        ] {
+            let encoded = encode_xpub(&xpub, script_type);
+            assert!(encoded.starts_with(prefix));
+            assert_eq!(decode(&encoded).unwrap(), (xpub, script_type));
+        }
+
+        let xprv = Xpriv::from_str("tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS").unwrap();
+        for (script_type, prefix) in [
+            (ScriptType::Legacy, "tprv"),
+            (ScriptType::P2shP2wpkh, "uprv"),
+            (ScriptType::P2wpkh, "vprv"),
+        ] {
+            let encoded = encode_xprv(&xprv, script_type);
+            assert!(encoded.starts_with(prefix));
+            assert_eq!(decode_xprv(&encoded).unwrap(), (xprv, script_type));
+        }
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let xpub = Xpub::from_str("xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V").unwrap();
+
+        // private key version bytes for a public key
+        let encoded = encode_xprv(
+            &Xpriv::from_str("xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu").unwrap(),
+            ScriptType::P2wpkh,
+        );
+        assert_eq!(
+            decode(&encoded),
+            Err(Error::UnknownVersion([0x04, 0xb2, 0x43, 0x0c]))
+        );
+
+        let mut data = xpub.encode();
+        data[..4].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(
+            decode(&base58::encode_check(&data)),
+            Err(Error::UnknownVersion([0xde, 0xad, 0xbe, 0xef]))
+        );
+        assert_eq!(
+            decode(&base58::encode_check(&data[..77])),
+            Err(Error::InvalidLength(77))
+        );
+        assert!(matches!(decode("zpub"), Err(Error::Base58(_))));
+    }
+
+    #[test]
+    fn test_replace_descriptor_keys() {
+        let descriptor = "wpkh([73c5da0a/84'/0'/0']zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs/0/*)";
+        let (converted, script_types) = replace_descriptor_keys(descriptor).unwrap().unwrap();
+        assert_eq!(converted, "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)");
+        assert_eq!(script_types, [ScriptType::P2wpkh]);
+
+        // standard keys are left alone
+        assert_eq!(replace_descriptor_keys(&converted).unwrap(), None);
+        assert_eq!(
+            replace_descriptor_keys(
+                "wpkh(02e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c)"
+            )
+            .unwrap(),
+            None
+        );
+    }
+}
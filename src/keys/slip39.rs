@@ -0,0 +1,1146 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2025 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! SLIP-0039 Shamir backups
+//!
+//! [SLIP-0039](https://github.com/satoshilabs/slips/blob/master/slip-0039.md) splits a master
+//! secret into groups of mnemonic shares: the secret can be recovered from a threshold number of
+//! groups, each of them with a threshold number of member shares.
+//!
+//! The recovered [`MasterSecret`] is the BIP32 seed of the wallet, so it can be used in descriptor
+//! templates like BIP39 mnemonics.
+//!
+//! ## Example
+//!
+//! ```
+//! # use bdk_wallet::bitcoin::Network;
+//! # use bdk_wallet::keys::slip39::{combine_shares, generate_shares};
+//! # use bdk_wallet::template::Bip84;
+//! # use bdk_wallet::{KeychainKind, Wallet};
+//! let master_secret = [0x42; 16];
+//! // 2 of 3 groups: one share alone, 2 of 3 shares, or 3 of 5 shares
+//! let groups = generate_shares(&master_secret, "", 2, &[(1, 1), (2, 3), (3, 5)])?;
+//!
+//! let recovered = combine_shares(
+//!     &[&groups[0][0], &groups[1][0], &groups[1][2]],
+//!     "",
+//! )?;
+//! assert_eq!(recovered.as_bytes(), master_secret);
+//!
+//! let wallet = Wallet::create(
+//!     Bip84(recovered.clone(), KeychainKind::External),
+//!     Bip84(recovered, KeychainKind::Internal),
+//! )
+//! .network(Network::Testnet)
+//! .create_wallet_no_persist()?;
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use bitcoin::bip32;
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use bitcoin::Network;
+use miniscript::ScriptContext;
+use rand_core::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
+
+use super::{any_network_kind, DerivableKey, DescriptorKey, ExtendedKey, KeyError};
+
+/// Number of bits of the random identifier of a set of shares.
+const ID_BITS: u32 = 15;
+/// Number of bits of a mnemonic word.
+const WORD_BITS: u32 = 10;
+/// Number of words of the checksum.
+const CHECKSUM_WORDS: usize = 3;
+/// Number of words before the share value: identifier, extendable flag, iteration exponent,
+/// group and member parameters.
+const HEADER_WORDS: usize = 4;
+/// Minimum number of words of a mnemonic, for a 128 bits master secret.
+const MIN_MNEMONIC_WORDS: usize = HEADER_WORDS + 13 + CHECKSUM_WORDS;
+/// Maximum number of groups, and of member shares in each group.
+const MAX_SHARE_COUNT: u8 = 16;
+/// Index of the share containing the digest of the shared secret.
+const DIGEST_INDEX: u8 = 254;
+/// Index of the share containing the shared secret.
+const SECRET_INDEX: u8 = 255;
+/// Length of the digest of the shared secret, in bytes.
+const DIGEST_LEN: usize = 4;
+/// Minimum length of the master secret, in bytes.
+const MIN_SECRET_LEN: usize = 16;
+/// Total number of PBKDF2 iterations of the encryption, multiplied by `2^iteration_exponent`.
+const BASE_ITERATION_COUNT: u32 = 10_000;
+/// Number of rounds of the Feistel network used to encrypt the master secret.
+const ROUND_COUNT: u8 = 4;
+/// Iteration exponent of the shares generated by [`generate_shares`].
+const ITERATION_EXPONENT: u8 = 1;
+
+/// Errors thrown while generating or combining SLIP-0039 shares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A word is not part of the SLIP-0039 wordlist.
+    InvalidWord(String),
+    /// The checksum of the share at this index is invalid.
+    InvalidChecksum(usize),
+    /// The share at this index is malformed: its length, padding or parameters are invalid.
+    InvalidShare(usize),
+    /// The shares don't belong to the same set, or disagree on the parameters of their group.
+    MismatchedParameters,
+    /// Two different shares have the same member index in the same group.
+    DuplicateShare,
+    /// Not enough groups have reached their member threshold.
+    InsufficientGroups {
+        /// Number of groups required to recover the master secret.
+        required: u8,
+        /// Number of groups with enough shares.
+        provided: u8,
+    },
+    /// A group doesn't have enough member shares.
+    InsufficientShares {
+        /// Index of the group.
+        group: u8,
+        /// Number of member shares required to recover the group secret.
+        required: u8,
+        /// Number of member shares provided.
+        provided: u8,
+    },
+    /// The shares are consistent but the recovered secret doesn't match its digest, meaning
+    /// that at least one of them is wrong.
+    InvalidDigest,
+    /// The master secret must be at least 16 bytes long, and its length must be even.
+    InvalidMasterSecret,
+    /// The passphrase may only contain printable ASCII characters.
+    InvalidPassphrase,
+    /// The group parameters are invalid: there must be up to 16 groups of up to 16 member shares,
+    /// with thresholds that can be met. Groups of more than one share can't have a threshold of
+    /// one.
+    InvalidGroups,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidWord(word) => write!(f, "Invalid SLIP-0039 word: {word}"),
+            Self::InvalidChecksum(index) => write!(f, "Invalid checksum of share #{index}"),
+            Self::InvalidShare(index) => write!(f, "Invalid share #{index}"),
+            Self::MismatchedParameters => write!(f, "The shares have mismatched parameters"),
+            Self::DuplicateShare => write!(f, "Duplicate member index in the same group"),
+            Self::InsufficientGroups { required, provided } => write!(
+                f,
+                "Insufficient groups: {required} are required, {provided} were provided"
+            ),
+            Self::InsufficientShares {
+                group,
+                required,
+                provided,
+            } => write!(
+                f,
+                "Insufficient shares for group #{group}: {required} are required, {provided} were provided"
+            ),
+            Self::InvalidDigest => write!(f, "The digest of the recovered secret doesn't match"),
+            Self::InvalidMasterSecret => write!(f, "Invalid master secret length"),
+            Self::InvalidPassphrase => write!(f, "Non-printable ASCII passphrases are not supported"),
+            Self::InvalidGroups => write!(f, "Invalid group parameters"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<Error> for KeyError {
+    fn from(err: Error) -> Self {
+        KeyError::Message(err.to_string())
+    }
+}
+
+/// A master secret recovered from SLIP-0039 shares.
+///
+/// This is the BIP32 seed of the wallet. It's zeroized when dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MasterSecret(Zeroizing<Vec<u8>>);
+
+impl MasterSecret {
+    /// Get the bytes of the master secret.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for MasterSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MasterSecret").field(&"<redacted>").finish()
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "slip39")))]
+impl<Ctx: ScriptContext> DerivableKey<Ctx> for MasterSecret {
+    fn into_extended_key(self) -> Result<ExtendedKey<Ctx>, KeyError> {
+        Ok(bip32::Xpriv::new_master(Network::Bitcoin, self.as_bytes())?.into())
+    }
+
+    fn into_descriptor_key(
+        self,
+        source: Option<bip32::KeySource>,
+        derivation_path: bip32::DerivationPath,
+    ) -> Result<DescriptorKey<Ctx>, KeyError> {
+        let descriptor_key = self
+            .into_extended_key()?
+            .into_descriptor_key(source, derivation_path)?;
+
+        // SLIP-0039 shares don't encode the network kind either, see the BIP39 keys
+        Ok(descriptor_key.override_valid_network_kinds(any_network_kind()))
+    }
+}
+
+/// A decoded share.
+struct Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Zeroizing<Vec<u8>>,
+}
+
+impl Share {
+    /// Whether `other` is part of the same set of shares.
+    fn same_set(&self, other: &Share) -> bool {
+        self.identifier == other.identifier
+            && self.extendable == other.extendable
+            && self.iteration_exponent == other.iteration_exponent
+            && self.group_threshold == other.group_threshold
+            && self.group_count == other.group_count
+            && self.value.len() == other.value.len()
+    }
+
+    /// Parse the share at `index` of the list of shares being combined.
+    fn parse(mnemonic: &str, index: usize) -> Result<Self, Error> {
+        let words = mnemonic
+            .split_whitespace()
+            .map(|word| {
+                let word = word.to_lowercase();
+                WORDLIST
+                    .binary_search(&word.as_str())
+                    .map(|i| i as u16)
+                    .map_err(|_| Error::InvalidWord(word))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if words.len() < MIN_MNEMONIC_WORDS {
+            return Err(Error::InvalidShare(index));
+        }
+
+        let extendable = (words[1] >> 4) & 1 == 1;
+        if !rs1024_verify(customization(extendable), &words) {
+            return Err(Error::InvalidChecksum(index));
+        }
+
+        let value_words = &words[HEADER_WORDS..words.len() - CHECKSUM_WORDS];
+        let padding = (WORD_BITS as usize * value_words.len()) % 16;
+        if padding > 8 {
+            return Err(Error::InvalidShare(index));
+        }
+        let value = words_to_bytes(value_words, padding).ok_or(Error::InvalidShare(index))?;
+
+        let id_exp = (u32::from(words[0]) << WORD_BITS) | u32::from(words[1]);
+        let params = (u32::from(words[2]) << WORD_BITS) | u32::from(words[3]);
+        let share = Share {
+            identifier: (id_exp >> (2 * WORD_BITS - ID_BITS)) as u16,
+            extendable,
+            iteration_exponent: (id_exp & 0xF) as u8,
+            group_index: (params >> 16) as u8,
+            group_threshold: ((params >> 12) & 0xF) as u8 + 1,
+            group_count: ((params >> 8) & 0xF) as u8 + 1,
+            member_index: ((params >> 4) & 0xF) as u8,
+            member_threshold: (params & 0xF) as u8 + 1,
+            value,
+        };
+        if share.group_count < share.group_threshold {
+            return Err(Error::InvalidShare(index));
+        }
+
+        Ok(share)
+    }
+
+    /// Encode the share as a mnemonic.
+    fn to_mnemonic(&self) -> String {
+        let id_exp = (u32::from(self.identifier) << (2 * WORD_BITS - ID_BITS))
+            | (u32::from(self.extendable) << 4)
+            | u32::from(self.iteration_exponent);
+        let params = (u32::from(self.group_index) << 16)
+            | (u32::from(self.group_threshold - 1) << 12)
+            | (u32::from(self.group_count - 1) << 8)
+            | (u32::from(self.member_index) << 4)
+            | u32::from(self.member_threshold - 1);
+
+        let mut words = Vec::new();
+        for field in [id_exp, params] {
+            words.push((field >> WORD_BITS) as u16);
+            words.push((field & 0x3FF) as u16);
+        }
+        words.extend(bytes_to_words(&self.value));
+        let checksum = rs1024_create_checksum(customization(self.extendable), &words);
+        words.extend(checksum);
+
+        words
+            .into_iter()
+            .map(|word| WORDLIST[word as usize])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The customization string of the checksum.
+fn customization(extendable: bool) -> &'static [u8] {
+    if extendable {
+        b"shamir_extendable"
+    } else {
+        b"shamir"
+    }
+}
+
+fn rs1024_polymod(values: impl IntoIterator<Item = u16>) -> u32 {
+    const GEN: [u32; 10] = [
+        0x00E0_E040,
+        0x01C1_C080,
+        0x0383_8100,
+        0x0707_0200,
+        0x0E0E_0009,
+        0x1C0C_2412,
+        0x3808_6C24,
+        0x3090_FC48,
+        0x21B1_F890,
+        0x03F3_F120,
+    ];
+    let mut chk = 1u32;
+    for value in values {
+        let b = chk >> 20;
+        chk = ((chk & 0xF_FFFF) << 10) ^ u32::from(value);
+        for (i, gen) in GEN.iter().enumerate() {
+            if (b >> i) & 1 == 1 {
+                chk ^= gen;
+            }
+        }
+    }
+    chk
+}
+
+fn rs1024_verify(customization: &[u8], words: &[u16]) -> bool {
+    let values = customization.iter().map(|&c| u16::from(c));
+    rs1024_polymod(values.chain(words.iter().copied())) == 1
+}
+
+fn rs1024_create_checksum(customization: &[u8], words: &[u16]) -> [u16; CHECKSUM_WORDS] {
+    let values = customization.iter().map(|&c| u16::from(c));
+    let polymod = rs1024_polymod(
+        values
+            .chain(words.iter().copied())
+            .chain([0; CHECKSUM_WORDS]),
+    ) ^ 1;
+    [
+        (polymod >> 20) as u16 & 0x3FF,
+        (polymod >> 10) as u16 & 0x3FF,
+        polymod as u16 & 0x3FF,
+    ]
+}
+
+/// Convert the share value words to bytes, checking that the `padding` bits are zero.
+fn words_to_bytes(words: &[u16], padding: usize) -> Option<Zeroizing<Vec<u8>>> {
+    let mut bytes = Zeroizing::new(Vec::with_capacity(words.len() * 10 / 8));
+    let (mut acc, mut bits) = (0u32, 0usize);
+    for (i, word) in words.iter().enumerate() {
+        acc = (acc << WORD_BITS) | u32::from(*word);
+        bits += WORD_BITS as usize;
+        if i == 0 {
+            // the padding bits are all in the first word
+            bits -= padding;
+            if acc >> bits != 0 {
+                return None;
+            }
+        }
+        while bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Convert a share value to words, padding it with zero bits to a multiple of the word size.
+fn bytes_to_words(bytes: &[u8]) -> Vec<u16> {
+    let word_count = (bytes.len() * 8).div_ceil(WORD_BITS as usize);
+    let mut words = Vec::with_capacity(word_count);
+    let (mut acc, mut bits) = (0u32, word_count * WORD_BITS as usize - bytes.len() * 8);
+    for byte in bytes {
+        acc = (acc << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= WORD_BITS as usize {
+            bits -= WORD_BITS as usize;
+            words.push((acc >> bits) as u16);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    words
+}
+
+/// Logarithm and exponent tables of GF(256) with the Rijndael polynomial.
+const GF256: ([u8; 256], [u8; 255]) = {
+    let mut log = [0u8; 256];
+    let mut exp = [0u8; 255];
+    let mut poly: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = poly as u8;
+        log[poly as usize] = i as u8;
+        // multiply by the generator 3
+        poly = (poly << 1) ^ poly;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11B;
+        }
+        i += 1;
+    }
+    (log, exp)
+};
+
+/// Evaluate at `x` the polynomial going through the points of `shares`.
+fn interpolate(shares: &[(u8, &[u8])], x: u8) -> Zeroizing<Vec<u8>> {
+    if let Some((_, value)) = shares.iter().find(|(share_x, _)| *share_x == x) {
+        return Zeroizing::new(value.to_vec());
+    }
+
+    let (log, exp) = &GF256;
+    let log_prod: usize = shares
+        .iter()
+        .map(|(share_x, _)| log[(share_x ^ x) as usize] as usize)
+        .sum();
+
+    let mut result = Zeroizing::new(vec![0u8; shares[0].1.len()]);
+    for (share_x, value) in shares {
+        // the logarithm of the Lagrange basis polynomial evaluated at `x`
+        let log_basis = (log_prod - log[(share_x ^ x) as usize] as usize + 255 * shares.len()
+            - shares
+                .iter()
+                .map(|(other_x, _)| log[(share_x ^ other_x) as usize] as usize)
+                .sum::<usize>())
+            % 255;
+        for (r, v) in result.iter_mut().zip(value.iter()) {
+            if *v != 0 {
+                *r ^= exp[(log[*v as usize] as usize + log_basis) % 255];
+            }
+        }
+    }
+    result
+}
+
+fn shared_secret_digest(random: &[u8], secret: &[u8]) -> [u8; DIGEST_LEN] {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(random);
+    engine.input(secret);
+    let digest = hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+    let mut result = [0; DIGEST_LEN];
+    result.copy_from_slice(&digest[..DIGEST_LEN]);
+    result
+}
+
+/// Split `secret` in `count` shares, `threshold` of which are required to recover it.
+fn split_secret(
+    threshold: u8,
+    count: u8,
+    secret: &[u8],
+    rng: &mut (impl CryptoRng + RngCore),
+) -> Vec<(u8, Zeroizing<Vec<u8>>)> {
+    if threshold == 1 {
+        return (0..count)
+            .map(|i| (i, Zeroizing::new(secret.to_vec())))
+            .collect();
+    }
+
+    let random_share_count = threshold - 2;
+    let mut shares = (0..random_share_count)
+        .map(|i| {
+            let mut value = Zeroizing::new(vec![0; secret.len()]);
+            rng.fill_bytes(&mut value);
+            (i, value)
+        })
+        .collect::<Vec<_>>();
+
+    let mut digest_share = Zeroizing::new(vec![0; secret.len()]);
+    rng.fill_bytes(&mut digest_share[DIGEST_LEN..]);
+    let digest = shared_secret_digest(&digest_share[DIGEST_LEN..], secret);
+    digest_share[..DIGEST_LEN].copy_from_slice(&digest);
+
+    let base_shares = shares
+        .iter()
+        .map(|(x, value)| (*x, value.as_slice()))
+        .chain([
+            (DIGEST_INDEX, digest_share.as_slice()),
+            (SECRET_INDEX, secret),
+        ])
+        .collect::<Vec<_>>();
+    let interpolated = (random_share_count..count)
+        .map(|i| (i, interpolate(&base_shares, i)))
+        .collect::<Vec<_>>();
+    shares.extend(interpolated);
+    shares
+}
+
+/// Recover the secret from `threshold` shares, checking its digest.
+fn recover_secret(threshold: u8, shares: &[(u8, &[u8])]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    if threshold == 1 {
+        return Ok(Zeroizing::new(shares[0].1.to_vec()));
+    }
+
+    let secret = interpolate(shares, SECRET_INDEX);
+    let digest_share = interpolate(shares, DIGEST_INDEX);
+    let (digest, random) = digest_share.split_at(DIGEST_LEN);
+    if digest != shared_secret_digest(random, &secret) {
+        return Err(Error::InvalidDigest);
+    }
+    Ok(secret)
+}
+
+/// PBKDF2-HMAC-SHA256 deriving `output.len()` bytes.
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, output: &mut [u8]) {
+    let hmac_key = hmac::HmacEngine::<sha256::Hash>::new(password);
+    for (block, chunk) in output.chunks_mut(32).enumerate() {
+        let mut engine = hmac_key.clone();
+        engine.input(salt);
+        engine.input(&(block as u32 + 1).to_be_bytes());
+        let mut u = hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+        let mut t = u;
+        for _ in 1..iterations {
+            let mut engine = hmac_key.clone();
+            engine.input(&u);
+            u = hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+            t.iter_mut().zip(u).for_each(|(t, u)| *t ^= u);
+        }
+        chunk.copy_from_slice(&t[..chunk.len()]);
+    }
+}
+
+/// Encrypt or decrypt `secret` with the Feistel network of SLIP-0039.
+fn feistel(
+    secret: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+    decrypt: bool,
+) -> Zeroizing<Vec<u8>> {
+    let mut salt = Vec::new();
+    if !extendable {
+        salt.extend_from_slice(b"shamir");
+        salt.extend_from_slice(&identifier.to_be_bytes());
+    }
+    let iterations = (BASE_ITERATION_COUNT << iteration_exponent) / u32::from(ROUND_COUNT);
+
+    let half = secret.len() / 2;
+    let mut left = Zeroizing::new(secret[..half].to_vec());
+    let mut right = Zeroizing::new(secret[half..].to_vec());
+    let mut round_key = Zeroizing::new(Vec::new());
+    let mut round_output = Zeroizing::new(vec![0; half]);
+    for i in 0..ROUND_COUNT {
+        let i = if decrypt { ROUND_COUNT - 1 - i } else { i };
+        round_key.clear();
+        round_key.push(i);
+        round_key.extend_from_slice(passphrase.as_bytes());
+        let mut round_salt = salt.clone();
+        round_salt.extend_from_slice(&right);
+        pbkdf2_sha256(&round_key, &round_salt, iterations, &mut round_output);
+
+        left.iter_mut()
+            .zip(round_output.iter())
+            .for_each(|(l, f)| *l ^= f);
+        core::mem::swap(&mut left, &mut right);
+    }
+
+    let mut result = Zeroizing::new(Vec::with_capacity(secret.len()));
+    result.extend_from_slice(&right);
+    result.extend_from_slice(&left);
+    result
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), Error> {
+    if passphrase.bytes().all(|b| (32..=126).contains(&b)) {
+        Ok(())
+    } else {
+        Err(Error::InvalidPassphrase)
+    }
+}
+
+/// Split `master_secret` in SLIP-0039 shares.
+///
+/// `groups` lists the `(member_threshold, member_count)` of each group: the master secret can be
+/// recovered from `group_threshold` groups, each with `member_threshold` of its shares. The
+/// master secret is encrypted with `passphrase`, which can be empty.
+///
+/// Returns the mnemonic shares of each group. The shares are extendable, meaning that more
+/// shares of the same master secret and passphrase can be generated later, and their iteration
+/// exponent is 1.
+///
+/// Uses the thread-local random number generator.
+#[cfg(feature = "std")]
+pub fn generate_shares(
+    master_secret: &[u8],
+    passphrase: &str,
+    group_threshold: u8,
+    groups: &[(u8, u8)],
+) -> Result<Vec<Vec<String>>, Error> {
+    generate_shares_with_aux_rand(
+        master_secret,
+        passphrase,
+        group_threshold,
+        groups,
+        &mut bitcoin::key::rand::thread_rng(),
+    )
+}
+
+/// Split `master_secret` in SLIP-0039 shares.
+///
+/// Uses a provided random number generator (rng), refer to [`generate_shares`] for more.
+pub fn generate_shares_with_aux_rand(
+    master_secret: &[u8],
+    passphrase: &str,
+    group_threshold: u8,
+    groups: &[(u8, u8)],
+    rng: &mut (impl CryptoRng + RngCore),
+) -> Result<Vec<Vec<String>>, Error> {
+    if master_secret.len() < MIN_SECRET_LEN || master_secret.len() % 2 != 0 {
+        return Err(Error::InvalidMasterSecret);
+    }
+    check_passphrase(passphrase)?;
+    if groups.is_empty()
+        || groups.len() > MAX_SHARE_COUNT as usize
+        || group_threshold == 0
+        || group_threshold as usize > groups.len()
+    {
+        return Err(Error::InvalidGroups);
+    }
+    for &(threshold, count) in groups {
+        if threshold == 0 || threshold > count || count > MAX_SHARE_COUNT {
+            return Err(Error::InvalidGroups);
+        }
+        if threshold == 1 && count > 1 {
+            return Err(Error::InvalidGroups);
+        }
+    }
+
+    let identifier = (rng.next_u32() & ((1 << ID_BITS) - 1)) as u16;
+    let extendable = true;
+    let encrypted_secret = feistel(
+        master_secret,
+        passphrase,
+        ITERATION_EXPONENT,
+        identifier,
+        extendable,
+        false,
+    );
+
+    let group_count = groups.len() as u8;
+    let group_secrets = split_secret(group_threshold, group_count, &encrypted_secret, rng);
+    Ok(groups
+        .iter()
+        .zip(group_secrets)
+        .map(
+            |(&(member_threshold, member_count), (group_index, group_secret))| {
+                split_secret(member_threshold, member_count, &group_secret, rng)
+                    .into_iter()
+                    .map(|(member_index, value)| {
+                        Share {
+                            identifier,
+                            extendable,
+                            iteration_exponent: ITERATION_EXPONENT,
+                            group_index,
+                            group_threshold,
+                            group_count,
+                            member_index,
+                            member_threshold,
+                            value,
+                        }
+                        .to_mnemonic()
+                    })
+                    .collect()
+            },
+        )
+        .collect())
+}
+
+/// Recover the master secret from SLIP-0039 `shares` and the `passphrase` used to generate
+/// them, which can be empty.
+///
+/// Extra groups or shares are ignored. Note that a wrong passphrase can't be detected: it
+/// recovers a different master secret.
+pub fn combine_shares(shares: &[&str], passphrase: &str) -> Result<MasterSecret, Error> {
+    check_passphrase(passphrase)?;
+    let shares = shares
+        .iter()
+        .enumerate()
+        .map(|(index, share)| Share::parse(share, index))
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = shares.first().ok_or(Error::InsufficientGroups {
+        required: 1,
+        provided: 0,
+    })?;
+    if !shares.iter().all(|share| share.same_set(first)) {
+        return Err(Error::MismatchedParameters);
+    }
+
+    let mut groups = BTreeMap::<u8, Vec<&Share>>::new();
+    for share in &shares {
+        let group = groups.entry(share.group_index).or_default();
+        if let Some(other) = group.iter().find(|s| s.member_index == share.member_index) {
+            if other.value != share.value {
+                return Err(Error::DuplicateShare);
+            }
+            continue;
+        }
+        if group
+            .iter()
+            .any(|s| s.member_threshold != share.member_threshold)
+        {
+            return Err(Error::MismatchedParameters);
+        }
+        group.push(share);
+    }
+
+    let mut group_secrets = Vec::new();
+    for (group_index, members) in &groups {
+        let member_threshold = members[0].member_threshold;
+        if members.len() < member_threshold as usize {
+            continue;
+        }
+        let member_shares = members
+            .iter()
+            .take(member_threshold as usize)
+            .map(|s| (s.member_index, s.value.as_slice()))
+            .collect::<Vec<_>>();
+        group_secrets.push((
+            *group_index,
+            recover_secret(member_threshold, &member_shares)?,
+        ));
+        if group_secrets.len() == first.group_threshold as usize {
+            break;
+        }
+    }
+
+    if group_secrets.len() < first.group_threshold as usize {
+        // report the first incomplete group, if any
+        if let Some((group, members)) = groups
+            .iter()
+            .find(|(_, members)| members.len() < members[0].member_threshold as usize)
+        {
+            return Err(Error::InsufficientShares {
+                group: *group,
+                required: members[0].member_threshold,
+                provided: members.len() as u8,
+            });
+        }
+        return Err(Error::InsufficientGroups {
+            required: first.group_threshold,
+            provided: group_secrets.len() as u8,
+        });
+    }
+
+    let group_shares = group_secrets
+        .iter()
+        .map(|(x, value)| (*x, value.as_slice()))
+        .collect::<Vec<_>>();
+    let encrypted_secret = recover_secret(first.group_threshold, &group_shares)?;
+    let secret = feistel(
+        &encrypted_secret,
+        passphrase,
+        first.iteration_exponent,
+        first.identifier,
+        first.extendable,
+        true,
+    );
+    Ok(MasterSecret(secret))
+}
+
+/// The SLIP-0039 wordlist.
+const WORDLIST: [&str; 1024] = [
+    "academic", "acid", "acne", "acquire", "acrobat", "activity", "actress", "adapt", "adequate",
+    "adjust", "admit", "adorn", "adult", "advance", "advocate", "afraid", "again", "agency",
+    "agree", "aide", "aircraft", "airline", "airport", "ajar", "alarm", "album", "alcohol",
+    "alien", "alive", "alpha", "already", "alto", "aluminum", "always", "amazing", "ambition",
+    "amount", "amuse", "analysis", "anatomy", "ancestor", "ancient", "angel", "angry", "animal",
+    "answer", "antenna", "anxiety", "apart", "aquatic", "arcade", "arena", "argue", "armed",
+    "artist", "artwork", "aspect", "auction", "august", "aunt", "average", "aviation", "avoid",
+    "award", "away", "axis", "axle", "beam", "beard", "beaver", "become", "bedroom", "behavior",
+    "being", "believe", "belong", "benefit", "best", "beyond", "bike", "biology", "birthday",
+    "bishop", "black", "blanket", "blessing", "blimp", "blind", "blue", "body", "bolt", "boring",
+    "born", "both", "boundary", "bracelet", "branch", "brave", "breathe", "briefing", "broken",
+    "brother", "browser", "bucket", "budget", "building", "bulb", "bulge", "bumpy", "bundle",
+    "burden", "burning", "busy", "buyer", "cage", "calcium", "camera", "campus", "canyon",
+    "capacity", "capital", "capture", "carbon", "cards", "careful", "cargo", "carpet", "carve",
+    "category", "cause", "ceiling", "center", "ceramic", "champion", "change", "charity", "check",
+    "chemical", "chest", "chew", "chubby", "cinema", "civil", "class", "clay", "cleanup", "client",
+    "climate", "clinic", "clock", "clogs", "closet", "clothes", "club", "cluster", "coal",
+    "coastal", "coding", "column", "company", "corner", "costume", "counter", "course", "cover",
+    "cowboy", "cradle", "craft", "crazy", "credit", "cricket", "criminal", "crisis", "critical",
+    "crowd", "crucial", "crunch", "crush", "crystal", "cubic", "cultural", "curious", "curly",
+    "custody", "cylinder", "daisy", "damage", "dance", "darkness", "database", "daughter",
+    "deadline", "deal", "debris", "debut", "decent", "decision", "declare", "decorate", "decrease",
+    "deliver", "demand", "density", "deny", "depart", "depend", "depict", "deploy", "describe",
+    "desert", "desire", "desktop", "destroy", "detailed", "detect", "device", "devote", "diagnose",
+    "dictate", "diet", "dilemma", "diminish", "dining", "diploma", "disaster", "discuss",
+    "disease", "dish", "dismiss", "display", "distance", "dive", "divorce", "document", "domain",
+    "domestic", "dominant", "dough", "downtown", "dragon", "dramatic", "dream", "dress", "drift",
+    "drink", "drove", "drug", "dryer", "duckling", "duke", "duration", "dwarf", "dynamic", "early",
+    "earth", "easel", "easy", "echo", "eclipse", "ecology", "edge", "editor", "educate", "either",
+    "elbow", "elder", "election", "elegant", "element", "elephant", "elevator", "elite", "else",
+    "email", "emerald", "emission", "emperor", "emphasis", "employer", "empty", "ending",
+    "endless", "endorse", "enemy", "energy", "enforce", "engage", "enjoy", "enlarge", "entrance",
+    "envelope", "envy", "epidemic", "episode", "equation", "equip", "eraser", "erode", "escape",
+    "estate", "estimate", "evaluate", "evening", "evidence", "evil", "evoke", "exact", "example",
+    "exceed", "exchange", "exclude", "excuse", "execute", "exercise", "exhaust", "exotic",
+    "expand", "expect", "explain", "express", "extend", "extra", "eyebrow", "facility", "fact",
+    "failure", "faint", "fake", "false", "family", "famous", "fancy", "fangs", "fantasy", "fatal",
+    "fatigue", "favorite", "fawn", "fiber", "fiction", "filter", "finance", "findings", "finger",
+    "firefly", "firm", "fiscal", "fishing", "fitness", "flame", "flash", "flavor", "flea",
+    "flexible", "flip", "float", "floral", "fluff", "focus", "forbid", "force", "forecast",
+    "forget", "formal", "fortune", "forward", "founder", "fraction", "fragment", "frequent",
+    "freshman", "friar", "fridge", "friendly", "frost", "froth", "frozen", "fumes", "funding",
+    "furl", "fused", "galaxy", "game", "garbage", "garden", "garlic", "gasoline", "gather",
+    "general", "genius", "genre", "genuine", "geology", "gesture", "glad", "glance", "glasses",
+    "glen", "glimpse", "goat", "golden", "graduate", "grant", "grasp", "gravity", "gray",
+    "greatest", "grief", "grill", "grin", "grocery", "gross", "group", "grownup", "grumpy",
+    "guard", "guest", "guilt", "guitar", "gums", "hairy", "hamster", "hand", "hanger", "harvest",
+    "have", "havoc", "hawk", "hazard", "headset", "health", "hearing", "heat", "helpful", "herald",
+    "herd", "hesitate", "hobo", "holiday", "holy", "home", "hormone", "hospital", "hour", "huge",
+    "human", "humidity", "hunting", "husband", "hush", "husky", "hybrid", "idea", "identify",
+    "idle", "image", "impact", "imply", "improve", "impulse", "include", "income", "increase",
+    "index", "indicate", "industry", "infant", "inform", "inherit", "injury", "inmate", "insect",
+    "inside", "install", "intend", "intimate", "invasion", "involve", "iris", "island", "isolate",
+    "item", "ivory", "jacket", "jerky", "jewelry", "join", "judicial", "juice", "jump", "junction",
+    "junior", "junk", "jury", "justice", "kernel", "keyboard", "kidney", "kind", "kitchen",
+    "knife", "knit", "laden", "ladle", "ladybug", "lair", "lamp", "language", "large", "laser",
+    "laundry", "lawsuit", "leader", "leaf", "learn", "leaves", "lecture", "legal", "legend",
+    "legs", "lend", "length", "level", "liberty", "library", "license", "lift", "likely", "lilac",
+    "lily", "lips", "liquid", "listen", "literary", "living", "lizard", "loan", "lobe", "location",
+    "losing", "loud", "loyalty", "luck", "lunar", "lunch", "lungs", "luxury", "lying", "lyrics",
+    "machine", "magazine", "maiden", "mailman", "main", "makeup", "making", "mama", "manager",
+    "mandate", "mansion", "manual", "marathon", "march", "market", "marvel", "mason", "material",
+    "math", "maximum", "mayor", "meaning", "medal", "medical", "member", "memory", "mental",
+    "merchant", "merit", "method", "metric", "midst", "mild", "military", "mineral", "minister",
+    "miracle", "mixed", "mixture", "mobile", "modern", "modify", "moisture", "moment", "morning",
+    "mortgage", "mother", "mountain", "mouse", "move", "much", "mule", "multiple", "muscle",
+    "museum", "music", "mustang", "nail", "national", "necklace", "negative", "nervous", "network",
+    "news", "nuclear", "numb", "numerous", "nylon", "oasis", "obesity", "object", "observe",
+    "obtain", "ocean", "often", "olympic", "omit", "oral", "orange", "orbit", "order", "ordinary",
+    "organize", "ounce", "oven", "overall", "owner", "paces", "pacific", "package", "paid",
+    "painting", "pajamas", "pancake", "pants", "papa", "paper", "parcel", "parking", "party",
+    "patent", "patrol", "payment", "payroll", "peaceful", "peanut", "peasant", "pecan", "penalty",
+    "pencil", "percent", "perfect", "permit", "petition", "phantom", "pharmacy", "photo", "phrase",
+    "physics", "pickup", "picture", "piece", "pile", "pink", "pipeline", "pistol", "pitch",
+    "plains", "plan", "plastic", "platform", "playoff", "pleasure", "plot", "plunge", "practice",
+    "prayer", "preach", "predator", "pregnant", "premium", "prepare", "presence", "prevent",
+    "priest", "primary", "priority", "prisoner", "privacy", "prize", "problem", "process",
+    "profile", "program", "promise", "prospect", "provide", "prune", "public", "pulse", "pumps",
+    "punish", "puny", "pupal", "purchase", "purple", "python", "quantity", "quarter", "quick",
+    "quiet", "race", "racism", "radar", "railroad", "rainbow", "raisin", "random", "ranked",
+    "rapids", "raspy", "reaction", "realize", "rebound", "rebuild", "recall", "receiver",
+    "recover", "regret", "regular", "reject", "relate", "remember", "remind", "remove", "render",
+    "repair", "repeat", "replace", "require", "rescue", "research", "resident", "response",
+    "result", "retailer", "retreat", "reunion", "revenue", "review", "reward", "rhyme", "rhythm",
+    "rich", "rival", "river", "robin", "rocky", "romantic", "romp", "roster", "round", "royal",
+    "ruin", "ruler", "rumor", "sack", "safari", "salary", "salon", "salt", "satisfy", "satoshi",
+    "saver", "says", "scandal", "scared", "scatter", "scene", "scholar", "science", "scout",
+    "scramble", "screw", "script", "scroll", "seafood", "season", "secret", "security", "segment",
+    "senior", "shadow", "shaft", "shame", "shaped", "sharp", "shelter", "sheriff", "short",
+    "should", "shrimp", "sidewalk", "silent", "silver", "similar", "simple", "single", "sister",
+    "skin", "skunk", "slap", "slavery", "sled", "slice", "slim", "slow", "slush", "smart", "smear",
+    "smell", "smirk", "smith", "smoking", "smug", "snake", "snapshot", "sniff", "society",
+    "software", "soldier", "solution", "soul", "source", "space", "spark", "speak", "species",
+    "spelling", "spend", "spew", "spider", "spill", "spine", "spirit", "spit", "spray", "sprinkle",
+    "square", "squeeze", "stadium", "staff", "standard", "starting", "station", "stay", "steady",
+    "step", "stick", "stilt", "story", "strategy", "strike", "style", "subject", "submit", "sugar",
+    "suitable", "sunlight", "superior", "surface", "surprise", "survive", "sweater", "swimming",
+    "swing", "switch", "symbolic", "sympathy", "syndrome", "system", "tackle", "tactics",
+    "tadpole", "talent", "task", "taste", "taught", "taxi", "teacher", "teammate", "teaspoon",
+    "temple", "tenant", "tendency", "tension", "terminal", "testify", "texture", "thank", "that",
+    "theater", "theory", "therapy", "thorn", "threaten", "thumb", "thunder", "ticket", "tidy",
+    "timber", "timely", "ting", "tofu", "together", "tolerate", "total", "toxic", "tracks",
+    "traffic", "training", "transfer", "trash", "traveler", "treat", "trend", "trial", "tricycle",
+    "trip", "triumph", "trouble", "true", "trust", "twice", "twin", "type", "typical", "ugly",
+    "ultimate", "umbrella", "uncover", "undergo", "unfair", "unfold", "unhappy", "union",
+    "universe", "unkind", "unknown", "unusual", "unwrap", "upgrade", "upstairs", "username",
+    "usher", "usual", "valid", "valuable", "vampire", "vanish", "various", "vegan", "velvet",
+    "venture", "verdict", "verify", "very", "veteran", "vexed", "victim", "video", "view",
+    "vintage", "violence", "viral", "visitor", "visual", "vitamins", "vocal", "voice", "volume",
+    "voter", "voting", "walnut", "warmth", "warn", "watch", "wavy", "wealthy", "weapon", "webcam",
+    "welcome", "welfare", "western", "width", "wildlife", "window", "wine", "wireless", "wisdom",
+    "withdraw", "wits", "wolf", "woman", "work", "worthy", "wrap", "wrist", "writing", "wrote",
+    "year", "yelp", "yield", "yoga", "zero",
+];
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    use alloc::string::ToString;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const PASSPHRASE: &str = "TREZOR";
+
+    // Test vectors from https://github.com/trezor/python-shamir-mnemonic/blob/master/vectors.json
+    const VECTOR_1: &str = "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard";
+    const VECTOR_4: [&str; 2] = [
+        "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+        "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+    ];
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| alloc::format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn test_combine_shares_vectors() {
+        let secret = combine_shares(&[VECTOR_1], PASSPHRASE).unwrap();
+        assert_eq!(hex(secret.as_bytes()), "bb54aac4b89dc868ba37d9cc21b2cece");
+        let xprv: ExtendedKey<miniscript::Segwitv0> = secret.into_extended_key().unwrap();
+        assert_eq!(
+            xprv.into_xprv(bitcoin::NetworkKind::Main).unwrap().to_string(),
+            "xprv9s21ZrQH143K4QViKpwKCpS2zVbz8GrZgpEchMDg6KME9HZtjfL7iThE9w5muQA4YPHKN1u5VM1w8D4pvnjxa2BmpGMfXr7hnRrRHZ93awZ"
+        );
+
+        // 2 of 3 shares, in any order
+        let secret = combine_shares(&VECTOR_4, PASSPHRASE).unwrap();
+        assert_eq!(hex(secret.as_bytes()), "b43ceb7e57a0ea8766221624d01b0864");
+        let secret = combine_shares(&[VECTOR_4[1], VECTOR_4[0]], PASSPHRASE).unwrap();
+        assert_eq!(hex(secret.as_bytes()), "b43ceb7e57a0ea8766221624d01b0864");
+
+        // extendable shares
+        let secret = combine_shares(
+            &["testify swimming academic academic column loyalty smear include exotic bedroom exotic wrist lobe cover grief golden smart junior estimate learn"],
+            PASSPHRASE,
+        )
+        .unwrap();
+        assert_eq!(hex(secret.as_bytes()), "1679b4516e0ee5954351d288a838f45e");
+    }
+
+    #[test]
+    fn test_combine_shares_invalid() {
+        // invalid checksum
+        let invalid_checksum = "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney";
+        assert_eq!(
+            combine_shares(&[invalid_checksum], PASSPHRASE),
+            Err(Error::InvalidChecksum(0))
+        );
+        assert_eq!(
+            combine_shares(&[VECTOR_4[0], invalid_checksum], PASSPHRASE),
+            Err(Error::InvalidChecksum(1))
+        );
+        // invalid padding
+        assert_eq!(
+            combine_shares(
+                &["duckling enlarge academic academic email result length solution fridge kidney coal piece deal husband erode duke ajar music cargo fitness"],
+                PASSPHRASE
+            ),
+            Err(Error::InvalidShare(0))
+        );
+        // unknown word
+        assert_eq!(
+            combine_shares(&["duckling enlarge bitcoin"], PASSPHRASE),
+            Err(Error::InvalidWord("bitcoin".to_string()))
+        );
+        // too short
+        assert_eq!(
+            combine_shares(&["duckling enlarge academic academic"], PASSPHRASE),
+            Err(Error::InvalidShare(0))
+        );
+        // not enough member shares
+        assert_eq!(
+            combine_shares(&VECTOR_4[..1], PASSPHRASE),
+            Err(Error::InsufficientShares {
+                group: 0,
+                required: 2,
+                provided: 1
+            })
+        );
+        // shares of different sets
+        assert_eq!(
+            combine_shares(&[VECTOR_4[0], VECTOR_1], PASSPHRASE),
+            Err(Error::MismatchedParameters)
+        );
+        assert_eq!(
+            combine_shares(&[], PASSPHRASE),
+            Err(Error::InsufficientGroups {
+                required: 1,
+                provided: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_generate_and_combine_groups() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let master_secret = [0x42; 32];
+        let groups = generate_shares_with_aux_rand(
+            &master_secret,
+            "",
+            2,
+            &[(1, 1), (2, 3), (3, 5)],
+            &mut rng,
+        )
+        .unwrap();
+        assert_eq!(
+            groups.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![1, 3, 5]
+        );
+        // 256 bits master secrets are encoded in 33 words
+        assert!(groups
+            .iter()
+            .flatten()
+            .all(|share| share.split(' ').count() == 33));
+
+        let combine = |shares: &[&String]| {
+            let shares = shares.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+            combine_shares(&shares, "")
+        };
+        for shares in [
+            vec![&groups[0][0], &groups[1][0], &groups[1][1]],
+            vec![
+                &groups[1][2],
+                &groups[2][4],
+                &groups[1][0],
+                &groups[2][0],
+                &groups[2][2],
+            ],
+            vec![&groups[2][1], &groups[0][0], &groups[2][2], &groups[2][3]],
+        ] {
+            assert_eq!(combine(&shares).unwrap().as_bytes(), master_secret);
+        }
+
+        // the same seeded rng generates the same shares
+        let mut rng = StdRng::seed_from_u64(42);
+        assert_eq!(
+            generate_shares_with_aux_rand(
+                &master_secret,
+                "",
+                2,
+                &[(1, 1), (2, 3), (3, 5)],
+                &mut rng
+            )
+            .unwrap(),
+            groups
+        );
+
+        // a different passphrase recovers a different master secret
+        let shares = [
+            groups[0][0].as_str(),
+            groups[1][0].as_str(),
+            groups[1][1].as_str(),
+        ];
+        assert_ne!(
+            combine_shares(&shares, PASSPHRASE).unwrap().as_bytes(),
+            master_secret
+        );
+
+        assert_eq!(
+            combine(&[&groups[0][0], &groups[1][0]]),
+            Err(Error::InsufficientShares {
+                group: 1,
+                required: 2,
+                provided: 1
+            })
+        );
+        assert_eq!(
+            combine(&[&groups[0][0]]),
+            Err(Error::InsufficientGroups {
+                required: 2,
+                provided: 1
+            })
+        );
+
+        // tampering with a share is detected by the digest
+        let other =
+            generate_shares_with_aux_rand(&[0x43; 32], "", 2, &[(1, 1), (2, 3), (3, 5)], &mut rng)
+                .unwrap();
+        let mut tampered = other[1][1].split(' ').collect::<Vec<_>>();
+        tampered[..2].copy_from_slice(&groups[1][1].split(' ').collect::<Vec<_>>()[..2]);
+        let words = tampered
+            .iter()
+            .map(|w| WORDLIST.binary_search(w).unwrap() as u16)
+            .collect::<Vec<_>>();
+        let checksum =
+            rs1024_create_checksum(customization(true), &words[..words.len() - CHECKSUM_WORDS]);
+        let checksum = checksum.map(|w| WORDLIST[w as usize]);
+        let len = tampered.len();
+        tampered[len - CHECKSUM_WORDS..].copy_from_slice(&checksum);
+        let tampered = tampered.join(" ");
+        assert_eq!(
+            combine_shares(&[&groups[1][0], &tampered, &groups[0][0]], ""),
+            Err(Error::InvalidDigest)
+        );
+    }
+
+    #[test]
+    fn test_generate_shares_invalid() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut generate = |secret: &[u8], passphrase, threshold, groups: &[(u8, u8)]| {
+            generate_shares_with_aux_rand(secret, passphrase, threshold, groups, &mut rng)
+        };
+        assert_eq!(
+            generate(&[0; 15], "", 1, &[(1, 1)]),
+            Err(Error::InvalidMasterSecret)
+        );
+        assert_eq!(
+            generate(&[0; 17], "", 1, &[(1, 1)]),
+            Err(Error::InvalidMasterSecret)
+        );
+        assert_eq!(
+            generate(&[0; 16], "caf\u{e9}", 1, &[(1, 1)]),
+            Err(Error::InvalidPassphrase)
+        );
+        assert_eq!(generate(&[0; 16], "", 1, &[]), Err(Error::InvalidGroups));
+        assert_eq!(
+            generate(&[0; 16], "", 2, &[(1, 1)]),
+            Err(Error::InvalidGroups)
+        );
+        assert_eq!(
+            generate(&[0; 16], "", 1, &[(3, 2)]),
+            Err(Error::InvalidGroups)
+        );
+        assert_eq!(
+            generate(&[0; 16], "", 1, &[(1, 2)]),
+            Err(Error::InvalidGroups)
+        );
+        assert_eq!(
+            generate(&[0; 16], "", 1, &[(2, 17)]),
+            Err(Error::InvalidGroups)
+        );
+    }
+
+    #[test]
+    fn test_master_secret_descriptor() {
+        let secret = combine_shares(&[VECTOR_1], PASSPHRASE).unwrap();
+        let (desc, keys, network_kinds) = crate::descriptor!(wpkh((
+            secret,
+            "m/84h/0h/0h/0".parse::<bip32::DerivationPath>().unwrap()
+        )))
+        .unwrap();
+        let xprv: bip32::Xpriv = "xprv9s21ZrQH143K4QViKpwKCpS2zVbz8GrZgpEchMDg6KME9HZtjfL7iThE9w5muQA4YPHKN1u5VM1w8D4pvnjxa2BmpGMfXr7hnRrRHZ93awZ".parse().unwrap();
+        let fingerprint = xprv.fingerprint(&crate::wallet::utils::SecpCtx::new());
+        assert!(desc
+            .to_string()
+            .starts_with(&alloc::format!("wpkh([{fingerprint}/84'/0'/0']xpub")));
+        assert_eq!(keys.len(), 1);
+        assert_eq!(network_kinds, any_network_kind());
+    }
+}
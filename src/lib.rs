@@ -48,6 +48,8 @@ pub use descriptor::template;
 pub use descriptor::HdKeyPaths;
 pub use signer;
 pub use signer::SignOptions;
+#[cfg(feature = "async-sqlite")]
+pub use tokio_rusqlite;
 pub use tx_builder::*;
 pub use types::*;
 pub use wallet::*;
@@ -9,7 +9,7 @@ use crate::{
         keychain_txout::{self},
         local_chain, tx_graph, ConfirmationBlockTime, DescriptorExt, Merge, SpkIterator,
     },
-    locked_outpoints,
+    labels, locked_outpoints, metadata,
     miniscript::descriptor::{Descriptor, DescriptorPublicKey},
     ChangeSet, WalletPersister,
 };
@@ -164,6 +164,14 @@ where
         outpoints: [(outpoint, true)].into(),
     };
 
+    let labels_changeset = labels::ChangeSet {
+        outputs: [(outpoint, Some("first label".into()))].into(),
+    };
+
+    let metadata_changeset = metadata::ChangeSet {
+        entries: [("app/key".into(), Some(b"first".to_vec()))].into(),
+    };
+
     let mut changeset = ChangeSet {
         descriptor: Some(descriptor.clone()),
         change_descriptor: Some(change_descriptor.clone()),
@@ -172,6 +180,13 @@ where
         tx_graph: tx_graph_changeset,
         indexer: keychain_txout_changeset,
         locked_outpoints: locked_outpoints_changeset,
+        labels: labels_changeset,
+        metadata: metadata_changeset,
+        spk_cache_digests: [
+            (descriptor.descriptor_id(), hash!("spks")),
+            (change_descriptor.descriptor_id(), hash!("change_spks")),
+        ]
+        .into(),
     };
 
     // persist and load
@@ -223,6 +238,14 @@ where
         outpoints: [(outpoint, true)].into(),
     };
 
+    let labels_changeset = labels::ChangeSet {
+        outputs: [(outpoint, Some("second label".into()))].into(),
+    };
+
+    let metadata_changeset = metadata::ChangeSet {
+        entries: [("app/key".into(), Some(b"second".to_vec()))].into(),
+    };
+
     let changeset_new = ChangeSet {
         descriptor: None,
         change_descriptor: None,
@@ -231,6 +254,9 @@ where
         tx_graph: tx_graph_changeset,
         indexer: keychain_txout_changeset,
         locked_outpoints: locked_outpoints_changeset,
+        labels: labels_changeset,
+        metadata: metadata_changeset,
+        spk_cache_digests: [(descriptor.descriptor_id(), hash!("more_spks"))].into(),
     };
 
     // persist, load and check if same as merged
@@ -12,10 +12,21 @@
 //! Additional functions on the `rust-bitcoin` `Psbt` structure.
 
 use alloc::vec::Vec;
+use bitcoin::bip32::Fingerprint;
+use bitcoin::psbt::{Input, PsbtSighashType};
 use bitcoin::Amount;
 use bitcoin::FeeRate;
 use bitcoin::Psbt;
 use bitcoin::TxOut;
+use bitcoin::{ScriptBuf, TxIn, Weight, Witness};
+use miniscript::psbt::PsbtInputExt;
+
+use crate::descriptor::ExtendedDescriptor;
+use crate::types::KeychainKind;
+use crate::Wallet;
+
+pub mod v2;
+pub use v2::{psbt_v0_to_v2, psbt_v2_to_v0, PsbtV2};
 
 // TODO upstream the functions here to `rust-bitcoin`?
 
@@ -34,6 +45,149 @@ pub trait PsbtUtils {
     /// transaction.
     /// If the PSBT is missing a TxOut for an input returns None.
     fn fee_rate(&self) -> Option<FeeRate>;
+
+    /// Report the signatures present on every input of the PSBT, e.g. to show how many cosigners
+    /// of a multisig have signed it.
+    ///
+    /// Signatures are attributed to cosigners by the fingerprint of the key origin of their
+    /// public key, from the `bip32_derivation` and `tap_key_origins` fields filled in from the
+    /// descriptor origins.
+    fn signature_status(&self) -> Vec<InputSigStatus>;
+}
+
+/// Functions on the inputs of a [`Psbt`].
+///
+/// This trait is sealed, it's only implemented for [`Psbt`] so that functions can be added to it
+/// without breaking downstream implementations.
+pub trait PsbtInputUtils: sealed::Sealed {
+    /// The weight needed to satisfy the input at `input_index` with `descriptor`, to be used as
+    /// the `satisfaction_weight` of [`TxBuilder::add_foreign_utxo`].
+    ///
+    /// Returns `None` if the input doesn't exist, if `descriptor` has no wildcard and doesn't
+    /// match the output spent by the input, or if it can't be satisfied.
+    ///
+    /// [`TxBuilder::add_foreign_utxo`]: crate::TxBuilder::add_foreign_utxo
+    fn input_satisfaction_weight(
+        &self,
+        input_index: usize,
+        descriptor: &ExtendedDescriptor,
+    ) -> Option<Weight>;
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for bitcoin::Psbt {}
+}
+
+/// Diagnostics of a PSBT input, returned by [`Wallet::analyze_psbt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputAnalysis {
+    /// The keychain and derivation index of the spent output, `None` if it isn't a wallet output
+    /// or the spent output is unknown.
+    pub derivation: Option<(KeychainKind, u32)>,
+    /// Whether the `witness_utxo` field is present.
+    pub has_witness_utxo: bool,
+    /// Whether the `non_witness_utxo` field is present and matches the spent outpoint.
+    pub has_non_witness_utxo: bool,
+    /// State of the BIP32 derivations of the input's keys.
+    pub derivations: DerivationStatus,
+    /// The sighash type requested by the input.
+    pub sighash_type: Option<PsbtSighashType>,
+    /// Whether the input is already finalized.
+    pub is_final: bool,
+    /// The weight of the input once finalized, estimated for wallet inputs that aren't finalized
+    /// yet, `None` for foreign inputs that aren't finalized.
+    pub weight: Option<Weight>,
+}
+
+impl InputAnalysis {
+    /// Whether the input spends a wallet output.
+    pub fn is_mine(&self) -> bool {
+        self.derivation.is_some()
+    }
+
+    /// Whether the spent output is missing from the input.
+    pub fn is_missing_utxo(&self) -> bool {
+        !self.has_witness_utxo && !self.has_non_witness_utxo
+    }
+}
+
+/// State of the BIP32 derivations of a PSBT input, see [`InputAnalysis::derivations`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DerivationStatus {
+    /// The input has no BIP32 derivation.
+    Missing,
+    /// The input has BIP32 derivations, but it doesn't spend a wallet output so they can't be
+    /// verified.
+    Unverified,
+    /// Every BIP32 derivation of the input matches the wallet descriptor.
+    Valid,
+    /// Some BIP32 derivations don't match the wallet descriptor.
+    Invalid,
+}
+
+/// Signatures present on a PSBT input, returned by [`PsbtUtils::signature_status`].
+///
+/// Finalizing an input removes its signatures, which are then part of the final `scriptSig` and
+/// witness, so only [`is_final`](Self::is_final) is set for a finalized input.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InputSigStatus {
+    /// Number of ECDSA signatures, in the `partial_sigs` field.
+    pub partial_sigs: usize,
+    /// Number of taproot script path signatures, in the `tap_script_sigs` field.
+    pub tap_script_sigs: usize,
+    /// Whether the input has a taproot key path signature.
+    pub has_tap_key_sig: bool,
+    /// Fingerprints of the cosigners which signed the input, sorted and deduplicated.
+    ///
+    /// A signature whose public key has no key origin is counted, but can't be attributed.
+    pub signed: Vec<Fingerprint>,
+    /// Fingerprints of every key origin of the input, i.e. of the cosigners which can sign it,
+    /// sorted and deduplicated.
+    pub cosigners: Vec<Fingerprint>,
+    /// Whether the input is already finalized.
+    pub is_final: bool,
+}
+
+impl InputSigStatus {
+    /// Total number of signatures on the input.
+    pub fn signatures(&self) -> usize {
+        self.partial_sigs + self.tap_script_sigs + usize::from(self.has_tap_key_sig)
+    }
+}
+
+/// Weight of an input finalized with the given `scriptSig` and witness.
+fn final_input_weight(script_sig: Option<&ScriptBuf>, witness: Option<&Witness>) -> Weight {
+    let txin = TxIn {
+        script_sig: script_sig.cloned().unwrap_or_default(),
+        witness: witness.cloned().unwrap_or_default(),
+        ..Default::default()
+    };
+    txin.segwit_weight()
+}
+
+fn derivation_status(input: &Input, expected: Option<&Input>) -> DerivationStatus {
+    if input.bip32_derivation.is_empty() && input.tap_key_origins.is_empty() {
+        return DerivationStatus::Missing;
+    }
+    let expected = match expected {
+        Some(expected) => expected,
+        None => return DerivationStatus::Unverified,
+    };
+    let valid = input
+        .bip32_derivation
+        .iter()
+        .all(|(pk, source)| expected.bip32_derivation.get(pk) == Some(source))
+        && input
+            .tap_key_origins
+            .iter()
+            .all(|(pk, origin)| expected.tap_key_origins.get(pk) == Some(origin));
+    if valid {
+        DerivationStatus::Valid
+    } else {
+        DerivationStatus::Invalid
+    }
 }
 
 impl PsbtUtils for Psbt {
@@ -72,12 +226,139 @@ impl PsbtUtils for Psbt {
         let weight = self.clone().extract_tx().ok()?.weight();
         fee_amount.map(|fee| fee / weight)
     }
+
+    fn signature_status(&self) -> Vec<InputSigStatus> {
+        self.inputs
+            .iter()
+            .map(|input| {
+                let ecdsa_signers = input.partial_sigs.keys().filter_map(|pk| {
+                    let (fingerprint, _) = input.bip32_derivation.get(&pk.inner)?;
+                    Some(*fingerprint)
+                });
+                let tap_script_signers = input.tap_script_sigs.keys().filter_map(|(pk, _)| {
+                    let (_, (fingerprint, _)) = input.tap_key_origins.get(pk)?;
+                    Some(*fingerprint)
+                });
+                let tap_key_signer = input
+                    .tap_key_sig
+                    .and(input.tap_internal_key)
+                    .and_then(|pk| input.tap_key_origins.get(&pk))
+                    .map(|(_, (fingerprint, _))| *fingerprint);
+                let mut signed: Vec<Fingerprint> = ecdsa_signers
+                    .chain(tap_script_signers)
+                    .chain(tap_key_signer)
+                    .collect();
+                signed.sort();
+                signed.dedup();
+
+                let mut cosigners: Vec<Fingerprint> = input
+                    .bip32_derivation
+                    .values()
+                    .map(|(fingerprint, _)| *fingerprint)
+                    .chain(
+                        input
+                            .tap_key_origins
+                            .values()
+                            .map(|(_, (fingerprint, _))| *fingerprint),
+                    )
+                    .collect();
+                cosigners.sort();
+                cosigners.dedup();
+
+                InputSigStatus {
+                    partial_sigs: input.partial_sigs.len(),
+                    tap_script_sigs: input.tap_script_sigs.len(),
+                    has_tap_key_sig: input.tap_key_sig.is_some(),
+                    signed,
+                    cosigners,
+                    is_final: input.final_script_sig.is_some()
+                        || input.final_script_witness.is_some(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl PsbtInputUtils for Psbt {
+    fn input_satisfaction_weight(
+        &self,
+        input_index: usize,
+        descriptor: &ExtendedDescriptor,
+    ) -> Option<Weight> {
+        self.inputs.get(input_index)?;
+        if !descriptor.has_wildcard() {
+            if let Some(utxo) = self.get_utxo_for(input_index) {
+                let desc = descriptor.at_derivation_index(0).ok()?;
+                if desc.script_pubkey() != utxo.script_pubkey {
+                    return None;
+                }
+            }
+        }
+        descriptor.max_weight_to_satisfy().ok()
+    }
+}
+
+/// Diagnose every input of `psbt` against `wallet`, see [`Wallet::analyze_psbt`].
+pub(crate) fn analyze(psbt: &Psbt, wallet: &Wallet) -> Vec<InputAnalysis> {
+    psbt.inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            let outpoint = psbt
+                .unsigned_tx
+                .input
+                .get(i)
+                .map(|txin| txin.previous_output);
+            let has_non_witness_utxo = input
+                .non_witness_utxo
+                .as_ref()
+                .zip(outpoint)
+                .is_some_and(|(tx, outpoint)| tx.compute_txid() == outpoint.txid);
+            let derivation = psbt
+                .get_utxo_for(i)
+                .and_then(|utxo| wallet.derivation_of_spk(utxo.script_pubkey));
+
+            // What the wallet would fill in for its own inputs.
+            let expected = derivation.map(|(keychain, index)| {
+                let desc = wallet
+                    .public_descriptor(keychain)
+                    .at_derivation_index(index)
+                    .expect("child can't be hardened");
+                let mut expected = Input::default();
+                let _ = expected.update_with_descriptor_unchecked(&desc);
+                expected
+            });
+
+            let is_final = input.final_script_sig.is_some() || input.final_script_witness.is_some();
+            let weight = if is_final {
+                Some(final_input_weight(
+                    input.final_script_sig.as_ref(),
+                    input.final_script_witness.as_ref(),
+                ))
+            } else {
+                derivation.and_then(|(keychain, _)| {
+                    let satisfaction_weight =
+                        psbt.input_satisfaction_weight(i, wallet.public_descriptor(keychain))?;
+                    Some(TxIn::default().segwit_weight() + satisfaction_weight)
+                })
+            };
+
+            InputAnalysis {
+                derivation,
+                has_witness_utxo: input.witness_utxo.is_some(),
+                has_non_witness_utxo,
+                derivations: derivation_status(input, expected.as_ref()),
+                sighash_type: input.sighash_type,
+                is_final,
+                weight,
+            }
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bitcoin::psbt::Input;
     use bitcoin::{
         absolute, transaction, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
         Witness,
@@ -154,4 +435,173 @@ mod tests {
         // Must return None — vout out of bounds, no panic
         assert_eq!(psbt.get_utxo_for(0), None);
     }
+
+    fn wallet_psbt() -> (Wallet, Psbt) {
+        use crate::test_utils::*;
+
+        let (mut wallet, _) = get_funded_wallet_wpkh();
+        let addr = wallet.peek_address(KeychainKind::External, 5);
+        let mut builder = wallet.build_tx();
+        builder.add_recipient(addr.script_pubkey(), Amount::from_sat(10_000));
+        let psbt = builder.finish().unwrap();
+        (wallet, psbt)
+    }
+
+    #[test]
+    fn analyze_wallet_input() {
+        use crate::SignOptions;
+        use bitcoin::EcdsaSighashType;
+
+        let (wallet, mut psbt) = wallet_psbt();
+        let analysis = wallet.analyze_psbt(&psbt);
+        assert_eq!(analysis.len(), 1);
+        let input = &analysis[0];
+        assert!(input.is_mine());
+        assert_eq!(
+            input.derivation.map(|(k, _)| k),
+            Some(KeychainKind::External)
+        );
+        assert!(input.has_witness_utxo && input.has_non_witness_utxo);
+        assert_eq!(input.derivations, DerivationStatus::Valid);
+        assert_eq!(input.sighash_type, None);
+        assert!(!input.is_final);
+        let satisfaction_weight = psbt
+            .input_satisfaction_weight(0, wallet.public_descriptor(KeychainKind::External))
+            .unwrap();
+        let estimated = TxIn::default().segwit_weight() + satisfaction_weight;
+        assert_eq!(input.weight, Some(estimated));
+
+        psbt.inputs[0].sighash_type = Some(EcdsaSighashType::All.into());
+        assert_eq!(
+            wallet.analyze_psbt(&psbt)[0].sighash_type,
+            Some(EcdsaSighashType::All.into())
+        );
+
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        let input = &wallet.analyze_psbt(&psbt)[0];
+        assert!(input.is_final);
+        let weight = input.weight.unwrap();
+        assert!(weight <= estimated);
+        let tx = psbt.extract_tx().unwrap();
+        assert_eq!(weight, tx.input[0].segwit_weight());
+    }
+
+    #[test]
+    fn analyze_broken_inputs() {
+        let (wallet, psbt) = wallet_psbt();
+
+        // Missing previous outputs, the input can't be recognized
+        let mut broken = psbt.clone();
+        broken.inputs[0].witness_utxo = None;
+        broken.inputs[0].non_witness_utxo = None;
+        let input = &wallet.analyze_psbt(&broken)[0];
+        assert!(input.is_missing_utxo());
+        assert!(!input.is_mine());
+        assert_eq!(input.derivations, DerivationStatus::Unverified);
+        assert_eq!(input.weight, None);
+
+        // `non_witness_utxo` for another transaction
+        let mut broken = psbt.clone();
+        let mut other_tx = psbt.inputs[0].non_witness_utxo.clone().unwrap();
+        other_tx.lock_time = absolute::LockTime::from_consensus(1);
+        broken.inputs[0].non_witness_utxo = Some(other_tx);
+        let input = &wallet.analyze_psbt(&broken)[0];
+        assert!(input.has_witness_utxo);
+        assert!(!input.has_non_witness_utxo);
+        assert!(input.is_mine());
+
+        // Missing derivations
+        let mut broken = psbt.clone();
+        broken.inputs[0].bip32_derivation.clear();
+        let input = &wallet.analyze_psbt(&broken)[0];
+        assert_eq!(input.derivations, DerivationStatus::Missing);
+
+        // Derivation with a wrong path
+        let mut broken = psbt.clone();
+        let (_, (_, path)) = broken.inputs[0].bip32_derivation.iter_mut().next().unwrap();
+        *path = path.child(bitcoin::bip32::ChildNumber::Normal { index: 1 });
+        let input = &wallet.analyze_psbt(&broken)[0];
+        assert_eq!(input.derivations, DerivationStatus::Invalid);
+    }
+
+    #[test]
+    fn signature_status() {
+        use crate::test_utils::*;
+        use crate::SignOptions;
+
+        let (wallet, mut psbt) = wallet_psbt();
+        let (fingerprint, _) = *psbt.inputs[0].bip32_derivation.values().next().unwrap();
+        let status = psbt.signature_status();
+        assert_eq!(status.len(), 1);
+        assert_eq!(
+            status[0],
+            InputSigStatus {
+                cosigners: vec![fingerprint],
+                ..Default::default()
+            }
+        );
+
+        let sign_options = SignOptions {
+            try_finalize: false,
+            ..Default::default()
+        };
+        assert!(!wallet.sign(&mut psbt, sign_options).unwrap());
+        let status = &psbt.signature_status()[0];
+        assert_eq!(status.partial_sigs, 1);
+        assert_eq!(status.signatures(), 1);
+        assert_eq!(status.signed, vec![fingerprint]);
+        assert!(!status.is_final);
+
+        // A signature without key origin isn't attributed
+        let mut unknown = psbt.clone();
+        unknown.inputs[0].bip32_derivation.clear();
+        let status = &unknown.signature_status()[0];
+        assert_eq!(status.signatures(), 1);
+        assert!(status.signed.is_empty() && status.cosigners.is_empty());
+
+        assert!(wallet
+            .finalize_psbt(&mut psbt, SignOptions::default())
+            .unwrap());
+        let status = &psbt.signature_status()[0];
+        assert!(status.is_final);
+        assert_eq!(status.signatures(), 0);
+
+        // Taproot key path
+        let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig_xprv());
+        let addr = wallet.peek_address(KeychainKind::External, 5);
+        let mut builder = wallet.build_tx();
+        builder.add_recipient(addr.script_pubkey(), Amount::from_sat(10_000));
+        let mut psbt = builder.finish().unwrap();
+        let sign_options = SignOptions {
+            try_finalize: false,
+            ..Default::default()
+        };
+        assert!(!wallet.sign(&mut psbt, sign_options).unwrap());
+        let status = &psbt.signature_status()[0];
+        assert!(status.has_tap_key_sig);
+        assert_eq!(status.signatures(), 1);
+        assert_eq!(status.signed.len(), 1);
+        assert_eq!(status.signed, status.cosigners);
+    }
+
+    #[test]
+    fn input_satisfaction_weight_checks_descriptor() {
+        use crate::descriptor::IntoWalletDescriptor;
+        use bitcoin::NetworkKind;
+
+        let (wallet, psbt) = wallet_psbt();
+        let secp = wallet.secp_ctx();
+        assert!(psbt
+            .input_satisfaction_weight(0, wallet.public_descriptor(KeychainKind::External))
+            .is_some());
+        assert!(psbt
+            .input_satisfaction_weight(1, wallet.public_descriptor(KeychainKind::External))
+            .is_none());
+
+        // A descriptor without wildcard must match the spent output
+        let (other, _) = "wpkh(02e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c)"
+            .into_wallet_descriptor(secp, NetworkKind::Test)
+            .unwrap();
+        assert!(psbt.input_satisfaction_weight(0, &other).is_none());
+    }
 }
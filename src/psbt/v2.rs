@@ -0,0 +1,753 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Conversion between version 0 ([BIP174]) and version 2 ([BIP370]) PSBTs.
+//!
+//! The rust-bitcoin [`Psbt`] can only represent version 0 PSBTs, where the unsigned transaction
+//! is stored in the global map. Version 2 PSBTs store the fields of the transaction in the
+//! per-input and per-output maps instead. [`PsbtV2`] holds a version 2 PSBT as a [`Psbt`] and
+//! only converts it when it is parsed or serialized, so it can be passed to [`Wallet::sign`] and
+//! [`Wallet::finalize_psbt`] as is. [`psbt_v2_to_v0`] and [`psbt_v0_to_v2`] do the conversions.
+//!
+//! The `PSBT_GLOBAL_TX_MODIFIABLE`, `PSBT_IN_REQUIRED_TIME_LOCKTIME` and
+//! `PSBT_IN_REQUIRED_HEIGHT_LOCKTIME` fields have no version 0 representation: [`PsbtV2`] keeps
+//! them aside and serializes them again, while [`psbt_v2_to_v0`] rejects PSBTs with them.
+//!
+//! [BIP174]: https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki
+//! [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+//! [`Wallet::sign`]: crate::Wallet::sign
+//! [`Wallet::finalize_psbt`]: crate::Wallet::finalize_psbt
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::str::FromStr;
+
+use bitcoin::base64::prelude::{Engine as _, BASE64_STANDARD};
+use bitcoin::consensus::encode::{self, deserialize_partial, VarInt};
+use bitcoin::{absolute, psbt, transaction, Amount, OutPoint, Psbt, ScriptBuf, Sequence};
+use bitcoin::{Transaction, TxIn, TxOut, Txid, Witness};
+
+use crate::collections::BTreeMap;
+
+const MAGIC: &[u8] = b"psbt\xff";
+
+const GLOBAL_UNSIGNED_TX: u64 = 0x00;
+const GLOBAL_TX_VERSION: u64 = 0x02;
+const GLOBAL_FALLBACK_LOCKTIME: u64 = 0x03;
+const GLOBAL_INPUT_COUNT: u64 = 0x04;
+const GLOBAL_OUTPUT_COUNT: u64 = 0x05;
+const GLOBAL_TX_MODIFIABLE: u64 = 0x06;
+const GLOBAL_VERSION: u64 = 0xFB;
+
+const IN_PREVIOUS_TXID: u64 = 0x0e;
+const IN_OUTPUT_INDEX: u64 = 0x0f;
+const IN_SEQUENCE: u64 = 0x10;
+const IN_REQUIRED_TIME_LOCKTIME: u64 = 0x11;
+const IN_REQUIRED_HEIGHT_LOCKTIME: u64 = 0x12;
+
+const OUT_AMOUNT: u64 = 0x03;
+const OUT_SCRIPT: u64 = 0x04;
+
+/// Errors returned by the conversions between version 0 and version 2 PSBTs.
+#[derive(Debug)]
+pub enum PsbtV2Error {
+    /// The data is not a serialized PSBT.
+    Malformed,
+    /// The PSBT version is not `2`.
+    UnsupportedVersion(u32),
+    /// A field required by BIP370 is missing.
+    MissingField(&'static str),
+    /// A field has an invalid value, or is not allowed in a version 2 PSBT.
+    InvalidField(&'static str),
+    /// The number of input and output maps doesn't match the global input and output counts, or
+    /// the inputs and outputs of the unsigned transaction.
+    MapCount,
+    /// The inputs require both a height based and a time based lock time.
+    LockTimeConflict,
+    /// The converted version 0 PSBT is invalid.
+    Psbt(psbt::Error),
+    /// A field of the version 2 PSBT can't be represented in a version 0 PSBT, use [`PsbtV2`] to
+    /// keep it.
+    Unrepresentable(&'static str),
+}
+
+impl fmt::Display for PsbtV2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "Malformed PSBT"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Unsupported PSBT version `{version}`, expected `2`")
+            }
+            Self::MissingField(field) => write!(f, "Missing required field {field}"),
+            Self::InvalidField(field) => write!(f, "Invalid field {field}"),
+            Self::MapCount => write!(
+                f,
+                "The number of input and output maps doesn't match the number of inputs and outputs"
+            ),
+            Self::LockTimeConflict => write!(
+                f,
+                "The inputs require both a height based and a time based lock time"
+            ),
+            Self::Psbt(err) => write!(f, "PSBT error: {err}"),
+            Self::Unrepresentable(field) => {
+                write!(f, "Field {field} can't be represented in a version 0 PSBT")
+            }
+        }
+    }
+}
+
+impl core::error::Error for PsbtV2Error {}
+
+impl From<psbt::Error> for PsbtV2Error {
+    fn from(err: psbt::Error) -> Self {
+        PsbtV2Error::Psbt(err)
+    }
+}
+
+/// A raw key-value map, keys include the key type.
+type Map = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The fields of a version 2 PSBT that can't be represented in a version 0 PSBT.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct V2Fields {
+    /// The value of `PSBT_GLOBAL_TX_MODIFIABLE`.
+    tx_modifiable: Option<Vec<u8>>,
+    /// The value of `PSBT_GLOBAL_FALLBACK_LOCKTIME` if the lock time is required by the inputs
+    /// instead.
+    fallback_locktime: Option<Vec<u8>>,
+    /// The `PSBT_IN_REQUIRED_TIME_LOCKTIME` and `PSBT_IN_REQUIRED_HEIGHT_LOCKTIME` entries of the
+    /// inputs, by previous output.
+    required_locktimes: BTreeMap<OutPoint, Map>,
+}
+
+fn key_type(key: &[u8]) -> Option<u64> {
+    deserialize_partial::<VarInt>(key).ok().map(|(t, _)| t.0)
+}
+
+fn get(map: &Map, ty: u64) -> Option<&[u8]> {
+    map.iter()
+        .find(|(k, _)| k.len() == 1 && key_type(k) == Some(ty))
+        .map(|(_, v)| v.as_slice())
+}
+
+fn insert(map: &mut Map, ty: u64, value: Vec<u8>) {
+    map.push((encode::serialize(&VarInt(ty)), value));
+}
+
+fn remove(map: &mut Map, types: &[u64]) {
+    take(map, types);
+}
+
+/// Remove the entries of `types` from `map` and return them.
+fn take(map: &mut Map, types: &[u64]) -> Map {
+    let (taken, kept) = core::mem::take(map)
+        .into_iter()
+        .partition(|(k, _)| key_type(k).is_some_and(|t| types.contains(&t)));
+    *map = kept;
+    taken
+}
+
+fn read_bytes<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8], PsbtV2Error> {
+    let (len, consumed) =
+        deserialize_partial::<VarInt>(bytes).map_err(|_| PsbtV2Error::Malformed)?;
+    let rest = &bytes[consumed..];
+    let len = usize::try_from(len.0).map_err(|_| PsbtV2Error::Malformed)?;
+    if rest.len() < len {
+        return Err(PsbtV2Error::Malformed);
+    }
+    let (data, rest) = rest.split_at(len);
+    *bytes = rest;
+    Ok(data)
+}
+
+/// Split a serialized PSBT in its key-value maps, the first one being the global map.
+fn parse_maps(mut bytes: &[u8]) -> Result<Vec<Map>, PsbtV2Error> {
+    bytes = bytes.strip_prefix(MAGIC).ok_or(PsbtV2Error::Malformed)?;
+    let mut maps = Vec::new();
+    while !bytes.is_empty() {
+        let mut map = Map::new();
+        loop {
+            let key = read_bytes(&mut bytes)?;
+            if key.is_empty() {
+                break;
+            }
+            if map.iter().any(|(k, _)| k == key) {
+                return Err(PsbtV2Error::Malformed);
+            }
+            let value = read_bytes(&mut bytes)?;
+            map.push((key.to_vec(), value.to_vec()));
+        }
+        maps.push(map);
+    }
+    Ok(maps)
+}
+
+fn serialize_maps(maps: Vec<Map>) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    for mut map in maps {
+        map.sort();
+        for (key, value) in map {
+            bytes.extend(encode::serialize(&VarInt(key.len() as u64)));
+            bytes.extend(key);
+            bytes.extend(encode::serialize(&VarInt(value.len() as u64)));
+            bytes.extend(value);
+        }
+        bytes.push(0x00);
+    }
+    bytes
+}
+
+fn decode<T: encode::Decodable>(value: &[u8], field: &'static str) -> Result<T, PsbtV2Error> {
+    encode::deserialize(value).map_err(|_| PsbtV2Error::InvalidField(field))
+}
+
+fn decode_count(value: &[u8], field: &'static str) -> Result<usize, PsbtV2Error> {
+    let count = decode::<VarInt>(value, field)?;
+    usize::try_from(count.0).map_err(|_| PsbtV2Error::InvalidField(field))
+}
+
+/// Serialize a [`Psbt`] as a version 2 PSBT.
+///
+/// The unsigned transaction is moved from the global map to the `PSBT_GLOBAL_TX_VERSION`,
+/// `PSBT_GLOBAL_FALLBACK_LOCKTIME`, `PSBT_IN_PREVIOUS_TXID`, `PSBT_IN_OUTPUT_INDEX`,
+/// `PSBT_IN_SEQUENCE`, `PSBT_OUT_AMOUNT` and `PSBT_OUT_SCRIPT` fields, all the other fields are
+/// kept unchanged. `PSBT_IN_SEQUENCE` is omitted for the final sequence, which is its default. The
+/// [`Psbt::version`] field is ignored.
+///
+/// Fails with [`PsbtV2Error::MapCount`] if the PSBT doesn't have as many input and output maps as
+/// the unsigned transaction has inputs and outputs.
+pub fn psbt_v0_to_v2(psbt: &Psbt) -> Result<Vec<u8>, PsbtV2Error> {
+    v0_to_v2(psbt, &V2Fields::default())
+}
+
+/// Serialize `psbt` as a version 2 PSBT, with the version 2 only `fields`.
+fn v0_to_v2(psbt: &Psbt, fields: &V2Fields) -> Result<Vec<u8>, PsbtV2Error> {
+    let tx = &psbt.unsigned_tx;
+    if psbt.inputs.len() != tx.input.len() || psbt.outputs.len() != tx.output.len() {
+        return Err(PsbtV2Error::MapCount);
+    }
+    let mut maps = parse_maps(&psbt.serialize()).expect("serialized PSBT must be valid");
+    let mut outputs = maps.split_off(1 + tx.input.len());
+    let mut inputs = maps.split_off(1);
+    let mut global = maps.pop().expect("global map must be present");
+
+    remove(&mut global, &[GLOBAL_UNSIGNED_TX, GLOBAL_VERSION]);
+    insert(
+        &mut global,
+        GLOBAL_TX_VERSION,
+        encode::serialize(&tx.version),
+    );
+    // the lock time is the fallback unless the inputs kept their requirements
+    let required_locktimes = tx
+        .input
+        .iter()
+        .filter_map(|txin| fields.required_locktimes.get(&txin.previous_output))
+        .collect::<Vec<_>>();
+    if required_locktimes.is_empty() {
+        insert(
+            &mut global,
+            GLOBAL_FALLBACK_LOCKTIME,
+            encode::serialize(&tx.lock_time),
+        );
+    } else if let Some(fallback_locktime) = &fields.fallback_locktime {
+        insert(
+            &mut global,
+            GLOBAL_FALLBACK_LOCKTIME,
+            fallback_locktime.clone(),
+        );
+    }
+    if let Some(tx_modifiable) = &fields.tx_modifiable {
+        insert(&mut global, GLOBAL_TX_MODIFIABLE, tx_modifiable.clone());
+    }
+    insert(
+        &mut global,
+        GLOBAL_INPUT_COUNT,
+        encode::serialize(&VarInt(tx.input.len() as u64)),
+    );
+    insert(
+        &mut global,
+        GLOBAL_OUTPUT_COUNT,
+        encode::serialize(&VarInt(tx.output.len() as u64)),
+    );
+    insert(&mut global, GLOBAL_VERSION, 2u32.to_le_bytes().to_vec());
+
+    for (map, txin) in inputs.iter_mut().zip(&tx.input) {
+        let outpoint = txin.previous_output;
+        insert(map, IN_PREVIOUS_TXID, encode::serialize(&outpoint.txid));
+        insert(map, IN_OUTPUT_INDEX, outpoint.vout.to_le_bytes().to_vec());
+        // `PSBT_IN_SEQUENCE` defaults to the final sequence when it's missing
+        if txin.sequence != Sequence::MAX {
+            insert(map, IN_SEQUENCE, encode::serialize(&txin.sequence));
+        }
+        if let Some(entries) = fields.required_locktimes.get(&outpoint) {
+            map.extend(entries.iter().cloned());
+        }
+    }
+    for (map, txout) in outputs.iter_mut().zip(&tx.output) {
+        insert(map, OUT_AMOUNT, encode::serialize(&txout.value));
+        insert(map, OUT_SCRIPT, txout.script_pubkey.to_bytes());
+    }
+
+    let mut maps = vec![global];
+    maps.extend(inputs);
+    maps.extend(outputs);
+    Ok(serialize_maps(maps))
+}
+
+/// Deserialize a version 2 PSBT into a [`Psbt`].
+///
+/// The unsigned transaction is rebuilt from the per-input and per-output fields, with the lock
+/// time computed as described in BIP370. The returned [`Psbt::version`] is `0`, so that
+/// [`Psbt::serialize`] returns a valid version 0 PSBT. Use [`psbt_v0_to_v2`] to serialize it as a
+/// version 2 PSBT again.
+///
+/// Fails with [`PsbtV2Error::Unrepresentable`] if the PSBT has a `PSBT_GLOBAL_TX_MODIFIABLE`,
+/// `PSBT_IN_REQUIRED_TIME_LOCKTIME` or `PSBT_IN_REQUIRED_HEIGHT_LOCKTIME` field, which can't be
+/// represented in a version 0 PSBT. [`PsbtV2`] keeps them instead.
+pub fn psbt_v2_to_v0(bytes: &[u8]) -> Result<Psbt, PsbtV2Error> {
+    let (psbt, fields) = v2_to_v0(bytes)?;
+    if fields.tx_modifiable.is_some() {
+        return Err(PsbtV2Error::Unrepresentable("PSBT_GLOBAL_TX_MODIFIABLE"));
+    }
+    if let Some(entries) = fields.required_locktimes.values().next() {
+        let field = match entries.iter().find_map(|(k, _)| key_type(k)) {
+            Some(IN_REQUIRED_TIME_LOCKTIME) => "PSBT_IN_REQUIRED_TIME_LOCKTIME",
+            _ => "PSBT_IN_REQUIRED_HEIGHT_LOCKTIME",
+        };
+        return Err(PsbtV2Error::Unrepresentable(field));
+    }
+    Ok(psbt)
+}
+
+/// Deserialize a version 2 PSBT into a [`Psbt`] and the fields it can't represent.
+fn v2_to_v0(bytes: &[u8]) -> Result<(Psbt, V2Fields), PsbtV2Error> {
+    let mut maps = parse_maps(bytes)?;
+    if maps.is_empty() {
+        return Err(PsbtV2Error::Malformed);
+    }
+    let mut global = maps.remove(0);
+
+    let version = match get(&global, GLOBAL_VERSION) {
+        Some(value) => decode::<u32>(value, "PSBT_GLOBAL_VERSION")?,
+        None => 0,
+    };
+    if version != 2 {
+        return Err(PsbtV2Error::UnsupportedVersion(version));
+    }
+    if get(&global, GLOBAL_UNSIGNED_TX).is_some() {
+        return Err(PsbtV2Error::InvalidField("PSBT_GLOBAL_UNSIGNED_TX"));
+    }
+    let tx_version = get(&global, GLOBAL_TX_VERSION)
+        .ok_or(PsbtV2Error::MissingField("PSBT_GLOBAL_TX_VERSION"))?;
+    let tx_version = decode::<transaction::Version>(tx_version, "PSBT_GLOBAL_TX_VERSION")?;
+    let fallback_locktime = get(&global, GLOBAL_FALLBACK_LOCKTIME)
+        .map(|value| decode::<absolute::LockTime>(value, "PSBT_GLOBAL_FALLBACK_LOCKTIME"))
+        .transpose()?;
+    let input_count = get(&global, GLOBAL_INPUT_COUNT)
+        .ok_or(PsbtV2Error::MissingField("PSBT_GLOBAL_INPUT_COUNT"))?;
+    let input_count = decode_count(input_count, "PSBT_GLOBAL_INPUT_COUNT")?;
+    let output_count = get(&global, GLOBAL_OUTPUT_COUNT)
+        .ok_or(PsbtV2Error::MissingField("PSBT_GLOBAL_OUTPUT_COUNT"))?;
+    let output_count = decode_count(output_count, "PSBT_GLOBAL_OUTPUT_COUNT")?;
+    if maps.len() != input_count + output_count {
+        return Err(PsbtV2Error::MapCount);
+    }
+    let mut outputs = maps.split_off(input_count);
+    let mut inputs = maps;
+
+    let mut txins = Vec::with_capacity(input_count);
+    // The lock time required by the inputs, `None` if no input has requirements.
+    let mut height_locktime: Option<Option<u32>> = None;
+    let mut time_locktime: Option<Option<u32>> = None;
+    for map in &inputs {
+        let txid =
+            get(map, IN_PREVIOUS_TXID).ok_or(PsbtV2Error::MissingField("PSBT_IN_PREVIOUS_TXID"))?;
+        let txid = decode::<Txid>(txid, "PSBT_IN_PREVIOUS_TXID")?;
+        let vout =
+            get(map, IN_OUTPUT_INDEX).ok_or(PsbtV2Error::MissingField("PSBT_IN_OUTPUT_INDEX"))?;
+        let vout = decode::<u32>(vout, "PSBT_IN_OUTPUT_INDEX")?;
+        let sequence = get(map, IN_SEQUENCE)
+            .map(|value| decode::<Sequence>(value, "PSBT_IN_SEQUENCE"))
+            .transpose()?
+            .unwrap_or(Sequence::MAX);
+        txins.push(TxIn {
+            previous_output: OutPoint::new(txid, vout),
+            script_sig: ScriptBuf::new(),
+            sequence,
+            witness: Witness::new(),
+        });
+
+        let time = get(map, IN_REQUIRED_TIME_LOCKTIME)
+            .map(|value| decode::<u32>(value, "PSBT_IN_REQUIRED_TIME_LOCKTIME"))
+            .transpose()?;
+        let height = get(map, IN_REQUIRED_HEIGHT_LOCKTIME)
+            .map(|value| decode::<u32>(value, "PSBT_IN_REQUIRED_HEIGHT_LOCKTIME"))
+            .transpose()?;
+        if time.is_none() && height.is_none() {
+            continue;
+        }
+        // An input only supports the lock time types it has a requirement for, track the
+        // highest requirement of each type or `None` once an input doesn't support it.
+        height_locktime = Some(match (height_locktime, height) {
+            (None, h) => h,
+            (Some(Some(max)), Some(h)) => Some(max.max(h)),
+            _ => None,
+        });
+        time_locktime = Some(match (time_locktime, time) {
+            (None, t) => t,
+            (Some(Some(max)), Some(t)) => Some(max.max(t)),
+            _ => None,
+        });
+    }
+    let lock_time = match (height_locktime, time_locktime) {
+        (None, None) => fallback_locktime.unwrap_or(absolute::LockTime::ZERO),
+        (Some(Some(height)), _) => absolute::LockTime::from_height(height)
+            .map_err(|_| PsbtV2Error::InvalidField("PSBT_IN_REQUIRED_HEIGHT_LOCKTIME"))?,
+        (_, Some(Some(time))) => absolute::LockTime::from_time(time)
+            .map_err(|_| PsbtV2Error::InvalidField("PSBT_IN_REQUIRED_TIME_LOCKTIME"))?,
+        _ => return Err(PsbtV2Error::LockTimeConflict),
+    };
+
+    let mut txouts = Vec::with_capacity(output_count);
+    for map in &outputs {
+        let amount = get(map, OUT_AMOUNT).ok_or(PsbtV2Error::MissingField("PSBT_OUT_AMOUNT"))?;
+        let amount = decode::<Amount>(amount, "PSBT_OUT_AMOUNT")?;
+        let script = get(map, OUT_SCRIPT).ok_or(PsbtV2Error::MissingField("PSBT_OUT_SCRIPT"))?;
+        txouts.push(TxOut {
+            value: amount,
+            script_pubkey: ScriptBuf::from_bytes(script.to_vec()),
+        });
+    }
+
+    let mut fields = V2Fields {
+        tx_modifiable: get(&global, GLOBAL_TX_MODIFIABLE).map(<[u8]>::to_vec),
+        ..Default::default()
+    };
+    if height_locktime.is_some() || time_locktime.is_some() {
+        fields.fallback_locktime = get(&global, GLOBAL_FALLBACK_LOCKTIME).map(<[u8]>::to_vec);
+    }
+
+    let tx = Transaction {
+        version: tx_version,
+        lock_time,
+        input: txins,
+        output: txouts,
+    };
+
+    remove(
+        &mut global,
+        &[
+            GLOBAL_TX_VERSION,
+            GLOBAL_FALLBACK_LOCKTIME,
+            GLOBAL_INPUT_COUNT,
+            GLOBAL_OUTPUT_COUNT,
+            GLOBAL_TX_MODIFIABLE,
+            GLOBAL_VERSION,
+        ],
+    );
+    insert(&mut global, GLOBAL_UNSIGNED_TX, encode::serialize(&tx));
+    for (map, txin) in inputs.iter_mut().zip(&tx.input) {
+        remove(map, &[IN_PREVIOUS_TXID, IN_OUTPUT_INDEX, IN_SEQUENCE]);
+        let required_locktimes = take(
+            map,
+            &[IN_REQUIRED_TIME_LOCKTIME, IN_REQUIRED_HEIGHT_LOCKTIME],
+        );
+        if !required_locktimes.is_empty() {
+            fields
+                .required_locktimes
+                .insert(txin.previous_output, required_locktimes);
+        }
+    }
+    for map in &mut outputs {
+        remove(map, &[OUT_AMOUNT, OUT_SCRIPT]);
+    }
+
+    let mut maps = vec![global];
+    maps.extend(inputs);
+    maps.extend(outputs);
+    Ok((Psbt::deserialize(&serialize_maps(maps))?, fields))
+}
+
+/// A version 2 ([BIP370]) PSBT.
+///
+/// The PSBT is held as a version 0 [`Psbt`], which it dereferences to, so it can be signed and
+/// finalized with [`Wallet::sign`] and [`Wallet::finalize_psbt`]. It is only converted to the
+/// version 2 format by [`PsbtV2::serialize`] and [`PsbtV2::to_base64`]. The fields that a version 0
+/// PSBT can't represent are kept aside, the required lock times of the inputs by previous output.
+///
+/// ```
+/// # use bdk_wallet::psbt::PsbtV2;
+/// # use bdk_wallet::bitcoin::Psbt;
+/// # fn sign(psbt: &mut Psbt) {}
+/// // The 1 input, 2 outputs PSBT of the BIP370 test vectors
+/// let base64 = "cHNidP8BAgQCAAAAAQQBAQEFAQIB+wQCAAAAAAEOIAsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAAAAAAABAwgIrwgAAAAAAAEEFgAUxDD2TEdW2jENvRoIVXLvKZkmJywAAQMIi73rCwAAAAABBBYAFE3Rk6yWSlasG54cyoRU/i9HT4UTAA==";
+/// let mut psbt: PsbtV2 = base64.parse()?;
+/// assert_eq!(psbt.unsigned_tx.output.len(), 2);
+/// sign(&mut psbt);
+/// let serialized = psbt.to_base64()?;
+/// assert_eq!(serialized.parse::<PsbtV2>()?, psbt);
+/// # Ok::<_, bdk_wallet::psbt::v2::PsbtV2Error>(())
+/// ```
+///
+/// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+/// [`Wallet::sign`]: crate::Wallet::sign
+/// [`Wallet::finalize_psbt`]: crate::Wallet::finalize_psbt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtV2 {
+    psbt: Psbt,
+    fields: V2Fields,
+}
+
+impl PsbtV2 {
+    /// Deserialize a version 2 PSBT, see [`psbt_v2_to_v0`].
+    ///
+    /// Unlike [`psbt_v2_to_v0`], the fields that can't be represented in a version 0 PSBT are
+    /// kept.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, PsbtV2Error> {
+        let (psbt, fields) = v2_to_v0(bytes)?;
+        Ok(Self { psbt, fields })
+    }
+
+    /// Serialize as a version 2 PSBT, see [`psbt_v0_to_v2`].
+    pub fn serialize(&self) -> Result<Vec<u8>, PsbtV2Error> {
+        v0_to_v2(&self.psbt, &self.fields)
+    }
+
+    /// Serialize as a version 2 PSBT encoded in base64.
+    pub fn to_base64(&self) -> Result<String, PsbtV2Error> {
+        Ok(BASE64_STANDARD.encode(self.serialize()?))
+    }
+
+    /// Return the PSBT in the version 0 representation, without the fields it can't represent.
+    pub fn into_inner(self) -> Psbt {
+        self.psbt
+    }
+}
+
+impl From<Psbt> for PsbtV2 {
+    /// Serialize `psbt` as a version 2 PSBT from now on, e.g. the PSBT returned by
+    /// [`TxBuilder::finish`](crate::TxBuilder::finish).
+    fn from(mut psbt: Psbt) -> Self {
+        psbt.version = 0;
+        Self {
+            psbt,
+            fields: V2Fields::default(),
+        }
+    }
+}
+
+impl Deref for PsbtV2 {
+    type Target = Psbt;
+
+    fn deref(&self) -> &Psbt {
+        &self.psbt
+    }
+}
+
+impl DerefMut for PsbtV2 {
+    fn deref_mut(&mut self) -> &mut Psbt {
+        &mut self.psbt
+    }
+}
+
+impl FromStr for PsbtV2 {
+    type Err = PsbtV2Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = BASE64_STANDARD
+            .decode(s)
+            .map_err(|_| PsbtV2Error::Malformed)?;
+        Self::deserialize(&bytes)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+    use alloc::string::ToString;
+
+    // Valid PSBT from the BIP370 test vectors: 1 input and 2 outputs.
+    const BIP370_PSBT: &str = "cHNidP8BAgQCAAAAAQQBAQEFAQIB+wQCAAAAAAEOIAsK2SFBnByHGXNdctxzn56p4GONH+TB7vD5lECEgV/IAQ8EAAAAAAABAwgIrwgAAAAAAAEEFgAUxDD2TEdW2jENvRoIVXLvKZkmJywAAQMIi73rCwAAAAABBBYAFE3Rk6yWSlasG54cyoRU/i9HT4UTAA==";
+
+    fn bip370_psbt() -> Vec<u8> {
+        BASE64_STANDARD.decode(BIP370_PSBT).unwrap()
+    }
+
+    #[test]
+    fn test_psbt_v2_to_v0() {
+        let bytes = bip370_psbt();
+        let psbt = psbt_v2_to_v0(&bytes).unwrap();
+        let tx = &psbt.unsigned_tx;
+
+        assert_eq!(psbt.version, 0);
+        assert_eq!(tx.version, transaction::Version::TWO);
+        assert_eq!(tx.lock_time, absolute::LockTime::ZERO);
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(
+            tx.input[0].previous_output.txid.to_string(),
+            "c85f81844094f9f0eec1e41f8d63e0a99e9f73dc725d7319871c9c4121d90a0b"
+        );
+        assert_eq!(tx.input[0].sequence, Sequence::MAX);
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[0].value, Amount::from_sat(569_096));
+        assert_eq!(tx.output[1].value, Amount::from_sat(199_998_859));
+        assert!(psbt.inputs[0].unknown.is_empty());
+        assert!(psbt.outputs.iter().all(|o| o.unknown.is_empty()));
+
+        let round_trip = psbt_v0_to_v2(&psbt).unwrap();
+        assert_eq!(psbt_v2_to_v0(&round_trip).unwrap(), psbt);
+    }
+
+    #[test]
+    fn test_psbt_v0_to_v2_round_trip() {
+        let psbt = psbt_v2_to_v0(&bip370_psbt()).unwrap();
+        let v0 = psbt.serialize();
+
+        let v2 = psbt_v0_to_v2(&psbt).unwrap();
+        assert_ne!(v2, v0);
+        let converted = psbt_v2_to_v0(&v2).unwrap();
+        assert_eq!(converted.serialize(), v0);
+
+        // Version 0 PSBTs are rejected
+        assert!(matches!(
+            psbt_v2_to_v0(&v0),
+            Err(PsbtV2Error::UnsupportedVersion(0))
+        ));
+
+        // The maps must match the unsigned transaction
+        let mut psbt = psbt;
+        psbt.outputs.pop();
+        assert!(matches!(psbt_v0_to_v2(&psbt), Err(PsbtV2Error::MapCount)));
+    }
+
+    #[test]
+    fn test_psbt_v2_required_locktime() {
+        let bytes = bip370_psbt();
+        let mut maps = parse_maps(&bytes).unwrap();
+        insert(
+            &mut maps[1],
+            IN_REQUIRED_HEIGHT_LOCKTIME,
+            800_000u32.to_le_bytes().to_vec(),
+        );
+        insert(
+            &mut maps[1],
+            IN_REQUIRED_TIME_LOCKTIME,
+            1_700_000_000u32.to_le_bytes().to_vec(),
+        );
+        let bytes = serialize_maps(maps.clone());
+        let psbt = PsbtV2::deserialize(&bytes).unwrap();
+        assert_eq!(
+            psbt.unsigned_tx.lock_time,
+            absolute::LockTime::from_height(800_000).unwrap()
+        );
+        // The requirements are kept, not the lock time they resolve to
+        assert_eq!(sorted_maps(&psbt.serialize().unwrap()), sorted_maps(&bytes));
+        assert!(matches!(
+            psbt_v2_to_v0(&bytes),
+            Err(PsbtV2Error::Unrepresentable(
+                "PSBT_IN_REQUIRED_TIME_LOCKTIME"
+            ))
+        ));
+
+        remove(&mut maps[1], &[IN_REQUIRED_HEIGHT_LOCKTIME]);
+        let psbt = PsbtV2::deserialize(&serialize_maps(maps.clone())).unwrap();
+        assert_eq!(
+            psbt.unsigned_tx.lock_time,
+            absolute::LockTime::from_time(1_700_000_000).unwrap()
+        );
+
+        // Another input only supporting height based lock times
+        let mut input = maps[1].clone();
+        remove(&mut input, &[IN_REQUIRED_TIME_LOCKTIME, IN_OUTPUT_INDEX]);
+        insert(&mut input, IN_OUTPUT_INDEX, 1u32.to_le_bytes().to_vec());
+        insert(
+            &mut input,
+            IN_REQUIRED_HEIGHT_LOCKTIME,
+            800_000u32.to_le_bytes().to_vec(),
+        );
+        maps.insert(2, input);
+        remove(&mut maps[0], &[GLOBAL_INPUT_COUNT]);
+        insert(&mut maps[0], GLOBAL_INPUT_COUNT, vec![2]);
+        assert!(matches!(
+            PsbtV2::deserialize(&serialize_maps(maps)),
+            Err(PsbtV2Error::LockTimeConflict)
+        ));
+    }
+
+    // Created with the psbt-v2 crate v0.3.0: 2 inputs and 2 outputs, modifiable inputs and
+    // outputs, the first input requiring the height based lock time 800000.
+    const PSBT_V2_MODIFIABLE: &str = "cHNidP8B+wQCAAAAAQIEAgAAAAEEAQIBBQECAQYBAwABDiCqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqgEPBAAAAAABEgQANQwAAQEfoIYBAAAAAAAWABQBAQEBAQEBAQEBAQEBAQEBAQEBAQABDiC7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7uwEPBAEAAAABAR9QwwAAAAAAABYAFAICAgICAgICAgICAgICAgICAgICAAEDCMDUAQAAAAAAAQQWABQDAwMDAwMDAwMDAwMDAwMDAwMDAwABAwhIcQAAAAAAAAEEFgAUBAQEBAQEBAQEBAQEBAQEBAQEBAQA";
+    // The same PSBT after the psbt-v2 updater: no longer modifiable, with the sequence of the
+    // second input set.
+    const PSBT_V2_UPDATED: &str = "cHNidP8B+wQCAAAAAQIEAgAAAAEEAQIBBQECAQYBAAABDiCqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqgEPBAAAAAABEgQANQwAAQEfoIYBAAAAAAAWABQBAQEBAQEBAQEBAQEBAQEBAQEBAQABDiC7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7uwEPBAEAAAABEAT+////AQEfUMMAAAAAAAAWABQCAgICAgICAgICAgICAgICAgICAgABAwjA1AEAAAAAAAEEFgAUAwMDAwMDAwMDAwMDAwMDAwMDAwMAAQMISHEAAAAAAAABBBYAFAQEBAQEBAQEBAQEBAQEBAQEBAQEAA==";
+
+    /// The maps of a serialized PSBT with their entries sorted, the order of the entries is not
+    /// significant.
+    fn sorted_maps(bytes: &[u8]) -> Vec<Map> {
+        let mut maps = parse_maps(bytes).unwrap();
+        maps.iter_mut().for_each(|map| map.sort());
+        maps
+    }
+
+    #[test]
+    fn test_psbt_v2_interop() {
+        for (base64, sequence) in [
+            (PSBT_V2_MODIFIABLE, Sequence::MAX),
+            (PSBT_V2_UPDATED, Sequence::ENABLE_LOCKTIME_NO_RBF),
+        ] {
+            let bytes = BASE64_STANDARD.decode(base64).unwrap();
+            let psbt: PsbtV2 = base64.parse().unwrap();
+            let tx = &psbt.unsigned_tx;
+            assert_eq!(
+                tx.lock_time,
+                absolute::LockTime::from_height(800_000).unwrap()
+            );
+            assert_eq!(tx.input.len(), 2);
+            assert_eq!(tx.input[0].sequence, Sequence::MAX);
+            assert_eq!(tx.input[1].sequence, sequence);
+            assert_eq!(tx.output[0].value, Amount::from_sat(120_000));
+            assert_eq!(tx.output[1].value, Amount::from_sat(29_000));
+            assert_eq!(
+                psbt.inputs[1]
+                    .witness_utxo
+                    .as_ref()
+                    .map(|txout| txout.value),
+                Some(Amount::from_sat(50_000))
+            );
+
+            assert_eq!(sorted_maps(&psbt.serialize().unwrap()), sorted_maps(&bytes));
+            assert!(matches!(
+                psbt_v2_to_v0(&bytes),
+                Err(PsbtV2Error::Unrepresentable("PSBT_GLOBAL_TX_MODIFIABLE"))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_psbt_v2_missing_field() {
+        let bytes = bip370_psbt();
+        let mut maps = parse_maps(&bytes).unwrap();
+        remove(&mut maps[2], &[OUT_SCRIPT]);
+        assert!(matches!(
+            psbt_v2_to_v0(&serialize_maps(maps)),
+            Err(PsbtV2Error::MissingField("PSBT_OUT_SCRIPT"))
+        ));
+
+        let mut maps = parse_maps(&bytes).unwrap();
+        maps.pop();
+        assert!(matches!(
+            psbt_v2_to_v0(&serialize_maps(maps)),
+            Err(PsbtV2Error::MapCount)
+        ));
+    }
+}
@@ -1,16 +1,26 @@
 //! `bdk_wallet` test utilities
 
+use alloc::boxed::Box;
 use alloc::string::ToString;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
 use core::str::FromStr;
+use core::task::{Context, Poll};
+use std::sync::Mutex;
 
-use bdk_chain::{BlockId, CheckPoint, ConfirmationBlockTime, TxUpdate};
+use bdk_chain::{BlockId, CheckPoint, ConfirmationBlockTime, Merge, TxUpdate};
 use bitcoin::{
     absolute, hashes::Hash, transaction, Address, Amount, BlockHash, FeeRate, Network, OutPoint,
     Transaction, TxIn, TxOut, Txid,
 };
 
-use crate::{KeychainKind, Update, Wallet};
+use crate::{
+    AsyncBroadcaster, AsyncWalletPersister, BroadcastError, Broadcaster, ChangeSet, FutureResult,
+    KeychainKind, Update, Wallet, WalletPersister,
+};
 
 /// Return a fake wallet that appears to be funded for testing.
 ///
@@ -210,6 +220,12 @@ pub fn get_test_tr_with_taptree_xprv() -> &'static str {
     "tr(cNJmN3fH9DDbDt131fQNkVakkpzawJBSeybCUNmP1BovpmGQ45xG,{pk(tprv8ZgxMBicQKsPdDArR4xSAECuVxeX1jwwSXR4ApKbkYgZiziDc4LdBy2WvJeGDfUSE4UT4hHhbgEwbdq8ajjUHiKDegkwrNU6V55CxcxonVN/*),pk(8aee2b8120a5f157f1223f72b5e62b825831a27a9fdf427db7cc697494d4a642)})"
 }
 
+/// taproot descriptor with an unspendable internal key and a `multi_a(2,A,B,C)` leaf, where the
+/// private keys of A and B are known
+pub fn get_test_tr_multi_a() -> &'static str {
+    "tr(50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0,multi_a(2,cPZzKuNmpuUjD1e8jUU4PVzy2b5LngbSip8mBsxf4e7rSFZVb4Uh,cNaQCDwmmh4dS9LzCgVtyy1e1xjCJ21GUDHe9K98nzb689JvinGV,8aee2b8120a5f157f1223f72b5e62b825831a27a9fdf427db7cc697494d4a642))"
+}
+
 /// taproot descriptor with duplicate script paths
 pub fn get_test_tr_dup_keys() -> &'static str {
     "tr(cNJmN3fH9DDbDt131fQNkVakkpzawJBSeybCUNmP1BovpmGQ45xG,{pk(8aee2b8120a5f157f1223f72b5e62b825831a27a9fdf427db7cc697494d4a642),pk(8aee2b8120a5f157f1223f72b5e62b825831a27a9fdf427db7cc697494d4a642)})"
@@ -361,3 +377,228 @@ pub fn insert_seen_at(wallet: &mut Wallet, txid: Txid, seen_at: u64) {
         })
         .expect("failed to apply update");
 }
+
+/// A [`Broadcaster`] recording the broadcasted transactions instead of sending them, or
+/// rejecting them when created with [`MockBroadcaster::rejecting`].
+#[derive(Debug, Default)]
+pub struct MockBroadcaster {
+    broadcasted: Mutex<Vec<Transaction>>,
+    reject: bool,
+}
+
+impl MockBroadcaster {
+    /// A broadcaster rejecting every transaction.
+    pub fn rejecting() -> Self {
+        Self {
+            reject: true,
+            ..Default::default()
+        }
+    }
+
+    /// The transactions broadcasted so far.
+    pub fn broadcasted(&self) -> Vec<Transaction> {
+        self.broadcasted.lock().unwrap().clone()
+    }
+}
+
+impl Broadcaster for MockBroadcaster {
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, BroadcastError> {
+        if self.reject {
+            return Err(BroadcastError::Rejected("mock rejection".to_string()));
+        }
+        self.broadcasted.lock().unwrap().push(tx.clone());
+        Ok(tx.compute_txid())
+    }
+}
+
+impl AsyncBroadcaster for MockBroadcaster {
+    fn broadcast<'a>(
+        &'a self,
+        tx: &'a Transaction,
+    ) -> Pin<Box<dyn Future<Output = Result<Txid, BroadcastError>> + Send + 'a>> {
+        Box::pin(async move { Broadcaster::broadcast(self, tx) })
+    }
+}
+
+/// An in-memory [`WalletPersister`] and [`AsyncWalletPersister`] that can be told to fail, to
+/// test how persistence errors are handled.
+///
+/// Every changeset passed to [`persist`](WalletPersister::persist) is recorded, whether or not
+/// it was stored, and [`changeset`](Self::changeset) is what a wallet would load.
+///
+/// ```rust
+/// # use bdk_wallet::test_utils::*;
+/// # use bdk_wallet::{bitcoin::Network, KeychainKind, Wallet};
+/// let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+/// // the first persist is made when creating the wallet
+/// let mut persister = FlakyPersister::new().fail_persist(2);
+/// let mut wallet = Wallet::create(desc, change_desc)
+///     .network(Network::Testnet)
+///     .create_wallet(&mut persister)?;
+/// wallet.reveal_next_address(KeychainKind::External);
+/// assert!(wallet.persist(&mut persister).is_err());
+/// assert!(wallet.staged().is_some());
+/// assert!(wallet.persist(&mut persister)?);
+/// assert_eq!(persister.received().len(), 3);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FlakyPersister {
+    changeset: ChangeSet,
+    received: Vec<ChangeSet>,
+    fail_initialize: bool,
+    fail_persist: Option<usize>,
+    partially_apply: bool,
+    delay: usize,
+}
+
+impl FlakyPersister {
+    /// An empty persister that never fails.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A persister that already stores `changeset`.
+    pub fn with_changeset(changeset: ChangeSet) -> Self {
+        Self {
+            changeset,
+            ..Default::default()
+        }
+    }
+
+    /// Fail every initialization.
+    pub fn fail_initialize(mut self) -> Self {
+        self.fail_initialize = true;
+        self
+    }
+
+    /// Fail the `nth` call to persist, counting from 1 and including the failing calls.
+    pub fn fail_persist(mut self, nth: usize) -> Self {
+        self.fail_persist = Some(nth);
+        self
+    }
+
+    /// Store the changeset of the failing persist before returning the error, like a persister
+    /// that loses its connection after writing.
+    pub fn partially_apply(mut self) -> Self {
+        self.partially_apply = true;
+        self
+    }
+
+    /// Make the async methods yield to the executor `polls` times before completing.
+    pub fn delay(mut self, polls: usize) -> Self {
+        self.delay = polls;
+        self
+    }
+
+    /// The stored changeset.
+    pub fn changeset(&self) -> &ChangeSet {
+        &self.changeset
+    }
+
+    /// Every changeset passed to persist, in order.
+    pub fn received(&self) -> &[ChangeSet] {
+        &self.received
+    }
+
+    fn do_initialize(&self) -> Result<ChangeSet, FlakyPersisterError> {
+        if self.fail_initialize {
+            return Err(FlakyPersisterError::Initialize);
+        }
+        Ok(self.changeset.clone())
+    }
+
+    fn do_persist(&mut self, changeset: &ChangeSet) -> Result<(), FlakyPersisterError> {
+        self.received.push(changeset.clone());
+        let nth = self.received.len();
+        if self.fail_persist == Some(nth) {
+            if self.partially_apply {
+                self.changeset.merge(changeset.clone());
+                return Err(FlakyPersisterError::PartiallyApplied(nth));
+            }
+            return Err(FlakyPersisterError::Persist(nth));
+        }
+        self.changeset.merge(changeset.clone());
+        Ok(())
+    }
+}
+
+impl WalletPersister for FlakyPersister {
+    type Error = FlakyPersisterError;
+
+    fn initialize(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        persister.do_initialize()
+    }
+
+    fn persist(persister: &mut Self, changeset: &ChangeSet) -> Result<(), Self::Error> {
+        persister.do_persist(changeset)
+    }
+}
+
+impl AsyncWalletPersister for FlakyPersister {
+    type Error = FlakyPersisterError;
+
+    fn initialize<'a>(persister: &'a mut Self) -> FutureResult<'a, ChangeSet, Self::Error>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            YieldNow(persister.delay).await;
+            persister.do_initialize()
+        })
+    }
+
+    fn persist<'a>(
+        persister: &'a mut Self,
+        changeset: &'a ChangeSet,
+    ) -> FutureResult<'a, (), Self::Error>
+    where
+        Self: 'a,
+    {
+        Box::pin(async move {
+            YieldNow(persister.delay).await;
+            persister.do_persist(changeset)
+        })
+    }
+}
+
+/// Future returning pending the given number of times before completing.
+struct YieldNow(usize);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 == 0 {
+            return Poll::Ready(());
+        }
+        self.0 -= 1;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Error of [`FlakyPersister`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlakyPersisterError {
+    /// Initialization was set to fail.
+    Initialize,
+    /// The persist with this number was set to fail, and nothing was stored.
+    Persist(usize),
+    /// The persist with this number was set to fail, after storing the changeset.
+    PartiallyApplied(usize),
+}
+
+impl fmt::Display for FlakyPersisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Initialize => write!(f, "injected initialization failure"),
+            Self::Persist(nth) => write!(f, "injected failure of persist {nth}"),
+            Self::PartiallyApplied(nth) => {
+                write!(f, "injected failure of persist {nth} after storing it")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FlakyPersisterError {}
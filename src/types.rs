@@ -10,15 +10,23 @@
 // licenses.
 
 use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use chain::Balance;
 use chain::{ChainPosition, ConfirmationBlockTime};
 use core::convert::AsRef;
 use core::fmt;
 
+use bitcoin::bip32::{KeySource, Xpriv, Xpub};
 use bitcoin::transaction::{OutPoint, Sequence, TxOut};
-use bitcoin::{psbt, Weight};
+use bitcoin::{psbt, Amount, SignedAmount, Txid, Weight};
+use miniscript::descriptor::DescriptorType;
 
 use serde::{Deserialize, Serialize};
 
+use crate::keys::slip132::{self, ScriptType};
+use crate::IsDust;
+
 /// Types of keychains
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub enum KeychainKind {
@@ -56,6 +64,69 @@ impl AsRef<[u8]> for KeychainKind {
     }
 }
 
+/// The type of the addresses derived from a descriptor.
+///
+/// See [`Wallet::address_type`](crate::Wallet::address_type).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressType {
+    /// Bare script, without an address form.
+    Bare,
+    /// Legacy pay to public key hash.
+    P2pkh,
+    /// Legacy pay to script hash.
+    P2sh,
+    /// Pay to witness public key hash nested in P2SH, also known as nested SegWit.
+    P2shP2wpkh,
+    /// Pay to witness script hash nested in P2SH.
+    P2shP2wsh,
+    /// Pay to witness public key hash, also known as native SegWit.
+    P2wpkh,
+    /// Pay to witness script hash.
+    P2wsh,
+    /// Pay to taproot.
+    P2tr,
+}
+
+impl AddressType {
+    /// Whether spending outputs of this type requires a witness.
+    pub fn is_witness(&self) -> bool {
+        !matches!(
+            self,
+            AddressType::Bare | AddressType::P2pkh | AddressType::P2sh
+        )
+    }
+}
+
+impl From<DescriptorType> for AddressType {
+    fn from(desc_type: DescriptorType) -> Self {
+        match desc_type {
+            DescriptorType::Bare => AddressType::Bare,
+            DescriptorType::Pkh => AddressType::P2pkh,
+            DescriptorType::Sh | DescriptorType::ShSortedMulti => AddressType::P2sh,
+            DescriptorType::ShWpkh => AddressType::P2shP2wpkh,
+            DescriptorType::ShWsh | DescriptorType::ShWshSortedMulti => AddressType::P2shP2wsh,
+            DescriptorType::Wpkh => AddressType::P2wpkh,
+            DescriptorType::Wsh | DescriptorType::WshSortedMulti => AddressType::P2wsh,
+            DescriptorType::Tr => AddressType::P2tr,
+        }
+    }
+}
+
+impl fmt::Display for AddressType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressType::Bare => write!(f, "Bare"),
+            AddressType::P2pkh => write!(f, "P2PKH"),
+            AddressType::P2sh => write!(f, "P2SH"),
+            AddressType::P2shP2wpkh => write!(f, "P2SH-P2WPKH"),
+            AddressType::P2shP2wsh => write!(f, "P2SH-P2WSH"),
+            AddressType::P2wpkh => write!(f, "P2WPKH"),
+            AddressType::P2wsh => write!(f, "P2WSH"),
+            AddressType::P2tr => write!(f, "P2TR"),
+        }
+    }
+}
+
 /// An unspent output owned by a [`Wallet`].
 ///
 /// [`Wallet`]: crate::Wallet
@@ -141,6 +212,171 @@ impl Utxo {
     }
 }
 
+/// Effect of a block on the wallet, returned by [`Wallet::preview_block`].
+///
+/// [`Wallet::preview_block`]: crate::Wallet::preview_block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceDelta {
+    /// Wallet transactions confirmed by the block, in block order
+    pub txids: Vec<Txid>,
+    /// Balance before the block
+    pub before: Balance,
+    /// Balance after the block
+    pub after: Balance,
+}
+
+impl BalanceDelta {
+    fn diff(after: Amount, before: Amount) -> SignedAmount {
+        SignedAmount::from_sat(after.to_sat() as i64 - before.to_sat() as i64)
+    }
+
+    /// Change of the confirmed balance
+    pub fn confirmed(&self) -> SignedAmount {
+        Self::diff(self.after.confirmed, self.before.confirmed)
+    }
+
+    /// Change of the trusted pending balance
+    pub fn trusted_pending(&self) -> SignedAmount {
+        Self::diff(self.after.trusted_pending, self.before.trusted_pending)
+    }
+
+    /// Change of the untrusted pending balance
+    pub fn untrusted_pending(&self) -> SignedAmount {
+        Self::diff(self.after.untrusted_pending, self.before.untrusted_pending)
+    }
+
+    /// Change of the immature balance
+    pub fn immature(&self) -> SignedAmount {
+        Self::diff(self.after.immature, self.before.immature)
+    }
+
+    /// Change of the total balance
+    pub fn total(&self) -> SignedAmount {
+        Self::diff(self.after.total(), self.before.total())
+    }
+}
+
+/// Blocks and transactions disconnected by a chain reorg, returned by
+/// [`Wallet::apply_block_connected_to_reorg`].
+///
+/// [`Wallet::apply_block_connected_to_reorg`]: crate::Wallet::apply_block_connected_to_reorg
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReorgSummary {
+    /// Height of the highest block of the wallet's chain kept by the reorg
+    pub fork_height: u32,
+    /// Wallet transactions that were confirmed in the disconnected blocks, in ascending order
+    ///
+    /// They are unconfirmed unless the applied block confirms them again.
+    pub disconnected_txs: Vec<Txid>,
+}
+
+/// How [`Wallet`] chooses between conflicting unconfirmed transactions last seen at the same time.
+///
+/// Of two conflicting unconfirmed transactions, the one last seen the latest is canonical, e.g.
+/// a replacement which was seen in the mempool after the transaction it replaces. When they were
+/// last seen at the same time, e.g. both in the same mempool sync, the policy chooses the
+/// canonical one. Ties of the policy are broken by [`TxidOrder`](Self::TxidOrder).
+///
+/// Set with [`Wallet::set_canonicalization_policy`].
+///
+/// [`Wallet`]: crate::Wallet
+/// [`Wallet::set_canonicalization_policy`]: crate::Wallet::set_canonicalization_policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CanonicalizationPolicy {
+    /// The transaction with the greatest txid is canonical.
+    ///
+    /// This is arbitrary, but is the default, as it doesn't depend on any other data.
+    #[default]
+    TxidOrder,
+    /// The transaction paying the highest absolute fee is canonical, as a replacement must pay a
+    /// higher fee than the transactions it replaces.
+    ///
+    /// A transaction whose fee can't be computed, because some of its previous outputs are
+    /// unknown, loses to the others.
+    HigherFeeWins,
+    /// The transaction first seen the latest is canonical, i.e. the most recent replacement.
+    ///
+    /// The tied transactions are all last seen at the same time, so they are ranked by the time
+    /// they were first seen instead. A transaction without a first seen time loses to the others.
+    FirstSeenLatestWins,
+}
+
+/// Statistics of the unspent outputs of a wallet, returned by [`Wallet::utxo_stats`].
+///
+/// The age of a confirmed UTXO is its confirmation depth: 1 when it is confirmed in the block at
+/// the current height.
+///
+/// [`Wallet::utxo_stats`]: crate::Wallet::utxo_stats
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct UtxoStats {
+    /// Number of UTXOs
+    pub count: usize,
+    /// Total value of the UTXOs
+    pub total_value: Amount,
+    /// Number of UTXOs below the dust limit of their script pubkey
+    pub dust_count: usize,
+    /// Total value of the UTXOs below the dust limit of their script pubkey
+    pub dust_value: Amount,
+    /// Number of unconfirmed UTXOs
+    pub unconfirmed_count: usize,
+    /// Number of confirmed UTXOs in each age range: `age_distribution[i]` counts the UTXOs
+    /// younger than [`AGE_BUCKETS[i]`](Self::AGE_BUCKETS) and at least as old as the previous
+    /// bound, and the last element the UTXOs at least as old as the last bound.
+    pub age_distribution: [usize; UtxoStats::AGE_BUCKETS.len() + 1],
+    /// Median age of the confirmed UTXOs, `None` if there are none
+    pub median_age: Option<u32>,
+    /// Age of the oldest confirmed UTXO, `None` if there are none
+    pub max_age: Option<u32>,
+}
+
+impl UtxoStats {
+    /// Upper bounds of the age ranges of [`age_distribution`](Self::age_distribution), in
+    /// blocks: about an hour, a day, a week, a month and a year.
+    pub const AGE_BUCKETS: [u32; 5] = [6, 144, 1_008, 4_320, 52_560];
+
+    pub(crate) fn new(utxos: impl IntoIterator<Item = LocalOutput>, current_height: u32) -> Self {
+        let mut stats = Self::default();
+        let mut ages = Vec::new();
+        for utxo in utxos {
+            let value = utxo.txout.value;
+            stats.count += 1;
+            stats.total_value += value;
+            if value.is_dust(&utxo.txout.script_pubkey) {
+                stats.dust_count += 1;
+                stats.dust_value += value;
+            }
+            match utxo.chain_position.confirmation_height_upper_bound() {
+                Some(height) => ages.push(current_height.saturating_sub(height) + 1),
+                None => stats.unconfirmed_count += 1,
+            }
+        }
+        for &age in &ages {
+            let bucket = Self::AGE_BUCKETS.partition_point(|&bound| bound <= age);
+            stats.age_distribution[bucket] += 1;
+        }
+        ages.sort_unstable();
+        let mid = ages.len() / 2;
+        stats.median_age = match ages.len() {
+            0 => None,
+            len if len % 2 == 1 => Some(ages[mid]),
+            _ => Some(ages[mid - 1] + (ages[mid] - ages[mid - 1]) / 2),
+        };
+        stats.max_age = ages.last().copied();
+        stats
+    }
+}
+
+/// Order of the transactions returned by
+/// [`Wallet::transactions_paginated`](crate::Wallet::transactions_paginated).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HistoryOrder {
+    /// Unconfirmed transactions first, then confirmed ones by descending height
+    #[default]
+    NewestFirst,
+    /// Confirmed transactions by ascending height, then unconfirmed ones
+    OldestFirst,
+}
+
 /// Index out of bounds error.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct IndexOutOfBoundsError {
@@ -169,6 +405,74 @@ impl fmt::Display for IndexOutOfBoundsError {
 
 impl core::error::Error for IndexOutOfBoundsError {}
 
+/// An account-level extended public key of a wallet descriptor.
+///
+/// See [`Wallet::account_xpubs`](crate::Wallet::account_xpubs).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountXpub {
+    /// The account extended public key.
+    pub xpub: Xpub,
+    /// The master fingerprint and derivation path of [`xpub`](Self::xpub).
+    ///
+    /// If the descriptor doesn't specify the key origin, this is the fingerprint of the key
+    /// itself and an empty path.
+    pub origin: KeySource,
+    /// The script type of the descriptor the key belongs to.
+    pub script_type: ScriptType,
+}
+
+impl AccountXpub {
+    /// Encode the key with the standard `xpub`/`tpub` version bytes.
+    pub fn to_xpub_string(&self) -> String {
+        self.xpub.to_string()
+    }
+
+    /// Encode the key with the [SLIP-0132] version bytes matching its script type, e.g. `zpub`
+    /// for a `wpkh()` descriptor.
+    ///
+    /// [SLIP-0132]: https://github.com/satoshilabs/slips/blob/master/slip-0132.md
+    pub fn to_slip132_string(&self) -> String {
+        slip132::encode_xpub(&self.xpub, self.script_type)
+    }
+}
+
+/// An account-level extended private key of a wallet descriptor.
+///
+/// See [`Wallet::account_xprvs`](crate::Wallet::account_xprvs).
+#[derive(Clone, PartialEq, Eq)]
+pub struct AccountXprv {
+    /// The account extended private key.
+    pub xprv: Xpriv,
+    /// The master fingerprint and derivation path of [`xprv`](Self::xprv).
+    pub origin: KeySource,
+    /// The script type of the descriptor the key belongs to.
+    pub script_type: ScriptType,
+}
+
+impl AccountXprv {
+    /// Encode the key with the standard `xprv`/`tprv` version bytes.
+    pub fn to_xprv_string(&self) -> String {
+        self.xprv.to_string()
+    }
+
+    /// Encode the key with the [SLIP-0132] version bytes matching its script type, e.g. `zprv`
+    /// for a `wpkh()` descriptor.
+    ///
+    /// [SLIP-0132]: https://github.com/satoshilabs/slips/blob/master/slip-0132.md
+    pub fn to_slip132_string(&self) -> String {
+        slip132::encode_xprv(&self.xprv, self.script_type)
+    }
+}
+
+impl fmt::Debug for AccountXprv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccountXprv")
+            .field("origin", &self.origin)
+            .field("script_type", &self.script_type)
+            .finish_non_exhaustive()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,208 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Automatic persistence of a [`PersistedWallet`] in batches.
+
+use core::fmt;
+use core::ops::Deref;
+use std::time::{Duration, Instant};
+
+use crate::{PersistedWallet, Wallet, WalletPersister};
+
+/// A [`PersistedWallet`] which persists its staged changes into its persister in batches.
+///
+/// Persisting after every change, e.g. every block applied while syncing, is slow, while
+/// persisting too rarely risks losing changes. [`AutoPersist`] owns the wallet and its persister,
+/// and persists the staged changes when changing the wallet with [`update`] leaves either:
+///
+/// * at least [`max_staged_txs`] transactions staged, or
+/// * staged changes for longer than [`max_interval`] since the last persist.
+///
+/// The changes can be persisted at any time with [`flush`], and they are persisted one last time
+/// when the [`AutoPersist`] is dropped.
+///
+/// # Errors
+///
+/// When persisting fails during [`update`], the change itself succeeded, so the error is kept and
+/// returned by the next call to [`update`] or [`flush_if_due`] instead, without doing anything
+/// else. The staged changes are kept, and are persisted again by the next flush. An error of the
+/// final flush on drop is lost, so [`flush`] should be called before dropping the wallet to
+/// handle it.
+///
+/// # Sharing between threads
+///
+/// All the methods take `&mut self`, so that an [`AutoPersist`] can be shared behind a mutex.
+/// With [`flush_if_due`], another thread can persist the changes staged for too long even when
+/// the wallet isn't updated:
+///
+/// ```rust,no_run
+/// # use std::sync::{Arc, Mutex};
+/// # use std::time::Duration;
+/// # use bdk_wallet::rusqlite::Connection;
+/// # use bdk_wallet::{AutoPersist, KeychainKind, Wallet};
+/// # let mut db = Connection::open("wallet.sqlite")?;
+/// # let wallet = Wallet::load().load_wallet(&mut db)?.expect("wallet is persisted");
+/// let wallet = Arc::new(Mutex::new(
+///     AutoPersist::new(wallet, db)
+///         .max_staged_txs(100)
+///         .max_interval(Duration::from_secs(10)),
+/// ));
+///
+/// let flusher = Arc::clone(&wallet);
+/// std::thread::spawn(move || loop {
+///     std::thread::sleep(Duration::from_secs(1));
+///     if let Err(e) = flusher.lock().unwrap().flush_if_due() {
+///         eprintln!("failed to persist wallet: {e}");
+///     }
+/// });
+///
+/// let address = wallet
+///     .lock()
+///     .unwrap()
+///     .update(|wallet| wallet.reveal_next_address(KeychainKind::External))?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// [`update`]: Self::update
+/// [`flush`]: Self::flush
+/// [`flush_if_due`]: Self::flush_if_due
+/// [`max_staged_txs`]: Self::max_staged_txs
+/// [`max_interval`]: Self::max_interval
+pub struct AutoPersist<P: WalletPersister> {
+    wallet: PersistedWallet<P>,
+    persister: P,
+    max_staged_txs: Option<usize>,
+    max_interval: Option<Duration>,
+    last_flush: Instant,
+    error: Option<P::Error>,
+}
+
+impl<P> fmt::Debug for AutoPersist<P>
+where
+    P: WalletPersister + fmt::Debug,
+    P::Error: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutoPersist")
+            .field("wallet", &self.wallet)
+            .field("persister", &self.persister)
+            .field("max_staged_txs", &self.max_staged_txs)
+            .field("max_interval", &self.max_interval)
+            .field("last_flush", &self.last_flush)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<P: WalletPersister> AutoPersist<P> {
+    /// Persist the changes of `wallet` into `persister`.
+    ///
+    /// Without thresholds, the changes are only persisted by [`flush`](Self::flush) and on drop.
+    pub fn new(wallet: PersistedWallet<P>, persister: P) -> Self {
+        Self {
+            wallet,
+            persister,
+            max_staged_txs: None,
+            max_interval: None,
+            last_flush: Instant::now(),
+            error: None,
+        }
+    }
+
+    /// Persist once `max_staged_txs` transactions are staged.
+    pub fn max_staged_txs(mut self, max_staged_txs: usize) -> Self {
+        self.max_staged_txs = Some(max_staged_txs);
+        self
+    }
+
+    /// Persist the staged changes once `max_interval` has elapsed since the last persist.
+    pub fn max_interval(mut self, max_interval: Duration) -> Self {
+        self.max_interval = Some(max_interval);
+        self
+    }
+
+    /// The persister the wallet is persisted into.
+    pub fn persister(&self) -> &P {
+        &self.persister
+    }
+
+    /// Number of transactions staged since the last persist.
+    pub fn staged_txs(&self) -> usize {
+        self.wallet
+            .staged()
+            .map_or(0, |changeset| changeset.tx_graph.txs.len())
+    }
+
+    /// Change the wallet with `f`, and persist the staged changes if a threshold is reached.
+    ///
+    /// Returns the result of `f`, or the error of a previous failed persist without calling `f`.
+    /// A failure to persist the changes of `f` is returned by the next call.
+    pub fn update<R>(&mut self, f: impl FnOnce(&mut Wallet) -> R) -> Result<R, P::Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let result = f(&mut self.wallet);
+        if self.is_due() {
+            if let Err(e) = self.flush() {
+                self.error = Some(e);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Persist the staged changes if a threshold is reached.
+    ///
+    /// Returns whether any changes were persisted, or the error of a previous failed persist.
+    pub fn flush_if_due(&mut self) -> Result<bool, P::Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if !self.is_due() {
+            return Ok(false);
+        }
+        self.flush()
+    }
+
+    /// Persist the staged changes now.
+    ///
+    /// Returns whether any changes were persisted. An error of a previous failed persist is
+    /// discarded, since its changes are persisted again.
+    pub fn flush(&mut self) -> Result<bool, P::Error> {
+        self.error = None;
+        let persisted = self.wallet.persist(&mut self.persister)?;
+        self.last_flush = Instant::now();
+        Ok(persisted)
+    }
+
+    fn is_due(&self) -> bool {
+        if self.wallet.staged().is_none() {
+            return false;
+        }
+        self.max_staged_txs
+            .is_some_and(|max_staged_txs| self.staged_txs() >= max_staged_txs)
+            || self
+                .max_interval
+                .is_some_and(|max_interval| self.last_flush.elapsed() >= max_interval)
+    }
+}
+
+impl<P: WalletPersister> Deref for AutoPersist<P> {
+    type Target = PersistedWallet<P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.wallet
+    }
+}
+
+impl<P: WalletPersister> Drop for AutoPersist<P> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,85 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Broadcasting transactions
+//!
+//! This module defines the [`Broadcaster`] and [`AsyncBroadcaster`] traits, implemented by chain
+//! sources able to send a transaction to the Bitcoin network. They are used by
+//! [`Wallet::broadcast_and_apply`](crate::Wallet::broadcast_and_apply) to broadcast a
+//! transaction and insert it in the wallet as unconfirmed in one step.
+//!
+//! The Electrum, Esplora and Bitcoin Core RPC clients live in their own crates, which don't
+//! implement these traits, and this crate doesn't depend on them. Until they do, wrap a client
+//! in a type of your own to implement the traits for it:
+//!
+//! ```
+//! # use bdk_wallet::bitcoin::{Transaction, Txid};
+//! use bdk_wallet::broadcast::{BroadcastError, Broadcaster};
+//!
+//! struct MyClient;
+//!
+//! impl MyClient {
+//!     fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, String> {
+//!         // ...
+//! #       Ok(tx.compute_txid())
+//!     }
+//! }
+//!
+//! impl Broadcaster for MyClient {
+//!     fn broadcast(&self, tx: &Transaction) -> Result<Txid, BroadcastError> {
+//!         self.send_raw_transaction(tx)
+//!             .map_err(BroadcastError::Rejected)
+//!     }
+//! }
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::fmt;
+
+use bitcoin::{Transaction, Txid};
+
+use super::persisted::FutureResult;
+
+/// Errors returned by a [`Broadcaster`] or an [`AsyncBroadcaster`].
+#[derive(Debug)]
+pub enum BroadcastError {
+    /// The transaction was rejected, with the reason given by the chain source
+    Rejected(String),
+    /// The chain source couldn't be reached
+    Backend(Box<dyn core::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for BroadcastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "Transaction rejected: {reason}"),
+            Self::Backend(err) => write!(f, "Chain source error: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for BroadcastError {}
+
+/// Trait for chain sources that can broadcast a transaction.
+///
+/// For an async version, use [`AsyncBroadcaster`].
+pub trait Broadcaster {
+    /// Broadcast `tx` to the network and return its txid.
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, BroadcastError>;
+}
+
+/// Async trait for chain sources that can broadcast a transaction.
+///
+/// For a blocking version, use [`Broadcaster`].
+pub trait AsyncBroadcaster {
+    /// Broadcast `tx` to the network and return its txid.
+    fn broadcast<'a>(&'a self, tx: &'a Transaction) -> FutureResult<'a, Txid, BroadcastError>;
+}
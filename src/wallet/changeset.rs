@@ -1,10 +1,18 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
 use bdk_chain::{
-    indexed_tx_graph, keychain_txout, local_chain, tx_graph, ConfirmationBlockTime, Merge,
+    indexed_tx_graph, keychain_txout, local_chain, tx_graph, ConfirmationBlockTime, DescriptorId,
+    Merge,
 };
+use bitcoin::hashes::sha256;
+use bitcoin::Transaction;
+use core::fmt;
 use miniscript::{Descriptor, DescriptorPublicKey};
 use serde::{Deserialize, Serialize};
 
-use crate::locked_outpoints;
+use crate::collections::BTreeMap;
+#[cfg(feature = "rusqlite")]
+use crate::SqliteError;
+use crate::{labels, locked_outpoints, metadata};
 
 type IndexedTxGraphChangeSet =
     indexed_tx_graph::ChangeSet<ConfirmationBlockTime, keychain_txout::ChangeSet>;
@@ -87,11 +95,18 @@ type IndexedTxGraphChangeSet =
 /// implement [`Default`] so that when reading N-1 data, absent fields are populated with default
 /// values.
 ///
-/// Limited forward compatibility is provided for downgrades: version N-1 will successfully
-/// deserialize version N data without errors by ignoring unknown fields. Users should be aware that
-/// features introduced in version N will not be available when downgrading to N-1, and that
-/// downgrading can result in loss of data if not backed up. For this reason we recommend carefully
-/// planning major upgrades and backing up necessary data to avoid compatibility issues.
+/// The backends of this crate record the version of the data they write: the SQLite wallet tables
+/// by their schema version, and the multi-wallet SQLite tables, the PostgreSQL persister and the
+/// [`VersionedChangeSets`](crate::VersionedChangeSets) entries of a
+/// [`bdk_file_store`](https://docs.rs/bdk_file_store) by the
+/// [`CURRENT_VERSION`](Self::CURRENT_VERSION) of every changeset. Older changesets are upgraded
+/// step by step with the registered [`Migration`](crate::migration::Migration)s when they are
+/// loaded.
+///
+/// Downgrades are not supported: data written by a newer version of the library is rejected with
+/// an error rather than loaded without the fields it does not know about, which could then be lost
+/// by the next write. Back up the wallet data before upgrading, so that the backup can be restored
+/// if the upgrade has to be reverted.
 ///
 /// Fields can be removed using a 3-version deprecation cycle: fields are marked deprecated in
 /// version N with a reason and instructions for migrating, the field is retained in version N+1
@@ -150,6 +165,16 @@ pub struct ChangeSet {
     /// Changes to locked outpoints.
     #[serde(default)]
     pub locked_outpoints: locked_outpoints::ChangeSet,
+    /// Changes to output labels.
+    #[serde(default)]
+    pub labels: labels::ChangeSet,
+    /// Changes to the wallet metadata.
+    #[serde(default)]
+    pub metadata: metadata::ChangeSet,
+    /// Digests of the SPK cache of each descriptor in the [`indexer`](Self::indexer), to detect a
+    /// corrupted cache when the wallet is loaded.
+    #[serde(default)]
+    pub spk_cache_digests: BTreeMap<DescriptorId, sha256::Hash>,
 }
 
 impl Merge for ChangeSet {
@@ -181,6 +206,15 @@ impl Merge for ChangeSet {
         // merge locked outpoints
         self.locked_outpoints.merge(other.locked_outpoints);
 
+        // merge output labels
+        self.labels.merge(other.labels);
+
+        // merge metadata
+        self.metadata.merge(other.metadata);
+
+        // the digests of the grown SPK caches replace the previous ones
+        self.spk_cache_digests.extend(other.spk_cache_digests);
+
         Merge::merge(&mut self.local_chain, other.local_chain);
         Merge::merge(&mut self.tx_graph, other.tx_graph);
         Merge::merge(&mut self.indexer, other.indexer);
@@ -194,9 +228,133 @@ impl Merge for ChangeSet {
             && self.tx_graph.is_empty()
             && self.indexer.is_empty()
             && self.locked_outpoints.is_empty()
+            && self.labels.is_empty()
+            && self.metadata.is_empty()
+            && self.spk_cache_digests.is_empty()
+    }
+}
+
+impl ChangeSet {
+    /// Version of the changeset representation written by this version of the library.
+    ///
+    /// It is incremented with every change of the fields of the changeset, and persisted by the
+    /// backends of this crate so that a changeset written by an older version can be upgraded
+    /// with the [`migration::MIGRATIONS`](crate::migration::MIGRATIONS), and one written by a newer
+    /// version is rejected.
+    ///
+    /// * 0: bdk_wallet 1.x and 2.x, with the descriptors, network, chain, transaction graph and
+    ///   indexer.
+    /// * 1: bdk_wallet 3.x, adds the [`locked_outpoints`](Self::locked_outpoints).
+    /// * 2: adds the [`labels`](Self::labels), the [`metadata`](Self::metadata) and the
+    ///   [`spk_cache_digests`](Self::spk_cache_digests).
+    ///
+    /// The entries of a `bdk_file_store::Store<ChangeSet>` are encoded without a version, so they
+    /// cannot be upgraded: a store only records the version in its first entry, and the stores
+    /// written by the released versions, which don't, are read with
+    /// [`migration::load_file_store`](crate::migration::load_file_store).
+    pub const CURRENT_VERSION: u32 = 2;
+}
+
+/// Version of the JSON representation written by [`ChangeSet::to_json`].
+///
+/// * 1: records the [`ChangeSet::CURRENT_VERSION`] of the changeset.
+const JSON_VERSION: u32 = 1;
+
+/// The JSON representation of a [`ChangeSet`], with the transactions consensus encoded as hex.
+#[derive(Serialize)]
+struct JsonChangeSet {
+    version: u32,
+    changeset_version: u32,
+    txs: Vec<String>,
+    changeset: ChangeSet,
+}
+
+impl ChangeSet {
+    /// Export the changeset as JSON, e.g. to move a wallet to another persistence backend or to
+    /// attach its state to a bug report.
+    ///
+    /// The representation is versioned so that it can be read by later versions of the library
+    /// with [`from_json`](Self::from_json). Transactions are written as consensus encoded hex.
+    pub fn to_json(&self) -> String {
+        let mut changeset = self.clone();
+        let txs = core::mem::take(&mut changeset.tx_graph.txs)
+            .into_iter()
+            .map(|tx| bitcoin::consensus::encode::serialize_hex(tx.as_ref()))
+            .collect();
+        let json = JsonChangeSet {
+            version: JSON_VERSION,
+            changeset_version: Self::CURRENT_VERSION,
+            txs,
+            changeset,
+        };
+        serde_json::to_string_pretty(&json).expect("changeset must serialize to JSON")
     }
+
+    /// Import a changeset exported with [`to_json`](Self::to_json).
+    ///
+    /// Changesets exported by older versions of the library are upgraded with the
+    /// [`migration::MIGRATIONS`](crate::migration::MIGRATIONS).
+    pub fn from_json(json: &str) -> Result<Self, ChangeSetJsonError> {
+        let JsonHeader {
+            version,
+            changeset_version,
+            txs,
+            changeset,
+        } = serde_json::from_str(json).map_err(ChangeSetJsonError::Json)?;
+        let changeset_version = match (version, changeset_version) {
+            (JSON_VERSION, Some(changeset_version)) => changeset_version,
+            _ => return Err(ChangeSetJsonError::UnsupportedVersion(version)),
+        };
+        let mut changeset = crate::migration::upgrade_changeset(changeset, changeset_version)
+            .map_err(ChangeSetJsonError::Migration)?;
+        for tx in txs {
+            let tx = bitcoin::consensus::encode::deserialize_hex::<Transaction>(&tx)
+                .map_err(ChangeSetJsonError::Tx)?;
+            changeset.tx_graph.txs.insert(Arc::new(tx));
+        }
+        Ok(changeset)
+    }
+}
+
+/// A [`JsonChangeSet`] of any version, with the changeset not decoded yet.
+#[derive(Deserialize)]
+struct JsonHeader {
+    version: u32,
+    #[serde(default)]
+    changeset_version: Option<u32>,
+    txs: Vec<String>,
+    changeset: serde_json::Value,
 }
 
+/// Error when importing a [`ChangeSet`] with [`ChangeSet::from_json`].
+#[derive(Debug)]
+pub enum ChangeSetJsonError {
+    /// The JSON is invalid.
+    Json(serde_json::Error),
+    /// The changeset was exported in a version of the representation that is not supported.
+    UnsupportedVersion(u32),
+    /// A transaction could not be decoded.
+    Tx(bitcoin::consensus::encode::FromHexError),
+    /// The changeset could not be upgraded to the [`ChangeSet::CURRENT_VERSION`].
+    Migration(crate::migration::ChangeSetMigrationError),
+}
+
+impl fmt::Display for ChangeSetJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "invalid changeset JSON: {e}"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported changeset JSON version {version}")
+            }
+            Self::Tx(e) => write!(f, "invalid transaction in changeset JSON: {e}"),
+            Self::Migration(e) => write!(f, "invalid changeset in changeset JSON: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChangeSetJsonError {}
+
 #[cfg(feature = "rusqlite")]
 impl ChangeSet {
     /// Schema name for wallet.
@@ -205,6 +363,15 @@ impl ChangeSet {
     pub const WALLET_TABLE_NAME: &'static str = "bdk_wallet";
     /// Name of table to store wallet locked outpoints.
     pub const WALLET_OUTPOINT_LOCK_TABLE_NAME: &'static str = "bdk_wallet_locked_outpoints";
+    /// Name of table to store wallet output labels.
+    pub const WALLET_OUTPUT_LABEL_TABLE_NAME: &'static str = "bdk_wallet_output_labels";
+    /// Name of table to store wallet metadata.
+    pub const WALLET_METADATA_TABLE_NAME: &'static str = "bdk_wallet_metadata";
+    /// Name of table to store the digests of the SPK cache of each descriptor.
+    pub const WALLET_SPK_CACHE_DIGEST_TABLE_NAME: &'static str = "bdk_wallet_spk_cache_digests";
+    /// Latest schema version of the wallet tables, independent of the
+    /// [`CURRENT_VERSION`](Self::CURRENT_VERSION) of the changeset.
+    const WALLET_SCHEMA_VERSION: u32 = 4;
 
     /// Get v0 sqlite [ChangeSet] schema
     pub fn schema_v0() -> alloc::string::String {
@@ -231,12 +398,79 @@ impl ChangeSet {
         )
     }
 
+    /// Get v2 sqlite [`ChangeSet`] schema. Schema v2 adds a table for output labels.
+    pub fn schema_v2() -> alloc::string::String {
+        format!(
+            "CREATE TABLE {} ( \
+                txid TEXT NOT NULL, \
+                vout INTEGER NOT NULL, \
+                label TEXT NOT NULL, \
+                PRIMARY KEY(txid, vout) \
+                ) STRICT;",
+            Self::WALLET_OUTPUT_LABEL_TABLE_NAME,
+        )
+    }
+
+    /// Get v3 sqlite [`ChangeSet`] schema. Schema v3 adds a table for wallet metadata.
+    pub fn schema_v3() -> alloc::string::String {
+        format!(
+            "CREATE TABLE {} ( \
+                key TEXT PRIMARY KEY NOT NULL, \
+                value BLOB NOT NULL \
+                ) STRICT;",
+            Self::WALLET_METADATA_TABLE_NAME,
+        )
+    }
+
+    /// Get v4 sqlite [`ChangeSet`] schema. Schema v4 adds a table for the digests of the SPK
+    /// cache of each descriptor.
+    pub fn schema_v4() -> alloc::string::String {
+        format!(
+            "CREATE TABLE {} ( \
+                descriptor_id TEXT PRIMARY KEY NOT NULL, \
+                digest BLOB NOT NULL \
+                ) STRICT;",
+            Self::WALLET_SPK_CACHE_DIGEST_TABLE_NAME,
+        )
+    }
+
     /// Initialize sqlite tables for wallet tables.
+    ///
+    /// The tables are migrated from older schema versions. If they were created by a newer version
+    /// of the library, the [`SqliteError`] of [`init_sqlite_tables_checked`] is returned as a
+    /// [`ToSqlConversionFailure`](chain::rusqlite::Error::ToSqlConversionFailure).
+    ///
+    /// [`init_sqlite_tables_checked`]: Self::init_sqlite_tables_checked
     pub fn init_sqlite_tables(db_tx: &chain::rusqlite::Transaction) -> chain::rusqlite::Result<()> {
+        Ok(Self::init_sqlite_tables_checked(db_tx)?)
+    }
+
+    /// Initialize sqlite tables for wallet tables.
+    ///
+    /// The tables are migrated from older schema versions, and [`SqliteError::NewerSchema`] is
+    /// returned if they were created by a newer version of the library, with a schema version this
+    /// version does not know.
+    pub fn init_sqlite_tables_checked(
+        db_tx: &chain::rusqlite::Transaction,
+    ) -> Result<(), SqliteError> {
+        if let Some(version) = Self::sqlite_version(db_tx)? {
+            if version > Self::WALLET_SCHEMA_VERSION {
+                return Err(SqliteError::NewerSchema(version));
+            }
+        }
+
+        let schemas = [
+            Self::schema_v0(),
+            Self::schema_v1(),
+            Self::schema_v2(),
+            Self::schema_v3(),
+            Self::schema_v4(),
+        ];
+        debug_assert_eq!(schemas.len(), Self::WALLET_SCHEMA_VERSION as usize + 1);
         crate::rusqlite_impl::migrate_schema(
             db_tx,
             Self::WALLET_SCHEMA_NAME,
-            &[&Self::schema_v0(), &Self::schema_v1()],
+            &schemas.each_ref().map(String::as_str),
         )?;
 
         bdk_chain::local_chain::ChangeSet::init_sqlite_tables(db_tx)?;
@@ -246,8 +480,53 @@ impl ChangeSet {
         Ok(())
     }
 
+    /// The schema version of the wallet tables, or `None` if they don't exist.
+    fn sqlite_version(
+        db_tx: &chain::rusqlite::Transaction,
+    ) -> chain::rusqlite::Result<Option<u32>> {
+        use crate::rusqlite_impl::SCHEMAS_TABLE_NAME;
+        use chain::rusqlite::OptionalExtension;
+
+        let schemas_exist: bool = db_tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type='table' AND name=?1)",
+            [SCHEMAS_TABLE_NAME],
+            |row| row.get(0),
+        )?;
+        if !schemas_exist {
+            return Ok(None);
+        }
+        db_tx
+            .query_row(
+                &format!("SELECT version FROM {SCHEMAS_TABLE_NAME} WHERE name=?1"),
+                [Self::WALLET_SCHEMA_NAME],
+                |row| row.get::<_, u32>(0),
+            )
+            .optional()
+    }
+
+    /// Recover a [`ChangeSet`] from sqlite database without writing to it, e.g. through a read-only
+    /// connection.
+    ///
+    /// Returns an empty changeset if the wallet tables don't exist, and an error if they are not
+    /// of the latest schema version, as they cannot be migrated.
+    pub fn from_sqlite_read_only(
+        db_tx: &chain::rusqlite::Transaction,
+    ) -> Result<Self, SqliteError> {
+        match Self::sqlite_version(db_tx)? {
+            None => Ok(Self::default()),
+            Some(version) if version == Self::WALLET_SCHEMA_VERSION => {
+                Ok(Self::from_sqlite(db_tx)?)
+            }
+            Some(version) if version > Self::WALLET_SCHEMA_VERSION => {
+                Err(SqliteError::NewerSchema(version))
+            }
+            Some(version) => Err(SqliteError::UpgradeRequired(version)),
+        }
+    }
+
     /// Recover a [`ChangeSet`] from sqlite database.
     pub fn from_sqlite(db_tx: &chain::rusqlite::Transaction) -> chain::rusqlite::Result<Self> {
+        use bitcoin::hashes::Hash;
         use bitcoin::{OutPoint, Txid};
         use chain::rusqlite::OptionalExtension;
         use chain::Impl;
@@ -293,6 +572,58 @@ impl ChangeSet {
             locked_outpoints.insert(outpoint, true);
         }
 
+        // Select output labels.
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT txid, vout, label FROM {}",
+            Self::WALLET_OUTPUT_LABEL_TABLE_NAME,
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, Impl<Txid>>("txid")?,
+                row.get::<_, u32>("vout")?,
+                row.get::<_, alloc::string::String>("label")?,
+            ))
+        })?;
+        let labels = &mut changeset.labels.outputs;
+        for row in rows {
+            let (Impl(txid), vout, label) = row?;
+            labels.insert(OutPoint::new(txid, vout), Some(label));
+        }
+
+        // Select metadata.
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT key, value FROM {}",
+            Self::WALLET_METADATA_TABLE_NAME,
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, alloc::string::String>("key")?,
+                row.get::<_, alloc::vec::Vec<u8>>("value")?,
+            ))
+        })?;
+        let metadata = &mut changeset.metadata.entries;
+        for row in rows {
+            let (key, value) = row?;
+            metadata.insert(key, Some(value));
+        }
+
+        // Select SPK cache digests.
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT descriptor_id, digest FROM {}",
+            Self::WALLET_SPK_CACHE_DIGEST_TABLE_NAME,
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, Impl<DescriptorId>>("descriptor_id")?,
+                row.get::<_, [u8; 32]>("digest")?,
+            ))
+        })?;
+        let spk_cache_digests = &mut changeset.spk_cache_digests;
+        for row in rows {
+            let (Impl(descriptor_id), digest) = row?;
+            spk_cache_digests.insert(descriptor_id, sha256::Hash::from_byte_array(digest));
+        }
+
         changeset.local_chain = local_chain::ChangeSet::from_sqlite(db_tx)?;
         changeset.tx_graph = tx_graph::ChangeSet::<_>::from_sqlite(db_tx)?;
         changeset.indexer = keychain_txout::ChangeSet::from_sqlite(db_tx)?;
@@ -301,10 +632,14 @@ impl ChangeSet {
     }
 
     /// Persist [`ChangeSet`] to sqlite database.
+    ///
+    /// Rows are written with cached multi-row upserts, so a large changeset, e.g. after a full
+    /// scan, should be persisted within a single transaction `db_tx`.
     pub fn persist_to_sqlite(
         &self,
         db_tx: &chain::rusqlite::Transaction,
     ) -> chain::rusqlite::Result<()> {
+        use bitcoin::hashes::Hash;
         use chain::rusqlite::named_params;
         use chain::Impl;
 
@@ -365,9 +700,66 @@ impl ChangeSet {
             }
         }
 
-        self.local_chain.persist_to_sqlite(db_tx)?;
-        self.tx_graph.persist_to_sqlite(db_tx)?;
-        self.indexer.persist_to_sqlite(db_tx)?;
+        // Insert, update or delete output labels.
+        let mut upsert_stmt = db_tx.prepare_cached(&format!(
+            "INSERT INTO {}(txid, vout, label) VALUES(:txid, :vout, :label) ON CONFLICT(txid, vout) DO UPDATE SET label=:label",
+            Self::WALLET_OUTPUT_LABEL_TABLE_NAME,
+        ))?;
+        let mut delete_stmt = db_tx.prepare_cached(&format!(
+            "DELETE FROM {} WHERE txid=:txid AND vout=:vout",
+            Self::WALLET_OUTPUT_LABEL_TABLE_NAME,
+        ))?;
+        for (&outpoint, label) in &self.labels.outputs {
+            let bitcoin::OutPoint { txid, vout } = outpoint;
+            match label {
+                Some(label) => upsert_stmt.execute(named_params! {
+                    ":txid": Impl(txid),
+                    ":vout": vout,
+                    ":label": label,
+                })?,
+                None => delete_stmt.execute(named_params! {
+                    ":txid": Impl(txid),
+                    ":vout": vout,
+                })?,
+            };
+        }
+
+        // Insert, update or delete metadata.
+        let mut upsert_stmt = db_tx.prepare_cached(&format!(
+            "INSERT INTO {}(key, value) VALUES(:key, :value) ON CONFLICT(key) DO UPDATE SET value=:value",
+            Self::WALLET_METADATA_TABLE_NAME,
+        ))?;
+        let mut delete_stmt = db_tx.prepare_cached(&format!(
+            "DELETE FROM {} WHERE key=:key",
+            Self::WALLET_METADATA_TABLE_NAME,
+        ))?;
+        for (key, value) in &self.metadata.entries {
+            match value {
+                Some(value) => upsert_stmt.execute(named_params! {
+                    ":key": key,
+                    ":value": value,
+                })?,
+                None => delete_stmt.execute(named_params! {
+                    ":key": key,
+                })?,
+            };
+        }
+
+        // Insert or update SPK cache digests.
+        let mut upsert_stmt = db_tx.prepare_cached(&format!(
+            "INSERT INTO {}(descriptor_id, digest) VALUES(:descriptor_id, :digest) ON CONFLICT(descriptor_id) DO UPDATE SET digest=:digest",
+            Self::WALLET_SPK_CACHE_DIGEST_TABLE_NAME,
+        ))?;
+        for (&descriptor_id, digest) in &self.spk_cache_digests {
+            upsert_stmt.execute(named_params! {
+                ":descriptor_id": Impl(descriptor_id),
+                ":digest": digest.to_byte_array(),
+            })?;
+        }
+
+        crate::wallet::sqlite::persist_local_chain(db_tx, &self.local_chain)?;
+        crate::wallet::sqlite::persist_tx_graph(db_tx, &self.tx_graph)?;
+        crate::wallet::sqlite::persist_indexer(db_tx, &self.indexer)?;
         Ok(())
     }
 }
@@ -417,3 +809,21 @@ impl From<locked_outpoints::ChangeSet> for ChangeSet {
         }
     }
 }
+
+impl From<metadata::ChangeSet> for ChangeSet {
+    fn from(metadata: metadata::ChangeSet) -> Self {
+        Self {
+            metadata,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<labels::ChangeSet> for ChangeSet {
+    fn from(labels: labels::ChangeSet) -> Self {
+        Self {
+            labels,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,175 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Combining PSBTs signed by different cosigners
+//!
+//! This module contains the types returned by [`Wallet::combine_psbts`](crate::Wallet::combine_psbts).
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use bitcoin::psbt::{self, Input};
+use bitcoin::taproot::TapLeafHash;
+use bitcoin::{PublicKey, XOnlyPublicKey};
+
+use crate::descriptor::DescriptorError;
+use crate::types::KeychainKind;
+use crate::wallet::signer::SignerError;
+
+/// Outcome of [`Wallet::combine_psbts`](crate::Wallet::combine_psbts).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombineReport {
+    /// Fields that had different values in the combined PSBTs, the first value found is kept.
+    pub conflicts: Vec<Conflict>,
+    /// Signatures collected for each input, `None` for inputs not spending a wallet output.
+    pub inputs: Vec<Option<InputSignatures>>,
+    /// Whether the combined PSBT was finalized.
+    pub finalized: bool,
+}
+
+impl CombineReport {
+    /// Whether every wallet input has collected enough signatures to be finalized.
+    pub fn is_complete(&self) -> bool {
+        self.inputs
+            .iter()
+            .flatten()
+            .all(InputSignatures::is_complete)
+    }
+}
+
+/// Signatures collected for an input spending a wallet output.
+///
+/// Signatures are counted against the threshold at the top level of the spending
+/// [`Policy`](crate::descriptor::Policy) of the descriptor, e.g. the `2` of a `multi(2,...)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputSignatures {
+    /// The keychain of the spent output.
+    pub keychain: KeychainKind,
+    /// Number of items of the policy satisfied by the signatures in the PSBT.
+    pub signatures: usize,
+    /// Number of items of the policy that must be satisfied.
+    pub threshold: usize,
+    /// Whether the input was already finalized before being combined.
+    pub is_final: bool,
+}
+
+impl InputSignatures {
+    /// Whether the input has enough signatures to be finalized.
+    pub fn is_complete(&self) -> bool {
+        self.is_final || self.signatures >= self.threshold
+    }
+}
+
+/// A field of an input that had different values in two of the combined PSBTs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Index of the input.
+    pub input: usize,
+    /// Index of the PSBT whose value was discarded, in the order they were passed to
+    /// [`Wallet::combine_psbts`](crate::Wallet::combine_psbts).
+    pub psbt: usize,
+    /// The conflicting field.
+    pub field: ConflictingField,
+}
+
+/// The conflicting field of a [`Conflict`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictingField {
+    /// The finalized `scriptSig`.
+    FinalScriptSig,
+    /// The finalized witness.
+    FinalScriptWitness,
+    /// The ECDSA signature of a key.
+    PartialSig(PublicKey),
+    /// The taproot key spend signature.
+    TapKeySig,
+    /// The taproot script spend signature of a key for a leaf.
+    TapScriptSig(XOnlyPublicKey, TapLeafHash),
+}
+
+/// Errors returned by [`Wallet::combine_psbts`](crate::Wallet::combine_psbts).
+#[derive(Debug)]
+pub enum CombineError {
+    /// The PSBT at this index is for a different unsigned transaction than the base PSBT.
+    UnsignedTxMismatch(usize),
+    /// The PSBTs couldn't be combined.
+    Psbt(psbt::Error),
+    /// Error while extracting the spending policy of an input.
+    Descriptor(DescriptorError),
+    /// Error while finalizing the combined PSBT.
+    Signer(SignerError),
+}
+
+impl fmt::Display for CombineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsignedTxMismatch(index) => write!(
+                f,
+                "PSBT {index} is for a different unsigned transaction than the base PSBT"
+            ),
+            Self::Psbt(err) => write!(f, "PSBT error: {err}"),
+            Self::Descriptor(err) => write!(f, "Descriptor error: {err}"),
+            Self::Signer(err) => write!(f, "Signer error: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for CombineError {}
+
+/// Record the fields of `other` that conflict with `base` and remove them from `other`, so that
+/// combining keeps the values of `base`.
+pub(crate) fn take_conflicts(
+    input: usize,
+    psbt: usize,
+    base: &Input,
+    other: &mut Input,
+    conflicts: &mut Vec<Conflict>,
+) {
+    let mut conflict = |field| conflicts.push(Conflict { input, psbt, field });
+
+    if base.final_script_sig.is_some()
+        && other.final_script_sig.is_some()
+        && base.final_script_sig != other.final_script_sig
+    {
+        other.final_script_sig = None;
+        conflict(ConflictingField::FinalScriptSig);
+    }
+    if base.final_script_witness.is_some()
+        && other.final_script_witness.is_some()
+        && base.final_script_witness != other.final_script_witness
+    {
+        other.final_script_witness = None;
+        conflict(ConflictingField::FinalScriptWitness);
+    }
+    other.partial_sigs.retain(|pk, sig| {
+        let conflicting = base.partial_sigs.get(pk).is_some_and(|s| s != sig);
+        if conflicting {
+            conflict(ConflictingField::PartialSig(*pk));
+        }
+        !conflicting
+    });
+    if base.tap_key_sig.is_some()
+        && other.tap_key_sig.is_some()
+        && base.tap_key_sig != other.tap_key_sig
+    {
+        other.tap_key_sig = None;
+        conflict(ConflictingField::TapKeySig);
+    }
+    other.tap_script_sigs.retain(|&(pk, leaf_hash), sig| {
+        let conflicting = base
+            .tap_script_sigs
+            .get(&(pk, leaf_hash))
+            .is_some_and(|s| s != sig);
+        if conflicting {
+            conflict(ConflictingField::TapScriptSig(pk, leaf_hash));
+        }
+        !conflicting
+    });
+}
@@ -0,0 +1,883 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Encrypted [`bdk_file_store`] persistence.
+//!
+//! [`EncryptedStore`] wraps a [`bdk_file_store::Store`] and encrypts every appended [`ChangeSet`]
+//! with XChaCha20-Poly1305, under a user-provided 256-bit key or a key derived from a passphrase
+//! with Argon2id.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use bdk_chain::Merge;
+use bdk_file_store::{Store, StoreError, StoreErrorWithDump};
+use bitcoin::key::rand::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::migration::{self, ChangeSetMigrationError};
+use crate::{ChangeSet, WalletPersister};
+
+/// Length in bytes of the key used by [`EncryptedStore`].
+pub const ENCRYPTION_KEY_LEN: usize = 32;
+
+const NONCE_LEN: usize = 24;
+
+const SALT_LEN: usize = 16;
+
+/// Length of the salt and the three Argon2 cost parameters written after the magic bytes.
+const KDF_HEADER_LEN: usize = SALT_LEN + 12;
+
+/// Maximum Argon2 memory cost in KiB (1 GiB) accepted from the header of a store.
+pub const MAX_KDF_M_COST: u32 = 1024 * 1024;
+
+/// Maximum Argon2 number of iterations accepted from the header of a store.
+pub const MAX_KDF_T_COST: u32 = 10;
+
+/// Maximum Argon2 degree of parallelism accepted from the header of a store.
+pub const MAX_KDF_P_COST: u32 = 16;
+
+/// A single encrypted changeset as written to the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptedEntry {
+    /// The [`ChangeSet::CURRENT_VERSION`] the changeset was written with, authenticated with it.
+    version: u32,
+    nonce: [u8; NONCE_LEN],
+    ciphertext: Vec<u8>,
+}
+
+/// The entries of an [`EncryptedStore`].
+///
+/// Ciphertexts cannot be merged, so merging simply collects the entries in the order they were
+/// appended. They are decrypted and aggregated into a [`ChangeSet`] by [`EncryptedStore::dump`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct EncryptedEntries(Vec<EncryptedEntry>);
+
+impl Merge for EncryptedEntries {
+    fn merge(&mut self, other: Self) {
+        self.0.extend(other.0)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The salt and Argon2id parameters used to derive the key of a store from a passphrase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfHeader {
+    salt: [u8; SALT_LEN],
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl KdfHeader {
+    /// Parameters with a fresh random salt.
+    fn new(m_cost: u32, t_cost: u32, p_cost: u32) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        bitcoin::key::rand::thread_rng().fill_bytes(&mut salt);
+        Self {
+            salt,
+            m_cost,
+            t_cost,
+            p_cost,
+        }
+    }
+
+    /// Read the parameters following `magic` at the start of the file at `file_path`.
+    fn read(magic: &[u8], file_path: &Path) -> Result<Self, EncryptedStoreError> {
+        let mut header = vec![0u8; magic.len() + KDF_HEADER_LEN];
+        File::open(file_path)
+            .and_then(|mut file| file.read_exact(&mut header))
+            .map_err(|e| EncryptedStoreError::Store(StoreError::Io(e)))?;
+        let (file_magic, bytes) = header.split_at(magic.len());
+        if file_magic != magic {
+            return Err(EncryptedStoreError::Store(StoreError::InvalidMagicBytes {
+                got: file_magic.to_vec(),
+                expected: magic.to_vec(),
+            }));
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().expect("4 bytes"));
+        let kdf = Self {
+            salt: bytes[..SALT_LEN].try_into().expect("SALT_LEN bytes"),
+            m_cost: u32_at(SALT_LEN),
+            t_cost: u32_at(SALT_LEN + 4),
+            p_cost: u32_at(SALT_LEN + 8),
+        };
+        kdf.check_costs()?;
+        Ok(kdf)
+    }
+
+    /// Check the costs against the maxima, as the header is not authenticated and a tampered file
+    /// could otherwise make the key derivation exhaust memory or run for hours.
+    fn check_costs(&self) -> Result<(), EncryptedStoreError> {
+        if self.m_cost > MAX_KDF_M_COST
+            || self.t_cost > MAX_KDF_T_COST
+            || self.p_cost > MAX_KDF_P_COST
+        {
+            return Err(EncryptedStoreError::KdfCosts {
+                m_cost: self.m_cost,
+                t_cost: self.t_cost,
+                p_cost: self.p_cost,
+            });
+        }
+        Ok(())
+    }
+
+    /// The magic bytes of the underlying [`Store`]: `magic` followed by the parameters.
+    fn store_magic(&self, magic: &[u8]) -> Vec<u8> {
+        let mut store_magic = magic.to_vec();
+        store_magic.extend_from_slice(&self.salt);
+        for cost in [self.m_cost, self.t_cost, self.p_cost] {
+            store_magic.extend_from_slice(&cost.to_le_bytes());
+        }
+        store_magic
+    }
+
+    fn derive_key(
+        &self,
+        passphrase: &str,
+    ) -> Result<Zeroizing<[u8; ENCRYPTION_KEY_LEN]>, EncryptedStoreError> {
+        self.check_costs()?;
+        let params = Params::new(
+            self.m_cost,
+            self.t_cost,
+            self.p_cost,
+            Some(ENCRYPTION_KEY_LEN),
+        )
+        .map_err(EncryptedStoreError::Kdf)?;
+        let mut key = Zeroizing::new([0u8; ENCRYPTION_KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &self.salt, &mut *key)
+            .map_err(EncryptedStoreError::Kdf)?;
+        Ok(key)
+    }
+}
+
+/// Encrypts and decrypts the entries of a store, authenticating its magic bytes and the position
+/// of each entry.
+struct EntryCipher {
+    cipher: XChaCha20Poly1305,
+    magic: Vec<u8>,
+    /// Index of the next entry to be appended.
+    next_index: u64,
+}
+
+impl EntryCipher {
+    fn new(magic: &[u8], key: &[u8; ENCRYPTION_KEY_LEN]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key)),
+            magic: magic.to_vec(),
+            next_index: 0,
+        }
+    }
+
+    /// The associated data of the entry at `index` of changeset `version`: the magic bytes
+    /// followed by the index and the version.
+    fn aad(&self, index: u64, version: u32) -> Vec<u8> {
+        let mut aad = self.magic.clone();
+        aad.extend_from_slice(&index.to_le_bytes());
+        aad.extend_from_slice(&version.to_le_bytes());
+        aad
+    }
+
+    /// Encrypt `changeset` as the entry at [`next_index`](Self::next_index).
+    fn encrypt(&self, changeset: &ChangeSet) -> Result<EncryptedEntries, EncryptedStoreError> {
+        let plaintext =
+            Zeroizing::new(serde_json::to_vec(changeset).map_err(EncryptedStoreError::Serde)?);
+        let mut nonce = [0u8; NONCE_LEN];
+        bitcoin::key::rand::thread_rng().fill_bytes(&mut nonce);
+        let version = ChangeSet::CURRENT_VERSION;
+        let aad = self.aad(self.next_index, version);
+        let payload = Payload {
+            msg: &plaintext,
+            aad: &aad,
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| EncryptedStoreError::Encrypt)?;
+        Ok(EncryptedEntries(vec![EncryptedEntry {
+            version,
+            nonce,
+            ciphertext,
+        }]))
+    }
+
+    /// Decrypt and aggregate `entries`, which must be the entries of the store from the first one.
+    ///
+    /// Each entry only decrypts at its own index, so an entry that was removed, duplicated or
+    /// moved makes the entries from it fail with [`EncryptedStoreError::Decrypt`].
+    fn decrypt_entries(
+        &self,
+        entries: Option<EncryptedEntries>,
+    ) -> Result<Option<ChangeSet>, EncryptedStoreError> {
+        let mut aggregate = Option::<ChangeSet>::None;
+        for (index, entry) in (0..).zip(entries.unwrap_or_default().0) {
+            let aad = self.aad(index, entry.version);
+            let payload = Payload {
+                msg: &entry.ciphertext,
+                aad: &aad,
+            };
+            let plaintext = Zeroizing::new(
+                self.cipher
+                    .decrypt(XNonce::from_slice(&entry.nonce), payload)
+                    .map_err(|_| EncryptedStoreError::Decrypt)?,
+            );
+            let value = serde_json::from_slice(&plaintext).map_err(EncryptedStoreError::Serde)?;
+            let changeset = migration::upgrade_changeset(value, entry.version)
+                .map_err(EncryptedStoreError::Migration)?;
+            match &mut aggregate {
+                Some(aggregate) => aggregate.merge(changeset),
+                aggregate => *aggregate = Some(changeset),
+            }
+        }
+        Ok(aggregate.filter(|changeset| !changeset.is_empty()))
+    }
+
+    /// Turn an error reading the store into an [`EncryptedStoreError`], decrypting the entries
+    /// read before an undecodable one.
+    fn store_error(&self, err: StoreErrorWithDump<EncryptedEntries>) -> EncryptedStoreError {
+        match err.error {
+            StoreError::Bincode(_) => match self.decrypt_entries(err.changeset.map(|c| *c)) {
+                Ok(changeset) => EncryptedStoreError::Corrupted {
+                    changeset: changeset.map(Box::new),
+                    error: err.error,
+                },
+                Err(e) => e,
+            },
+            error => EncryptedStoreError::Store(error),
+        }
+    }
+}
+
+/// Persists an append-only list of encrypted [`ChangeSet`]s to a single file.
+///
+/// The file has the same layout as a [`bdk_file_store::Store`]: the `magic` bytes are written in
+/// cleartext at the start of the file so the format can be detected, followed by the appended
+/// entries. Each entry holds a [`ChangeSet`] encrypted with XChaCha20-Poly1305 under a fresh
+/// random nonce, with the `magic` bytes and the index of the entry as associated data, so that
+/// entries can't be removed, duplicated or reordered without failing to load. Whole entries
+/// removed from the end of the file can't be detected. Each entry also records the
+/// [`ChangeSet::CURRENT_VERSION`] it was written with, so that it is upgraded with the
+/// [`migration::MIGRATIONS`] from its own version when loaded.
+///
+/// The key is either given directly, or derived from a passphrase with Argon2id by the
+/// `*_with_passphrase` constructors. In the latter case the random salt and the cost parameters of
+/// the derivation are written in cleartext after the `magic` bytes, and authenticated with them.
+///
+/// An empty changeset is encrypted and written when the file is created, so that loading with the
+/// wrong key fails with [`EncryptedStoreError::Decrypt`] even before any changes are persisted.
+///
+/// ```rust,no_run
+/// # use bdk_wallet::{EncryptedStore, KeychainKind, Wallet};
+/// # let (external_desc, internal_desc) = ("", "");
+/// const MAGIC: &[u8] = b"my_encrypted_wallet";
+/// let key = [42u8; 32]; // use a secret key, e.g. derived from a passphrase
+///
+/// let (mut store, changeset) = EncryptedStore::load_or_create(MAGIC, "wallet.db", &key)?;
+/// let mut wallet = match changeset {
+///     Some(changeset) => Wallet::load().load_wallet_no_persist(changeset)?.expect("has data"),
+///     None => Wallet::create(external_desc, internal_desc).create_wallet_no_persist()?,
+/// };
+/// wallet.reveal_next_address(KeychainKind::External);
+/// if let Some(changeset) = wallet.take_staged() {
+///     store.append(&changeset)?;
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct EncryptedStore {
+    store: Store<EncryptedEntries>,
+    cipher: EntryCipher,
+}
+
+impl fmt::Debug for EncryptedStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedStore")
+            .field("store", &self.store)
+            .field("magic", &self.cipher.magic)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EncryptedStore {
+    /// Create a new [`EncryptedStore`] file; error if the file exists.
+    ///
+    /// `magic` is the cleartext prefix of the new file, which is checked when loading the store
+    /// in the future with [`load`](Self::load). Changesets are encrypted with `key`.
+    pub fn create<P>(
+        magic: &[u8],
+        file_path: P,
+        key: &[u8; ENCRYPTION_KEY_LEN],
+    ) -> Result<Self, EncryptedStoreError>
+    where
+        P: AsRef<Path>,
+    {
+        let store = Store::create(magic, file_path).map_err(EncryptedStoreError::Store)?;
+        let mut encrypted_store = Self {
+            store,
+            cipher: EntryCipher::new(magic, key),
+        };
+        let key_check = encrypted_store.cipher.encrypt(&ChangeSet::default())?;
+        encrypted_store
+            .store
+            .append(&key_check)
+            .map_err(EncryptedStoreError::Write)?;
+        encrypted_store.cipher.next_index += 1;
+        Ok(encrypted_store)
+    }
+
+    /// Load an existing [`EncryptedStore`] and the aggregate of its changesets.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`EncryptedStoreError::Decrypt`] if any entry cannot be authenticated under
+    /// `key`, which is usually the result of using the wrong key. The file is left untouched.
+    ///
+    /// Fails with [`EncryptedStoreError::Corrupted`] if an entry cannot be read, e.g. because it
+    /// was only partially written when the process crashed. The error holds the aggregate of the
+    /// entries before it, which can be appended to a new store to recover from it.
+    pub fn load<P>(
+        magic: &[u8],
+        file_path: P,
+        key: &[u8; ENCRYPTION_KEY_LEN],
+    ) -> Result<(Self, Option<ChangeSet>), EncryptedStoreError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut cipher = EntryCipher::new(magic, key);
+        let (store, entries) =
+            Store::load(magic, file_path).map_err(|err| cipher.store_error(err))?;
+        cipher.next_index = entries.as_ref().map_or(0, |entries| entries.0.len() as u64);
+        let changeset = cipher.decrypt_entries(entries)?;
+        Ok((Self { store, cipher }, changeset))
+    }
+
+    /// Attempt to load an existing [`EncryptedStore`] file; create it if the file does not exist.
+    ///
+    /// Internally, this calls either [`load`](Self::load) or [`create`](Self::create).
+    pub fn load_or_create<P>(
+        magic: &[u8],
+        file_path: P,
+        key: &[u8; ENCRYPTION_KEY_LEN],
+    ) -> Result<(Self, Option<ChangeSet>), EncryptedStoreError>
+    where
+        P: AsRef<Path>,
+    {
+        if file_path.as_ref().exists() {
+            Self::load(magic, file_path, key)
+        } else {
+            Self::create(magic, file_path, key).map(|store| (store, None))
+        }
+    }
+
+    /// Create a new [`EncryptedStore`] file encrypted with a key derived from `passphrase`; error
+    /// if the file exists.
+    ///
+    /// The key is derived with Argon2id, with the default parameters of the [`argon2`] crate and
+    /// a random salt.
+    pub fn create_with_passphrase<P>(
+        magic: &[u8],
+        file_path: P,
+        passphrase: &str,
+    ) -> Result<Self, EncryptedStoreError>
+    where
+        P: AsRef<Path>,
+    {
+        let kdf = KdfHeader::new(
+            Params::DEFAULT_M_COST,
+            Params::DEFAULT_T_COST,
+            Params::DEFAULT_P_COST,
+        );
+        Self::create_with_kdf(magic, file_path, passphrase, kdf)
+    }
+
+    fn create_with_kdf<P>(
+        magic: &[u8],
+        file_path: P,
+        passphrase: &str,
+        kdf: KdfHeader,
+    ) -> Result<Self, EncryptedStoreError>
+    where
+        P: AsRef<Path>,
+    {
+        let key = kdf.derive_key(passphrase)?;
+        Self::create(&kdf.store_magic(magic), file_path, &key)
+    }
+
+    /// Load an existing [`EncryptedStore`] created with
+    /// [`create_with_passphrase`](Self::create_with_passphrase) and the aggregate of its
+    /// changesets.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`EncryptedStoreError::Decrypt`] if the passphrase is wrong. See
+    /// [`load`](Self::load) for the other errors.
+    pub fn load_with_passphrase<P>(
+        magic: &[u8],
+        file_path: P,
+        passphrase: &str,
+    ) -> Result<(Self, Option<ChangeSet>), EncryptedStoreError>
+    where
+        P: AsRef<Path>,
+    {
+        let kdf = KdfHeader::read(magic, file_path.as_ref())?;
+        let key = kdf.derive_key(passphrase)?;
+        Self::load(&kdf.store_magic(magic), file_path, &key)
+    }
+
+    /// Attempt to load an existing [`EncryptedStore`] file encrypted with a key derived from
+    /// `passphrase`; create it if the file does not exist.
+    ///
+    /// Internally, this calls either [`load_with_passphrase`](Self::load_with_passphrase) or
+    /// [`create_with_passphrase`](Self::create_with_passphrase).
+    pub fn load_or_create_with_passphrase<P>(
+        magic: &[u8],
+        file_path: P,
+        passphrase: &str,
+    ) -> Result<(Self, Option<ChangeSet>), EncryptedStoreError>
+    where
+        P: AsRef<Path>,
+    {
+        if file_path.as_ref().exists() {
+            Self::load_with_passphrase(magic, file_path, passphrase)
+        } else {
+            Self::create_with_passphrase(magic, file_path, passphrase).map(|store| (store, None))
+        }
+    }
+
+    /// Decrypt and aggregate all changesets in the [`EncryptedStore`].
+    pub fn dump(&mut self) -> Result<Option<ChangeSet>, EncryptedStoreError> {
+        let entries = self
+            .store
+            .dump()
+            .map_err(|err| self.cipher.store_error(err))?;
+        self.cipher.decrypt_entries(entries)
+    }
+
+    /// Encrypt and append a new changeset to the file. Does nothing if the changeset is empty.
+    pub fn append(&mut self, changeset: &ChangeSet) -> Result<(), EncryptedStoreError> {
+        if changeset.is_empty() {
+            return Ok(());
+        }
+        let entries = self.cipher.encrypt(changeset)?;
+        self.store
+            .append(&entries)
+            .map_err(EncryptedStoreError::Write)?;
+        self.cipher.next_index += 1;
+        Ok(())
+    }
+}
+
+/// Error for [`EncryptedStore`].
+#[derive(Debug)]
+pub enum EncryptedStoreError {
+    /// Error when opening or reading the underlying store.
+    Store(StoreError),
+    /// An entry of the store could not be read, e.g. because it was only partially written.
+    Corrupted {
+        /// The aggregate of the changesets before the entry that could not be read.
+        changeset: Option<Box<ChangeSet>>,
+        /// The error reading the entry.
+        error: StoreError,
+    },
+    /// Error when writing to the store.
+    Write(std::io::Error),
+    /// The key could not be derived from the passphrase.
+    Kdf(argon2::Error),
+    /// The Argon2 costs in the header of the store exceed [`MAX_KDF_M_COST`], [`MAX_KDF_T_COST`]
+    /// or [`MAX_KDF_P_COST`].
+    KdfCosts {
+        /// The memory cost in KiB.
+        m_cost: u32,
+        /// The number of iterations.
+        t_cost: u32,
+        /// The degree of parallelism.
+        p_cost: u32,
+    },
+    /// A changeset could not be encrypted.
+    Encrypt,
+    /// A changeset could not be decrypted, most likely because the key is wrong, or because the
+    /// entries of the store were tampered with.
+    Decrypt,
+    /// A changeset could not be serialized or deserialized.
+    Serde(serde_json::Error),
+    /// A decrypted changeset could not be upgraded to the [`ChangeSet::CURRENT_VERSION`].
+    Migration(ChangeSetMigrationError),
+}
+
+impl fmt::Display for EncryptedStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(e) => fmt::Display::fmt(e, f),
+            Self::Corrupted { error, .. } => write!(f, "failed to read changeset: {error}"),
+            Self::Write(e) => fmt::Display::fmt(e, f),
+            Self::Kdf(e) => write!(f, "failed to derive key from passphrase: {e}"),
+            Self::KdfCosts {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => write!(
+                f,
+                "key derivation costs m={m_cost} KiB, t={t_cost}, p={p_cost} exceed the maximum of \
+                 m={MAX_KDF_M_COST} KiB, t={MAX_KDF_T_COST}, p={MAX_KDF_P_COST}"
+            ),
+            Self::Encrypt => write!(f, "failed to encrypt changeset"),
+            Self::Decrypt => write!(f, "failed to decrypt changeset, the key may be wrong"),
+            Self::Serde(e) => write!(f, "failed to encode or decode changeset: {e}"),
+            Self::Migration(e) => write!(f, "failed to upgrade changeset: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for EncryptedStoreError {}
+
+impl WalletPersister for EncryptedStore {
+    type Error = EncryptedStoreError;
+
+    fn initialize(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        persister.dump().map(Option::unwrap_or_default)
+    }
+
+    fn persist(persister: &mut Self, changeset: &ChangeSet) -> Result<(), Self::Error> {
+        persister.append(changeset)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    use bitcoin::Network;
+
+    const MAGIC: &[u8] = b"bdk_encrypted_test";
+    const KEY: [u8; ENCRYPTION_KEY_LEN] = [7; ENCRYPTION_KEY_LEN];
+
+    fn network_changeset() -> ChangeSet {
+        ChangeSet {
+            network: Some(Network::Signet),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+
+        let (mut store, changeset) = EncryptedStore::load_or_create(MAGIC, &path, &KEY).unwrap();
+        assert!(changeset.is_none());
+        store.append(&network_changeset()).unwrap();
+        drop(store);
+
+        let (_, changeset) = EncryptedStore::load(MAGIC, &path, &KEY).unwrap();
+        assert_eq!(changeset, Some(network_changeset()));
+
+        // the magic bytes are cleartext but the changeset is not
+        let mut contents = Vec::new();
+        std::fs::File::open(&path)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert!(contents.starts_with(MAGIC));
+        let needle = b"signet";
+        assert!(!contents.windows(needle.len()).any(|w| w == needle));
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+
+        // fails even before any changeset is appended
+        EncryptedStore::create(MAGIC, &path, &KEY).unwrap();
+        let wrong_key = [8; ENCRYPTION_KEY_LEN];
+        assert!(matches!(
+            EncryptedStore::load(MAGIC, &path, &wrong_key),
+            Err(EncryptedStoreError::Decrypt)
+        ));
+
+        // and the file is not modified by a failed load
+        let len = std::fs::metadata(&path).unwrap().len();
+        let (mut store, _) = EncryptedStore::load(MAGIC, &path, &KEY).unwrap();
+        store.append(&network_changeset()).unwrap();
+        assert!(matches!(
+            EncryptedStore::load(MAGIC, &path, &wrong_key),
+            Err(EncryptedStoreError::Decrypt)
+        ));
+        assert!(std::fs::metadata(&path).unwrap().len() > len);
+        let (_, changeset) = EncryptedStore::load(MAGIC, &path, &KEY).unwrap();
+        assert_eq!(changeset, Some(network_changeset()));
+    }
+
+    #[test]
+    fn test_tampered_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+
+        let mut store = EncryptedStore::create(MAGIC, &path, &KEY).unwrap();
+        store.append(&network_changeset()).unwrap();
+        drop(store);
+
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let end = file.seek(SeekFrom::End(-1)).unwrap();
+        let mut byte = [0u8; 1];
+        file.read_exact(&mut byte).unwrap();
+        file.seek(SeekFrom::Start(end)).unwrap();
+        std::io::Write::write_all(&mut file, &[byte[0] ^ 1]).unwrap();
+        drop(file);
+
+        assert!(matches!(
+            EncryptedStore::load(MAGIC, &path, &KEY),
+            Err(EncryptedStoreError::Decrypt)
+        ));
+    }
+
+    #[test]
+    fn test_reordered_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+
+        let mut store = EncryptedStore::create(MAGIC, &path, &KEY).unwrap();
+        store.append(&network_changeset()).unwrap();
+        store
+            .append(&ChangeSet {
+                local_chain: bdk_chain::local_chain::ChangeSet {
+                    blocks: [(1, None)].into(),
+                },
+                ..Default::default()
+            })
+            .unwrap();
+        drop(store);
+        let (_, entries) = Store::<EncryptedEntries>::load(MAGIC, &path).unwrap();
+        let entries = entries.unwrap().0;
+        assert_eq!(entries.len(), 3);
+
+        // rewrite the store with the entries at `indices`
+        let rewrite = |indices: &[usize]| {
+            std::fs::remove_file(&path).unwrap();
+            let mut store = Store::<EncryptedEntries>::create(MAGIC, &path).unwrap();
+            for &i in indices {
+                store
+                    .append(&EncryptedEntries(vec![entries[i].clone()]))
+                    .unwrap();
+            }
+        };
+        for indices in [&[0, 2, 1][..], &[0, 2], &[0, 1, 1, 2], &[1, 2]] {
+            rewrite(indices);
+            assert!(
+                matches!(
+                    EncryptedStore::load(MAGIC, &path, &KEY),
+                    Err(EncryptedStoreError::Decrypt)
+                ),
+                "entries {indices:?} must not load"
+            );
+        }
+
+        // appending after loading continues the sequence
+        rewrite(&[0, 1]);
+        let (mut store, changeset) = EncryptedStore::load(MAGIC, &path, &KEY).unwrap();
+        assert_eq!(changeset, Some(network_changeset()));
+        store
+            .append(&ChangeSet {
+                local_chain: bdk_chain::local_chain::ChangeSet {
+                    blocks: [(1, None)].into(),
+                },
+                ..Default::default()
+            })
+            .unwrap();
+        drop(store);
+        assert!(EncryptedStore::load(MAGIC, &path, &KEY).is_ok());
+    }
+
+    #[test]
+    fn test_entry_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+
+        let mut store = EncryptedStore::create(MAGIC, &path, &KEY).unwrap();
+        store.append(&network_changeset()).unwrap();
+        drop(store);
+        let (_, entries) = Store::<EncryptedEntries>::load(MAGIC, &path).unwrap();
+        let mut entries = entries.unwrap().0;
+        assert!(entries
+            .iter()
+            .all(|entry| entry.version == ChangeSet::CURRENT_VERSION));
+
+        let rewrite = |entries: &[EncryptedEntry]| {
+            std::fs::remove_file(&path).unwrap();
+            let mut store = Store::<EncryptedEntries>::create(MAGIC, &path).unwrap();
+            for entry in entries {
+                store
+                    .append(&EncryptedEntries(vec![entry.clone()]))
+                    .unwrap();
+            }
+        };
+
+        // the version is authenticated with the entry
+        entries[1].version = 0;
+        rewrite(&entries);
+        assert!(matches!(
+            EncryptedStore::load(MAGIC, &path, &KEY),
+            Err(EncryptedStoreError::Decrypt)
+        ));
+
+        // entries of a newer version are rejected
+        let version = ChangeSet::CURRENT_VERSION + 1;
+        let cipher = EntryCipher::new(MAGIC, &KEY);
+        let aad = cipher.aad(1, version);
+        let nonce = [1u8; NONCE_LEN];
+        let ciphertext = cipher
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &serde_json::to_vec(&network_changeset()).unwrap(),
+                    aad: &aad,
+                },
+            )
+            .unwrap();
+        entries[1] = EncryptedEntry {
+            version,
+            nonce,
+            ciphertext,
+        };
+        rewrite(&entries);
+        assert!(matches!(
+            EncryptedStore::load(MAGIC, &path, &KEY),
+            Err(EncryptedStoreError::Migration(
+                ChangeSetMigrationError::NewerVersion(v)
+            )) if v == version
+        ));
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+
+        // cheap parameters to keep the test fast, they are read back from the file
+        let kdf = KdfHeader::new(64, 1, 1);
+        let mut store =
+            EncryptedStore::create_with_kdf(MAGIC, &path, "correct horse", kdf).unwrap();
+        store.append(&network_changeset()).unwrap();
+        drop(store);
+
+        let (_, changeset) =
+            EncryptedStore::load_or_create_with_passphrase(MAGIC, &path, "correct horse").unwrap();
+        assert_eq!(changeset, Some(network_changeset()));
+        assert!(matches!(
+            EncryptedStore::load_with_passphrase(MAGIC, &path, "battery staple"),
+            Err(EncryptedStoreError::Decrypt)
+        ));
+        assert!(matches!(
+            EncryptedStore::load_with_passphrase(b"other_magic", &path, "correct horse"),
+            Err(EncryptedStoreError::Store(
+                StoreError::InvalidMagicBytes { .. }
+            ))
+        ));
+        assert_eq!(KdfHeader::read(MAGIC, &path).unwrap(), kdf);
+    }
+
+    #[test]
+    fn test_kdf_costs_are_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+
+        let kdf = KdfHeader::new(64, 1, 1);
+        let store = EncryptedStore::create_with_kdf(MAGIC, &path, "correct horse", kdf).unwrap();
+        drop(store);
+
+        // raise the memory cost in the header to 4 TiB
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start((MAGIC.len() + SALT_LEN) as u64))
+            .unwrap();
+        file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        drop(file);
+
+        assert!(matches!(
+            EncryptedStore::load_with_passphrase(MAGIC, &path, "correct horse"),
+            Err(EncryptedStoreError::KdfCosts {
+                m_cost: u32::MAX,
+                t_cost: 1,
+                p_cost: 1,
+            })
+        ));
+        assert!(matches!(
+            EncryptedStore::create_with_kdf(
+                MAGIC,
+                dir.path().join("other.db"),
+                "correct horse",
+                KdfHeader::new(64, MAX_KDF_T_COST + 1, 1),
+            ),
+            Err(EncryptedStoreError::KdfCosts { .. })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+
+        let mut store = EncryptedStore::create(MAGIC, &path, &KEY).unwrap();
+        store.append(&network_changeset()).unwrap();
+        store
+            .append(&ChangeSet {
+                local_chain: bdk_chain::local_chain::ChangeSet {
+                    blocks: [(1, None)].into(),
+                },
+                ..Default::default()
+            })
+            .unwrap();
+        drop(store);
+
+        // the last entry was only partially written
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        let len = file.metadata().unwrap().len();
+        file.set_len(len - 2).unwrap();
+        drop(file);
+
+        let changeset = match EncryptedStore::load(MAGIC, &path, &KEY) {
+            Err(EncryptedStoreError::Corrupted { changeset, .. }) => changeset,
+            res => panic!("unexpected result: {res:?}"),
+        };
+        assert_eq!(changeset.as_deref(), Some(&network_changeset()));
+        // with the wrong key, the entries read so far can't be recovered
+        assert!(matches!(
+            EncryptedStore::load(MAGIC, &path, &[8; ENCRYPTION_KEY_LEN]),
+            Err(EncryptedStoreError::Decrypt)
+        ));
+
+        // recover by writing the aggregate to a new store
+        let backup_path = dir.path().join("wallet.db.backup");
+        let mut store = EncryptedStore::create(MAGIC, &backup_path, &KEY).unwrap();
+        store.append(&changeset.unwrap()).unwrap();
+        drop(store);
+        std::fs::rename(&backup_path, &path).unwrap();
+        let (_, changeset) = EncryptedStore::load(MAGIC, &path, &KEY).unwrap();
+        assert_eq!(changeset, Some(network_changeset()));
+    }
+}
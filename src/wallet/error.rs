@@ -14,6 +14,7 @@
 use crate::descriptor::policy::PolicyError;
 use crate::descriptor::{DescriptorError, ExtendedDescriptor};
 use crate::wallet::coin_selection;
+use crate::wallet::fee_estimation::FeeEstimationError;
 use crate::{descriptor, KeychainKind, LoadWithPersistError};
 use alloc::{
     boxed::Box,
@@ -176,6 +177,8 @@ pub enum CreateTxError {
         /// Required `LockTime`
         required: absolute::LockTime,
     },
+    /// Cannot enable RBF with a `Sequence` of `0xFFFFFFFE` or more
+    RbfSequence(Sequence),
     /// Cannot enable RBF with `Sequence` given a required OP_CSV
     RbfSequenceCsv {
         /// Given RBF `Sequence`
@@ -183,6 +186,11 @@ pub enum CreateTxError {
         /// Required OP_CSV `Sequence`
         csv: Sequence,
     },
+    /// The requested `Sequence` of `0xFFFFFFFF` disables the nLockTime required by an OP_CLTV
+    FinalSequenceLockTime {
+        /// Required `LockTime`
+        required: absolute::LockTime,
+    },
     /// When bumping a tx the absolute fee requested is lower than replaced tx absolute fee
     FeeTooLow {
         /// Required fee absolute value [`Amount`]
@@ -193,6 +201,11 @@ pub enum CreateTxError {
         /// Required fee rate
         required: bitcoin::FeeRate,
     },
+    /// The fee rate requested is lower than the minimum relay fee of the wallet
+    FeeRateBelowMinRelay {
+        /// Minimum relay fee of the wallet
+        required: bitcoin::FeeRate,
+    },
     /// `manually_selected_only` option is selected but no utxo has been passed
     NoUtxosSelected,
     /// Output created is under the dust limit, 546 satoshis
@@ -215,6 +228,29 @@ pub enum CreateTxError {
     MissingNonWitnessUtxo(OutPoint),
     /// Miniscript PSBT error
     MiniscriptPsbt(MiniscriptPsbtError),
+    /// The fee estimator passed to [`TxBuilder::fee_rate_from`] failed
+    ///
+    /// [`TxBuilder::fee_rate_from`]: crate::wallet::tx_builder::TxBuilder::fee_rate_from
+    FeeEstimation(FeeEstimationError),
+    /// An outpoint passed to [`TxBuilder::with_input_order`] is not spent by the transaction
+    ///
+    /// [`TxBuilder::with_input_order`]: crate::wallet::tx_builder::TxBuilder::with_input_order
+    InputNotSelected(OutPoint),
+    /// The transaction is signed with `SIGHASH_SINGLE` but the input at this index has no output
+    /// at the same index
+    ///
+    /// Such an input commits to no output at all, and the signature of a legacy input is even
+    /// valid for any transaction spending it.
+    SighashSingleUnpairedInput(usize),
+    /// The value left over exceeds the maximum of [`LeftoverPolicy::AddToFee`]
+    ///
+    /// [`LeftoverPolicy::AddToFee`]: crate::wallet::tx_builder::LeftoverPolicy::AddToFee
+    LeftoverAboveMax {
+        /// Value left over after paying the recipients and the fee
+        leftover: Amount,
+        /// Maximum value that can be added to the fee
+        max_leftover: Amount,
+    },
 }
 
 impl fmt::Display for CreateTxError {
@@ -240,12 +276,21 @@ impl fmt::Display for CreateTxError {
             } => {
                 write!(f, "TxBuilder requested timelock of `{requested}`, but at least `{required}` is required to spend from this script")
             }
+            CreateTxError::RbfSequence(sequence) => {
+                write!(f, "Cannot enable RBF with nSequence `{sequence}`")
+            }
             CreateTxError::RbfSequenceCsv { sequence, csv } => {
                 write!(
                     f,
                     "Cannot enable RBF with nSequence `{sequence}` given a required OP_CSV of `{csv}`"
                 )
             }
+            CreateTxError::FinalSequenceLockTime { required } => {
+                write!(
+                    f,
+                    "A final nSequence disables the timelock of `{required}` required to spend from this script"
+                )
+            }
             CreateTxError::FeeTooLow { required } => {
                 write!(f, "Fee to low: required {}", required.display_dynamic())
             }
@@ -258,6 +303,13 @@ impl fmt::Display for CreateTxError {
                     crate::floating_rate!(required)
                 )
             }
+            CreateTxError::FeeRateBelowMinRelay { required } => {
+                write!(
+                    f,
+                    "Fee rate below the minimum relay fee of {} sat/vb",
+                    crate::floating_rate!(required)
+                )
+            }
             CreateTxError::NoUtxosSelected => {
                 write!(f, "No UTXO selected")
             }
@@ -281,6 +333,29 @@ impl fmt::Display for CreateTxError {
             CreateTxError::MiniscriptPsbt(err) => {
                 write!(f, "Miniscript PSBT error: {err}")
             }
+            CreateTxError::FeeEstimation(err) => {
+                write!(f, "Fee estimation error: {err}")
+            }
+            CreateTxError::InputNotSelected(outpoint) => {
+                write!(f, "Outpoint {outpoint} is not an input of the transaction")
+            }
+            CreateTxError::SighashSingleUnpairedInput(index) => {
+                write!(
+                    f,
+                    "Input {index} has no matching output to sign with SIGHASH_SINGLE"
+                )
+            }
+            CreateTxError::LeftoverAboveMax {
+                leftover,
+                max_leftover,
+            } => {
+                write!(
+                    f,
+                    "Leftover of {} would be added to the fee, above the maximum of {}",
+                    leftover.display_dynamic(),
+                    max_leftover.display_dynamic()
+                )
+            }
         }
     }
 }
@@ -365,3 +440,42 @@ impl fmt::Display for BuildFeeBumpError {
 }
 
 impl core::error::Error for BuildFeeBumpError {}
+
+#[derive(Debug)]
+/// Error returned from [`Wallet::verify_tx`]
+///
+/// [`Wallet::verify_tx`]: super::Wallet::verify_tx
+pub enum VerifyError {
+    /// The input doesn't satisfy the script pubkey of the wallet output it spends
+    InvalidInput {
+        /// Index of the input
+        index: usize,
+        /// Error returned by the miniscript interpreter
+        error: miniscript::interpreter::Error,
+    },
+    /// The input doesn't satisfy the script pubkey of the wallet output it spends, according to
+    /// libbitcoinconsensus
+    #[cfg(feature = "bitcoinconsensus")]
+    Consensus {
+        /// Index of the input
+        index: usize,
+        /// Error returned by libbitcoinconsensus
+        error: bitcoin::consensus::validation::BitcoinconsensusError,
+    },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidInput { index, error } => {
+                write!(f, "Input {index} doesn't satisfy the spent script: {error}")
+            }
+            #[cfg(feature = "bitcoinconsensus")]
+            Self::Consensus { index, error } => {
+                write!(f, "Input {index} doesn't satisfy the spent script: {error}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for VerifyError {}
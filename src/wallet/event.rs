@@ -6,7 +6,7 @@ use crate::Wallet;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitcoin::{Transaction, Txid};
-use chain::{BlockId, ChainPosition, ConfirmationBlockTime};
+use chain::{local_chain::CheckPoint, BlockId, ChainPosition, ConfirmationBlockTime};
 
 /// Events representing changes to wallet transactions.
 ///
@@ -22,6 +22,24 @@ pub enum WalletEvent {
         /// New chain tip.
         new_tip: BlockId,
     },
+    /// The blocks at the old chain tip were replaced by a chain reorg.
+    ///
+    /// Follows the [`WalletEvent::ChainTipChanged`] event of the reorg. Transactions confirmed in
+    /// the replaced blocks are reported with [`WalletEvent::TxUnconfirmed`] events, or with
+    /// [`WalletEvent::TxConfirmed`] events if they're confirmed again in the new blocks.
+    ReorgDetected {
+        /// Number of blocks of the old chain replaced, from the lowest block of the wallet's
+        /// chain that the new chain replaced with a different block, up to the old tip.
+        ///
+        /// The wallet's chain doesn't necessarily contain every block, so the reorg can be deeper:
+        /// the chains fork somewhere between that block and the highest block of the old chain
+        /// still in the new chain.
+        depth: u32,
+        /// Chain tip before the reorg.
+        old_tip: BlockId,
+        /// Chain tip after the reorg.
+        new_tip: BlockId,
+    },
     /// A transaction is now confirmed.
     ///
     /// If the transaction was previously unconfirmed `old_block_time` will be `None`.
@@ -71,9 +89,10 @@ pub enum WalletEvent {
     },
     /// Unconfirmed transaction dropped.
     ///
-    /// The transaction was dropped from the local mempool. This is generally due to the fee rate
-    /// being too low. The transaction can still reappear in the mempool in the future resulting in
-    /// a [`WalletEvent::TxUnconfirmed`] event.
+    /// The transaction was dropped from the local mempool, i.e. it was evicted without being
+    /// replaced by a conflicting transaction. This is generally due to the fee rate being too
+    /// low. The transaction can still reappear in the mempool in the future resulting in a
+    /// [`WalletEvent::TxUnconfirmed`] event.
     TxDropped {
         /// Transaction id.
         txid: Txid,
@@ -82,23 +101,64 @@ pub enum WalletEvent {
     },
 }
 
+/// A chain reorg found by comparing the chains of two tips with [`find_reorg`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Reorg {
+    /// Height of the highest block of the old chain still in the new chain.
+    pub fork_height: u32,
+    /// Number of blocks of the old chain replaced, see [`WalletEvent::ReorgDetected`].
+    pub depth: u32,
+}
+
+/// Find the blocks of the chain of `old_tip` replaced in the chain of `new_tip`, or `None` if
+/// the old tip is in the new chain.
+pub(crate) fn find_reorg(old_tip: &CheckPoint, new_tip: &CheckPoint) -> Option<Reorg> {
+    let new_hash = |cp: &CheckPoint| new_tip.get(cp.height()).map(|new| new.hash());
+    // the blocks of the old chain above the highest one still in the new chain
+    let replaced = old_tip
+        .iter()
+        .take_while(|cp| new_hash(cp) != Some(cp.hash()))
+        .collect::<Vec<_>>();
+    // the lowest block with a different block at its height in the new chain is certainly
+    // replaced, the blocks below it may just be missing from the new chain
+    let lowest_replaced = replaced
+        .iter()
+        .rev()
+        .find(|cp| new_hash(cp).is_some())
+        .or(replaced.last())?;
+    Some(Reorg {
+        fork_height: old_tip
+            .iter()
+            .find(|cp| new_hash(cp) == Some(cp.hash()))
+            .map_or(0, |cp| cp.height()),
+        depth: old_tip.height() - lowest_replaced.height() + 1,
+    })
+}
+
 /// Generate `WalletEvent`s by comparing the chain tip and wallet transactions before and after
 /// updating the state of the `Wallet`.
 pub(crate) fn wallet_events(
     wallet: &Wallet,
-    chain_tip1: BlockId,
-    chain_tip2: BlockId,
+    chain_tip1: CheckPoint,
+    chain_tip2: CheckPoint,
     wallet_txs1: BTreeMap<Txid, (Arc<Transaction>, ChainPosition<ConfirmationBlockTime>)>,
     wallet_txs2: BTreeMap<Txid, (Arc<Transaction>, ChainPosition<ConfirmationBlockTime>)>,
 ) -> Vec<WalletEvent> {
     let mut events: Vec<WalletEvent> = Vec::new();
 
     // find chain tip change
-    if chain_tip1 != chain_tip2 {
-        events.push(WalletEvent::ChainTipChanged {
-            old_tip: chain_tip1,
-            new_tip: chain_tip2,
-        });
+    let old_tip = chain_tip1.block_id();
+    let new_tip = chain_tip2.block_id();
+    if old_tip != new_tip {
+        events.push(WalletEvent::ChainTipChanged { old_tip, new_tip });
+
+        if let Some(Reorg { depth, .. }) = find_reorg(&chain_tip1, &chain_tip2) {
+            events.push(WalletEvent::ReorgDetected {
+                depth,
+                old_tip,
+                new_tip,
+            });
+        }
     }
 
     // find transaction canonical status changes
@@ -0,0 +1,193 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Fee estimation
+//!
+//! This module defines the [`FeeEstimator`] trait, implemented by chain sources able to estimate
+//! the fee rate needed for a transaction to confirm within a number of blocks. An estimator can
+//! be passed to [`TxBuilder::fee_rate_from`](crate::TxBuilder::fee_rate_from) so that the fee
+//! rate is resolved when the transaction is built.
+//!
+//! Estimates coming from a chain source shouldn't be trusted blindly, wrap the estimator in a
+//! [`ClampedFeeEstimator`] to bound them.
+//!
+//! The Electrum, Esplora and Bitcoin Core RPC clients live in their own crates, which don't
+//! implement [`FeeEstimator`], and this crate doesn't depend on them. Until they do, wrap a
+//! client in a type of your own to implement the trait for it:
+//!
+//! ```
+//! # use bdk_wallet::bitcoin::FeeRate;
+//! use bdk_wallet::fee_estimation::{ClampedFeeEstimator, FeeEstimationError, FeeEstimator};
+//!
+//! struct MyClient;
+//!
+//! impl MyClient {
+//!     fn estimate_smart_fee(&self, target_blocks: usize) -> Option<u32> {
+//!         // ...
+//! #       Some(60 / target_blocks as u32)
+//!     }
+//! }
+//!
+//! impl FeeEstimator for MyClient {
+//!     fn estimate(&self, target_blocks: usize) -> Result<FeeRate, FeeEstimationError> {
+//!         self.estimate_smart_fee(target_blocks)
+//!             .map(FeeRate::from_sat_per_vb_u32)
+//!             .ok_or(FeeEstimationError::Unavailable(target_blocks))
+//!     }
+//! }
+//!
+//! let estimator = ClampedFeeEstimator::new(
+//!     MyClient,
+//!     FeeRate::from_sat_per_vb_u32(1),
+//!     FeeRate::from_sat_per_vb_u32(10),
+//! );
+//! assert_eq!(estimator.estimate(1)?, FeeRate::from_sat_per_vb_u32(10));
+//! assert_eq!(estimator.estimate(100)?, FeeRate::from_sat_per_vb_u32(1));
+//! # Ok::<(), FeeEstimationError>(())
+//! ```
+
+use alloc::boxed::Box;
+use core::fmt;
+
+use bitcoin::FeeRate;
+
+/// Errors returned by a [`FeeEstimator`].
+#[derive(Debug)]
+pub enum FeeEstimationError {
+    /// No estimate is available for the requested number of blocks
+    Unavailable(usize),
+    /// The chain source couldn't be reached
+    Backend(Box<dyn core::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for FeeEstimationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unavailable(target_blocks) => {
+                write!(f, "No fee estimate available for {target_blocks} blocks")
+            }
+            Self::Backend(err) => write!(f, "Chain source error: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for FeeEstimationError {}
+
+/// Trait for chain sources that can estimate fee rates.
+pub trait FeeEstimator {
+    /// Estimate the fee rate needed for a transaction to confirm within `target_blocks` blocks.
+    fn estimate(&self, target_blocks: usize) -> Result<FeeRate, FeeEstimationError>;
+}
+
+impl<E: FeeEstimator + ?Sized> FeeEstimator for &E {
+    fn estimate(&self, target_blocks: usize) -> Result<FeeRate, FeeEstimationError> {
+        (**self).estimate(target_blocks)
+    }
+}
+
+impl fmt::Debug for dyn FeeEstimator + Sync + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("dyn FeeEstimator")
+    }
+}
+
+/// A [`FeeEstimator`] returning the same fee rate for any target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticFeeEstimator(pub FeeRate);
+
+impl FeeEstimator for StaticFeeEstimator {
+    fn estimate(&self, _target_blocks: usize) -> Result<FeeRate, FeeEstimationError> {
+        Ok(self.0)
+    }
+}
+
+/// A [`FeeEstimator`] bounding the estimates of another estimator between a minimum and a
+/// maximum fee rate.
+#[derive(Debug, Clone, Copy)]
+pub struct ClampedFeeEstimator<E> {
+    inner: E,
+    min: FeeRate,
+    max: FeeRate,
+}
+
+impl<E> ClampedFeeEstimator<E> {
+    /// Create a new [`ClampedFeeEstimator`] bounding the estimates of `inner`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is greater than `max`.
+    pub fn new(inner: E, min: FeeRate, max: FeeRate) -> Self {
+        assert!(
+            min <= max,
+            "the minimum fee rate must not exceed the maximum"
+        );
+        Self { inner, min, max }
+    }
+
+    /// Return the wrapped estimator.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: FeeEstimator> FeeEstimator for ClampedFeeEstimator<E> {
+    fn estimate(&self, target_blocks: usize) -> Result<FeeRate, FeeEstimationError> {
+        Ok(self
+            .inner
+            .estimate(target_blocks)?
+            .clamp(self.min, self.max))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    fn sat_vb(rate: u32) -> FeeRate {
+        FeeRate::from_sat_per_vb_u32(rate)
+    }
+
+    #[test]
+    fn test_clamped_fee_estimator() {
+        let estimator =
+            ClampedFeeEstimator::new(StaticFeeEstimator(sat_vb(5)), sat_vb(2), sat_vb(8));
+        assert_eq!(estimator.estimate(6).unwrap(), sat_vb(5));
+
+        let estimator =
+            ClampedFeeEstimator::new(StaticFeeEstimator(sat_vb(1)), sat_vb(2), sat_vb(8));
+        assert_eq!(estimator.estimate(6).unwrap(), sat_vb(2));
+
+        let estimator =
+            ClampedFeeEstimator::new(StaticFeeEstimator(sat_vb(100)), sat_vb(2), sat_vb(8));
+        assert_eq!(estimator.estimate(6).unwrap(), sat_vb(8));
+    }
+
+    #[test]
+    fn test_clamped_fee_estimator_error() {
+        struct Failing;
+        impl FeeEstimator for Failing {
+            fn estimate(&self, target_blocks: usize) -> Result<FeeRate, FeeEstimationError> {
+                Err(FeeEstimationError::Unavailable(target_blocks))
+            }
+        }
+        let estimator = ClampedFeeEstimator::new(Failing, sat_vb(2), sat_vb(8));
+        assert!(matches!(
+            estimator.estimate(3),
+            Err(FeeEstimationError::Unavailable(3))
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_clamped_fee_estimator_invalid_bounds() {
+        ClampedFeeEstimator::new(StaticFeeEstimator(sat_vb(5)), sat_vb(8), sat_vb(2));
+    }
+}
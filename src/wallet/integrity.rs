@@ -0,0 +1,423 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Integrity checks of persisted wallets.
+//!
+//! A wallet loaded with [`LoadParams::on_corruption`] checks its persisted data before it is
+//! loaded, and reports any inconsistency found in a [`CorruptionReport`] instead of panicking or
+//! silently loading a wallet missing part of its history:
+//!
+//! * The persister checks its storage with [`WalletPersister::check_integrity`]. The SQLite
+//!   persister runs `PRAGMA quick_check` and looks for anchors of transactions missing from the
+//!   database.
+//! * The loaded [`ChangeSet`] is checked for anchors of blocks contradicting the block persisted at
+//!   the same height, and for floating outputs contradicting the transaction they belong to.
+//!   Anchors of blocks missing from the persisted chain are not reported: updates applied without
+//!   a chain update, e.g. of a transaction confirmed above the tip of the wallet, legitimately
+//!   leave them.
+//!
+//! With [`OnCorruption::Fail`], loading fails with [`LoadWithPersistError::Corrupted`]. With
+//! [`OnCorruption::Recover`], the inconsistent data is left out of the loaded wallet, without
+//! modifying the storage, and the report is available from
+//! [`PersistedWallet::corruption_report`].
+//!
+//! The entries of a [`bdk_file_store`] are checked when the store is opened, with
+//! [`load_store`], which reports the first entry that can't be decoded and can truncate the store
+//! before it:
+//!
+//! ```rust,no_run
+//! # use bdk_wallet::integrity::{load_store, OnCorruption};
+//! # use bdk_wallet::Wallet;
+//! let (mut store, report) = load_store(b"my_app", "wallet.db", OnCorruption::Recover)?;
+//! if !report.is_empty() {
+//!     eprintln!("recovered from a corrupted store: {report}");
+//! }
+//! let wallet = Wallet::load()
+//!     .on_corruption(OnCorruption::Recover)
+//!     .load_wallet(&mut store)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! [`LoadParams::on_corruption`]: crate::LoadParams::on_corruption
+//! [`WalletPersister::check_integrity`]: crate::WalletPersister::check_integrity
+//! [`LoadWithPersistError::Corrupted`]: crate::LoadWithPersistError::Corrupted
+//! [`PersistedWallet::corruption_report`]: crate::PersistedWallet::corruption_report
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use bdk_chain::BlockId;
+use bitcoin::{BlockHash, OutPoint, Txid};
+
+use crate::collections::{BTreeSet, HashMap};
+use crate::ChangeSet;
+
+/// What to do when loading a wallet whose persisted data is corrupted, see
+/// [`LoadParams::on_corruption`](crate::LoadParams::on_corruption).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnCorruption {
+    /// Fail to load the wallet, reporting the corruption.
+    Fail,
+    /// Load the wallet without the corrupted data.
+    ///
+    /// The data left out is usually found again by the next sync of the wallet.
+    Recover,
+}
+
+/// An inconsistency of persisted wallet data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Corruption {
+    /// An entry of a file store can't be decoded, e.g. because it was only partially written.
+    ///
+    /// The entries from this one to the end of the file can't be read.
+    InvalidEntry {
+        /// Index of the entry, the number of entries before it.
+        index: usize,
+        /// Offset of the entry in bytes from the start of the file.
+        offset: u64,
+        /// Length of the file in bytes.
+        file_len: u64,
+        /// The decoding error.
+        error: String,
+    },
+    /// The database reported a damaged page or index.
+    Database(String),
+    /// An anchor of a transaction that is missing from the database.
+    MissingAnchorTx {
+        /// The transaction of the anchor.
+        txid: Txid,
+        /// The block of the anchor.
+        block: BlockId,
+    },
+    /// An anchor of a block that differs from the block persisted at the same height.
+    AnchorBlockMismatch {
+        /// The transaction of the anchor.
+        txid: Txid,
+        /// The block of the anchor.
+        block: BlockId,
+        /// The hash of the block persisted at the height of the anchor.
+        chain_hash: BlockHash,
+    },
+    /// A floating output that contradicts the transaction it belongs to.
+    TxOutMismatch(OutPoint),
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidEntry {
+                index,
+                offset,
+                file_len,
+                error,
+            } => write!(
+                f,
+                "entry {index} at offset {offset} of {file_len} bytes can't be decoded: {error}"
+            ),
+            Self::Database(message) => write!(f, "database is damaged: {message}"),
+            Self::MissingAnchorTx { txid, block } => write!(
+                f,
+                "anchor of tx {txid} in block {} at height {} has no tx",
+                block.hash, block.height
+            ),
+            Self::AnchorBlockMismatch {
+                txid,
+                block,
+                chain_hash,
+            } => write!(
+                f,
+                "anchor of tx {txid} in block {} at height {} contradicts block {chain_hash}",
+                block.hash, block.height
+            ),
+            Self::TxOutMismatch(outpoint) => {
+                write!(f, "txout {outpoint} contradicts its transaction")
+            }
+        }
+    }
+}
+
+/// The inconsistencies found when loading a wallet, see the [module](self) documentation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CorruptionReport {
+    /// The inconsistencies, in the order they were found.
+    pub corruptions: Vec<Corruption>,
+}
+
+impl CorruptionReport {
+    /// Whether no inconsistency was found.
+    pub fn is_empty(&self) -> bool {
+        self.corruptions.is_empty()
+    }
+}
+
+impl fmt::Display for CorruptionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "persisted wallet data is corrupted")?;
+        for (i, corruption) in self.corruptions.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            write!(f, "{sep}{corruption}")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for CorruptionReport {}
+
+/// Check the loaded `changeset` and handle the corruptions found in it and in the storage
+/// `report` as `on_corruption` requires.
+///
+/// Returns the full report, or an error with it if the load must fail.
+pub(crate) fn check_loaded(
+    changeset: &mut ChangeSet,
+    mut report: CorruptionReport,
+    on_corruption: OnCorruption,
+) -> Result<CorruptionReport, CorruptionReport> {
+    report.corruptions.extend(check_changeset(changeset));
+    if report.is_empty() {
+        return Ok(report);
+    }
+    match on_corruption {
+        OnCorruption::Fail => Err(report),
+        OnCorruption::Recover => {
+            recover(changeset, &report);
+            Ok(report)
+        }
+    }
+}
+
+/// The inconsistencies of the data of `changeset`.
+fn check_changeset(changeset: &ChangeSet) -> Vec<Corruption> {
+    let mut corruptions = Vec::new();
+
+    corruptions.extend(
+        changeset
+            .tx_graph
+            .anchors
+            .iter()
+            .filter_map(|(anchor, txid)| {
+                let chain_hash = changeset
+                    .local_chain
+                    .blocks
+                    .get(&anchor.block_id.height)
+                    .copied()
+                    .flatten()?;
+                (chain_hash != anchor.block_id.hash).then_some(Corruption::AnchorBlockMismatch {
+                    txid: *txid,
+                    block: anchor.block_id,
+                    chain_hash,
+                })
+            }),
+    );
+
+    let txs = changeset
+        .tx_graph
+        .txs
+        .iter()
+        .map(|tx| (tx.compute_txid(), tx))
+        .collect::<HashMap<_, _>>();
+    corruptions.extend(
+        changeset
+            .tx_graph
+            .txouts
+            .iter()
+            .filter(|(outpoint, txout)| {
+                txs.get(&outpoint.txid)
+                    .is_some_and(|tx| tx.output.get(outpoint.vout as usize) != Some(txout))
+            })
+            .map(|(outpoint, _)| Corruption::TxOutMismatch(*outpoint)),
+    );
+
+    corruptions
+}
+
+/// Leave the data of the `report` out of `changeset`.
+fn recover(changeset: &mut ChangeSet, report: &CorruptionReport) {
+    let mut anchors = BTreeSet::new();
+    for corruption in &report.corruptions {
+        match corruption {
+            Corruption::MissingAnchorTx { txid, block }
+            | Corruption::AnchorBlockMismatch { txid, block, .. } => {
+                anchors.insert((*txid, *block));
+            }
+            Corruption::TxOutMismatch(outpoint) => {
+                changeset.tx_graph.txouts.remove(outpoint);
+            }
+            Corruption::InvalidEntry { .. } | Corruption::Database(_) => {}
+        }
+    }
+    changeset
+        .tx_graph
+        .anchors
+        .retain(|(anchor, txid)| !anchors.contains(&(*txid, anchor.block_id)));
+}
+
+/// The storage corruptions of the SQLite database of a wallet.
+#[cfg(feature = "rusqlite")]
+pub(crate) fn check_sqlite(
+    conn: &bdk_chain::rusqlite::Connection,
+) -> bdk_chain::rusqlite::Result<Vec<Corruption>> {
+    use alloc::format;
+    use bdk_chain::{tx_graph, ConfirmationBlockTime, Impl};
+
+    type TxGraphChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+
+    let mut corruptions = Vec::new();
+
+    let mut stmt = conn.prepare("PRAGMA quick_check")?;
+    let messages = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if messages != ["ok"] {
+        corruptions.extend(messages.into_iter().map(Corruption::Database));
+    }
+
+    let tables_exist: bool = conn.query_row(
+        "SELECT COUNT(*) = 2 FROM sqlite_master WHERE type='table' AND name IN (?1, ?2)",
+        [
+            TxGraphChangeSet::TXS_TABLE_NAME,
+            TxGraphChangeSet::ANCHORS_TABLE_NAME,
+        ],
+        |row| row.get(0),
+    )?;
+    if tables_exist {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT txid, block_height, block_hash FROM {anchors} AS a \
+             WHERE NOT EXISTS (SELECT 1 FROM {txs} AS t WHERE t.txid = a.txid)",
+            anchors = TxGraphChangeSet::ANCHORS_TABLE_NAME,
+            txs = TxGraphChangeSet::TXS_TABLE_NAME,
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Corruption::MissingAnchorTx {
+                txid: row.get::<_, Impl<Txid>>("txid")?.0,
+                block: BlockId {
+                    height: row.get("block_height")?,
+                    hash: row.get::<_, Impl<BlockHash>>("block_hash")?.0,
+                },
+            })
+        })?;
+        for row in rows {
+            corruptions.push(row?);
+        }
+    }
+
+    Ok(corruptions)
+}
+
+/// Error when opening a [`bdk_file_store::Store`] with [`load_store`].
+#[cfg(feature = "file_store")]
+#[derive(Debug)]
+pub enum LoadStoreError {
+    /// The store could not be opened, e.g. because of its magic bytes.
+    Load(bdk_file_store::StoreErrorWithDump<ChangeSet>),
+    /// An entry of the store can't be decoded, with [`OnCorruption::Fail`].
+    Corrupted(CorruptionReport),
+    /// The store could not be truncated before the corrupted entry.
+    Truncate(std::io::Error),
+}
+
+#[cfg(feature = "file_store")]
+impl fmt::Display for LoadStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Load(e) => fmt::Display::fmt(e, f),
+            Self::Corrupted(report) => fmt::Display::fmt(report, f),
+            Self::Truncate(e) => write!(f, "failed to truncate corrupted store: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "file_store")]
+impl core::error::Error for LoadStoreError {}
+
+/// Open the [`bdk_file_store::Store`] at `file_path`, checking that all its entries can be
+/// decoded.
+///
+/// The first entry that can't be decoded is reported with its index and offset in the file. With
+/// [`OnCorruption::Recover`], the file is truncated before it, so that the store can be loaded
+/// and appended to again; the entries after it are lost. With [`OnCorruption::Fail`], the file
+/// is left untouched and [`LoadStoreError::Corrupted`] is returned.
+#[cfg(feature = "file_store")]
+pub fn load_store<P>(
+    magic: &[u8],
+    file_path: P,
+    on_corruption: OnCorruption,
+) -> Result<(bdk_file_store::Store<ChangeSet>, CorruptionReport), LoadStoreError>
+where
+    P: AsRef<std::path::Path>,
+{
+    let file_path = file_path.as_ref();
+    let mut report = CorruptionReport::default();
+    if let Some(corruption) = find_invalid_entry(magic, file_path)? {
+        let offset = match &corruption {
+            Corruption::InvalidEntry { offset, .. } => *offset,
+            _ => unreachable!("only invalid entries are found"),
+        };
+        report.corruptions.push(corruption);
+        if on_corruption == OnCorruption::Fail {
+            return Err(LoadStoreError::Corrupted(report));
+        }
+        let file = std::fs::File::options()
+            .write(true)
+            .open(file_path)
+            .map_err(LoadStoreError::Truncate)?;
+        file.set_len(offset).map_err(LoadStoreError::Truncate)?;
+        file.sync_all().map_err(LoadStoreError::Truncate)?;
+    }
+    let (store, _) = bdk_file_store::Store::load(magic, file_path).map_err(LoadStoreError::Load)?;
+    Ok((store, report))
+}
+
+/// The first entry of the store at `file_path` that can't be decoded, if any.
+#[cfg(feature = "file_store")]
+fn find_invalid_entry(
+    magic: &[u8],
+    file_path: &std::path::Path,
+) -> Result<Option<Corruption>, LoadStoreError> {
+    use alloc::string::ToString;
+    use bdk_file_store::{EntryIter, StoreError, StoreErrorWithDump};
+    use std::io::{Read, Seek};
+
+    let load_error = |error: std::io::Error| LoadStoreError::Load(error.into());
+    let mut file = std::fs::File::open(file_path).map_err(load_error)?;
+    let file_len = file.metadata().map_err(load_error)?.len();
+    let mut got = alloc::vec![0u8; magic.len()];
+    file.read_exact(&mut got).map_err(load_error)?;
+    if got != magic {
+        return Err(LoadStoreError::Load(StoreErrorWithDump {
+            changeset: None,
+            error: StoreError::InvalidMagicBytes {
+                got,
+                expected: magic.to_vec(),
+            },
+        }));
+    }
+
+    let mut offset = magic.len() as u64;
+    for index in 0.. {
+        // Reading a single entry with each iterator leaves the file at the end of the entry once
+        // the iterator is dropped.
+        let entry = EntryIter::<ChangeSet>::new(offset, &mut file).next();
+        match entry {
+            None => return Ok(None),
+            Some(Ok(_)) => offset = file.stream_position().map_err(load_error)?,
+            Some(Err(StoreError::Io(e))) => return Err(load_error(e)),
+            Some(Err(error)) => {
+                return Ok(Some(Corruption::InvalidEntry {
+                    index,
+                    offset,
+                    file_len,
+                    error: error.to_string(),
+                }))
+            }
+        }
+    }
+    unreachable!("the entries are read until the end of the file")
+}
@@ -0,0 +1,28 @@
+//! Module containing the output labels change set.
+
+use alloc::string::String;
+
+use bdk_chain::Merge;
+use bitcoin::OutPoint;
+use serde::{Deserialize, Serialize};
+
+use crate::collections::BTreeMap;
+
+/// Represents changes to output labels.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeSet {
+    /// The label of an output, `None` if the label was removed.
+    pub outputs: BTreeMap<OutPoint, Option<String>>,
+}
+
+impl Merge for ChangeSet {
+    fn merge(&mut self, other: Self) {
+        // Extend self with other. Any entries in `self` that share the same
+        // outpoint are overwritten.
+        self.outputs.extend(other.outputs);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
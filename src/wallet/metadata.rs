@@ -0,0 +1,121 @@
+//! Module containing the wallet metadata change set.
+//!
+//! Applications can store a few wallet-scoped settings, e.g. a preferred fee target or the time of
+//! the last backup, alongside the wallet with [`Wallet::set_meta`], so that they are persisted by
+//! the same backend. The values are opaque bytes.
+//!
+//! [`Wallet::set_meta`]: crate::Wallet::set_meta
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use bdk_chain::Merge;
+use serde::{Deserialize, Serialize};
+
+use crate::collections::BTreeMap;
+
+/// Namespace of the keys set by applications with [`Wallet::set_meta`].
+///
+/// The keys of the [`ChangeSet`] are prefixed with their namespace, so that the keys of
+/// applications never collide with the ones the library may use in the future.
+///
+/// [`Wallet::set_meta`]: crate::Wallet::set_meta
+pub const APP_NAMESPACE: &str = "app/";
+
+/// Maximum length in bytes of a metadata key, without its namespace.
+pub const MAX_KEY_LEN: usize = 256;
+
+/// Maximum length in bytes of a metadata value.
+pub const MAX_VALUE_LEN: usize = 64 * 1024;
+
+/// Represents changes to the wallet metadata.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeSet {
+    /// The value of a namespaced key, `None` if the key was deleted.
+    ///
+    /// The values are hex encoded in human readable formats, e.g. JSON.
+    #[serde(with = "hex_values")]
+    pub entries: BTreeMap<String, Option<Vec<u8>>>,
+}
+
+/// (De)serialization of the metadata values as hex strings in human readable formats, instead of
+/// arrays of numbers.
+mod hex_values {
+    use super::*;
+    use bitcoin::hex::{DisplayHex, FromHex};
+    use serde::{de, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        entries: &BTreeMap<String, Option<Vec<u8>>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return entries.serialize(serializer);
+        }
+        entries
+            .iter()
+            .map(|(key, value)| (key, value.as_ref().map(|v| v.to_lower_hex_string())))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<String, Option<Vec<u8>>>, D::Error> {
+        if !deserializer.is_human_readable() {
+            return BTreeMap::deserialize(deserializer);
+        }
+        BTreeMap::<String, Option<String>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| {
+                let value = value
+                    .map(|hex| Vec::from_hex(&hex))
+                    .transpose()
+                    .map_err(de::Error::custom)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+}
+
+impl Merge for ChangeSet {
+    fn merge(&mut self, other: Self) {
+        // Extend self with other. Any entries in `self` that share the same
+        // key are overwritten.
+        self.entries.extend(other.entries);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Error when setting wallet metadata with [`Wallet::set_meta`].
+///
+/// [`Wallet::set_meta`]: crate::Wallet::set_meta
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataError {
+    /// The key is longer than [`MAX_KEY_LEN`], with this length.
+    KeyTooLong(usize),
+    /// The value is larger than [`MAX_VALUE_LEN`], with this length.
+    ValueTooLarge(usize),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeyTooLong(len) => write!(
+                f,
+                "metadata key of {len} bytes is longer than the maximum of {MAX_KEY_LEN} bytes"
+            ),
+            Self::ValueTooLarge(len) => write!(
+                f,
+                "metadata value of {len} bytes is larger than the maximum of {MAX_VALUE_LEN} bytes"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MetadataError {}
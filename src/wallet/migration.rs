@@ -8,19 +8,38 @@
 // You may not use this file except in accordance with one or both of these
 // licenses.
 
-//! Migration utilities for upgrading between BDK library versions.
+//! Migration utilities for upgrading between BDK library versions and moving funds between
+//! wallets.
 //!
 //! This module provides helper functions and types to assist users in migrating wallet data
-//! when upgrading between major versions of the `bdk_wallet` crate.
+//! when upgrading between major versions of the `bdk_wallet` crate, and [`plan_migration`] to
+//! sweep the funds of a wallet into a new one, e.g. after adding or changing a BIP39 passphrase.
+//!
+//! Persisted changesets are versioned with [`ChangeSet::CURRENT_VERSION`], and changesets written
+//! by older versions of the library are upgraded with the [`Migration`]s of [`MIGRATIONS`].
 
+use crate::collections::BTreeSet;
+#[cfg(feature = "rusqlite")]
 use crate::rusqlite::{self, Connection};
-use crate::KeychainKind::{self, External, Internal};
-use alloc::{
-    string::{FromUtf8Error, String, ToString},
-    vec::Vec,
-};
+use crate::AddressInfo;
+use crate::ChangeSet;
+#[cfg(feature = "rusqlite")]
+use crate::KeychainKind::{External, Internal};
+use crate::{KeychainKind, LoadParams, Wallet, WalletPersister};
+#[cfg(feature = "rusqlite")]
+use alloc::string::{FromUtf8Error, String, ToString};
+use alloc::vec::Vec;
+use bdk_chain::Merge;
+#[cfg(feature = "std")]
+use bitcoin::{policy::MAX_STANDARD_TX_WEIGHT, FeeRate, TxIn};
+use bitcoin::{Amount, Network, Psbt, Weight};
 use core::fmt;
 
+use super::error::{CreateTxError, LoadError};
+#[cfg(feature = "std")]
+use super::tx_builder::TxParams;
+
+#[cfg(feature = "rusqlite")]
 /// [`PreV1WalletKeychain`] represents a structure that holds the keychain details
 /// and metadata required for managing a wallet's keys.
 #[derive(Debug)]
@@ -34,6 +53,7 @@ pub struct PreV1WalletKeychain {
     pub checksum: String,
 }
 
+#[cfg(feature = "rusqlite")]
 /// Errors thrown when migrating from a pre-v1.0.0 BDK database.
 #[derive(Debug)]
 pub enum PreV1MigrationError {
@@ -45,6 +65,7 @@ pub enum PreV1MigrationError {
     InvalidChecksum(FromUtf8Error),
 }
 
+#[cfg(feature = "rusqlite")]
 impl fmt::Display for PreV1MigrationError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -55,14 +76,17 @@ impl fmt::Display for PreV1MigrationError {
     }
 }
 
+#[cfg(feature = "rusqlite")]
 impl std::error::Error for PreV1MigrationError {}
 
+#[cfg(feature = "rusqlite")]
 impl From<rusqlite::Error> for PreV1MigrationError {
     fn from(e: rusqlite::Error) -> Self {
         PreV1MigrationError::RusqliteError(e)
     }
 }
 
+#[cfg(feature = "rusqlite")]
 /// Retrieves a list of [`PreV1WalletKeychain`] objects from a pre-v1.0.0 bdk SQLite database.
 ///
 /// This function uses a connection to a pre-1.0 bdk wallet SQLite database to execute a query that
@@ -102,7 +126,572 @@ pub fn get_pre_v1_wallet_keychains(
     Ok(keychains)
 }
 
-#[cfg(test)]
+/// The transactions moving the funds of a wallet into another, see [`plan_migration`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationPlan {
+    /// The unsigned sweep transactions, to be signed by the old wallet.
+    ///
+    /// Each transaction spends a disjoint set of UTXOs, so they can be broadcast in any order.
+    pub psbts: Vec<Psbt>,
+    /// The total amount received by the new wallet.
+    pub total_moved: Amount,
+    /// The total fees paid by the sweep transactions.
+    pub total_fees: Amount,
+    /// The addresses of the new wallet the funds are moved to, one per transaction.
+    pub addresses: Vec<AddressInfo>,
+}
+
+/// Errors thrown by [`plan_migration`].
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The wallets are on different networks.
+    NetworkMismatch {
+        /// The network of the old wallet.
+        old: Network,
+        /// The network of the new wallet.
+        new: Network,
+    },
+    /// A single input exceeds the weight limit of the sweep transactions.
+    WeightLimitTooLow {
+        /// The weight limit of the sweep transactions.
+        max_weight: Weight,
+        /// The weight of a transaction spending a single input.
+        required: Weight,
+    },
+    /// Creating one of the sweep transactions failed.
+    CreateTx(CreateTxError),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::NetworkMismatch { old, new } => write!(
+                f,
+                "Network mismatch: the old wallet is on {old} while the new wallet is on {new}"
+            ),
+            MigrationError::WeightLimitTooLow {
+                max_weight,
+                required,
+            } => write!(
+                f,
+                "Weight limit too low: {max_weight} is less than the {required} required to spend a single input"
+            ),
+            MigrationError::CreateTx(e) => write!(f, "Failed to create a sweep transaction: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MigrationError {}
+
+impl From<CreateTxError> for MigrationError {
+    fn from(e: CreateTxError) -> Self {
+        MigrationError::CreateTx(e)
+    }
+}
+
+/// Plan the transactions moving all the spendable funds of `old_wallet` into `new_wallet`.
+///
+/// Every UTXO of `old_wallet` that can be spent, i.e. that is neither locked nor an immature
+/// coinbase output, is swept to a fresh external address of `new_wallet` paying `fee_rate`.
+/// When spending them all at once would exceed the standard transaction weight, the UTXOs are
+/// split across multiple transactions, refer to [`plan_migration_with_weight_limit`] to use a
+/// different limit.
+///
+/// This is meant for moving funds after changing the keys of a wallet, e.g. when adding or
+/// changing the BIP39 passphrase of its mnemonic. The returned PSBTs still have to be signed by
+/// `old_wallet`. Each sweep transaction pays to a new address of `new_wallet`, which are only
+/// revealed if all the transactions are created, so `new_wallet` should be persisted before
+/// broadcasting them.
+///
+/// # Errors
+///
+/// Fails if the wallets are on different networks, or if any of the sweep transactions can't be
+/// created, e.g. because its inputs don't cover its fee.
+#[cfg(feature = "std")]
+pub fn plan_migration(
+    old_wallet: &mut Wallet,
+    new_wallet: &mut Wallet,
+    fee_rate: FeeRate,
+) -> Result<MigrationPlan, MigrationError> {
+    plan_migration_with_weight_limit(
+        old_wallet,
+        new_wallet,
+        fee_rate,
+        Weight::from_wu(MAX_STANDARD_TX_WEIGHT as u64),
+    )
+}
+
+/// Plan the transactions moving all the spendable funds of `old_wallet` into `new_wallet`, with
+/// each transaction weighing at most `max_weight`.
+///
+/// Refer to [`plan_migration`] for more.
+#[cfg(feature = "std")]
+pub fn plan_migration_with_weight_limit(
+    old_wallet: &mut Wallet,
+    new_wallet: &mut Wallet,
+    fee_rate: FeeRate,
+    max_weight: Weight,
+) -> Result<MigrationPlan, MigrationError> {
+    if old_wallet.network() != new_wallet.network() {
+        return Err(MigrationError::NetworkMismatch {
+            old: old_wallet.network(),
+            new: new_wallet.network(),
+        });
+    }
+
+    let tip_height = old_wallet.latest_checkpoint().height();
+    let utxos = old_wallet.filter_utxos(&TxParams::default(), tip_height);
+
+    // weight of a transaction without inputs paying to a single address of the new wallet,
+    // including the segwit marker and flag
+    let drain_script = new_wallet
+        .peek_address(KeychainKind::External, 0)
+        .script_pubkey();
+    let base_weight = bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: Vec::new(),
+        output: vec![bitcoin::TxOut {
+            value: Amount::ZERO,
+            script_pubkey: drain_script,
+        }],
+    }
+    .weight()
+        + Weight::from_wu(2);
+
+    let mut chunks: Vec<Vec<_>> = Vec::new();
+    let mut chunk_weight = base_weight;
+    for utxo in utxos {
+        let input_weight = TxIn::default().segwit_weight() + utxo.satisfaction_weight;
+        if base_weight + input_weight > max_weight {
+            return Err(MigrationError::WeightLimitTooLow {
+                max_weight,
+                required: base_weight + input_weight,
+            });
+        }
+        match chunks.last_mut() {
+            Some(chunk) if chunk_weight + input_weight <= max_weight => {
+                chunk.push(utxo.utxo.outpoint());
+                chunk_weight += input_weight;
+            }
+            _ => {
+                chunks.push(vec![utxo.utxo.outpoint()]);
+                chunk_weight = base_weight + input_weight;
+            }
+        }
+    }
+
+    let mut plan = MigrationPlan {
+        psbts: Vec::with_capacity(chunks.len()),
+        total_moved: Amount::ZERO,
+        total_fees: Amount::ZERO,
+        addresses: Vec::with_capacity(chunks.len()),
+    };
+    // the addresses are only revealed once all the transactions are created, so that a failure
+    // doesn't leave addresses of the new wallet revealed for nothing
+    let next_index = new_wallet.next_derivation_index(KeychainKind::External);
+    for (i, outpoints) in chunks.into_iter().enumerate() {
+        let address = new_wallet.peek_address(KeychainKind::External, next_index + i as u32);
+        let mut builder = old_wallet.build_tx();
+        builder
+            .add_utxos(&outpoints)
+            .expect("the UTXOs must be known to the old wallet")
+            .manually_selected_only()
+            .drain_to(address.script_pubkey())
+            .fee_rate(fee_rate);
+        let psbt = builder.finish()?;
+
+        plan.total_moved += psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .map(|txout| txout.value)
+            .sum();
+        plan.total_fees += psbt.fee().expect("the fee must be valid");
+        plan.psbts.push(psbt);
+        plan.addresses.push(address);
+    }
+    if let Some(last) = plan.addresses.last() {
+        let _ = new_wallet.reveal_addresses_to(KeychainKind::External, last.index);
+    }
+
+    Ok(plan)
+}
+
+/// Errors thrown by [`migrate_persister`].
+#[derive(Debug)]
+pub enum PersisterMigrationError<S, T> {
+    /// The source persister failed.
+    Source(S),
+    /// The target persister failed.
+    Target(T),
+    /// The source persister holds no wallet.
+    SourceEmpty,
+    /// The target persister already holds data. Nothing was written to it.
+    TargetNotEmpty,
+    /// The data of the source persister is not a valid wallet.
+    InvalidChangeSet(LoadError),
+    /// The wallet loaded from the target persister differs from the one of the source.
+    Mismatch,
+}
+
+impl<S: fmt::Display, T: fmt::Display> fmt::Display for PersisterMigrationError<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Source(e) => write!(f, "Failed to read the source persister: {e}"),
+            Self::Target(e) => write!(f, "Failed to write the target persister: {e}"),
+            Self::SourceEmpty => write!(f, "The source persister holds no wallet"),
+            Self::TargetNotEmpty => write!(f, "The target persister already holds data"),
+            Self::InvalidChangeSet(e) => {
+                write!(f, "The source persister holds no valid wallet: {e}")
+            }
+            Self::Mismatch => write!(
+                f,
+                "The wallet loaded from the target persister differs from the source"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: fmt::Debug + fmt::Display, T: fmt::Debug + fmt::Display> std::error::Error
+    for PersisterMigrationError<S, T>
+{
+}
+
+/// Copy the wallet persisted in `source` into the empty persister `target`, e.g. to move it from
+/// a [`bdk_file_store`] file to a SQLite database.
+///
+/// The aggregate changeset of `source` is written to `target` as a single changeset. The wallet
+/// is then loaded back from `target` and checked to have the same network, descriptors, chain
+/// tip, derivation indices, transactions and balance as the one of `source`.
+///
+/// [`bdk_file_store`]: https://docs.rs/bdk_file_store
+pub fn migrate_persister<S, T>(
+    source: &mut S,
+    target: &mut T,
+) -> Result<(), PersisterMigrationError<S::Error, T::Error>>
+where
+    S: WalletPersister,
+    T: WalletPersister,
+{
+    let changeset = S::initialize(source).map_err(PersisterMigrationError::Source)?;
+    let expected = Wallet::load_with_params(changeset.clone(), LoadParams::new())
+        .map_err(PersisterMigrationError::InvalidChangeSet)?
+        .ok_or(PersisterMigrationError::SourceEmpty)?;
+
+    if !T::initialize(target)
+        .map_err(PersisterMigrationError::Target)?
+        .is_empty()
+    {
+        return Err(PersisterMigrationError::TargetNotEmpty);
+    }
+    T::persist(target, &changeset).map_err(PersisterMigrationError::Target)?;
+
+    let migrated = T::initialize(target).map_err(PersisterMigrationError::Target)?;
+    match Wallet::load_with_params(migrated, LoadParams::new()) {
+        Ok(Some(wallet)) if same_wallet_state(&expected, &wallet) => Ok(()),
+        _ => Err(PersisterMigrationError::Mismatch),
+    }
+}
+
+/// Whether two wallets have the same network, descriptors, chain tip, derivation indices,
+/// transactions and balance.
+fn same_wallet_state(a: &Wallet, b: &Wallet) -> bool {
+    let txids = |wallet: &Wallet| {
+        wallet
+            .tx_graph()
+            .full_txs()
+            .map(|tx| tx.txid)
+            .collect::<BTreeSet<_>>()
+    };
+    a.network() == b.network()
+        && a.keychains().eq(b.keychains())
+        && a.latest_checkpoint().block_id() == b.latest_checkpoint().block_id()
+        && [KeychainKind::External, KeychainKind::Internal]
+            .into_iter()
+            .all(|keychain| a.derivation_index(keychain) == b.derivation_index(keychain))
+        && txids(a) == txids(b)
+        && a.balance() == b.balance()
+}
+
+/// A step upgrading the JSON representation of a [`ChangeSet`] written by an older version.
+///
+/// The backends storing changesets as JSON record the [`ChangeSet::CURRENT_VERSION`] each
+/// changeset was written with, and upgrade older changesets with the [`MIGRATIONS`] when loading
+/// them, in order. Every version adding fields registers a migration inserting them, e.g. the
+/// labels and metadata of version 2, and a version changing the representation of a field
+/// registers one converting it, e.g. when it is renamed:
+///
+/// ```rust
+/// use bdk_wallet::migration::{upgrade_changeset_with, ChangeSetMigrationError, Migration};
+/// use bdk_wallet::ChangeSet;
+///
+/// /// Changesets of version 1 called the indexer `keychain_txout`.
+/// struct RenameIndexer;
+///
+/// impl Migration for RenameIndexer {
+///     fn source_version(&self) -> u32 {
+///         1
+///     }
+///
+///     fn migrate(&self, changeset: &mut serde_json::Value) -> Result<(), ChangeSetMigrationError> {
+///         let object = changeset
+///             .as_object_mut()
+///             .ok_or(ChangeSetMigrationError::Malformed(1))?;
+///         if let Some(indexer) = object.remove("keychain_txout") {
+///             object.insert("indexer".into(), indexer);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut changeset = serde_json::to_value(ChangeSet::default()).unwrap();
+/// let object = changeset.as_object_mut().unwrap();
+/// let indexer = object.remove("indexer").unwrap();
+/// object.insert("keychain_txout".into(), indexer);
+///
+/// assert!(upgrade_changeset_with(changeset.clone(), 1, &[]).is_err());
+/// assert_eq!(
+///     upgrade_changeset_with(changeset, 1, &[&RenameIndexer])?,
+///     ChangeSet::default()
+/// );
+/// # Ok::<(), ChangeSetMigrationError>(())
+/// ```
+pub trait Migration {
+    /// Version of the changesets upgraded by this migration, to the next version.
+    fn source_version(&self) -> u32;
+
+    /// Upgrade the JSON object of a `changeset` of the
+    /// [`source_version`](Self::source_version).
+    fn migrate(&self, changeset: &mut serde_json::Value) -> Result<(), ChangeSetMigrationError>;
+}
+
+/// Migrations run by the persistence backends of this crate, in order.
+///
+/// The entries of the `bdk_file_store` files written by the released versions, which are not
+/// JSON, are converted to JSON and upgraded with them by [`load_file_store`].
+pub const MIGRATIONS: &[&dyn Migration] = &[&AddLabelsAndMetadata];
+
+/// Upgrade the changesets of version 1, written by bdk_wallet 3.x, to version 2.
+///
+/// Version 2 adds the [`labels`](ChangeSet::labels), the [`metadata`](ChangeSet::metadata) and
+/// the [`spk_cache_digests`](ChangeSet::spk_cache_digests), which are inserted empty: the wallet
+/// had no labels nor metadata, and the digests of its SPK cache are computed again when it is
+/// loaded.
+struct AddLabelsAndMetadata;
+
+impl Migration for AddLabelsAndMetadata {
+    fn source_version(&self) -> u32 {
+        1
+    }
+
+    fn migrate(&self, changeset: &mut serde_json::Value) -> Result<(), ChangeSetMigrationError> {
+        let object = changeset
+            .as_object_mut()
+            .ok_or(ChangeSetMigrationError::Malformed(1))?;
+        let empty = ChangeSet::default();
+        let fields = [
+            ("labels", serde_json::to_value(&empty.labels)),
+            ("metadata", serde_json::to_value(&empty.metadata)),
+            (
+                "spk_cache_digests",
+                serde_json::to_value(&empty.spk_cache_digests),
+            ),
+        ];
+        for (field, value) in fields {
+            let value = value.map_err(ChangeSetMigrationError::Json)?;
+            object.entry(field).or_insert(value);
+        }
+        Ok(())
+    }
+}
+
+/// Check that data written with changeset `version` can be read by this version of the library.
+pub fn check_changeset_version(version: u32) -> Result<(), ChangeSetMigrationError> {
+    if version > ChangeSet::CURRENT_VERSION {
+        return Err(ChangeSetMigrationError::NewerVersion(version));
+    }
+    Ok(())
+}
+
+/// Decode the JSON `changeset` written with changeset `version`, after upgrading it with the
+/// [`MIGRATIONS`].
+pub fn upgrade_changeset(
+    changeset: serde_json::Value,
+    version: u32,
+) -> Result<ChangeSet, ChangeSetMigrationError> {
+    upgrade_changeset_with(changeset, version, MIGRATIONS)
+}
+
+/// Like [`upgrade_changeset`], with the given `migrations` instead of the [`MIGRATIONS`], e.g. for
+/// a custom persister storing additional data.
+pub fn upgrade_changeset_with(
+    mut changeset: serde_json::Value,
+    version: u32,
+    migrations: &[&dyn Migration],
+) -> Result<ChangeSet, ChangeSetMigrationError> {
+    check_changeset_version(version)?;
+    let mut migrations = migrations
+        .iter()
+        .filter(|migration| migration.source_version() >= version)
+        .collect::<Vec<_>>();
+    migrations.sort_by_key(|migration| migration.source_version());
+    for migration in migrations {
+        migration.migrate(&mut changeset)?;
+    }
+    serde_json::from_value(changeset).map_err(ChangeSetMigrationError::Json)
+}
+
+/// The [`ChangeSet`] of version 0, written by bdk_wallet 1.x and 2.x.
+#[cfg(feature = "file_store")]
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ChangeSetV0 {
+    descriptor: Option<miniscript::Descriptor<miniscript::DescriptorPublicKey>>,
+    change_descriptor: Option<miniscript::Descriptor<miniscript::DescriptorPublicKey>>,
+    network: Option<Network>,
+    local_chain: bdk_chain::local_chain::ChangeSet,
+    tx_graph: bdk_chain::tx_graph::ChangeSet<bdk_chain::ConfirmationBlockTime>,
+    indexer: bdk_chain::keychain_txout::ChangeSet,
+}
+
+/// The [`ChangeSet`] of version 1, written by bdk_wallet 3.x.
+#[cfg(feature = "file_store")]
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ChangeSetV1 {
+    descriptor: Option<miniscript::Descriptor<miniscript::DescriptorPublicKey>>,
+    change_descriptor: Option<miniscript::Descriptor<miniscript::DescriptorPublicKey>>,
+    network: Option<Network>,
+    local_chain: bdk_chain::local_chain::ChangeSet,
+    tx_graph: bdk_chain::tx_graph::ChangeSet<bdk_chain::ConfirmationBlockTime>,
+    indexer: bdk_chain::keychain_txout::ChangeSet,
+    locked_outpoints: crate::locked_outpoints::ChangeSet,
+}
+
+/// Read the aggregate changeset of a `bdk_file_store::Store<ChangeSet>` file written by a version
+/// of the library whose changesets are of `version`, e.g. 1 for bdk_wallet 3.x.
+///
+/// The entries of such a store are `bincode` encoded without their version, so they can only be
+/// decoded as the changesets of the version that wrote them, and are then upgraded with the
+/// [`MIGRATIONS`]. Write the returned changeset to a
+/// [`VersionedChangeSets`](crate::VersionedChangeSets) store, or any other versioned backend, to
+/// upgrade the wallet. Returns `None` if the store has no entries.
+///
+/// Fails with [`FileStoreError::Load`](crate::FileStoreError::Load), holding the aggregate of
+/// the entries read before, if an entry cannot be decoded, and with
+/// [`FileStoreError::Migration`](crate::FileStoreError::Migration) if `version` is newer than the
+/// [`ChangeSet::CURRENT_VERSION`] or an entry can't be upgraded.
+#[cfg(feature = "file_store")]
+pub fn load_file_store<P>(
+    magic: &[u8],
+    file_path: P,
+    version: u32,
+) -> Result<Option<ChangeSet>, crate::FileStoreError>
+where
+    P: AsRef<std::path::Path>,
+{
+    check_changeset_version(version).map_err(crate::FileStoreError::Migration)?;
+    let file_path = file_path.as_ref();
+    match version {
+        0 => read_file_store::<ChangeSetV0>(magic, file_path, version),
+        1 => read_file_store::<ChangeSetV1>(magic, file_path, version),
+        _ => read_file_store::<ChangeSet>(magic, file_path, version),
+    }
+}
+
+/// Read and aggregate the entries of the store at `file_path`, decoded as `C` and upgraded from
+/// changeset `version`.
+#[cfg(feature = "file_store")]
+fn read_file_store<C>(
+    magic: &[u8],
+    file_path: &std::path::Path,
+    version: u32,
+) -> Result<Option<ChangeSet>, crate::FileStoreError>
+where
+    C: serde::de::DeserializeOwned + serde::Serialize,
+{
+    use crate::FileStoreError;
+    use bdk_file_store::{EntryIter, StoreError, StoreErrorWithDump};
+    use std::io::Read;
+
+    let upgrade = |changeset: C| {
+        serde_json::to_value(changeset)
+            .map_err(ChangeSetMigrationError::Json)
+            .and_then(|value| upgrade_changeset(value, version))
+            .map_err(FileStoreError::Migration)
+    };
+    let load_error = |error| {
+        FileStoreError::Load(StoreErrorWithDump {
+            changeset: None,
+            error,
+        })
+    };
+
+    let mut file = std::fs::File::open(file_path).map_err(|e| load_error(StoreError::Io(e)))?;
+    let mut got = alloc::vec![0u8; magic.len()];
+    file.read_exact(&mut got)
+        .map_err(|e| load_error(StoreError::Io(e)))?;
+    if got != magic {
+        return Err(load_error(StoreError::InvalidMagicBytes {
+            got,
+            expected: magic.to_vec(),
+        }));
+    }
+
+    let mut aggregate = Option::<ChangeSet>::None;
+    for entry in EntryIter::<C>::new(magic.len() as u64, &mut file) {
+        match entry {
+            Ok(changeset) => {
+                let changeset = upgrade(changeset)?;
+                match &mut aggregate {
+                    Some(aggregate) => aggregate.merge(changeset),
+                    None => aggregate = Some(changeset),
+                }
+            }
+            Err(error) => {
+                return Err(FileStoreError::Load(StoreErrorWithDump {
+                    changeset: aggregate.map(alloc::boxed::Box::new),
+                    error,
+                }))
+            }
+        }
+    }
+    Ok(aggregate)
+}
+
+/// Error when upgrading a persisted [`ChangeSet`] to the [`ChangeSet::CURRENT_VERSION`].
+#[derive(Debug)]
+pub enum ChangeSetMigrationError {
+    /// The changeset was written by a newer version of the library, with this changeset version.
+    NewerVersion(u32),
+    /// The changeset of this version is malformed, e.g. it is not a JSON object.
+    Malformed(u32),
+    /// The changeset could not be encoded or decoded.
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ChangeSetMigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NewerVersion(version) => write!(
+                f,
+                "The database is from a newer bdk_wallet: changeset version {version} is ahead of \
+                 the supported version {}",
+                ChangeSet::CURRENT_VERSION
+            ),
+            Self::Malformed(version) => {
+                write!(f, "The changeset of version {version} is malformed")
+            }
+            Self::Json(e) => write!(f, "Failed to decode the changeset: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChangeSetMigrationError {}
+
+#[cfg(all(test, feature = "rusqlite"))]
 mod test {
     use crate::rusqlite::{self, Connection};
     use crate::KeychainKind::{External, Internal};
@@ -15,12 +15,17 @@
 
 use alloc::{
     boxed::Box,
+    collections::BinaryHeap,
     string::{String, ToString},
     sync::Arc,
     vec::Vec,
 };
 use core::fmt::{Debug, Display};
-use core::{cmp::Ordering, fmt, mem, ops::Deref};
+use core::{
+    cmp::{Ordering, Reverse},
+    fmt, mem,
+    ops::{Deref, Range},
+};
 
 use bdk_chain::{
     indexed_tx_graph,
@@ -32,61 +37,121 @@ use bdk_chain::{
     },
     tx_graph::{CalculateFeeError, CanonicalTx, TxGraph, TxUpdate},
     BlockId, CanonicalizationParams, ChainPosition, ConfirmationBlockTime, DescriptorExt,
-    FullTxOut, Indexed, IndexedTxGraph, Indexer, Merge,
+    DescriptorId, FullTxOut, Indexed, IndexedTxGraph, Indexer, Merge, SpkIterator, BIP32_MAX_INDEX,
 };
 use bitcoin::{
     absolute,
+    bip158::{self, BlockFilter},
+    bip32::{DerivationPath, Fingerprint, Xpub},
     consensus::encode::serialize,
     constants::genesis_block,
+    hashes::{sha256, sha256d, Hash, HashEngine},
     psbt,
     secp256k1::Secp256k1,
-    sighash::{EcdsaSighashType, TapSighashType},
-    transaction, Address, Amount, Block, FeeRate, Network, NetworkKind, OutPoint, Psbt, ScriptBuf,
-    Sequence, SignedAmount, Transaction, TxOut, Txid, Weight, Witness,
+    sighash::{EcdsaSighashType, Prevouts, TapSighashType},
+    taproot::TapNodeHash,
+    transaction, Address, Amount, Block, BlockHash, FeeRate, Network, NetworkKind, OutPoint, Psbt,
+    Script, ScriptBuf, Sequence, SignedAmount, Transaction, TxOut, Txid, Weight, Witness,
+    XOnlyPublicKey,
 };
 use miniscript::{
-    descriptor::KeyMap,
+    descriptor::{DescriptorPublicKey, DescriptorSecretKey, KeyMap},
+    interpreter::Interpreter,
     psbt::{PsbtExt, PsbtInputExt, PsbtInputSatisfier},
+    Descriptor, ForEachKey,
 };
 use rand_core::RngCore;
 
+#[cfg(feature = "std")]
+mod auto_persist;
+pub mod broadcast;
 mod changeset;
 pub mod coin_selection;
+pub mod combine;
+#[cfg(feature = "encrypted_file_store")]
+mod encrypted_store;
 pub mod error;
 mod event;
 pub mod export;
+pub mod fee_estimation;
+pub mod integrity;
+pub mod labels;
 pub mod locked_outpoints;
-#[cfg(feature = "rusqlite")]
+pub mod metadata;
 pub mod migration;
+mod multi_sync;
 mod params;
 mod persisted;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+mod scan_cursor;
+pub mod scantxoutset;
+#[cfg(feature = "std")]
+mod shared;
 pub mod signer;
+mod spk_filter;
+#[cfg(feature = "rusqlite")]
+mod sqlite;
+#[cfg(feature = "rusqlite")]
+pub mod sqlite_wallets;
+#[cfg(feature = "file_store")]
+pub mod store_compaction;
 pub mod tx_builder;
+mod update_builder;
 pub(crate) mod utils;
+pub mod validation;
 
-use crate::collections::{BTreeMap, HashMap, HashSet};
+use crate::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use crate::descriptor::{
-    check_wallet_descriptor, checksum::calc_checksum, error::Error as DescriptorError,
-    policy::BuildSatisfaction, DerivedDescriptor, DescriptorMeta, ExtendedDescriptor,
-    ExtractPolicy, IntoWalletDescriptor, Policy, XKeyUtils,
+    check_wallet_descriptor,
+    checksum::calc_checksum,
+    error::Error as DescriptorError,
+    merge_multipath,
+    policy::{BuildSatisfaction, Satisfaction, SpendPath, Timelock},
+    rebase_keys, DerivedDescriptor, DescriptorMeta, ExtendedDescriptor, ExtractPolicy,
+    IntoWalletDescriptor, Policy, XKeyUtils,
 };
-use crate::psbt::PsbtUtils;
+use crate::keys::slip132::ScriptType;
+use crate::psbt::{InputAnalysis, PsbtUtils};
 use crate::types::*;
 use crate::wallet::{
-    coin_selection::{DefaultCoinSelectionAlgorithm, Excess, InsufficientFunds},
-    error::{BuildFeeBumpError, CreateTxError, MiniscriptPsbtError},
-    signer::{SignOptions, SignerError, SignerOrdering, SignersContainer, TransactionSigner},
+    coin_selection::{
+        BranchAndBoundCoinSelection, CoinSelectionAlgorithm, DefaultCoinSelectionAlgorithm, Excess,
+        InsufficientFunds,
+    },
+    combine::{CombineError, CombineReport, InputSignatures},
+    error::{BuildFeeBumpError, CreateTxError, MiniscriptPsbtError, VerifyError},
+    scantxoutset::{ScanTxOutSetError, ScanTxOutSetResult},
+    signer::{
+        PreimageSatisfier, SignOptions, SignerError, SignerOrdering, SignersContainer,
+        TransactionSigner,
+    },
     tx_builder::{FeePolicy, TxBuilder, TxParams},
     utils::{check_nsequence_rbf, After, Older, SecpCtx},
+    validation::{ApplyError, ApplyOptions},
 };
 
 // re-exports
+#[cfg(feature = "std")]
+pub use auto_persist::AutoPersist;
 pub use bdk_chain::Balance;
-pub use changeset::ChangeSet;
+pub use broadcast::{AsyncBroadcaster, BroadcastError, Broadcaster};
+pub use changeset::{ChangeSet, ChangeSetJsonError};
+#[cfg(feature = "encrypted_file_store")]
+pub use encrypted_store::*;
 pub use error::{LoadError, LoadMismatch};
 pub use event::*;
+pub use fee_estimation::{FeeEstimationError, FeeEstimator};
+pub use multi_sync::MultiSyncRequest;
 pub use params::*;
 pub use persisted::*;
+pub use scan_cursor::ScanCursor;
+#[cfg(feature = "std")]
+pub use shared::{SharedWallet, SharedWalletError};
+pub use spk_filter::{FilteredBlock, SpkFilter};
+#[cfg(feature = "rusqlite")]
+pub use sqlite::SqlitePragmas;
+pub use update_builder::{UpdateBuilder, UpdateBuilderError};
 pub use utils::IsDust;
 pub use utils::TxDetails;
 
@@ -117,6 +182,16 @@ pub struct Wallet {
     network: Network,
     secp: SecpCtx,
     locked_outpoints: HashSet<OutPoint>,
+    output_labels: BTreeMap<OutPoint, String>,
+    metadata: BTreeMap<String, Vec<u8>>,
+    pending_labels: HashMap<sha256d::Hash, BTreeMap<u32, String>>,
+    keychain_lookahead: BTreeMap<KeychainKind, u32>,
+    spk_cache_digests: BTreeMap<DescriptorId, SpkCacheDigest>,
+    min_relay_fee: FeeRate,
+    canonicalization_policy: CanonicalizationPolicy,
+    /// The parameters following `canonicalization_policy`, computed again when the transactions
+    /// or the chain change.
+    canonicalization_params: CanonicalizationParams,
 }
 
 /// An update to [`Wallet`].
@@ -181,9 +256,52 @@ impl fmt::Display for AddressInfo {
     }
 }
 
+/// Status of an address of a keychain, as returned by [`Wallet::address_statuses`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressStatus {
+    /// The address is past the last revealed index of the keychain.
+    Unrevealed,
+    /// The address is revealed, but no transaction of the wallet pays to it.
+    Unused,
+    /// A transaction of the wallet pays to the address.
+    Used,
+}
+
 /// A `CanonicalTx` managed by a `Wallet`.
 pub type WalletTx<'a> = CanonicalTx<'a, Arc<Transaction>, ConfirmationBlockTime>;
 
+/// Ordering of [`WalletTx`]s for display, as in a transaction history.
+///
+/// This is a trait because [`WalletTx`] is an alias of a type defined in [`bdk_chain`].
+pub trait WalletTxOrd {
+    /// Compare two transactions, newest first.
+    ///
+    /// Unconfirmed transactions come first, by descending last seen time, followed by confirmed
+    /// transactions by descending confirmation height. The position of a transaction in its block
+    /// isn't known from its anchor, so transactions seen at the same time or confirmed in the same
+    /// block are ordered by txid. This is a total order, so sorting with it gives the same result
+    /// in every run.
+    fn display_ord(&self, other: &Self) -> Ordering;
+}
+
+impl WalletTxOrd for WalletTx<'_> {
+    fn display_ord(&self, other: &Self) -> Ordering {
+        fn key(tx: &WalletTx) -> (bool, Reverse<u64>) {
+            match &tx.chain_position {
+                ChainPosition::Unconfirmed { last_seen, .. } => {
+                    (false, Reverse(last_seen.unwrap_or(0)))
+                }
+                ChainPosition::Confirmed { anchor, .. } => {
+                    (true, Reverse(anchor.block_id.height as u64))
+                }
+            }
+        }
+        key(self)
+            .cmp(&key(other))
+            .then_with(|| self.tx_node.txid.cmp(&other.tx_node.txid))
+    }
+}
+
 impl Wallet {
     /// Build a new single descriptor [`Wallet`].
     ///
@@ -232,6 +350,39 @@ impl Wallet {
         CreateParams::new_single(descriptor)
     }
 
+    /// Build a new watch-only [`Wallet`] with a single `descriptor`.
+    ///
+    /// This is [`create_single`](Self::create_single) with
+    /// [`CreateParams::watch_only`]: creating the wallet fails with
+    /// [`DescriptorError::SecretKeysNotAllowed`] if the descriptor contains any secret key, so
+    /// that a wallet that must never sign is guaranteed to be created without signers. Use
+    /// [`CreateParams::watch_only`] for a wallet with a change descriptor.
+    ///
+    /// ```rust
+    /// # use bdk_wallet::descriptor::DescriptorError;
+    /// # use bdk_wallet::Wallet;
+    /// # use bitcoin::Network;
+    /// let xpub = "wpkh(tpubDDks68wKK1xKaVVVbNmXUAx68K1K817M6KwjvjEyCrjdU7xMvjKnfYAtZjfZcrfPfGFzqmibuVqMzKJGbBnK7mo7WSJri8Y9QgM7aNQ3fCp/0/*)";
+    /// let wallet = Wallet::create_watch_only(xpub)
+    ///     .network(Network::Testnet)
+    ///     .create_wallet_no_persist()?;
+    ///
+    /// let xprv = "tr(tprv8ZgxMBicQKsPdDArR4xSAECuVxeX1jwwSXR4ApKbkYgZiziDc4LdBy2WvJeGDfUSE4UT4hHhbgEwbdq8ajjUHiKDegkwrNU6V55CxcxonVN/*)";
+    /// assert!(matches!(
+    ///     Wallet::create_watch_only(xprv)
+    ///         .network(Network::Testnet)
+    ///         .create_wallet_no_persist(),
+    ///     Err(DescriptorError::SecretKeysNotAllowed)
+    /// ));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn create_watch_only<D>(descriptor: D) -> CreateParams
+    where
+        D: IntoWalletDescriptor + Send + Clone + 'static,
+    {
+        CreateParams::new_single(descriptor).watch_only()
+    }
+
     /// Build a new [`Wallet`].
     ///
     /// If you have previously created a wallet, use [`load`](Self::load) instead.
@@ -321,8 +472,16 @@ impl Wallet {
         let (chain, chain_changeset) = LocalChain::from_genesis_hash(genesis_hash);
 
         let (descriptor, mut descriptor_keymap) = (params.descriptor)(&secp, network_kind)?;
-        check_wallet_descriptor(&descriptor)?;
         descriptor_keymap.extend(params.descriptor_keymap);
+        if params.watch_only && !descriptor_keymap.is_empty() {
+            return Err(DescriptorError::SecretKeysNotAllowed);
+        }
+        let (descriptor, descriptor_keymap) = if params.rebase_hardened {
+            rebase_keys(&secp, descriptor, descriptor_keymap)?
+        } else {
+            (descriptor, descriptor_keymap)
+        };
+        check_wallet_descriptor(&descriptor)?;
 
         let signers = Arc::new(SignersContainer::build(
             descriptor_keymap,
@@ -333,8 +492,16 @@ impl Wallet {
         let (change_descriptor, change_signers) = match params.change_descriptor {
             Some(make_desc) => {
                 let (change_descriptor, mut internal_keymap) = make_desc(&secp, network_kind)?;
-                check_wallet_descriptor(&change_descriptor)?;
                 internal_keymap.extend(params.change_descriptor_keymap);
+                if params.watch_only && !internal_keymap.is_empty() {
+                    return Err(DescriptorError::SecretKeysNotAllowed);
+                }
+                let (change_descriptor, internal_keymap) = if params.rebase_hardened {
+                    rebase_keys(&secp, change_descriptor, internal_keymap)?
+                } else {
+                    (change_descriptor, internal_keymap)
+                };
+                check_wallet_descriptor(&change_descriptor)?;
                 let change_signers = Arc::new(SignersContainer::build(
                     internal_keymap,
                     &change_descriptor,
@@ -346,6 +513,8 @@ impl Wallet {
         };
 
         let locked_outpoints = HashSet::new();
+        let output_labels = BTreeMap::new();
+        let metadata = BTreeMap::new();
 
         let mut stage = ChangeSet {
             descriptor: Some(descriptor.clone()),
@@ -365,16 +534,28 @@ impl Wallet {
             params.use_spk_cache,
         )?;
 
-        Ok(Wallet {
+        let mut wallet = Wallet {
             signers,
             change_signers,
             network,
             chain,
             tx_graph,
-            stage,
+            stage: ChangeSet::default(),
             secp,
             locked_outpoints,
-        })
+            output_labels,
+            metadata,
+            pending_labels: HashMap::new(),
+            keychain_lookahead: params.keychain_lookahead,
+            spk_cache_digests: BTreeMap::new(),
+            min_relay_fee: FeeRate::BROADCAST_MIN,
+            canonicalization_policy: CanonicalizationPolicy::default(),
+            canonicalization_params: CanonicalizationParams::default(),
+        };
+        wallet.stage_changes(stage);
+        wallet.replenish_keychain_lookahead();
+
+        Ok(wallet)
     }
 
     /// Build [`Wallet`] by loading from persistence or [`ChangeSet`].
@@ -384,6 +565,8 @@ impl Wallet {
     /// can also add keys when building the wallet by using [`LoadParams::keymap`]. Finally
     /// you can check the wallet's descriptors are what you expect with [`LoadParams::descriptor`]
     /// which will try to populate signers if [`LoadParams::extract_keys`] is enabled.
+    /// Signers added with [`Wallet::add_signer`] aren't persisted either, re-attach them with
+    /// [`LoadParams::add_signer`].
     ///
     /// # Synopsis
     ///
@@ -559,29 +742,91 @@ impl Wallet {
             .map(|(op, _)| op)
             .collect();
 
+        // Apply output labels
+        let output_labels = changeset
+            .labels
+            .outputs
+            .into_iter()
+            .filter_map(|(op, label)| Some((op, label?)))
+            .collect();
+
+        // Apply metadata
+        let metadata = changeset
+            .metadata
+            .entries
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value?)))
+            .collect();
+
         let mut stage = ChangeSet::default();
 
-        let tx_graph = make_indexed_graph(
+        let mut indexer = changeset.indexer;
+        if params.ignore_spk_cache {
+            indexer.spk_cache.clear();
+        }
+        let spk_cache_digests = [Some(&descriptor), change_descriptor.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|descriptor| {
+                let digest = verify_spk_cache(
+                    &mut indexer.spk_cache,
+                    descriptor,
+                    &changeset.spk_cache_digests,
+                )?;
+                Some((descriptor.descriptor_id(), digest))
+            })
+            .collect::<BTreeMap<_, _>>();
+        // caches persisted before their digest get one staged
+        let undigested_spk_caches = spk_cache_digests
+            .keys()
+            .filter(|&did| !changeset.spk_cache_digests.contains_key(did))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut tx_graph = make_indexed_graph(
             &mut stage,
             changeset.tx_graph,
-            changeset.indexer,
+            indexer,
             descriptor,
             change_descriptor,
             params.lookahead,
             params.use_spk_cache,
         )
         .map_err(LoadError::Descriptor)?;
-
-        Ok(Some(Wallet {
+        // stage the script pubkeys derived in place of the dropped caches, which revealing nothing
+        // does
+        let mut indexer_changeset = tx_graph.index.reveal_to_target_multi(&BTreeMap::new());
+        indexer_changeset
+            .spk_cache
+            .retain(|_, spks| !spks.is_empty());
+        stage.indexer.merge(indexer_changeset);
+
+        let mut wallet = Wallet {
             signers,
             change_signers,
             chain,
             tx_graph,
-            stage,
+            stage: ChangeSet::default(),
             network,
             secp,
             locked_outpoints,
-        }))
+            output_labels,
+            metadata,
+            pending_labels: HashMap::new(),
+            keychain_lookahead: params.keychain_lookahead,
+            spk_cache_digests,
+            min_relay_fee: FeeRate::BROADCAST_MIN,
+            canonicalization_policy: CanonicalizationPolicy::default(),
+            canonicalization_params: CanonicalizationParams::default(),
+        };
+        wallet.stage_changes(stage);
+        wallet.stage_spk_cache_digests(undigested_spk_caches);
+        wallet.reindex_keychain_lookahead();
+        for (keychain, ordering, signer) in params.signers {
+            wallet.add_signer(keychain, ordering, signer);
+        }
+
+        Ok(Some(wallet))
     }
 
     /// Get the [`Network`] the wallet is using.
@@ -650,14 +895,14 @@ impl Wallet {
     /// ```
     pub fn reveal_next_address(&mut self, keychain: KeychainKind) -> AddressInfo {
         let keychain = self.map_keychain(keychain);
-        let index = &mut self.tx_graph.index;
-        let stage = &mut self.stage;
-
-        let ((index, spk), index_changeset) = index
+        let ((index, spk), index_changeset) = self
+            .tx_graph
+            .index
             .reveal_next_spk(keychain)
             .expect("keychain must exist");
 
-        stage.merge(index_changeset.into());
+        self.stage_changes(index_changeset);
+        self.replenish_keychain_lookahead();
 
         AddressInfo {
             index,
@@ -688,7 +933,8 @@ impl Wallet {
             .reveal_to_target(keychain, index)
             .expect("keychain must exist");
 
-        self.stage.merge(index_changeset.into());
+        self.stage_changes(index_changeset);
+        self.replenish_keychain_lookahead();
 
         spks.into_iter().map(move |(index, spk)| AddressInfo {
             index,
@@ -716,6 +962,7 @@ impl Wallet {
 
         self.stage
             .merge(indexed_tx_graph::ChangeSet::from(index_changeset).into());
+        self.replenish_keychain_lookahead();
 
         AddressInfo {
             index,
@@ -744,6 +991,96 @@ impl Wallet {
         self.tx_graph.index.unmark_used(keychain, index)
     }
 
+    /// Get the lookahead of the given `keychain`.
+    ///
+    /// This is the number of script pubkeys derived over and above the last revealed index of
+    /// the `keychain`. See [`CreateParams::lookahead`] and [`CreateParams::lookahead_for`].
+    pub fn lookahead(&self, keychain: KeychainKind) -> u32 {
+        let lookahead = self.tx_graph.index.lookahead();
+        self.keychain_lookahead
+            .get(&keychain)
+            .map_or(lookahead, |&keychain_lookahead| {
+                keychain_lookahead.max(lookahead)
+            })
+    }
+
+    /// Set the lookahead of the given `keychain`.
+    ///
+    /// Script pubkeys up to the new lookahead are derived immediately and the transactions
+    /// already known to the wallet are reindexed, so outputs that previously fell outside of the
+    /// lookahead window are detected and revealed. The next sync or full scan request will
+    /// reflect the new window.
+    ///
+    /// Lowering the lookahead does not forget script pubkeys that were already derived, and the
+    /// lookahead of a keychain can never be lower than the wallet-wide lookahead the wallet was
+    /// created or loaded with.
+    ///
+    /// **WARNING**: The change of lookahead itself is not persisted. To keep using the new value
+    /// after reloading the wallet, pass it to [`LoadParams::lookahead_for`].
+    pub fn set_lookahead(&mut self, keychain: KeychainKind, lookahead: u32) {
+        self.keychain_lookahead.insert(keychain, lookahead);
+        self.reindex_keychain_lookahead();
+    }
+
+    /// Returns whether funds of the `keychain` may exist beyond the range of script pubkeys
+    /// scanned so far, suggesting a deeper scan.
+    ///
+    /// This is a heuristic for restored wallets: it returns `true` when the last used index of
+    /// the `keychain` is within the [`lookahead`](Self::lookahead) of its last revealed index,
+    /// i.e. when `last_revealed - last_used < lookahead`. Since used script pubkeys found in the
+    /// lookahead window are revealed, this means the last used address was close to the end of
+    /// the scanned range. A `keychain` without used script pubkeys never needs a rescan.
+    ///
+    /// To rescan, raise the lookahead to [`recommended_lookahead`](Self::recommended_lookahead)
+    /// with [`set_lookahead`](Self::set_lookahead) and perform a full scan.
+    pub fn rescan_needed(&self, keychain: KeychainKind) -> bool {
+        let index = &self.tx_graph.index;
+        match (
+            index.last_revealed_index(keychain),
+            index.last_used_index(keychain),
+        ) {
+            (Some(last_revealed), Some(last_used)) => {
+                last_revealed.saturating_sub(last_used) < self.lookahead(keychain)
+            }
+            _ => false,
+        }
+    }
+
+    /// The lookahead recommended to rescan the `keychain` when
+    /// [`rescan_needed`](Self::rescan_needed) returns `true`.
+    ///
+    /// This is twice the current [`lookahead`](Self::lookahead) of the `keychain`.
+    pub fn recommended_lookahead(&self, keychain: KeychainKind) -> u32 {
+        self.lookahead(keychain).saturating_mul(2)
+    }
+
+    /// Derive script pubkeys up to the lookahead of every keychain with a custom lookahead.
+    ///
+    /// Returns whether new script pubkeys were derived.
+    fn replenish_keychain_lookahead(&mut self) -> bool {
+        let spk_count = self.tx_graph.index.inner().all_spks().len();
+        for (keychain, lookahead) in self.keychain_lookahead.clone() {
+            let index = &mut self.tx_graph.index;
+            let next_index = match index.next_index(keychain) {
+                Some((next_index, _)) => next_index,
+                None => continue,
+            };
+            let target_index = next_index.saturating_add(lookahead).saturating_sub(1);
+            let index_changeset = index.lookahead_to_target(keychain, target_index);
+            self.stage_changes(index_changeset);
+        }
+        self.tx_graph.index.inner().all_spks().len() > spk_count
+    }
+
+    /// Like [`replenish_keychain_lookahead`](Self::replenish_keychain_lookahead), but also
+    /// reindexes the transaction graph until no more script pubkeys are derived.
+    fn reindex_keychain_lookahead(&mut self) {
+        while self.replenish_keychain_lookahead() {
+            let changeset = self.tx_graph.reindex();
+            self.stage_changes(changeset);
+        }
+    }
+
     /// List addresses that are revealed but unused.
     ///
     /// Note if the returned iterator is empty you can reveal more addresses
@@ -764,11 +1101,69 @@ impl Wallet {
             })
     }
 
+    /// List the addresses of `keychain` at the indices of `range` with their [`AddressStatus`].
+    ///
+    /// An address is [used](AddressStatus::Used) when it appears in an output of a transaction of
+    /// the wallet, whether or not the transaction is canonical. Indices past the
+    /// [BIP32](https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki) max index are
+    /// skipped, and a non-wildcard descriptor only has the index 0.
+    pub fn address_statuses(
+        &self,
+        keychain: KeychainKind,
+        range: Range<u32>,
+    ) -> Vec<(u32, Address, AddressStatus)> {
+        let keychain = self.map_keychain(keychain);
+        let index = &self.tx_graph.index;
+        let spk_iter = index
+            .unbounded_spk_iter(keychain)
+            .expect("keychain must exist");
+        let end = if spk_iter.descriptor().has_wildcard() {
+            range.end.min(BIP32_MAX_INDEX + 1)
+        } else {
+            range.end.min(1)
+        };
+        let last_revealed = index.last_revealed_index(keychain);
+        spk_iter
+            .skip(range.start as usize)
+            .take(end.saturating_sub(range.start) as usize)
+            .map(|(i, spk)| {
+                let status = if index.is_used(keychain, i) {
+                    AddressStatus::Used
+                } else if last_revealed.is_some_and(|last| i <= last) {
+                    AddressStatus::Unused
+                } else {
+                    AddressStatus::Unrevealed
+                };
+                let address =
+                    Address::from_script(&spk, self.network).expect("must have address form");
+                (i, address, status)
+            })
+            .collect()
+    }
+
     /// Return whether or not a `script` is part of this wallet (either internal or external)
     pub fn is_mine(&self, script: ScriptBuf) -> bool {
         self.tx_graph.index.index_of_spk(script).is_some()
     }
 
+    /// Return whether the compact block filter ([BIP158]) of the block with `block_hash` matches
+    /// any of the wallet's script pubkeys, including the ones in the lookahead window.
+    ///
+    /// This lets a chain source download only the blocks that may be relevant to the wallet.
+    /// Filters have false positives, so a matching block may still have no wallet transaction.
+    /// The lookahead window moves as blocks are applied, so filters must be matched in order,
+    /// after applying the matching blocks before them.
+    ///
+    /// [BIP158]: https://github.com/bitcoin/bips/blob/master/bip-0158.mediawiki
+    pub fn block_filter_matches(
+        &self,
+        filter: &BlockFilter,
+        block_hash: &BlockHash,
+    ) -> Result<bool, bip158::Error> {
+        let spks = self.tx_graph.index.inner().all_spks().values();
+        filter.match_any(block_hash, spks.map(|spk| spk.as_bytes()))
+    }
+
     /// Finds how the wallet derived the script pubkey `spk`.
     ///
     /// Will only return `Some(_)` if the wallet has given out the spk.
@@ -783,12 +1178,20 @@ impl Wallet {
             .filter_chain_unspents(
                 &self.chain,
                 self.chain.tip().block_id(),
-                CanonicalizationParams::default(),
+                self.canonicalization_params(),
                 self.tx_graph.index.outpoints().iter().cloned(),
             )
             .map(|((k, i), full_txo)| new_local_utxo(k, i, full_txo))
     }
 
+    /// Summarize the unspent outputs of the wallet: their number and value, how many of them are
+    /// below the dust limit, and their ages at `current_height`.
+    ///
+    /// This can help deciding when to consolidate UTXOs.
+    pub fn utxo_stats(&self, current_height: u32) -> UtxoStats {
+        UtxoStats::new(self.list_unspent(), current_height)
+    }
+
     /// Get the [`TxDetails`] of a wallet transaction.
     ///
     /// If the transaction with txid [`Txid`] cannot be found in the wallet's transactions, `None`
@@ -825,7 +1228,7 @@ impl Wallet {
             .filter_chain_txouts(
                 &self.chain,
                 self.chain.tip().block_id(),
-                CanonicalizationParams::default(),
+                self.canonicalization_params(),
                 self.tx_graph.index.outpoints().iter().cloned(),
             )
             .map(|((k, i), full_txo)| new_local_utxo(k, i, full_txo))
@@ -841,6 +1244,13 @@ impl Wallet {
         self.chain.tip()
     }
 
+    /// Start building an [`Update`] on top of the wallet's chain tip.
+    ///
+    /// This is meant for chain sources without a BDK crate, see [`UpdateBuilder`].
+    pub fn update_builder(&self) -> UpdateBuilder {
+        UpdateBuilder::new(self.chain.tip())
+    }
+
     /// Get unbounded script pubkey iterators for both `Internal` and `External` keychains.
     ///
     /// This is intended to be used when doing a full scan of your addresses (e.g. after restoring
@@ -872,6 +1282,9 @@ impl Wallet {
 
     /// Returns the utxo owned by this wallet corresponding to `outpoint` if it exists in the
     /// wallet's database.
+    ///
+    /// Returns `None` if the outpoint is unknown, not owned by the wallet or already spent by a
+    /// canonical transaction.
     pub fn get_utxo(&self, op: OutPoint) -> Option<LocalOutput> {
         let ((keychain, index), _) = self.tx_graph.index.txout(op)?;
         self.tx_graph
@@ -879,13 +1292,21 @@ impl Wallet {
             .filter_chain_unspents(
                 &self.chain,
                 self.chain.tip().block_id(),
-                CanonicalizationParams::default(),
+                self.canonicalization_params(),
                 core::iter::once(((), op)),
             )
             .map(|(_, full_txo)| new_local_utxo(keychain, index, full_txo))
             .next()
     }
 
+    /// Return whether `outpoint` is currently an unspent output owned by this wallet.
+    ///
+    /// This is useful to check that an input of a PSBT we are asked to sign is ours. See
+    /// [`Wallet::get_utxo`].
+    pub fn is_mine_utxo(&self, outpoint: OutPoint) -> bool {
+        self.get_utxo(outpoint).is_some()
+    }
+
     /// Inserts a [`TxOut`] at [`OutPoint`] into the wallet's transaction graph.
     ///
     /// This is used for providing a previous output's value so that we can use [`calculate_fee`]
@@ -905,7 +1326,8 @@ impl Wallet {
     /// [`list_output`]: Self::list_output
     pub fn insert_txout(&mut self, outpoint: OutPoint, txout: TxOut) {
         let additions = self.tx_graph.insert_txout(outpoint, txout);
-        self.stage.merge(additions.into());
+        self.stage_changes(additions);
+        self.update_canonicalization_params();
     }
 
     /// Calculates the fee of a given transaction. Returns [`Amount::ZERO`] if `tx` is a coinbase
@@ -1059,11 +1481,69 @@ impl Wallet {
             .list_canonical_txs(
                 &self.chain,
                 self.chain.tip().block_id(),
-                CanonicalizationParams::default(),
+                self.canonicalization_params(),
             )
             .find(|tx| tx.tx_node.txid == txid)
     }
 
+    /// Get the transactions in the wallet's graph spending any of the outputs spent by the
+    /// transaction with `txid`.
+    ///
+    /// This lists replacements and double-spends of the transaction, whether they are confirmed
+    /// or not, including non-canonical ones. Descendants of the conflicting transactions are not
+    /// included. Returns an empty list if the transaction is not in the graph.
+    pub fn conflicting_txs(&self, txid: Txid) -> Vec<Txid> {
+        let graph = self.tx_graph.graph();
+        let tx = match graph.get_tx(txid) {
+            Some(tx) => tx,
+            None => return Vec::new(),
+        };
+        let mut conflicts = graph
+            .direct_conflicts(&tx)
+            .map(|(_, txid)| txid)
+            .collect::<Vec<_>>();
+        conflicts.sort();
+        conflicts.dedup();
+        conflicts
+    }
+
+    /// Get the chain of replacements the transaction with `txid` belongs to, e.g. a transaction
+    /// and its successive fee bumps.
+    ///
+    /// The chain is made of the transactions in the wallet's graph spending the same outputs as
+    /// `txid`, directly or through other members of the chain. They're ordered by increasing fee,
+    /// then by the time they were first seen, so the newest replacement comes last. Replaced and
+    /// evicted transactions stay in the graph, so an intermediate replacement evicted from the
+    /// mempool is still part of the chain. Transactions with an unknown fee come first.
+    ///
+    /// Returns just `txid` if the transaction has no conflicts, and an empty list if it is not in
+    /// the graph.
+    pub fn replacement_chain(&self, txid: Txid) -> Vec<Txid> {
+        let graph = self.tx_graph.graph();
+        let Some(tx) = graph.get_tx(txid) else {
+            return Vec::new();
+        };
+        let mut members = BTreeSet::from([txid]);
+        let mut to_visit = vec![tx];
+        while let Some(tx) = to_visit.pop() {
+            for (_, conflict) in graph.direct_conflicts(&tx) {
+                if members.insert(conflict) {
+                    to_visit.extend(graph.get_tx(conflict));
+                }
+            }
+        }
+        let mut chain = members
+            .into_iter()
+            .filter_map(|txid| {
+                let node = graph.get_tx_node(txid)?;
+                let fee = graph.calculate_fee(&node.tx).ok();
+                Some((fee, node.first_seen, txid))
+            })
+            .collect::<Vec<_>>();
+        chain.sort();
+        chain.into_iter().map(|(_, _, txid)| txid).collect()
+    }
+
     /// Iterate over relevant and canonical transactions in the wallet.
     ///
     /// A transaction is relevant when it spends from or spends to at least one tracked output. A
@@ -1082,7 +1562,7 @@ impl Wallet {
             .list_canonical_txs(
                 &self.chain,
                 self.chain.tip().block_id(),
-                CanonicalizationParams::default(),
+                self.canonicalization_params(),
             )
             .filter(|c_tx| tx_index.is_tx_relevant(&c_tx.tx_node.tx))
     }
@@ -1112,20 +1592,122 @@ impl Wallet {
         txs
     }
 
+    /// Array of relevant and canonical transactions in the wallet, newest first.
+    ///
+    /// This is the order of a transaction history, see [`WalletTxOrd::display_ord`].
+    pub fn transactions_sorted(&self) -> Vec<WalletTx<'_>> {
+        self.transactions_sort_by(|tx1, tx2| tx1.display_ord(tx2))
+    }
+
+    /// A page of the relevant and canonical transactions in the wallet: `limit` transactions
+    /// after skipping the first `offset` ones in the given `order`.
+    ///
+    /// Transactions are ordered with [`WalletTxOrd::display_ord`] for [`HistoryOrder::NewestFirst`],
+    /// so that the pages concatenated are [`transactions_sorted`](Self::transactions_sorted), and
+    /// in the reverse order for [`HistoryOrder::OldestFirst`].
+    ///
+    /// The transaction graph isn't indexed by position, so every call still goes through all the
+    /// wallet transactions, but only `offset + limit` of them are kept in memory at once.
+    pub fn transactions_paginated(
+        &self,
+        offset: usize,
+        limit: usize,
+        order: HistoryOrder,
+    ) -> Vec<WalletTx<'_>> {
+        struct PageEntry<'a>(WalletTx<'a>, HistoryOrder);
+
+        impl Ord for PageEntry<'_> {
+            fn cmp(&self, other: &Self) -> Ordering {
+                let ord = self.0.display_ord(&other.0);
+                match self.1 {
+                    HistoryOrder::NewestFirst => ord,
+                    HistoryOrder::OldestFirst => ord.reverse(),
+                }
+            }
+        }
+        impl PartialOrd for PageEntry<'_> {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl PartialEq for PageEntry<'_> {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
+        impl Eq for PageEntry<'_> {}
+
+        if limit == 0 {
+            return Vec::new();
+        }
+        let len = offset.saturating_add(limit);
+        // max-heap of the first `len` transactions, the last one in page order on top
+        let mut heap = BinaryHeap::new();
+        for tx in self.transactions() {
+            heap.push(PageEntry(tx, order));
+            if heap.len() > len {
+                heap.pop();
+            }
+        }
+        heap.into_sorted_vec()
+            .into_iter()
+            .skip(offset)
+            .map(|entry| entry.0)
+            .collect()
+    }
+
     /// Return the balance, separated into available, trusted-pending, untrusted-pending, and
     /// immature values.
     pub fn balance(&self) -> Balance {
         self.tx_graph.graph().balance(
             &self.chain,
             self.chain.tip().block_id(),
-            CanonicalizationParams::default(),
+            self.canonicalization_params(),
             self.tx_graph.index.outpoints().iter().cloned(),
             |&(k, _), _| k == KeychainKind::Internal,
         )
     }
 
+    /// Return the balance the wallet had as of the block at `height`.
+    ///
+    /// Only outputs of transactions confirmed at or before `height` are counted, and outputs
+    /// spent by transactions confirmed after `height` are still considered owned. Unconfirmed
+    /// transactions are ignored, so the pending values of the returned [`Balance`] are always
+    /// zero. If `height` is above the wallet's tip the balance at the tip is returned.
+    pub fn balance_at(&self, height: u32) -> Balance {
+        let tip = self
+            .chain
+            .tip()
+            .floor_at(height)
+            .expect("local chain always contains genesis")
+            .block_id();
+        let mut balance = Balance::default();
+        for (_, txout) in self.tx_graph.graph().filter_chain_txouts(
+            &self.chain,
+            tip,
+            CanonicalizationParams::default(),
+            self.tx_graph.index.outpoints().iter().cloned(),
+        ) {
+            if !txout.chain_position.is_confirmed() {
+                continue;
+            }
+            if matches!(&txout.spent_by, Some((pos, _)) if pos.is_confirmed()) {
+                continue;
+            }
+            if txout.is_mature(tip.height) {
+                balance.confirmed += txout.txout.value;
+            } else {
+                balance.immature += txout.txout.value;
+            }
+        }
+        balance
+    }
+
     /// Add an external signer
     ///
+    /// Signers are not persisted, use [`LoadParams::add_signer`] to add them again when loading
+    /// the wallet.
+    ///
     /// See [the `signer` module](signer) for an example.
     pub fn add_signer(
         &mut self,
@@ -1188,6 +1770,74 @@ impl Wallet {
         }
     }
 
+    /// Get the minimum fee rate assumed to be relayed by the network.
+    ///
+    /// See [`set_min_relay_fee`](Self::set_min_relay_fee).
+    pub fn min_relay_fee(&self) -> FeeRate {
+        self.min_relay_fee
+    }
+
+    /// Set the minimum fee rate assumed to be relayed by the network, 1 sat/vB by default.
+    ///
+    /// Building a transaction with a lower [fee rate](TxBuilder::fee_rate) fails with
+    /// [`CreateTxError::FeeRateBelowMinRelay`], since it would not be relayed. An
+    /// [absolute fee](TxBuilder::fee_absolute) is used as is.
+    ///
+    /// The dust limit of the recipients and of the change output is computed at this fee rate
+    /// when it is higher than the default dust relay fee of 3 sat/vB. Change below it is added
+    /// to the fee.
+    ///
+    /// **WARNING**: The minimum relay fee is not persisted and is reset when the wallet is
+    /// loaded.
+    pub fn set_min_relay_fee(&mut self, min_relay_fee: FeeRate) {
+        self.min_relay_fee = min_relay_fee;
+    }
+
+    /// Get the policy choosing between conflicting unconfirmed transactions last seen at the same
+    /// time.
+    ///
+    /// See [`set_canonicalization_policy`](Self::set_canonicalization_policy).
+    pub fn canonicalization_policy(&self) -> CanonicalizationPolicy {
+        self.canonicalization_policy
+    }
+
+    /// Set the policy choosing between conflicting unconfirmed transactions last seen at the same
+    /// time, [`CanonicalizationPolicy::TxidOrder`] by default.
+    ///
+    /// The policy applies to every view of the wallet transactions, e.g. the
+    /// [balance](Self::balance), the [unspent outputs](Self::list_unspent) and the outputs
+    /// selected to build transactions. It never makes a confirmed transaction non-canonical.
+    /// Other policies than the default canonicalize the wallet transactions several times
+    /// whenever they or the chain change, which makes applying updates slower for large wallets.
+    ///
+    /// **WARNING**: The policy is not persisted and is reset when the wallet is loaded.
+    pub fn set_canonicalization_policy(&mut self, policy: CanonicalizationPolicy) {
+        self.canonicalization_policy = policy;
+        self.update_canonicalization_params();
+    }
+
+    /// The parameters of the canonicalization of the wallet transactions, following the
+    /// [`CanonicalizationPolicy`].
+    fn canonicalization_params(&self) -> CanonicalizationParams {
+        self.canonicalization_params.clone()
+    }
+
+    /// Compute the [`canonicalization_params`](Self::canonicalization_params) again, after the
+    /// wallet transactions or chain changed.
+    fn update_canonicalization_params(&mut self) {
+        self.canonicalization_params = canonicalization_params(
+            self.canonicalization_policy,
+            self.tx_graph.graph(),
+            &self.chain,
+        );
+    }
+
+    /// The fee rate at which an output whose spending cost exceeds its value is dust.
+    fn dust_relay_fee(&self) -> FeeRate {
+        const DUST_RELAY_FEE: FeeRate = FeeRate::from_sat_per_kwu(750); // 3 sat/vb
+        self.min_relay_fee.max(DUST_RELAY_FEE)
+    }
+
     /// Start building a transaction.
     ///
     /// This returns a blank [`TxBuilder`] from which you can specify the parameters for the
@@ -1222,6 +1872,7 @@ impl Wallet {
             wallet: self,
             params: TxParams::default(),
             coin_selection: DefaultCoinSelectionAlgorithm::default(),
+            fee_estimator: None,
         }
     }
 
@@ -1359,10 +2010,22 @@ impl Wallet {
             (None, None) => Sequence::ENABLE_RBF_NO_LOCKTIME,
             // None requested, use required.
             (None, Some(csv)) => csv,
+            // Requested sequence doesn't signal RBF.
+            (Some(sequence), _) if params.require_rbf && !sequence.is_rbf() => {
+                return Err(CreateTxError::RbfSequence(sequence))
+            }
             // Requested sequence is incompatible with requirements.
             (Some(sequence), Some(csv)) if !check_nsequence_rbf(sequence, csv) => {
                 return Err(CreateTxError::RbfSequenceCsv { sequence, csv })
             }
+            // Requested sequence disables the required absolute timelock.
+            (Some(sequence), _)
+                if !sequence.enables_absolute_lock_time() && requirements.timelock.is_some() =>
+            {
+                return Err(CreateTxError::FinalSequenceLockTime {
+                    required: requirements.timelock.expect("checked above"),
+                })
+            }
             // Use requested nSequence value.
             (Some(sequence), _) => sequence,
         };
@@ -1380,6 +2043,11 @@ impl Wallet {
                 (FeeRate::ZERO, fee)
             }
             FeePolicy::FeeRate(rate) => {
+                if rate < self.min_relay_fee {
+                    return Err(CreateTxError::FeeRateBelowMinRelay {
+                        required: self.min_relay_fee,
+                    });
+                }
                 if let Some(previous_fee) = params.bumping_fee {
                     let required_feerate = FeeRate::from_sat_per_kwu(
                         previous_fee.rate.to_sat_per_kwu()
@@ -1410,7 +2078,8 @@ impl Wallet {
         let recipients = params.recipients.iter().map(|(r, v)| (r, *v));
 
         for (index, (script_pubkey, value)) in recipients.enumerate() {
-            if !params.allow_dust && value.is_dust(script_pubkey) && !script_pubkey.is_op_return() {
+            let dust_limit = script_pubkey.minimal_non_dust_custom(self.dust_relay_fee());
+            if !params.allow_dust && value < dust_limit && !script_pubkey.is_op_return() {
                 return Err(CreateTxError::OutputBelowDustLimit(index));
             }
 
@@ -1445,7 +2114,11 @@ impl Wallet {
         let drain_script = match params.drain_to {
             Some(ref drain_recipient) => drain_recipient.clone(),
             None => {
-                let change_keychain = self.map_keychain(KeychainKind::Internal);
+                let change_keychain = self.map_keychain(
+                    params
+                        .drain_keychain
+                        .unwrap_or_else(|| self.preferred_change_keychain(&params)),
+                );
                 let (index, spk) = self
                     .tx_graph
                     .index
@@ -1467,18 +2140,79 @@ impl Wallet {
             }
         };
 
-        let coin_selection = coin_selection
-            .coin_select(
+        let coin_selection = match params.leftover_policy {
+            // The leftover would go to the fee, look for inputs that need no change first.
+            tx_builder::LeftoverPolicy::AddToFee { .. } if !tx.output.is_empty() => {
+                // value (8 bytes) + script len + script
+                let size_of_change = serialize(&drain_script).len() as u64 + 8;
+                BranchAndBoundCoinSelection::new(size_of_change, coin_selection).coin_select(
+                    required_utxos,
+                    optional_utxos,
+                    fee_rate,
+                    outgoing + fee_amount,
+                    &drain_script,
+                    rng,
+                )
+            }
+            _ => coin_selection.coin_select(
                 required_utxos,
                 optional_utxos,
                 fee_rate,
                 outgoing + fee_amount,
                 &drain_script,
                 rng,
-            )
-            .map_err(CreateTxError::CoinSelection)?;
-
-        let excess = &coin_selection.excess;
+            ),
+        }
+        .map_err(CreateTxError::CoinSelection)?;
+
+        // Coin selection only knows the default dust relay fee, which may be lower than the
+        // wallet's.
+        let change_dust_limit = drain_script.minimal_non_dust_custom(self.dust_relay_fee());
+        // Change the leftover policy doesn't keep goes to the fee, unless it's all the tx sends.
+        // Dust change goes to the fee in any case.
+        let excess = match coin_selection.excess {
+            Excess::Change { amount, fee }
+                if amount < change_dust_limit
+                    || (!tx.output.is_empty() && !params.leftover_policy.keeps_change(amount)) =>
+            {
+                Excess::NoChange {
+                    dust_threshold: match params.leftover_policy {
+                        tx_builder::LeftoverPolicy::Threshold { min_change }
+                            if !tx.output.is_empty() =>
+                        {
+                            min_change.max(change_dust_limit)
+                        }
+                        _ => change_dust_limit,
+                    },
+                    remaining_amount: amount + fee,
+                    change_fee: fee,
+                }
+            }
+            Excess::NoChange {
+                dust_threshold,
+                remaining_amount,
+                change_fee,
+            } => Excess::NoChange {
+                dust_threshold: dust_threshold.max(change_dust_limit),
+                remaining_amount,
+                change_fee,
+            },
+            excess => excess,
+        };
+        if let (
+            tx_builder::LeftoverPolicy::AddToFee { max_leftover },
+            Excess::NoChange {
+                remaining_amount, ..
+            },
+        ) = (params.leftover_policy, &excess)
+        {
+            if !tx.output.is_empty() && *remaining_amount > max_leftover {
+                return Err(CreateTxError::LeftoverAboveMax {
+                    leftover: *remaining_amount,
+                    max_leftover,
+                });
+            }
+        }
         tx.input = coin_selection
             .selected
             .iter()
@@ -1498,12 +2232,14 @@ impl Wallet {
             // sweeping specific UTXOs to a given address)
             // Otherwise, we don't know who we should send the funds to, and how much
             // we should send!
-            if params.drain_to.is_some() && (params.drain_wallet || !params.utxos.is_empty()) {
+            if (params.drain_to.is_some() || params.drain_keychain.is_some())
+                && (params.drain_wallet || !params.utxos.is_empty())
+            {
                 if let Excess::NoChange {
                     dust_threshold,
                     remaining_amount,
                     change_fee,
-                } = excess
+                } = &excess
                 {
                     return Err(CreateTxError::CoinSelection(InsufficientFunds {
                         needed: *dust_threshold,
@@ -1518,7 +2254,7 @@ impl Wallet {
         }
 
         // If there's change, create and add a change output.
-        if let Excess::Change { amount, .. } = excess {
+        if let Excess::Change { amount, .. } = &excess {
             // Create drain output.
             let drain_output = TxOut {
                 value: *amount,
@@ -1531,17 +2267,64 @@ impl Wallet {
             tx.output.push(drain_output);
         }
 
+        // Remember which outputs are labelled before they are reordered.
+        let labelled_outputs = params
+            .recipient_labels
+            .iter()
+            .filter_map(|(&index, label)| Some((tx.output.get(index)?.clone(), label.clone())))
+            .collect::<Vec<_>>();
+
         // Sort inputs/outputs according to the chosen algorithm.
         params.ordering.sort_tx_with_aux_rand(&mut tx, rng);
 
+        // Move the inputs with a requested position to the front.
+        if !params.input_order.is_empty() {
+            let mut inputs = Vec::with_capacity(tx.input.len());
+            for outpoint in &params.input_order {
+                let position = tx
+                    .input
+                    .iter()
+                    .position(|txin| txin.previous_output == *outpoint)
+                    .ok_or(CreateTxError::InputNotSelected(*outpoint))?;
+                inputs.push(tx.input.remove(position));
+            }
+            inputs.append(&mut tx.input);
+            tx.input = inputs;
+        }
+
+        // With SIGHASH_SINGLE each input only signs the output at its own index, so every input
+        // needs one. For legacy inputs without one the signature commits to the constant `1`
+        // instead of a transaction digest, and can be reused by anyone.
+        let sighash_single = params
+            .sighash
+            .is_some_and(|sighash| sighash.to_u32() & 0x1f == EcdsaSighashType::Single as u32);
+        if sighash_single && tx.input.len() > tx.output.len() {
+            return Err(CreateTxError::SighashSingleUnpairedInput(tx.output.len()));
+        }
+
         let psbt = self.complete_transaction(tx, coin_selection.selected, params)?;
 
+        // Record the labels to commit once the transaction is applied to the wallet.
+        if !labelled_outputs.is_empty() {
+            let outputs = &psbt.unsigned_tx.output;
+            let mut labels = BTreeMap::<u32, String>::new();
+            for (txout, label) in labelled_outputs {
+                let vout = (0..outputs.len() as u32)
+                    .find(|vout| !labels.contains_key(vout) && outputs[*vout as usize] == txout);
+                if let Some(vout) = vout {
+                    labels.insert(vout, label);
+                }
+            }
+            self.pending_labels
+                .insert(psbt.unsigned_tx.compute_ntxid(), labels);
+        }
+
         // Recording changes to the change keychain.
         if let (Excess::Change { .. }, Some((keychain, index))) = (excess, drain_index) {
             if let Some((_, index_changeset)) =
                 self.tx_graph.index.reveal_to_target(keychain, index)
             {
-                self.stage.merge(index_changeset.into());
+                self.stage_changes(index_changeset);
                 self.mark_used(keychain, index);
             }
         }
@@ -1598,7 +2381,7 @@ impl Wallet {
         let txout_index = &self.tx_graph.index;
         let chain_tip = self.chain.tip().block_id();
         let chain_positions: HashMap<Txid, ChainPosition<_>> = tx_graph
-            .list_canonical_txs(&self.chain, chain_tip, CanonicalizationParams::default())
+            .list_canonical_txs(&self.chain, chain_tip, self.canonicalization_params())
             .map(|canon_tx| (canon_tx.tx_node.txid, canon_tx.chain_position))
             .collect();
 
@@ -1722,26 +2505,88 @@ impl Wallet {
             wallet: self,
             params,
             coin_selection: DefaultCoinSelectionAlgorithm::default(),
+            fee_estimator: None,
         })
     }
 
-    /// Sign a transaction with all the wallet's signers, in the order specified by every signer's
-    /// [`SignerOrdering`]. This function returns the `Result` type with an encapsulated `bool` that
-    /// has the value true if the PSBT was finalized, or false otherwise.
+    /// Start building a transaction spending `outpoint` back to the wallet.
+    ///
+    /// The returned [`TxBuilder`] spends `outpoint` as its only input, and sends its value minus
+    /// the fee at `fee_rate` to the next unused address of the internal keychain. This can be
+    /// used to move a coin to a fresh address, for example to reset its age. Another keychain
+    /// can be chosen with [`TxBuilder::drain_to_keychain`].
+    ///
+    /// The inputs of the builder can't be changed: adding UTXOs returns
+    /// [`AddUtxoError::FixedInputs`](tx_builder::AddUtxoError::FixedInputs).
+    ///
+    /// Returns [`AddUtxoError::UnknownUtxo`](tx_builder::AddUtxoError::UnknownUtxo) if
+    /// `outpoint` isn't an unspent output of the wallet.
     ///
     /// ## Example
     ///
     /// ```
-    /// # use std::str::FromStr;
-    /// # use bitcoin::*;
     /// # use bdk_wallet::*;
-    /// # use bdk_wallet::ChangeSet;
-    /// # use bdk_wallet::error::CreateTxError;
-    /// # let descriptor = "wpkh(tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq/*)";
+    /// # use bdk_wallet::bitcoin::*;
     /// # let mut wallet = doctest_wallet!();
-    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt").unwrap().assume_checked();
-    /// let mut psbt = {
-    ///     let mut builder = wallet.build_tx();
+    /// let outpoint = wallet.list_unspent().next().unwrap().outpoint;
+    /// let mut builder = wallet.build_utxo_refresh(outpoint, FeeRate::from_sat_per_vb_u32(2))?;
+    /// builder.drain_to_keychain(KeychainKind::External);
+    /// let psbt = builder.finish()?;
+    /// assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn build_utxo_refresh(
+        &mut self,
+        outpoint: OutPoint,
+        fee_rate: FeeRate,
+    ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, tx_builder::AddUtxoError> {
+        let mut builder = self.build_tx();
+        builder
+            .add_utxo(outpoint)?
+            .manually_selected_only()
+            .fee_rate(fee_rate)
+            .drain_to_keychain(KeychainKind::Internal);
+        builder.params.fixed_inputs = true;
+        Ok(builder)
+    }
+
+    /// Diagnose every input of `psbt` against the wallet, to find out why it can't be signed or
+    /// finalized.
+    pub fn analyze_psbt(&self, psbt: &Psbt) -> Vec<InputAnalysis> {
+        crate::psbt::analyze(psbt, self)
+    }
+
+    /// Sign a transaction with all the wallet's signers, in the order specified by every signer's
+    /// [`SignerOrdering`]. This function returns the `Result` type with an encapsulated `bool` that
+    /// has the value true if the PSBT was finalized, or false otherwise.
+    ///
+    /// Signers may sign only part of the inputs, and signing stops once the PSBT can be
+    /// finalized: signers with a higher ordering, e.g. a hardware signer added with
+    /// [`Wallet::add_signer`], are only called if the previous ones left something to sign.
+    ///
+    /// Version 2 PSBTs ([BIP370]) are signed by passing a [`PsbtV2`](crate::psbt::PsbtV2), which
+    /// holds the PSBT in the version 0 representation until it is serialized again.
+    ///
+    /// Inputs and outputs are matched to the wallet descriptors by script pubkey, so the key
+    /// origins (`bip32_derivation` and `tap_key_origins`) of the wallet inputs are filled in
+    /// before signing even if the PSBT doesn't provide them. This requires the previous output of
+    /// the inputs, see [`Wallet::populate_non_witness_utxos`] for PSBTs missing them.
+    ///
+    /// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # use bdk_wallet::ChangeSet;
+    /// # use bdk_wallet::error::CreateTxError;
+    /// # let descriptor = "wpkh(tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq/*)";
+    /// # let mut wallet = doctest_wallet!();
+    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt").unwrap().assume_checked();
+    /// let mut psbt = {
+    ///     let mut builder = wallet.build_tx();
     ///     builder.add_recipient(to_address.script_pubkey(), Amount::from_sat(50_000));
     ///     builder.finish()?
     /// };
@@ -1749,7 +2594,40 @@ impl Wallet {
     /// assert!(finalized, "we should have signed all the inputs");
     /// # Ok::<(),anyhow::Error>(())
     /// ```
+    ///
+    /// Unless [`SignOptions::aux_rand`] is set, the auxiliary randomness of the Schnorr signatures
+    /// is taken from the thread-local random number generator (rng), see
+    /// [`Wallet::sign_with_aux_rand`] to provide one.
     pub fn sign(&self, psbt: &mut Psbt, sign_options: SignOptions) -> Result<bool, SignerError> {
+        #[cfg(feature = "std")]
+        if sign_options.aux_rand.is_none() {
+            return self.sign_with_aux_rand(
+                psbt,
+                sign_options,
+                &mut bitcoin::key::rand::thread_rng(),
+            );
+        }
+        self.sign_with_signers(
+            psbt,
+            &[self.signers.as_ref(), self.change_signers.as_ref()],
+            sign_options,
+        )
+    }
+
+    /// Sign a transaction with all the wallet's signers, like [`Wallet::sign`].
+    ///
+    /// Uses a provided random number generator (rng) for the auxiliary randomness of the Schnorr
+    /// signatures, replacing [`SignOptions::aux_rand`], so that signing with a seeded rng always
+    /// produces the same signatures. ECDSA signatures are deterministic and don't use it.
+    pub fn sign_with_aux_rand(
+        &self,
+        psbt: &mut Psbt,
+        mut sign_options: SignOptions,
+        rng: &mut impl RngCore,
+    ) -> Result<bool, SignerError> {
+        let mut aux_rand = [0_u8; 32];
+        rng.fill_bytes(&mut aux_rand);
+        sign_options.aux_rand = Some(aux_rand);
         self.sign_with_signers(
             psbt,
             &[self.signers.as_ref(), self.change_signers.as_ref()],
@@ -1759,8 +2637,10 @@ impl Wallet {
 
     /// Sign a transaction with the provided signer containers.
     ///
-    /// Signer containers are processed in the order provided. Signers inside each container are
-    /// processed according to their [`SignerOrdering`].
+    /// The signers of all the containers are called according to their [`SignerOrdering`], from
+    /// lowest to highest, and signers with the same ordering are called in the order of their
+    /// containers. Signing stops as soon as the PSBT can be finalized, so the signers left don't
+    /// get called.
     ///
     /// The [`SignOptions`] can be used to tweak the behavior of the software signers, and the way
     /// the transaction is finalized at the end. Note that it can't be guaranteed that *every*
@@ -1841,7 +2721,15 @@ impl Wallet {
             return Err(SignerError::NonStandardSighash);
         }
 
-        for signer in signers.iter().flat_map(|container| container.signers()) {
+        let mut signers = signers
+            .iter()
+            .flat_map(|container| container.iter_ordered())
+            .collect::<Vec<_>>();
+        signers.sort_by_key(|(ordering, _)| *ordering);
+        for (i, (_, signer)) in signers.into_iter().enumerate() {
+            if i > 0 && self.is_psbt_complete(psbt, &sign_options) {
+                break;
+            }
             signer.sign_transaction(psbt, &sign_options, &self.secp)?;
         }
 
@@ -1853,9 +2741,50 @@ impl Wallet {
         }
     }
 
+    /// Whether every input of `psbt` is finalized or can be satisfied with the signatures it has.
+    ///
+    /// Unlike [`Wallet::finalize_psbt`] this doesn't look up the confirmation heights of the
+    /// previous outputs, relative time locks are only satisfied by the sequence of the input. It
+    /// may return `false` for a PSBT that can be finalized, never the other way around.
+    fn is_psbt_complete(&self, psbt: &Psbt, sign_options: &SignOptions) -> bool {
+        let current_height = sign_options
+            .assume_height
+            .unwrap_or_else(|| self.chain.tip().height());
+        if psbt.inputs.len() != psbt.unsigned_tx.input.len() {
+            return false;
+        }
+        psbt.inputs.iter().enumerate().all(|(n, psbt_input)| {
+            if psbt_input.final_script_sig.is_some() || psbt_input.final_script_witness.is_some() {
+                return true;
+            }
+            let desc = psbt
+                .get_utxo_for(n)
+                .and_then(|txout| self.get_descriptor_for_txout(&txout))
+                .or_else(|| {
+                    self.tx_graph.index.keychains().find_map(|(_, desc)| {
+                        desc.derive_from_psbt_input(psbt_input, psbt.get_utxo_for(n), &self.secp)
+                    })
+                });
+            desc.is_some_and(|desc| {
+                desc.get_satisfaction((
+                    PsbtInputSatisfier::new(psbt, n),
+                    After::new(Some(current_height), false),
+                    Older::new(None, None, false),
+                    PreimageSatisfier(&sign_options.preimages),
+                ))
+                .is_ok()
+            })
+        })
+    }
+
     /// Return the spending policies for the wallet's descriptor.
+    ///
+    /// Works the same for both keychains, if the wallet doesn't have a change descriptor the
+    /// policies of [`KeychainKind::Internal`] are the ones of the external descriptor, which is
+    /// used for change. Returns `None` if the descriptor of `keychain` has no spending
+    /// conditions.
     pub fn policies(&self, keychain: KeychainKind) -> Result<Option<Policy>, DescriptorError> {
-        let signers = match keychain {
+        let signers = match self.map_keychain(keychain) {
             KeychainKind::External => &self.signers,
             KeychainKind::Internal => &self.change_signers,
         };
@@ -1867,6 +2796,40 @@ impl Wallet {
         )
     }
 
+    /// Returns the timelocks of the spending policy of `keychain`, with the spend paths requiring
+    /// them.
+    ///
+    /// See [`Policy::timelocks`]. This can be used to show users when a timelocked path, e.g. the
+    /// recovery path of an inheritance wallet, becomes spendable. Returns an empty list if the
+    /// policy has no timelocks.
+    pub fn policy_timelocks(
+        &self,
+        keychain: KeychainKind,
+    ) -> Result<Vec<(SpendPath, Timelock)>, DescriptorError> {
+        Ok(self
+            .policies(keychain)?
+            .map(|policy| policy.timelocks())
+            .unwrap_or_default())
+    }
+
+    /// Returns whether the external and internal descriptors have the same spending policies,
+    /// i.e. they require the same keys with the same thresholds and timelocks.
+    ///
+    /// Keys are compared by the fingerprint of their master key for extended keys, so the usual
+    /// `.../0/*` and `.../1/*` descriptors derived from the same extended keys match. A mismatch
+    /// means funds sent to change can be spent under different conditions than funds received,
+    /// e.g. a 1-of-2 change descriptor for a 2-of-2 wallet.
+    ///
+    /// Always returns `true` if the wallet doesn't have a change descriptor.
+    pub fn policies_match(&self) -> Result<bool, DescriptorError> {
+        let external = self.policies(KeychainKind::External)?;
+        let internal = self.policies(KeychainKind::Internal)?;
+        Ok(match (external, internal) {
+            (Some(external), Some(internal)) => external.same_conditions(&internal),
+            (external, internal) => external.is_none() && internal.is_none(),
+        })
+    }
+
     /// Returns the descriptor used to create addresses for a particular `keychain`.
     ///
     /// It's the "public" version of the wallet's descriptor, meaning a new descriptor that has
@@ -1879,6 +2842,201 @@ impl Wallet {
             .expect("keychain must exist")
     }
 
+    /// Returns whether the descriptor of `keychain` is ranged, i.e. has a wildcard.
+    ///
+    /// A descriptor without a wildcard only has a single address: revealing addresses, e.g. with
+    /// [`next_unused_address`](Self::next_unused_address), always returns the same one.
+    pub fn is_ranged(&self, keychain: KeychainKind) -> bool {
+        self.public_descriptor(keychain).has_wildcard()
+    }
+
+    /// Returns the external and internal descriptors merged into a single multipath descriptor,
+    /// e.g. `wpkh([fingerprint/84'/1'/0']tpub.../<0;1>/*)#checksum`.
+    ///
+    /// Returns `None` if the wallet has no change descriptor, or if the two descriptors can't be
+    /// merged because they differ by more than the `0` and `1` derivation steps of their
+    /// extended keys.
+    pub fn multipath_descriptor(&self) -> Option<String> {
+        let external = self.tx_graph.index.get_descriptor(KeychainKind::External)?;
+        let internal = self.tx_graph.index.get_descriptor(KeychainKind::Internal)?;
+        merge_multipath(external, internal).map(|descriptor| descriptor.to_string())
+    }
+
+    /// Returns the type of the addresses derived from the descriptor of `keychain`, e.g.
+    /// [`AddressType::P2wpkh`] for a `wpkh()` descriptor.
+    pub fn address_type(&self, keychain: KeychainKind) -> AddressType {
+        self.public_descriptor(keychain).desc_type().into()
+    }
+
+    /// Returns the dust limit of the outputs of `keychain`, the smallest value an output of its
+    /// [`address_type`](Self::address_type) must have to be relayed, e.g. 294 sats for P2WPKH or
+    /// 330 sats for P2TR.
+    ///
+    /// It is computed at the dust relay fee rate of Bitcoin Core, 3 sat/vb, or at the
+    /// [`min_relay_fee`](Self::min_relay_fee) if it is higher.
+    pub fn dust_value(&self, keychain: KeychainKind) -> Amount {
+        let (_, spk) = self
+            .tx_graph
+            .index
+            .unbounded_spk_iter(self.map_keychain(keychain))
+            .expect("keychain must exist")
+            .next()
+            .expect("descriptor must derive index 0");
+        spk.minimal_non_dust_custom(self.dust_relay_fee())
+    }
+
+    /// Returns the taproot internal key, merkle root and tweaked output key of the output of
+    /// `keychain` at derivation `index`.
+    ///
+    /// The merkle root is `None` for descriptors without script paths. Returns `None` if the
+    /// descriptor of `keychain` is not a taproot descriptor or `index` is hardened.
+    pub fn taproot_output_info(
+        &self,
+        keychain: KeychainKind,
+        index: u32,
+    ) -> Option<(XOnlyPublicKey, Option<TapNodeHash>, XOnlyPublicKey)> {
+        let descriptor = self
+            .public_descriptor(keychain)
+            .at_derivation_index(index)
+            .ok()?;
+        match descriptor {
+            Descriptor::Tr(tr) => {
+                let spend_info = tr.spend_info();
+                Some((
+                    spend_info.internal_key(),
+                    spend_info.merkle_root(),
+                    spend_info.output_key().to_x_only_public_key(),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the account-level extended public keys of the descriptor of `keychain`.
+    ///
+    /// These are the extended keys that appear in the [public descriptor](Self::public_descriptor)
+    /// after applying any hardened derivation steps, e.g. the key at `m/84'/0'/0'` for a BIP84
+    /// descriptor. Keys are returned in the order they appear in the descriptor; single keys that
+    /// aren't extended keys are skipped.
+    pub fn account_xpubs(&self, keychain: KeychainKind) -> Vec<AccountXpub> {
+        let descriptor = self.public_descriptor(keychain);
+        let script_type = ScriptType::from_descriptor_type(descriptor.desc_type());
+        let mut xpubs = Vec::new();
+        descriptor.for_each_key(|key| {
+            if let DescriptorPublicKey::XPub(xkey) = key {
+                xpubs.push(AccountXpub {
+                    xpub: xkey.xkey,
+                    origin: xkey
+                        .origin
+                        .clone()
+                        .unwrap_or_else(|| (xkey.xkey.fingerprint(), DerivationPath::master())),
+                    script_type,
+                });
+            }
+            true
+        });
+        xpubs
+    }
+
+    /// Returns the account-level extended public key of the descriptor of `keychain`.
+    ///
+    /// Returns `None` unless the descriptor contains exactly one extended key, refer to
+    /// [`account_xpubs`](Self::account_xpubs) for descriptors with multiple keys.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use bdk_wallet::{KeychainKind, Wallet};
+    /// # use bdk_wallet::bitcoin::Network;
+    /// let descriptor = "wpkh(xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu/84'/0'/0'/0/*)";
+    /// let wallet = Wallet::create_single(descriptor)
+    ///     .network(Network::Bitcoin)
+    ///     .create_wallet_no_persist()?;
+    /// let account_xpub = wallet
+    ///     .account_xpub(KeychainKind::External)
+    ///     .expect("must have a single xpub");
+    /// assert_eq!(
+    ///     account_xpub.to_slip132_string(),
+    ///     "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs"
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn account_xpub(&self, keychain: KeychainKind) -> Option<AccountXpub> {
+        let mut xpubs = self.account_xpubs(keychain);
+        if xpubs.len() == 1 {
+            xpubs.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the account-level extended private keys of the descriptor of `keychain`.
+    ///
+    /// This is the private counterpart of [`account_xpubs`](Self::account_xpubs), limited to the
+    /// keys the wallet holds the extended private key of. Private keys that don't match their
+    /// public key in the descriptor are skipped.
+    pub fn account_xprvs(&self, keychain: KeychainKind) -> Vec<AccountXprv> {
+        let key_map = self
+            .get_signers(self.map_keychain(keychain))
+            .as_key_map(&self.secp);
+        let descriptor = self.public_descriptor(keychain);
+        let script_type = ScriptType::from_descriptor_type(descriptor.desc_type());
+        let mut xprvs = Vec::new();
+        descriptor.for_each_key(|key| {
+            let (DescriptorPublicKey::XPub(xkey), Some(DescriptorSecretKey::XPrv(secret))) =
+                (key, key_map.get(key))
+            else {
+                return true;
+            };
+            // the public key has the hardened steps of the secret key's path already applied
+            let Some(hardened_len) = secret
+                .derivation_path
+                .len()
+                .checked_sub(xkey.derivation_path.len())
+            else {
+                return true;
+            };
+            let path = &secret.derivation_path[..hardened_len];
+            let Ok(xprv) = secret.xkey.derive_priv(&self.secp, &path) else {
+                return true;
+            };
+            if Xpub::from_priv(&self.secp, &xprv) == xkey.xkey {
+                xprvs.push(AccountXprv {
+                    xprv,
+                    origin: xkey
+                        .origin
+                        .clone()
+                        .unwrap_or_else(|| (xkey.xkey.fingerprint(), DerivationPath::master())),
+                    script_type,
+                });
+            }
+            true
+        });
+        xprvs
+    }
+
+    /// Returns whether an extended key of the external or internal descriptor has the master key
+    /// `fingerprint`.
+    ///
+    /// The fingerprint is the one of the key origin if present, or of the extended key itself
+    /// otherwise. This can be used to check that a cosigner's key is part of the wallet, e.g.
+    /// before trusting the signatures of a PSBT input with that fingerprint in its key origins.
+    pub fn contains_fingerprint(&self, fingerprint: Fingerprint) -> bool {
+        self.keychains()
+            .flat_map(|(_, desc)| desc.get_extended_keys())
+            .any(|xkey| xkey.root_fingerprint(&self.secp) == fingerprint)
+    }
+
+    /// Returns whether `xpub` is one of the extended keys of the external or internal descriptor.
+    ///
+    /// Keys are compared as they appear in the [public descriptor](Self::public_descriptor), i.e.
+    /// after applying the hardened derivation steps of extended private keys.
+    pub fn contains_xpub(&self, xpub: &Xpub) -> bool {
+        self.keychains()
+            .flat_map(|(_, desc)| desc.get_extended_keys())
+            .any(|xkey| xkey.xkey == *xpub)
+    }
+
     /// Finalize a PSBT, i.e., for each input determine if sufficient data is available to pass
     /// validation and construct the respective `scriptSig` or `scriptWitness`. Please refer to
     /// [BIP174](https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki#Input_Finalizer),
@@ -1893,17 +3051,21 @@ impl Wallet {
         psbt: &mut Psbt,
         sign_options: SignOptions,
     ) -> Result<bool, SignerError> {
-        let tx = &psbt.unsigned_tx;
-        let chain_tip = self.chain.tip().block_id();
-        let prev_txids = tx
+        let prevouts = psbt
+            .unsigned_tx
             .input
             .iter()
-            .map(|txin| txin.previous_output.txid)
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>();
+        let chain_tip = self.chain.tip().block_id();
+        let prev_txids = prevouts
+            .iter()
+            .map(|prevout| prevout.txid)
             .collect::<HashSet<Txid>>();
         let confirmation_heights = self
             .tx_graph
             .graph()
-            .list_canonical_txs(&self.chain, chain_tip, CanonicalizationParams::default())
+            .list_canonical_txs(&self.chain, chain_tip, self.canonicalization_params())
             .filter(|canon_tx| prev_txids.contains(&canon_tx.tx_node.txid))
             // This is for a small performance gain. Although `.filter` filters out excess txs, it
             // will still consume the internal `CanonicalIter` entirely. Having a `.take` here
@@ -1920,7 +3082,7 @@ impl Wallet {
 
         let mut finished = true;
 
-        for (n, input) in tx.input.iter().enumerate() {
+        for (n, prevout) in prevouts.iter().enumerate() {
             let psbt_input = &psbt
                 .inputs
                 .get(n)
@@ -1928,9 +3090,7 @@ impl Wallet {
             if psbt_input.final_script_sig.is_some() || psbt_input.final_script_witness.is_some() {
                 continue;
             }
-            let confirmation_height = confirmation_heights
-                .get(&input.previous_output.txid)
-                .copied();
+            let confirmation_height = confirmation_heights.get(&prevout.txid).copied();
             let current_height = sign_options
                 .assume_height
                 .unwrap_or_else(|| self.chain.tip().height());
@@ -1959,6 +3119,7 @@ impl Wallet {
                             PsbtInputSatisfier::new(psbt, n),
                             After::new(Some(current_height), false),
                             Older::new(Some(current_height), confirmation_height, false),
+                            PreimageSatisfier(&sign_options.preimages),
                         ),
                     ) {
                         Ok(_) => {
@@ -1982,6 +3143,18 @@ impl Wallet {
                         Err(_) => finished = false,
                     }
                 }
+                // Without a descriptor we can still satisfy the leaf scripts of a taproot input,
+                // if the user trusts them.
+                None if sign_options.trust_tap_scripts
+                    && !psbt_input.tap_scripts.is_empty()
+                    && psbt
+                        .get_utxo_for(n)
+                        .is_some_and(|txout| txout.script_pubkey.is_p2tr()) =>
+                {
+                    if psbt.finalize_inp_mut(&self.secp, n).is_err() {
+                        finished = false;
+                    }
+                }
                 None => finished = false,
             }
         }
@@ -1997,6 +3170,182 @@ impl Wallet {
         Ok(finished)
     }
 
+    /// Combine the PSBTs signed by different cosigners into `base`.
+    ///
+    /// All the PSBTs must be for the same unsigned transaction as `base`. Their signatures, key
+    /// origins, taproot fields and any other data are merged into `base`. Fields that have
+    /// different values in two PSBTs, e.g. two different finalized witnesses for the same input,
+    /// keep the value that was found first and are listed in the returned
+    /// [`CombineReport::conflicts`].
+    ///
+    /// The report also counts the signatures of each input spending a wallet output against the
+    /// threshold of the spending policy of its descriptor. If `sign_options.try_finalize` is set
+    /// the combined PSBT is then finalized, refer to [`finalize_psbt`](Self::finalize_psbt).
+    ///
+    /// `base` is left untouched if an error is returned.
+    pub fn combine_psbts(
+        &self,
+        base: &mut Psbt,
+        others: impl IntoIterator<Item = Psbt>,
+        sign_options: SignOptions,
+    ) -> Result<CombineReport, CombineError> {
+        let others = others.into_iter().collect::<Vec<_>>();
+        if let Some(index) = others
+            .iter()
+            .position(|other| other.unsigned_tx != base.unsigned_tx)
+        {
+            return Err(CombineError::UnsignedTxMismatch(index));
+        }
+
+        let mut combined = base.clone();
+        let mut conflicts = Vec::new();
+        for (psbt_index, mut other) in others.into_iter().enumerate() {
+            for (input_index, (input, other_input)) in combined
+                .inputs
+                .iter()
+                .zip(other.inputs.iter_mut())
+                .enumerate()
+            {
+                combine::take_conflicts(
+                    input_index,
+                    psbt_index,
+                    input,
+                    other_input,
+                    &mut conflicts,
+                );
+            }
+            combined.combine(other).map_err(CombineError::Psbt)?;
+        }
+
+        let inputs = (0..combined.inputs.len())
+            .map(|index| self.input_signatures(&combined, index))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(CombineError::Descriptor)?;
+
+        let finalized = if sign_options.try_finalize {
+            self.finalize_psbt(&mut combined, sign_options)
+                .map_err(CombineError::Signer)?
+        } else {
+            false
+        };
+
+        *base = combined;
+        Ok(CombineReport {
+            conflicts,
+            inputs,
+            finalized,
+        })
+    }
+
+    /// Count the signatures of the input at `index` of `psbt` against the threshold of the
+    /// spending policy of its descriptor.
+    fn input_signatures(
+        &self,
+        psbt: &Psbt,
+        index: usize,
+    ) -> Result<Option<InputSignatures>, DescriptorError> {
+        let Some(txout) = psbt.get_utxo_for(index) else {
+            return Ok(None);
+        };
+        let Some(&(keychain, _)) = self.tx_graph.index.index_of_spk(txout.script_pubkey) else {
+            return Ok(None);
+        };
+        let input = &psbt.inputs[index];
+        let is_final = input.final_script_sig.is_some() || input.final_script_witness.is_some();
+
+        // the policy looks for signatures in every input, so only leave this one in the PSBT
+        let mut single_input = psbt.clone();
+        single_input.unsigned_tx.input = vec![psbt.unsigned_tx.input[index].clone()];
+        single_input.inputs = vec![input.clone()];
+        let signers = match keychain {
+            KeychainKind::External => &self.signers,
+            KeychainKind::Internal => &self.change_signers,
+        };
+        let policy = self.public_descriptor(keychain).extract_policy(
+            signers,
+            BuildSatisfaction::Psbt(&single_input),
+            &self.secp,
+        )?;
+        let (signatures, threshold) = match policy.map(|policy| policy.satisfaction) {
+            Some(
+                Satisfaction::Partial { m, items, .. }
+                | Satisfaction::PartialComplete { m, items, .. },
+            ) => (items.len(), m),
+            Some(Satisfaction::Complete { .. }) => (1, 1),
+            Some(Satisfaction::None) => (0, 1),
+            None => (0, 0),
+        };
+
+        Ok(Some(InputSignatures {
+            keychain,
+            signatures,
+            threshold,
+            is_final,
+        }))
+    }
+
+    /// Check that the inputs of `tx` spending wallet outputs satisfy the script pubkeys of the
+    /// spent outputs, including their signatures and timelocks.
+    ///
+    /// This is meant as a sanity check on a finalized transaction before broadcasting it. Inputs
+    /// spending outputs that don't belong to the wallet are not checked. With the
+    /// `bitcoinconsensus` feature, non-taproot inputs are verified with libbitcoinconsensus,
+    /// otherwise all the inputs are verified with the miniscript interpreter.
+    ///
+    /// The signature hash of a taproot input commits to every spent output, so taproot inputs
+    /// can't be verified and are skipped when the wallet doesn't know all the outputs spent by
+    /// `tx`, e.g. for a payjoin or coinjoin with foreign inputs.
+    pub fn verify_tx(&self, tx: &Transaction) -> Result<(), VerifyError> {
+        let graph = self.tx_graph.graph();
+        let prevouts = tx
+            .input
+            .iter()
+            .map(|txin| graph.get_txout(txin.previous_output).cloned())
+            .collect::<Vec<_>>();
+        let all_prevouts = prevouts.iter().cloned().collect::<Option<Vec<_>>>();
+        #[cfg(feature = "bitcoinconsensus")]
+        let serialized_tx = bitcoin::consensus::serialize(tx);
+
+        for (index, (txin, prevout)) in tx.input.iter().zip(&prevouts).enumerate() {
+            let prevout = match prevout {
+                Some(prevout) if self.is_mine(prevout.script_pubkey.clone()) => prevout,
+                _ => continue,
+            };
+            let prevouts = match &all_prevouts {
+                Some(all_prevouts) => Prevouts::All(all_prevouts),
+                None if prevout.script_pubkey.is_p2tr() => continue,
+                None => Prevouts::One(index, prevout.clone()),
+            };
+
+            // libbitcoinconsensus doesn't verify taproot spends
+            #[cfg(feature = "bitcoinconsensus")]
+            if !prevout.script_pubkey.is_p2tr() {
+                prevout
+                    .script_pubkey
+                    .verify(index, prevout.value, &serialized_tx)
+                    .map_err(|error| VerifyError::Consensus { index, error })?;
+                continue;
+            }
+
+            let invalid_input = |error| VerifyError::InvalidInput { index, error };
+            let interpreter = Interpreter::from_txdata(
+                &prevout.script_pubkey,
+                &txin.script_sig,
+                &txin.witness,
+                txin.sequence,
+                tx.lock_time,
+            )
+            .map_err(invalid_input)?;
+            let error = interpreter
+                .iter(&self.secp, tx, index, &prevouts)
+                .find_map(Result::err);
+            if let Some(error) = error {
+                return Err(invalid_input(error));
+            }
+        }
+        Ok(())
+    }
+
     /// Return the secp256k1 context used for all signing operations.
     pub fn secp_ctx(&self) -> &SecpCtx {
         &self.secp
@@ -2047,7 +3396,7 @@ impl Wallet {
                 .filter_chain_unspents(
                     &self.chain,
                     self.chain.tip().block_id(),
-                    CanonicalizationParams::default(),
+                    self.canonicalization_params(),
                     self.tx_graph.index.outpoints().iter().cloned(),
                 )
                 // Filter out locked outpoints.
@@ -2210,6 +3559,102 @@ impl Wallet {
         Ok(psbt_input)
     }
 
+    /// Fill in the missing [`non_witness_utxo`] of the non-taproot inputs of `psbt` with the
+    /// previous transactions found in the wallet's transaction graph.
+    ///
+    /// Some hardware signers refuse to sign inputs, even SegWit ones, without the full previous
+    /// transaction. PSBTs built with [`TxBuilder::only_witness_utxo`] or received from other
+    /// software may lack it. Returns the indexes of the inputs whose previous transaction is
+    /// not in the wallet, so that it can be fetched from a chain source.
+    ///
+    /// [`non_witness_utxo`]: psbt::Input::non_witness_utxo
+    pub fn populate_non_witness_utxos(&self, psbt: &mut Psbt) -> Vec<usize> {
+        let mut missing = Vec::new();
+        for (index, (psbt_input, txin)) in psbt
+            .inputs
+            .iter_mut()
+            .zip(&psbt.unsigned_tx.input)
+            .enumerate()
+        {
+            let is_taproot = psbt_input.tap_internal_key.is_some()
+                || psbt_input
+                    .witness_utxo
+                    .as_ref()
+                    .is_some_and(|txout| txout.script_pubkey.is_p2tr());
+            if psbt_input.non_witness_utxo.is_some() || is_taproot {
+                continue;
+            }
+            match self.tx_graph.graph().get_tx(txin.previous_output.txid) {
+                Some(prev_tx) => psbt_input.non_witness_utxo = Some(prev_tx.as_ref().clone()),
+                None => missing.push(index),
+            }
+        }
+        missing
+    }
+
+    /// Rebuild a signable [`Psbt`] for `tx` from the wallet's data.
+    ///
+    /// This is useful when only the [`Transaction`] of a draft is left, e.g. because its PSBT
+    /// was lost. The inputs spending an output of the wallet get their UTXOs, scripts and key
+    /// derivations from the descriptor at the matching index, and so do the outputs paying to
+    /// the wallet. Foreign inputs only get the UTXOs known to the wallet, if any.
+    ///
+    /// The `script_sig` and `witness` of the inputs of `tx` are cleared, so any signature it
+    /// already carries has to be produced again.
+    pub fn psbt_from_tx(&self, tx: &Transaction) -> Result<Psbt, CreateTxError> {
+        let mut unsigned_tx = tx.clone();
+        for txin in &mut unsigned_tx.input {
+            txin.script_sig = ScriptBuf::new();
+            txin.witness = Witness::new();
+        }
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx)?;
+
+        for (psbt_input, txin) in psbt.inputs.iter_mut().zip(&psbt.unsigned_tx.input) {
+            let prev_output = txin.previous_output;
+            let prev_tx = self.tx_graph.graph().get_tx(prev_output.txid);
+            match self.tx_graph.index.txout(prev_output) {
+                Some(((keychain, child), _)) => {
+                    let desc = self.public_descriptor(keychain);
+                    let derived_descriptor = desc
+                        .at_derivation_index(child)
+                        .expect("child can't be hardened");
+                    psbt_input
+                        .update_with_descriptor_unchecked(&derived_descriptor)
+                        .map_err(MiniscriptPsbtError::Conversion)?;
+
+                    if let Some(prev_tx) = prev_tx {
+                        let prevout = prev_tx.output.get(prev_output.vout as usize).ok_or(
+                            MiniscriptPsbtError::UtxoUpdate(
+                                miniscript::psbt::UtxoUpdateError::UtxoCheck,
+                            ),
+                        )?;
+                        if desc.is_witness() || desc.is_taproot() {
+                            psbt_input.witness_utxo = Some(prevout.clone());
+                        }
+                        if !desc.is_taproot() {
+                            psbt_input.non_witness_utxo = Some(prev_tx.as_ref().clone());
+                        }
+                    }
+                }
+                None => {
+                    // Without the descriptor, only native SegWit outputs are known to be
+                    // spent with a witness.
+                    let txout = self.tx_graph.graph().get_txout(prev_output);
+                    if txout.is_some_and(|txout| txout.script_pubkey.is_witness_program()) {
+                        psbt_input.witness_utxo = txout.cloned();
+                    }
+                    if let Some(prev_tx) = prev_tx {
+                        psbt_input.non_witness_utxo = Some(prev_tx.as_ref().clone());
+                    }
+                }
+            }
+        }
+
+        self.update_psbt_with_descriptor(&mut psbt)?;
+
+        Ok(psbt)
+    }
+
     fn update_psbt_with_descriptor(&self, psbt: &mut Psbt) -> Result<(), MiniscriptPsbtError> {
         // We need to borrow `psbt` mutably within the loops, so we have to allocate a vec for all
         // the input utxos and outputs.
@@ -2264,11 +3709,23 @@ impl Wallet {
     ///
     /// After applying updates you should persist the staged wallet changes. For an example of how
     /// to persist staged wallet changes see [`Wallet::reveal_next_address`].
+    ///
+    /// # Updates from multiple chain sources
+    ///
+    /// Updates from different chain sources can be applied one after the other, the transaction
+    /// data of all of them is merged. The chain of an update that conflicts with the wallet's
+    /// chain (a block at the same height with a different hash) is ignored if its tip is lower
+    /// than the wallet's tip: a chain source lagging behind, or on a stale fork, can't reorg the
+    /// wallet to a shorter chain. Competing chains of the same height are resolved in favor of the
+    /// update. Anchors of the update pointing to blocks of an ignored chain don't confirm
+    /// transactions, only anchors consistent with the wallet's chain do.
     pub fn apply_update(&mut self, update: impl Into<Update>) -> Result<(), CannotConnectError> {
         let update = update.into();
         let mut changeset = match update.chain {
-            Some(chain_update) => ChangeSet::from(self.chain.apply_update(chain_update)?),
-            None => ChangeSet::default(),
+            Some(chain_update) if !self.is_stale_chain_update(&chain_update) => {
+                ChangeSet::from(self.chain.apply_update(chain_update)?)
+            }
+            _ => ChangeSet::default(),
         };
 
         let index_changeset = self
@@ -2276,11 +3733,48 @@ impl Wallet {
             .index
             .reveal_to_target_multi(&update.last_active_indices);
         changeset.merge(index_changeset.into());
-        changeset.merge(self.tx_graph.apply_update(update.tx_update).into());
-        self.stage.merge(changeset);
+        self.stage_changes(changeset);
+        self.replenish_keychain_lookahead();
+        let changeset = self.tx_graph.apply_update(update.tx_update);
+        self.commit_pending_labels(&changeset.tx_graph.txs);
+        self.stage_changes(changeset);
+        self.reindex_keychain_lookahead();
+        self.update_canonicalization_params();
         Ok(())
     }
 
+    /// Whether the `chain_update` conflicts with the wallet's chain and has a lower tip.
+    ///
+    /// The height of the tips stands in for the work of the chains, which checkpoints don't
+    /// carry. Both chains are walked together from their tips, like
+    /// [`LocalChain::apply_update`] does, stopping where they share the same checkpoints.
+    fn is_stale_chain_update(&self, chain_update: &CheckPoint) -> bool {
+        let tip = self.chain.tip();
+        if chain_update.height() >= tip.height() {
+            return false;
+        }
+        let mut local = tip.iter().peekable();
+        for update_cp in chain_update.iter() {
+            while local
+                .next_if(|cp| cp.height() > update_cp.height())
+                .is_some()
+            {}
+            let Some(local_cp) = local.peek() else {
+                break;
+            };
+            if local_cp.height() == update_cp.height() {
+                if local_cp.eq_ptr(&update_cp) {
+                    // the rest of both chains is the same
+                    break;
+                }
+                if local_cp.hash() != update_cp.hash() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
     /// Applies an update to the wallet, stages the changes, and returns events.
     ///
     /// Usually you create an `update` by interacting with some blockchain data source and inserting
@@ -2368,29 +3862,172 @@ impl Wallet {
         self.events_helper(|wallet| wallet.apply_update(update))
     }
 
-    /// Get a reference of the staged [`ChangeSet`] that is yet to be committed (if any).
-    pub fn staged(&self) -> Option<&ChangeSet> {
-        if self.stage.is_empty() {
-            None
-        } else {
-            Some(&self.stage)
+    /// Applies an `update` like [`apply_update`], after validating it according to `options`.
+    ///
+    /// With a [`HeaderProvider`](validation::HeaderProvider), the blocks of the update's chain and
+    /// of its anchors that aren't in the wallet's chain yet must be part of the provider's chain,
+    /// otherwise nothing is applied. The update doesn't contain headers, so
+    /// [`ApplyOptions::validate_pow`] doesn't apply.
+    ///
+    /// [`apply_update`]: Self::apply_update
+    pub fn apply_update_with_options(
+        &mut self,
+        update: impl Into<Update>,
+        options: &ApplyOptions,
+    ) -> Result<(), ApplyError> {
+        let update = update.into();
+        if let Some(provider) = options.header_provider {
+            let chain_blocks = update.chain.iter().flat_map(|tip| tip.iter());
+            let anchor_blocks = update
+                .tx_update
+                .anchors
+                .iter()
+                .map(|(anchor, _)| anchor.block_id);
+            let unknown_blocks = chain_blocks
+                .map(|cp| cp.block_id())
+                .chain(anchor_blocks)
+                .filter(|block| {
+                    self.chain.get(block.height).map(|cp| cp.hash()) != Some(block.hash)
+                })
+                .collect::<BTreeSet<_>>();
+            validation::check_blocks(provider, unknown_blocks)?;
         }
+        Ok(self.apply_update(update)?)
     }
 
-    /// Get a mutable reference of the staged [`ChangeSet`] that is yet to be committed (if any).
-    pub fn staged_mut(&mut self) -> Option<&mut ChangeSet> {
-        if self.stage.is_empty() {
-            None
-        } else {
-            Some(&mut self.stage)
-        }
-    }
+    /// Applies the unspent outputs of a Bitcoin Core `scantxoutset` result to the wallet.
+    ///
+    /// `txs` are the transactions creating the outputs, with the time of the block confirming
+    /// them. Outputs with a script pubkey not derived from the wallet's descriptors at an index
+    /// below `range_end`, usually the end of the range given to `scantxoutset`, are ignored, the
+    /// others are inserted as confirmed at the block of the scan result and the addresses they
+    /// pay to are revealed. The block the UTXO set was scanned at becomes the wallet's tip, unless
+    /// the wallet's chain is already longer.
+    ///
+    /// This allows a wallet to start from the UTXO set instead of scanning the whole chain, the
+    /// transaction history before the scan is missing though. See the [`scantxoutset`] module
+    /// for how to get the data.
+    pub fn apply_scantxoutset(
+        &mut self,
+        result: &ScanTxOutSetResult,
+        range_end: u32,
+        txs: impl IntoIterator<Item = (Transaction, u64)>,
+    ) -> Result<(), ScanTxOutSetError> {
+        let txs = txs
+            .into_iter()
+            .map(|(tx, block_time)| (tx.compute_txid(), (tx, block_time)))
+            .collect::<HashMap<_, _>>();
+        let derived_spks = self
+            .keychains()
+            .flat_map(|(keychain, descriptor)| {
+                SpkIterator::new_with_range(descriptor.clone(), 0..range_end)
+                    .map(move |(index, spk)| (spk, (keychain, index)))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut update = Update::default();
+        let mut blocks = BTreeMap::from([
+            (0, self.chain.genesis_hash()),
+            (result.height, result.best_block),
+        ]);
+        for unspent in &result.unspents {
+            let Some(&(keychain, index)) = derived_spks.get(&unspent.script_pubkey) else {
+                continue;
+            };
+            let (tx, block_time) = txs
+                .get(&unspent.txid)
+                .ok_or(ScanTxOutSetError::MissingTx(unspent.txid))?;
+            match tx.output.get(unspent.vout as usize) {
+                Some(txout)
+                    if txout.script_pubkey == unspent.script_pubkey
+                        && txout.value == unspent.amount => {}
+                _ => return Err(ScanTxOutSetError::InconsistentTxOut(unspent.outpoint())),
+            }
+            if *blocks.entry(unspent.height).or_insert(unspent.block_hash) != unspent.block_hash {
+                return Err(ScanTxOutSetError::ConflictingBlocks(unspent.height));
+            }
+            let last_active = update.last_active_indices.entry(keychain).or_insert(index);
+            *last_active = index.max(*last_active);
+            update.tx_update.txs.push(Arc::new(tx.clone()));
+            update.tx_update.anchors.insert((
+                ConfirmationBlockTime {
+                    block_id: BlockId {
+                        height: unspent.height,
+                        hash: unspent.block_hash,
+                    },
+                    confirmation_time: *block_time,
+                },
+                unspent.txid,
+            ));
+        }
+        update.chain = Some(
+            CheckPoint::from_block_ids(
+                blocks
+                    .into_iter()
+                    .map(|(height, hash)| BlockId { height, hash }),
+            )
+            .expect("blocks are sorted by height"),
+        );
+        Ok(self.apply_update(update)?)
+    }
+
+    /// Get a reference of the staged [`ChangeSet`] that is yet to be committed (if any).
+    ///
+    /// This does not clear the staged changes, so it can be used to inspect what the next persist
+    /// will write. `None` is returned when there is nothing to persist, which is equivalent to the
+    /// stage being [empty](Merge::is_empty).
+    pub fn staged(&self) -> Option<&ChangeSet> {
+        if self.stage.is_empty() {
+            None
+        } else {
+            Some(&self.stage)
+        }
+    }
+
+    /// Get a mutable reference of the staged [`ChangeSet`] that is yet to be committed (if any).
+    pub fn staged_mut(&mut self) -> Option<&mut ChangeSet> {
+        if self.stage.is_empty() {
+            None
+        } else {
+            Some(&mut self.stage)
+        }
+    }
 
     /// Take the staged [`ChangeSet`] to be persisted now (if any).
+    ///
+    /// The staged changes are cleared, so the returned [`ChangeSet`] must be written to the
+    /// persister by the caller or it is lost. Use [`staged`](Self::staged) to only inspect them.
     pub fn take_staged(&mut self) -> Option<ChangeSet> {
         self.stage.take()
     }
 
+    /// Stage `changeset`, with the digest of the SPK cache of the descriptors it caches new script
+    /// pubkeys of.
+    fn stage_changes(&mut self, changeset: impl Into<ChangeSet>) {
+        let changeset = changeset.into();
+        let mut updated = Vec::new();
+        for (&did, spks) in &changeset.indexer.spk_cache {
+            if self.spk_cache_digests.entry(did).or_default().update(spks) {
+                updated.push(did);
+            }
+        }
+        self.stage.merge(changeset);
+        self.stage_spk_cache_digests(updated);
+    }
+
+    /// Stage the digest of the SPK cache of the descriptors `dids`, so that a corrupted cache is
+    /// detected when loading.
+    fn stage_spk_cache_digests(&mut self, dids: impl IntoIterator<Item = DescriptorId>) {
+        let spk_cache_digests = dids
+            .into_iter()
+            .filter_map(|did| Some((did, self.spk_cache_digests.get(&did)?.digest())))
+            .collect::<BTreeMap<_, _>>();
+        self.stage.merge(ChangeSet {
+            spk_cache_digests,
+            ..Default::default()
+        });
+    }
+
     /// Get a reference to the inner [`TxGraph`].
     pub fn tx_graph(&self) -> &TxGraph<ConfirmationBlockTime> {
         self.tx_graph.graph()
@@ -2435,7 +4072,7 @@ impl Wallet {
             let changeset = locked_outpoints::ChangeSet {
                 outpoints: [(outpoint, true)].into(),
             };
-            self.stage.merge(changeset.into());
+            self.stage_changes(changeset);
         }
     }
 
@@ -2447,7 +4084,117 @@ impl Wallet {
             let changeset = locked_outpoints::ChangeSet {
                 outpoints: [(outpoint, false)].into(),
             };
-            self.stage.merge(changeset.into());
+            self.stage_changes(changeset);
+        }
+    }
+
+    /// List the labelled outputs and their labels.
+    pub fn list_output_labels(&self) -> impl Iterator<Item = (OutPoint, &str)> + '_ {
+        self.output_labels
+            .iter()
+            .map(|(&outpoint, label)| (outpoint, label.as_str()))
+    }
+
+    /// Get the label of the output identified by the given `outpoint`, if any.
+    pub fn output_label(&self, outpoint: OutPoint) -> Option<&str> {
+        self.output_labels.get(&outpoint).map(String::as_str)
+    }
+
+    /// Set the `label` of the output identified by the given `outpoint`, replacing any previous
+    /// label.
+    ///
+    /// Labels can also be set when creating a transaction with
+    /// [`TxBuilder::add_recipient_with_label`].
+    ///
+    /// **You must persist the staged change for the label to be persistent**.
+    pub fn set_output_label(&mut self, outpoint: OutPoint, label: impl Into<String>) {
+        let label = label.into();
+        if self.output_label(outpoint) != Some(label.as_str()) {
+            self.output_labels.insert(outpoint, label.clone());
+            let changeset = labels::ChangeSet {
+                outputs: [(outpoint, Some(label))].into(),
+            };
+            self.stage_changes(changeset);
+        }
+    }
+
+    /// Remove the label of the output identified by the given `outpoint` and return it.
+    ///
+    /// **You must persist the staged change for the removal to be persistent**.
+    pub fn remove_output_label(&mut self, outpoint: OutPoint) -> Option<String> {
+        let label = self.output_labels.remove(&outpoint)?;
+        let changeset = labels::ChangeSet {
+            outputs: [(outpoint, None)].into(),
+        };
+        self.stage_changes(changeset);
+        Some(label)
+    }
+
+    /// Get the value of the application metadata `key`, if any.
+    ///
+    /// See [`set_meta`](Self::set_meta).
+    pub fn get_meta(&self, key: &str) -> Option<&[u8]> {
+        self.metadata
+            .get(&format!("{}{key}", metadata::APP_NAMESPACE))
+            .map(Vec::as_slice)
+    }
+
+    /// Set the `value` of the application metadata `key`, replacing any previous value.
+    ///
+    /// The metadata is stored in the wallet [`ChangeSet`] to persist a few wallet-scoped settings
+    /// of the application, e.g. the time of the last backup, with the wallet. The keys are in
+    /// their own namespace, see [`metadata::APP_NAMESPACE`], and the values are opaque bytes.
+    ///
+    /// Returns an error if the key is longer than [`metadata::MAX_KEY_LEN`] or the value larger
+    /// than [`metadata::MAX_VALUE_LEN`] bytes.
+    ///
+    /// **You must persist the staged change for the value to be persistent**.
+    pub fn set_meta(&mut self, key: &str, value: Vec<u8>) -> Result<(), metadata::MetadataError> {
+        if key.len() > metadata::MAX_KEY_LEN {
+            return Err(metadata::MetadataError::KeyTooLong(key.len()));
+        }
+        if value.len() > metadata::MAX_VALUE_LEN {
+            return Err(metadata::MetadataError::ValueTooLarge(value.len()));
+        }
+        if self.get_meta(key) != Some(value.as_slice()) {
+            let key = format!("{}{key}", metadata::APP_NAMESPACE);
+            self.metadata.insert(key.clone(), value.clone());
+            let changeset = metadata::ChangeSet {
+                entries: [(key, Some(value))].into(),
+            };
+            self.stage_changes(changeset);
+        }
+        Ok(())
+    }
+
+    /// Delete the application metadata `key` and return its value.
+    ///
+    /// **You must persist the staged change for the deletion to be persistent**.
+    pub fn delete_meta(&mut self, key: &str) -> Option<Vec<u8>> {
+        let key = format!("{}{key}", metadata::APP_NAMESPACE);
+        let value = self.metadata.remove(&key)?;
+        let changeset = metadata::ChangeSet {
+            entries: [(key, None)].into(),
+        };
+        self.stage_changes(changeset);
+        Some(value)
+    }
+
+    /// Commit the labels of transactions created with [`TxBuilder::add_recipient_with_label`]
+    /// that are among the newly applied `txs`.
+    fn commit_pending_labels<'t>(&mut self, txs: impl IntoIterator<Item = &'t Arc<Transaction>>) {
+        if self.pending_labels.is_empty() {
+            return;
+        }
+        for tx in txs {
+            let labels = match self.pending_labels.remove(&tx.compute_ntxid()) {
+                Some(labels) => labels,
+                None => continue,
+            };
+            let txid = tx.compute_txid();
+            for (vout, label) in labels {
+                self.set_output_label(OutPoint::new(txid, vout), label);
+            }
         }
     }
 
@@ -2459,16 +4206,7 @@ impl Wallet {
     ///
     /// [`apply_block_connected_to`]: Self::apply_block_connected_to
     pub fn apply_block(&mut self, block: &Block, height: u32) -> Result<(), CannotConnectError> {
-        let connected_to = match height.checked_sub(1) {
-            Some(prev_height) => BlockId {
-                height: prev_height,
-                hash: block.header.prev_blockhash,
-            },
-            None => BlockId {
-                height,
-                hash: block.block_hash(),
-            },
-        };
+        let connected_to = block_connected_to(block, height);
         self.apply_block_connected_to(block, height, connected_to)
             .map_err(|err| match err {
                 ApplyHeaderError::InconsistentBlocks => {
@@ -2513,17 +4251,132 @@ impl Wallet {
         height: u32,
         connected_to: BlockId,
     ) -> Result<(), ApplyHeaderError> {
-        let mut changeset = ChangeSet::default();
-        changeset.merge(
+        let chain_changeset =
             self.chain
-                .apply_header_connected_to(&block.header, height, connected_to)?
-                .into(),
-        );
-        changeset.merge(self.tx_graph.apply_block_relevant(block, height).into());
-        self.stage.merge(changeset);
+                .apply_header_connected_to(&block.header, height, connected_to)?;
+        self.apply_block_txs(block, height, chain_changeset);
         Ok(())
     }
 
+    /// Applies `block` of `height` like [`apply_block_connected_to`], and applies it as a chain
+    /// reorg if the wallet's chain has a different block at the height of `connected_to`.
+    ///
+    /// [`apply_block_connected_to`] returns [`ApplyHeaderError::CannotConnect`] in that case.
+    /// Here `connected_to` is assumed to be in the best chain instead, and the wallet's blocks
+    /// from its height up are disconnected. The transactions confirmed in the disconnected blocks
+    /// become unconfirmed, unless `block` confirms them again.
+    ///
+    /// Returns what the reorg disconnected, if any. A reorg happens when `connected_to`, or the
+    /// parent of `block`, is not the wallet's block at its height. The returned [`ReorgSummary`]
+    /// lists the wallet transactions that were confirmed in the disconnected blocks.
+    ///
+    /// [`apply_block_connected_to`]: Self::apply_block_connected_to
+    pub fn apply_block_connected_to_reorg(
+        &mut self,
+        block: &Block,
+        height: u32,
+        connected_to: BlockId,
+    ) -> Result<Option<ReorgSummary>, ApplyHeaderError> {
+        let old_chain = self.chain.clone();
+        let conflicting = self
+            .chain
+            .get(connected_to.height)
+            .is_some_and(|cp| cp.hash() != connected_to.hash);
+        let chain_changeset = if conflicting {
+            let this = BlockId {
+                height,
+                hash: block.block_hash(),
+            };
+            let prev = height.checked_sub(1).map(|height| BlockId {
+                height,
+                hash: block.header.prev_blockhash,
+            });
+            // same rules as `LocalChain::apply_header_connected_to`
+            if connected_to != this
+                && Some(connected_to) != prev
+                && connected_to.height >= height.saturating_sub(1)
+            {
+                return Err(ApplyHeaderError::InconsistentBlocks);
+            }
+            let blocks = [Some(connected_to), prev, Some(this)]
+                .into_iter()
+                .flatten()
+                .map(|block_id| (block_id.height, block_id.hash))
+                .collect::<BTreeMap<_, _>>();
+            // connect the blocks to the highest block of the wallet below them, which replaces
+            // the wallet's blocks from the height of `connected_to` up
+            let lowest_height = *blocks.keys().next().expect("must contain this block");
+            let fork = self
+                .chain
+                .tip()
+                .iter()
+                .find(|cp| cp.height() < lowest_height)
+                .ok_or(ApplyHeaderError::CannotConnect(CannotConnectError {
+                    try_include_height: 0,
+                }))?;
+            let update = CheckPoint::from_block_ids(
+                core::iter::once(fork.block_id()).chain(
+                    blocks
+                        .into_iter()
+                        .map(|(height, hash)| BlockId { height, hash }),
+                ),
+            )
+            .expect("heights must be increasing");
+            self.chain
+                .apply_update(update)
+                .map_err(ApplyHeaderError::CannotConnect)?
+        } else {
+            self.chain
+                .apply_header_connected_to(&block.header, height, connected_to)?
+        };
+        self.apply_block_txs(block, height, chain_changeset);
+
+        let fork_height = match event::find_reorg(&old_chain.tip(), &self.chain.tip()) {
+            Some(reorg) => reorg.fork_height,
+            None => return Ok(None),
+        };
+        let mut disconnected_txs = self
+            .tx_graph
+            .graph()
+            .list_canonical_txs(
+                &old_chain,
+                old_chain.tip().block_id(),
+                CanonicalizationParams::default(),
+            )
+            .filter(|c_tx| self.tx_graph.index.is_tx_relevant(&c_tx.tx_node.tx))
+            .filter_map(|c_tx| match c_tx.chain_position {
+                ChainPosition::Confirmed { anchor, .. } if anchor.block_id.height > fork_height => {
+                    Some(c_tx.tx_node.txid)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        disconnected_txs.sort_unstable();
+        Ok(Some(ReorgSummary {
+            fork_height,
+            disconnected_txs,
+        }))
+    }
+
+    /// Stages `chain_changeset` along with the relevant transactions of `block` of `height`.
+    fn apply_block_txs(
+        &mut self,
+        block: &Block,
+        height: u32,
+        chain_changeset: bdk_chain::local_chain::ChangeSet,
+    ) {
+        let mut changeset = ChangeSet::from(chain_changeset);
+        let mut indexed_graph_changeset = self.tx_graph.apply_block_relevant(block, height);
+        // txs paying to script pubkeys derived as the keychain lookahead moves forward
+        while self.replenish_keychain_lookahead() {
+            indexed_graph_changeset.merge(self.tx_graph.apply_block_relevant(block, height));
+        }
+        self.commit_pending_labels(&indexed_graph_changeset.tx_graph.txs);
+        changeset.merge(indexed_graph_changeset.into());
+        self.stage_changes(changeset);
+        self.update_canonicalization_params();
+    }
+
     /// Applies relevant transactions from `block` of `height` to the wallet, connects the
     /// block to the internal chain and returns events.
     ///
@@ -2542,6 +4395,147 @@ impl Wallet {
         self.events_helper(|wallet| wallet.apply_block_connected_to(block, height, connected_to))
     }
 
+    /// Applies `block` of `height` like [`apply_block_connected_to`], after validating it
+    /// according to `options`.
+    ///
+    /// Nothing is applied to the wallet if the block fails validation. See the
+    /// [`validation`] module for the checks available.
+    ///
+    /// [`apply_block_connected_to`]: Self::apply_block_connected_to
+    pub fn apply_block_connected_to_with_options(
+        &mut self,
+        block: &Block,
+        height: u32,
+        connected_to: BlockId,
+        options: &ApplyOptions,
+    ) -> Result<(), ApplyError> {
+        if options.validate_pow {
+            validation::validate_block(block, height, self.network)?;
+        }
+        if let Some(provider) = options.header_provider {
+            let block_id = BlockId {
+                height,
+                hash: block.block_hash(),
+            };
+            validation::check_blocks(provider, [block_id])?;
+        }
+        Ok(self.apply_block_connected_to(block, height, connected_to)?)
+    }
+
+    /// Create a [`SpkFilter`] matching the transactions of a block relevant to the wallet.
+    ///
+    /// The filter matches the script pubkeys derived by the wallet, including the lookahead, and
+    /// the outpoints owned or spent by the wallet's transactions. See [`SpkFilter`] for the
+    /// script pubkeys it can miss.
+    pub fn spk_filter(&self) -> SpkFilter {
+        let index = &self.tx_graph.index;
+        let mut filter = SpkFilter::new(index.inner().all_spks().values().cloned());
+        filter.add_outpoints(index.outpoints().iter().map(|(_, outpoint)| *outpoint));
+        for tx in self.tx_graph.graph().full_txs() {
+            filter.add_outpoints(tx.tx.input.iter().map(|txin| txin.previous_output));
+        }
+        filter
+    }
+
+    /// Applies the transactions of a [`FilteredBlock`] to the wallet, and connects the block to
+    /// the internal chain.
+    ///
+    /// This is like [`apply_block`], but only the transactions kept by the [`SpkFilter`] are
+    /// scanned. The transactions are anchored to the block using the time of its header.
+    ///
+    /// [`apply_block`]: Self::apply_block
+    pub fn apply_filtered_block(
+        &mut self,
+        block: &FilteredBlock,
+    ) -> Result<(), CannotConnectError> {
+        let block_id = block.block_id();
+        let mut changeset = ChangeSet::default();
+        changeset.merge(
+            self.chain
+                .apply_header_connected_to(&block.header, block.height, block.connected_to())
+                .map_err(|err| match err {
+                    ApplyHeaderError::InconsistentBlocks => {
+                        unreachable!("connected_to is derived from the block so must be consistent")
+                    }
+                    ApplyHeaderError::CannotConnect(err) => err,
+                })?
+                .into(),
+        );
+        let anchor = ConfirmationBlockTime {
+            block_id,
+            confirmation_time: block.header.time as u64,
+        };
+        let relevant_txs = || block.txs.iter().map(|tx| (tx.clone(), [anchor]));
+        let mut indexed_graph_changeset = self.tx_graph.batch_insert_relevant(relevant_txs());
+        // txs paying to script pubkeys derived as the keychain lookahead moves forward
+        while self.replenish_keychain_lookahead() {
+            indexed_graph_changeset.merge(self.tx_graph.batch_insert_relevant(relevant_txs()));
+        }
+        self.commit_pending_labels(&indexed_graph_changeset.tx_graph.txs);
+        changeset.merge(indexed_graph_changeset.into());
+        self.stage_changes(changeset);
+        self.update_canonicalization_params();
+        Ok(())
+    }
+
+    /// Applies the transactions of a [`FilteredBlock`] to the wallet like
+    /// [`apply_filtered_block`] and returns events.
+    ///
+    /// See [`apply_update_events`] for more information on the returned [`WalletEvent`]s.
+    ///
+    /// [`apply_filtered_block`]: Self::apply_filtered_block
+    /// [`apply_update_events`]: Self::apply_update_events
+    pub fn apply_filtered_block_events(
+        &mut self,
+        block: &FilteredBlock,
+    ) -> Result<Vec<WalletEvent>, CannotConnectError> {
+        self.events_helper(|wallet| wallet.apply_filtered_block(block))
+    }
+
+    /// Compute the effect of applying `block` of `height` to the wallet, without changing it.
+    ///
+    /// The returned [`BalanceDelta`] contains the wallet transactions the block would confirm and
+    /// the balance before and after applying it. Like [`apply_block`], the block must connect to
+    /// the `prev_blockhash` of its header.
+    ///
+    /// [`apply_block`]: Self::apply_block
+    pub fn preview_block(
+        &self,
+        block: &Block,
+        height: u32,
+    ) -> Result<BalanceDelta, CannotConnectError> {
+        let mut chain = self.chain.clone();
+        chain
+            .apply_header_connected_to(&block.header, height, block_connected_to(block, height))
+            .map_err(|err| match err {
+                ApplyHeaderError::InconsistentBlocks => {
+                    unreachable!("connected_to is derived from the block so must be consistent")
+                }
+                ApplyHeaderError::CannotConnect(err) => err,
+            })?;
+        let mut tx_graph = self.tx_graph.clone();
+        let _ = tx_graph.apply_block_relevant(block, height);
+
+        let txids = block
+            .txdata
+            .iter()
+            .map(|tx| tx.compute_txid())
+            .filter(|&txid| tx_graph.graph().get_tx(txid).is_some())
+            .collect();
+
+        Ok(BalanceDelta {
+            txids,
+            before: self.balance(),
+            after: tx_graph.graph().balance(
+                &chain,
+                chain.tip().block_id(),
+                canonicalization_params(self.canonicalization_policy, tx_graph.graph(), &chain),
+                tx_graph.index.outpoints().iter().cloned(),
+                |&(k, _), _| k == KeychainKind::Internal,
+            ),
+        })
+    }
+
     /// Apply relevant unconfirmed transactions to the wallet.
     ///
     /// Transactions that are not relevant are filtered out.
@@ -2558,10 +4552,70 @@ impl Wallet {
         &mut self,
         unconfirmed_txs: impl IntoIterator<Item = (T, u64)>,
     ) {
-        let indexed_graph_changeset = self
+        let unconfirmed_txs = unconfirmed_txs
+            .into_iter()
+            .map(|(tx, last_seen)| (tx.into(), last_seen))
+            .collect::<Vec<(Arc<Transaction>, u64)>>();
+        let mut indexed_graph_changeset = self
             .tx_graph
-            .batch_insert_relevant_unconfirmed(unconfirmed_txs);
-        self.stage.merge(indexed_graph_changeset.into());
+            .batch_insert_relevant_unconfirmed(unconfirmed_txs.iter().cloned());
+        // txs paying to script pubkeys derived as the keychain lookahead moves forward
+        while self.replenish_keychain_lookahead() {
+            indexed_graph_changeset.merge(
+                self.tx_graph
+                    .batch_insert_relevant_unconfirmed(unconfirmed_txs.iter().cloned()),
+            );
+        }
+        self.commit_pending_labels(&indexed_graph_changeset.tx_graph.txs);
+        self.stage_changes(indexed_graph_changeset);
+        self.update_canonicalization_params();
+    }
+
+    /// Broadcast `tx` with `broadcaster` and insert it in the wallet as unconfirmed, last seen
+    /// now.
+    ///
+    /// Returns the txid of the broadcasted transaction. The wallet is not changed if the
+    /// broadcast fails.
+    ///
+    /// **WARNING**: You must persist the changes resulting from one or more calls to this method
+    /// if you need the applied transaction to be reloaded after closing the wallet.
+    #[cfg(feature = "std")]
+    pub fn broadcast_and_apply<B: Broadcaster>(
+        &mut self,
+        tx: &Transaction,
+        broadcaster: &B,
+    ) -> Result<Txid, BroadcastError> {
+        self.broadcast_and_apply_at(tx, broadcaster, now())
+    }
+
+    /// Broadcast `tx` with `broadcaster` and insert it in the wallet as unconfirmed, last seen at
+    /// `seen_at`.
+    ///
+    /// See [`broadcast_and_apply`](Self::broadcast_and_apply) for more.
+    pub fn broadcast_and_apply_at<B: Broadcaster>(
+        &mut self,
+        tx: &Transaction,
+        broadcaster: &B,
+        seen_at: u64,
+    ) -> Result<Txid, BroadcastError> {
+        let txid = broadcaster.broadcast(tx)?;
+        self.apply_unconfirmed_txs([(tx.clone(), seen_at)]);
+        Ok(txid)
+    }
+
+    /// Broadcast `tx` with the async `broadcaster` and insert it in the wallet as unconfirmed,
+    /// last seen now.
+    ///
+    /// See [`broadcast_and_apply`](Self::broadcast_and_apply) for more.
+    #[cfg(feature = "std")]
+    pub async fn broadcast_and_apply_async<B: AsyncBroadcaster>(
+        &mut self,
+        tx: &Transaction,
+        broadcaster: &B,
+    ) -> Result<Txid, BroadcastError> {
+        let txid = broadcaster.broadcast(tx).await?;
+        self.apply_unconfirmed_txs([(tx.clone(), now())]);
+        Ok(txid)
     }
 
     /// Apply relevant unconfirmed transactions to the wallet and returns events.
@@ -2629,7 +4683,7 @@ impl Wallet {
             .list_canonical_txs(
                 chain,
                 chain.tip().block_id(),
-                CanonicalizationParams::default(),
+                self.canonicalization_params(),
             )
             .map(|c| c.tx_node.txid)
             .collect();
@@ -2640,7 +4694,8 @@ impl Wallet {
                 .filter(|(txid, _)| canon_txids.contains(txid)),
         );
 
-        self.stage.merge(changeset.into());
+        self.stage_changes(changeset);
+        self.update_canonicalization_params();
     }
 
     /// Apply evictions of the given transaction IDs with their associated timestamps and returns
@@ -2706,14 +4761,14 @@ impl Wallet {
         E: Debug + Display,
     {
         // Snapshot of chain tip and transactions before
-        let chain_tip1 = self.chain.tip().block_id();
+        let chain_tip1 = self.chain.tip();
         let wallet_txs1 = self.map_transactions();
 
         // Call `f` on self
         f(self)?;
 
         // Chain tip and transactions after
-        let chain_tip2 = self.chain.tip().block_id();
+        let chain_tip2 = self.chain.tip();
         let wallet_txs2 = self.map_transactions();
 
         Ok(wallet_events(
@@ -2725,6 +4780,42 @@ impl Wallet {
         ))
     }
 
+    /// The keychain receiving the change of a transaction built with `params`: the keychain of
+    /// the preferred change address type, or else of the type of most recipients, if only the
+    /// external keychain is of that type.
+    fn preferred_change_keychain(&self, params: &TxParams) -> KeychainKind {
+        if self.keychains().count() == 1 {
+            return KeychainKind::Internal;
+        }
+        let external = self.address_type(KeychainKind::External);
+        let internal = self.address_type(KeychainKind::Internal);
+        let prefers_external = match params.change_address_type {
+            Some(address_type) => external == address_type && internal != address_type,
+            None => {
+                let mut counts = HashMap::<AddressType, usize>::new();
+                for (script_pubkey, _) in &params.recipients {
+                    *counts
+                        .entry(script_address_type(script_pubkey))
+                        .or_default() += 1;
+                }
+                let max = counts.values().copied().max().unwrap_or(0);
+                let mut majority = counts.into_iter().filter(|&(_, count)| count == max);
+                match (majority.next(), majority.next()) {
+                    (Some((address_type, _)), None) => {
+                        script_class(external) == address_type
+                            && script_class(internal) != address_type
+                    }
+                    _ => false,
+                }
+            }
+        };
+        if prefers_external {
+            KeychainKind::External
+        } else {
+            KeychainKind::Internal
+        }
+    }
+
     /// Used internally to ensure that all methods requiring a [`KeychainKind`] will use a
     /// keychain with an associated descriptor. For example in case the wallet was created
     /// with only one keychain, passing [`KeychainKind::Internal`] here will instead return
@@ -2781,6 +4872,11 @@ impl Wallet {
     /// [`SyncRequest`] collects all revealed script pubkeys from the wallet keychain needed to
     /// start a blockchain sync with a spk based blockchain client.
     ///
+    /// The request also carries the transactions the wallet expects for each script pubkey.
+    /// Chain sources report the expected transactions missing from the history of a script pubkey
+    /// as evicted, e.g. when they have been replaced in the mempool, so that they stop being
+    /// listed as pending once the update is applied.
+    ///
     /// The time of the sync is the current system time and is used to record the
     /// tx last-seen for mempool transactions. Or if an expected transaction is missing
     /// or evicted, it is the time of the eviction. Note that timestamps may only increase
@@ -2800,6 +4896,72 @@ impl Wallet {
             ))
     }
 
+    /// Create a [`SyncRequest`] for the given `txids` only, at `start_time`.
+    ///
+    /// See [`Wallet::start_sync_for_txids`] for more.
+    pub fn start_sync_for_txids_at(
+        &self,
+        txids: impl IntoIterator<Item = Txid>,
+        start_time: u64,
+    ) -> SyncRequestBuilder<(KeychainKind, u32)> {
+        SyncRequest::builder_at(start_time)
+            .chain_tip(self.chain.tip())
+            .txids(txids)
+    }
+
+    /// Create a [`SyncRequest`] for the given `txids` only.
+    ///
+    /// Unlike [`Wallet::start_sync_with_revealed_spks`] no script pubkeys are requested, the chain
+    /// source only looks up the confirmation status of the transactions, e.g. to poll for the
+    /// confirmation of a transaction after broadcasting it. Transactions received by the wallet's
+    /// addresses in the meantime aren't part of the response, and are picked up by the next
+    /// regular sync.
+    ///
+    /// The time of the sync is the current system time. To supply your own start time see
+    /// [`Wallet::start_sync_for_txids_at`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[cfg(feature = "std")]
+    pub fn start_sync_for_txids(
+        &self,
+        txids: impl IntoIterator<Item = Txid>,
+    ) -> SyncRequestBuilder<(KeychainKind, u32)> {
+        SyncRequest::builder()
+            .chain_tip(self.chain.tip())
+            .txids(txids)
+    }
+
+    /// Create a [`SyncRequest`] for the given `outpoints` only, at `start_time`.
+    ///
+    /// See [`Wallet::start_sync_for_outpoints`] for more.
+    pub fn start_sync_for_outpoints_at(
+        &self,
+        outpoints: impl IntoIterator<Item = OutPoint>,
+        start_time: u64,
+    ) -> SyncRequestBuilder<(KeychainKind, u32)> {
+        SyncRequest::builder_at(start_time)
+            .chain_tip(self.chain.tip())
+            .outpoints(outpoints)
+    }
+
+    /// Create a [`SyncRequest`] for the given `outpoints` only.
+    ///
+    /// The chain source looks up the transactions creating and spending each outpoint, e.g. to
+    /// check whether the inputs of a broadcast transaction have been spent by a conflicting
+    /// transaction. As with [`Wallet::start_sync_for_txids`], no script pubkeys are requested.
+    ///
+    /// The time of the sync is the current system time. To supply your own start time see
+    /// [`Wallet::start_sync_for_outpoints_at`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[cfg(feature = "std")]
+    pub fn start_sync_for_outpoints(
+        &self,
+        outpoints: impl IntoIterator<Item = OutPoint>,
+    ) -> SyncRequestBuilder<(KeychainKind, u32)> {
+        SyncRequest::builder()
+            .chain_tip(self.chain.tip())
+            .outpoints(outpoints)
+    }
+
     /// Create a [`FullScanRequest] for this wallet.
     ///
     /// This is the first step when performing a spk-based wallet full scan, the returned
@@ -2827,6 +4989,125 @@ impl Wallet {
             .chain_tip(self.chain.tip())
             .spks_from_indexer(&self.tx_graph.index)
     }
+
+    /// Create a [`FullScanRequest`] for a single `keychain` of this wallet.
+    ///
+    /// Chain sources apply the same stop gap to every keychain of a request. Scanning each
+    /// keychain with its own request allows choosing a different stop gap for each of them, for
+    /// example a large one for a merchant's external keychain and a small one for the change
+    /// keychain. The responses can be applied one after the other, and each reports the last
+    /// active index found for its keychain in [`FullScanResponse::last_active_indices`].
+    ///
+    /// ```rust,no_run
+    /// # use bdk_wallet::{KeychainKind, Wallet};
+    /// # let wallet: Wallet = todo!();
+    /// let external = wallet.start_full_scan_for_keychain(KeychainKind::External);
+    /// let internal = wallet.start_full_scan_for_keychain(KeychainKind::Internal);
+    /// // scan `external` with a stop gap of 1000 and `internal` with a stop gap of 20
+    /// ```
+    ///
+    /// For a wallet without a change descriptor, requesting [`KeychainKind::Internal`] returns a
+    /// request for the external keychain.
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[cfg(feature = "std")]
+    pub fn start_full_scan_for_keychain(
+        &self,
+        keychain: KeychainKind,
+    ) -> FullScanRequestBuilder<KeychainKind> {
+        self.keychain_full_scan(FullScanRequest::builder(), keychain)
+    }
+
+    /// Create a [`FullScanRequest`] builder for a single `keychain` at `start_time`.
+    ///
+    /// See [`Wallet::start_full_scan_for_keychain`].
+    pub fn start_full_scan_for_keychain_at(
+        &self,
+        keychain: KeychainKind,
+        start_time: u64,
+    ) -> FullScanRequestBuilder<KeychainKind> {
+        self.keychain_full_scan(FullScanRequest::builder_at(start_time), keychain)
+    }
+
+    /// Create a [`ScanCursor`] to full scan the wallet's keychains in batches of `batch_size`
+    /// script pubkeys, stopping on each keychain after `stop_gap` consecutive unused ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stop_gap` or `batch_size` is zero.
+    pub fn scan_cursor(&self, stop_gap: u32, batch_size: u32) -> ScanCursor {
+        let keychains = self
+            .tx_graph
+            .index
+            .keychains()
+            .map(|(keychain, descriptor)| {
+                let end = if descriptor.has_wildcard() {
+                    BIP32_MAX_INDEX + 1
+                } else {
+                    1
+                };
+                (keychain, end)
+            });
+        ScanCursor::new(stop_gap, batch_size, keychains)
+    }
+
+    /// Create a [`FullScanRequest`] for the next batch of script pubkeys of `cursor`.
+    ///
+    /// The request is empty if the cursor is finished. See [`ScanCursor`] for how to resume an
+    /// interrupted full scan.
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[cfg(feature = "std")]
+    pub fn start_full_scan_batch(
+        &self,
+        cursor: &ScanCursor,
+    ) -> FullScanRequestBuilder<KeychainKind> {
+        self.full_scan_batch(FullScanRequest::builder(), cursor)
+    }
+
+    /// Create a [`FullScanRequest`] builder for the next batch of script pubkeys of `cursor` at
+    /// `start_time`.
+    ///
+    /// See [`Wallet::start_full_scan_batch`].
+    pub fn start_full_scan_batch_at(
+        &self,
+        cursor: &ScanCursor,
+        start_time: u64,
+    ) -> FullScanRequestBuilder<KeychainKind> {
+        self.full_scan_batch(FullScanRequest::builder_at(start_time), cursor)
+    }
+
+    fn full_scan_batch(
+        &self,
+        mut builder: FullScanRequestBuilder<KeychainKind>,
+        cursor: &ScanCursor,
+    ) -> FullScanRequestBuilder<KeychainKind> {
+        builder = builder.chain_tip(self.chain.tip());
+        for (keychain, range) in cursor.pending_ranges() {
+            if let Some(descriptor) = self.tx_graph.index.get_descriptor(keychain) {
+                let spks = SpkIterator::new_with_range(descriptor.clone(), range);
+                builder = builder.spks_for_keychain(keychain, spks);
+            }
+        }
+        builder
+    }
+
+    fn keychain_full_scan(
+        &self,
+        builder: FullScanRequestBuilder<KeychainKind>,
+        keychain: KeychainKind,
+    ) -> FullScanRequestBuilder<KeychainKind> {
+        let keychain = self.map_keychain(keychain);
+        let index = &self.tx_graph.index;
+        let spks = index
+            .unbounded_spk_iter(keychain)
+            .expect("keychain must exist");
+        let builder = builder
+            .chain_tip(self.chain.tip())
+            .spks_for_keychain(keychain, spks);
+        match index.last_revealed_index(keychain) {
+            Some(last_revealed) => builder.last_revealed_for_keychain(keychain, last_revealed),
+            None => builder,
+        }
+    }
 }
 
 impl AsRef<bdk_chain::tx_graph::TxGraph<ConfirmationBlockTime>> for Wallet {
@@ -2870,6 +5151,55 @@ where
     Ok(wallet_name)
 }
 
+/// Current UNIX timestamp in seconds.
+#[cfg(feature = "std")]
+fn now() -> u64 {
+    std::time::UNIX_EPOCH
+        .elapsed()
+        .expect("system time must be after the UNIX epoch")
+        .as_secs()
+}
+
+/// The block a `block` of `height` connects to, or the block itself for the genesis block.
+fn block_connected_to(block: &Block, height: u32) -> BlockId {
+    match height.checked_sub(1) {
+        Some(prev_height) => BlockId {
+            height: prev_height,
+            hash: block.header.prev_blockhash,
+        },
+        None => BlockId {
+            height,
+            hash: block.block_hash(),
+        },
+    }
+}
+
+/// The address type of `script_pubkey` as far as it can be told from the script, which doesn't
+/// reveal whether a P2SH output nests a witness program.
+fn script_address_type(script_pubkey: &Script) -> AddressType {
+    if script_pubkey.is_p2tr() {
+        AddressType::P2tr
+    } else if script_pubkey.is_p2wpkh() {
+        AddressType::P2wpkh
+    } else if script_pubkey.is_p2wsh() {
+        AddressType::P2wsh
+    } else if script_pubkey.is_p2sh() {
+        AddressType::P2sh
+    } else if script_pubkey.is_p2pkh() {
+        AddressType::P2pkh
+    } else {
+        AddressType::Bare
+    }
+}
+
+/// The address type of the scripts of `address_type`, see [`script_address_type`].
+fn script_class(address_type: AddressType) -> AddressType {
+    match address_type {
+        AddressType::P2shP2wpkh | AddressType::P2shP2wsh => AddressType::P2sh,
+        address_type => address_type,
+    }
+}
+
 fn new_local_utxo(
     keychain: KeychainKind,
     derivation_index: u32,
@@ -2885,6 +5215,160 @@ fn new_local_utxo(
     }
 }
 
+/// Number of entries of the SPK cache of a descriptor checked when a wallet is loaded.
+const SPK_CACHE_SAMPLE_SIZE: usize = 8;
+
+/// Running digest of the cached script pubkeys of a descriptor, by increasing index.
+///
+/// The SPK cache only grows by deriving script pubkeys at indices past the cached ones, so the
+/// digest is updated with the newly cached script pubkeys instead of hashing the whole cache again.
+#[derive(Clone, Default)]
+struct SpkCacheDigest {
+    engine: sha256::HashEngine,
+    /// The index following the last script pubkey fed to `engine`.
+    next_index: u32,
+}
+
+impl SpkCacheDigest {
+    /// Feed the script pubkeys of `spks` from `next_index` on, return whether any was fed.
+    fn update(&mut self, spks: &BTreeMap<u32, ScriptBuf>) -> bool {
+        let next_index = self.next_index;
+        for (&index, spk) in spks.range(next_index..) {
+            self.engine.input(&index.to_be_bytes());
+            self.engine.input(&(spk.len() as u64).to_be_bytes());
+            self.engine.input(spk.as_bytes());
+            self.next_index = index.saturating_add(1);
+        }
+        self.next_index != next_index
+    }
+
+    fn digest(&self) -> sha256::Hash {
+        sha256::Hash::from_engine(self.engine.clone())
+    }
+}
+
+impl fmt::Debug for SpkCacheDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpkCacheDigest")
+            .field("digest", &self.digest())
+            .field("next_index", &self.next_index)
+            .finish()
+    }
+}
+
+/// Check the cached script pubkeys of `descriptor` against their persisted digest in `digests` and
+/// a sample of them against the descriptor, and drop them all if any is wrong, so that they are
+/// derived again. Return the digest of the cache if it's kept.
+///
+/// The digest covers every entry, it's missing for caches persisted before digests were. The
+/// first and last entries of the sample are always checked.
+fn verify_spk_cache(
+    spk_cache: &mut BTreeMap<DescriptorId, BTreeMap<u32, ScriptBuf>>,
+    descriptor: &ExtendedDescriptor,
+    digests: &BTreeMap<DescriptorId, sha256::Hash>,
+) -> Option<SpkCacheDigest> {
+    let descriptor_id = descriptor.descriptor_id();
+    let spks = spk_cache.get(&descriptor_id)?;
+    let mut spk_cache_digest = SpkCacheDigest::default();
+    spk_cache_digest.update(spks);
+    let step = (spks.len() / SPK_CACHE_SAMPLE_SIZE).max(1);
+    let is_valid = digests
+        .get(&descriptor_id)
+        .is_none_or(|digest| spk_cache_digest.digest() == *digest)
+        && spks
+            .iter()
+            .step_by(step)
+            .chain(spks.iter().next_back())
+            .all(|(&index, spk)| {
+                descriptor
+                    .at_derivation_index(index)
+                    .is_ok_and(|descriptor| descriptor.script_pubkey() == *spk)
+            });
+    if !is_valid {
+        spk_cache.remove(&descriptor_id);
+        return None;
+    }
+    Some(spk_cache_digest)
+}
+
+/// The parameters of the canonicalization of the transactions of `graph`, assuming the winners of
+/// the ties broken by `policy` to be canonical.
+fn canonicalization_params(
+    policy: CanonicalizationPolicy,
+    graph: &TxGraph<ConfirmationBlockTime>,
+    chain: &LocalChain,
+) -> CanonicalizationParams {
+    if policy == CanonicalizationPolicy::TxidOrder {
+        // the order of `bdk_chain`
+        return CanonicalizationParams::default();
+    }
+    let chain_tip = chain.tip().block_id();
+    let canonical_txs = |params| -> HashMap<Txid, bool> {
+        graph
+            .list_canonical_txs(chain, chain_tip, params)
+            .map(|c| (c.tx_node.txid, c.chain_position.is_confirmed()))
+            .collect()
+    };
+    let canonical = canonical_txs(CanonicalizationParams::default());
+    // last seen of the transactions which aren't evicted from the mempool
+    let last_seen: HashMap<Txid, u64> = graph
+        .txids_by_descending_last_seen()
+        .map(|(last_seen, txid)| (txid, last_seen))
+        .collect();
+    let policy_key = |txid: Txid| {
+        let tx_node = graph.get_tx_node(txid);
+        match policy {
+            CanonicalizationPolicy::TxidOrder => None,
+            CanonicalizationPolicy::HigherFeeWins => tx_node
+                .and_then(|tx_node| graph.calculate_fee(&tx_node.tx).ok())
+                .map(Amount::to_sat),
+            CanonicalizationPolicy::FirstSeenLatestWins => {
+                tx_node.and_then(|tx_node| tx_node.first_seen)
+            }
+        }
+    };
+
+    // conflicting transactions which lost a tie to a canonical one, but win by the policy
+    let mut winners = BTreeSet::new();
+    for (&txid, &confirmed) in &canonical {
+        let Some(&seen_at) = last_seen.get(&txid).filter(|_| !confirmed) else {
+            continue;
+        };
+        let Some(tx_node) = graph.get_tx_node(txid) else {
+            continue;
+        };
+        let winner = graph
+            .direct_conflicts(&tx_node.tx)
+            .map(|(_, conflict)| conflict)
+            .filter(|conflict| {
+                !canonical.contains_key(conflict) && last_seen.get(conflict) == Some(&seen_at)
+            })
+            .chain([txid])
+            .max_by_key(|&txid| (policy_key(txid), txid))
+            .expect("contains txid");
+        if winner != txid {
+            winners.insert(winner);
+        }
+    }
+
+    // a winner which would evict a confirmed transaction isn't assumed to be canonical
+    let mut assume_canonical = Vec::new();
+    for winner in winners {
+        assume_canonical.push(winner);
+        let params = CanonicalizationParams {
+            assume_canonical: assume_canonical.clone(),
+        };
+        let assumed = canonical_txs(params);
+        let evicts_confirmed = canonical
+            .iter()
+            .any(|(txid, &confirmed)| confirmed && !assumed.contains_key(txid));
+        if evicts_confirmed {
+            assume_canonical.pop();
+        }
+    }
+    CanonicalizationParams { assume_canonical }
+}
+
 fn make_indexed_graph(
     stage: &mut ChangeSet,
     tx_graph_changeset: chain::tx_graph::ChangeSet<ConfirmationBlockTime>,
@@ -0,0 +1,306 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Syncing several wallets with a single pass of a chain source.
+
+use alloc::vec::Vec;
+
+use bitcoin::{ScriptBuf, Txid};
+use chain::local_chain::CheckPoint;
+use chain::spk_client::{
+    FullScanRequest, FullScanRequestBuilder, FullScanResponse, SyncRequest, SyncRequestBuilder,
+    SyncResponse,
+};
+use chain::{ConfirmationBlockTime, SpkIterator, TxUpdate};
+
+use crate::collections::{BTreeMap, HashSet};
+use crate::descriptor::ExtendedDescriptor;
+use crate::wallet::{SpkFilter, Update, Wallet};
+use crate::KeychainKind;
+
+/// A request to sync or full scan several wallets at once.
+///
+/// Syncing many wallets against the same chain source one by one fetches the same headers for
+/// each of them. Instead, the wallets can be added to a `MultiSyncRequest` under an id chosen by
+/// the caller, and the chain source is given a single request merging the script pubkeys of all
+/// of them, keyed by wallet id. The response is then split into one [`Update`] per wallet with
+/// [`split_sync`](Self::split_sync) or [`split_full_scan`](Self::split_full_scan).
+///
+/// Each update only contains the transactions relevant to its wallet, but all of them carry the
+/// chain update of the response, so the tip of every wallet advances. For full scans, each
+/// keychain of each wallet is scanned until the stop gap separately, like when scanning the
+/// wallets one by one.
+///
+/// ```rust,no_run
+/// # use bdk_wallet::{MultiSyncRequest, Wallet};
+/// # use bdk_wallet::chain::spk_client::{SyncRequest, SyncResponse};
+/// # fn sync<I>(_: SyncRequest<I>) -> anyhow::Result<SyncResponse> { todo!() }
+/// # let mut wallets: Vec<Wallet> = todo!();
+/// let mut multi = MultiSyncRequest::new();
+/// for (id, wallet) in wallets.iter().enumerate() {
+///     multi.add_wallet(id, wallet);
+/// }
+/// let response = sync(multi.start_sync().build())?;
+/// for (id, update) in multi.split_sync(response) {
+///     wallets[id].apply_update(update)?;
+/// }
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+///
+/// The request is a snapshot of the wallets, which must not change before the response is
+/// split.
+#[derive(Debug, Clone)]
+pub struct MultiSyncRequest<W> {
+    chain_tip: Option<CheckPoint>,
+    wallets: BTreeMap<W, WalletSnapshot>,
+}
+
+/// The data of a wallet needed to request and split updates.
+#[derive(Debug, Clone)]
+struct WalletSnapshot {
+    revealed_spks: Vec<((KeychainKind, u32), ScriptBuf)>,
+    expected_spk_txids: Vec<(ScriptBuf, Txid)>,
+    last_revealed: BTreeMap<KeychainKind, u32>,
+    descriptors: BTreeMap<KeychainKind, ExtendedDescriptor>,
+    filter: SpkFilter,
+    txids: HashSet<Txid>,
+}
+
+impl<W: Ord + Clone> Default for MultiSyncRequest<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Ord + Clone> MultiSyncRequest<W> {
+    /// Create an empty request.
+    pub fn new() -> Self {
+        Self {
+            chain_tip: None,
+            wallets: BTreeMap::new(),
+        }
+    }
+
+    /// Add `wallet` to the request, under `id`.
+    ///
+    /// The chain tip of the request is the highest tip of the wallets added. Adding a wallet
+    /// under the id of another one replaces it.
+    pub fn add_wallet(&mut self, id: W, wallet: &Wallet) -> &mut Self {
+        let tip = wallet.latest_checkpoint();
+        if self
+            .chain_tip
+            .as_ref()
+            .is_none_or(|chain_tip| tip.height() > chain_tip.height())
+        {
+            self.chain_tip = Some(tip.clone());
+        }
+        let index = &wallet.tx_graph.index;
+        let snapshot = WalletSnapshot {
+            revealed_spks: index.revealed_spks(..).collect(),
+            expected_spk_txids: wallet
+                .tx_graph
+                .list_expected_spk_txids(&wallet.chain, tip.block_id(), ..)
+                .collect(),
+            last_revealed: index.last_revealed_indices(),
+            descriptors: wallet
+                .keychains()
+                .map(|(keychain, descriptor)| (keychain, descriptor.clone()))
+                .collect(),
+            filter: wallet.spk_filter(),
+            txids: wallet
+                .tx_graph
+                .graph()
+                .full_txs()
+                .map(|tx| tx.txid)
+                .collect(),
+        };
+        self.wallets.insert(id, snapshot);
+        self
+    }
+
+    /// The ids of the wallets of the request.
+    pub fn wallet_ids(&self) -> impl Iterator<Item = &W> {
+        self.wallets.keys()
+    }
+
+    /// Create a [`SyncRequest`] of the revealed script pubkeys of every wallet, at `start_time`.
+    ///
+    /// See [`Wallet::start_sync_with_revealed_spks_at`].
+    pub fn start_sync_at(&self, start_time: u64) -> SyncRequestBuilder<(W, (KeychainKind, u32))> {
+        self.sync_builder(SyncRequest::builder_at(start_time))
+    }
+
+    /// Create a [`SyncRequest`] of the revealed script pubkeys of every wallet.
+    ///
+    /// See [`Wallet::start_sync_with_revealed_spks`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[cfg(feature = "std")]
+    pub fn start_sync(&self) -> SyncRequestBuilder<(W, (KeychainKind, u32))> {
+        self.sync_builder(SyncRequest::builder())
+    }
+
+    fn sync_builder(
+        &self,
+        mut builder: SyncRequestBuilder<(W, (KeychainKind, u32))>,
+    ) -> SyncRequestBuilder<(W, (KeychainKind, u32))> {
+        if let Some(tip) = &self.chain_tip {
+            builder = builder.chain_tip(tip.clone());
+        }
+        for (id, snapshot) in &self.wallets {
+            builder = builder
+                .spks_with_indexes(
+                    snapshot
+                        .revealed_spks
+                        .iter()
+                        .map(|(index, spk)| ((id.clone(), *index), spk.clone())),
+                )
+                .expected_spk_txids(snapshot.expected_spk_txids.iter().cloned());
+        }
+        builder
+    }
+
+    /// Create a [`FullScanRequest`] of every keychain of every wallet, at `start_time`.
+    ///
+    /// See [`Wallet::start_full_scan_at`].
+    pub fn start_full_scan_at(&self, start_time: u64) -> FullScanRequestBuilder<(W, KeychainKind)> {
+        self.full_scan_builder(FullScanRequest::builder_at(start_time))
+    }
+
+    /// Create a [`FullScanRequest`] of every keychain of every wallet.
+    ///
+    /// See [`Wallet::start_full_scan`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[cfg(feature = "std")]
+    pub fn start_full_scan(&self) -> FullScanRequestBuilder<(W, KeychainKind)> {
+        self.full_scan_builder(FullScanRequest::builder())
+    }
+
+    fn full_scan_builder(
+        &self,
+        mut builder: FullScanRequestBuilder<(W, KeychainKind)>,
+    ) -> FullScanRequestBuilder<(W, KeychainKind)> {
+        if let Some(tip) = &self.chain_tip {
+            builder = builder.chain_tip(tip.clone());
+        }
+        for (id, snapshot) in &self.wallets {
+            for (&keychain, descriptor) in &snapshot.descriptors {
+                let keychain_id = (id.clone(), keychain);
+                builder = builder
+                    .spks_for_keychain(keychain_id.clone(), SpkIterator::new(descriptor.clone()));
+                if let Some(&index) = snapshot.last_revealed.get(&keychain) {
+                    builder = builder.last_revealed_for_keychain(keychain_id, index);
+                }
+            }
+        }
+        builder
+    }
+
+    /// Split the `response` to a request created with [`start_sync`](Self::start_sync) into an
+    /// update for each wallet.
+    pub fn split_sync(&self, response: SyncResponse) -> BTreeMap<W, Update> {
+        self.wallets
+            .iter()
+            .map(|(id, snapshot)| {
+                let update = Update {
+                    last_active_indices: BTreeMap::new(),
+                    tx_update: snapshot.split(&response.tx_update, snapshot.filter.clone()),
+                    chain: response.chain_update.clone(),
+                };
+                (id.clone(), update)
+            })
+            .collect()
+    }
+
+    /// Split the `response` to a request created with
+    /// [`start_full_scan`](Self::start_full_scan) into an update for each wallet.
+    pub fn split_full_scan(
+        &self,
+        response: FullScanResponse<(W, KeychainKind)>,
+    ) -> BTreeMap<W, Update> {
+        self.wallets
+            .iter()
+            .map(|(id, snapshot)| {
+                let last_active_indices = response
+                    .last_active_indices
+                    .iter()
+                    .filter(|((wallet_id, _), _)| wallet_id == id)
+                    .map(|((_, keychain), &index)| (*keychain, index))
+                    .collect::<BTreeMap<_, _>>();
+                // the scan may find transactions paying to script pubkeys not derived yet
+                let mut filter = snapshot.filter.clone();
+                for (keychain, &index) in &last_active_indices {
+                    if let Some(descriptor) = snapshot.descriptors.get(keychain) {
+                        filter.add_spks(
+                            SpkIterator::new_with_range(descriptor.clone(), ..=index)
+                                .map(|(_, spk)| spk),
+                        );
+                    }
+                }
+                let update = Update {
+                    tx_update: snapshot.split(&response.tx_update, filter),
+                    last_active_indices,
+                    chain: response.chain_update.clone(),
+                };
+                (id.clone(), update)
+            })
+            .collect()
+    }
+}
+
+impl WalletSnapshot {
+    /// Keep the data of `tx_update` relevant to the wallet, using `filter` to find its
+    /// transactions.
+    fn split(
+        &self,
+        tx_update: &TxUpdate<ConfirmationBlockTime>,
+        mut filter: SpkFilter,
+    ) -> TxUpdate<ConfirmationBlockTime> {
+        let matched = filter.filter_txs(&tx_update.txs);
+        let is_relevant = |txid: &Txid| matched.contains_key(txid) || self.txids.contains(txid);
+
+        let mut indices = matched.values().copied().collect::<Vec<_>>();
+        indices.sort_unstable();
+        let mut split = TxUpdate::default();
+        split.txs = indices
+            .into_iter()
+            .map(|i| tx_update.txs[i].clone())
+            .collect();
+        let spent = split
+            .txs
+            .iter()
+            .flat_map(|tx| tx.input.iter().map(|txin| txin.previous_output))
+            .collect::<HashSet<_>>();
+        split.txouts = tx_update
+            .txouts
+            .iter()
+            .filter(|(outpoint, _)| spent.contains(*outpoint))
+            .map(|(outpoint, txout)| (*outpoint, txout.clone()))
+            .collect();
+        split.anchors = tx_update
+            .anchors
+            .iter()
+            .filter(|(_, txid)| is_relevant(txid))
+            .cloned()
+            .collect();
+        split.seen_ats = tx_update
+            .seen_ats
+            .iter()
+            .filter(|(txid, _)| is_relevant(txid))
+            .cloned()
+            .collect();
+        split.evicted_ats = tx_update
+            .evicted_ats
+            .iter()
+            .filter(|(txid, _)| is_relevant(txid))
+            .cloned()
+            .collect();
+        split
+    }
+}
@@ -1,11 +1,16 @@
 use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 use bdk_chain::keychain_txout::DEFAULT_LOOKAHEAD;
 use bitcoin::{BlockHash, Network, NetworkKind};
 use miniscript::descriptor::KeyMap;
 
 use crate::{
+    collections::BTreeMap,
     descriptor::{DescriptorError, ExtendedDescriptor, IntoWalletDescriptor},
+    integrity::OnCorruption,
+    signer::{SignerOrdering, TransactionSigner},
     utils::SecpCtx,
     AsyncWalletPersister, CreateWithPersistError, KeychainKind, LoadWithPersistError, Wallet,
     WalletPersister,
@@ -66,7 +71,10 @@ pub struct CreateParams {
     pub(crate) network: Network,
     pub(crate) genesis_hash: Option<BlockHash>,
     pub(crate) lookahead: u32,
+    pub(crate) keychain_lookahead: BTreeMap<KeychainKind, u32>,
     pub(crate) use_spk_cache: bool,
+    pub(crate) rebase_hardened: bool,
+    pub(crate) watch_only: bool,
 }
 
 impl CreateParams {
@@ -89,7 +97,10 @@ impl CreateParams {
             network: Network::Bitcoin,
             genesis_hash: None,
             lookahead: DEFAULT_LOOKAHEAD,
+            keychain_lookahead: BTreeMap::new(),
             use_spk_cache: false,
+            rebase_hardened: false,
+            watch_only: false,
         }
     }
 
@@ -111,7 +122,10 @@ impl CreateParams {
             network: Network::Bitcoin,
             genesis_hash: None,
             lookahead: DEFAULT_LOOKAHEAD,
+            keychain_lookahead: BTreeMap::new(),
             use_spk_cache: false,
+            rebase_hardened: false,
+            watch_only: false,
         }
     }
 
@@ -136,7 +150,10 @@ impl CreateParams {
             network: Network::Bitcoin,
             genesis_hash: None,
             lookahead: DEFAULT_LOOKAHEAD,
+            keychain_lookahead: BTreeMap::new(),
             use_spk_cache: false,
+            rebase_hardened: false,
+            watch_only: false,
         }
     }
 
@@ -173,6 +190,18 @@ impl CreateParams {
         self
     }
 
+    /// Use a custom `lookahead` value for the given `keychain` only.
+    ///
+    /// This allows, for example, a large lookahead on the external keychain while keeping the
+    /// internal keychain's lookahead small. The keychain lookahead can only extend the window set
+    /// by [`lookahead`](Self::lookahead), so a value smaller than it has no effect.
+    ///
+    /// The keychain lookahead can be changed later with [`Wallet::set_lookahead`].
+    pub fn lookahead_for(mut self, keychain: KeychainKind, lookahead: u32) -> Self {
+        self.keychain_lookahead.insert(keychain, lookahead);
+        self
+    }
+
     /// Use a persistent cache of indexed script pubkeys (SPKs).
     ///
     /// **Note:** To persist across restarts, this option must also be set at load time with
@@ -182,6 +211,28 @@ impl CreateParams {
         self
     }
 
+    /// Apply the hardened derivation steps of extended public keys whose extended private key is
+    /// in the descriptor [`keymap`](Self::keymap), instead of rejecting the descriptor.
+    ///
+    /// The wallet stores the rebased descriptors, which must be used when loading it. Refer to
+    /// [`rebase_hardened`](crate::descriptor::rebase_hardened) for more.
+    pub fn rebase_hardened(mut self, rebase_hardened: bool) -> Self {
+        self.rebase_hardened = rebase_hardened;
+        self
+    }
+
+    /// Reject any secret key, so that the wallet is created watch-only.
+    ///
+    /// Creating the wallet fails with [`DescriptorError::SecretKeysNotAllowed`] if the
+    /// descriptors, or the [`keymap`](Self::keymap), contain a secret key, e.g. an `xprv` or a WIF
+    /// key. The wallet is created without signers.
+    ///
+    /// Note that signers can still be added to the wallet later with [`Wallet::add_signer`].
+    pub fn watch_only(mut self) -> Self {
+        self.watch_only = true;
+        self
+    }
+
     /// Create [`PersistedWallet`] with the given [`WalletPersister`].
     pub fn create_wallet<P>(
         self,
@@ -216,12 +267,17 @@ pub struct LoadParams {
     pub(crate) descriptor_keymap: KeyMap,
     pub(crate) change_descriptor_keymap: KeyMap,
     pub(crate) lookahead: u32,
+    pub(crate) keychain_lookahead: BTreeMap<KeychainKind, u32>,
     pub(crate) check_network: Option<Network>,
     pub(crate) check_genesis_hash: Option<BlockHash>,
     pub(crate) check_descriptor: Option<Option<DescriptorToExtract>>,
     pub(crate) check_change_descriptor: Option<Option<DescriptorToExtract>>,
     pub(crate) extract_keys: bool,
     pub(crate) use_spk_cache: bool,
+    pub(crate) signers: Vec<(KeychainKind, SignerOrdering, Arc<dyn TransactionSigner>)>,
+    pub(crate) read_only: bool,
+    pub(crate) ignore_spk_cache: bool,
+    pub(crate) on_corruption: Option<OnCorruption>,
 }
 
 impl LoadParams {
@@ -233,15 +289,42 @@ impl LoadParams {
             descriptor_keymap: KeyMap::default(),
             change_descriptor_keymap: KeyMap::default(),
             lookahead: DEFAULT_LOOKAHEAD,
+            keychain_lookahead: BTreeMap::new(),
             check_network: None,
             check_genesis_hash: None,
             check_descriptor: None,
             check_change_descriptor: None,
             extract_keys: false,
             use_spk_cache: false,
+            signers: Vec::new(),
+            read_only: false,
+            ignore_spk_cache: false,
+            on_corruption: None,
         }
     }
 
+    /// Load the wallet without ever writing to the persister.
+    ///
+    /// The persister is not initialized, so its schema is neither created nor migrated, and
+    /// [`PersistedWallet::persist`] does nothing and returns `Ok(false)`. The wallet still
+    /// stages its changes, which can be inspected with [`Wallet::staged`]. This allows opening
+    /// the database of a wallet in use by another process, for example read-only with SQLite:
+    ///
+    /// ```rust,no_run
+    /// # use bdk_wallet::rusqlite::{Connection, OpenFlags};
+    /// # use bdk_wallet::Wallet;
+    /// let mut conn = Connection::open_with_flags("wallet.sqlite", OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    /// let wallet = Wallet::load().read_only().load_wallet(&mut conn)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// A SQLite database written by an older version of this crate must be loaded for writing
+    /// once to be upgraded, before it can be loaded read-only.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
     /// Extend the given `keychain`'s `keymap`.
     pub fn keymap(mut self, keychain: KeychainKind, keymap: KeyMap) -> Self {
         match keychain {
@@ -315,6 +398,18 @@ impl LoadParams {
         self
     }
 
+    /// Use a custom `lookahead` value for the given `keychain` only.
+    ///
+    /// This allows, for example, a large lookahead on the external keychain while keeping the
+    /// internal keychain's lookahead small. The keychain lookahead can only extend the window set
+    /// by [`lookahead`](Self::lookahead), so a value smaller than it has no effect.
+    ///
+    /// The keychain lookahead can be changed later with [`Wallet::set_lookahead`].
+    pub fn lookahead_for(mut self, keychain: KeychainKind, lookahead: u32) -> Self {
+        self.keychain_lookahead.insert(keychain, lookahead);
+        self
+    }
+
     /// Whether to try extracting private keys from the *provided descriptors* upon loading.
     /// See also [`LoadParams::descriptor`].
     pub fn extract_keys(mut self) -> Self {
@@ -324,6 +419,12 @@ impl LoadParams {
 
     /// Use a persistent cache of indexed script pubkeys (SPKs).
     ///
+    /// The cached script pubkeys are used instead of deriving them again from the descriptors,
+    /// which makes loading a wallet with a large lookahead faster. The cached script pubkeys of
+    /// each descriptor are checked against a digest persisted with them when loading, and the
+    /// cache of a descriptor is derived again if any of them is wrong. See
+    /// [`ignore_spk_cache`](Self::ignore_spk_cache) to derive the whole cache again.
+    ///
     /// NOTE: This should only be used if you have previously persisted a cache of script
     /// pubkeys using [`CreateParams::use_spk_cache`].
     pub fn use_spk_cache(mut self, use_spk_cache: bool) -> Self {
@@ -331,6 +432,40 @@ impl LoadParams {
         self
     }
 
+    /// Derive the script pubkeys again instead of using the persisted cache.
+    ///
+    /// With [`use_spk_cache`](Self::use_spk_cache), the script pubkeys derived again are staged
+    /// to replace the cache when the wallet is persisted, e.g. if it is suspected to be corrupted.
+    pub fn ignore_spk_cache(mut self) -> Self {
+        self.ignore_spk_cache = true;
+        self
+    }
+
+    /// Check the integrity of the persisted data when loading, and handle any corruption found as
+    /// `on_corruption` requires.
+    ///
+    /// See the [`integrity`](crate::integrity) module for the checks. Without this, the data is
+    /// loaded as is.
+    pub fn on_corruption(mut self, on_corruption: OnCorruption) -> Self {
+        self.on_corruption = Some(on_corruption);
+        self
+    }
+
+    /// Add an external signer to the loaded wallet, as [`Wallet::add_signer`] would.
+    ///
+    /// Only descriptors are persisted, signers added with [`Wallet::add_signer`] are lost when
+    /// the wallet is reloaded. Wallets whose descriptors don't contain private keys are loaded
+    /// watch-only unless their signers are added again here.
+    pub fn add_signer(
+        mut self,
+        keychain: KeychainKind,
+        ordering: SignerOrdering,
+        signer: Arc<dyn TransactionSigner>,
+    ) -> Self {
+        self.signers.push((keychain, ordering, signer));
+        self
+    }
+
     /// Load [`PersistedWallet`] with the given [`WalletPersister`].
     pub fn load_wallet<P>(
         self,
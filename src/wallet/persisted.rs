@@ -8,8 +8,13 @@ use core::{
 
 use alloc::{boxed::Box, string::ToString};
 use chain::Merge;
+#[cfg(feature = "file_store")]
+use serde::{Deserialize, Serialize};
 
 use crate::error::LoadError;
+use crate::integrity::{self, CorruptionReport};
+#[cfg(any(feature = "rusqlite", feature = "file_store"))]
+use crate::migration::ChangeSetMigrationError;
 use crate::{
     descriptor::{calc_checksum, DescriptorError},
     ChangeSet, CreateParams, LoadParams, Wallet,
@@ -48,6 +53,37 @@ pub trait WalletPersister {
     /// [`persist`]: WalletPersister::persist
     fn initialize(persister: &mut Self) -> Result<ChangeSet, Self::Error>;
 
+    /// Load all data of the `persister` without writing to it.
+    ///
+    /// This is called by [`PersistedWallet::load`] instead of [`initialize`] when the wallet is
+    /// loaded [read-only](crate::LoadParams::read_only).
+    ///
+    /// # Implementation Details
+    ///
+    /// Unlike [`initialize`], the schema of the `persister` must not be created or migrated, and
+    /// an uninitialized `persister` should be reported as empty. The default implementation calls
+    /// [`initialize`], which is only correct for persisters whose initialization never writes.
+    ///
+    /// [`initialize`]: WalletPersister::initialize
+    fn load_read_only(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        Self::initialize(persister)
+    }
+
+    /// Check the integrity of the storage of the `persister`.
+    ///
+    /// This is called by [`PersistedWallet::load`] before loading the data, when the wallet is
+    /// loaded with [`LoadParams::on_corruption`]. See the [`integrity`](crate::integrity) module.
+    ///
+    /// # Implementation Details
+    ///
+    /// The storage must not be written to. The loaded [`ChangeSet`] is checked by
+    /// [`PersistedWallet::load`] for all persisters, so only inconsistencies that don't show in it
+    /// are reported here. The default implementation reports none.
+    fn check_integrity(persister: &mut Self) -> Result<CorruptionReport, Self::Error> {
+        let _ = persister;
+        Ok(CorruptionReport::default())
+    }
+
     /// Persist the given `changeset` to the `persister`.
     ///
     /// This method can fail if the `persister` is not [`initialize`]d.
@@ -57,9 +93,9 @@ pub trait WalletPersister {
 }
 
 #[cfg(feature = "std")]
-type FutureResult<'a, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'a>>;
+pub(crate) type FutureResult<'a, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'a>>;
 #[cfg(not(feature = "std"))]
-type FutureResult<'a, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + 'a>>;
+pub(crate) type FutureResult<'a, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + 'a>>;
 
 /// Async trait that persists [`PersistedWallet`].
 ///
@@ -97,6 +133,34 @@ pub trait AsyncWalletPersister {
     where
         Self: 'a;
 
+    /// Load all data of the `persister` without writing to it.
+    ///
+    /// This is called by [`PersistedWallet::load_async`] instead of [`initialize`] when the
+    /// wallet is loaded [read-only](crate::LoadParams::read_only). See
+    /// [`WalletPersister::load_read_only`] for the implementation details.
+    ///
+    /// [`initialize`]: AsyncWalletPersister::initialize
+    fn load_read_only<'a>(persister: &'a mut Self) -> FutureResult<'a, ChangeSet, Self::Error>
+    where
+        Self: 'a,
+    {
+        Self::initialize(persister)
+    }
+
+    /// Check the integrity of the storage of the `persister`.
+    ///
+    /// This is called by [`PersistedWallet::load_async`] before loading the data, when the wallet
+    /// is loaded with [`LoadParams::on_corruption`]. See [`WalletPersister::check_integrity`].
+    fn check_integrity<'a>(
+        persister: &'a mut Self,
+    ) -> FutureResult<'a, CorruptionReport, Self::Error>
+    where
+        Self: 'a,
+    {
+        let _ = persister;
+        Box::pin(async { Ok(CorruptionReport::default()) })
+    }
+
     /// Persist the given `changeset` to the `persister`.
     ///
     /// This method can fail if the `persister` is not [`initialize`]d.
@@ -125,9 +189,29 @@ pub trait AsyncWalletPersister {
 /// * Ensure the wallet is persisted to the same `P` type as when created/loaded. Note that this is
 ///   not completely fool-proof as you can have multiple instances of the same `P` type that are
 ///   connected to different databases.
+///
+/// # Persistence errors
+///
+/// When persisting fails, the staged changes are kept in the [`Wallet`] and nothing else about
+/// the wallet changes. The next successful persist writes them, merged with any changes staged
+/// in the meantime. The failed changeset may have been partially or fully stored by the
+/// persister, so a retry can hand it changes it already has, which is harmless since merging a
+/// changeset is idempotent. A wallet whose creation fails to persist is not returned, and the
+/// persister may hold part of its initial changeset.
+///
+/// The `test_utils::FlakyPersister` of the `test-utils` feature can be used to test the handling
+/// of these errors.
+///
+/// # Read-only wallets
+///
+/// A wallet loaded with [`LoadParams::read_only`] never writes to its persister: it is loaded
+/// without initializing the persister, and persisting it does nothing. Its changes are still
+/// staged, and can be inspected with [`Wallet::staged`].
 #[derive(Debug)]
 pub struct PersistedWallet<P> {
     inner: Wallet,
+    read_only: bool,
+    corruption_report: CorruptionReport,
     _marker: PhantomData<fn(&mut P)>,
 }
 
@@ -145,6 +229,21 @@ impl<P> DerefMut for PersistedWallet<P> {
     }
 }
 
+impl<P> PersistedWallet<P> {
+    /// Whether the wallet was loaded [read-only](Self#read-only-wallets).
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The corruptions left out of the wallet when it was loaded with
+    /// [`OnCorruption::Recover`](crate::integrity::OnCorruption::Recover).
+    ///
+    /// Empty if no corruption was found, or if the integrity of the data was not checked.
+    pub fn corruption_report(&self) -> &CorruptionReport {
+        &self.corruption_report
+    }
+}
+
 /// Methods when `P` is a [`WalletPersister`].
 impl<P: WalletPersister> PersistedWallet<P> {
     /// Create a new [`PersistedWallet`] with the given `persister` and `params`.
@@ -165,6 +264,8 @@ impl<P: WalletPersister> PersistedWallet<P> {
         }
         Ok(Self {
             inner,
+            read_only: false,
+            corruption_report: CorruptionReport::default(),
             _marker: PhantomData,
         })
     }
@@ -174,11 +275,28 @@ impl<P: WalletPersister> PersistedWallet<P> {
         persister: &mut P,
         params: LoadParams,
     ) -> Result<Option<Self>, LoadWithPersistError<P::Error>> {
-        let changeset = P::initialize(persister).map_err(LoadWithPersistError::Persist)?;
+        let read_only = params.read_only;
+        let report = match params.on_corruption {
+            Some(_) => P::check_integrity(persister).map_err(LoadWithPersistError::Persist)?,
+            None => CorruptionReport::default(),
+        };
+        let mut changeset = if read_only {
+            P::load_read_only(persister)
+        } else {
+            P::initialize(persister)
+        }
+        .map_err(LoadWithPersistError::Persist)?;
+        let corruption_report = match params.on_corruption {
+            Some(on_corruption) => integrity::check_loaded(&mut changeset, report, on_corruption)
+                .map_err(LoadWithPersistError::Corrupted)?,
+            None => report,
+        };
         Wallet::load_with_params(changeset, params)
             .map(|opt| {
                 opt.map(|inner| PersistedWallet {
                     inner,
+                    read_only,
+                    corruption_report,
                     _marker: PhantomData,
                 })
             })
@@ -189,8 +307,15 @@ impl<P: WalletPersister> PersistedWallet<P> {
     ///
     /// Returns whether any new changes were persisted.
     ///
-    /// If the `persister` errors, the staged changes will not be cleared.
+    /// If the `persister` errors, the staged changes will not be cleared. See
+    /// [Persistence errors](Self#persistence-errors).
+    ///
+    /// A [read-only](Self#read-only-wallets) wallet is never persisted, and keeps its staged
+    /// changes.
     pub fn persist(&mut self, persister: &mut P) -> Result<bool, P::Error> {
+        if self.read_only {
+            return Ok(false);
+        }
         match self.inner.staged_mut() {
             Some(stage) => {
                 P::persist(persister, &*stage)?;
@@ -226,6 +351,8 @@ impl<P: AsyncWalletPersister> PersistedWallet<P> {
         }
         Ok(Self {
             inner,
+            read_only: false,
+            corruption_report: CorruptionReport::default(),
             _marker: PhantomData,
         })
     }
@@ -235,13 +362,30 @@ impl<P: AsyncWalletPersister> PersistedWallet<P> {
         persister: &mut P,
         params: LoadParams,
     ) -> Result<Option<Self>, LoadWithPersistError<P::Error>> {
-        let changeset = P::initialize(persister)
-            .await
-            .map_err(LoadWithPersistError::Persist)?;
+        let read_only = params.read_only;
+        let report = match params.on_corruption {
+            Some(_) => P::check_integrity(persister)
+                .await
+                .map_err(LoadWithPersistError::Persist)?,
+            None => CorruptionReport::default(),
+        };
+        let mut changeset = if read_only {
+            P::load_read_only(persister).await
+        } else {
+            P::initialize(persister).await
+        }
+        .map_err(LoadWithPersistError::Persist)?;
+        let corruption_report = match params.on_corruption {
+            Some(on_corruption) => integrity::check_loaded(&mut changeset, report, on_corruption)
+                .map_err(LoadWithPersistError::Corrupted)?,
+            None => report,
+        };
         Wallet::load_with_params(changeset, params)
             .map(|opt| {
                 opt.map(|inner| PersistedWallet {
                     inner,
+                    read_only,
+                    corruption_report,
                     _marker: PhantomData,
                 })
             })
@@ -252,8 +396,15 @@ impl<P: AsyncWalletPersister> PersistedWallet<P> {
     ///
     /// Returns whether any new changes were persisted.
     ///
-    /// If the `persister` errors, the staged changes will not be cleared.
+    /// If the `persister` errors, the staged changes will not be cleared. See
+    /// [Persistence errors](Self#persistence-errors).
+    ///
+    /// A [read-only](Self#read-only-wallets) wallet is never persisted, and keeps its staged
+    /// changes.
     pub async fn persist_async(&mut self, persister: &mut P) -> Result<bool, P::Error> {
+        if self.read_only {
+            return Ok(false);
+        }
         match self.inner.staged_mut() {
             Some(stage) => {
                 P::persist(persister, &*stage).await?;
@@ -265,6 +416,76 @@ impl<P: AsyncWalletPersister> PersistedWallet<P> {
     }
 }
 
+/// Error of the SQLite wallet tables.
+///
+/// The [`WalletPersister`] implementations of [`rusqlite`](bdk_chain::rusqlite) return it inside
+/// a [`ToSqlConversionFailure`](bdk_chain::rusqlite::Error::ToSqlConversionFailure) unless it is a
+/// [`SqliteError::Sqlite`].
+#[cfg(feature = "rusqlite")]
+#[derive(Debug)]
+pub enum SqliteError {
+    /// A query failed.
+    Sqlite(bdk_chain::rusqlite::Error),
+    /// The wallet tables were created by a newer version of the library, with this schema
+    /// version.
+    NewerSchema(u32),
+    /// The wallet tables have this older schema version and must be upgraded, which is not
+    /// possible when they are loaded read-only.
+    UpgradeRequired(u32),
+    /// A stored changeset could not be upgraded to the [`ChangeSet::CURRENT_VERSION`].
+    Migration(ChangeSetMigrationError),
+}
+
+#[cfg(feature = "rusqlite")]
+impl fmt::Display for SqliteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sqlite(e) => fmt::Display::fmt(e, f),
+            Self::NewerSchema(version) => write!(
+                f,
+                "The database is from a newer bdk_wallet: schema version {version} of the wallet \
+                 tables is not supported"
+            ),
+            Self::UpgradeRequired(version) => write!(
+                f,
+                "The wallet tables have schema version {version} and must be upgraded by opening \
+                 the database for writing"
+            ),
+            Self::Migration(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl error::Error for SqliteError {}
+
+#[cfg(feature = "rusqlite")]
+impl From<bdk_chain::rusqlite::Error> for SqliteError {
+    fn from(e: bdk_chain::rusqlite::Error) -> Self {
+        Self::Sqlite(e)
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl From<ChangeSetMigrationError> for SqliteError {
+    fn from(e: ChangeSetMigrationError) -> Self {
+        Self::Migration(e)
+    }
+}
+
+/// The errors other than [`SqliteError::Sqlite`] are returned as a
+/// [`ToSqlConversionFailure`](bdk_chain::rusqlite::Error::ToSqlConversionFailure), from which
+/// they can be downcast.
+#[cfg(feature = "rusqlite")]
+impl From<SqliteError> for bdk_chain::rusqlite::Error {
+    fn from(e: SqliteError) -> Self {
+        match e {
+            SqliteError::Sqlite(e) => e,
+            e => Self::ToSqlConversionFailure(Box::new(e)),
+        }
+    }
+}
+
 #[cfg(feature = "rusqlite")]
 impl WalletPersister for bdk_chain::rusqlite::Transaction<'_> {
     type Error = bdk_chain::rusqlite::Error;
@@ -274,6 +495,15 @@ impl WalletPersister for bdk_chain::rusqlite::Transaction<'_> {
         ChangeSet::from_sqlite(persister)
     }
 
+    fn load_read_only(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        Ok(ChangeSet::from_sqlite_read_only(persister)?)
+    }
+
+    fn check_integrity(persister: &mut Self) -> Result<CorruptionReport, Self::Error> {
+        let corruptions = integrity::check_sqlite(persister)?;
+        Ok(CorruptionReport { corruptions })
+    }
+
     fn persist(persister: &mut Self, changeset: &ChangeSet) -> Result<(), Self::Error> {
         changeset.persist_to_sqlite(persister)
     }
@@ -291,6 +521,18 @@ impl WalletPersister for bdk_chain::rusqlite::Connection {
         Ok(changeset)
     }
 
+    fn load_read_only(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        let db_tx = persister.transaction()?;
+        let changeset = ChangeSet::from_sqlite_read_only(&db_tx)?;
+        db_tx.commit()?;
+        Ok(changeset)
+    }
+
+    fn check_integrity(persister: &mut Self) -> Result<CorruptionReport, Self::Error> {
+        let corruptions = integrity::check_sqlite(persister)?;
+        Ok(CorruptionReport { corruptions })
+    }
+
     fn persist(persister: &mut Self, changeset: &ChangeSet) -> Result<(), Self::Error> {
         let mut db_tx = persister.transaction()?;
         <bdk_chain::rusqlite::Transaction<'_> as WalletPersister>::persist(&mut db_tx, changeset)?;
@@ -298,6 +540,88 @@ impl WalletPersister for bdk_chain::rusqlite::Connection {
     }
 }
 
+/// Persist a wallet to SQLite without blocking the async runtime.
+///
+/// The queries run on the background thread of the [`tokio_rusqlite::Connection`], with the same
+/// schema as the blocking [`rusqlite`](bdk_chain::rusqlite) backend, so a database can be opened
+/// with either of them. All the clones of a connection share its thread, which runs their calls
+/// one at a time and in order, so the changesets of a wallet are written in the order they were
+/// persisted.
+///
+/// ```rust,no_run
+/// # use bdk_wallet::{KeychainKind, PersistedWallet, Wallet};
+/// # use bdk_wallet::bitcoin::Network;
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> anyhow::Result<()> {
+/// # let (external, internal) = ("wpkh(tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq/0/*)", "wpkh(tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq/1/*)");
+/// let mut conn = bdk_wallet::tokio_rusqlite::Connection::open("wallet.sqlite").await?;
+/// let mut wallet = match Wallet::load().load_wallet_async(&mut conn).await? {
+///     Some(wallet) => wallet,
+///     None => {
+///         Wallet::create(external, internal)
+///             .network(Network::Testnet)
+///             .create_wallet_async(&mut conn)
+///             .await?
+///     }
+/// };
+/// wallet.reveal_next_address(KeychainKind::External);
+/// wallet.persist_async(&mut conn).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "async-sqlite")]
+impl AsyncWalletPersister for tokio_rusqlite::Connection {
+    type Error = tokio_rusqlite::Error;
+
+    fn initialize<'a>(persister: &'a mut Self) -> FutureResult<'a, ChangeSet, Self::Error>
+    where
+        Self: 'a,
+    {
+        Box::pin(persister.call(|conn| {
+            let changeset = <bdk_chain::rusqlite::Connection as WalletPersister>::initialize(conn)?;
+            Ok(changeset)
+        }))
+    }
+
+    fn load_read_only<'a>(persister: &'a mut Self) -> FutureResult<'a, ChangeSet, Self::Error>
+    where
+        Self: 'a,
+    {
+        Box::pin(persister.call(|conn| {
+            let changeset =
+                <bdk_chain::rusqlite::Connection as WalletPersister>::load_read_only(conn)?;
+            Ok(changeset)
+        }))
+    }
+
+    fn check_integrity<'a>(
+        persister: &'a mut Self,
+    ) -> FutureResult<'a, CorruptionReport, Self::Error>
+    where
+        Self: 'a,
+    {
+        Box::pin(persister.call(|conn| {
+            let report =
+                <bdk_chain::rusqlite::Connection as WalletPersister>::check_integrity(conn)?;
+            Ok(report)
+        }))
+    }
+
+    fn persist<'a>(
+        persister: &'a mut Self,
+        changeset: &'a ChangeSet,
+    ) -> FutureResult<'a, (), Self::Error>
+    where
+        Self: 'a,
+    {
+        let changeset = changeset.clone();
+        Box::pin(persister.call(move |conn| {
+            <bdk_chain::rusqlite::Connection as WalletPersister>::persist(conn, &changeset)?;
+            Ok(())
+        }))
+    }
+}
+
 /// Error for [`bdk_file_store`]'s implementation of [`WalletPersister`].
 #[cfg(feature = "file_store")]
 #[derive(Debug)]
@@ -306,6 +630,12 @@ pub enum FileStoreError {
     Load(bdk_file_store::StoreErrorWithDump<ChangeSet>),
     /// Error when writing to the store.
     Write(std::io::Error),
+    /// A changeset of the store could not be upgraded to the [`ChangeSet::CURRENT_VERSION`].
+    Migration(ChangeSetMigrationError),
+    /// The `Store<ChangeSet>` doesn't record its changeset version, it was written by an older
+    /// version of the library and must be read with
+    /// [`migration::load_file_store`](crate::migration::load_file_store).
+    Unversioned,
 }
 
 #[cfg(feature = "file_store")]
@@ -315,6 +645,12 @@ impl core::fmt::Display for FileStoreError {
         match self {
             FileStoreError::Load(e) => Display::fmt(e, f),
             FileStoreError::Write(e) => Display::fmt(e, f),
+            FileStoreError::Migration(e) => Display::fmt(e, f),
+            FileStoreError::Unversioned => write!(
+                f,
+                "The store was written by an older bdk_wallet without a changeset version, read \
+                 it with `migration::load_file_store` and write it to a new store"
+            ),
         }
     }
 }
@@ -322,15 +658,40 @@ impl core::fmt::Display for FileStoreError {
 #[cfg(feature = "file_store")]
 impl error::Error for FileStoreError {}
 
+/// Metadata key of the [`ChangeSet::CURRENT_VERSION`] of a `bdk_file_store::Store<ChangeSet>`.
+#[cfg(feature = "file_store")]
+pub(crate) const FILE_STORE_VERSION_KEY: &str = "bdk/file_store_version";
+
+/// The entries of a `bdk_file_store::Store<ChangeSet>` are `bincode` encoded without a version,
+/// so the first entry of a store created by this implementation only records the
+/// [`ChangeSet::CURRENT_VERSION`], in the metadata of the changeset. Loading fails with
+/// [`FileStoreError::Unversioned`] if a store doesn't start with it, i.e. if it was written by an
+/// older version of the library, whose entries may decode as garbage. Those stores are read with
+/// [`migration::load_file_store`](crate::migration::load_file_store) instead.
 #[cfg(feature = "file_store")]
 impl WalletPersister for bdk_file_store::Store<ChangeSet> {
     type Error = FileStoreError;
 
     fn initialize(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
-        persister
-            .dump()
-            .map(Option::unwrap_or_default)
-            .map_err(FileStoreError::Load)
+        match persister.dump().map_err(FileStoreError::Load)? {
+            Some(changeset) => file_store_changeset(changeset),
+            None => {
+                let mut version = ChangeSet::default();
+                version.metadata.entries.insert(
+                    FILE_STORE_VERSION_KEY.into(),
+                    Some(ChangeSet::CURRENT_VERSION.to_le_bytes().to_vec()),
+                );
+                persister.append(&version).map_err(FileStoreError::Write)?;
+                Ok(ChangeSet::default())
+            }
+        }
+    }
+
+    fn load_read_only(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        match persister.dump().map_err(FileStoreError::Load)? {
+            Some(changeset) => file_store_changeset(changeset),
+            None => Ok(ChangeSet::default()),
+        }
     }
 
     fn persist(persister: &mut Self, changeset: &ChangeSet) -> Result<(), Self::Error> {
@@ -338,6 +699,121 @@ impl WalletPersister for bdk_file_store::Store<ChangeSet> {
     }
 }
 
+/// Check the version recorded in the aggregate `changeset` of a `Store<ChangeSet>`, and remove it.
+#[cfg(feature = "file_store")]
+fn file_store_changeset(mut changeset: ChangeSet) -> Result<ChangeSet, FileStoreError> {
+    let version = changeset
+        .metadata
+        .entries
+        .remove(FILE_STORE_VERSION_KEY)
+        .flatten()
+        .ok_or(FileStoreError::Unversioned)?;
+    let version = <[u8; 4]>::try_from(version.as_slice())
+        .map(u32::from_le_bytes)
+        .map_err(|_| FileStoreError::Unversioned)?;
+    crate::migration::check_changeset_version(version).map_err(FileStoreError::Migration)?;
+    Ok(changeset)
+}
+
+/// A changeset of [`VersionedChangeSets`], with the version it was written with.
+#[cfg(feature = "file_store")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VersionedChangeSet {
+    version: u32,
+    /// The changeset as JSON, which can be upgraded by the
+    /// [`Migration`](crate::migration::Migration)s, unlike its `bincode` encoding.
+    changeset: alloc::string::String,
+}
+
+/// The entries of a [`bdk_file_store::Store`] recording the [`ChangeSet::CURRENT_VERSION`] of
+/// their changesets.
+///
+/// Unlike the entries of a `Store<ChangeSet>`, the ones written by an older version of the
+/// library are upgraded with the [`migration::MIGRATIONS`](crate::migration::MIGRATIONS) when the
+/// store is loaded, and the ones written by a newer version are rejected with
+/// [`FileStoreError::Migration`]. The stores written by the released versions, which are not
+/// versioned, are read with [`migration::load_file_store`](crate::migration::load_file_store).
+///
+/// ```rust,no_run
+/// # use bdk_wallet::{KeychainKind, VersionedChangeSets, Wallet};
+/// # use bdk_wallet::file_store::Store;
+/// # let (external_desc, internal_desc) = ("", "");
+/// const MAGIC: &[u8] = b"my_wallet";
+///
+/// let (mut store, _) = Store::<VersionedChangeSets>::load_or_create(MAGIC, "wallet.db")?;
+/// let mut wallet = match Wallet::load().load_wallet(&mut store)? {
+///     Some(wallet) => wallet,
+///     None => Wallet::create(external_desc, internal_desc).create_wallet(&mut store)?,
+/// };
+/// wallet.reveal_next_address(KeychainKind::External);
+/// wallet.persist(&mut store)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[cfg(feature = "file_store")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VersionedChangeSets(alloc::vec::Vec<VersionedChangeSet>);
+
+#[cfg(feature = "file_store")]
+impl Merge for VersionedChangeSets {
+    fn merge(&mut self, other: Self) {
+        self.0.extend(other.0)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(feature = "file_store")]
+impl VersionedChangeSets {
+    /// A single entry holding `changeset`, of the [`ChangeSet::CURRENT_VERSION`].
+    fn new(changeset: &ChangeSet) -> Self {
+        Self(alloc::vec![VersionedChangeSet {
+            version: ChangeSet::CURRENT_VERSION,
+            changeset: serde_json::to_string(changeset).expect("changeset must serialize to JSON"),
+        }])
+    }
+
+    /// Upgrade the changesets to the [`ChangeSet::CURRENT_VERSION`] and aggregate them.
+    fn upgrade(self) -> Result<ChangeSet, ChangeSetMigrationError> {
+        let mut aggregate = ChangeSet::default();
+        for entry in self.0 {
+            let value =
+                serde_json::from_str(&entry.changeset).map_err(ChangeSetMigrationError::Json)?;
+            aggregate.merge(crate::migration::upgrade_changeset(value, entry.version)?);
+        }
+        Ok(aggregate)
+    }
+}
+
+#[cfg(feature = "file_store")]
+impl WalletPersister for bdk_file_store::Store<VersionedChangeSets> {
+    type Error = FileStoreError;
+
+    fn initialize(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        let entries = persister.dump().map_err(|err| {
+            // upgrade the entries read before the one that could not be
+            match err.changeset.map(|entries| entries.upgrade()).transpose() {
+                Ok(changeset) => FileStoreError::Load(bdk_file_store::StoreErrorWithDump {
+                    changeset: changeset.map(Box::new),
+                    error: err.error,
+                }),
+                Err(e) => FileStoreError::Migration(e),
+            }
+        })?;
+        entries
+            .unwrap_or_default()
+            .upgrade()
+            .map_err(FileStoreError::Migration)
+    }
+
+    fn persist(persister: &mut Self, changeset: &ChangeSet) -> Result<(), Self::Error> {
+        persister
+            .append(&VersionedChangeSets::new(changeset))
+            .map_err(FileStoreError::Write)
+    }
+}
+
 /// Error type for [`PersistedWallet::load`].
 #[derive(Debug, PartialEq)]
 pub enum LoadWithPersistError<E> {
@@ -345,6 +821,8 @@ pub enum LoadWithPersistError<E> {
     Persist(E),
     /// Occurs when the loaded changeset cannot construct [`Wallet`].
     InvalidChangeSet(LoadError),
+    /// The persisted data is corrupted, see [`LoadParams::on_corruption`].
+    Corrupted(CorruptionReport),
 }
 
 impl<E: fmt::Display> fmt::Display for LoadWithPersistError<E> {
@@ -352,6 +830,7 @@ impl<E: fmt::Display> fmt::Display for LoadWithPersistError<E> {
         match self {
             Self::Persist(err) => fmt::Display::fmt(err, f),
             Self::InvalidChangeSet(err) => fmt::Display::fmt(&err, f),
+            Self::Corrupted(report) => fmt::Display::fmt(report, f),
         }
     }
 }
@@ -0,0 +1,273 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Persistence of wallets in a PostgreSQL database.
+//!
+//! [`PostgresStore`] implements [`AsyncWalletPersister`] over a [`deadpool_postgres`] connection
+//! pool, storing the wallet under an id of the caller's choosing so that many wallets can share a
+//! database:
+//!
+//! ```rust,no_run
+//! # use bdk_wallet::postgres::{deadpool_postgres, PostgresStore};
+//! # use bdk_wallet::{KeychainKind, Wallet};
+//! # use std::str::FromStr;
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> anyhow::Result<()> {
+//! # let (external_desc, internal_desc) = ("", "");
+//! let config = deadpool_postgres::tokio_postgres::Config::from_str("postgres://localhost/bdk")?;
+//! let manager = deadpool_postgres::Manager::new(config, deadpool_postgres::tokio_postgres::NoTls);
+//! let pool = deadpool_postgres::Pool::builder(manager).build()?;
+//!
+//! let mut store = PostgresStore::new(pool, "user-42");
+//! let mut wallet = match Wallet::load().load_wallet_async(&mut store).await? {
+//!     Some(wallet) => wallet,
+//!     None => {
+//!         Wallet::create(external_desc, internal_desc)
+//!             .create_wallet_async(&mut store)
+//!             .await?
+//!     }
+//! };
+//! wallet.reveal_next_address(KeychainKind::External);
+//! wallet.persist_async(&mut store).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The changesets are stored as JSON rows of a `bdk_wallet_changesets` table with a wallet id
+//! column, the same layout as the `sqlite_wallets` module. Each changeset is written in its own
+//! database transaction with the [`ChangeSet::CURRENT_VERSION`], and the rows of a wallet are
+//! upgraded with the [`migration::MIGRATIONS`] when it is loaded. Once a wallet has
+//! [`COMPACTION_THRESHOLD`] rows, they are merged into one in the same transaction.
+//!
+//! If the connection is lost during [`persist`](AsyncWalletPersister::persist), the changeset
+//! may or may not have been committed, along with the merge of the rows, and the error is
+//! returned with the changes still staged in the wallet. Persisting them again is safe: merging a
+//! changeset twice has the same result as merging it once.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use bdk_chain::Merge;
+use deadpool_postgres::tokio_postgres;
+use deadpool_postgres::{Pool, PoolError};
+
+use crate::migration::{self, ChangeSetMigrationError};
+use crate::{AsyncWalletPersister, ChangeSet, FutureResult};
+
+pub use deadpool_postgres;
+
+/// Number of rows of a wallet after which they are merged into one when it is loaded or persisted.
+pub const COMPACTION_THRESHOLD: usize = 100;
+
+/// Name of the table storing the changesets of every wallet.
+const TABLE_NAME: &str = "bdk_wallet_changesets";
+
+/// Key of the advisory lock serializing the creation of the schema by concurrent clients.
+const SCHEMA_LOCK_KEY: i64 = 0x6264_6b5f_7761_6c6c;
+
+/// A wallet stored in a PostgreSQL database under an id.
+#[derive(Clone)]
+pub struct PostgresStore {
+    pool: Pool,
+    wallet_id: String,
+}
+
+impl core::fmt::Debug for PostgresStore {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PostgresStore")
+            .field("wallet_id", &self.wallet_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PostgresStore {
+    /// Create a store for the wallet with `wallet_id`, using connections of `pool`.
+    pub fn new(pool: Pool, wallet_id: impl Into<String>) -> Self {
+        Self {
+            pool,
+            wallet_id: wallet_id.into(),
+        }
+    }
+
+    /// The id of the wallet in the database.
+    pub fn wallet_id(&self) -> &str {
+        &self.wallet_id
+    }
+
+    async fn initialize(&self) -> Result<ChangeSet, PostgresError> {
+        let mut client = self.pool.get().await?;
+        let db_tx = client.transaction().await?;
+        db_tx
+            .execute("SELECT pg_advisory_xact_lock($1)", &[&SCHEMA_LOCK_KEY])
+            .await?;
+        db_tx
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {TABLE_NAME} ( \
+                    id BIGSERIAL PRIMARY KEY, \
+                    wallet_id TEXT NOT NULL, \
+                    changeset TEXT NOT NULL \
+                ); \
+                CREATE INDEX IF NOT EXISTS {TABLE_NAME}_wallet_id ON {TABLE_NAME}(wallet_id); \
+                ALTER TABLE {TABLE_NAME} ADD COLUMN IF NOT EXISTS version INTEGER;"
+            ))
+            .await?;
+        let aggregate = self.load(&db_tx).await?;
+        db_tx.commit().await?;
+        Ok(aggregate)
+    }
+
+    async fn persist(&self, changeset: &ChangeSet) -> Result<(), PostgresError> {
+        if changeset.is_empty() {
+            return Ok(());
+        }
+        let mut client = self.pool.get().await?;
+        let db_tx = client.transaction().await?;
+        self.insert(&db_tx, changeset).await?;
+        let count: i64 = db_tx
+            .query_one(
+                &format!("SELECT COUNT(*) FROM {TABLE_NAME} WHERE wallet_id=$1"),
+                &[&self.wallet_id],
+            )
+            .await?
+            .try_get(0)?;
+        if count >= COMPACTION_THRESHOLD as i64 {
+            self.load(&db_tx).await?;
+        }
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Load the aggregate changeset of the wallet, merging its rows into one if there are
+    /// [`COMPACTION_THRESHOLD`] of them.
+    async fn load(
+        &self,
+        db_tx: &tokio_postgres::Transaction<'_>,
+    ) -> Result<ChangeSet, PostgresError> {
+        let rows = db_tx
+            .query(
+                &format!(
+                    "SELECT id, changeset, version FROM {TABLE_NAME} WHERE wallet_id=$1 ORDER BY id"
+                ),
+                &[&self.wallet_id],
+            )
+            .await?;
+        let mut aggregate = ChangeSet::default();
+        let mut ids = Vec::with_capacity(rows.len());
+        for row in rows {
+            ids.push(row.try_get::<_, i64>("id")?);
+            let json: String = row.try_get("changeset")?;
+            let version: Option<i32> = row.try_get("version")?;
+            let version = version.unwrap_or(0).try_into().unwrap_or(u32::MAX);
+            let changeset = migration::upgrade_changeset(serde_json::from_str(&json)?, version)?;
+            aggregate.merge(changeset);
+        }
+        if ids.len() >= COMPACTION_THRESHOLD {
+            // Only the rows that were read are deleted, not the ones committed since by
+            // concurrent transactions.
+            db_tx
+                .execute(
+                    &format!("DELETE FROM {TABLE_NAME} WHERE id = ANY($1)"),
+                    &[&ids],
+                )
+                .await?;
+            self.insert(db_tx, &aggregate).await?;
+        }
+        Ok(aggregate)
+    }
+
+    async fn insert(
+        &self,
+        db_tx: &tokio_postgres::Transaction<'_>,
+        changeset: &ChangeSet,
+    ) -> Result<(), PostgresError> {
+        let json = serde_json::to_string(changeset)?;
+        db_tx
+            .execute(
+                &format!(
+                    "INSERT INTO {TABLE_NAME}(wallet_id, changeset, version) VALUES($1, $2, $3)"
+                ),
+                &[&self.wallet_id, &json, &(ChangeSet::CURRENT_VERSION as i32)],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl AsyncWalletPersister for PostgresStore {
+    type Error = PostgresError;
+
+    fn initialize<'a>(persister: &'a mut Self) -> FutureResult<'a, ChangeSet, Self::Error>
+    where
+        Self: 'a,
+    {
+        Box::pin(PostgresStore::initialize(persister))
+    }
+
+    fn persist<'a>(
+        persister: &'a mut Self,
+        changeset: &'a ChangeSet,
+    ) -> FutureResult<'a, (), Self::Error>
+    where
+        Self: 'a,
+    {
+        Box::pin(PostgresStore::persist(persister, changeset))
+    }
+}
+
+/// Error for [`PostgresStore`].
+#[derive(Debug)]
+pub enum PostgresError {
+    /// No connection could be obtained from the pool.
+    Pool(PoolError),
+    /// A query failed, or the connection was lost.
+    Postgres(tokio_postgres::Error),
+    /// A changeset could not be serialized or deserialized.
+    Serde(serde_json::Error),
+    /// A stored changeset could not be upgraded to the current version.
+    Migration(ChangeSetMigrationError),
+}
+
+impl core::fmt::Display for PostgresError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Pool(e) => write!(f, "failed to get a database connection: {e}"),
+            Self::Postgres(e) => core::fmt::Display::fmt(e, f),
+            Self::Serde(e) => write!(f, "failed to encode or decode changeset: {e}"),
+            Self::Migration(e) => core::fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl std::error::Error for PostgresError {}
+
+impl From<PoolError> for PostgresError {
+    fn from(e: PoolError) -> Self {
+        Self::Pool(e)
+    }
+}
+
+impl From<tokio_postgres::Error> for PostgresError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        Self::Postgres(e)
+    }
+}
+
+impl From<ChangeSetMigrationError> for PostgresError {
+    fn from(e: ChangeSetMigrationError) -> Self {
+        Self::Migration(e)
+    }
+}
+
+impl From<serde_json::Error> for PostgresError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serde(e)
+    }
+}
@@ -0,0 +1,169 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Resumable full scans.
+
+use core::ops::Range;
+
+use chain::spk_client::FullScanResponse;
+use serde::{Deserialize, Serialize};
+
+use crate::collections::BTreeMap;
+use crate::KeychainKind;
+
+/// Progress of a full scan done in batches of script pubkeys.
+///
+/// A cursor is created with [`Wallet::scan_cursor`], and each batch is requested with
+/// [`Wallet::start_full_scan_batch`]. After applying the response of a batch to the wallet, the
+/// cursor is moved forward with [`ScanCursor::advance`]. Persisting the cursor together with the
+/// wallet allows resuming an interrupted scan without scanning the covered indices again.
+///
+/// The stop gap is applied by the cursor across the whole scan, relative to the highest active
+/// index found so far, so a scan resumed any number of times ends where an uninterrupted scan
+/// with the same stop gap would. Chain sources must scan every script pubkey of a batch, which
+/// is the case if they are given a stop gap of at least [`ScanCursor::batch_size`].
+///
+/// ```rust,no_run
+/// # use bdk_wallet::{ScanCursor, Wallet};
+/// # use bdk_wallet::chain::spk_client::{FullScanRequest, FullScanResponse};
+/// # use bdk_wallet::KeychainKind;
+/// # fn full_scan(
+/// #     _: FullScanRequest<KeychainKind>,
+/// #     _: usize,
+/// # ) -> anyhow::Result<FullScanResponse<KeychainKind>> {
+/// #     todo!()
+/// # }
+/// # fn save(_: &ScanCursor) {}
+/// # let mut wallet: Wallet = todo!();
+/// let mut cursor = wallet.scan_cursor(200, 50);
+/// while !cursor.is_finished() {
+///     let request = wallet.start_full_scan_batch(&cursor);
+///     let response = full_scan(request.build(), cursor.batch_size() as usize)?;
+///     cursor.advance(&response);
+///     wallet.apply_update(response)?;
+///     // persist the wallet, then the cursor
+///     save(&cursor);
+/// }
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+///
+/// [`Wallet::scan_cursor`]: crate::Wallet::scan_cursor
+/// [`Wallet::start_full_scan_batch`]: crate::Wallet::start_full_scan_batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanCursor {
+    stop_gap: u32,
+    batch_size: u32,
+    keychains: BTreeMap<KeychainKind, KeychainCursor>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct KeychainCursor {
+    /// First index not scanned yet
+    next_index: u32,
+    /// End of the derivation range of the keychain (exclusive)
+    end: u32,
+    /// Highest index found with a transaction
+    last_active: Option<u32>,
+}
+
+impl KeychainCursor {
+    /// End of the range scanned before the stop gap is reached (exclusive)
+    fn gap_end(&self, stop_gap: u32) -> u32 {
+        self.last_active
+            .map_or(0, |index| index.saturating_add(1))
+            .saturating_add(stop_gap)
+            .min(self.end)
+    }
+}
+
+impl ScanCursor {
+    /// Create a cursor at the start of the keychains, each given with the end of its derivation
+    /// range.
+    pub(crate) fn new(
+        stop_gap: u32,
+        batch_size: u32,
+        keychains: impl IntoIterator<Item = (KeychainKind, u32)>,
+    ) -> Self {
+        assert!(stop_gap > 0, "the stop gap must be greater than zero");
+        assert!(batch_size > 0, "the batch size must be greater than zero");
+        let keychains = keychains
+            .into_iter()
+            .map(|(keychain, end)| {
+                let cursor = KeychainCursor {
+                    next_index: 0,
+                    end,
+                    last_active: None,
+                };
+                (keychain, cursor)
+            })
+            .collect();
+        Self {
+            stop_gap,
+            batch_size,
+            keychains,
+        }
+    }
+
+    /// Number of consecutive unused script pubkeys after which a keychain is done.
+    pub fn stop_gap(&self) -> u32 {
+        self.stop_gap
+    }
+
+    /// Maximum number of script pubkeys of a keychain requested in a batch.
+    pub fn batch_size(&self) -> u32 {
+        self.batch_size
+    }
+
+    /// First index of `keychain` that hasn't been scanned yet, or `None` if the cursor doesn't
+    /// track `keychain`.
+    pub fn next_index(&self, keychain: KeychainKind) -> Option<u32> {
+        self.keychains
+            .get(&keychain)
+            .map(|cursor| cursor.next_index)
+    }
+
+    /// Highest index of `keychain` found with a transaction so far.
+    pub fn last_active(&self, keychain: KeychainKind) -> Option<u32> {
+        self.keychains.get(&keychain)?.last_active
+    }
+
+    /// Whether every keychain has been scanned up to its stop gap.
+    pub fn is_finished(&self) -> bool {
+        self.pending_ranges().next().is_none()
+    }
+
+    /// Ranges of indices to request in the next batch, for every keychain not done yet.
+    pub(crate) fn pending_ranges(&self) -> impl Iterator<Item = (KeychainKind, Range<u32>)> + '_ {
+        self.keychains.iter().filter_map(|(&keychain, cursor)| {
+            let end = cursor
+                .gap_end(self.stop_gap)
+                .min(cursor.next_index.saturating_add(self.batch_size));
+            (cursor.next_index < end).then_some((keychain, cursor.next_index..end))
+        })
+    }
+
+    /// Move the cursor past the batch requested with the current cursor, recording the active
+    /// indices found in `response`.
+    pub fn advance(&mut self, response: &FullScanResponse<KeychainKind>) {
+        let ranges = self.pending_ranges().collect::<BTreeMap<_, _>>();
+        for (keychain, range) in ranges {
+            let cursor = self
+                .keychains
+                .get_mut(&keychain)
+                .expect("range of a tracked keychain");
+            if let Some(&index) = response.last_active_indices.get(&keychain) {
+                if range.contains(&index) {
+                    cursor.last_active = cursor.last_active.max(Some(index));
+                }
+            }
+            cursor.next_index = range.end;
+        }
+    }
+}
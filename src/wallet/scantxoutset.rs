@@ -0,0 +1,108 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Import of a Bitcoin Core `scantxoutset` result
+//!
+//! Bitcoin Core's `scantxoutset` RPC lists the unspent outputs of the UTXO set matching a set of
+//! descriptors, which is much faster than scanning the blocks of the chain. The result can be
+//! deserialized into a [`ScanTxOutSetResult`] and applied with
+//! [`Wallet::apply_scantxoutset`](crate::Wallet::apply_scantxoutset).
+//!
+//! The result doesn't contain the transactions creating the outputs, which the wallet needs to
+//! track them. They can be fetched with `getrawtransaction <txid> true <blockhash>`, which also
+//! returns the time of the block and doesn't require a transaction index.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use bitcoin::{Amount, BlockHash, OutPoint, ScriptBuf, Txid};
+use chain::local_chain::CannotConnectError;
+use serde::{Deserialize, Serialize};
+
+/// The result of a `scantxoutset start` call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanTxOutSetResult {
+    /// Height of the block the UTXO set was scanned at
+    pub height: u32,
+    /// Hash of the block the UTXO set was scanned at
+    #[serde(rename = "bestblock")]
+    pub best_block: BlockHash,
+    /// The unspent outputs found
+    pub unspents: Vec<ScanTxOutSetUnspent>,
+}
+
+/// An unspent output of a [`ScanTxOutSetResult`].
+///
+/// The `blockhash` of the outputs is only returned by Bitcoin Core 25.0 and later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanTxOutSetUnspent {
+    /// Id of the transaction creating the output
+    pub txid: Txid,
+    /// Index of the output in the transaction
+    pub vout: u32,
+    /// Script pubkey of the output
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: ScriptBuf,
+    /// Value of the output
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub amount: Amount,
+    /// Height of the block confirming the transaction
+    pub height: u32,
+    /// Hash of the block confirming the transaction
+    #[serde(rename = "blockhash")]
+    pub block_hash: BlockHash,
+}
+
+impl ScanTxOutSetUnspent {
+    /// The outpoint of the output.
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint::new(self.txid, self.vout)
+    }
+}
+
+/// Errors returned by [`Wallet::apply_scantxoutset`](crate::Wallet::apply_scantxoutset).
+#[derive(Debug)]
+pub enum ScanTxOutSetError {
+    /// The transaction creating an output of the wallet wasn't provided
+    MissingTx(Txid),
+    /// The output of the provided transaction doesn't match the scan result
+    InconsistentTxOut(OutPoint),
+    /// The scan result has different blocks at the same height
+    ConflictingBlocks(u32),
+    /// The blocks of the scan result don't connect to the wallet's chain
+    CannotConnect(CannotConnectError),
+}
+
+impl fmt::Display for ScanTxOutSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTx(txid) => write!(f, "Transaction {txid} is missing"),
+            Self::InconsistentTxOut(outpoint) => write!(
+                f,
+                "Output {outpoint} of the transaction doesn't match the scan result"
+            ),
+            Self::ConflictingBlocks(height) => {
+                write!(
+                    f,
+                    "The scan result has conflicting blocks at height {height}"
+                )
+            }
+            Self::CannotConnect(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl core::error::Error for ScanTxOutSetError {}
+
+impl From<CannotConnectError> for ScanTxOutSetError {
+    fn from(err: CannotConnectError) -> Self {
+        Self::CannotConnect(err)
+    }
+}
@@ -0,0 +1,226 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! A [`PersistedWallet`] shared between threads.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use std::sync::{Mutex, PoisonError, RwLock, RwLockReadGuard};
+
+use bdk_chain::local_chain::CannotConnectError;
+use bdk_chain::Balance;
+use bitcoin::Psbt;
+
+use crate::signer::{SignOptions, SignerError};
+use crate::WalletPersister;
+use crate::{AddressInfo, KeychainKind, LocalOutput, PersistedWallet, Update, Wallet};
+
+/// A [`PersistedWallet`] and its persister, shared between threads.
+///
+/// Applications serving addresses, syncing and spending from different threads can clone a
+/// [`SharedWallet`] into each of them instead of wrapping the wallet and its persister in their
+/// own lock. Reading the wallet, e.g. with [`balance`] or [`read`], only takes a shared lock, so
+/// readers run concurrently. Changing the wallet, e.g. with [`apply_update_and_persist`] or
+/// [`update`], takes an exclusive lock and persists the staged changes before releasing it, so
+/// readers never see a change that wasn't persisted unless persisting failed, and the changes are
+/// persisted in the order they were made.
+///
+/// ```rust,no_run
+/// # use bdk_wallet::rusqlite::Connection;
+/// # use bdk_wallet::{KeychainKind, SharedWallet, Wallet};
+/// # let mut db = Connection::open("wallet.sqlite")?;
+/// # let wallet = Wallet::load().load_wallet(&mut db)?.expect("wallet is persisted");
+/// let wallet = SharedWallet::new(wallet, db);
+///
+/// let server = wallet.clone();
+/// std::thread::spawn(move || {
+///     let address = server.reveal_next_address_persisted(KeychainKind::External);
+///     // ...
+/// });
+///
+/// println!("balance: {}", wallet.balance().total());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// # Locking
+///
+/// The wallet is behind a [`RwLock`] and the persister behind a [`Mutex`], which is only locked
+/// while the wallet is locked exclusively, so the two locks are always taken in the same order.
+/// Readers wait while a change is persisted.
+///
+/// The closures given to [`read`] and [`update`] run with the wallet locked, and must not call
+/// methods of the same [`SharedWallet`], or any of its clones, which would deadlock.
+///
+/// # Poisoning
+///
+/// If a thread panics while the wallet is locked exclusively, e.g. in the closure given to
+/// [`update`], the wallet may be left half-changed. Its changes are then never persisted: all the
+/// methods changing the wallet fail with [`SharedWalletError::Poisoned`]. Reading the wallet is
+/// still possible, and the persisted wallet can be loaded again to recover.
+///
+/// [`balance`]: Self::balance
+/// [`read`]: Self::read
+/// [`update`]: Self::update
+/// [`apply_update_and_persist`]: Self::apply_update_and_persist
+pub struct SharedWallet<P: WalletPersister> {
+    inner: Arc<Shared<P>>,
+}
+
+struct Shared<P: WalletPersister> {
+    wallet: RwLock<PersistedWallet<P>>,
+    persister: Mutex<P>,
+}
+
+impl<P: WalletPersister> Clone for SharedWallet<P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<P> fmt::Debug for SharedWallet<P>
+where
+    P: WalletPersister + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedWallet")
+            .field("wallet", &self.inner.wallet)
+            .field("persister", &self.inner.persister)
+            .finish()
+    }
+}
+
+/// Error when changing a [`SharedWallet`].
+#[derive(Debug)]
+pub enum SharedWalletError<E> {
+    /// A thread panicked while changing the wallet, see [`SharedWallet#poisoning`].
+    Poisoned,
+    /// The change was made, but could not be persisted.
+    ///
+    /// The changes stay staged, and are persisted with the next change.
+    Persist(E),
+    /// The update does not connect to the chain of the wallet, nothing was changed.
+    CannotConnect(CannotConnectError),
+    /// Signing failed.
+    Signer(SignerError),
+}
+
+impl<E: fmt::Display> fmt::Display for SharedWalletError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Poisoned => write!(f, "a thread panicked while changing the shared wallet"),
+            Self::Persist(e) => write!(f, "failed to persist the shared wallet: {e}"),
+            Self::CannotConnect(e) => fmt::Display::fmt(e, f),
+            Self::Signer(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for SharedWalletError<E> {}
+
+impl<P: WalletPersister> SharedWallet<P> {
+    /// Share `wallet`, which is persisted into `persister`.
+    pub fn new(wallet: PersistedWallet<P>, persister: P) -> Self {
+        Self {
+            inner: Arc::new(Shared {
+                wallet: RwLock::new(wallet),
+                persister: Mutex::new(persister),
+            }),
+        }
+    }
+
+    /// Read the wallet with `f`, sharing the lock with other readers.
+    pub fn read<R>(&self, f: impl FnOnce(&Wallet) -> R) -> R {
+        f(&self.read_lock())
+    }
+
+    /// Change the wallet with `f`, and persist the staged changes.
+    ///
+    /// Returns the result of `f`. If persisting fails, the change is kept and
+    /// [`SharedWalletError::Persist`] is returned instead.
+    pub fn update<R>(
+        &self,
+        f: impl FnOnce(&mut Wallet) -> R,
+    ) -> Result<R, SharedWalletError<P::Error>> {
+        let mut wallet = self
+            .inner
+            .wallet
+            .write()
+            .map_err(|_| SharedWalletError::Poisoned)?;
+        let result = f(&mut wallet);
+        let mut persister = self
+            .inner
+            .persister
+            .lock()
+            .map_err(|_| SharedWalletError::Poisoned)?;
+        wallet
+            .persist(&mut persister)
+            .map_err(SharedWalletError::Persist)?;
+        Ok(result)
+    }
+
+    /// The balance of the wallet, see [`Wallet::balance`].
+    pub fn balance(&self) -> Balance {
+        self.read_lock().balance()
+    }
+
+    /// The unspent outputs of the wallet, see [`Wallet::list_unspent`].
+    pub fn list_unspent(&self) -> Vec<LocalOutput> {
+        self.read_lock().list_unspent().collect()
+    }
+
+    /// The address of `keychain` at `index`, see [`Wallet::peek_address`].
+    pub fn peek_address(&self, keychain: KeychainKind, index: u32) -> AddressInfo {
+        self.read_lock().peek_address(keychain, index)
+    }
+
+    /// Apply `update` to the wallet and persist it, see [`Wallet::apply_update`].
+    pub fn apply_update_and_persist(
+        &self,
+        update: impl Into<Update>,
+    ) -> Result<(), SharedWalletError<P::Error>> {
+        self.update(|wallet| wallet.apply_update(update))?
+            .map_err(SharedWalletError::CannotConnect)
+    }
+
+    /// Reveal the next address of `keychain` and persist it, see
+    /// [`Wallet::reveal_next_address`].
+    ///
+    /// The address is only returned once persisted, so that it is never given out twice.
+    pub fn reveal_next_address_persisted(
+        &self,
+        keychain: KeychainKind,
+    ) -> Result<AddressInfo, SharedWalletError<P::Error>> {
+        self.update(|wallet| wallet.reveal_next_address(keychain))
+    }
+
+    /// Sign `psbt` with the signers of the wallet, see [`Wallet::sign`].
+    ///
+    /// The wallet is locked exclusively while signing, so that no other change is made to it
+    /// meanwhile.
+    pub fn sign(
+        &self,
+        psbt: &mut Psbt,
+        sign_options: SignOptions,
+    ) -> Result<bool, SharedWalletError<P::Error>> {
+        self.update(|wallet| wallet.sign(psbt, sign_options))?
+            .map_err(SharedWalletError::Signer)
+    }
+
+    /// Lock the wallet for reading, even if it is poisoned.
+    fn read_lock(&self) -> RwLockReadGuard<'_, PersistedWallet<P>> {
+        self.inner
+            .wallet
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -90,19 +90,21 @@ use core::fmt;
 use core::ops::{Bound::Included, Deref};
 
 use bitcoin::bip32::{ChildNumber, DerivationPath, Fingerprint, Xpriv};
-use bitcoin::hashes::hash160;
+use bitcoin::hashes::{hash160, sha256, Hash};
 use bitcoin::psbt::PsbtSighashType;
+use bitcoin::script::Instruction;
 use bitcoin::secp256k1::Message;
 use bitcoin::sighash::{EcdsaSighashType, TapSighash, TapSighashType};
+use bitcoin::taproot::LeafVersion;
 use bitcoin::{ecdsa, psbt, sighash, taproot};
 use bitcoin::{key::TapTweak, key::XOnlyPublicKey, secp256k1};
-use bitcoin::{PrivateKey, Psbt, PublicKey};
+use bitcoin::{PrivateKey, Psbt, PublicKey, Script};
 
 use miniscript::descriptor::{
     Descriptor, DescriptorMultiXKey, DescriptorPublicKey, DescriptorSecretKey, DescriptorXKey,
     InnerXKey, KeyMap, SinglePriv, SinglePubKey,
 };
-use miniscript::{SigType, ToPublicKey};
+use miniscript::{MiniscriptKey, Preimage32, Satisfier, SigType, ToPublicKey};
 
 use super::utils::SecpCtx;
 use crate::descriptor::{DescriptorMeta, XKeyUtils};
@@ -508,45 +510,57 @@ impl InputSigner for SignerWrapper<PrivateKey> {
                             sighash,
                             sighash_type,
                             secp,
+                            sign_options.aux_rand.as_ref(),
                         );
                     }
                 }
 
-                if let Some((leaf_hashes, _)) =
-                    psbt.inputs[input_index].tap_key_origins.get(&x_only_pubkey)
-                {
-                    let leaf_hashes = leaf_hashes
-                        .iter()
-                        .filter(|lh| {
-                            // Removing the leaves we shouldn't sign for
-                            let should_sign = match &sign_options.tap_leaves_options {
-                                TapLeavesOptions::All => true,
-                                TapLeavesOptions::Include(v) => v.contains(lh),
-                                TapLeavesOptions::Exclude(v) => !v.contains(lh),
-                                TapLeavesOptions::None => false,
-                            };
-                            // Filtering out the leaves without our key
-                            should_sign
-                                && !psbt.inputs[input_index]
-                                    .tap_script_sigs
-                                    .contains_key(&(x_only_pubkey, **lh))
-                        })
-                        .cloned()
-                        .collect::<Vec<_>>();
-                    for lh in leaf_hashes {
-                        let (sighash, sighash_type) =
-                            compute_tap_sighash(psbt, input_index, Some(lh))?;
-                        sign_psbt_schnorr(
-                            &self.inner,
-                            x_only_pubkey,
-                            Some(lh),
-                            &mut psbt.inputs[input_index],
-                            sighash,
-                            sighash_type,
-                            secp,
-                        );
+                let mut leaf_hashes = psbt.inputs[input_index]
+                    .tap_key_origins
+                    .get(&x_only_pubkey)
+                    .map(|(leaf_hashes, _)| leaf_hashes.clone())
+                    .unwrap_or_default();
+                if sign_options.trust_tap_scripts {
+                    // Also look for our key in the leaf scripts of the PSBT, for inputs that come
+                    // without the `tap_key_origins` of the keys they use
+                    for (script, version) in psbt.inputs[input_index].tap_scripts.values() {
+                        let lh = taproot::TapLeafHash::from_script(script, *version);
+                        if *version == LeafVersion::TapScript
+                            && !leaf_hashes.contains(&lh)
+                            && tap_script_has_key(script, &x_only_pubkey)
+                        {
+                            leaf_hashes.push(lh);
+                        }
                     }
                 }
+
+                leaf_hashes.retain(|lh| {
+                    // Removing the leaves we shouldn't sign for
+                    let should_sign = match &sign_options.tap_leaves_options {
+                        TapLeavesOptions::All => true,
+                        TapLeavesOptions::Include(v) => v.contains(lh),
+                        TapLeavesOptions::Exclude(v) => !v.contains(lh),
+                        TapLeavesOptions::None => false,
+                    };
+                    // Filtering out the leaves without our key
+                    should_sign
+                        && !psbt.inputs[input_index]
+                            .tap_script_sigs
+                            .contains_key(&(x_only_pubkey, *lh))
+                });
+                for lh in leaf_hashes {
+                    let (sighash, sighash_type) = compute_tap_sighash(psbt, input_index, Some(lh))?;
+                    sign_psbt_schnorr(
+                        &self.inner,
+                        x_only_pubkey,
+                        Some(lh),
+                        &mut psbt.inputs[input_index],
+                        sighash,
+                        sighash_type,
+                        secp,
+                        sign_options.aux_rand.as_ref(),
+                    );
+                }
             }
             SignerContext::Segwitv0 | SignerContext::Legacy => {
                 if psbt.inputs[input_index].partial_sigs.contains_key(&pubkey) {
@@ -598,7 +612,16 @@ fn sign_psbt_ecdsa(
     psbt_input.partial_sigs.insert(pubkey, final_signature);
 }
 
+// Whether `x_only_pubkey` is pushed anywhere in the leaf `script`
+fn tap_script_has_key(script: &Script, x_only_pubkey: &XOnlyPublicKey) -> bool {
+    let serialized = x_only_pubkey.serialize();
+    script.instructions().any(|instruction| {
+        matches!(instruction, Ok(Instruction::PushBytes(bytes)) if bytes.as_bytes() == serialized)
+    })
+}
+
 // Calling this with `leaf_hash` = `None` will sign for key-spend
+#[allow(clippy::too_many_arguments)]
 fn sign_psbt_schnorr(
     secret_key: &secp256k1::SecretKey,
     pubkey: XOnlyPublicKey,
@@ -607,6 +630,7 @@ fn sign_psbt_schnorr(
     sighash: TapSighash,
     sighash_type: TapSighashType,
     secp: &SecpCtx,
+    aux_rand: Option<&[u8; 32]>,
 ) {
     let keypair = secp256k1::Keypair::from_seckey_slice(secp, secret_key.as_ref()).unwrap();
     let keypair = match leaf_hash {
@@ -617,7 +641,10 @@ fn sign_psbt_schnorr(
     };
 
     let msg = &Message::from(sighash);
-    let signature = secp.sign_schnorr_no_aux_rand(msg, &keypair);
+    let signature = match aux_rand {
+        Some(aux_rand) => secp.sign_schnorr_with_aux_rand(msg, &keypair, aux_rand),
+        None => secp.sign_schnorr_no_aux_rand(msg, &keypair),
+    };
     secp.verify_schnorr(&signature, msg, &XOnlyPublicKey::from_keypair(&keypair).0)
         .expect("invalid or corrupted schnorr signature");
 
@@ -640,6 +667,12 @@ fn sign_psbt_schnorr(
 /// The default value is `100`. Signers with an ordering above that will be called later,
 /// and they will thus see the partial signatures added to the transaction once they get to sign
 /// themselves.
+///
+/// A signer doesn't need to sign every input: it may sign only the inputs it can and leave the
+/// rest to the signers called after it. Once the PSBT can be finalized the remaining signers
+/// aren't called, so cheap signers (e.g. the software ones created from the wallet's keys) can
+/// be given a low ordering and expensive ones (e.g. a hardware device) a high ordering to only
+/// be asked for what's left.
 #[derive(Debug, Clone, PartialOrd, PartialEq, Ord, Eq)]
 pub struct SignerOrdering(pub usize);
 
@@ -765,6 +798,14 @@ impl SignersContainer {
         self.0.values().collect()
     }
 
+    /// Iterate over the signers in the container with their ordering, sorted by lowest to
+    /// highest `ordering`
+    pub(crate) fn iter_ordered(
+        &self,
+    ) -> impl Iterator<Item = (&SignerOrdering, &Arc<dyn TransactionSigner>)> {
+        self.0.iter().map(|(key, signer)| (&key.ordering, signer))
+    }
+
     /// Finds the signer with lowest ordering for a given id in the container.
     pub fn find(&self, id: SignerId) -> Option<&Arc<dyn TransactionSigner>> {
         self.0
@@ -832,6 +873,35 @@ pub struct SignOptions {
     /// or not.
     /// Defaults to `true`, i.e., we always grind ECDSA signature to sign with low r.
     pub allow_grinding: bool,
+
+    /// Whether the signer should trust the leaf scripts in the `tap_scripts` of a PSBT input
+    ///
+    /// Defaults to `false`, which will only sign for the leaves listed in the `tap_key_origins`
+    /// of our keys. PSBTs created by other software might only provide the leaf scripts and
+    /// their control blocks: setting this to `true` signs every leaf script containing one of
+    /// our single keys, still subject to [`tap_leaves_options`](Self::tap_leaves_options), and
+    /// lets [`Wallet::finalize_psbt`](crate::Wallet::finalize_psbt) assemble the witness from
+    /// `tap_scripts` for inputs that aren't spending from the wallet's descriptors.
+    pub trust_tap_scripts: bool,
+
+    /// Preimages of the `sha256()` hashlocks of the descriptors, used when finalizing.
+    ///
+    /// Defaults to none. Each entry maps a hash to its 32 byte preimage, which is added to the
+    /// witness of the inputs whose spending path needs it. Entries whose preimage doesn't hash to
+    /// the given hash, or that aren't 32 bytes long, are ignored.
+    pub preimages: Vec<(sha256::Hash, Vec<u8>)>,
+
+    /// Auxiliary randomness of the Schnorr signatures, as defined in [BIP340].
+    ///
+    /// Defaults to `None`, which signs without auxiliary randomness, making the signatures
+    /// deterministic. [`Wallet::sign`](crate::Wallet::sign) fills it in from the thread-local
+    /// random number generator, and [`Wallet::sign_with_aux_rand`](crate::Wallet::sign_with_aux_rand)
+    /// from a provided one. The same randomness can safely be used for all the signatures of a
+    /// PSBT. ECDSA signatures don't use it: their nonce is always derived as in [RFC6979].
+    ///
+    /// [BIP340]: https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki
+    /// [RFC6979]: https://www.rfc-editor.org/rfc/rfc6979
+    pub aux_rand: Option<[u8; 32]>,
 }
 
 /// Customize which taproot script-path leaves the signer should sign.
@@ -859,10 +929,27 @@ impl Default for SignOptions {
             tap_leaves_options: TapLeavesOptions::default(),
             sign_with_tap_internal_key: true,
             allow_grinding: true,
+            trust_tap_scripts: false,
+            preimages: Vec::new(),
+            aux_rand: None,
         }
     }
 }
 
+/// Satisfier providing the [`SignOptions::preimages`] that match their hash.
+pub(crate) struct PreimageSatisfier<'a>(pub(crate) &'a [(sha256::Hash, Vec<u8>)]);
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for PreimageSatisfier<'_> {
+    fn lookup_sha256(&self, h: &Pk::Sha256) -> Option<Preimage32> {
+        let hash = Pk::to_sha256(h);
+        self.0
+            .iter()
+            .filter(|(preimage_hash, _)| *preimage_hash == hash)
+            .filter_map(|(_, preimage)| Preimage32::try_from(preimage.as_slice()).ok())
+            .find(|preimage| sha256::Hash::hash(preimage) == hash)
+    }
+}
+
 /// Computes the taproot sighash.
 fn compute_tap_sighash(
     psbt: &Psbt,
@@ -0,0 +1,234 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Filtering of the transactions of a block relevant to a wallet.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use bitcoin::{block::Header, Block, OutPoint, ScriptBuf, Transaction, Txid};
+use chain::BlockId;
+
+use crate::collections::{HashMap, HashSet};
+
+/// A filter keeping the transactions of a block that may be relevant to a wallet.
+///
+/// Applying a whole block with [`Wallet::apply_block`] makes the wallet scan every transaction
+/// of the block. A block source can instead filter the block with
+/// [`filter_block`](Self::filter_block), e.g. on the thread fetching it, and only send the
+/// resulting [`FilteredBlock`] to be applied with [`Wallet::apply_filtered_block`].
+///
+/// The filter returned by [`Wallet::spk_filter`] matches the wallet's derived script pubkeys,
+/// i.e. the revealed ones and the lookahead, and the outpoints spent or owned by the wallet's
+/// transactions, to keep the spends of the wallet's outputs and their conflicts.
+///
+/// **WARNING**: The filter is a snapshot of the wallet. Script pubkeys derived afterwards, e.g.
+/// when the lookahead is replenished after a transaction paying to the lookahead window is
+/// applied, aren't matched. Create a new filter after applying a block with transactions of the
+/// wallet, and sync the script pubkeys revealed since the filter was created to find the
+/// transactions the filter may have missed.
+///
+/// [`Wallet::apply_block`]: crate::Wallet::apply_block
+/// [`Wallet::apply_filtered_block`]: crate::Wallet::apply_filtered_block
+/// [`Wallet::spk_filter`]: crate::Wallet::spk_filter
+#[derive(Debug, Clone, Default)]
+pub struct SpkFilter {
+    spks: HashSet<ScriptBuf>,
+    outpoints: HashSet<OutPoint>,
+}
+
+impl SpkFilter {
+    /// Create a filter matching the transactions paying to `spks`.
+    pub fn new(spks: impl IntoIterator<Item = ScriptBuf>) -> Self {
+        Self {
+            spks: spks.into_iter().collect(),
+            outpoints: HashSet::new(),
+        }
+    }
+
+    /// Also match the transactions paying to `spks`.
+    pub fn add_spks(&mut self, spks: impl IntoIterator<Item = ScriptBuf>) {
+        self.spks.extend(spks);
+    }
+
+    /// Also match the transactions spending `outpoints`.
+    pub fn add_outpoints(&mut self, outpoints: impl IntoIterator<Item = OutPoint>) {
+        self.outpoints.extend(outpoints);
+    }
+
+    /// Returns whether `tx` pays to a script pubkey or spends an outpoint of the filter.
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        tx.input
+            .iter()
+            .any(|txin| self.outpoints.contains(&txin.previous_output))
+            || tx
+                .output
+                .iter()
+                .any(|txout| self.spks.contains(&txout.script_pubkey))
+    }
+
+    /// Returns the txids of the transactions of `txs` matching the filter, with their index in
+    /// `txs`.
+    ///
+    /// Like [`filter_block`](Self::filter_block), the outputs of the matching transactions paying
+    /// to the filter's script pubkeys are added to the filter, until no more transactions match.
+    pub(crate) fn filter_txs(&mut self, txs: &[Arc<Transaction>]) -> HashMap<Txid, usize> {
+        let txids = txs.iter().map(|tx| tx.compute_txid()).collect::<Vec<_>>();
+        // the transactions spending each outpoint, to find the ones matching once it is added
+        let mut spends = HashMap::<OutPoint, Vec<usize>>::new();
+        for (i, tx) in txs.iter().enumerate() {
+            for txin in &tx.input {
+                spends.entry(txin.previous_output).or_default().push(i);
+            }
+        }
+
+        let mut matched = HashMap::new();
+        let mut pending = (0..txs.len())
+            .filter(|&i| self.matches(&txs[i]))
+            .collect::<Vec<_>>();
+        while let Some(i) = pending.pop() {
+            let (tx, txid) = (&txs[i], txids[i]);
+            if matched.insert(txid, i).is_some() {
+                continue;
+            }
+            for (vout, txout) in tx.output.iter().enumerate() {
+                if !self.spks.contains(&txout.script_pubkey) {
+                    continue;
+                }
+                let outpoint = OutPoint::new(txid, vout as u32);
+                self.outpoints.insert(outpoint);
+                if let Some(spending) = spends.get(&outpoint) {
+                    pending.extend(
+                        spending
+                            .iter()
+                            .filter(|&&j| !matched.contains_key(&txids[j])),
+                    );
+                }
+            }
+        }
+        matched
+    }
+
+    /// Add the outputs of `tx` paying to the filter's script pubkeys to its outpoints.
+    fn add_outputs(&mut self, txid: Txid, tx: &Transaction) {
+        for (vout, txout) in tx.output.iter().enumerate() {
+            if self.spks.contains(&txout.script_pubkey) {
+                self.outpoints.insert(OutPoint::new(txid, vout as u32));
+            }
+        }
+    }
+
+    /// Keep the transactions of `block` matching the filter.
+    ///
+    /// The outputs of the matching transactions paying to the filter's script pubkeys are added
+    /// to the filter, so that transactions spending them later in the block, or in the next
+    /// blocks, are kept as well.
+    pub fn filter_block(&mut self, block: &Block, height: u32) -> FilteredBlock {
+        let mut txs = Vec::new();
+        for tx in &block.txdata {
+            if !self.matches(tx) {
+                continue;
+            }
+            self.add_outputs(tx.compute_txid(), tx);
+            txs.push(tx.clone());
+        }
+        FilteredBlock {
+            header: block.header,
+            height,
+            txs,
+        }
+    }
+}
+
+/// The header of a block and its transactions kept by a [`SpkFilter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilteredBlock {
+    /// Header of the block
+    pub header: Header,
+    /// Height of the block
+    pub height: u32,
+    /// Transactions of the block matching the filter, in the order of the block
+    pub txs: Vec<Transaction>,
+}
+
+impl FilteredBlock {
+    /// The id of the block.
+    pub fn block_id(&self) -> BlockId {
+        BlockId {
+            height: self.height,
+            hash: self.header.block_hash(),
+        }
+    }
+
+    /// The block this block connects to, i.e. the previous block, or the block itself if it's
+    /// the genesis block.
+    pub fn connected_to(&self) -> BlockId {
+        match self.height.checked_sub(1) {
+            Some(prev_height) => BlockId {
+                height: prev_height,
+                hash: self.header.prev_blockhash,
+            },
+            None => self.block_id(),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    use bitcoin::hashes::Hash;
+    use bitcoin::{absolute, transaction, Amount, TxIn, TxOut};
+
+    fn tx(inputs: &[OutPoint], spk: &ScriptBuf) -> Arc<Transaction> {
+        Arc::new(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: inputs
+                .iter()
+                .map(|&previous_output| TxIn {
+                    previous_output,
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: spk.clone(),
+            }],
+        })
+    }
+
+    #[test]
+    fn test_filter_txs_follows_spends_in_any_order() {
+        let spk = ScriptBuf::from_bytes(vec![0x51]);
+        let other = ScriptBuf::from_bytes(vec![0x52]);
+        let funding = tx(&[OutPoint::new(Txid::from_byte_array([1; 32]), 0)], &spk);
+        let change = tx(&[OutPoint::new(funding.compute_txid(), 0)], &spk);
+        let spend = tx(&[OutPoint::new(change.compute_txid(), 0)], &other);
+        let unrelated = tx(&[OutPoint::new(spend.compute_txid(), 0)], &other);
+
+        // the spends are listed before the transactions they spend
+        let txs = [unrelated, spend.clone(), change.clone(), funding.clone()];
+        let mut filter = SpkFilter::new([spk]);
+        let matched = filter.filter_txs(&txs);
+        assert_eq!(
+            matched,
+            HashMap::from([
+                (funding.compute_txid(), 3),
+                (change.compute_txid(), 2),
+                (spend.compute_txid(), 1),
+            ])
+        );
+        assert!(filter
+            .outpoints
+            .contains(&OutPoint::new(change.compute_txid(), 0)));
+    }
+}
@@ -0,0 +1,309 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Settings of SQLite connections used to persist wallets, and batched writers of the
+//! `bdk_chain` tables.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use bdk_chain::rusqlite::types::ToSql;
+use bdk_chain::rusqlite::{self, params_from_iter, Connection};
+use bdk_chain::{keychain_txout, local_chain, tx_graph, ConfirmationBlockTime, Impl};
+use bitcoin::consensus::serialize;
+use bitcoin::hashes::Hash;
+use bitcoin::Txid;
+
+/// Pragmas applied to a SQLite connection to persist wallets faster.
+///
+/// Pragmas are settings of the connection rather than of the database, so they must be applied
+/// with [`apply`](Self::apply) every time the connection is opened, before initializing the
+/// wallet.
+///
+/// ```rust,no_run
+/// # use bdk_wallet::rusqlite::Connection;
+/// # use bdk_wallet::{SqlitePragmas, Wallet};
+/// let mut conn = Connection::open("wallet.sqlite")?;
+/// SqlitePragmas::fast().apply(&conn)?;
+/// let wallet = Wallet::load().load_wallet(&mut conn)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SqlitePragmas {
+    /// Use a write-ahead log (`journal_mode=WAL`), so that readers don't block the writer.
+    ///
+    /// The journal mode is persisted in the database file, and a database in WAL mode can't be
+    /// opened from a read-only location.
+    pub wal: bool,
+    /// Only sync the database to disk at critical moments (`synchronous=NORMAL`).
+    ///
+    /// With [`wal`](Self::wal), the last persisted changes may be lost on power failure, but the
+    /// database can't be corrupted.
+    pub synchronous_normal: bool,
+}
+
+impl SqlitePragmas {
+    /// Both [`wal`](Self::wal) and [`synchronous_normal`](Self::synchronous_normal).
+    pub fn fast() -> Self {
+        Self {
+            wal: true,
+            synchronous_normal: true,
+        }
+    }
+
+    /// Apply the pragmas to `conn`.
+    ///
+    /// This must not be called while a transaction of `conn` is open.
+    pub fn apply(&self, conn: &Connection) -> rusqlite::Result<()> {
+        if self.wal {
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        }
+        if self.synchronous_normal {
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+        }
+        Ok(())
+    }
+}
+
+/// Maximum number of rows inserted by a single statement.
+///
+/// This keeps the number of bound parameters far below the SQLite limit of 32766.
+const ROWS_PER_STATEMENT: usize = 256;
+
+/// Insert `rows` into a table with multi-row `INSERT` statements.
+///
+/// `insert` is the statement up to its `VALUES` clause, e.g. `INSERT INTO table(a, b)`, and
+/// `on_conflict` follows the values. `bind` binds the `columns` parameters of a row, starting at
+/// the given parameter index. Statements are cached, so that persisting again reuses them.
+fn insert_rows<R>(
+    db_tx: &rusqlite::Transaction,
+    insert: &str,
+    columns: usize,
+    on_conflict: &str,
+    rows: &[R],
+    bind: impl Fn(&mut rusqlite::Statement, usize, &R) -> rusqlite::Result<()>,
+) -> rusqlite::Result<()> {
+    let placeholders = format!("({})", vec!["?"; columns].join(", "));
+    for chunk in rows.chunks(ROWS_PER_STATEMENT) {
+        let values = vec![placeholders.as_str(); chunk.len()].join(", ");
+        let mut statement =
+            db_tx.prepare_cached(&format!("{insert} VALUES {values} {on_conflict}"))?;
+        for (i, row) in chunk.iter().enumerate() {
+            bind(&mut statement, i * columns + 1, row)?;
+        }
+        statement.raw_execute()?;
+    }
+    Ok(())
+}
+
+/// Delete the rows of `table` whose `column` is one of `keys`.
+fn delete_rows<K: ToSql>(
+    db_tx: &rusqlite::Transaction,
+    table: &str,
+    column: &str,
+    keys: &[K],
+) -> rusqlite::Result<()> {
+    for chunk in keys.chunks(ROWS_PER_STATEMENT) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        let mut statement = db_tx.prepare_cached(&format!(
+            "DELETE FROM {table} WHERE {column} IN ({placeholders})"
+        ))?;
+        statement.execute(params_from_iter(chunk))?;
+    }
+    Ok(())
+}
+
+/// Persist the blocks of a [`local_chain::ChangeSet`].
+///
+/// This writes the same rows as [`local_chain::ChangeSet::persist_to_sqlite`].
+pub(crate) fn persist_local_chain(
+    db_tx: &rusqlite::Transaction,
+    changeset: &local_chain::ChangeSet,
+) -> rusqlite::Result<()> {
+    let table = local_chain::ChangeSet::BLOCKS_TABLE_NAME;
+    let (inserted, deleted): (Vec<_>, Vec<_>) = changeset
+        .blocks
+        .iter()
+        .partition(|(_, hash)| hash.is_some());
+    let deleted = deleted
+        .into_iter()
+        .map(|(&height, _)| height)
+        .collect::<Vec<_>>();
+    delete_rows(db_tx, table, "block_height", &deleted)?;
+    insert_rows(
+        db_tx,
+        &format!("INSERT INTO {table}(block_height, block_hash)"),
+        2,
+        "ON CONFLICT(block_height) DO UPDATE SET block_hash=excluded.block_hash",
+        &inserted,
+        |statement, i, (&height, hash)| {
+            let hash = hash.expect("only inserted blocks");
+            statement.raw_bind_parameter(i, height)?;
+            statement.raw_bind_parameter(i + 1, Impl(hash))
+        },
+    )
+}
+
+/// A key ordering txids like their hex strings, the primary keys of the `bdk_chain` tables.
+///
+/// Inserting rows in the order of their primary key is much faster than in random order.
+fn hex_order(txid: &Txid) -> [u8; 32] {
+    let mut bytes = txid.to_byte_array();
+    bytes.reverse();
+    bytes
+}
+
+/// A row of the transactions table, with the columns to update.
+#[derive(Default)]
+struct TxRow {
+    raw_tx: Option<Vec<u8>>,
+    first_seen: Option<u64>,
+    last_seen: Option<u64>,
+    last_evicted: Option<u64>,
+}
+
+/// Persist a [`tx_graph::ChangeSet`].
+///
+/// This writes the same rows as [`tx_graph::ChangeSet::persist_to_sqlite`], but every column of a
+/// transaction is written by a single upsert, and rows are inserted in the order of their primary
+/// key.
+pub(crate) fn persist_tx_graph(
+    db_tx: &rusqlite::Transaction,
+    changeset: &tx_graph::ChangeSet<ConfirmationBlockTime>,
+) -> rusqlite::Result<()> {
+    type TxGraphChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+
+    let mut txs = BTreeMap::<Txid, TxRow>::new();
+    for tx in &changeset.txs {
+        txs.entry(tx.compute_txid()).or_default().raw_tx = Some(serialize(tx.as_ref()));
+    }
+    for (&txid, &first_seen) in &changeset.first_seen {
+        txs.entry(txid).or_default().first_seen = Some(first_seen);
+    }
+    for (&txid, &last_seen) in &changeset.last_seen {
+        txs.entry(txid).or_default().last_seen = Some(last_seen);
+    }
+    for (&txid, &last_evicted) in &changeset.last_evicted {
+        txs.entry(txid).or_default().last_evicted = Some(last_evicted);
+    }
+    // anchored transactions must have a row, even without any of its columns
+    for (_, txid) in &changeset.anchors {
+        txs.entry(*txid).or_default();
+    }
+    let mut txs = txs.into_iter().collect::<Vec<_>>();
+    txs.sort_unstable_by_key(|(txid, _)| hex_order(txid));
+    insert_rows(
+        db_tx,
+        &format!(
+            "INSERT INTO {}(txid, raw_tx, first_seen, last_seen, last_evicted)",
+            TxGraphChangeSet::TXS_TABLE_NAME
+        ),
+        5,
+        "ON CONFLICT(txid) DO UPDATE SET \
+            raw_tx=coalesce(excluded.raw_tx, raw_tx), \
+            first_seen=coalesce(excluded.first_seen, first_seen), \
+            last_seen=coalesce(excluded.last_seen, last_seen), \
+            last_evicted=coalesce(excluded.last_evicted, last_evicted)",
+        &txs,
+        |statement, i, (txid, row)| {
+            statement.raw_bind_parameter(i, Impl(*txid))?;
+            statement.raw_bind_parameter(i + 1, &row.raw_tx)?;
+            statement.raw_bind_parameter(i + 2, row.first_seen)?;
+            statement.raw_bind_parameter(i + 3, row.last_seen)?;
+            statement.raw_bind_parameter(i + 4, row.last_evicted)
+        },
+    )?;
+
+    let mut txouts = changeset.txouts.iter().collect::<Vec<_>>();
+    txouts.sort_unstable_by_key(|(outpoint, _)| (hex_order(&outpoint.txid), outpoint.vout));
+    insert_rows(
+        db_tx,
+        &format!(
+            "INSERT INTO {}(txid, vout, value, script)",
+            TxGraphChangeSet::TXOUTS_TABLE_NAME
+        ),
+        4,
+        "ON CONFLICT(txid, vout) DO UPDATE SET value=excluded.value, script=excluded.script",
+        &txouts,
+        |statement, i, (outpoint, txout)| {
+            statement.raw_bind_parameter(i, Impl(outpoint.txid))?;
+            statement.raw_bind_parameter(i + 1, outpoint.vout)?;
+            statement.raw_bind_parameter(i + 2, Impl(txout.value))?;
+            statement.raw_bind_parameter(i + 3, txout.script_pubkey.as_bytes())
+        },
+    )?;
+
+    let mut anchors = changeset.anchors.iter().collect::<Vec<_>>();
+    anchors.sort_unstable_by_key(|(anchor, txid)| (hex_order(txid), anchor.block_id.height));
+    insert_rows(
+        db_tx,
+        &format!(
+            "INSERT INTO {}(txid, block_height, block_hash, confirmation_time)",
+            TxGraphChangeSet::ANCHORS_TABLE_NAME
+        ),
+        4,
+        "ON CONFLICT(txid, block_height, block_hash) DO UPDATE SET \
+            confirmation_time=excluded.confirmation_time",
+        &anchors,
+        |statement, i, (anchor, txid)| {
+            statement.raw_bind_parameter(i, Impl(*txid))?;
+            statement.raw_bind_parameter(i + 1, anchor.block_id.height)?;
+            statement.raw_bind_parameter(i + 2, Impl(anchor.block_id.hash))?;
+            statement.raw_bind_parameter(i + 3, anchor.confirmation_time)
+        },
+    )
+}
+
+/// Persist a [`keychain_txout::ChangeSet`].
+///
+/// This writes the same rows as [`keychain_txout::ChangeSet::persist_to_sqlite`].
+pub(crate) fn persist_indexer(
+    db_tx: &rusqlite::Transaction,
+    changeset: &keychain_txout::ChangeSet,
+) -> rusqlite::Result<()> {
+    type IndexerChangeSet = keychain_txout::ChangeSet;
+
+    let last_revealed = changeset.last_revealed.iter().collect::<Vec<_>>();
+    insert_rows(
+        db_tx,
+        &format!(
+            "INSERT INTO {}(descriptor_id, last_revealed)",
+            IndexerChangeSet::LAST_REVEALED_TABLE_NAME
+        ),
+        2,
+        "ON CONFLICT(descriptor_id) DO UPDATE SET last_revealed=excluded.last_revealed",
+        &last_revealed,
+        |statement, i, (&did, &last_revealed)| {
+            statement.raw_bind_parameter(i, Impl(did))?;
+            statement.raw_bind_parameter(i + 1, last_revealed)
+        },
+    )?;
+
+    let spks = changeset
+        .spk_cache
+        .iter()
+        .flat_map(|(&did, spks)| spks.iter().map(move |(&index, spk)| (did, index, spk)))
+        .collect::<Vec<_>>();
+    insert_rows(
+        db_tx,
+        &format!(
+            "INSERT INTO {}(descriptor_id, spk_index, spk)",
+            IndexerChangeSet::DERIVED_SPKS_TABLE_NAME
+        ),
+        3,
+        "ON CONFLICT(descriptor_id, spk_index) DO UPDATE SET spk=excluded.spk",
+        &spks,
+        |statement, i, (did, index, spk)| {
+            statement.raw_bind_parameter(i, Impl(*did))?;
+            statement.raw_bind_parameter(i + 1, *index)?;
+            statement.raw_bind_parameter(i + 2, spk.as_bytes())
+        },
+    )
+}
@@ -0,0 +1,305 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Persistence of several wallets in a single SQLite database.
+//!
+//! The [`WalletPersister`] implementation of [`Connection`] stores a single wallet per database.
+//! Instead, [`SqliteWalletStore`] stores the wallet under an id of the caller's choosing, so that
+//! many wallets can share a database:
+//!
+//! ```rust,no_run
+//! # use bdk_wallet::rusqlite::Connection;
+//! # use bdk_wallet::sqlite_wallets::SqliteWalletStore;
+//! # use bdk_wallet::{KeychainKind, Wallet};
+//! # let (external_desc, internal_desc) = ("", "");
+//! let mut store = SqliteWalletStore::new(Connection::open("wallets.sqlite")?, "user-42");
+//! let mut wallet = match Wallet::load().load_wallet(&mut store)? {
+//!     Some(wallet) => wallet,
+//!     None => Wallet::create(external_desc, internal_desc).create_wallet(&mut store)?,
+//! };
+//! wallet.reveal_next_address(KeychainKind::External);
+//! wallet.persist(&mut store)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! The changesets of every wallet are stored as JSON rows of a table with a wallet id column, so
+//! that every wallet is independent of the others. Persisting appends a row with the staged
+//! changes in a single database transaction, so its cost doesn't grow with the history of the
+//! wallet. The rows of a wallet are merged into one once it has [`COMPACTION_THRESHOLD`] of them,
+//! when it is loaded or persisted. Each row records the [`ChangeSet::CURRENT_VERSION`] it was
+//! written with, and is upgraded with the [`migration::MIGRATIONS`] when it is loaded.
+//!
+//! Unlike the single wallet [`WalletPersister`] implementation, the wallets are not stored in the
+//! relational `bdk_*` tables, whose rows are written by `bdk_chain` without a wallet id. As a
+//! consequence, the storage checks of [`LoadParams::on_corruption`] don't apply to them: only the
+//! consistency of the loaded changeset is checked.
+//!
+//! A wallet stored with the single wallet [`WalletPersister`] implementation is copied under
+//! [`DEFAULT_WALLET_ID`] the first time the database is used with this module. The single
+//! wallet tables are only read, so they must have the latest schema version: older ones are
+//! upgraded by loading the wallet once with the single wallet implementation. They are left
+//! untouched, but should not be written to anymore.
+//!
+//! [`WalletPersister`]: crate::WalletPersister
+//! [`LoadParams::on_corruption`]: crate::LoadParams::on_corruption
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use bdk_chain::rusqlite::{self, named_params, Connection, Transaction};
+use bdk_chain::Merge;
+
+use crate::migration::{self, ChangeSetMigrationError};
+use crate::{ChangeSet, SqliteError, WalletPersister};
+
+/// Id under which the wallet of a single wallet database is migrated.
+pub const DEFAULT_WALLET_ID: &str = "default";
+
+/// Number of rows of a wallet after which they are merged into one when it is loaded or persisted.
+pub const COMPACTION_THRESHOLD: usize = 100;
+
+/// Schema name of the wallet changesets table.
+const SCHEMA_NAME: &str = "bdk_wallet_changesets";
+/// Name of the table storing the changesets of every wallet.
+const TABLE_NAME: &str = "bdk_wallet_changesets";
+
+fn schema_v0() -> String {
+    format!(
+        "CREATE TABLE {TABLE_NAME} ( \
+            id INTEGER PRIMARY KEY AUTOINCREMENT, \
+            wallet_id TEXT NOT NULL, \
+            changeset TEXT NOT NULL \
+            ) STRICT; \
+        CREATE INDEX {TABLE_NAME}_wallet_id ON {TABLE_NAME}(wallet_id);"
+    )
+}
+
+/// Schema v1 records the [`ChangeSet::CURRENT_VERSION`] each changeset was written with. The
+/// changesets written before are upgraded from version 0.
+fn schema_v1() -> String {
+    format!("ALTER TABLE {TABLE_NAME} ADD COLUMN version INTEGER;")
+}
+
+fn table_exists(db_tx: &Transaction, table: &str) -> rusqlite::Result<bool> {
+    db_tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type='table' AND name=:name)",
+        named_params! { ":name": table },
+        |row| row.get(0),
+    )
+}
+
+/// A wallet stored in a SQLite database under an id, next to other wallets.
+#[derive(Debug)]
+pub struct SqliteWalletStore {
+    conn: Connection,
+    wallet_id: String,
+}
+
+impl SqliteWalletStore {
+    /// Create a store for the wallet with `wallet_id` in the database of `conn`.
+    pub fn new(conn: Connection, wallet_id: impl Into<String>) -> Self {
+        Self {
+            conn,
+            wallet_id: wallet_id.into(),
+        }
+    }
+
+    /// The id of the wallet in the database.
+    pub fn wallet_id(&self) -> &str {
+        &self.wallet_id
+    }
+
+    /// The connection to the database.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Return the connection to the database.
+    pub fn into_connection(self) -> Connection {
+        self.conn
+    }
+}
+
+impl WalletPersister for SqliteWalletStore {
+    type Error = SqliteError;
+
+    fn initialize(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        let db_tx = persister.conn.transaction()?;
+        init(&db_tx)?;
+        let changeset = load(&db_tx, &persister.wallet_id)?;
+        db_tx.commit()?;
+        Ok(changeset)
+    }
+
+    fn load_read_only(persister: &mut Self) -> Result<ChangeSet, Self::Error> {
+        let db_tx = persister.conn.transaction()?;
+        let changeset = load_read_only(&db_tx, &persister.wallet_id)?;
+        db_tx.commit()?;
+        Ok(changeset)
+    }
+
+    fn persist(persister: &mut Self, changeset: &ChangeSet) -> Result<(), Self::Error> {
+        let db_tx = persister.conn.transaction()?;
+        persist(&db_tx, &persister.wallet_id, changeset)?;
+        Ok(db_tx.commit()?)
+    }
+}
+
+/// Create the changesets table, and copy the wallet of a single wallet database to it.
+fn init(db_tx: &Transaction) -> Result<(), SqliteError> {
+    let first_use = !table_exists(db_tx, TABLE_NAME)?;
+    crate::rusqlite_impl::migrate_schema(db_tx, SCHEMA_NAME, &[&schema_v0(), &schema_v1()])?;
+    if first_use {
+        let changeset = ChangeSet::from_sqlite_read_only(db_tx)?;
+        persist(db_tx, DEFAULT_WALLET_ID, &changeset)?;
+    }
+    Ok(())
+}
+
+/// Load the aggregate changeset of the wallet with `wallet_id`, empty if there is none, merging
+/// its rows into one if there are [`COMPACTION_THRESHOLD`] of them.
+fn load(db_tx: &Transaction, wallet_id: &str) -> Result<ChangeSet, SqliteError> {
+    let (aggregate, count) = load_rows(db_tx, wallet_id)?;
+    if count >= COMPACTION_THRESHOLD {
+        delete(db_tx, wallet_id)?;
+        insert(db_tx, wallet_id, &aggregate)?;
+    }
+    Ok(aggregate)
+}
+
+/// Load the aggregate changeset of the wallet with `wallet_id` without writing to the database.
+///
+/// Unlike [`load`], the changesets table is neither created nor migrated, so a wallet of a single
+/// wallet database that was never used with this module is not found.
+fn load_read_only(db_tx: &Transaction, wallet_id: &str) -> Result<ChangeSet, SqliteError> {
+    use crate::rusqlite_impl::SCHEMAS_TABLE_NAME;
+    use rusqlite::OptionalExtension;
+
+    if !table_exists(db_tx, TABLE_NAME)? {
+        return Ok(ChangeSet::default());
+    }
+    let schema_version = db_tx
+        .query_row(
+            &format!("SELECT version FROM {SCHEMAS_TABLE_NAME} WHERE name=:name"),
+            named_params! { ":name": SCHEMA_NAME },
+            |row| row.get::<_, u32>(0),
+        )
+        .optional()?;
+    // the version column is added by schema v1
+    if schema_version != Some(1) {
+        return Err(SqliteError::UpgradeRequired(schema_version.unwrap_or(0)));
+    }
+    Ok(load_rows(db_tx, wallet_id)?.0)
+}
+
+/// Merge the changesets of the wallet with `wallet_id`, and count them.
+fn load_rows(db_tx: &Transaction, wallet_id: &str) -> Result<(ChangeSet, usize), SqliteError> {
+    let mut stmt = db_tx.prepare(&format!(
+        "SELECT changeset, version FROM {TABLE_NAME} WHERE wallet_id=:wallet_id ORDER BY id"
+    ))?;
+    let rows = stmt.query_map(named_params! { ":wallet_id": wallet_id }, |row| {
+        let json = row.get::<_, String>("changeset")?;
+        let version = row.get::<_, Option<u32>>("version")?.unwrap_or(0);
+        Ok((json, version))
+    })?;
+    let mut aggregate = ChangeSet::default();
+    let mut count = 0;
+    for row in rows {
+        let (json, version) = row?;
+        let value = serde_json::from_str(&json).map_err(ChangeSetMigrationError::Json)?;
+        aggregate.merge(migration::upgrade_changeset(value, version)?);
+        count += 1;
+    }
+    Ok((aggregate, count))
+}
+
+/// Append `changeset` to the changesets of the wallet with `wallet_id`, merging them into one if
+/// there are [`COMPACTION_THRESHOLD`] of them.
+fn persist(db_tx: &Transaction, wallet_id: &str, changeset: &ChangeSet) -> Result<(), SqliteError> {
+    if changeset.is_empty() {
+        return Ok(());
+    }
+    insert(db_tx, wallet_id, changeset)?;
+    let count: usize = db_tx.query_row(
+        &format!("SELECT COUNT(*) FROM {TABLE_NAME} WHERE wallet_id=:wallet_id"),
+        named_params! { ":wallet_id": wallet_id },
+        |row| row.get(0),
+    )?;
+    if count >= COMPACTION_THRESHOLD {
+        load(db_tx, wallet_id)?;
+    }
+    Ok(())
+}
+
+fn insert(db_tx: &Transaction, wallet_id: &str, changeset: &ChangeSet) -> rusqlite::Result<()> {
+    let json = serde_json::to_string(changeset)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    db_tx.execute(
+        &format!(
+            "INSERT INTO {TABLE_NAME}(wallet_id, changeset, version) \
+            VALUES(:wallet_id, :changeset, :version)"
+        ),
+        named_params! {
+            ":wallet_id": wallet_id,
+            ":changeset": json,
+            ":version": ChangeSet::CURRENT_VERSION,
+        },
+    )?;
+    Ok(())
+}
+
+fn delete(db_tx: &Transaction, wallet_id: &str) -> rusqlite::Result<usize> {
+    db_tx.execute(
+        &format!("DELETE FROM {TABLE_NAME} WHERE wallet_id=:wallet_id"),
+        named_params! { ":wallet_id": wallet_id },
+    )
+}
+
+/// List the ids of the wallets stored in the database of `conn`, in ascending order.
+///
+/// The database is not written to. A wallet of a single wallet database that was never used with
+/// this module is listed as [`DEFAULT_WALLET_ID`], the id it is migrated under when loaded.
+pub fn list_wallet_ids(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let db_tx = conn.unchecked_transaction()?;
+    if !table_exists(&db_tx, TABLE_NAME)? {
+        let single_wallet = table_exists(&db_tx, ChangeSet::WALLET_TABLE_NAME)?
+            && db_tx.query_row(
+                &format!(
+                    "SELECT EXISTS(SELECT 1 FROM {})",
+                    ChangeSet::WALLET_TABLE_NAME
+                ),
+                [],
+                |row| row.get(0),
+            )?;
+        return Ok(single_wallet
+            .then(|| DEFAULT_WALLET_ID.into())
+            .into_iter()
+            .collect());
+    }
+    let wallet_ids = db_tx
+        .prepare(&format!(
+            "SELECT DISTINCT wallet_id FROM {TABLE_NAME} ORDER BY wallet_id"
+        ))?
+        .query_map([], |row| row.get::<_, String>("wallet_id"))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(wallet_ids)
+}
+
+/// Delete the wallet with `wallet_id` from the database of `conn`.
+///
+/// Returns whether the wallet existed.
+pub fn delete_wallet(conn: &mut Connection, wallet_id: &str) -> Result<bool, SqliteError> {
+    let db_tx = conn.transaction()?;
+    init(&db_tx)?;
+    let deleted = delete(&db_tx, wallet_id)?;
+    db_tx.commit()?;
+    Ok(deleted > 0)
+}
@@ -0,0 +1,493 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Compaction of [`bdk_file_store`] files.
+//!
+//! A [`Store`] is an append-only log: every persisted [`ChangeSet`] is a new entry, and loading
+//! the wallet reads and merges all of them. [`compact`] rewrites the file as a single entry
+//! holding the aggregate of the log, and [`load_or_create_with_compaction`] does so on load once
+//! the log grows past a number of entries:
+//!
+//! ```rust,no_run
+//! # use bdk_wallet::store_compaction::load_or_create_with_compaction;
+//! let (store, changeset) = load_or_create_with_compaction(b"my_app", "wallet.db", 1000)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! The aggregate is first written to a temporary file next to the original, with the same
+//! magic bytes, and synced to disk. Only then is it renamed over the original. Until the rename
+//! the original file is left as it was, so a crash at any point leaves a valid store behind.
+//!
+//! A [`Store`] already open on the file keeps writing to the file that was replaced, so it must
+//! be dropped before compacting and opened again afterwards.
+//!
+//! Only the `Store<ChangeSet>` files written by the [`WalletPersister`] implementation of this
+//! version of the library can be compacted: their first entry records the
+//! [`ChangeSet::CURRENT_VERSION`]. Other files, such as the `Store<VersionedChangeSets>` ones,
+//! whose entries don't decode as changesets, or the ones written by an older version, are
+//! rejected with [`CompactionError::Unversioned`] without being written to.
+//!
+//! [`WalletPersister`]: crate::WalletPersister
+
+use alloc::boxed::Box;
+use alloc::vec;
+use core::fmt;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use bdk_chain::Merge;
+use bdk_file_store::{EntryIter, Store, StoreError, StoreErrorWithDump};
+
+use super::persisted::FILE_STORE_VERSION_KEY;
+use crate::ChangeSet;
+
+/// Extension appended to the file name of a store to name its temporary compacted file.
+const COMPACT_EXTENSION: &str = "compact";
+
+/// Sizes and entry counts of a store before and after [`compact`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionStats {
+    /// Number of changesets in the store before compaction.
+    pub entries_before: usize,
+    /// Number of changesets in the store after compaction, one unless the store was empty.
+    pub entries_after: usize,
+    /// Size of the file in bytes before compaction.
+    pub size_before: u64,
+    /// Size of the file in bytes after compaction.
+    pub size_after: u64,
+}
+
+/// Error when compacting a [`Store`].
+#[derive(Debug)]
+pub enum CompactionError {
+    /// The store could not be read. Nothing was written.
+    Load(StoreErrorWithDump<ChangeSet>),
+    /// The compacted file could not be written. The original file is left untouched.
+    Write(io::Error),
+    /// The store doesn't record its changeset version, it isn't a `Store<ChangeSet>` written by
+    /// this version of the library. Nothing was written.
+    Unversioned,
+}
+
+impl fmt::Display for CompactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Load(e) => fmt::Display::fmt(e, f),
+            Self::Write(e) => write!(f, "failed to write compacted store: {e}"),
+            Self::Unversioned => write!(
+                f,
+                "the store doesn't record its changeset version and can't be compacted"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CompactionError {}
+
+/// Rewrite the [`Store`] at `file_path` as a single entry holding the aggregate of its
+/// changesets.
+///
+/// Compaction refuses to run if any entry cannot be read, e.g. because it was only partially
+/// written, and fails with [`CompactionError::Load`] without writing anything. Recover the
+/// store first, with the aggregate of the readable entries held by the error.
+pub fn compact<P>(magic: &[u8], file_path: P) -> Result<CompactionStats, CompactionError>
+where
+    P: AsRef<Path>,
+{
+    let file_path = file_path.as_ref();
+    let (changeset, entries) = aggregate_entries(magic, file_path)?;
+    write_compacted(&StdFs, magic, file_path, changeset.as_ref(), entries)
+}
+
+/// Attempt to load an existing [`Store`], compacting it first if it holds more than
+/// `max_entries` changesets; create it if the file does not exist.
+///
+/// The entries are read and merged once, and their aggregate is returned, without the version
+/// recorded by the store. Opening the store
+/// reads it again, since [`Store::load`] is the only way to open a store for appending: after a
+/// compaction, that is only the compacted entry.
+///
+/// See [`compact`] for the errors of the compaction.
+pub fn load_or_create_with_compaction<P>(
+    magic: &[u8],
+    file_path: P,
+    max_entries: usize,
+) -> Result<(Store<ChangeSet>, Option<ChangeSet>), CompactionError>
+where
+    P: AsRef<Path>,
+{
+    let file_path = file_path.as_ref();
+    if !file_path.exists() {
+        let store = Store::create(magic, file_path).map_err(write_error)?;
+        return Ok((store, None));
+    }
+    let (changeset, entries) = aggregate_entries(magic, file_path)?;
+    if entries > max_entries {
+        write_compacted(&StdFs, magic, file_path, changeset.as_ref(), entries)?;
+    }
+    let (store, _) = Store::load(magic, file_path).map_err(CompactionError::Load)?;
+    let changeset = changeset.map(|mut changeset| {
+        changeset.metadata.entries.remove(FILE_STORE_VERSION_KEY);
+        changeset
+    });
+    Ok((store, changeset))
+}
+
+/// The file system operations of a compaction that tests replace to simulate a crash.
+trait CompactionFs {
+    /// Replace the file at `to` with the file at `from`.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+}
+
+struct StdFs;
+
+impl CompactionFs for StdFs {
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)?;
+        // Persist the rename itself.
+        #[cfg(unix)]
+        if let Some(dir) = to.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Replace the store at `file_path`, holding `entries_before` entries, with a single entry
+/// holding their aggregate `changeset`.
+fn write_compacted(
+    fs: &impl CompactionFs,
+    magic: &[u8],
+    file_path: &Path,
+    changeset: Option<&ChangeSet>,
+    entries_before: usize,
+) -> Result<CompactionStats, CompactionError> {
+    let size_before = fs::metadata(file_path)
+        .map_err(|err| CompactionError::Load(err.into()))?
+        .len();
+
+    let compact_path = compact_path(file_path);
+    match fs::remove_file(&compact_path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            return Err(CompactionError::Write(err))
+        }
+        _ => {}
+    }
+    let mut store = Store::<ChangeSet>::create(magic, &compact_path).map_err(write_error)?;
+    let entries_after = match changeset {
+        Some(changeset) if !changeset.is_empty() => {
+            store.append(changeset).map_err(CompactionError::Write)?;
+            1
+        }
+        _ => 0,
+    };
+    drop(store);
+
+    let compact_file = File::options()
+        .write(true)
+        .open(&compact_path)
+        .map_err(CompactionError::Write)?;
+    compact_file.sync_all().map_err(CompactionError::Write)?;
+    let size_after = compact_file
+        .metadata()
+        .map_err(CompactionError::Write)?
+        .len();
+    drop(compact_file);
+
+    fs.rename(&compact_path, file_path)
+        .map_err(CompactionError::Write)?;
+
+    Ok(CompactionStats {
+        entries_before,
+        entries_after,
+        size_before,
+        size_after,
+    })
+}
+
+fn write_error(err: StoreError) -> CompactionError {
+    CompactionError::Write(match err {
+        StoreError::Io(err) => err,
+        err => io::Error::other(err),
+    })
+}
+
+/// Read all the entries of the store at `file_path`, returning their aggregate and count.
+///
+/// Fails with [`CompactionError::Unversioned`] if the store has entries but doesn't record its
+/// changeset version.
+fn aggregate_entries(
+    magic: &[u8],
+    file_path: &Path,
+) -> Result<(Option<ChangeSet>, usize), CompactionError> {
+    let load_error = |err: io::Error| CompactionError::Load(err.into());
+    let mut file = File::open(file_path).map_err(load_error)?;
+    let mut got = vec![0u8; magic.len()];
+    file.read_exact(&mut got).map_err(load_error)?;
+    if got != magic {
+        return Err(CompactionError::Load(StoreErrorWithDump {
+            changeset: None,
+            error: StoreError::InvalidMagicBytes {
+                got,
+                expected: magic.to_vec(),
+            },
+        }));
+    }
+
+    let mut aggregate = Option::<ChangeSet>::None;
+    let mut entries = 0;
+    for entry in EntryIter::<ChangeSet>::new(magic.len() as u64, &mut file) {
+        match entry {
+            Ok(changeset) => {
+                entries += 1;
+                match &mut aggregate {
+                    Some(aggregate) => aggregate.merge(changeset),
+                    None => aggregate = Some(changeset),
+                }
+            }
+            Err(error) => {
+                return Err(CompactionError::Load(StoreErrorWithDump {
+                    changeset: aggregate.map(Box::new),
+                    error,
+                }))
+            }
+        }
+    }
+    if aggregate.as_ref().is_some_and(|changeset| {
+        !matches!(
+            changeset.metadata.entries.get(FILE_STORE_VERSION_KEY),
+            Some(Some(_))
+        )
+    }) {
+        return Err(CompactionError::Unversioned);
+    }
+    Ok((aggregate, entries))
+}
+
+/// Path of the temporary compacted file of the store at `file_path`.
+fn compact_path(file_path: &Path) -> PathBuf {
+    let mut file_name = file_path
+        .file_name()
+        .map(OsString::from)
+        .unwrap_or_default();
+    file_name.push(".");
+    file_name.push(COMPACT_EXTENSION);
+    file_path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::*;
+
+    use bitcoin::Network;
+
+    use crate::{VersionedChangeSets, WalletPersister};
+
+    const MAGIC: &[u8] = b"bdk_compaction_test";
+
+    /// Simulates a crash after the compacted file is written, before it replaces the original.
+    struct CrashBeforeRename;
+
+    impl CompactionFs for CrashBeforeRename {
+        fn rename(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+            Err(io::Error::other("crash"))
+        }
+    }
+
+    fn changesets() -> impl Iterator<Item = ChangeSet> {
+        let network = ChangeSet {
+            network: Some(Network::Signet),
+            ..Default::default()
+        };
+        let labels = (0..10u32).map(|vout| {
+            let mut changeset = ChangeSet::default();
+            changeset.labels.outputs.insert(
+                bitcoin::OutPoint::new(bitcoin::hashes::Hash::from_byte_array([1; 32]), vout),
+                Some(format!("label {vout}")),
+            );
+            changeset
+        });
+        core::iter::once(network).chain(labels)
+    }
+
+    fn aggregate() -> ChangeSet {
+        let mut aggregate = ChangeSet::default();
+        changesets().for_each(|changeset| aggregate.merge(changeset));
+        aggregate
+    }
+
+    /// Create a store of 12 entries, the first one recording the changeset version.
+    fn create_store(path: &Path) {
+        let mut store = Store::<ChangeSet>::create(MAGIC, path).unwrap();
+        WalletPersister::initialize(&mut store).unwrap();
+        for changeset in changesets() {
+            store.append(&changeset).unwrap();
+        }
+    }
+
+    /// The changeset of the store at `path`, without the version it records.
+    fn load(path: &Path) -> ChangeSet {
+        let (mut store, _) = Store::<ChangeSet>::load(MAGIC, path).unwrap();
+        WalletPersister::load_read_only(&mut store).unwrap()
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+        create_store(&path);
+
+        let stats = compact(MAGIC, &path).unwrap();
+        assert_eq!(stats.entries_before, 12);
+        assert_eq!(stats.entries_after, 1);
+        assert_eq!(stats.size_after, fs::metadata(&path).unwrap().len());
+        assert!(stats.size_after < stats.size_before);
+        assert!(!compact_path(&path).exists());
+        assert_eq!(load(&path), aggregate());
+
+        // an empty store stays empty
+        let empty_path = dir.path().join("empty.db");
+        Store::<ChangeSet>::create(MAGIC, &empty_path).unwrap();
+        let stats = compact(MAGIC, &empty_path).unwrap();
+        assert_eq!((stats.entries_before, stats.entries_after), (0, 0));
+        let (_, changeset) = Store::<ChangeSet>::load(MAGIC, &empty_path).unwrap();
+        assert_eq!(changeset, None);
+    }
+
+    #[test]
+    fn test_crash_before_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+        create_store(&path);
+        let contents = fs::read(&path).unwrap();
+
+        let (changeset, entries) = aggregate_entries(MAGIC, &path).unwrap();
+        assert!(matches!(
+            write_compacted(
+                &CrashBeforeRename,
+                MAGIC,
+                &path,
+                changeset.as_ref(),
+                entries
+            ),
+            Err(CompactionError::Write(_))
+        ));
+        // the original is untouched and the compacted file is left behind
+        assert_eq!(fs::read(&path).unwrap(), contents);
+        assert!(compact_path(&path).exists());
+
+        // a later compaction replaces the leftover file
+        let stats = compact(MAGIC, &path).unwrap();
+        assert_eq!(stats.entries_before, 12);
+        assert!(!compact_path(&path).exists());
+        assert_eq!(load(&path), aggregate());
+    }
+
+    #[test]
+    fn test_refuse_corrupted_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+        create_store(&path);
+
+        // simulate a partially written last entry
+        let file = File::options().write(true).open(&path).unwrap();
+        let len = file.metadata().unwrap().len();
+        file.set_len(len - 2).unwrap();
+        drop(file);
+        let contents = fs::read(&path).unwrap();
+
+        match compact(MAGIC, &path) {
+            Err(CompactionError::Load(StoreErrorWithDump { changeset, .. })) => {
+                assert_eq!(changeset.map(|c| c.network), Some(Some(Network::Signet)));
+            }
+            res => panic!("unexpected result: {res:?}"),
+        }
+        assert_eq!(fs::read(&path).unwrap(), contents);
+        assert!(!compact_path(&path).exists());
+
+        assert!(matches!(
+            compact(b"wrong_magic", &path),
+            Err(CompactionError::Load(StoreErrorWithDump {
+                error: StoreError::InvalidMagicBytes { .. },
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_load_or_create_with_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.db");
+
+        let (_, changeset) =
+            load_or_create_with_compaction(MAGIC, dir.path().join("new.db"), 5).unwrap();
+        assert_eq!(changeset, None);
+        create_store(&path);
+
+        // below the threshold the file is not rewritten
+        let len = fs::metadata(&path).unwrap().len();
+        let (_, changeset) = load_or_create_with_compaction(MAGIC, &path, 12).unwrap();
+        assert_eq!(changeset, Some(aggregate()));
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+
+        let (mut store, changeset) = load_or_create_with_compaction(MAGIC, &path, 11).unwrap();
+        assert_eq!(changeset, Some(aggregate()));
+        assert!(fs::metadata(&path).unwrap().len() < len);
+        assert_eq!(load(&path), aggregate());
+
+        // the reloaded store can be appended to
+        let network = ChangeSet {
+            network: Some(Network::Signet),
+            ..Default::default()
+        };
+        store.append(&network).unwrap();
+        drop(store);
+        let stats = compact(MAGIC, &path).unwrap();
+        assert_eq!(stats.entries_before, 2);
+    }
+
+    #[test]
+    fn test_refuse_unversioned_store() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // the entries of a store written by an older version don't record the version
+        let path = dir.path().join("wallet.db");
+        let mut store = Store::<ChangeSet>::create(MAGIC, &path).unwrap();
+        for changeset in changesets() {
+            store.append(&changeset).unwrap();
+        }
+        drop(store);
+        assert!(matches!(
+            compact(MAGIC, &path),
+            Err(CompactionError::Unversioned)
+        ));
+        // the entries of a `Store<VersionedChangeSets>` aren't changesets
+        let versioned_path = dir.path().join("versioned.db");
+        let mut store = Store::<VersionedChangeSets>::create(MAGIC, &versioned_path).unwrap();
+        WalletPersister::persist(&mut store, &aggregate()).unwrap();
+        drop(store);
+
+        for path in [path, versioned_path] {
+            let contents = fs::read(&path).unwrap();
+            assert!(matches!(
+                compact(MAGIC, &path),
+                Err(CompactionError::Unversioned | CompactionError::Load(_))
+            ));
+            assert!(matches!(
+                load_or_create_with_compaction(MAGIC, &path, 0),
+                Err(CompactionError::Unversioned | CompactionError::Load(_))
+            ));
+            assert_eq!(fs::read(&path).unwrap(), contents);
+        }
+    }
+}
@@ -41,19 +41,22 @@ use core::fmt;
 
 use alloc::sync::Arc;
 
+use bitcoin::address::{NetworkUnchecked, NetworkValidation};
+use bitcoin::hashes::Hash;
 use bitcoin::psbt::{self, Psbt};
 use bitcoin::script::PushBytes;
 use bitcoin::{
-    absolute, transaction::Version, Amount, FeeRate, OutPoint, ScriptBuf, Sequence, Transaction,
-    TxIn, TxOut, Txid, Weight,
+    absolute, transaction::Version, Address, Amount, FeeRate, Network, OutPoint, ScriptBuf,
+    Sequence, Transaction, TxIn, TxOut, Txid, Weight,
 };
 use rand_core::RngCore;
 
 use super::coin_selection::CoinSelectionAlgorithm;
+use super::fee_estimation::FeeEstimator;
 use super::utils::shuffle_slice;
 use super::{CreateTxError, Wallet};
 use crate::collections::{BTreeMap, HashMap, HashSet};
-use crate::{KeychainKind, LocalOutput, Utxo, WeightedUtxo};
+use crate::{AddressType, KeychainKind, LocalOutput, Utxo, WeightedUtxo};
 
 /// A transaction builder
 ///
@@ -114,6 +117,7 @@ pub struct TxBuilder<'a, Cs> {
     pub(crate) wallet: &'a mut Wallet,
     pub(crate) params: TxParams,
     pub(crate) coin_selection: Cs,
+    pub(crate) fee_estimator: Option<(&'a (dyn FeeEstimator + Sync), usize)>,
 }
 
 /// The parameters for transaction creation sans coin selection algorithm.
@@ -121,6 +125,7 @@ pub struct TxBuilder<'a, Cs> {
 #[derive(Default, Debug, Clone)]
 pub(crate) struct TxParams {
     pub(crate) recipients: Vec<(ScriptBuf, Amount)>,
+    pub(crate) recipient_labels: BTreeMap<usize, String>,
     pub(crate) drain_wallet: bool,
     pub(crate) drain_to: Option<ScriptBuf>,
     pub(crate) fee_policy: Option<FeePolicy>,
@@ -131,8 +136,10 @@ pub(crate) struct TxParams {
     pub(crate) manually_selected_only: bool,
     pub(crate) sighash: Option<psbt::PsbtSighashType>,
     pub(crate) ordering: TxOrdering,
+    pub(crate) input_order: Vec<OutPoint>,
     pub(crate) locktime: Option<absolute::LockTime>,
     pub(crate) sequence: Option<Sequence>,
+    pub(crate) require_rbf: bool,
     pub(crate) version: Option<Version>,
     pub(crate) change_policy: ChangeSpendPolicy,
     pub(crate) only_witness_utxo: bool,
@@ -140,6 +147,10 @@ pub(crate) struct TxParams {
     pub(crate) bumping_fee: Option<PreviousFee>,
     pub(crate) current_height: Option<absolute::LockTime>,
     pub(crate) allow_dust: bool,
+    pub(crate) leftover_policy: LeftoverPolicy,
+    pub(crate) drain_keychain: Option<KeychainKind>,
+    pub(crate) change_address_type: Option<AddressType>,
+    pub(crate) fixed_inputs: bool,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -174,6 +185,29 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// excess might not be viable.
     pub fn fee_rate(&mut self, fee_rate: FeeRate) -> &mut Self {
         self.params.fee_policy = Some(FeePolicy::FeeRate(fee_rate));
+        self.fee_estimator = None;
+        self
+    }
+
+    /// Set the fee rate to the estimate of `estimator` for a confirmation within `target_blocks`
+    /// blocks.
+    ///
+    /// The estimator is queried when the transaction is built by [`finish`], and an error of the
+    /// estimator is returned as [`CreateTxError::FeeEstimation`]. The chosen fee rate can be read
+    /// back from the returned PSBT with [`PsbtUtils::fee_rate`].
+    ///
+    /// Like [`fee_rate`], this replaces any fee rate or absolute fee set before.
+    ///
+    /// [`finish`]: Self::finish
+    /// [`fee_rate`]: Self::fee_rate
+    /// [`PsbtUtils::fee_rate`]: crate::psbt::PsbtUtils::fee_rate
+    pub fn fee_rate_from(
+        &mut self,
+        estimator: &'a (dyn FeeEstimator + Sync),
+        target_blocks: usize,
+    ) -> &mut Self {
+        self.params.fee_policy = None;
+        self.fee_estimator = Some((estimator, target_blocks));
         self
     }
 
@@ -188,6 +222,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// excess might not be viable.
     pub fn fee_absolute(&mut self, fee_amount: Amount) -> &mut Self {
         self.params.fee_policy = Some(FeePolicy::FeeAmount(fee_amount));
+        self.fee_estimator = None;
         self
     }
 
@@ -278,6 +313,10 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     ///
     /// If a UTXO is inserted multiple times, only the final insertion will take effect.
     pub fn add_utxos(&mut self, outpoints: &[OutPoint]) -> Result<&mut Self, AddUtxoError> {
+        if self.params.fixed_inputs {
+            return Err(AddUtxoError::FixedInputs);
+        }
+
         // Canonicalize once, instead of once for every call to `get_utxo`.
         let unspent: HashMap<OutPoint, LocalOutput> = self
             .wallet
@@ -407,6 +446,10 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         satisfaction_weight: Weight,
         sequence: Sequence,
     ) -> Result<&mut Self, AddForeignUtxoError> {
+        if self.params.fixed_inputs {
+            return Err(AddForeignUtxoError::FixedInputs);
+        }
+
         // Always validate non_witness_utxo if present
         if let Some(tx) = psbt_input.non_witness_utxo.as_ref() {
             if tx.compute_txid() != outpoint.txid {
@@ -465,6 +508,9 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
 
     /// Replace the internal list of unspendable utxos with a new list
     ///
+    /// Unspendable utxos are excluded from coin selection for this transaction only, unlike
+    /// the ones locked with [`Wallet::lock_outpoint`] which are excluded from every transaction.
+    ///
     /// It's important to note that the "must-be-spent" utxos added with [`TxBuilder::add_utxo`]
     /// have priority over these. See the docs of the two linked methods for more details.
     pub fn unspendable(&mut self, unspendable: Vec<OutPoint>) -> &mut Self {
@@ -523,6 +569,12 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// Sign with a specific sig hash
     ///
     /// **Use this option very carefully**
+    ///
+    /// With `SIGHASH_SINGLE`, each input only signs the output at the same index. Building the
+    /// transaction fails with [`CreateTxError::SighashSingleUnpairedInput`] if it has more inputs
+    /// than outputs: because of the `SIGHASH_SINGLE` bug, the signature of a legacy input without
+    /// a matching output is valid for any transaction. Since the inputs and outputs are paired by
+    /// index, use [`TxOrdering::Untouched`] to control which output each input signs.
     pub fn sighash(&mut self, sighash: psbt::PsbtSighashType) -> &mut Self {
         self.params.sighash = Some(sighash);
         self
@@ -534,11 +586,25 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// recipients and manually selected UTXOs is preserved and reflected exactly in transaction's
     /// output and input vectors respectively. If algorithmically selected UTXOs are included, they
     /// will be placed after all the manually selected ones in the transaction's input vector.
+    ///
+    /// Defaults to [TxOrdering::Shuffle].
     pub fn ordering(&mut self, ordering: TxOrdering) -> &mut Self {
         self.params.ordering = ordering;
         self
     }
 
+    /// Place the inputs spending `outpoints` first in the transaction, in the given order
+    ///
+    /// The other inputs, e.g. the ones added by coin selection, follow in the order chosen by
+    /// [`TxBuilder::ordering`]. The `outpoints` can be wallet UTXOs or foreign UTXOs, and must be
+    /// spent by the transaction, for example by adding them with [`TxBuilder::add_utxos`] or
+    /// [`TxBuilder::add_foreign_utxo`], otherwise [`CreateTxError::InputNotSelected`] is returned
+    /// when the transaction is built.
+    pub fn with_input_order(&mut self, outpoints: Vec<OutPoint>) -> &mut Self {
+        self.params.input_order = outpoints;
+        self
+    }
+
     /// Use a specific nLockTime while creating the transaction
     ///
     /// This can cause conflicts if the wallet's descriptors contain an "after" (OP_CLTV) operator.
@@ -588,7 +654,8 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// when spending from SegWit descriptors.
     ///
     /// This reduces the size of the PSBT, but some signers might reject them due to the lack of
-    /// the `non_witness_utxo`.
+    /// the `non_witness_utxo`. By default the `non_witness_utxo` is filled for every non-taproot
+    /// input, it can be added later with [`Wallet::populate_non_witness_utxos`].
     pub fn only_witness_utxo(&mut self) -> &mut Self {
         self.params.only_witness_utxo = true;
         self
@@ -622,6 +689,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
             wallet: self.wallet,
             params: self.params,
             coin_selection,
+            fee_estimator: self.fee_estimator,
         }
     }
 
@@ -631,6 +699,34 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// "older" (OP_CSV) operator and the given `nsequence` is lower than the CSV value.
     pub fn set_exact_sequence(&mut self, n_sequence: Sequence) -> &mut Self {
         self.params.sequence = Some(n_sequence);
+        self.params.require_rbf = false;
+        self
+    }
+
+    /// Don't signal replaceability ([BIP125]), setting the nSequence of the inputs to
+    /// `0xFFFFFFFF`.
+    ///
+    /// By default transactions signal RBF with an nSequence of `0xFFFFFFFD`. A final nSequence
+    /// disables both relative and absolute timelocks, so [`finish`](Self::finish) returns an
+    /// error if the spending policy requires an "older" (OP_CSV) or "after" (OP_CLTV) timelock.
+    ///
+    /// [BIP125]: https://github.com/bitcoin/bips/blob/master/bip-0125.mediawiki
+    pub fn disable_rbf(&mut self) -> &mut Self {
+        self.set_exact_sequence(Sequence::MAX)
+    }
+
+    /// Signal replaceability ([BIP125]) with the given nSequence, which must be lower than
+    /// `0xFFFFFFFE`.
+    ///
+    /// [`finish`](Self::finish) returns [`CreateTxError::RbfSequence`] if `n_sequence` doesn't
+    /// signal RBF. Values up to `0xEFFFFFFF` are also interpreted as relative timelocks, see
+    /// [`set_exact_sequence`](Self::set_exact_sequence) for the interaction with "older"
+    /// (OP_CSV) spending policies.
+    ///
+    /// [BIP125]: https://github.com/bitcoin/bips/blob/master/bip-0125.mediawiki
+    pub fn enable_rbf_with_sequence(&mut self, n_sequence: Sequence) -> &mut Self {
+        self.params.sequence = Some(n_sequence);
+        self.params.require_rbf = true;
         self
     }
 
@@ -660,9 +756,35 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         self
     }
 
+    /// Set the minimum value of the change output.
+    ///
+    /// Change below `min_change` is added to the fee instead of creating an output, which avoids
+    /// creating small change UTXOs that cost more to spend than they're worth. By default the
+    /// change output is only dropped when it would be dust.
+    ///
+    /// This doesn't apply when the change output is the only output of the transaction, e.g.
+    /// when draining the wallet to an address with [`drain_to`](Self::drain_to).
+    ///
+    /// This is a shorthand for [`leftover_policy`](Self::leftover_policy) with
+    /// [`LeftoverPolicy::Threshold`].
+    pub fn min_change(&mut self, min_change: Amount) -> &mut Self {
+        self.leftover_policy(LeftoverPolicy::Threshold { min_change })
+    }
+
+    /// Set what to do with the value left over after paying the recipients and the fee.
+    ///
+    /// See [`LeftoverPolicy`] for the available options, by default a change output is created
+    /// unless it would be dust. Like [`min_change`](Self::min_change), the policy doesn't apply
+    /// when the change output is the only output of the transaction.
+    pub fn leftover_policy(&mut self, policy: LeftoverPolicy) -> &mut Self {
+        self.params.leftover_policy = policy;
+        self
+    }
+
     /// Replace the recipients already added with a new list
     pub fn set_recipients(&mut self, recipients: Vec<(ScriptBuf, Amount)>) -> &mut Self {
         self.params.recipients = recipients;
+        self.params.recipient_labels.clear();
         self
     }
 
@@ -676,6 +798,47 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         self
     }
 
+    /// Add a recipient to the internal list and `label` the resulting output.
+    ///
+    /// The label is only committed to the wallet once the created transaction is applied to it,
+    /// for example when it is found by a chain source or with [`Wallet::apply_unconfirmed_txs`].
+    /// It can then be retrieved with [`Wallet::output_label`]. Labels of transactions that are
+    /// never applied are not persisted.
+    pub fn add_recipient_with_label(
+        &mut self,
+        script_pubkey: impl Into<ScriptBuf>,
+        amount: Amount,
+        label: impl Into<String>,
+    ) -> &mut Self {
+        self.params
+            .recipient_labels
+            .insert(self.params.recipients.len(), label.into());
+        self.add_recipient(script_pubkey, amount)
+    }
+
+    /// Add an address to the internal list of recipients, checking that it's valid for the network
+    /// of the wallet
+    ///
+    /// Unlike [`add_recipient`](Self::add_recipient), which takes any script pubkey, this catches
+    /// addresses of a different network, like a testnet address in a mainnet transaction. Note
+    /// that testnet, testnet4 and signet share the same addresses, and so does regtest except for
+    /// segwit addresses.
+    pub fn add_recipient_address<V: NetworkValidation>(
+        &mut self,
+        address: &Address<V>,
+        amount: Amount,
+    ) -> Result<&mut Self, AddressNetworkMismatch> {
+        let network = self.wallet.network();
+        let address = address.as_unchecked();
+        if !address.is_valid_for_network(network) {
+            return Err(AddressNetworkMismatch {
+                address: address.clone(),
+                network,
+            });
+        }
+        Ok(self.add_recipient(address.assume_checked_ref().script_pubkey(), amount))
+    }
+
     /// Add data as an output, using OP_RETURN
     pub fn add_data<T: AsRef<PushBytes>>(&mut self, data: &T) -> &mut Self {
         let script = ScriptBuf::new_op_return(data);
@@ -731,6 +894,41 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         self.params.drain_to = Some(script_pubkey);
         self
     }
+
+    /// Sends the excess of the transaction to the next unused address of `keychain`.
+    ///
+    /// By default the change goes to the internal keychain. Like [`drain_to`], this allows
+    /// building a transaction without recipients spending all the manually selected UTXOs (or the
+    /// whole wallet with [`drain_wallet`]), but to a fresh address of the wallet. A script set
+    /// with [`drain_to`] takes precedence.
+    ///
+    /// [`drain_to`]: Self::drain_to
+    /// [`drain_wallet`]: Self::drain_wallet
+    pub fn drain_to_keychain(&mut self, keychain: KeychainKind) -> &mut Self {
+        self.params.drain_keychain = Some(keychain);
+        self
+    }
+
+    /// Sends the change to the keychain whose addresses are of `address_type`.
+    ///
+    /// This only matters for wallets whose keychains are of different types, e.g. a taproot
+    /// external keychain and a segwit v0 internal one. Change of the same type as the recipients
+    /// is harder to tell apart from them, while change of a cheaper type to spend saves fees
+    /// later. If neither keychain is of `address_type`, the change goes to the internal keychain.
+    ///
+    /// By default, the change goes to the keychain of the same type as most recipients, if there
+    /// is one, or else to the internal keychain. A keychain set with [`drain_to_keychain`] takes
+    /// precedence.
+    ///
+    /// Note that change sent to the external keychain is not recognized as change when bumping
+    /// the fee of the transaction with [`Wallet::build_fee_bump`], and is kept as a recipient.
+    ///
+    /// [`drain_to_keychain`]: Self::drain_to_keychain
+    /// [`Wallet::build_fee_bump`]: crate::Wallet::build_fee_bump
+    pub fn change_script_type_preference(&mut self, address_type: AddressType) -> &mut Self {
+        self.params.change_address_type = Some(address_type);
+        self
+    }
 }
 
 impl<Cs: CoinSelectionAlgorithm> TxBuilder<'_, Cs> {
@@ -759,7 +957,13 @@ impl<Cs: CoinSelectionAlgorithm> TxBuilder<'_, Cs> {
     ///
     /// **WARNING**: To avoid change address reuse you must persist the changes resulting from one
     /// or more calls to this method before closing the wallet. See [`Wallet::reveal_next_address`].
-    pub fn finish_with_aux_rand(self, rng: &mut impl RngCore) -> Result<Psbt, CreateTxError> {
+    pub fn finish_with_aux_rand(mut self, rng: &mut impl RngCore) -> Result<Psbt, CreateTxError> {
+        if let Some((estimator, target_blocks)) = self.fee_estimator {
+            let fee_rate = estimator
+                .estimate(target_blocks)
+                .map_err(CreateTxError::FeeEstimation)?;
+            self.params.fee_policy = Some(FeePolicy::FeeRate(fee_rate));
+        }
         self.wallet.create_tx(self.coin_selection, self.params, rng)
     }
 }
@@ -769,6 +973,8 @@ impl<Cs: CoinSelectionAlgorithm> TxBuilder<'_, Cs> {
 pub enum AddUtxoError {
     /// Happens when trying to spend an UTXO that is not in the internal database
     UnknownUtxo(OutPoint),
+    /// The inputs of the transaction can't be changed, see [`Wallet::build_utxo_refresh`]
+    FixedInputs,
 }
 
 impl fmt::Display for AddUtxoError {
@@ -779,6 +985,7 @@ impl fmt::Display for AddUtxoError {
                 "UTXO not found in the internal database for txid: {} with vout: {}",
                 outpoint.txid, outpoint.vout
             ),
+            Self::FixedInputs => write!(f, "The inputs of the transaction can't be changed"),
         }
     }
 }
@@ -799,6 +1006,8 @@ pub enum AddForeignUtxoError {
     InvalidOutpoint(OutPoint),
     /// Foreign utxo missing witness_utxo or non_witness_utxo
     MissingUtxo,
+    /// The inputs of the transaction can't be changed, see [`Wallet::build_utxo_refresh`]
+    FixedInputs,
 }
 
 impl fmt::Display for AddForeignUtxoError {
@@ -818,12 +1027,36 @@ impl fmt::Display for AddForeignUtxoError {
                 outpoint.txid, outpoint.vout,
             ),
             Self::MissingUtxo => write!(f, "Foreign utxo missing witness_utxo or non_witness_utxo"),
+            Self::FixedInputs => write!(f, "The inputs of the transaction can't be changed"),
         }
     }
 }
 
 impl core::error::Error for AddForeignUtxoError {}
 
+/// Error returned from [`TxBuilder::add_recipient_address`] when the address isn't valid for the
+/// network of the wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressNetworkMismatch {
+    /// The address of the recipient
+    pub address: Address<NetworkUnchecked>,
+    /// The network of the wallet
+    pub network: Network,
+}
+
+impl fmt::Display for AddressNetworkMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Address {} is not valid for network {}",
+            self.address.assume_checked_ref(),
+            self.network
+        )
+    }
+}
+
+impl core::error::Error for AddressNetworkMismatch {}
+
 type TxSort<T> = dyn (Fn(&T, &T) -> core::cmp::Ordering) + Send + Sync;
 
 /// Ordering of the transaction's inputs and outputs
@@ -832,6 +1065,11 @@ pub enum TxOrdering {
     /// Randomized (default)
     #[default]
     Shuffle,
+    /// Randomized with a random number generator seeded with the given value
+    ///
+    /// The same seed always results in the same ordering of the same inputs and outputs, e.g.
+    /// for multiple parties to agree on the ordering without revealing it beforehand.
+    ShuffleWithSeed(u64),
     /// Untouched
     ///
     /// Untouched insertion order for recipients and for manually added UTXOs. This guarantees all
@@ -840,6 +1078,16 @@ pub enum TxOrdering {
     /// guarantees about algorithmically selected UTXOs. However, by design they will always be
     /// placed after the manually selected ones.
     Untouched,
+    /// Lexicographical ordering as described in [BIP69]
+    ///
+    /// Inputs are sorted by the txid (in its displayed byte order) and then the index of their
+    /// previous output, outputs by amount and then script pubkey. Only the order of the inputs
+    /// and outputs is deterministic: unless the inputs are chosen with
+    /// [`TxBuilder::manually_selected_only`], coin selection may pick different UTXOs for the
+    /// same recipients.
+    ///
+    /// [BIP69]: https://github.com/bitcoin/bips/blob/master/bip-0069.mediawiki
+    Bip69,
     /// Provide custom comparison functions for sorting
     Custom {
         /// Transaction inputs sort function
@@ -853,7 +1101,9 @@ impl core::fmt::Debug for TxOrdering {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             TxOrdering::Shuffle => write!(f, "Shuffle"),
+            TxOrdering::ShuffleWithSeed(seed) => write!(f, "ShuffleWithSeed({seed})"),
             TxOrdering::Untouched => write!(f, "Untouched"),
+            TxOrdering::Bip69 => write!(f, "Bip69"),
             TxOrdering::Custom { .. } => write!(f, "Custom"),
         }
     }
@@ -878,6 +1128,25 @@ impl TxOrdering {
                 shuffle_slice(&mut tx.input, rng);
                 shuffle_slice(&mut tx.output, rng);
             }
+            TxOrdering::ShuffleWithSeed(seed) => {
+                let mut rng = SeededRng(*seed);
+                shuffle_slice(&mut tx.input, &mut rng);
+                shuffle_slice(&mut tx.output, &mut rng);
+            }
+            TxOrdering::Bip69 => {
+                tx.input.sort_unstable_by(|a, b| {
+                    let (a, b) = (a.previous_output, b.previous_output);
+                    a.txid
+                        .to_byte_array()
+                        .iter()
+                        .rev()
+                        .cmp(b.txid.to_byte_array().iter().rev())
+                        .then(a.vout.cmp(&b.vout))
+                });
+                tx.output.sort_unstable_by(|a, b| {
+                    (a.value, &a.script_pubkey).cmp(&(b.value, &b.script_pubkey))
+                });
+            }
             TxOrdering::Custom {
                 input_sort,
                 output_sort,
@@ -889,6 +1158,33 @@ impl TxOrdering {
     }
 }
 
+/// The SplitMix64 generator, used by [`TxOrdering::ShuffleWithSeed`] so the ordering doesn't
+/// depend on the implementation of an external random number generator.
+struct SeededRng(u64);
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand_core::impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
 /// Policy regarding the use of change outputs when creating a transaction
 #[derive(Default, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Clone, Copy)]
 pub enum ChangeSpendPolicy {
@@ -911,6 +1207,45 @@ impl ChangeSpendPolicy {
     }
 }
 
+/// Policy regarding the value left over after paying the recipients and the fee of a transaction
+///
+/// See [`TxBuilder::leftover_policy`].
+#[derive(Default, Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum LeftoverPolicy {
+    /// Create a change output, unless it would be dust (default)
+    #[default]
+    CreateChange,
+    /// Never create a change output, add the leftover value to the fee
+    ///
+    /// Coin selection first looks for inputs that need no change, falling back to the selected
+    /// algorithm, which may pick inputs well above the amount to send. Building the transaction
+    /// then fails with [`CreateTxError::LeftoverAboveMax`] rather than giving more than
+    /// `max_leftover` away to the fee.
+    ///
+    /// [`CreateTxError::LeftoverAboveMax`]: crate::error::CreateTxError::LeftoverAboveMax
+    AddToFee {
+        /// Maximum value added to the fee
+        max_leftover: Amount,
+    },
+    /// Create a change output only if its value is at least `min_change`, otherwise add the
+    /// leftover value to the fee (see [`TxBuilder::min_change`])
+    Threshold {
+        /// Minimum value of the change output
+        min_change: Amount,
+    },
+}
+
+impl LeftoverPolicy {
+    /// Whether a change output of `amount` is kept, given that it's above the dust limit.
+    pub(crate) fn keeps_change(&self, amount: Amount) -> bool {
+        match self {
+            LeftoverPolicy::CreateChange => true,
+            LeftoverPolicy::AddToFee { .. } => false,
+            LeftoverPolicy::Threshold { min_change } => amount >= *min_change,
+        }
+    }
+}
+
 #[cfg_attr(coverage_nightly, coverage(off))]
 #[cfg(test)]
 mod test {
@@ -964,6 +1299,69 @@ mod test {
             .expect("it should have moved the outputs at least once");
     }
 
+    #[test]
+    fn test_output_ordering_shuffle_with_seed() {
+        let original_tx = ordering_test_tx!();
+
+        let mut tx_1 = original_tx.clone();
+        let mut tx_2 = original_tx.clone();
+        TxOrdering::ShuffleWithSeed(42).sort_tx(&mut tx_1);
+        TxOrdering::ShuffleWithSeed(42).sort_tx(&mut tx_2);
+        assert_eq!(tx_1, tx_2);
+
+        (0..40)
+            .find(|&seed| {
+                let mut tx = original_tx.clone();
+                TxOrdering::ShuffleWithSeed(seed).sort_tx(&mut tx);
+                original_tx.input != tx.input && original_tx.output != tx.output
+            })
+            .expect("some seed should have moved the inputs and outputs");
+    }
+
+    #[test]
+    fn test_output_ordering_bip69() {
+        use core::str::FromStr;
+
+        let mut tx = ordering_test_tx!();
+        TxOrdering::Bip69.sort_tx(&mut tx);
+
+        let outpoints = tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outpoints,
+            [
+                "0e53ec5dfb2cb8a71fec32dc9a634a35b7e24799295ddd5278217822e0b31f57:5",
+                "0f60fdd185542f2c6ea19030b0796051e7772b6026dd5ddccd7a2f93b73e6fc2:0",
+                "0f60fdd185542f2c6ea19030b0796051e7772b6026dd5ddccd7a2f93b73e6fc2:1",
+            ]
+            .map(|s| OutPoint::from_str(s).unwrap())
+        );
+        assert_eq!(tx.output[0].value.to_sat(), 800);
+        assert_eq!(tx.output[1].script_pubkey, ScriptBuf::from(vec![0xAA]));
+        assert_eq!(
+            tx.output[2].script_pubkey,
+            ScriptBuf::from(vec![0xAA, 0xEE])
+        );
+
+        // txids are compared in their displayed byte order
+        let txid_a =
+            Txid::from_str("00000000000000000000000000000000000000000000000000000000000000ff")
+                .unwrap();
+        let txid_b =
+            Txid::from_str("ff00000000000000000000000000000000000000000000000000000000000000")
+                .unwrap();
+        let mut tx = ordering_test_tx!();
+        tx.input.truncate(2);
+        tx.input[0].previous_output = OutPoint::new(txid_b, 0);
+        tx.input[1].previous_output = OutPoint::new(txid_a, 1);
+        TxOrdering::Bip69.sort_tx(&mut tx);
+        assert_eq!(tx.input[0].previous_output, OutPoint::new(txid_a, 1));
+        assert_eq!(tx.input[1].previous_output, OutPoint::new(txid_b, 0));
+    }
+
     #[test]
     fn test_output_ordering_custom_but_bip69() {
         use core::str::FromStr;
@@ -1160,6 +1558,8 @@ mod test {
             .network(Network::Regtest)
             .create_wallet_no_persist()
             .unwrap();
+        // A zero fee rate keeps the drained amounts round.
+        wallet.set_min_relay_fee(FeeRate::ZERO);
         let recipient = wallet.next_unused_address(KeychainKind::External).address;
 
         insert_checkpoint(
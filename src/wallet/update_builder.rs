@@ -0,0 +1,206 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Construction of wallet updates for custom chain sources.
+
+use alloc::sync::Arc;
+use core::fmt;
+
+use alloc::vec::Vec;
+use bitcoin::{BlockHash, Transaction, Txid};
+use chain::local_chain::CheckPoint;
+use chain::{BlockId, ConfirmationBlockTime};
+
+use crate::wallet::Update;
+use crate::KeychainKind;
+
+/// A builder of [`Update`]s for chain sources other than the ones supported by the BDK crates.
+///
+/// The builder starts from the wallet's chain tip, returned by [`Wallet::latest_checkpoint`], and
+/// collects the transaction data and the new blocks found by the chain source. The anchors of
+/// the transactions must point to blocks of the resulting chain, which is checked by
+/// [`build`](Self::build).
+///
+/// ```
+/// # use bdk_wallet::bitcoin::{hashes::Hash, BlockHash};
+/// # use bdk_wallet::chain::{BlockId, ConfirmationBlockTime};
+/// # use bdk_wallet::test_utils::*;
+/// # use bdk_wallet::UpdateBuilder;
+/// # let (mut wallet, _) = get_funded_wallet_wpkh();
+/// # let tx = new_tx(0);
+/// let block = BlockId {
+///     height: 2_100,
+///     hash: BlockHash::all_zeros(),
+/// };
+/// let mut builder = UpdateBuilder::new(wallet.latest_checkpoint());
+/// builder
+///     .extend_chain([block])?
+///     .add_tx(
+///         tx,
+///         ConfirmationBlockTime {
+///             block_id: block,
+///             confirmation_time: 1_700_000_000,
+///         },
+///     );
+/// wallet.apply_update(builder.build()?)?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+///
+/// [`Wallet::latest_checkpoint`]: crate::Wallet::latest_checkpoint
+#[derive(Debug, Clone)]
+pub struct UpdateBuilder {
+    tip: CheckPoint,
+    chain_extended: bool,
+    update: Update,
+}
+
+impl UpdateBuilder {
+    /// Create a builder of an update to a wallet whose chain tip is `tip`.
+    pub fn new(tip: CheckPoint) -> Self {
+        Self {
+            tip,
+            chain_extended: false,
+            update: Update::default(),
+        }
+    }
+
+    /// Add a transaction confirmed in the block of `anchor`.
+    pub fn add_tx(
+        &mut self,
+        tx: impl Into<Arc<Transaction>>,
+        anchor: ConfirmationBlockTime,
+    ) -> &mut Self {
+        let tx = tx.into();
+        self.update
+            .tx_update
+            .anchors
+            .insert((anchor, tx.compute_txid()));
+        self.update.tx_update.txs.push(tx);
+        self
+    }
+
+    /// Add a transaction last seen in the mempool at `last_seen`, as a UNIX timestamp.
+    pub fn add_unconfirmed(
+        &mut self,
+        tx: impl Into<Arc<Transaction>>,
+        last_seen: u64,
+    ) -> &mut Self {
+        let tx = tx.into();
+        self.update
+            .tx_update
+            .seen_ats
+            .insert((tx.compute_txid(), last_seen));
+        self.update.tx_update.txs.push(tx);
+        self
+    }
+
+    /// Record that the transaction with `txid` was evicted from the mempool at `evicted_at`, as a
+    /// UNIX timestamp.
+    pub fn add_evicted(&mut self, txid: Txid, evicted_at: u64) -> &mut Self {
+        self.update.tx_update.evicted_ats.insert((txid, evicted_at));
+        self
+    }
+
+    /// Add `blocks` to the chain of the update.
+    ///
+    /// Blocks are inserted in order. A block at the height of an existing block with a different
+    /// hash replaces it and every block above it, as in a reorg.
+    ///
+    /// Fails without adding any block if a block is at height 0 with a different hash than the
+    /// genesis block of the chain, which can't be replaced.
+    pub fn extend_chain(
+        &mut self,
+        blocks: impl IntoIterator<Item = BlockId>,
+    ) -> Result<&mut Self, UpdateBuilderError> {
+        let blocks = blocks.into_iter().collect::<Vec<_>>();
+        if let Some(genesis) = self.tip.get(0) {
+            if let Some(block) = blocks
+                .iter()
+                .find(|block| block.height == 0 && block.hash != genesis.hash())
+            {
+                return Err(UpdateBuilderError::GenesisMismatch {
+                    genesis: genesis.hash(),
+                    got: block.hash,
+                });
+            }
+        }
+        for block in blocks {
+            self.tip = self.tip.clone().insert(block);
+            self.chain_extended = true;
+        }
+        Ok(self)
+    }
+
+    /// Set the highest derivation index of `keychain` with a transaction.
+    ///
+    /// The wallet reveals the addresses of `keychain` up to `index` when the update is applied.
+    pub fn set_last_active_index(&mut self, keychain: KeychainKind, index: u32) -> &mut Self {
+        self.update.last_active_indices.insert(keychain, index);
+        self
+    }
+
+    /// Check the consistency of the update and return it.
+    ///
+    /// Fails if an anchor points to a block not in the chain of the update, i.e. neither one of
+    /// the blocks added with [`extend_chain`](Self::extend_chain), nor one of the wallet's chain.
+    pub fn build(self) -> Result<Update, UpdateBuilderError> {
+        let mut update = self.update;
+        for (anchor, txid) in &update.tx_update.anchors {
+            let block_id = anchor.block_id;
+            if self.tip.get(block_id.height).map(|cp| cp.hash()) != Some(block_id.hash) {
+                return Err(UpdateBuilderError::AnchorNotInChain {
+                    txid: *txid,
+                    block_id,
+                });
+            }
+        }
+        if self.chain_extended {
+            update.chain = Some(self.tip);
+        }
+        Ok(update)
+    }
+}
+
+/// Errors returned by [`UpdateBuilder::extend_chain`] and [`UpdateBuilder::build`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateBuilderError {
+    /// The anchor of a transaction points to a block not in the chain of the update
+    AnchorNotInChain {
+        /// Id of the anchored transaction
+        txid: Txid,
+        /// Block of the anchor
+        block_id: BlockId,
+    },
+    /// A block at height 0 added to the chain is not the genesis block of the chain
+    GenesisMismatch {
+        /// Hash of the genesis block of the chain
+        genesis: BlockHash,
+        /// Hash of the block added at height 0
+        got: BlockHash,
+    },
+}
+
+impl fmt::Display for UpdateBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AnchorNotInChain { txid, block_id } => write!(
+                f,
+                "Transaction {txid} is anchored to block {} at height {}, which is not in the chain",
+                block_id.hash, block_id.height
+            ),
+            Self::GenesisMismatch { genesis, got } => write!(
+                f,
+                "Block {got} at height 0 is not the genesis block {genesis} of the chain"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for UpdateBuilderError {}
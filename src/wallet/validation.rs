@@ -0,0 +1,222 @@
+// Bitcoin Dev Kit
+//
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Validation of chain data
+//!
+//! By default the wallet trusts the blocks and updates it's given by its chain source. When
+//! syncing from an untrusted server, the data can be checked before it's applied by passing
+//! [`ApplyOptions`] to
+//! [`Wallet::apply_block_connected_to_with_options`](crate::Wallet::apply_block_connected_to_with_options)
+//! or [`Wallet::apply_update_with_options`](crate::Wallet::apply_update_with_options):
+//!
+//! - with [`ApplyOptions::validate_pow`], the header of a block must have a valid proof of work
+//!   for the target it claims, within the limit of the wallet's network, and commit to the
+//!   transactions of the block.
+//! - with a [`HeaderProvider`], e.g. a header chain synced from the P2P network, every block an
+//!   update refers to must be part of the provider's chain.
+//!
+//! Note that the difficulty of a header isn't checked against the difficulty adjustment rules,
+//! which requires the headers of the previous blocks.
+//!
+//! ```
+//! # use bdk_wallet::bitcoin::BlockHash;
+//! # use bdk_wallet::chain::BlockId;
+//! use bdk_wallet::validation::{ApplyOptions, HeaderProvider, HeaderProviderError};
+//!
+//! struct HeaderChain(Vec<BlockHash>);
+//!
+//! impl HeaderProvider for HeaderChain {
+//!     fn block_hash(&self, height: u32) -> Result<Option<BlockHash>, HeaderProviderError> {
+//!         Ok(self.0.get(height as usize).copied())
+//!     }
+//! }
+//!
+//! let headers = HeaderChain(vec![]);
+//! let options = ApplyOptions {
+//!     header_provider: Some(&headers),
+//!     ..Default::default()
+//! };
+//! ```
+
+use alloc::boxed::Box;
+use core::fmt;
+
+use bitcoin::{Block, BlockHash, Network};
+use chain::local_chain::{ApplyHeaderError, CannotConnectError};
+use chain::BlockId;
+
+/// Error of a [`HeaderProvider`] failing to look up a block.
+pub type HeaderProviderError = Box<dyn core::error::Error + Send + Sync>;
+
+/// Trait for sources of block headers the wallet checks chain data against.
+pub trait HeaderProvider {
+    /// Returns the hash of the block at `height` in the best chain, or `None` if the chain
+    /// doesn't reach `height`.
+    fn block_hash(&self, height: u32) -> Result<Option<BlockHash>, HeaderProviderError>;
+}
+
+impl<P: HeaderProvider + ?Sized> HeaderProvider for &P {
+    fn block_hash(&self, height: u32) -> Result<Option<BlockHash>, HeaderProviderError> {
+        (**self).block_hash(height)
+    }
+}
+
+impl fmt::Debug for dyn HeaderProvider + Sync + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("dyn HeaderProvider")
+    }
+}
+
+/// Options to validate the chain data applied to the wallet.
+///
+/// The default options don't validate anything, see the [module documentation](self).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApplyOptions<'a> {
+    /// Check the proof of work and the merkle root of the headers of applied blocks.
+    ///
+    /// Default: **false**
+    pub validate_pow: bool,
+    /// Check that applied blocks, and the blocks referred to by the checkpoints and anchors of
+    /// updates, are part of the provider's chain.
+    ///
+    /// Blocks already in the wallet's chain aren't checked again.
+    ///
+    /// Default: **None**
+    pub header_provider: Option<&'a (dyn HeaderProvider + Sync)>,
+}
+
+/// Errors of chain data failing validation.
+#[derive(Debug)]
+pub enum ValidationError {
+    /// The target of the header is above the proof of work limit of the network
+    TargetAboveLimit(BlockId),
+    /// The hash of the header doesn't meet its target
+    InvalidProofOfWork(BlockId),
+    /// The merkle root of the header doesn't commit to the transactions of the block
+    InvalidMerkleRoot(BlockId),
+    /// The block isn't part of the header provider's chain
+    UnknownBlock(BlockId),
+    /// The header provider failed to look up a block
+    HeaderProvider(HeaderProviderError),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TargetAboveLimit(block) => write!(
+                f,
+                "Target of block {} at height {} is above the proof of work limit",
+                block.hash, block.height
+            ),
+            Self::InvalidProofOfWork(block) => write!(
+                f,
+                "Invalid proof of work for block {} at height {}",
+                block.hash, block.height
+            ),
+            Self::InvalidMerkleRoot(block) => write!(
+                f,
+                "Invalid merkle root for block {} at height {}",
+                block.hash, block.height
+            ),
+            Self::UnknownBlock(block) => write!(
+                f,
+                "Block {} at height {} is not part of the header chain",
+                block.hash, block.height
+            ),
+            Self::HeaderProvider(err) => write!(f, "Header provider error: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for ValidationError {}
+
+/// Errors returned when applying validated chain data to the wallet.
+#[derive(Debug)]
+pub enum ApplyError {
+    /// The chain data failed validation
+    Validation(ValidationError),
+    /// The block doesn't connect to the wallet's chain
+    CannotConnect(CannotConnectError),
+    /// The header of the block is inconsistent with the block it's connected to
+    InconsistentBlocks,
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Validation(err) => write!(f, "{err}"),
+            Self::CannotConnect(err) => write!(f, "{err}"),
+            Self::InconsistentBlocks => write!(f, "{}", ApplyHeaderError::InconsistentBlocks),
+        }
+    }
+}
+
+impl core::error::Error for ApplyError {}
+
+impl From<ValidationError> for ApplyError {
+    fn from(err: ValidationError) -> Self {
+        Self::Validation(err)
+    }
+}
+
+impl From<CannotConnectError> for ApplyError {
+    fn from(err: CannotConnectError) -> Self {
+        Self::CannotConnect(err)
+    }
+}
+
+impl From<ApplyHeaderError> for ApplyError {
+    fn from(err: ApplyHeaderError) -> Self {
+        match err {
+            ApplyHeaderError::InconsistentBlocks => Self::InconsistentBlocks,
+            ApplyHeaderError::CannotConnect(err) => Self::CannotConnect(err),
+        }
+    }
+}
+
+/// Check the proof of work and the merkle root of `block`, at `height` in the chain of `network`.
+pub(crate) fn validate_block(
+    block: &Block,
+    height: u32,
+    network: Network,
+) -> Result<(), ValidationError> {
+    let block_id = BlockId {
+        height,
+        hash: block.block_hash(),
+    };
+    let target = block.header.target();
+    if target > network.params().max_attainable_target {
+        return Err(ValidationError::TargetAboveLimit(block_id));
+    }
+    block
+        .header
+        .validate_pow(target)
+        .map_err(|_| ValidationError::InvalidProofOfWork(block_id))?;
+    if !block.check_merkle_root() {
+        return Err(ValidationError::InvalidMerkleRoot(block_id));
+    }
+    Ok(())
+}
+
+/// Check that every block of `blocks` is part of the chain of `provider`.
+pub(crate) fn check_blocks(
+    provider: &(dyn HeaderProvider + Sync),
+    blocks: impl IntoIterator<Item = BlockId>,
+) -> Result<(), ValidationError> {
+    for block in blocks {
+        let hash = provider
+            .block_hash(block.height)
+            .map_err(ValidationError::HeaderProvider)?;
+        if hash != Some(block.hash) {
+            return Err(ValidationError::UnknownBlock(block));
+        }
+    }
+    Ok(())
+}
@@ -1,11 +1,13 @@
 use std::str::FromStr;
 
+use assert_matches::assert_matches;
+use bdk_wallet::error::CreateTxError;
 use bdk_wallet::psbt::PsbtUtils;
 use bdk_wallet::signer::SignOptions;
 use bdk_wallet::test_utils::*;
 use bdk_wallet::tx_builder::AddForeignUtxoError;
 use bdk_wallet::KeychainKind;
-use bitcoin::{psbt, Address, Amount};
+use bitcoin::{hashes::Hash, psbt, Address, Amount, OutPoint, Txid};
 
 mod common;
 
@@ -82,6 +84,63 @@ fn test_add_foreign_utxo() {
     assert!(finished, "all the inputs should have been signed now");
 }
 
+#[test]
+fn test_with_input_order() {
+    let (mut wallet1, txid) = get_funded_wallet_wpkh();
+    let funding_outpoint = OutPoint::new(txid, 0);
+    let outpoint = receive_output_in_latest_block(&mut wallet1, Amount::from_sat(30_000));
+    let (wallet2, _) =
+        get_funded_wallet_single("wpkh(cVbZ8ovhye9AoAHFsqobCf7LxbXDAECy9Kb8TZdfsDYMZGBUyCnm)");
+
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let utxo = wallet2.list_unspent().next().expect("must take!");
+    let foreign_utxo_satisfaction = wallet2
+        .public_descriptor(KeychainKind::External)
+        .max_weight_to_satisfy()
+        .unwrap();
+    let psbt_input = psbt::Input {
+        witness_utxo: Some(utxo.txout.clone()),
+        ..Default::default()
+    };
+
+    // the listed inputs come first, followed by the one added by coin selection
+    let mut builder = wallet1.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(120_000))
+        .only_witness_utxo()
+        .add_utxo(outpoint)
+        .unwrap()
+        .add_foreign_utxo(utxo.outpoint, psbt_input, foreign_utxo_satisfaction)
+        .unwrap()
+        .with_input_order(vec![utxo.outpoint, outpoint]);
+    let psbt = builder.finish().unwrap();
+    let inputs = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|txin| txin.previous_output)
+        .collect::<Vec<_>>();
+    assert_eq!(inputs, vec![utxo.outpoint, outpoint, funding_outpoint]);
+    assert_eq!(
+        psbt.inputs[0].witness_utxo.as_ref(),
+        Some(&utxo.txout),
+        "psbt inputs must follow the order of the transaction"
+    );
+
+    // an outpoint which isn't spent by the transaction is an error
+    let unknown = OutPoint::new(Txid::all_zeros(), 0);
+    let mut builder = wallet1.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .with_input_order(vec![unknown]);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::InputNotSelected(outpoint)) if outpoint == unknown
+    );
+}
+
 #[test]
 fn test_calculate_fee_with_missing_foreign_utxo() {
     use bdk_chain::tx_graph::CalculateFeeError;
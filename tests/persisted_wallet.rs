@@ -5,14 +5,16 @@ use anyhow::Context;
 use assert_matches::assert_matches;
 use bdk_chain::DescriptorId;
 use bdk_chain::{
-    keychain_txout::DEFAULT_LOOKAHEAD, ChainPosition, ConfirmationBlockTime, DescriptorExt,
+    keychain_txout::DEFAULT_LOOKAHEAD, ChainPosition, ConfirmationBlockTime, DescriptorExt, Merge,
 };
 use bdk_wallet::coin_selection::InsufficientFunds;
 use bdk_wallet::descriptor::IntoWalletDescriptor;
 use bdk_wallet::error::CreateTxError;
 use bdk_wallet::test_utils::*;
 use bdk_wallet::{
-    ChangeSet, KeychainKind, LoadError, LoadMismatch, LoadWithPersistError, Wallet, WalletPersister,
+    AsyncWalletPersister, ChangeSet, CreateWithPersistError, EncryptedStore, KeychainKind,
+    LoadError, LoadMismatch, LoadWithPersistError, SqliteError, VersionedChangeSets, Wallet,
+    WalletPersister,
 };
 use bitcoin::constants::ChainHash;
 use bitcoin::hashes::Hash;
@@ -31,6 +33,7 @@ mod common;
 use common::*;
 
 const DB_MAGIC: &[u8] = &[0x21, 0x24, 0x48];
+const DB_KEY: [u8; 32] = [0x42; 32];
 
 #[test]
 fn wallet_is_persisted() -> anyhow::Result<()> {
@@ -217,8 +220,22 @@ fn wallet_is_persisted() -> anyhow::Result<()> {
 
     run(
         "store.db",
-        |path| Ok(bdk_file_store::Store::create(DB_MAGIC, path)?),
-        |path| Ok(bdk_file_store::Store::load(DB_MAGIC, path)?.0),
+        |path| Ok(bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, path)?),
+        |path| Ok(bdk_file_store::Store::<ChangeSet>::load(DB_MAGIC, path)?.0),
+    )?;
+    run(
+        "store.versioned.db",
+        |path| {
+            Ok(bdk_file_store::Store::<VersionedChangeSets>::create(
+                DB_MAGIC, path,
+            )?)
+        },
+        |path| Ok(bdk_file_store::Store::<VersionedChangeSets>::load(DB_MAGIC, path)?.0),
+    )?;
+    run(
+        "store.enc.db",
+        |path| Ok(EncryptedStore::create(DB_MAGIC, path, &DB_KEY)?),
+        |path| Ok(EncryptedStore::load(DB_MAGIC, path, &DB_KEY)?.0),
     )?;
     run::<bdk_chain::rusqlite::Connection, _, _>(
         "store.sqlite",
@@ -455,7 +472,15 @@ fn two_path_descriptor_wallet_persist_and_recover() {
 #[test]
 fn wallet_changeset_is_persisted() {
     persist_wallet_changeset("store.db", |path| {
-        Ok(bdk_file_store::Store::create(DB_MAGIC, path)?)
+        Ok(bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, path)?)
+    });
+    persist_wallet_changeset("store.versioned.db", |path| {
+        Ok(bdk_file_store::Store::<VersionedChangeSets>::create(
+            DB_MAGIC, path,
+        )?)
+    });
+    persist_wallet_changeset("store.enc.db", |path| {
+        Ok(EncryptedStore::create(DB_MAGIC, path, &DB_KEY)?)
     });
     persist_wallet_changeset::<bdk_chain::rusqlite::Connection, _>("store.sqlite", |path| {
         Ok(bdk_chain::rusqlite::Connection::open(path)?)
@@ -465,7 +490,12 @@ fn wallet_changeset_is_persisted() {
 #[test]
 fn keychains_are_persisted() {
     persist_keychains("store.db", |path| {
-        Ok(bdk_file_store::Store::create(DB_MAGIC, path)?)
+        Ok(bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, path)?)
+    });
+    persist_keychains("store.versioned.db", |path| {
+        Ok(bdk_file_store::Store::<VersionedChangeSets>::create(
+            DB_MAGIC, path,
+        )?)
     });
     persist_keychains::<bdk_chain::rusqlite::Connection, _>("store.sqlite", |path| {
         Ok(bdk_chain::rusqlite::Connection::open(path)?)
@@ -475,7 +505,12 @@ fn keychains_are_persisted() {
 #[test]
 fn single_keychain_is_persisted() {
     persist_single_keychain("store.db", |path| {
-        Ok(bdk_file_store::Store::create(DB_MAGIC, path)?)
+        Ok(bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, path)?)
+    });
+    persist_single_keychain("store.versioned.db", |path| {
+        Ok(bdk_file_store::Store::<VersionedChangeSets>::create(
+            DB_MAGIC, path,
+        )?)
     });
     persist_single_keychain::<bdk_chain::rusqlite::Connection, _>("store.sqlite", |path| {
         Ok(bdk_chain::rusqlite::Connection::open(path)?)
@@ -485,7 +520,12 @@ fn single_keychain_is_persisted() {
 #[test]
 fn network_is_persisted() {
     persist_network("store.db", |path| {
-        Ok(bdk_file_store::Store::create(DB_MAGIC, path)?)
+        Ok(bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, path)?)
+    });
+    persist_network("store.versioned.db", |path| {
+        Ok(bdk_file_store::Store::<VersionedChangeSets>::create(
+            DB_MAGIC, path,
+        )?)
     });
     persist_network::<bdk_chain::rusqlite::Connection, _>("store.sqlite", |path| {
         Ok(bdk_chain::rusqlite::Connection::open(path)?)
@@ -575,3 +615,1914 @@ fn test_lock_outpoint_persist() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_output_label_persist() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Signet)
+        .create_wallet(&mut conn)?;
+
+    let labelled = receive_output(&mut wallet, Amount::from_sat(10_000), ReceiveTo::Mempool(0));
+    let removed = receive_output(&mut wallet, Amount::from_sat(20_000), ReceiveTo::Mempool(1));
+    wallet.set_output_label(labelled, "savings");
+    wallet.set_output_label(removed, "to be removed");
+    wallet.persist(&mut conn)?;
+
+    wallet = Wallet::load()
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert_eq!(wallet.output_label(labelled), Some("savings"));
+    assert_eq!(wallet.output_label(removed), Some("to be removed"));
+
+    // Test: removed labels are deleted
+    assert!(wallet.remove_output_label(removed).is_some());
+    wallet.persist(&mut conn)?;
+
+    wallet = Wallet::load()
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert_eq!(
+        wallet.list_output_labels().collect::<Vec<_>>(),
+        vec![(labelled, "savings")]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_metadata_persist() -> anyhow::Result<()> {
+    use bdk_wallet::metadata::{MetadataError, MAX_KEY_LEN, MAX_VALUE_LEN};
+
+    fn run<Db, CreateDb, OpenDb>(
+        filename: &str,
+        create_db: CreateDb,
+        open_db: OpenDb,
+    ) -> anyhow::Result<()>
+    where
+        CreateDb: Fn(&Path) -> anyhow::Result<Db>,
+        OpenDb: Fn(&Path) -> anyhow::Result<Db>,
+        Db: WalletPersister,
+        Db::Error: core::error::Error + Send + Sync + 'static,
+    {
+        let temp_dir = tempfile::tempdir().expect("must create tempdir");
+        let file_path = temp_dir.path().join(filename);
+        let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+
+        {
+            let mut db = create_db(&file_path)?;
+            let mut wallet = Wallet::create(desc, change_desc)
+                .network(Network::Signet)
+                .create_wallet(&mut db)?;
+            wallet.set_meta("fee_target", vec![6])?;
+            wallet.set_meta("last_backup", 1_755_317_760u64.to_le_bytes().to_vec())?;
+            wallet.set_meta("to_be_deleted", b"value".to_vec())?;
+            assert_eq!(
+                wallet.set_meta(&"k".repeat(MAX_KEY_LEN + 1), vec![]),
+                Err(MetadataError::KeyTooLong(MAX_KEY_LEN + 1))
+            );
+            assert_eq!(
+                wallet.set_meta("too_large", vec![0; MAX_VALUE_LEN + 1]),
+                Err(MetadataError::ValueTooLarge(MAX_VALUE_LEN + 1))
+            );
+            wallet.persist(&mut db)?;
+        }
+
+        {
+            let mut db = open_db(&file_path)?;
+            let mut wallet = Wallet::load()
+                .load_wallet(&mut db)?
+                .expect("wallet is persisted");
+            assert_eq!(wallet.get_meta("fee_target"), Some(&[6][..]));
+            assert_eq!(
+                wallet.get_meta("last_backup"),
+                Some(&1_755_317_760u64.to_le_bytes()[..])
+            );
+            assert_eq!(wallet.get_meta("too_large"), None);
+
+            // Test: values are replaced and deleted keys are removed
+            wallet.set_meta("fee_target", vec![3])?;
+            assert_eq!(wallet.delete_meta("to_be_deleted"), Some(b"value".to_vec()));
+            assert_eq!(wallet.delete_meta("to_be_deleted"), None);
+            wallet.persist(&mut db)?;
+        }
+
+        let mut db = open_db(&file_path)?;
+        let wallet = Wallet::load()
+            .load_wallet(&mut db)?
+            .expect("wallet is persisted");
+        assert_eq!(wallet.get_meta("fee_target"), Some(&[3][..]));
+        assert_eq!(wallet.get_meta("to_be_deleted"), None);
+
+        Ok(())
+    }
+
+    run(
+        "store.db",
+        |path| Ok(bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, path)?),
+        |path| Ok(bdk_file_store::Store::<ChangeSet>::load(DB_MAGIC, path)?.0),
+    )?;
+    run(
+        "store.sqlite",
+        |path| Ok(bdk_chain::rusqlite::Connection::open(path)?),
+        |path| Ok(bdk_chain::rusqlite::Connection::open(path)?),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_file_store_corruption() -> anyhow::Result<()> {
+    use bdk_wallet::integrity::{load_store, Corruption, LoadStoreError, OnCorruption};
+    use std::fs;
+
+    let temp_dir = tempfile::tempdir()?;
+    let file_path = temp_dir.path().join("store.db");
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+
+    // a store of 4 entries, the first one recording the changeset version, the offset of each
+    // one is the length of the file before it
+    let mut offsets = vec![DB_MAGIC.len() as u64];
+    {
+        let mut store = bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, &file_path)?;
+        WalletPersister::initialize(&mut store)?;
+        offsets.push(fs::metadata(&file_path)?.len());
+        let mut wallet = Wallet::create(desc, change_desc)
+            .network(Network::Signet)
+            .create_wallet(&mut store)?;
+        offsets.push(fs::metadata(&file_path)?.len());
+        wallet.reveal_next_address(KeychainKind::External);
+        wallet.persist(&mut store)?;
+        offsets.push(fs::metadata(&file_path)?.len());
+        wallet.set_meta("key", b"value".to_vec())?;
+        wallet.persist(&mut store)?;
+    }
+    let contents = fs::read(&file_path)?;
+    let file_len = contents.len() as u64;
+
+    let (_, report) = load_store(DB_MAGIC, &file_path, OnCorruption::Fail)?;
+    assert!(report.is_empty());
+
+    // Test: garbage in the middle of the file is reported at the entry it starts
+    let mut garbage = contents.clone();
+    // the tag of the first field of the entry, an option, can't be 7
+    garbage[offsets[1] as usize] = 7;
+    fs::write(&file_path, &garbage)?;
+    match load_store(DB_MAGIC, &file_path, OnCorruption::Fail) {
+        Err(LoadStoreError::Corrupted(report)) => assert_matches!(
+            report.corruptions.as_slice(),
+            [Corruption::InvalidEntry { index: 1, offset, file_len: len, .. }]
+                if *offset == offsets[1] && *len == file_len
+        ),
+        res => panic!("unexpected result: {:?}", res.map(|(_, report)| report)),
+    }
+    assert_eq!(
+        fs::read(&file_path)?,
+        garbage,
+        "Fail must not modify the store"
+    );
+
+    // Test: a partially written last entry is truncated on recovery
+    let truncated = &contents[..contents.len() - 2];
+    fs::write(&file_path, truncated)?;
+    let (mut store, report) = load_store(DB_MAGIC, &file_path, OnCorruption::Recover)?;
+    assert_matches!(
+        report.corruptions.as_slice(),
+        [Corruption::InvalidEntry { index: 3, offset, file_len: len, .. }]
+            if *offset == offsets[3] && *len == file_len - 2
+    );
+    assert_eq!(fs::metadata(&file_path)?.len(), offsets[3]);
+
+    // the store can be appended to again
+    let mut wallet = Wallet::load()
+        .on_corruption(OnCorruption::Fail)
+        .load_wallet(&mut store)?
+        .expect("wallet is persisted");
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(0));
+    assert_eq!(wallet.get_meta("key"), None);
+    wallet.set_meta("key", b"again".to_vec())?;
+    wallet.persist(&mut store)?;
+    drop(store);
+
+    let (mut store, report) = load_store(DB_MAGIC, &file_path, OnCorruption::Fail)?;
+    assert!(report.is_empty());
+    let wallet = Wallet::load()
+        .load_wallet(&mut store)?
+        .expect("wallet is persisted");
+    assert_eq!(wallet.get_meta("key"), Some(&b"again"[..]));
+
+    Ok(())
+}
+
+#[test]
+fn test_sqlite_corruption() -> anyhow::Result<()> {
+    use bdk_chain::{local_chain, rusqlite, tx_graph, BlockId};
+    use bdk_wallet::integrity::{Corruption, OnCorruption};
+
+    type TxGraphChangeSet = tx_graph::ChangeSet<ConfirmationBlockTime>;
+
+    let temp_dir = tempfile::tempdir()?;
+    let file_path = temp_dir.path().join("wallet.sqlite");
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+
+    let block = |height| BlockId {
+        height,
+        hash: BlockHash::hash(&u32::to_le_bytes(height)),
+    };
+    let (missing_tx, reorged) = {
+        let mut conn = rusqlite::Connection::open(&file_path)?;
+        let mut wallet = Wallet::create(desc, change_desc)
+            .network(Network::Signet)
+            .create_wallet(&mut conn)?;
+        insert_checkpoint(&mut wallet, block(100));
+        insert_checkpoint(&mut wallet, block(200));
+        let missing_tx = receive_output(
+            &mut wallet,
+            Amount::from_sat(10_000),
+            ConfirmationBlockTime {
+                block_id: block(100),
+                confirmation_time: 100,
+            },
+        );
+        let reorged = receive_output_in_latest_block(&mut wallet, Amount::from_sat(20_000));
+        wallet.persist(&mut conn)?;
+        (missing_tx, reorged)
+    };
+
+    let mut conn = rusqlite::Connection::open(&file_path)?;
+    let wallet = Wallet::load()
+        .on_corruption(OnCorruption::Fail)
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert!(wallet.corruption_report().is_empty());
+    drop(wallet);
+
+    // Corrupt the database: a tx of an anchor is deleted, the last block is replaced without
+    // its anchors, and a floating txout contradicts its tx.
+    conn.pragma_update(None, "foreign_keys", false)?;
+    conn.execute(
+        &format!(
+            "DELETE FROM {} WHERE txid = ?1",
+            TxGraphChangeSet::TXS_TABLE_NAME
+        ),
+        [missing_tx.txid.to_string()],
+    )?;
+    let reorged_hash = BlockHash::hash(b"reorged");
+    conn.execute(
+        &format!(
+            "UPDATE {} SET block_hash = ?1 WHERE block_height = 200",
+            local_chain::ChangeSet::BLOCKS_TABLE_NAME
+        ),
+        [reorged_hash.to_string()],
+    )?;
+    conn.execute(
+        &format!(
+            "INSERT INTO {}(txid, vout, value, script) VALUES(?1, 0, 1, x'')",
+            TxGraphChangeSet::TXOUTS_TABLE_NAME
+        ),
+        [reorged.txid.to_string()],
+    )?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    let expected = vec![
+        Corruption::MissingAnchorTx {
+            txid: missing_tx.txid,
+            block: block(100),
+        },
+        Corruption::AnchorBlockMismatch {
+            txid: reorged.txid,
+            block: block(200),
+            chain_hash: reorged_hash,
+        },
+        Corruption::TxOutMismatch(reorged),
+    ];
+
+    // Test: without checks the wallet silently loses the confirmation of the tx
+    let wallet = Wallet::load()
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert!(wallet.corruption_report().is_empty());
+    assert!(!wallet
+        .get_tx(reorged.txid)
+        .expect("tx is in the wallet")
+        .chain_position
+        .is_confirmed());
+
+    assert_matches!(
+        Wallet::load()
+            .on_corruption(OnCorruption::Fail)
+            .load_wallet(&mut conn),
+        Err(LoadWithPersistError::Corrupted(report)) if report.corruptions == expected
+    );
+
+    // Test: recovery leaves the corrupted data out, and a read-only load reports it too
+    for read_only in [false, true] {
+        let mut params = Wallet::load().on_corruption(OnCorruption::Recover);
+        if read_only {
+            params = params.read_only();
+        }
+        let wallet = params.load_wallet(&mut conn)?.expect("wallet is persisted");
+        assert_eq!(wallet.corruption_report().corruptions, expected);
+        assert!(wallet.get_tx(missing_tx.txid).is_none());
+        assert_eq!(
+            wallet
+                .tx_graph()
+                .get_txout(reorged)
+                .map(|txout| txout.value),
+            Some(Amount::from_sat(20_000))
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_anchor_above_tip_is_not_corruption() -> anyhow::Result<()> {
+    use bdk_chain::{rusqlite, BlockId};
+    use bdk_wallet::integrity::OnCorruption;
+
+    let block = |height| BlockId {
+        height,
+        hash: BlockHash::hash(&u32::to_le_bytes(height)),
+    };
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Signet)
+        .create_wallet(&mut conn)?;
+    insert_checkpoint(&mut wallet, block(100));
+    // an update without a chain update anchors the tx above the tip of the wallet
+    let outpoint = receive_output(
+        &mut wallet,
+        Amount::from_sat(10_000),
+        ConfirmationBlockTime {
+            block_id: block(300),
+            confirmation_time: 300,
+        },
+    );
+    wallet.persist(&mut conn)?;
+
+    let wallet = Wallet::load()
+        .on_corruption(OnCorruption::Fail)
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert!(wallet.corruption_report().is_empty());
+
+    let mut wallet = Wallet::load()
+        .on_corruption(OnCorruption::Recover)
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert!(wallet.corruption_report().is_empty());
+    assert!(wallet
+        .tx_graph()
+        .all_anchors()
+        .get(&outpoint.txid)
+        .is_some_and(|anchors| anchors.iter().any(|a| a.block_id == block(300))));
+
+    // the tx is confirmed once the chain of the wallet reaches its block
+    insert_checkpoint(&mut wallet, block(300));
+    assert!(wallet
+        .get_tx(outpoint.txid)
+        .expect("tx is in the wallet")
+        .chain_position
+        .is_confirmed());
+
+    Ok(())
+}
+
+#[test]
+fn wallet_load_with_signer() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+    use bdk_wallet::signer::{SignerContext, SignerOrdering, SignerWrapper};
+    use bdk_wallet::SignOptions;
+    use bitcoin::PrivateKey;
+    use std::sync::Arc;
+
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    let secp = Secp256k1::new();
+    let key = PrivateKey::from_wif("cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW")?;
+    let desc = format!("wpkh({})", key.public_key(&secp));
+    let signer = Arc::new(SignerWrapper::new(key, SignerContext::Segwitv0));
+
+    let mut wallet = Wallet::create_single(desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut conn)?;
+    receive_output(&mut wallet, Amount::from_sat(25_000), ReceiveTo::Mempool(0));
+    wallet.persist(&mut conn)?;
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+
+    // Without the signer the loaded wallet is watch-only
+    let mut wallet = Wallet::load()
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let mut psbt = builder.finish()?;
+    assert!(!wallet.sign(&mut psbt.clone(), SignOptions::default())?);
+
+    let wallet = Wallet::load()
+        .add_signer(KeychainKind::External, SignerOrdering::default(), signer)
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert_eq!(
+        wallet.get_signers(KeychainKind::External).signers().len(),
+        1
+    );
+    assert!(wallet.sign(&mut psbt, SignOptions::default())?);
+
+    Ok(())
+}
+
+#[test]
+fn test_sqlite_wallets_with_ids() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+    use bdk_wallet::sqlite_wallets::{
+        delete_wallet, list_wallet_ids, SqliteWalletStore, COMPACTION_THRESHOLD,
+    };
+    use bdk_wallet::CreateWithPersistError;
+
+    let temp_dir = tempfile::tempdir()?;
+    let file_path = temp_dir.path().join("wallets.sqlite");
+    let mut conn = rusqlite::Connection::open(&file_path)?;
+    assert!(list_wallet_ids(&conn)?.is_empty());
+    let store = |wallet_id| -> rusqlite::Result<SqliteWalletStore> {
+        Ok(SqliteWalletStore::new(
+            rusqlite::Connection::open(&file_path)?,
+            wallet_id,
+        ))
+    };
+    let mut alice_store = store("alice")?;
+    let mut bob_store = store("bob")?;
+
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut alice = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut alice_store)?;
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut bob = Wallet::create(desc, change_desc)
+        .network(Network::Signet)
+        .create_wallet(&mut bob_store)?;
+    assert_matches!(
+        Wallet::create(desc, change_desc).create_wallet(&mut bob_store),
+        Err(CreateWithPersistError::DataAlreadyExists(_))
+    );
+    assert_eq!(list_wallet_ids(&conn)?, ["alice", "bob"]);
+
+    // interleave the changes of both wallets
+    for _ in 0..3 {
+        alice.reveal_next_address(KeychainKind::External);
+        assert!(alice.persist(&mut alice_store)?);
+        bob.reveal_next_address(KeychainKind::Internal);
+        assert!(bob.persist(&mut bob_store)?);
+    }
+    assert!(!bob.persist(&mut bob_store)?);
+    let rows = |conn: &rusqlite::Connection, wallet_id: &str| -> rusqlite::Result<usize> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM bdk_wallet_changesets WHERE wallet_id = ?1",
+            [wallet_id],
+            |row| row.get(0),
+        )
+    };
+    // persisting appends a row, loading leaves the rows as they are below the threshold
+    assert_eq!(rows(&conn, "alice")?, 4);
+    let mut alice = Wallet::load()
+        .load_wallet(&mut alice_store)?
+        .expect("wallet is persisted");
+    assert_eq!(rows(&conn, "alice")?, 4);
+    assert_eq!(rows(&conn, "bob")?, 4);
+
+    // the rows are merged once there are too many
+    for _ in 4..COMPACTION_THRESHOLD {
+        alice.reveal_next_address(KeychainKind::Internal);
+        assert!(alice.persist(&mut alice_store)?);
+    }
+    assert_eq!(rows(&conn, "alice")?, 1);
+    let expected = COMPACTION_THRESHOLD as u32 - 5;
+    assert_eq!(
+        alice.derivation_index(KeychainKind::Internal),
+        Some(expected)
+    );
+    alice.reveal_next_address(KeychainKind::Internal);
+    assert!(alice.persist(&mut alice_store)?);
+    assert_eq!(rows(&conn, "alice")?, 2);
+
+    let alice = Wallet::load()
+        .load_wallet(&mut alice_store)?
+        .expect("wallet is persisted");
+    assert_eq!(rows(&conn, "alice")?, 2);
+    assert_eq!(
+        alice.derivation_index(KeychainKind::Internal),
+        Some(expected + 1)
+    );
+    assert_eq!(alice.network(), Network::Testnet);
+    assert_eq!(alice.derivation_index(KeychainKind::External), Some(2));
+
+    // rows left over the threshold, e.g. by concurrent writers, are merged when loading
+    while rows(&conn, "bob")? < COMPACTION_THRESHOLD {
+        conn.execute(
+            "INSERT INTO bdk_wallet_changesets(wallet_id, changeset, version) \
+             SELECT wallet_id, changeset, version FROM bdk_wallet_changesets \
+             WHERE wallet_id = 'bob'",
+            [],
+        )?;
+    }
+    let bob = Wallet::load()
+        .load_wallet(&mut bob_store)?
+        .expect("wallet is persisted");
+    assert_eq!(rows(&conn, "bob")?, 1);
+    assert_eq!(bob.network(), Network::Signet);
+    assert_eq!(bob.derivation_index(KeychainKind::External), None);
+    assert_eq!(bob.derivation_index(KeychainKind::Internal), Some(2));
+
+    // the unscoped tables are not used
+    assert!(Wallet::load().load_wallet(&mut conn)?.is_none());
+
+    assert!(delete_wallet(&mut conn, "alice")?);
+    assert!(!delete_wallet(&mut conn, "alice")?);
+    assert!(Wallet::load().load_wallet(&mut alice_store)?.is_none());
+    assert_eq!(list_wallet_ids(&conn)?, ["bob"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_sqlite_wallets_migrate_single_wallet() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+    use bdk_wallet::sqlite_wallets::{list_wallet_ids, SqliteWalletStore, DEFAULT_WALLET_ID};
+
+    let temp_dir = tempfile::tempdir()?;
+    let file_path = temp_dir.path().join("wallet.sqlite");
+    let mut conn = rusqlite::Connection::open(&file_path)?;
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut conn)?;
+    let address = wallet.reveal_next_address(KeychainKind::External);
+    wallet.persist(&mut conn)?;
+    let single_wallet_tables = |conn: &rusqlite::Connection| -> rusqlite::Result<Vec<String>> {
+        conn.prepare(
+            "SELECT sql FROM sqlite_master \
+             WHERE name GLOB 'bdk_*' AND name NOT GLOB 'bdk_wallet_changesets*' \
+             UNION ALL SELECT name || version FROM bdk_schemas \
+             WHERE name != 'bdk_wallet_changesets' ORDER BY 1",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect()
+    };
+    let tables = single_wallet_tables(&conn)?;
+
+    // listing the wallets doesn't migrate them
+    assert_eq!(list_wallet_ids(&conn)?, [DEFAULT_WALLET_ID]);
+    let migrated: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'bdk_wallet_changesets')",
+        [],
+        |row| row.get(0),
+    )?;
+    assert!(!migrated);
+    let mut store =
+        SqliteWalletStore::new(rusqlite::Connection::open(&file_path)?, DEFAULT_WALLET_ID);
+    let mut wallet = Wallet::load()
+        .descriptor(KeychainKind::External, Some(desc))
+        .load_wallet(&mut store)?
+        .expect("wallet is migrated");
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::External),
+        Some(address.index)
+    );
+
+    // the migration only happens once, and leaves the single wallet tables untouched
+    wallet.reveal_next_address(KeychainKind::External);
+    wallet.persist(&mut store)?;
+    let wallet = Wallet::load()
+        .load_wallet(&mut store)?
+        .expect("wallet is persisted");
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::External),
+        Some(address.index + 1)
+    );
+    assert_eq!(single_wallet_tables(&conn)?, tables);
+    let wallet = Wallet::load()
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::External),
+        Some(address.index)
+    );
+
+    // single wallet tables of a newer version are not migrated
+    let file_path = temp_dir.path().join("newer.sqlite");
+    let mut conn = rusqlite::Connection::open(&file_path)?;
+    Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut conn)?;
+    conn.execute(
+        "UPDATE bdk_schemas SET version=version+1 WHERE name='bdk_wallet'",
+        [],
+    )?;
+    let mut store =
+        SqliteWalletStore::new(rusqlite::Connection::open(&file_path)?, DEFAULT_WALLET_ID);
+    assert_matches!(
+        Wallet::load().load_wallet(&mut store),
+        Err(LoadWithPersistError::Persist(SqliteError::NewerSchema(_)))
+    );
+    let migrated: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'bdk_wallet_changesets')",
+        [],
+        |row| row.get(0),
+    )?;
+    assert!(!migrated);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_sqlite_persister() -> anyhow::Result<()> {
+    use bdk_wallet::{rusqlite, tokio_rusqlite};
+
+    let temp_dir = tempfile::tempdir().expect("must create tempdir");
+    let file_path = temp_dir.path().join("store.sqlite");
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+
+    let mut conn = tokio_rusqlite::Connection::open(&file_path).await?;
+    assert!(Wallet::load().load_wallet_async(&mut conn).await?.is_none());
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet_async(&mut conn)
+        .await?;
+    for _ in 0..3 {
+        wallet.reveal_next_address(KeychainKind::External);
+        assert!(wallet.persist_async(&mut conn).await?);
+    }
+    assert!(!wallet.persist_async(&mut conn).await?);
+
+    // the database is readable by the blocking backend and vice versa
+    let mut blocking_conn = rusqlite::Connection::open(&file_path)?;
+    let mut wallet = Wallet::load()
+        .load_wallet(&mut blocking_conn)?
+        .expect("wallet is persisted");
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(2));
+    wallet.reveal_next_address(KeychainKind::Internal);
+    assert!(wallet.persist(&mut blocking_conn)?);
+    drop(blocking_conn);
+
+    let wallet = Wallet::load()
+        .load_wallet_async(&mut conn)
+        .await?
+        .expect("wallet is persisted");
+    assert_eq!(wallet.network(), Network::Testnet);
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(2));
+    assert_eq!(wallet.derivation_index(KeychainKind::Internal), Some(0));
+
+    Ok(())
+}
+
+#[test]
+fn test_changeset_json_and_persister_migration() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+    use bdk_wallet::migration::{migrate_persister, PersisterMigrationError};
+    use bdk_wallet::ChangeSetJsonError;
+
+    let temp_dir = tempfile::tempdir().expect("must create tempdir");
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let (mut wallet, _) = get_funded_wallet(desc, change_desc);
+    wallet.reveal_next_address(KeychainKind::External);
+    let outpoint = wallet.list_unspent().next().unwrap().outpoint;
+    wallet.lock_outpoint(outpoint);
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    WalletPersister::initialize(&mut conn)?;
+    WalletPersister::persist(&mut conn, &wallet.take_staged().unwrap())?;
+
+    // sqlite -> json -> file store
+    let changeset = WalletPersister::initialize(&mut conn)?;
+    let json = changeset.to_json();
+    assert_eq!(ChangeSet::from_json(&json)?, changeset);
+    let file_path = temp_dir.path().join("json.db");
+    let mut store = bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, &file_path)?;
+    WalletPersister::initialize(&mut store)?;
+    WalletPersister::persist(&mut store, &ChangeSet::from_json(&json)?)?;
+    drop(store);
+    let mut store = bdk_file_store::Store::<ChangeSet>::load(DB_MAGIC, &file_path)?.0;
+    let loaded = Wallet::load()
+        .load_wallet(&mut store)?
+        .expect("wallet is persisted");
+    assert_eq!(loaded.network(), wallet.network());
+    assert!(loaded.keychains().eq(wallet.keychains()));
+    assert_eq!(loaded.latest_checkpoint(), wallet.latest_checkpoint());
+    for keychain in [KeychainKind::External, KeychainKind::Internal] {
+        assert_eq!(
+            loaded.derivation_index(keychain),
+            wallet.derivation_index(keychain)
+        );
+    }
+    assert_eq!(
+        loaded
+            .transactions()
+            .map(|tx| tx.tx_node.txid)
+            .collect::<BTreeSet<_>>(),
+        wallet
+            .transactions()
+            .map(|tx| tx.tx_node.txid)
+            .collect::<BTreeSet<_>>()
+    );
+    assert!(loaded.list_unspent().eq(wallet.list_unspent()));
+    assert!(loaded
+        .list_locked_outpoints()
+        .eq(wallet.list_locked_outpoints()));
+    assert_eq!(loaded.balance(), wallet.balance());
+
+    assert_matches!(
+        ChangeSet::from_json(&json.replacen("\"version\": 1", "\"version\": 2", 1)),
+        Err(ChangeSetJsonError::UnsupportedVersion(2))
+    );
+
+    // sqlite -> file store
+    let file_path = temp_dir.path().join("migrated.db");
+    let mut store = bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, &file_path)?;
+    migrate_persister(&mut conn, &mut store)?;
+    let mut store = bdk_file_store::Store::<ChangeSet>::load(DB_MAGIC, &file_path)?.0;
+    let loaded = Wallet::load()
+        .load_wallet(&mut store)?
+        .expect("wallet is persisted");
+    assert_eq!(loaded.balance(), wallet.balance());
+    assert_matches!(
+        migrate_persister(&mut conn, &mut store),
+        Err(PersisterMigrationError::TargetNotEmpty)
+    );
+    let mut empty = rusqlite::Connection::open_in_memory()?;
+    assert_matches!(
+        migrate_persister(&mut empty, &mut rusqlite::Connection::open_in_memory()?),
+        Err(PersisterMigrationError::SourceEmpty)
+    );
+
+    Ok(())
+}
+
+fn postgres_pool(url: &str) -> anyhow::Result<bdk_wallet::postgres::deadpool_postgres::Pool> {
+    use bdk_wallet::postgres::deadpool_postgres::{tokio_postgres, Manager, Pool};
+    use std::str::FromStr;
+
+    let config = tokio_postgres::Config::from_str(url)?;
+    let manager = Manager::new(config, tokio_postgres::NoTls);
+    Ok(Pool::builder(manager).max_size(2).build()?)
+}
+
+/// Runs against the database at `BDK_POSTGRES_URL`, and is skipped when it is not set.
+#[tokio::test]
+async fn test_postgres_persister() -> anyhow::Result<()> {
+    use bdk_wallet::postgres::{PostgresStore, COMPACTION_THRESHOLD};
+
+    let Ok(url) = std::env::var("BDK_POSTGRES_URL") else {
+        return Ok(());
+    };
+    let pool = postgres_pool(&url)?;
+    let wallet_id = format!("test-{}", rand::random::<u64>());
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+
+    let mut store = PostgresStore::new(pool.clone(), wallet_id.clone());
+    assert!(Wallet::load()
+        .load_wallet_async(&mut store)
+        .await?
+        .is_none());
+    let (mut wallet, _) = get_funded_wallet(desc, change_desc);
+    let changeset = wallet.take_staged().expect("wallet has changes");
+    AsyncWalletPersister::persist(&mut store, &changeset).await?;
+    // persisting the same changeset again, as after a lost commit, is harmless
+    AsyncWalletPersister::persist(&mut store, &changeset).await?;
+    wallet.reveal_next_address(KeychainKind::External);
+    let changeset = wallet.take_staged().expect("address is revealed");
+    AsyncWalletPersister::persist(&mut store, &changeset).await?;
+
+    // other wallets of the database are independent
+    let mut other = PostgresStore::new(pool.clone(), format!("{wallet_id}-other"));
+    assert!(Wallet::load()
+        .load_wallet_async(&mut other)
+        .await?
+        .is_none());
+
+    let mut store = PostgresStore::new(pool.clone(), wallet_id.clone());
+    let loaded = Wallet::load()
+        .load_wallet_async(&mut store)
+        .await?
+        .expect("wallet is persisted");
+    assert_eq!(loaded.network(), wallet.network());
+    assert_eq!(loaded.latest_checkpoint(), wallet.latest_checkpoint());
+    assert_eq!(
+        loaded.derivation_index(KeychainKind::External),
+        wallet.derivation_index(KeychainKind::External)
+    );
+    assert_eq!(loaded.balance(), wallet.balance());
+
+    // the rows of a wallet are merged once there are too many
+    let rows = || async {
+        let count: i64 = pool
+            .get()
+            .await?
+            .query_one(
+                "SELECT COUNT(*) FROM bdk_wallet_changesets WHERE wallet_id = $1",
+                &[&wallet_id],
+            )
+            .await?
+            .try_get(0)?;
+        anyhow::Ok(count as usize)
+    };
+    assert_eq!(rows().await?, 3);
+    for _ in 3..COMPACTION_THRESHOLD {
+        wallet.reveal_next_address(KeychainKind::Internal);
+        let changeset = wallet.take_staged().expect("address is revealed");
+        AsyncWalletPersister::persist(&mut store, &changeset).await?;
+    }
+    assert_eq!(rows().await?, 1);
+    let loaded = Wallet::load()
+        .load_wallet_async(&mut store)
+        .await?
+        .expect("wallet is persisted");
+    assert_eq!(
+        loaded.derivation_index(KeychainKind::Internal),
+        wallet.derivation_index(KeychainKind::Internal)
+    );
+    assert_eq!(loaded.balance(), wallet.balance());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_postgres_persister_connection_error() -> anyhow::Result<()> {
+    use bdk_wallet::postgres::{PostgresError, PostgresStore};
+
+    // nothing listens on the discard port
+    let mut store = PostgresStore::new(postgres_pool("postgres://localhost:9/bdk")?, "test");
+    assert_matches!(
+        Wallet::load().load_wallet_async(&mut store).await,
+        Err(LoadWithPersistError::Persist(PostgresError::Pool(_)))
+    );
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    assert_matches!(
+        Wallet::create(desc, change_desc)
+            .network(Network::Testnet)
+            .create_wallet_async(&mut store)
+            .await,
+        Err(CreateWithPersistError::Persist(PostgresError::Pool(_)))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_error_keeps_staged_changes() -> anyhow::Result<()> {
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+
+    // creation fails when its first persist fails
+    let mut persister = FlakyPersister::new().fail_persist(1);
+    assert_matches!(
+        Wallet::create(desc, change_desc)
+            .network(Network::Testnet)
+            .create_wallet(&mut persister),
+        Err(CreateWithPersistError::Persist(
+            FlakyPersisterError::Persist(1)
+        ))
+    );
+    assert!(persister.changeset().is_empty());
+    assert_matches!(
+        Wallet::load().load_wallet(&mut FlakyPersister::new().fail_initialize()),
+        Err(LoadWithPersistError::Persist(
+            FlakyPersisterError::Initialize
+        ))
+    );
+
+    let mut persister = FlakyPersister::new().fail_persist(2);
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut persister)?;
+    wallet.reveal_next_address(KeychainKind::External);
+    let staged = wallet.staged().cloned().expect("address is revealed");
+    assert_eq!(
+        wallet.persist(&mut persister),
+        Err(FlakyPersisterError::Persist(2))
+    );
+    assert_eq!(wallet.staged(), Some(&staged));
+    assert_eq!(persister.received().last(), Some(&staged));
+
+    // the retry writes the failed changes together with the new ones
+    wallet.reveal_next_address(KeychainKind::Internal);
+    assert!(wallet.persist(&mut persister)?);
+    assert!(wallet.staged().is_none());
+    let retried = persister.received().last().unwrap();
+    assert_eq!(
+        retried.indexer.last_revealed.len(),
+        2,
+        "both keychains are persisted"
+    );
+    let loaded = Wallet::load()
+        .load_wallet(&mut persister)?
+        .expect("wallet is persisted");
+    assert_eq!(loaded.derivation_index(KeychainKind::External), Some(0));
+    assert_eq!(loaded.derivation_index(KeychainKind::Internal), Some(0));
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_error_after_partial_write() -> anyhow::Result<()> {
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut persister = FlakyPersister::new().fail_persist(2).partially_apply();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut persister)?;
+    let _ = wallet.reveal_addresses_to(KeychainKind::External, 2);
+    assert_eq!(
+        wallet.persist(&mut persister),
+        Err(FlakyPersisterError::PartiallyApplied(2))
+    );
+    assert!(wallet.staged().is_some());
+
+    // storing the same changes again leaves the stored wallet unchanged
+    let stored = persister.changeset().clone();
+    assert!(wallet.persist(&mut persister)?);
+    assert_eq!(persister.changeset(), &stored);
+    let loaded = Wallet::load()
+        .load_wallet(&mut persister)?
+        .expect("wallet is persisted");
+    assert_eq!(loaded.derivation_index(KeychainKind::External), Some(2));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_persist_error_keeps_staged_changes() -> anyhow::Result<()> {
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut persister = FlakyPersister::new().fail_persist(2).delay(3);
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet_async(&mut persister)
+        .await?;
+    wallet.reveal_next_address(KeychainKind::External);
+    assert_eq!(
+        wallet.persist_async(&mut persister).await,
+        Err(FlakyPersisterError::Persist(2))
+    );
+    assert!(wallet.staged().is_some());
+    assert!(wallet.persist_async(&mut persister).await?);
+    assert!(!wallet.persist_async(&mut persister).await?);
+    assert_eq!(persister.received().len(), 3);
+
+    let loaded = Wallet::load()
+        .load_wallet_async(&mut persister)
+        .await?
+        .expect("wallet is persisted");
+    assert_eq!(loaded.derivation_index(KeychainKind::External), Some(0));
+
+    Ok(())
+}
+
+/// The wallet of the fixtures, a funded wallet with its UTXO locked.
+fn fixture_wallet() -> Wallet {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let (mut wallet, _) = get_funded_wallet(desc, change_desc);
+    let outpoint = wallet.list_unspent().next().unwrap().outpoint;
+    wallet.lock_outpoint(outpoint);
+    wallet
+}
+
+fn assert_same_wallet_state(loaded: &Wallet, expected: &Wallet) {
+    assert_eq!(loaded.network(), expected.network());
+    assert!(loaded.keychains().eq(expected.keychains()));
+    assert_eq!(loaded.latest_checkpoint(), expected.latest_checkpoint());
+    for keychain in [KeychainKind::External, KeychainKind::Internal] {
+        assert_eq!(
+            loaded.derivation_index(keychain),
+            expected.derivation_index(keychain)
+        );
+    }
+    assert_eq!(loaded.balance(), expected.balance());
+}
+
+/// Magic bytes of the file store fixtures.
+const FIXTURE_MAGIC: &[u8] = b"bdk_wallet_fixture";
+
+// The fixtures of version 0 were written by bdk_wallet 2.4.0, and the ones of version 1 by
+// bdk_wallet 3.1.0, from the `fixture_wallet` of their test utilities: the wallet of version 0
+// has no locked outpoint.
+
+#[test]
+fn test_load_sqlite_fixture_of_previous_version() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+
+    for version in [0, 1] {
+        let temp_dir = tempfile::tempdir().expect("must create tempdir");
+        let file_path = temp_dir.path().join("wallet.sqlite");
+        std::fs::copy(format!("tests/data/wallet_v{version}.sqlite"), &file_path)?;
+        let expected = fixture_wallet();
+
+        // the databases have no labels nor metadata table
+        let mut conn = rusqlite::Connection::open(&file_path)?;
+        let mut wallet = Wallet::load()
+            .load_wallet(&mut conn)?
+            .expect("wallet is persisted");
+        assert_same_wallet_state(&wallet, &expected);
+        assert_eq!(
+            wallet.list_locked_outpoints().count(),
+            if version == 0 { 0 } else { 1 }
+        );
+        let outpoint = wallet.list_unspent().next().unwrap().outpoint;
+        wallet.set_output_label(outpoint, "upgraded");
+        assert!(wallet.persist(&mut conn)?);
+        let wallet = Wallet::load()
+            .load_wallet(&mut conn)?
+            .expect("wallet is persisted");
+        assert_eq!(wallet.output_label(outpoint), Some("upgraded"));
+
+        // a database of a newer version is rejected
+        conn.execute(
+            "UPDATE bdk_schemas SET version=version+1 WHERE name='bdk_wallet'",
+            [],
+        )?;
+        let err = Wallet::load()
+            .load_wallet(&mut conn)
+            .expect_err("database is from a newer version");
+        // the `rusqlite::Error` of the persister holds the `SqliteError`
+        match &err {
+            LoadWithPersistError::Persist(rusqlite::Error::ToSqlConversionFailure(e)) => {
+                assert_matches!(
+                    e.downcast_ref::<SqliteError>(),
+                    Some(SqliteError::NewerSchema(_))
+                )
+            }
+            err => panic!("unexpected error: {err:?}"),
+        }
+        assert!(err.to_string().contains("newer bdk_wallet"), "{err}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_upgrade_changeset_fixture_of_previous_version() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+    use bdk_wallet::migration::{upgrade_changeset, ChangeSetMigrationError, MIGRATIONS};
+    use bdk_wallet::sqlite_wallets::SqliteWalletStore;
+
+    for version in [0, 1] {
+        let json = std::fs::read_to_string(format!("tests/data/changeset_v{version}.json"))?;
+        let value: serde_json::Value = serde_json::from_str(&json)?;
+        let expected = fixture_wallet();
+
+        // the changesets of version 1 lack the fields of version 2, which the registered
+        // migration inserts
+        let mut migrated = value.clone();
+        for migration in MIGRATIONS {
+            if migration.source_version() >= version {
+                migration.migrate(&mut migrated)?;
+            }
+        }
+        for field in ["labels", "metadata", "spk_cache_digests"] {
+            assert!(value.get(field).is_none(), "{field}");
+            assert!(migrated.get(field).is_some(), "{field}");
+        }
+
+        let changeset = upgrade_changeset(value.clone(), version)?;
+        assert_eq!(serde_json::from_value::<ChangeSet>(migrated)?, changeset);
+        assert_eq!(
+            changeset.locked_outpoints.outpoints.len(),
+            if version == 0 { 0 } else { 1 }
+        );
+        assert!(changeset.labels.outputs.is_empty());
+        assert!(changeset.metadata.entries.is_empty());
+        let wallet = Wallet::load()
+            .load_wallet_no_persist(changeset)?
+            .expect("changeset holds a wallet");
+        assert_same_wallet_state(&wallet, &expected);
+
+        assert_matches!(
+            upgrade_changeset(value.clone(), ChangeSet::CURRENT_VERSION + 1),
+            Err(ChangeSetMigrationError::NewerVersion(v)) if v == ChangeSet::CURRENT_VERSION + 1
+        );
+
+        // the rows of the multi wallet tables are upgraded when loading
+        let mut store = SqliteWalletStore::new(rusqlite::Connection::open_in_memory()?, "old");
+        assert!(Wallet::load().load_wallet(&mut store)?.is_none());
+        store.connection().execute(
+            "INSERT INTO bdk_wallet_changesets(wallet_id, changeset, version) \
+             VALUES('old', ?1, ?2)",
+            rusqlite::params![&json, version],
+        )?;
+        let wallet = Wallet::load()
+            .load_wallet(&mut store)?
+            .expect("wallet is persisted");
+        assert_same_wallet_state(&wallet, &expected);
+        store.connection().execute(
+            "INSERT INTO bdk_wallet_changesets(wallet_id, changeset, version) \
+             VALUES('new', ?1, ?2)",
+            rusqlite::params![&json, ChangeSet::CURRENT_VERSION + 1],
+        )?;
+        let mut store = SqliteWalletStore::new(store.into_connection(), "new");
+        let err = Wallet::load()
+            .load_wallet(&mut store)
+            .expect_err("changeset is from a newer version");
+        assert!(err.to_string().contains("newer bdk_wallet"), "{err}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_load_file_store_fixture_of_previous_version() -> anyhow::Result<()> {
+    use bdk_wallet::migration::{load_file_store, ChangeSetMigrationError};
+    use bdk_wallet::FileStoreError;
+
+    let temp_dir = tempfile::tempdir().expect("must create tempdir");
+    let expected = fixture_wallet();
+
+    for version in [0, 1] {
+        let fixture = format!("tests/data/wallet_v{version}.db");
+        // the entries of a released version don't decode as the changesets of this version
+        assert!(bdk_file_store::Store::<ChangeSet>::load(FIXTURE_MAGIC, &fixture).is_err());
+
+        let changeset =
+            load_file_store(FIXTURE_MAGIC, &fixture, version)?.expect("store has entries");
+        assert_eq!(
+            changeset.locked_outpoints.outpoints.len(),
+            if version == 0 { 0 } else { 1 }
+        );
+        let wallet = Wallet::load()
+            .load_wallet_no_persist(changeset.clone())?
+            .expect("changeset holds a wallet");
+        assert_same_wallet_state(&wallet, &expected);
+
+        // a `Store<ChangeSet>` whose first entry doesn't record the changeset version, like those
+        // written by a released version, is rejected instead of being read as garbage
+        let file_path = temp_dir.path().join(format!("unversioned_v{version}.db"));
+        let mut store = bdk_file_store::Store::<ChangeSet>::create(FIXTURE_MAGIC, &file_path)?;
+        store.append(&changeset)?;
+        let (mut store, _) = bdk_file_store::Store::<ChangeSet>::load(FIXTURE_MAGIC, &file_path)?;
+        assert_matches!(
+            Wallet::load().load_wallet(&mut store),
+            Err(LoadWithPersistError::Persist(FileStoreError::Unversioned))
+        );
+
+        // the wallet is upgraded by writing it to a versioned store
+        let file_path = temp_dir.path().join(format!("versioned_v{version}.db"));
+        let mut store =
+            bdk_file_store::Store::<VersionedChangeSets>::create(FIXTURE_MAGIC, &file_path)?;
+        WalletPersister::persist(&mut store, &changeset)?;
+        let (mut store, _) =
+            bdk_file_store::Store::<VersionedChangeSets>::load(FIXTURE_MAGIC, &file_path)?;
+        let mut wallet = Wallet::load()
+            .load_wallet(&mut store)?
+            .expect("wallet is persisted");
+        assert_same_wallet_state(&wallet, &expected);
+        let outpoint = wallet.list_unspent().next().unwrap().outpoint;
+        wallet.set_output_label(outpoint, "upgraded");
+        assert!(wallet.persist(&mut store)?);
+        let (mut store, _) =
+            bdk_file_store::Store::<VersionedChangeSets>::load(FIXTURE_MAGIC, &file_path)?;
+        let wallet = Wallet::load()
+            .load_wallet(&mut store)?
+            .expect("wallet is persisted");
+        assert_eq!(wallet.output_label(outpoint), Some("upgraded"));
+
+        // the entries record the version they were written with: after the magic bytes, the
+        // `bincode` varint encoding of an entry starts with its number of changesets, 1, then the
+        // version of the changeset
+        let mut bytes = std::fs::read(&file_path)?;
+        let version_pos = FIXTURE_MAGIC.len() + 1;
+        assert_eq!(u32::from(bytes[version_pos]), ChangeSet::CURRENT_VERSION);
+        bytes[version_pos] += 1;
+        std::fs::write(&file_path, bytes)?;
+        let (mut store, _) =
+            bdk_file_store::Store::<VersionedChangeSets>::load(FIXTURE_MAGIC, &file_path)?;
+        let err = Wallet::load()
+            .load_wallet(&mut store)
+            .expect_err("store is from a newer version");
+        assert_matches!(
+            err,
+            LoadWithPersistError::Persist(FileStoreError::Migration(
+                ChangeSetMigrationError::NewerVersion(_)
+            ))
+        );
+    }
+
+    // a versioned store holding a changeset of version 1 is upgraded by the migrations when the
+    // wallet is loaded
+    let json = std::fs::read_to_string("tests/data/changeset_v1.json")?;
+    let file_path = temp_dir.path().join("versioned_fixture.db");
+    bdk_file_store::Store::<VersionedChangeSets>::create(FIXTURE_MAGIC, &file_path)?;
+    // the `bincode` varint encoding of an entry: its number of changesets, then the version and
+    // the length of the JSON of each, lengths of 251 to 65535 being encoded in 3 bytes
+    let json_len = u16::try_from(json.len())?;
+    assert!(json_len >= 251);
+    let mut bytes = std::fs::read(&file_path)?;
+    bytes.extend([1, 1, 251]);
+    bytes.extend(json_len.to_le_bytes());
+    bytes.extend(json.as_bytes());
+    std::fs::write(&file_path, bytes)?;
+    let (mut store, _) =
+        bdk_file_store::Store::<VersionedChangeSets>::load(FIXTURE_MAGIC, &file_path)?;
+    let mut wallet = Wallet::load()
+        .load_wallet(&mut store)?
+        .expect("wallet is persisted");
+    assert_same_wallet_state(&wallet, &expected);
+    assert_eq!(wallet.list_locked_outpoints().count(), 1);
+    let outpoint = wallet.list_unspent().next().unwrap().outpoint;
+    wallet.set_output_label(outpoint, "upgraded");
+    assert!(wallet.persist(&mut store)?);
+    let (mut store, _) =
+        bdk_file_store::Store::<VersionedChangeSets>::load(FIXTURE_MAGIC, &file_path)?;
+    let wallet = Wallet::load()
+        .load_wallet(&mut store)?
+        .expect("wallet is persisted");
+    assert_eq!(wallet.output_label(outpoint), Some("upgraded"));
+
+    assert_matches!(
+        load_file_store(
+            FIXTURE_MAGIC,
+            "tests/data/wallet_v1.db",
+            ChangeSet::CURRENT_VERSION + 1
+        ),
+        Err(FileStoreError::Migration(
+            ChangeSetMigrationError::NewerVersion(_)
+        ))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_load_read_only() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite::{Connection, OpenFlags};
+    use bdk_wallet::sqlite_wallets::SqliteWalletStore;
+
+    fn run<Db, FW, FR>(filename: &str, create_db: FW, open_read_only: FR) -> anyhow::Result<()>
+    where
+        Db: WalletPersister,
+        Db::Error: std::error::Error + Send + Sync + 'static,
+        FW: Fn(&Path) -> anyhow::Result<Db>,
+        FR: Fn(&Path) -> anyhow::Result<Db>,
+    {
+        let temp_dir = tempfile::tempdir().expect("must create tempdir");
+        let file_path = temp_dir.path().join(filename);
+        let (desc, change_desc) = get_test_wpkh_and_change_desc();
+
+        let mut writer_db = create_db(&file_path)?;
+        let mut writer = Wallet::create(desc, change_desc)
+            .network(Network::Testnet)
+            .create_wallet(&mut writer_db)?;
+        assert!(!writer.is_read_only());
+        writer.reveal_next_address(KeychainKind::External);
+        writer.persist(&mut writer_db)?;
+
+        let mut reader_db = open_read_only(&file_path)?;
+        let mut reader = Wallet::load()
+            .read_only()
+            .load_wallet(&mut reader_db)?
+            .expect("wallet is persisted");
+        assert!(reader.is_read_only());
+        assert_eq!(
+            reader.derivation_index(KeychainKind::External),
+            writer.derivation_index(KeychainKind::External)
+        );
+
+        // the writer keeps persisting while the reader is open
+        writer.reveal_next_address(KeychainKind::External);
+        writer.persist(&mut writer_db)?;
+
+        // the changes of the reader are staged but never written
+        let contents = std::fs::read(&file_path)?;
+        reader.reveal_next_address(KeychainKind::Internal);
+        assert!(!reader.persist(&mut reader_db)?);
+        assert!(reader.staged().is_some());
+        assert_eq!(std::fs::read(&file_path)?, contents);
+
+        // reloading sees the changes of the writer only
+        let mut reader_db = open_read_only(&file_path)?;
+        let reader = Wallet::load()
+            .read_only()
+            .load_wallet(&mut reader_db)?
+            .expect("wallet is persisted");
+        assert_eq!(reader.derivation_index(KeychainKind::External), Some(1));
+        assert_eq!(reader.derivation_index(KeychainKind::Internal), None);
+
+        Ok(())
+    }
+
+    run(
+        "store.db",
+        |path| Ok(bdk_file_store::Store::<ChangeSet>::create(DB_MAGIC, path)?),
+        |path| Ok(bdk_file_store::Store::<ChangeSet>::load(DB_MAGIC, path)?.0),
+    )?;
+    run(
+        "store.versioned.db",
+        |path| {
+            Ok(bdk_file_store::Store::<VersionedChangeSets>::create(
+                DB_MAGIC, path,
+            )?)
+        },
+        |path| Ok(bdk_file_store::Store::<VersionedChangeSets>::load(DB_MAGIC, path)?.0),
+    )?;
+    run(
+        "store.sqlite",
+        |path| Ok(Connection::open(path)?),
+        |path| {
+            Ok(Connection::open_with_flags(
+                path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY,
+            )?)
+        },
+    )?;
+    run(
+        "wallets.sqlite",
+        |path| Ok(SqliteWalletStore::new(Connection::open(path)?, "alice")),
+        |path| {
+            let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+            Ok(SqliteWalletStore::new(conn, "alice"))
+        },
+    )?;
+
+    // an uninitialized database is empty, and is left uninitialized
+    let temp_dir = tempfile::tempdir().expect("must create tempdir");
+    let file_path = temp_dir.path().join("empty.sqlite");
+    Connection::open(&file_path)?.execute_batch("CREATE TABLE other (id INTEGER)")?;
+    let mut conn = Connection::open_with_flags(&file_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    assert!(Wallet::load().read_only().load_wallet(&mut conn)?.is_none());
+    let read_only_conn = Connection::open_with_flags(&file_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    assert!(Wallet::load()
+        .read_only()
+        .load_wallet(&mut SqliteWalletStore::new(read_only_conn, "alice"))?
+        .is_none());
+
+    // a database of a previous version must be upgraded first
+    let file_path = temp_dir.path().join("wallet_v1.sqlite");
+    std::fs::copy("tests/data/wallet_v1.sqlite", &file_path)?;
+    let mut conn = Connection::open_with_flags(&file_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let err = Wallet::load()
+        .read_only()
+        .load_wallet(&mut conn)
+        .expect_err("database must be upgraded");
+    assert!(err.to_string().contains("must be upgraded"), "{err}");
+    Wallet::load()
+        .load_wallet(&mut Connection::open(&file_path)?)?
+        .expect("wallet is persisted");
+    let wallet = Wallet::load()
+        .read_only()
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert_same_wallet_state(&wallet, &fixture_wallet());
+
+    Ok(())
+}
+
+#[test]
+fn test_auto_persist_thresholds() -> anyhow::Result<()> {
+    use bdk_wallet::AutoPersist;
+    use std::time::Duration;
+
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let create = || -> anyhow::Result<_> {
+        let mut db = FlakyPersister::new();
+        let wallet = Wallet::create(desc, change_desc)
+            .network(Network::Testnet)
+            .create_wallet(&mut db)?;
+        Ok((wallet, db))
+    };
+
+    // persisted once enough transactions are staged
+    let (wallet, db) = create()?;
+    let mut wallet = AutoPersist::new(wallet, db).max_staged_txs(2);
+    wallet.update(|wallet| wallet.reveal_next_address(KeychainKind::External))?;
+    wallet.update(|wallet| insert_tx(wallet, new_tx(1)))?;
+    assert_eq!(wallet.staged_txs(), 1);
+    assert_eq!(wallet.persister().received().len(), 1);
+    wallet.update(|wallet| insert_tx(wallet, new_tx(2)))?;
+    assert_eq!(wallet.staged_txs(), 0);
+    assert_eq!(wallet.persister().received().len(), 2);
+    assert_eq!(wallet.persister().received()[1].tx_graph.txs.len(), 2);
+
+    // persisted once the interval has elapsed
+    let (wallet, db) = create()?;
+    let mut wallet = AutoPersist::new(wallet, db).max_interval(Duration::from_secs(3600));
+    wallet.update(|wallet| insert_tx(wallet, new_tx(1)))?;
+    assert!(!wallet.flush_if_due()?);
+    assert_eq!(wallet.persister().received().len(), 1);
+    let (wallet, db) = create()?;
+    let mut wallet = AutoPersist::new(wallet, db).max_interval(Duration::ZERO);
+    assert!(!wallet.flush_if_due()?, "nothing is staged");
+    wallet.update(|wallet| insert_tx(wallet, new_tx(1)))?;
+    assert_eq!(wallet.persister().received().len(), 2);
+
+    // without thresholds, only flushing persists
+    let (wallet, db) = create()?;
+    let mut wallet = AutoPersist::new(wallet, db);
+    for locktime in 0..10 {
+        wallet.update(|wallet| insert_tx(wallet, new_tx(locktime)))?;
+    }
+    assert!(!wallet.flush_if_due()?);
+    assert_eq!(wallet.staged_txs(), 10);
+    assert!(wallet.flush()?);
+    assert!(!wallet.flush()?);
+    assert_eq!(wallet.persister().received().len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_auto_persist_error_is_returned_by_next_operation() -> anyhow::Result<()> {
+    use bdk_wallet::AutoPersist;
+
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut db = FlakyPersister::new().fail_persist(2);
+    let wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut db)?;
+    let mut wallet = AutoPersist::new(wallet, db).max_staged_txs(1);
+
+    // the update succeeds even though persisting its changes fails
+    let address = wallet.update(|wallet| {
+        insert_tx(wallet, new_tx(1));
+        wallet.reveal_next_address(KeychainKind::External)
+    })?;
+    assert_eq!(address.index, 0);
+    assert_eq!(wallet.staged_txs(), 1);
+
+    // the next update is not applied and returns the error
+    assert_matches!(
+        wallet.update(|wallet| wallet.reveal_next_address(KeychainKind::External)),
+        Err(FlakyPersisterError::Persist(2))
+    );
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(0));
+
+    // the following one persists the changes kept staged
+    wallet.update(|wallet| insert_tx(wallet, new_tx(2)))?;
+    assert_eq!(wallet.staged_txs(), 0);
+    let changeset = wallet.persister().changeset().clone();
+    assert_eq!(changeset.tx_graph.txs.len(), 2);
+    let loaded = Wallet::load()
+        .load_wallet_no_persist(changeset)?
+        .expect("wallet is persisted");
+    assert_eq!(loaded.derivation_index(KeychainKind::External), Some(0));
+
+    Ok(())
+}
+
+#[test]
+fn test_auto_persist_flushes_on_drop() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite::Connection;
+    use bdk_wallet::AutoPersist;
+
+    let temp_dir = tempfile::tempdir().expect("must create tempdir");
+    let file_path = temp_dir.path().join("wallet.sqlite");
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut db = Connection::open(&file_path)?;
+    let wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut db)?;
+
+    let mut wallet = AutoPersist::new(wallet, db).max_staged_txs(100);
+    wallet.update(|wallet| insert_tx(wallet, new_tx(1)))?;
+    wallet.update(|wallet| wallet.reveal_next_address(KeychainKind::External))?;
+    assert_eq!(wallet.staged_txs(), 1);
+    drop(wallet);
+
+    let wallet = Wallet::load()
+        .load_wallet(&mut Connection::open(&file_path)?)?
+        .expect("wallet is persisted");
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(0));
+    assert_eq!(wallet.tx_graph().full_txs().count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_wallet_concurrent_readers_and_writer() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite::Connection;
+    use bdk_chain::BlockId;
+    use bdk_wallet::{SharedWallet, Update};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    const BLOCKS: u32 = 50;
+    const READERS: usize = 4;
+
+    let temp_dir = tempfile::tempdir().expect("must create tempdir");
+    let file_path = temp_dir.path().join("wallet.sqlite");
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut db = Connection::open(&file_path)?;
+    let wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut db)?;
+    let wallet = SharedWallet::new(wallet, db);
+    let first_address = wallet.peek_address(KeychainKind::External, 0);
+    let done = Arc::new(AtomicBool::new(false));
+
+    let readers = (0..READERS)
+        .map(|_| {
+            let wallet = wallet.clone();
+            let done = Arc::clone(&done);
+            let first_address = first_address.clone();
+            std::thread::spawn(move || {
+                let mut last_total = Amount::ZERO;
+                let mut reads = 0;
+                while !done.load(Ordering::Acquire) {
+                    // a reader sees every change of the writer whole
+                    let (total, unspent) = wallet.read(|wallet| {
+                        let unspent = wallet.list_unspent().map(|utxo| utxo.txout.value).sum();
+                        (wallet.balance().total(), unspent)
+                    });
+                    assert_eq!(total, unspent);
+                    assert!(total >= last_total);
+                    last_total = total;
+                    assert_eq!(
+                        wallet.peek_address(KeychainKind::External, 0),
+                        first_address
+                    );
+                    reads += 1;
+                }
+                reads
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let writer = {
+        let wallet = wallet.clone();
+        std::thread::spawn(move || -> anyhow::Result<()> {
+            for height in 1..=BLOCKS {
+                let address = wallet.reveal_next_address_persisted(KeychainKind::External)?;
+                assert_eq!(address.index, height - 1);
+                let tip = wallet.read(|wallet| wallet.latest_checkpoint());
+                wallet.apply_update_and_persist(Update {
+                    chain: Some(tip.insert(BlockId {
+                        height,
+                        hash: BlockHash::hash(&height.to_le_bytes()),
+                    })),
+                    ..Default::default()
+                })?;
+                wallet.update(|wallet| {
+                    receive_output_in_latest_block(wallet, Amount::from_sat(1_000))
+                })?;
+            }
+            Ok(())
+        })
+    };
+
+    writer.join().expect("writer must not panic")?;
+    done.store(true, Ordering::Release);
+    for reader in readers {
+        assert!(reader.join().expect("reader must not panic") > 0);
+    }
+
+    let expected = Amount::from_sat(1_000) * u64::from(BLOCKS);
+    assert_eq!(wallet.balance().confirmed, expected);
+    assert_eq!(wallet.list_unspent().len(), BLOCKS as usize);
+
+    // all the changes were persisted
+    let loaded = Wallet::load()
+        .load_wallet(&mut Connection::open(&file_path)?)?
+        .expect("wallet is persisted");
+    assert_eq!(loaded.balance().confirmed, expected);
+    assert_eq!(
+        loaded.derivation_index(KeychainKind::External),
+        Some(BLOCKS - 1)
+    );
+    assert_eq!(loaded.latest_checkpoint().height(), BLOCKS);
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_wallet_poisoning() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite::Connection;
+    use bdk_wallet::{SharedWallet, SharedWalletError};
+
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut db = Connection::open_in_memory()?;
+    let wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut db)?;
+    let wallet = SharedWallet::new(wallet, db);
+    wallet.reveal_next_address_persisted(KeychainKind::External)?;
+
+    let panicking = wallet.clone();
+    let res = std::thread::spawn(move || {
+        panicking.update(|wallet| {
+            wallet.reveal_next_address(KeychainKind::External);
+            panic!("interrupted change");
+        })
+    })
+    .join();
+    assert!(res.is_err());
+
+    // the half-changed wallet can be read, but not changed or persisted
+    assert_eq!(
+        wallet.read(|wallet| wallet.derivation_index(KeychainKind::External)),
+        Some(1)
+    );
+    assert_matches!(
+        wallet.reveal_next_address_persisted(KeychainKind::External),
+        Err(SharedWalletError::Poisoned)
+    );
+    assert_matches!(wallet.update(|_| ()), Err(SharedWalletError::Poisoned));
+
+    Ok(())
+}
+
+#[test]
+fn test_corrupted_spk_cache_is_derived_again() -> anyhow::Result<()> {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .use_spk_cache(true)
+        .create_wallet_no_persist()?;
+    let _ = wallet.reveal_next_address(KeychainKind::External);
+    let expected = (0..=DEFAULT_LOOKAHEAD)
+        .map(|index| wallet.peek_address(KeychainKind::External, index))
+        .collect::<Vec<_>>();
+    let changeset = wallet.take_staged().expect("wallet is created");
+    let did = wallet
+        .public_descriptor(KeychainKind::External)
+        .descriptor_id();
+    let other_spk = wallet
+        .peek_address(KeychainKind::Internal, 0)
+        .script_pubkey();
+    assert_eq!(
+        changeset.indexer.spk_cache[&did].len(),
+        DEFAULT_LOOKAHEAD as usize + 1
+    );
+    assert!(changeset.spk_cache_digests.contains_key(&did));
+
+    let load = |changeset: ChangeSet, params: bdk_wallet::LoadParams| -> anyhow::Result<Wallet> {
+        let mut wallet = params
+            .use_spk_cache(true)
+            .load_wallet_no_persist(changeset)?
+            .expect("wallet is persisted");
+        for address in &expected {
+            assert_eq!(
+                wallet.peek_address(KeychainKind::External, address.index),
+                *address
+            );
+            // the script pubkey watched by the wallet is the right one too
+            assert_eq!(
+                wallet
+                    .spk_index()
+                    .spk_at_index(KeychainKind::External, address.index),
+                Some(address.script_pubkey())
+            );
+        }
+        // the derived script pubkeys are staged to replace the cache
+        let staged = wallet.take_staged().unwrap_or_default();
+        for address in &expected {
+            assert_eq!(
+                staged.indexer.spk_cache[&did][&address.index],
+                address.script_pubkey()
+            );
+        }
+        Ok(wallet)
+    };
+
+    // the first entry is always checked
+    let mut corrupted = changeset.clone();
+    corrupted
+        .indexer
+        .spk_cache
+        .get_mut(&did)
+        .unwrap()
+        .insert(0, other_spk.clone());
+    load(corrupted, Wallet::load())?;
+
+    // an entry outside of the sample is detected by the digest of the cache
+    let mut corrupted = changeset.clone();
+    corrupted
+        .indexer
+        .spk_cache
+        .get_mut(&did)
+        .unwrap()
+        .insert(DEFAULT_LOOKAHEAD / 2 + 1, other_spk.clone());
+    load(corrupted.clone(), Wallet::load())?;
+
+    // any entry is derived again when the cache is ignored
+    load(corrupted, Wallet::load().ignore_spk_cache())?;
+
+    // a cache persisted without a digest gets one
+    let mut legacy = changeset.clone();
+    legacy.spk_cache_digests.clear();
+    let mut wallet = Wallet::load()
+        .use_spk_cache(true)
+        .load_wallet_no_persist(legacy)?
+        .expect("wallet is persisted");
+    let staged = wallet.take_staged().expect("the digests are staged");
+    assert_eq!(staged.spk_cache_digests, changeset.spk_cache_digests);
+    assert!(staged.indexer.spk_cache.is_empty());
+
+    // a valid cache is used as is
+    let mut wallet = Wallet::load()
+        .use_spk_cache(true)
+        .load_wallet_no_persist(changeset.clone())?
+        .expect("wallet is persisted");
+    assert!(wallet.take_staged().is_none());
+
+    // the digest of newly cached script pubkeys is staged as soon as they are
+    let _ = wallet.reveal_addresses_to(KeychainKind::External, DEFAULT_LOOKAHEAD);
+    let staged = wallet.staged().expect("new script pubkeys are cached");
+    assert!(staged.indexer.spk_cache.contains_key(&did));
+    assert_eq!(
+        staged.spk_cache_digests.keys().collect::<Vec<_>>(),
+        vec![&did]
+    );
+    let mut changeset = changeset;
+    changeset.merge(wallet.take_staged().expect("changes are staged"));
+    let mut wallet = Wallet::load()
+        .use_spk_cache(true)
+        .load_wallet_no_persist(changeset)?
+        .expect("wallet is persisted");
+    assert!(wallet.take_staged().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_sqlite_pragmas() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite::Connection;
+    use bdk_wallet::SqlitePragmas;
+
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("wallet.sqlite");
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+
+    let mut conn = Connection::open(&path)?;
+    SqlitePragmas::fast().apply(&conn)?;
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+    assert_eq!(journal_mode, "wal");
+    let synchronous: u32 = conn.query_row("PRAGMA synchronous", [], |row| row.get(0))?;
+    assert_eq!(synchronous, 1, "synchronous must be NORMAL");
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet(&mut conn)?;
+    let address = wallet.reveal_next_address(KeychainKind::External);
+    wallet.persist(&mut conn)?;
+    drop(conn);
+
+    let mut conn = Connection::open(&path)?;
+    let wallet = Wallet::load()
+        .load_wallet(&mut conn)?
+        .expect("wallet is persisted");
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::External),
+        Some(address.index)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_sqlite_batched_persist_writes_bdk_chain_rows() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite::{types::Value, Connection};
+    use bdk_chain::{keychain_txout, local_chain, tx_graph, BlockId};
+    use bitcoin::{OutPoint, TxIn};
+    use std::sync::Arc;
+
+    /// Every row of the `bdk_chain` tables, in a deterministic order.
+    fn dump(conn: &Connection) -> anyhow::Result<Vec<(&'static str, Vec<Value>)>> {
+        let tables = [
+            local_chain::ChangeSet::BLOCKS_TABLE_NAME,
+            tx_graph::ChangeSet::<ConfirmationBlockTime>::TXS_TABLE_NAME,
+            tx_graph::ChangeSet::<ConfirmationBlockTime>::TXOUTS_TABLE_NAME,
+            tx_graph::ChangeSet::<ConfirmationBlockTime>::ANCHORS_TABLE_NAME,
+            keychain_txout::ChangeSet::LAST_REVEALED_TABLE_NAME,
+            keychain_txout::ChangeSet::DERIVED_SPKS_TABLE_NAME,
+        ];
+        let mut rows = Vec::new();
+        for table in tables {
+            let mut statement = conn.prepare(&format!("SELECT * FROM {table}"))?;
+            let columns = statement.column_count();
+            let mut table_rows = statement
+                .query_map([], |row| {
+                    (0..columns).map(|i| row.get::<_, Value>(i)).collect()
+                })?
+                .collect::<Result<Vec<Vec<Value>>, _>>()?;
+            table_rows.sort_by_key(|row| format!("{row:?}"));
+            rows.extend(table_rows.into_iter().map(|row| (table, row)));
+        }
+        Ok(rows)
+    }
+
+    let tx = |i: u32| Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::from_consensus(i),
+        input: vec![TxIn::default()],
+        output: vec![TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new(),
+        }],
+    };
+    let (tx1, tx2) = (tx(1), tx(2));
+    let (txid1, txid2) = (tx1.compute_txid(), tx2.compute_txid());
+    let missing_txid = tx(3).compute_txid();
+    let block = |height: u32, byte: u8| BlockId {
+        height,
+        hash: BlockHash::from_byte_array([byte; 32]),
+    };
+    let anchor = |block_id, confirmation_time| ConfirmationBlockTime {
+        block_id,
+        confirmation_time,
+    };
+    let did = DescriptorId::from_byte_array([0x42; 32]);
+
+    let first = ChangeSet {
+        local_chain: local_chain::ChangeSet {
+            blocks: [
+                (0, Some(block(0, 0).hash)),
+                (1, Some(block(1, 1).hash)),
+                (2, Some(block(2, 2).hash)),
+            ]
+            .into(),
+        },
+        tx_graph: tx_graph::ChangeSet {
+            txs: [Arc::new(tx1.clone()), Arc::new(tx2.clone())].into(),
+            txouts: [(
+                OutPoint::new(missing_txid, 0),
+                TxOut {
+                    value: Amount::from_sat(1),
+                    script_pubkey: ScriptBuf::new(),
+                },
+            )]
+            .into(),
+            anchors: [
+                (anchor(block(1, 1), 100), txid1),
+                (anchor(block(2, 2), 200), missing_txid),
+            ]
+            .into(),
+            first_seen: [(txid1, 10)].into(),
+            last_seen: [(txid1, 20), (txid2, 30)].into(),
+            last_evicted: BTreeMap::new(),
+        },
+        indexer: keychain_txout::ChangeSet {
+            last_revealed: [(did, 1)].into(),
+            // more rows than written by a single statement
+            spk_cache: [(
+                did,
+                (0..300)
+                    .map(|i| (i, ScriptBuf::from_bytes(vec![0])))
+                    .collect(),
+            )]
+            .into(),
+        },
+        ..Default::default()
+    };
+    // updates some of the columns of existing rows
+    let second = ChangeSet {
+        local_chain: local_chain::ChangeSet {
+            blocks: [(1, Some(block(1, 3).hash)), (2, None)].into(),
+        },
+        tx_graph: tx_graph::ChangeSet {
+            txouts: [(
+                OutPoint::new(missing_txid, 0),
+                TxOut {
+                    value: Amount::from_sat(2),
+                    script_pubkey: ScriptBuf::new(),
+                },
+            )]
+            .into(),
+            anchors: [(anchor(block(1, 1), 101), txid1)].into(),
+            last_seen: [(txid1, 40)].into(),
+            last_evicted: [(txid2, 50)].into(),
+            ..Default::default()
+        },
+        indexer: keychain_txout::ChangeSet {
+            last_revealed: [(did, 2)].into(),
+            spk_cache: [(did, [(1, ScriptBuf::from_bytes(vec![2]))].into())].into(),
+        },
+        ..Default::default()
+    };
+
+    let mut batched = Connection::open_in_memory()?;
+    let mut row_by_row = Connection::open_in_memory()?;
+    for conn in [&mut batched, &mut row_by_row] {
+        let db_tx = conn.transaction()?;
+        ChangeSet::init_sqlite_tables(&db_tx)?;
+        db_tx.commit()?;
+    }
+    for changeset in [&first, &second] {
+        let db_tx = batched.transaction()?;
+        changeset.persist_to_sqlite(&db_tx)?;
+        db_tx.commit()?;
+
+        let db_tx = row_by_row.transaction()?;
+        changeset.local_chain.persist_to_sqlite(&db_tx)?;
+        changeset.tx_graph.persist_to_sqlite(&db_tx)?;
+        changeset.indexer.persist_to_sqlite(&db_tx)?;
+        db_tx.commit()?;
+
+        assert_eq!(dump(&batched)?, dump(&row_by_row)?);
+    }
+
+    let db_tx = batched.transaction()?;
+    let loaded = ChangeSet::from_sqlite(&db_tx)?;
+    assert_eq!(loaded.local_chain.blocks.len(), 2);
+    assert_eq!(loaded.tx_graph.txs.len(), 2);
+    assert_eq!(loaded.tx_graph.last_seen, [(txid1, 40), (txid2, 30)].into());
+    assert_eq!(loaded.tx_graph.last_evicted, [(txid2, 50)].into());
+    assert_eq!(loaded.indexer.spk_cache[&did].len(), 300);
+    assert_eq!(
+        loaded.indexer.spk_cache[&did][&1],
+        ScriptBuf::from_bytes(vec![2])
+    );
+    Ok(())
+}
@@ -221,3 +221,369 @@ fn test_psbt_multiple_internalkey_signers() {
     let verify_res = secp.verify_schnorr(&signature, &message, &xonlykey);
     assert!(verify_res.is_ok(), "The wrong internal key was used");
 }
+
+#[test]
+fn test_combine_psbts_multisig() {
+    use bdk_wallet::bitcoin::bip32::{Xpriv, Xpub};
+    use bdk_wallet::bitcoin::secp256k1::Secp256k1;
+    use bdk_wallet::bitcoin::Network;
+    use bdk_wallet::combine::{CombineError, ConflictingField, InputSignatures};
+    use bdk_wallet::Wallet;
+
+    let secp = Secp256k1::new();
+    let xprvs = [1u8, 2, 3].map(|seed| Xpriv::new_master(Network::Regtest, &[seed; 32]).unwrap());
+    let xpubs = xprvs.map(|xprv| Xpub::from_priv(&secp, &xprv));
+    // the descriptors of the cosigner holding the private key at `signer`
+    let descriptors = |signer: Option<usize>| {
+        let keys = |path: &str| {
+            (0..3)
+                .map(|i| match signer {
+                    Some(signer) if signer == i => format!("{}/{path}/*", xprvs[i]),
+                    _ => format!("{}/{path}/*", xpubs[i]),
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        (
+            format!("wsh(multi(2,{}))", keys("0")),
+            format!("wsh(multi(2,{}))", keys("1")),
+        )
+    };
+    let (descriptor, change_descriptor) = descriptors(None);
+    let (mut coordinator, _) = get_funded_wallet(&descriptor, &change_descriptor);
+    let cosigner = |signer| {
+        let (descriptor, change_descriptor) = descriptors(Some(signer));
+        Wallet::create(descriptor, change_descriptor)
+            .network(Network::Regtest)
+            .create_wallet_no_persist()
+            .unwrap()
+    };
+
+    let send_to = coordinator.peek_address(KeychainKind::External, 0);
+    let mut builder = coordinator.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder.finish().unwrap();
+
+    let no_finalize = SignOptions {
+        try_finalize: false,
+        ..Default::default()
+    };
+    let mut psbt_a = psbt.clone();
+    assert!(!cosigner(0).sign(&mut psbt_a, no_finalize.clone()).unwrap());
+    let mut psbt_c = psbt.clone();
+    assert!(!cosigner(2).sign(&mut psbt_c, no_finalize.clone()).unwrap());
+
+    // a single signature is not enough
+    let mut base = psbt.clone();
+    let report = coordinator
+        .combine_psbts(&mut base, [psbt_a.clone()], SignOptions::default())
+        .unwrap();
+    assert!(report.conflicts.is_empty());
+    assert_eq!(
+        report.inputs,
+        vec![Some(InputSignatures {
+            keychain: KeychainKind::External,
+            signatures: 1,
+            threshold: 2,
+            is_final: false,
+        })]
+    );
+    assert!(!report.is_complete());
+    assert!(!report.finalized);
+    assert_eq!(base.inputs[0].partial_sigs.len(), 1);
+
+    // the second signature completes the threshold
+    let report = coordinator
+        .combine_psbts(&mut base, [psbt_c.clone()], SignOptions::default())
+        .unwrap();
+    assert!(report.conflicts.is_empty());
+    assert_eq!(report.inputs[0].unwrap().signatures, 2);
+    assert!(report.is_complete());
+    assert!(report.finalized);
+    assert!(base.inputs[0].final_script_witness.is_some());
+    base.extract_tx().expect("must be finalized");
+
+    // both signatures at once, without finalizing
+    let mut base = psbt.clone();
+    let report = coordinator
+        .combine_psbts(
+            &mut base,
+            [psbt_a.clone(), psbt_c.clone()],
+            no_finalize.clone(),
+        )
+        .unwrap();
+    assert!(report.is_complete());
+    assert!(!report.finalized);
+    assert_eq!(base.inputs[0].partial_sigs.len(), 2);
+
+    // conflicting signatures keep the first one
+    let (pk_a, sig_a) = psbt_a.inputs[0].partial_sigs.iter().next().unwrap();
+    let sig_c = *psbt_c.inputs[0].partial_sigs.values().next().unwrap();
+    let mut forged = psbt.clone();
+    forged.inputs[0].partial_sigs.insert(*pk_a, sig_c);
+    let mut base = psbt_a.clone();
+    let report = coordinator
+        .combine_psbts(&mut base, [psbt_c.clone(), forged], no_finalize)
+        .unwrap();
+    assert_eq!(report.conflicts.len(), 1);
+    assert_eq!(report.conflicts[0].input, 0);
+    assert_eq!(report.conflicts[0].psbt, 1);
+    assert_eq!(
+        report.conflicts[0].field,
+        ConflictingField::PartialSig(*pk_a)
+    );
+    assert_eq!(base.inputs[0].partial_sigs.get(pk_a), Some(sig_a));
+    assert_eq!(report.inputs[0].unwrap().signatures, 2);
+
+    // PSBTs for a different transaction are rejected and the base is left untouched
+    let mut other_tx = psbt_c.clone();
+    other_tx.unsigned_tx.output[0].value += Amount::from_sat(1);
+    let mut base = psbt_a.clone();
+    assert!(matches!(
+        coordinator.combine_psbts(&mut base, [psbt_c, other_tx], SignOptions::default()),
+        Err(CombineError::UnsignedTxMismatch(1))
+    ));
+    assert_eq!(base, psbt_a);
+}
+
+#[test]
+fn test_psbt_v2_create_and_sign() {
+    use bdk_wallet::psbt::PsbtV2;
+
+    let (mut wallet, _) = get_funded_wallet_single(get_test_wpkh());
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = PsbtV2::from(builder.finish().unwrap());
+
+    // version 2 PSBTs don't contain the unsigned transaction and can't be parsed as version 0
+    let bytes = psbt.serialize().unwrap();
+    assert!(Psbt::deserialize(&bytes).is_err());
+
+    let mut parsed = PsbtV2::deserialize(&bytes).unwrap();
+    assert_eq!(parsed, psbt);
+    // the version 0 representation is a valid version 0 PSBT
+    assert_eq!(parsed.version, 0);
+    assert_eq!(Psbt::deserialize(&(*parsed).serialize()).unwrap(), *parsed);
+
+    let finalized = wallet.sign(&mut parsed, SignOptions::default()).unwrap();
+    assert!(finalized);
+    let signed: PsbtV2 = parsed.to_base64().unwrap().parse().unwrap();
+    assert_eq!(signed, parsed);
+    assert!(signed.into_inner().extract_tx().is_ok());
+}
+
+#[test]
+fn test_psbt_v2_sign_external() {
+    use bdk_wallet::bitcoin::consensus::serialize;
+    use bdk_wallet::psbt::v2::{psbt_v2_to_v0, PsbtV2Error};
+    use bdk_wallet::psbt::PsbtV2;
+
+    fn push_pair(bytes: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+        bytes.push(key.len() as u8);
+        bytes.extend(key);
+        bytes.push(value.len() as u8);
+        bytes.extend(value);
+    }
+
+    let (wallet, _) = get_funded_wallet_single(get_test_wpkh());
+    let utxo = wallet.list_unspent().next().unwrap();
+    let send_to = wallet.peek_address(KeychainKind::External, 5);
+    let amount = utxo.txout.value - Amount::from_sat(500);
+
+    // A version 2 PSBT as a coordinator following BIP370 would create it: no derivation paths,
+    // only the previous output and the fields of the transaction.
+    let mut bytes = b"psbt\xff".to_vec();
+    push_pair(&mut bytes, &[0x02], &2u32.to_le_bytes());
+    push_pair(&mut bytes, &[0x04], &[1]);
+    push_pair(&mut bytes, &[0x05], &[1]);
+    push_pair(&mut bytes, &[0xfb], &2u32.to_le_bytes());
+    bytes.push(0x00);
+    push_pair(&mut bytes, &[0x01], &serialize(&utxo.txout));
+    push_pair(&mut bytes, &[0x0e], &serialize(&utxo.outpoint.txid));
+    push_pair(&mut bytes, &[0x0f], &utxo.outpoint.vout.to_le_bytes());
+    push_pair(&mut bytes, &[0x12], &100u32.to_le_bytes());
+    bytes.push(0x00);
+    push_pair(&mut bytes, &[0x03], &amount.to_sat().to_le_bytes());
+    push_pair(&mut bytes, &[0x04], send_to.script_pubkey().as_bytes());
+    bytes.push(0x00);
+
+    let mut psbt = PsbtV2::deserialize(&bytes).unwrap();
+    assert_eq!(psbt.unsigned_tx.lock_time.to_consensus_u32(), 100);
+    assert_eq!(psbt.unsigned_tx.input[0].previous_output, utxo.outpoint);
+    assert_eq!(psbt.unsigned_tx.output[0].value, amount);
+
+    let options = SignOptions {
+        trust_witness_utxo: true,
+        ..Default::default()
+    };
+    let finalized = wallet.sign(&mut psbt, options).unwrap();
+    assert!(finalized);
+    assert!(psbt.inputs[0].final_script_witness.is_some());
+
+    // the required height lock time is serialized again, it can't be converted to version 0
+    let signed = psbt.serialize().unwrap();
+    assert!(signed
+        .windows(7)
+        .any(|pair| pair == [0x01, 0x12, 0x04, 100, 0, 0, 0]));
+    assert!(matches!(
+        psbt_v2_to_v0(&signed),
+        Err(PsbtV2Error::Unrepresentable(
+            "PSBT_IN_REQUIRED_HEIGHT_LOCKTIME"
+        ))
+    ));
+}
+
+#[test]
+fn test_sign_psbt_without_key_origins() {
+    for (desc, change_desc) in [
+        get_test_wpkh_and_change_desc(),
+        get_test_tr_single_sig_xprv_and_change_desc(),
+    ] {
+        let (mut wallet, _) = get_funded_wallet(desc, change_desc);
+        let addr = wallet.next_unused_address(KeychainKind::External);
+        let mut builder = wallet.build_tx();
+        builder.add_recipient(addr.script_pubkey(), Amount::from_sat(10_000));
+        let mut psbt = builder.finish().unwrap();
+        let original = psbt.clone();
+
+        // strip the PSBT down to what an external tool may hand over
+        for input in &mut psbt.inputs {
+            input.bip32_derivation.clear();
+            input.tap_key_origins.clear();
+            input.tap_internal_key = None;
+            input.witness_utxo = None;
+            input.non_witness_utxo = None;
+        }
+        for output in &mut psbt.outputs {
+            output.bip32_derivation.clear();
+            output.tap_key_origins.clear();
+            output.tap_internal_key = None;
+        }
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).is_err());
+
+        assert!(wallet.populate_non_witness_utxos(&mut psbt).is_empty());
+        if psbt
+            .inputs
+            .iter()
+            .any(|input| input.non_witness_utxo.is_none())
+        {
+            // taproot inputs are skipped, the witness utxo is enough to sign them
+            for (input, original) in psbt.inputs.iter_mut().zip(&original.inputs) {
+                input.witness_utxo = original.witness_utxo.clone();
+            }
+        }
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        let tx = psbt.extract_tx().unwrap();
+        wallet.verify_tx(&tx).unwrap();
+    }
+}
+
+#[test]
+fn test_sign_signer_ordering() {
+    use bdk_wallet::signer::{
+        SignerCommon, SignerContext, SignerError, SignerId, SignerOrdering, SignerWrapper,
+        TransactionSigner,
+    };
+    use bitcoin::secp256k1::{All, Secp256k1};
+    use bitcoin::PrivateKey;
+    use miniscript::descriptor::KeyMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Records its calls, and signs with `inner` if any.
+    #[derive(Debug)]
+    struct MockSigner {
+        id: u64,
+        inner: Option<Arc<dyn TransactionSigner>>,
+        calls: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl SignerCommon for MockSigner {
+        fn id(&self, _secp: &Secp256k1<All>) -> SignerId {
+            SignerId::Dummy(self.id)
+        }
+    }
+
+    impl TransactionSigner for MockSigner {
+        fn sign_transaction(
+            &self,
+            psbt: &mut Psbt,
+            sign_options: &SignOptions,
+            secp: &Secp256k1<All>,
+        ) -> Result<(), SignerError> {
+            self.calls.lock().unwrap().push(self.id);
+            match &self.inner {
+                Some(inner) => inner.sign_transaction(psbt, sign_options, secp),
+                None => Ok(()),
+            }
+        }
+    }
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let mock = |id, inner| {
+        Arc::new(MockSigner {
+            id,
+            inner,
+            calls: Arc::clone(&calls),
+        })
+    };
+    let software: Arc<dyn TransactionSigner> = Arc::new(SignerWrapper::new(
+        PrivateKey::from_wif("cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW").unwrap(),
+        SignerContext::Segwitv0,
+    ));
+
+    // the wallet's own signer completes the PSBT, so the hardware one isn't called
+    let (mut wallet, _) = get_funded_wallet_single(get_test_wpkh());
+    wallet.add_signer(KeychainKind::External, SignerOrdering(200), mock(0, None));
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.drain_to(send_to.script_pubkey()).drain_wallet();
+    let psbt = builder.finish().unwrap();
+    assert!(wallet
+        .sign(&mut psbt.clone(), SignOptions::default())
+        .unwrap());
+    assert!(calls.lock().unwrap().is_empty());
+
+    // signers are called in ascending order until the PSBT is complete
+    wallet.set_keymap(KeychainKind::External, KeyMap::new());
+    wallet.add_signer(KeychainKind::External, SignerOrdering(150), mock(1, None));
+    wallet.add_signer(
+        KeychainKind::Internal,
+        SignerOrdering(120),
+        mock(2, Some(software)),
+    );
+    wallet.add_signer(KeychainKind::External, SignerOrdering(50), mock(3, None));
+    assert!(wallet
+        .sign(&mut psbt.clone(), SignOptions::default())
+        .unwrap());
+    assert_eq!(*calls.lock().unwrap(), [3, 2]);
+}
+
+#[test]
+fn test_psbt_from_tx_round_trip() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let mut psbt = builder.finish().unwrap();
+    let draft = psbt.unsigned_tx.clone();
+
+    let mut rebuilt = wallet.psbt_from_tx(&draft).unwrap();
+    assert_eq!(rebuilt.unsigned_tx, draft);
+    assert_eq!(rebuilt.inputs[0].witness_utxo, psbt.inputs[0].witness_utxo);
+    assert_eq!(
+        rebuilt.inputs[0].bip32_derivation,
+        psbt.inputs[0].bip32_derivation
+    );
+    assert_eq!(rebuilt.outputs, psbt.outputs);
+
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    assert!(wallet.sign(&mut rebuilt, SignOptions::default()).unwrap());
+    let signed = psbt.extract_tx().unwrap();
+    assert_eq!(rebuilt.extract_tx().unwrap(), signed);
+
+    // the signatures of an already signed transaction are dropped
+    let rebuilt = wallet.psbt_from_tx(&signed).unwrap();
+    assert_eq!(rebuilt.unsigned_tx, draft);
+    assert!(rebuilt.inputs[0].final_script_witness.is_none());
+}
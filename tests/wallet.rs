@@ -2,26 +2,47 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use assert_matches::assert_matches;
-use bdk_chain::{BlockId, CanonicalizationParams, ConfirmationBlockTime};
+use bdk_chain::spk_client::{FullScanRequest, FullScanResponse, SyncRequest, SyncResponse};
+use bdk_chain::{
+    BlockId, CanonicalizationParams, ChainPosition, CheckPoint, ConfirmationBlockTime,
+};
 use bdk_wallet::coin_selection;
-use bdk_wallet::descriptor::{calc_checksum, DescriptorError, IntoWalletDescriptor};
-use bdk_wallet::error::CreateTxError;
+use bdk_wallet::descriptor::{
+    calc_checksum, DescriptorError, ExtendedDescriptor, IntoWalletDescriptor,
+};
+use bdk_wallet::error::{CreateTxError, VerifyError};
+use bdk_wallet::fee_estimation::{
+    ClampedFeeEstimator, FeeEstimationError, FeeEstimator, StaticFeeEstimator,
+};
+use bdk_wallet::keys::slip132::ScriptType;
+use bdk_wallet::migration::{plan_migration, plan_migration_with_weight_limit, MigrationError};
 use bdk_wallet::psbt::PsbtUtils;
+use bdk_wallet::scantxoutset::{ScanTxOutSetError, ScanTxOutSetResult};
 use bdk_wallet::signer::{SignOptions, SignerError, SignersContainer};
 use bdk_wallet::test_utils::*;
+use bdk_wallet::validation::{
+    ApplyError, ApplyOptions, HeaderProvider, HeaderProviderError, ValidationError,
+};
 use bdk_wallet::KeychainKind;
-use bdk_wallet::{AddressInfo, Balance, PersistedWallet, Update, Wallet, WalletTx};
+use bdk_wallet::{
+    AddressInfo, AddressStatus, AddressType, Balance, BroadcastError, HistoryOrder, LeftoverPolicy,
+    MultiSyncRequest, PersistedWallet, ScanCursor, Update, UpdateBuilderError, UtxoStats, Wallet,
+    WalletTx, WalletTxOrd,
+};
+use bitcoin::bip32::{ChildNumber, Fingerprint, Xpriv, Xpub};
+use bitcoin::block::{self, Block};
 use bitcoin::constants::COINBASE_MATURITY;
 use bitcoin::hashes::Hash;
 use bitcoin::script::PushBytesBuf;
 use bitcoin::sighash::{EcdsaSighashType, TapSighashType};
 use bitcoin::taproot::TapNodeHash;
 use bitcoin::{
-    absolute, transaction, Address, Amount, BlockHash, FeeRate, Network, NetworkKind, OutPoint,
-    ScriptBuf, Sequence, SignedAmount, Transaction, TxIn, TxOut, Txid,
+    absolute, transaction, Address, Amount, BlockHash, CompactTarget, FeeRate, Network,
+    NetworkKind, OutPoint, ScriptBuf, Sequence, SignedAmount, Transaction, TxIn, TxMerkleNode,
+    TxOut, Txid, Weight,
 };
 use rand::rngs::StdRng;
-use rand::SeedableRng;
+use rand::{RngCore, SeedableRng};
 
 mod common;
 
@@ -68,6 +89,42 @@ fn test_descriptor_checksum() {
     assert_eq!(calc_checksum(&raw_descriptor).unwrap(), checksum);
 }
 
+#[test]
+fn test_multipath_descriptor() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let (wallet, _) = get_funded_wallet(desc, change_desc);
+    let multipath = wallet.multipath_descriptor().unwrap();
+    assert!(multipath.contains("/<0;1>/*"));
+
+    let multipath = ExtendedDescriptor::from_str(&multipath).unwrap();
+    assert_eq!(
+        multipath.into_single_descriptors().unwrap(),
+        vec![
+            wallet.public_descriptor(KeychainKind::External).clone(),
+            wallet.public_descriptor(KeychainKind::Internal).clone(),
+        ]
+    );
+
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let (wallet, _) = get_funded_wallet(desc, change_desc);
+    assert!(wallet.multipath_descriptor().unwrap().starts_with("tr("));
+
+    // different keys
+    let (wallet, _) = get_funded_wallet(get_test_wpkh_and_change_desc().0, get_test_wpkh());
+    assert_eq!(wallet.multipath_descriptor(), None);
+
+    // same key with a different derivation step
+    let (wallet, _) = get_funded_wallet(
+        desc,
+        "tr(tprv8ZgxMBicQKsPdDArR4xSAECuVxeX1jwwSXR4ApKbkYgZiziDc4LdBy2WvJeGDfUSE4UT4hHhbgEwbdq8ajjUHiKDegkwrNU6V55CxcxonVN/2/*)",
+    );
+    assert_eq!(wallet.multipath_descriptor(), None);
+
+    // no change descriptor
+    let (wallet, _) = get_funded_wallet_single(desc);
+    assert_eq!(wallet.multipath_descriptor(), None);
+}
+
 #[test]
 fn test_get_funded_wallet_balance() {
     let (wallet, _) = get_funded_wallet_wpkh();
@@ -403,6 +460,81 @@ fn test_create_tx_custom_rbf_sequence() {
     assert_eq!(psbt.unsigned_tx.input[0].sequence, Sequence(0xDEADBEEF));
 }
 
+#[test]
+fn test_create_tx_disable_rbf() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let psbt = builder.finish().unwrap();
+    assert!(psbt.unsigned_tx.is_explicitly_rbf());
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .disable_rbf();
+    let psbt = builder.finish().unwrap();
+    assert!(!psbt.unsigned_tx.is_explicitly_rbf());
+    assert_eq!(psbt.unsigned_tx.input[0].sequence, Sequence::MAX);
+}
+
+#[test]
+fn test_create_tx_disable_rbf_timelocks() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_csv());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .disable_rbf();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::RbfSequenceCsv { sequence, .. }) if sequence == Sequence::MAX
+    );
+
+    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_cltv());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .disable_rbf();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::FinalSequenceLockTime { required })
+        if required.to_consensus_u32() == 100_000
+    );
+}
+
+#[test]
+fn test_create_tx_enable_rbf_with_sequence() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .enable_rbf_with_sequence(Sequence(0xFFFFFFF0));
+    let psbt = builder.finish().unwrap();
+    assert!(psbt.unsigned_tx.is_explicitly_rbf());
+    assert_eq!(psbt.unsigned_tx.input[0].sequence, Sequence(0xFFFFFFF0));
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .enable_rbf_with_sequence(Sequence::ENABLE_LOCKTIME_NO_RBF);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::RbfSequence(Sequence::ENABLE_LOCKTIME_NO_RBF))
+    );
+
+    // the sequence still has to satisfy the OP_CSV
+    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_csv());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .enable_rbf_with_sequence(Sequence(3));
+    assert_matches!(builder.finish(), Err(CreateTxError::RbfSequenceCsv { .. }));
+}
+
 #[test]
 fn test_create_tx_change_policy() {
     let (mut wallet, _) = get_funded_wallet_wpkh();
@@ -539,6 +671,137 @@ fn test_create_tx_custom_fee_rate() {
     assert_fee_rate!(psbt, fee, FeeRate::from_sat_per_vb_u32(5), @add_signature);
 }
 
+#[test]
+fn test_create_tx_min_relay_fee() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    assert_eq!(wallet.min_relay_fee(), FeeRate::BROADCAST_MIN);
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let half_sat_per_vb = FeeRate::from_sat_per_kwu(125);
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(half_sat_per_vb);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::FeeRateBelowMinRelay { required }) if required == FeeRate::BROADCAST_MIN
+    );
+
+    wallet.set_min_relay_fee(half_sat_per_vb);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(half_sat_per_vb);
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+    assert_fee_rate!(psbt, fee, half_sat_per_vb, @add_signature);
+
+    // a floor above the dust relay fee raises the dust limit
+    let dust_limit = addr.script_pubkey().minimal_non_dust();
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), dust_limit);
+    assert!(builder.finish().is_ok());
+    wallet.set_min_relay_fee(FeeRate::from_sat_per_vb_u32(10));
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), dust_limit)
+        .fee_rate(FeeRate::from_sat_per_vb_u32(10));
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::OutputBelowDustLimit(0))
+    );
+}
+
+#[test]
+fn test_create_tx_change_below_dust_relay_fee() {
+    let fee_rate = FeeRate::from_sat_per_vb_u32(10);
+    let build = |min_relay_fee: Option<FeeRate>| {
+        let (mut wallet, _) = get_funded_wallet_wpkh();
+        if let Some(min_relay_fee) = min_relay_fee {
+            wallet.set_min_relay_fee(min_relay_fee);
+        }
+        let addr = wallet.peek_address(KeychainKind::External, 0);
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(48_000))
+            .fee_rate(fee_rate);
+        let psbt = builder.finish().unwrap();
+        let fee = check_fee!(wallet, psbt);
+        let change_dust_limit = wallet.dust_value(KeychainKind::Internal);
+        (psbt, fee, change_dust_limit)
+    };
+
+    // the change is above the default dust limit
+    let (psbt, _, change_dust_limit) = build(None);
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+    let change = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .find(|txout| txout.value != Amount::from_sat(48_000))
+        .unwrap()
+        .value;
+    assert!(change >= change_dust_limit);
+
+    // but is dust at the wallet's minimum relay fee, so it goes to the fee
+    let (psbt, fee, change_dust_limit) = build(Some(fee_rate));
+    assert!(change < change_dust_limit);
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert_eq!(fee, Amount::from_sat(50_000 - 48_000));
+}
+
+#[test]
+fn test_create_tx_fee_rate_from_estimator() {
+    struct MockEstimator;
+    impl FeeEstimator for MockEstimator {
+        fn estimate(&self, target_blocks: usize) -> Result<FeeRate, FeeEstimationError> {
+            match target_blocks {
+                0 => Err(FeeEstimationError::Unavailable(0)),
+                n => Ok(FeeRate::from_sat_per_vb_u32(60 / n as u32)),
+            }
+        }
+    }
+    let estimator = ClampedFeeEstimator::new(
+        MockEstimator,
+        FeeRate::from_sat_per_vb_u32(2),
+        FeeRate::from_sat_per_vb_u32(20),
+    );
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    for (target_blocks, expected) in [(1, 20), (6, 10), (144, 2)] {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+            .fee_rate_from(&estimator, target_blocks);
+        let psbt = builder.finish().unwrap();
+        let fee = check_fee!(wallet, psbt);
+        assert_fee_rate!(psbt, fee, FeeRate::from_sat_per_vb_u32(expected), @add_signature);
+    }
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate_from(&estimator, 0);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::FeeEstimation(
+            FeeEstimationError::Unavailable(0)
+        ))
+    );
+
+    // a fee rate set afterwards replaces the estimator
+    let static_estimator = StaticFeeEstimator(FeeRate::from_sat_per_vb_u32(7));
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate_from(&static_estimator, 6)
+        .fee_rate(FeeRate::from_sat_per_vb_u32(3));
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+    assert_fee_rate!(psbt, fee, FeeRate::from_sat_per_vb_u32(3), @add_signature);
+}
+
 #[test]
 fn test_legacy_create_tx_custom_fee_rate() {
     let (mut wallet, _) = get_funded_wallet_single(get_test_pkh());
@@ -695,6 +958,162 @@ fn test_create_tx_skip_change_dust() {
     assert_eq!(fee, Amount::from_sat(200));
 }
 
+#[test]
+fn test_create_tx_min_change() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(48_000));
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+    let change = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .find(|txout| txout.script_pubkey != addr.script_pubkey())
+        .unwrap()
+        .value;
+
+    // change just below the minimum goes to the fee
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(48_000))
+        .min_change(change + Amount::from_sat(1));
+    let psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert_eq!(psbt.unsigned_tx.output[0].value.to_sat(), 48_000);
+    assert_eq!(fee, Amount::from_sat(2_000));
+
+    // change at the minimum is kept
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(48_000))
+        .min_change(change);
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+
+    // the minimum doesn't apply when draining to a single output
+    let mut builder = wallet.build_tx();
+    builder
+        .drain_to(addr.script_pubkey())
+        .drain_wallet()
+        .min_change(Amount::from_sat(100_000));
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+}
+
+#[test]
+fn test_create_tx_leftover_policy() {
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let utxo = OutPoint { txid, vout: 0 };
+    assert_eq!(
+        wallet.get_utxo(utxo).unwrap().txout.value,
+        Amount::from_sat(50_000)
+    );
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let create_tx = |wallet: &mut Wallet, policy: Option<LeftoverPolicy>| {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_utxo(utxo)
+            .unwrap()
+            .manually_selected_only()
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(45_000))
+            .fee_rate(FeeRate::from_sat_per_vb_u32(1));
+        if let Some(policy) = policy {
+            builder.leftover_policy(policy);
+        }
+        builder.finish().unwrap()
+    };
+
+    let default = create_tx(&mut wallet, None);
+    let psbt = create_tx(&mut wallet, Some(LeftoverPolicy::CreateChange));
+    assert_eq!(check_fee!(wallet, psbt), check_fee!(wallet, default));
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+    let change = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .find(|txout| txout.script_pubkey != addr.script_pubkey())
+        .unwrap()
+        .value;
+
+    let psbt = create_tx(
+        &mut wallet,
+        Some(LeftoverPolicy::AddToFee {
+            max_leftover: Amount::from_sat(5_000),
+        }),
+    );
+    let fee = check_fee!(wallet, psbt);
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert_eq!(fee, Amount::from_sat(5_000));
+
+    let psbt = create_tx(
+        &mut wallet,
+        Some(LeftoverPolicy::Threshold { min_change: change }),
+    );
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+    let psbt = create_tx(
+        &mut wallet,
+        Some(LeftoverPolicy::Threshold {
+            min_change: change + Amount::from_sat(1),
+        }),
+    );
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+}
+
+#[test]
+fn test_create_tx_leftover_policy_add_to_fee() {
+    use coin_selection::LargestFirstCoinSelection;
+
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let small = OutPoint { txid, vout: 0 };
+    let large = receive_output_in_latest_block(&mut wallet, Amount::from_btc(1.0).unwrap());
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let add_to_fee = LeftoverPolicy::AddToFee {
+        max_leftover: Amount::from_sat(10_000),
+    };
+
+    // inputs needing no change are looked for, even if the algorithm would pick other ones
+    let mut builder = wallet.build_tx().coin_selection(LargestFirstCoinSelection);
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(48_750))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(10))
+        .leftover_policy(add_to_fee);
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    assert_eq!(psbt.unsigned_tx.input[0].previous_output, small);
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert_eq!(check_fee!(wallet, psbt), Amount::from_sat(1_250));
+
+    // paying 0.01 BTC from the 1 BTC UTXO would give most of it away to the fee
+    let mut builder = wallet.build_tx();
+    builder
+        .add_utxo(large)
+        .unwrap()
+        .manually_selected_only()
+        .add_recipient(addr.script_pubkey(), Amount::from_btc(0.01).unwrap())
+        .leftover_policy(add_to_fee);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::LeftoverAboveMax { leftover, max_leftover })
+            if leftover > Amount::from_btc(0.98).unwrap()
+                && max_leftover == Amount::from_sat(10_000)
+    );
+
+    // the same when the algorithm falls back to selecting the large UTXO
+    let mut builder = wallet.build_tx().coin_selection(LargestFirstCoinSelection);
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_btc(0.01).unwrap())
+        .leftover_policy(add_to_fee);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::LeftoverAboveMax { .. })
+    );
+}
+
 #[test]
 #[should_panic(expected = "InsufficientFunds")]
 fn test_create_tx_drain_to_dust_amount() {
@@ -748,19 +1167,211 @@ fn test_create_tx_ordering_respected() {
 }
 
 #[test]
-fn test_create_tx_default_sighash() {
+fn test_create_tx_unspendable() {
+    use coin_selection::LargestFirstCoinSelection;
+
     let (mut wallet, _) = get_funded_wallet_wpkh();
-    let addr = wallet.next_unused_address(KeychainKind::External);
+    let largest = receive_output_in_latest_block(&mut wallet, Amount::from_sat(1_000_000));
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+
+    let spends = |psbt: &bitcoin::Psbt| {
+        psbt.unsigned_tx
+            .input
+            .iter()
+            .any(|txin| txin.previous_output == largest)
+    };
+
+    // the largest utxo is picked first, unless it's unspendable
+    let mut builder = wallet.build_tx().coin_selection(LargestFirstCoinSelection);
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(20_000));
+    assert!(spends(&builder.finish().unwrap()));
+
+    let mut builder = wallet.build_tx().coin_selection(LargestFirstCoinSelection);
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(20_000))
+        .unspendable(vec![largest]);
+    assert!(!spends(&builder.finish().unwrap()));
+
     let mut builder = wallet.build_tx();
-    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(30_000));
-    let psbt = builder.finish().unwrap();
+    builder
+        .drain_to(addr.script_pubkey())
+        .drain_wallet()
+        .add_unspendable(largest);
+    assert!(!spends(&builder.finish().unwrap()));
 
-    assert_eq!(psbt.inputs[0].sighash_type, None);
+    // it's only unspendable for that transaction
+    assert!(!wallet.is_outpoint_locked(largest));
+
+    // and manually selected utxos are spent anyway
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(20_000))
+        .unspendable(vec![largest])
+        .add_utxo(largest)
+        .unwrap();
+    assert!(spends(&builder.finish().unwrap()));
 }
 
 #[test]
-fn test_legacy_create_tx_default_sighash() {
-    let (mut wallet, _) = get_funded_wallet_single(get_test_pkh());
+fn test_create_tx_add_recipient_address() {
+    use bdk_wallet::tx_builder::AddressNetworkMismatch;
+    use bitcoin::address::NetworkUnchecked;
+    use bitcoin::CompressedPublicKey;
+
+    let pk = CompressedPublicKey::from_str(
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    )
+    .unwrap();
+    let networks = [
+        Network::Bitcoin,
+        Network::Testnet,
+        Network::Testnet4,
+        Network::Signet,
+        Network::Regtest,
+    ];
+    for wallet_network in networks {
+        let mut wallet = Wallet::create_single(
+            "wpkh(02e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c)",
+        )
+        .network(wallet_network)
+        .create_wallet_no_persist()
+        .unwrap();
+        assert_eq!(wallet.network(), wallet_network);
+
+        for address_network in networks {
+            for address in [
+                Address::p2wpkh(&pk, address_network),
+                Address::p2pkh(pk, address_network),
+            ] {
+                // testnet, testnet4 and signet addresses are the same, and so are regtest ones
+                // except for segwit
+                let parsed: Address<NetworkUnchecked> = address.to_string().parse().unwrap();
+                let is_test = |network| network != Network::Bitcoin;
+                let valid = match (wallet_network, address_network) {
+                    (Network::Regtest, Network::Regtest) => true,
+                    (Network::Regtest, network) | (network, Network::Regtest) => {
+                        is_test(network) && address.script_pubkey().is_p2pkh()
+                    }
+                    (wallet_network, address_network) => {
+                        is_test(wallet_network) == is_test(address_network)
+                    }
+                };
+
+                let mut builder = wallet.build_tx();
+                let result = builder.add_recipient_address(&parsed, Amount::from_sat(10_000));
+                if valid {
+                    assert!(
+                        result.is_ok(),
+                        "{address} must be valid on {wallet_network}"
+                    );
+                } else {
+                    assert_eq!(
+                        result.err(),
+                        Some(AddressNetworkMismatch {
+                            address: parsed.clone(),
+                            network: wallet_network,
+                        }),
+                        "{address} must not be valid on {wallet_network}"
+                    );
+                }
+            }
+        }
+    }
+
+    // the address is added as a recipient
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let address = wallet.peek_address(KeychainKind::External, 5).address;
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient_address(&address, Amount::from_sat(25_000))
+        .unwrap();
+    let psbt = builder.finish().unwrap();
+    assert!(psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey == address.script_pubkey()
+            && txout.value == Amount::from_sat(25_000)));
+}
+
+#[test]
+fn test_create_tx_bip69_ordering() {
+    use bdk_wallet::TxOrdering;
+
+    let build = |ordering: TxOrdering| {
+        // the wallet reserves the change address of every tx it builds, so use a fresh one
+        let (mut wallet, _) = get_funded_wallet_wpkh();
+        for value in [20_000, 5_000, 40_000, 15_000] {
+            receive_output(&mut wallet, Amount::from_sat(value), ReceiveTo::Mempool(0));
+        }
+        let utxos = wallet
+            .list_unspent()
+            .map(|utxo| utxo.outpoint)
+            .collect::<Vec<_>>();
+        let addr = wallet.peek_address(KeychainKind::External, 0);
+        let mut builder = wallet.build_tx();
+        builder
+            .add_utxos(&utxos)
+            .unwrap()
+            .manually_selected_only()
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(30_000))
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+            .ordering(ordering);
+        let psbt = builder.finish().unwrap();
+        let fee = check_fee!(wallet, psbt);
+        (psbt, fee)
+    };
+
+    let (psbt_1, fee) = build(TxOrdering::Bip69);
+    let (psbt_2, _) = build(TxOrdering::Bip69);
+    assert_eq!(psbt_1.unsigned_tx, psbt_2.unsigned_tx);
+
+    let inputs = psbt_1
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|txin| txin.previous_output)
+        .collect::<Vec<_>>();
+    assert_eq!(inputs.len(), 5);
+    let mut sorted_inputs = inputs.clone();
+    sorted_inputs.sort_by_key(|outpoint| (outpoint.txid.to_string(), outpoint.vout));
+    assert_eq!(inputs, sorted_inputs);
+
+    let values = psbt_1
+        .unsigned_tx
+        .output
+        .iter()
+        .map(|txout| txout.value)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        [
+            Amount::from_sat(10_000),
+            Amount::from_sat(30_000),
+            Amount::from_sat(90_000) - fee,
+        ]
+    );
+
+    // the default ordering is still randomized
+    assert_matches!(TxOrdering::default(), TxOrdering::Shuffle);
+}
+
+#[test]
+fn test_create_tx_default_sighash() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(30_000));
+    let psbt = builder.finish().unwrap();
+
+    assert_eq!(psbt.inputs[0].sighash_type, None);
+}
+
+#[test]
+fn test_legacy_create_tx_default_sighash() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_pkh());
     let addr = wallet.next_unused_address(KeychainKind::External);
     let mut builder = wallet.build_tx();
     builder.add_recipient(addr.script_pubkey(), Amount::from_sat(30_000));
@@ -801,6 +1412,49 @@ fn test_legacy_create_tx_custom_sighash() {
     );
 }
 
+#[test]
+fn test_create_tx_sighash_single_unpaired_input() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_pkh());
+    receive_output_in_latest_block(&mut wallet, Amount::from_sat(20_000));
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    // two inputs and a single output
+    for sighash in [
+        EcdsaSighashType::Single.into(),
+        EcdsaSighashType::SinglePlusAnyoneCanPay.into(),
+        TapSighashType::Single.into(),
+    ] {
+        let mut builder = wallet.build_tx();
+        builder
+            .drain_to(addr.script_pubkey())
+            .drain_wallet()
+            .sighash(sighash);
+        assert_matches!(
+            builder.finish(),
+            Err(CreateTxError::SighashSingleUnpairedInput(1))
+        );
+    }
+
+    // fine with as many outputs as inputs
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(60_000))
+        .drain_to(addr.script_pubkey())
+        .drain_wallet()
+        .sighash(EcdsaSighashType::Single.into());
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 2);
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+
+    // other sighash types don't pair inputs and outputs
+    let mut builder = wallet.build_tx();
+    builder
+        .drain_to(addr.script_pubkey())
+        .drain_wallet()
+        .sighash(EcdsaSighashType::All.into());
+    assert!(builder.finish().is_ok());
+}
+
 #[test]
 fn test_create_tx_input_hd_keypaths() {
     use bitcoin::bip32::{DerivationPath, Fingerprint};
@@ -964,6 +1618,382 @@ fn test_create_tx_both_non_witness_utxo_and_witness_utxo_default() {
     assert!(psbt.inputs[0].witness_utxo.is_some());
 }
 
+#[test]
+fn test_create_tx_non_witness_utxo_script_types() {
+    for (desc, is_witness) in [
+        (
+            "pkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)",
+            false,
+        ),
+        (
+            "sh(wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW))",
+            true,
+        ),
+        (
+            "wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)",
+            true,
+        ),
+    ] {
+        let (mut wallet, txid) = get_funded_wallet_single(desc);
+        let addr = wallet.next_unused_address(KeychainKind::External);
+
+        let mut builder = wallet.build_tx();
+        builder.drain_to(addr.script_pubkey()).drain_wallet();
+        let psbt = builder.finish().unwrap();
+        let prev_tx = psbt.inputs[0].non_witness_utxo.as_ref();
+        assert_eq!(prev_tx.map(|tx| tx.compute_txid()), Some(txid), "{desc}");
+
+        // Only legacy inputs keep the previous transaction when opting out
+        let mut builder = wallet.build_tx();
+        builder
+            .drain_to(addr.script_pubkey())
+            .drain_wallet()
+            .only_witness_utxo();
+        let mut psbt = builder.finish().unwrap();
+        assert_eq!(
+            psbt.inputs[0].non_witness_utxo.is_none(),
+            is_witness,
+            "{desc}"
+        );
+
+        assert!(wallet.populate_non_witness_utxos(&mut psbt).is_empty());
+        let prev_tx = psbt.inputs[0].non_witness_utxo.as_ref();
+        assert_eq!(prev_tx.map(|tx| tx.compute_txid()), Some(txid), "{desc}");
+    }
+}
+
+#[test]
+fn test_populate_non_witness_utxos_missing() {
+    let (mut wallet1, _) = get_funded_wallet_wpkh();
+    let (wallet2, _) =
+        get_funded_wallet_single("wpkh(cVbZ8ovhye9AoAHFsqobCf7LxbXDAECy9Kb8TZdfsDYMZGBUyCnm)");
+    let addr = wallet1.next_unused_address(KeychainKind::External);
+    let utxo2 = wallet2.list_unspent().next().unwrap();
+    let satisfaction_weight = wallet2
+        .public_descriptor(KeychainKind::External)
+        .max_weight_to_satisfy()
+        .unwrap();
+
+    let mut builder = wallet1.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(60_000))
+        .only_witness_utxo()
+        .add_foreign_utxo(
+            utxo2.outpoint,
+            bitcoin::psbt::Input {
+                witness_utxo: Some(utxo2.txout.clone()),
+                ..Default::default()
+            },
+            satisfaction_weight,
+        )
+        .unwrap();
+    let mut psbt = builder.finish().unwrap();
+    let foreign = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .position(|txin| txin.previous_output == utxo2.outpoint)
+        .unwrap();
+
+    // The foreign transaction isn't in the graph of the first wallet
+    assert_eq!(wallet1.populate_non_witness_utxos(&mut psbt), vec![foreign]);
+    assert!(psbt.inputs[foreign].non_witness_utxo.is_none());
+    assert!(psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .all(|(i, input)| i == foreign || input.non_witness_utxo.is_some()));
+    assert!(wallet2.populate_non_witness_utxos(&mut psbt).is_empty());
+}
+
+#[test]
+fn test_populate_non_witness_utxos_skips_taproot() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.drain_to(addr.script_pubkey()).drain_wallet();
+    let mut psbt = builder.finish().unwrap();
+
+    assert!(wallet.populate_non_witness_utxos(&mut psbt).is_empty());
+    assert!(psbt.inputs[0].non_witness_utxo.is_none());
+}
+
+#[test]
+fn test_conflicting_txs() {
+    let (mut wallet, funding_txid) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    let mut txs = Vec::new();
+    for amount in [10_000, 20_000] {
+        let mut builder = wallet.build_tx();
+        builder.add_recipient(addr.script_pubkey(), Amount::from_sat(amount));
+        let mut psbt = builder.finish().unwrap();
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        txs.push(psbt.extract_tx().unwrap());
+    }
+    let txid1 = txs[0].compute_txid();
+    let txid2 = txs[1].compute_txid();
+    for (tx, seen_at) in txs.into_iter().zip([100, 200]) {
+        let txid = tx.compute_txid();
+        insert_tx(&mut wallet, tx);
+        insert_seen_at(&mut wallet, txid, seen_at);
+    }
+
+    // unconfirmed double-spends
+    assert_eq!(wallet.conflicting_txs(txid1), vec![txid2]);
+    assert_eq!(wallet.conflicting_txs(txid2), vec![txid1]);
+    assert!(wallet.conflicting_txs(funding_txid).is_empty());
+    assert!(wallet
+        .conflicting_txs(Txid::from_byte_array([1; 32]))
+        .is_empty());
+
+    // the replaced transaction is still reported once the other is confirmed
+    let anchor = ConfirmationBlockTime {
+        block_id: wallet.latest_checkpoint().block_id(),
+        confirmation_time: 1_000,
+    };
+    insert_anchor(&mut wallet, txid1, anchor);
+    assert_eq!(wallet.conflicting_txs(txid1), vec![txid2]);
+    assert_eq!(wallet.conflicting_txs(txid2), vec![txid1]);
+}
+
+#[test]
+fn test_replacement_chain() {
+    let (mut wallet, funding_txid) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(2));
+    let tx = builder.finish().unwrap().extract_tx().unwrap();
+    let original = tx.compute_txid();
+    wallet.apply_unconfirmed_txs([(tx, 100)]);
+    assert_eq!(wallet.replacement_chain(original), vec![original]);
+
+    let mut bumps = Vec::new();
+    let mut txid = original;
+    for (fee_rate, seen_at) in [(5, 200), (10, 300)] {
+        let mut builder = wallet.build_fee_bump(txid).unwrap();
+        builder.fee_rate(FeeRate::from_sat_per_vb_u32(fee_rate));
+        let tx = builder.finish().unwrap().extract_tx().unwrap();
+        txid = tx.compute_txid();
+        wallet.apply_unconfirmed_txs([(tx, seen_at)]);
+        bumps.push(txid);
+    }
+    let chain = vec![original, bumps[0], bumps[1]];
+    assert_eq!(wallet.replacement_chain(original), chain);
+    assert_eq!(wallet.replacement_chain(bumps[1]), chain);
+
+    // the intermediate replacement is evicted from the mempool
+    wallet.apply_evicted_txs([(bumps[0], 310)]);
+    assert!(wallet.get_tx(bumps[0]).is_none());
+    assert_eq!(wallet.replacement_chain(bumps[0]), chain);
+    assert_eq!(wallet.replacement_chain(original), chain);
+
+    assert_eq!(wallet.replacement_chain(funding_txid), vec![funding_txid]);
+    assert!(wallet
+        .replacement_chain(Txid::from_byte_array([1; 32]))
+        .is_empty());
+}
+
+#[test]
+fn test_broadcast_and_apply() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(10_000));
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().unwrap();
+    let txid = tx.compute_txid();
+
+    // a failed broadcast doesn't change the wallet
+    let staged = wallet.staged().cloned();
+    let err = wallet
+        .broadcast_and_apply(&tx, &MockBroadcaster::rejecting())
+        .unwrap_err();
+    assert!(matches!(err, BroadcastError::Rejected(_)));
+    assert!(wallet.get_tx(txid).is_none());
+    assert_eq!(wallet.staged().cloned(), staged);
+
+    let broadcaster = MockBroadcaster::default();
+    assert_eq!(wallet.broadcast_and_apply(&tx, &broadcaster).unwrap(), txid);
+    assert_eq!(broadcaster.broadcasted(), vec![tx]);
+    let wallet_tx = wallet.get_tx(txid).expect("tx must be in the wallet");
+    assert!(!wallet_tx.chain_position.is_confirmed());
+}
+
+#[tokio::test]
+async fn test_broadcast_and_apply_async() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(10_000));
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().unwrap();
+    let txid = tx.compute_txid();
+
+    assert!(wallet
+        .broadcast_and_apply_async(&tx, &MockBroadcaster::rejecting())
+        .await
+        .is_err());
+    assert!(wallet.get_tx(txid).is_none());
+
+    let broadcaster = MockBroadcaster::default();
+    let broadcasted = wallet.broadcast_and_apply_async(&tx, &broadcaster).await;
+    assert_eq!(broadcasted.unwrap(), txid);
+    assert!(wallet.get_tx(txid).is_some());
+}
+
+/// The index of the input that failed [`Wallet::verify_tx`].
+fn invalid_input_index(error: VerifyError) -> usize {
+    match error {
+        VerifyError::InvalidInput { index, .. } => index,
+        #[cfg(feature = "bitcoinconsensus")]
+        VerifyError::Consensus { index, .. } => index,
+    }
+}
+
+#[test]
+fn test_verify_tx() {
+    for desc in [get_test_wpkh(), get_test_pkh(), get_test_tr_single_sig()] {
+        let (mut wallet, _) = get_funded_wallet_single(desc);
+        let addr = wallet.next_unused_address(KeychainKind::External);
+        let mut builder = wallet.build_tx();
+        builder.add_recipient(addr.script_pubkey(), Amount::from_sat(10_000));
+        let mut psbt = builder.finish().unwrap();
+
+        // not signed yet
+        let unsigned_tx = psbt.unsigned_tx.clone();
+        let error = wallet.verify_tx(&unsigned_tx).unwrap_err();
+        assert_eq!(invalid_input_index(error), 0);
+
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        let tx = psbt.extract_tx().unwrap();
+        wallet.verify_tx(&tx).unwrap();
+
+        // spending to a different output invalidates the signature
+        let mut tampered_tx = tx.clone();
+        tampered_tx.output[0].value -= Amount::from_sat(1);
+        let error = wallet.verify_tx(&tampered_tx).unwrap_err();
+        assert_eq!(invalid_input_index(error), 0);
+    }
+}
+
+#[test]
+fn test_verify_tx_foreign_inputs() {
+    for (desc, is_taproot) in [(get_test_wpkh(), false), (get_test_tr_single_sig(), true)] {
+        let (mut wallet1, _) = get_funded_wallet_single(desc);
+        let (wallet2, _) =
+            get_funded_wallet_single("wpkh(cVbZ8ovhye9AoAHFsqobCf7LxbXDAECy9Kb8TZdfsDYMZGBUyCnm)");
+        let addr = wallet1.next_unused_address(KeychainKind::External);
+        let utxo2 = wallet2.list_unspent().next().unwrap();
+        let satisfaction_weight = wallet2
+            .public_descriptor(KeychainKind::External)
+            .max_weight_to_satisfy()
+            .unwrap();
+
+        // a payjoin-like transaction with an input of another wallet
+        let mut builder = wallet1.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(60_000))
+            .only_witness_utxo()
+            .add_foreign_utxo(
+                utxo2.outpoint,
+                bitcoin::psbt::Input {
+                    witness_utxo: Some(utxo2.txout.clone()),
+                    ..Default::default()
+                },
+                satisfaction_weight,
+            )
+            .unwrap();
+        let mut psbt = builder.finish().unwrap();
+        let sign_options = SignOptions {
+            trust_witness_utxo: true,
+            ..Default::default()
+        };
+        wallet1.sign(&mut psbt, sign_options.clone()).unwrap();
+        wallet2.sign(&mut psbt, sign_options).unwrap();
+        let tx = psbt.extract_tx().unwrap();
+
+        // the taproot input can't be checked without the foreign spent output, but isn't
+        // reported as invalid
+        wallet1.verify_tx(&tx).unwrap();
+
+        // the other wallet inputs are still checked
+        let mut tampered_tx = tx.clone();
+        tampered_tx.output[0].value -= Amount::from_sat(1);
+        let result = wallet1.verify_tx(&tampered_tx);
+        if is_taproot {
+            assert!(result.is_ok());
+        } else {
+            let own = tx
+                .input
+                .iter()
+                .position(|txin| txin.previous_output != utxo2.outpoint)
+                .unwrap();
+            assert_eq!(invalid_input_index(result.unwrap_err()), own);
+        }
+    }
+}
+
+#[test]
+fn test_create_tx_add_recipient_with_label() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let other_addr = wallet.peek_address(KeychainKind::External, 10).address;
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(other_addr.script_pubkey(), Amount::from_sat(10_000))
+        .add_recipient_with_label(addr.script_pubkey(), Amount::from_sat(25_000), "invoice 42")
+        .ordering(bdk_wallet::TxOrdering::Shuffle);
+    let mut psbt = builder.finish().unwrap();
+
+    // the label is not committed when building the tx
+    assert_eq!(wallet.list_output_labels().count(), 0);
+    assert!(wallet
+        .staged()
+        .is_none_or(|cs| cs.labels.outputs.is_empty()));
+
+    // but only once the tx is applied to the wallet
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().unwrap();
+    let txid = tx.compute_txid();
+    let vout = tx
+        .output
+        .iter()
+        .position(|txout| txout.script_pubkey == addr.script_pubkey())
+        .unwrap() as u32;
+    wallet.apply_unconfirmed_txs([(tx, 100)]);
+
+    let outpoint = OutPoint::new(txid, vout);
+    assert_eq!(wallet.output_label(outpoint), Some("invoice 42"));
+    assert_eq!(
+        wallet.list_output_labels().collect::<Vec<_>>(),
+        vec![(outpoint, "invoice 42")]
+    );
+    assert_eq!(
+        wallet.staged().unwrap().labels.outputs.get(&outpoint),
+        Some(&Some("invoice 42".to_string()))
+    );
+
+    // labels can be changed and removed
+    wallet.set_output_label(outpoint, "paid");
+    assert_eq!(wallet.output_label(outpoint), Some("paid"));
+    assert_eq!(
+        wallet.remove_output_label(outpoint),
+        Some("paid".to_string())
+    );
+    assert_eq!(wallet.output_label(outpoint), None);
+    assert_eq!(
+        wallet.staged().unwrap().labels.outputs.get(&outpoint),
+        Some(&None)
+    );
+}
+
 #[test]
 fn test_create_tx_add_utxo() {
     let (mut wallet, _) = get_funded_wallet_wpkh();
@@ -1096,6 +2126,35 @@ fn test_create_tx_policy_path_no_csv() {
     assert_eq!(psbt.unsigned_tx.input[0].sequence, Sequence(0xFFFFFFFD));
 }
 
+#[test]
+fn test_policy_timelocks() {
+    use bdk_wallet::descriptor::policy::Timelock;
+
+    let (wallet, _) = get_funded_wallet_single(get_test_a_or_b_plus_csv());
+    let timelocks = wallet.policy_timelocks(KeychainKind::External).unwrap();
+    // only the path through key "B" is timelocked
+    assert_eq!(timelocks.len(), 1);
+    let (path, timelock) = &timelocks[0];
+    assert_eq!(*timelock, Timelock::Relative(Sequence(144)));
+    let root_id = wallet.policies(KeychainKind::External).unwrap().unwrap().id;
+    assert_eq!(path.policy_path[&root_id], vec![1]);
+
+    let (wallet, _) = get_funded_wallet_single(get_test_single_sig_cltv());
+    let timelocks = wallet.policy_timelocks(KeychainKind::External).unwrap();
+    assert_eq!(
+        timelocks.iter().map(|(_, t)| *t).collect::<Vec<_>>(),
+        vec![Timelock::Absolute(
+            absolute::LockTime::from_height(100_000).unwrap()
+        )]
+    );
+
+    let (wallet, _) = get_funded_wallet_wpkh();
+    assert!(wallet
+        .policy_timelocks(KeychainKind::External)
+        .unwrap()
+        .is_empty());
+}
+
 #[test]
 fn test_create_tx_policy_path_use_csv() {
     let (mut wallet, _) = get_funded_wallet_single(get_test_a_or_b_plus_csv());
@@ -1139,18 +2198,102 @@ fn test_create_tx_policy_path_ignored_subtree_with_csv() {
 }
 
 #[test]
-fn test_create_tx_global_xpubs_with_origin() {
-    use bitcoin::bip32;
-    let (mut wallet, _) = get_funded_wallet_single("wpkh([73756c7f/48'/0'/0'/2']tpubDCKxNyM3bLgbEX13Mcd8mYxbVg9ajDkWXMh29hMWBurKfVmBfWAM96QVP3zaUcN51HvkZ3ar4VwP82kC8JZhhux8vFQoJintSpVBwpFvyU3/0/*)");
-    let addr = wallet.next_unused_address(KeychainKind::External);
-    let mut builder = wallet.build_tx();
-    builder
-        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
-        .add_global_xpubs();
-    let psbt = builder.finish().unwrap();
+fn test_policies_internal_keychain() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    let external_policy = wallet.policies(KeychainKind::External).unwrap().unwrap();
+    let internal_policy = wallet.policies(KeychainKind::Internal).unwrap().unwrap();
+    assert_matches!(
+        internal_policy.item,
+        bdk_wallet::descriptor::policy::SatisfiableItem::EcdsaSignature(_)
+    );
+    assert_eq!(external_policy.contribution, internal_policy.contribution);
+    assert!(wallet.policies_match().unwrap());
 
-    let key = bip32::Xpub::from_str("tpubDCKxNyM3bLgbEX13Mcd8mYxbVg9ajDkWXMh29hMWBurKfVmBfWAM96QVP3zaUcN51HvkZ3ar4VwP82kC8JZhhux8vFQoJintSpVBwpFvyU3").unwrap();
-    let fingerprint = bip32::Fingerprint::from_hex("73756c7f").unwrap();
+    // without a change descriptor the external policies are used for change
+    let wallet = Wallet::create_single(get_test_a_or_b_plus_csv())
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert_eq!(
+        wallet.policies(KeychainKind::Internal).unwrap(),
+        wallet.policies(KeychainKind::External).unwrap()
+    );
+    assert!(wallet.policies_match().unwrap());
+}
+
+#[test]
+fn test_policies_match() {
+    let tprv = "tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS";
+    let tpub = bitcoin::bip32::Xpub::from_priv(
+        &bitcoin::secp256k1::Secp256k1::new(),
+        &bitcoin::bip32::Xpriv::from_str(tprv).unwrap(),
+    );
+    let other_tpub = "tpubDDnGNapGEY6AZAdQbfRJgMg9fvz8pUBrLwvyvUqEgcUfgzM6zc2eVK4vY9x9L5FJWdX8WumXuLEDV5zDZnTfbn87vLe9XceCFwTu9so9Kks";
+    let create = |desc: String, change_desc: String| {
+        Wallet::create(desc, change_desc)
+            .network(Network::Testnet)
+            .create_wallet_no_persist()
+            .unwrap()
+    };
+
+    // the same keys and threshold match, even if only the external descriptor has private keys
+    let wallet = create(
+        format!("wsh(multi(2,{tprv}/0/*,{other_tpub}/0/*))"),
+        format!("wsh(multi(2,{tpub}/1/*,{other_tpub}/1/*))"),
+    );
+    assert!(wallet.policies_match().unwrap());
+    assert_ne!(
+        wallet
+            .policies(KeychainKind::External)
+            .unwrap()
+            .unwrap()
+            .contribution,
+        wallet
+            .policies(KeychainKind::Internal)
+            .unwrap()
+            .unwrap()
+            .contribution
+    );
+
+    // a lower threshold for change doesn't
+    let wallet = create(
+        format!("wsh(multi(2,{tpub}/0/*,{other_tpub}/0/*))"),
+        format!("wsh(multi(1,{tpub}/1/*,{other_tpub}/1/*))"),
+    );
+    assert!(!wallet.policies_match().unwrap());
+
+    // and neither do different keys
+    let wallet = create(
+        format!("wpkh({tpub}/0/*)"),
+        format!("wpkh({other_tpub}/1/*)"),
+    );
+    assert!(!wallet.policies_match().unwrap());
+
+    // or additional spending paths
+    let wallet = create(
+        format!("wsh(pk({tpub}/0/*))"),
+        format!("wsh(or_d(pk({tpub}/1/*),and_v(v:pk({other_tpub}/1/*),older(144))))"),
+    );
+    assert!(!wallet.policies_match().unwrap());
+}
+
+#[test]
+fn test_create_tx_global_xpubs_with_origin() {
+    use bitcoin::bip32;
+    let (mut wallet, _) = get_funded_wallet_single("wpkh([73756c7f/48'/0'/0'/2']tpubDCKxNyM3bLgbEX13Mcd8mYxbVg9ajDkWXMh29hMWBurKfVmBfWAM96QVP3zaUcN51HvkZ3ar4VwP82kC8JZhhux8vFQoJintSpVBwpFvyU3/0/*)");
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_global_xpubs();
+    let psbt = builder.finish().unwrap();
+
+    let key = bip32::Xpub::from_str("tpubDCKxNyM3bLgbEX13Mcd8mYxbVg9ajDkWXMh29hMWBurKfVmBfWAM96QVP3zaUcN51HvkZ3ar4VwP82kC8JZhhux8vFQoJintSpVBwpFvyU3").unwrap();
+    let fingerprint = bip32::Fingerprint::from_hex("73756c7f").unwrap();
     let path = bip32::DerivationPath::from_str("m/48'/0'/0'/2'").unwrap();
 
     assert_eq!(psbt.xpub.len(), 1);
@@ -1732,6 +2875,62 @@ fn test_unused_address() {
     );
 }
 
+#[test]
+fn test_address_statuses() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let (mut wallet, _) = get_funded_wallet(desc, change_desc);
+    let _ = wallet.reveal_addresses_to(KeychainKind::External, 2);
+    let addr = wallet.peek_address(KeychainKind::External, 2).address;
+    receive_output_to_address(
+        &mut wallet,
+        addr,
+        Amount::from_sat(10_000),
+        ReceiveTo::Mempool(0),
+    );
+
+    let statuses = wallet.address_statuses(KeychainKind::External, 0..5);
+    assert_eq!(
+        statuses
+            .iter()
+            .map(|(index, _, status)| (*index, *status))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, AddressStatus::Used),
+            (1, AddressStatus::Unused),
+            (2, AddressStatus::Used),
+            (3, AddressStatus::Unrevealed),
+            (4, AddressStatus::Unrevealed),
+        ]
+    );
+    for (index, address, _) in &statuses {
+        assert_eq!(
+            *address,
+            wallet.peek_address(KeychainKind::External, *index).address
+        );
+    }
+    assert!(wallet
+        .address_statuses(KeychainKind::External, 3..3)
+        .is_empty());
+
+    // a non-wildcard descriptor only has the index 0
+    let mut wallet = Wallet::create_single(get_test_wpkh())
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert_eq!(
+        wallet
+            .address_statuses(KeychainKind::External, 0..5)
+            .into_iter()
+            .map(|(index, _, status)| (index, status))
+            .collect::<Vec<_>>(),
+        vec![(0, AddressStatus::Unrevealed)]
+    );
+    let _ = wallet.reveal_next_address(KeychainKind::External);
+    assert!(wallet
+        .address_statuses(KeychainKind::External, 1..5)
+        .is_empty());
+}
+
 #[test]
 fn test_next_unused_address() {
     let descriptor = "wpkh(tpubEBr4i6yk5nf5DAaJpsi9N2pPYBeJ7fZ5Z9rmN4977iYLCGco1VyjB9tvvuvYtfZzjD5A8igzgw3HeWeeKFmanHYqksqZXYXGsw5zjnj7KM9/*)";
@@ -1972,498 +3171,966 @@ fn test_reveal_addresses() {
 }
 
 #[test]
-fn test_get_address_no_reuse() {
-    use bdk_wallet::descriptor::template::Bip84;
-    use std::collections::HashSet;
-
-    let key = bitcoin::bip32::Xpriv::from_str("tprv8ZgxMBicQKsPcx5nBGsR63Pe8KnRUqmbJNENAfGftF3yuXoMMoVJJcYeUw5eVkm9WBPjWYt6HMWYJNesB5HaNVBaFc1M6dRjWSYnmewUMYy").unwrap();
-    let mut wallet = Wallet::create(
-        Bip84(key, KeychainKind::External),
-        Bip84(key, KeychainKind::Internal),
-    )
-    .network(Network::Regtest)
-    .create_wallet_no_persist()
-    .unwrap();
+fn test_lookahead_for_keychain() {
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Signet)
+        .lookahead(5)
+        .lookahead_for(KeychainKind::External, 50)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert_eq!(wallet.lookahead(KeychainKind::External), 50);
+    assert_eq!(wallet.lookahead(KeychainKind::Internal), 5);
 
-    let mut used_set = HashSet::new();
+    let index = wallet.spk_index();
+    assert!(index.spk_at_index(KeychainKind::External, 49).is_some());
+    assert!(index.spk_at_index(KeychainKind::External, 50).is_none());
+    assert!(index.spk_at_index(KeychainKind::Internal, 4).is_some());
+    assert!(index.spk_at_index(KeychainKind::Internal, 5).is_none());
 
-    (0..3).for_each(|_| {
-        let external_addr = wallet.reveal_next_address(KeychainKind::External).address;
-        assert!(used_set.insert(external_addr));
+    // the keychain lookahead window moves forward as addresses are revealed
+    wallet.reveal_next_address(KeychainKind::External);
+    assert!(wallet
+        .spk_index()
+        .spk_at_index(KeychainKind::External, 50)
+        .is_some());
+
+    // an output within the external lookahead is detected
+    let addr = wallet.peek_address(KeychainKind::External, 40).address;
+    let mut tx = new_tx(0);
+    tx.output.push(TxOut {
+        value: Amount::from_sat(5_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    insert_tx(&mut wallet, tx);
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(40));
+    assert!(wallet
+        .spk_index()
+        .spk_at_index(KeychainKind::External, 90)
+        .is_some());
+}
 
-        let internal_addr = wallet.reveal_next_address(KeychainKind::Internal).address;
-        assert!(used_set.insert(internal_addr));
+#[test]
+fn test_lookahead_for_keychain_in_update() {
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let new_wallet = || {
+        Wallet::create(desc, change_desc)
+            .network(Network::Signet)
+            .lookahead(5)
+            .lookahead_for(KeychainKind::External, 50)
+            .create_wallet_no_persist()
+            .unwrap()
+    };
+    // a payment within the keychain lookahead, and one only within the lookahead window moved
+    // forward by the first one
+    let wallet = new_wallet();
+    let txs = [10, 55].map(|index| {
+        let mut tx = new_tx(index);
+        tx.output.push(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: wallet
+                .peek_address(KeychainKind::External, index)
+                .script_pubkey(),
+        });
+        tx
     });
+    let check = |wallet: &Wallet| {
+        assert_eq!(wallet.balance().total(), Amount::from_sat(20_000));
+        assert_eq!(wallet.derivation_index(KeychainKind::External), Some(55));
+    };
+
+    let mut wallet = new_wallet();
+    let mut tx_update = bdk_chain::TxUpdate::default();
+    tx_update.txs = txs.iter().cloned().map(Arc::new).collect();
+    tx_update.seen_ats = txs.iter().map(|tx| (tx.compute_txid(), 1)).collect();
+    wallet
+        .apply_update(Update {
+            tx_update,
+            ..Default::default()
+        })
+        .unwrap();
+    check(&wallet);
+
+    let mut wallet = new_wallet();
+    wallet.apply_unconfirmed_txs(txs.iter().cloned().map(|tx| (tx, 1)));
+    check(&wallet);
+
+    let mut wallet = new_wallet();
+    let tip = wallet.latest_checkpoint().block_id();
+    let mut block = mine_block(tip.hash, 0x207fffff, true);
+    block.txdata.extend(txs);
+    wallet.apply_block(&block, tip.height + 1).unwrap();
+    check(&wallet);
 }
 
 #[test]
-fn test_taproot_psbt_populate_tap_key_origins() {
+fn test_set_lookahead() {
     let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
-    let (mut wallet, _) = get_funded_wallet(desc, change_desc);
-    let addr = wallet.reveal_next_address(KeychainKind::External);
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Signet)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert_eq!(wallet.lookahead(KeychainKind::External), 25);
+
+    // a funding tx beyond the lookahead is not detected
+    let addr = wallet.peek_address(KeychainKind::External, 60).address;
+    let mut tx = new_tx(0);
+    tx.output.push(TxOut {
+        value: Amount::from_sat(10_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+    assert_eq!(wallet.balance().total(), Amount::ZERO);
+    assert_eq!(wallet.derivation_index(KeychainKind::External), None);
 
-    let mut builder = wallet.build_tx();
-    builder.drain_to(addr.script_pubkey()).drain_wallet();
-    let psbt = builder.finish().unwrap();
+    // raising the lookahead picks it up
+    wallet.set_lookahead(KeychainKind::External, 100);
+    assert_eq!(wallet.lookahead(KeychainKind::External), 100);
+    assert_eq!(wallet.lookahead(KeychainKind::Internal), 25);
+    assert_eq!(wallet.balance().total(), Amount::from_sat(10_000));
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(60));
+    assert!(wallet.get_tx(txid).is_some());
+    assert!(wallet
+        .spk_index()
+        .spk_at_index(KeychainKind::External, 160)
+        .is_some());
+
+    // and the next sync request includes the spk of the funding tx
+    let mut request = wallet.start_sync_with_revealed_spks_at(0).build();
+    assert!(request
+        .iter_spks_with_expected_txids()
+        .any(|spk_with_txids| spk_with_txids.spk == addr.script_pubkey()));
+
+    // shrinking the lookahead doesn't forget derived spks
+    wallet.set_lookahead(KeychainKind::External, 0);
+    assert_eq!(wallet.lookahead(KeychainKind::External), 25);
+    assert!(wallet
+        .spk_index()
+        .spk_at_index(KeychainKind::External, 160)
+        .is_some());
+}
 
+#[test]
+fn test_is_ranged() {
+    let (wallet, _) = get_funded_wallet_wpkh();
+    assert!(wallet.is_ranged(KeychainKind::External));
+    assert!(wallet.is_ranged(KeychainKind::Internal));
+
+    let mut wallet = Wallet::create_single(get_test_wpkh())
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert!(!wallet.is_ranged(KeychainKind::External));
+    let addr = wallet.reveal_next_address(KeychainKind::External);
     assert_eq!(
-        psbt.inputs[0]
-            .tap_key_origins
-            .clone()
-            .into_iter()
-            .collect::<Vec<_>>(),
-        vec![(
-            from_str!("0841db1dbaf949dbbda893e01a18f2cca9179cf8ea2d8e667857690502b06483"),
-            (vec![], (from_str!("f6a5cb8b"), from_str!("m/0/0")))
-        )],
-        "Wrong input tap_key_origins"
-    );
-    assert_eq!(
-        psbt.outputs[0]
-            .tap_key_origins
-            .clone()
-            .into_iter()
-            .collect::<Vec<_>>(),
-        vec![(
-            from_str!("9187c1e80002d19ddde9c5c7f5394e9a063cee8695867b58815af0562695ca21"),
-            (vec![], (from_str!("f6a5cb8b"), from_str!("m/0/1")))
-        )],
-        "Wrong output tap_key_origins"
+        wallet.reveal_next_address(KeychainKind::External).address,
+        addr.address
     );
 }
 
 #[test]
-fn test_taproot_psbt_populate_tap_key_origins_repeated_key() {
-    let (mut wallet, _) = get_funded_wallet(get_test_tr_repeated_key(), get_test_tr_single_sig());
-    let addr = wallet.reveal_next_address(KeychainKind::External);
-
-    let path = vec![("rn4nre9c".to_string(), vec![0])]
-        .into_iter()
-        .collect();
+fn test_rescan_needed() {
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Signet)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert!(!wallet.rescan_needed(KeychainKind::External));
+    assert_eq!(wallet.recommended_lookahead(KeychainKind::External), 50);
+
+    // funds found in the lookahead window are close to the end of the scanned range
+    let addr = wallet.peek_address(KeychainKind::External, 20).address;
+    let mut tx = new_tx(0);
+    tx.output.push(TxOut {
+        value: Amount::from_sat(10_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    insert_tx(&mut wallet, tx);
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(20));
+    assert!(wallet.rescan_needed(KeychainKind::External));
+    assert!(!wallet.rescan_needed(KeychainKind::Internal));
 
-    let mut builder = wallet.build_tx();
-    builder
-        .drain_to(addr.script_pubkey())
-        .drain_wallet()
-        .policy_path(path, KeychainKind::External);
-    let psbt = builder.finish().unwrap();
+    // revealing enough unused addresses after the last used one clears the hint
+    wallet
+        .reveal_addresses_to(KeychainKind::External, 45)
+        .for_each(drop);
+    assert!(!wallet.rescan_needed(KeychainKind::External));
+}
 
-    let mut input_key_origins = psbt.inputs[0]
-        .tap_key_origins
-        .clone()
-        .into_iter()
-        .collect::<Vec<_>>();
-    input_key_origins.sort();
+#[test]
+fn test_plan_migration() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut old_wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut new_wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
 
+    let addr = old_wallet
+        .reveal_next_address(KeychainKind::External)
+        .address;
+    let mut tx = new_tx(0);
+    tx.output = vec![
+        TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: addr.script_pubkey(),
+        };
+        300
+    ];
+    insert_tx(&mut old_wallet, tx);
+    assert_eq!(old_wallet.list_unspent().count(), 300);
+
+    let max_weight = Weight::from_wu(30_000);
+    let fee_rate = FeeRate::from_sat_per_vb_u32(2);
+    let plan =
+        plan_migration_with_weight_limit(&mut old_wallet, &mut new_wallet, fee_rate, max_weight)
+            .unwrap();
+    assert_eq!(plan.psbts.len(), 3);
+    assert_eq!(plan.addresses.len(), 3);
     assert_eq!(
-        input_key_origins,
-        vec![
-            (
-                from_str!("2b0558078bec38694a84933d659303e2575dae7e91685911454115bfd64487e3"),
-                (
-                    vec![
-                        from_str!(
-                            "858ad7a7d7f270e2c490c4d6ba00c499e46b18fdd59ea3c2c47d20347110271e"
-                        ),
-                        from_str!(
-                            "f6e927ad4492c051fe325894a4f5f14538333b55a35f099876be42009ec8f903"
-                        ),
-                    ],
-                    (FromStr::from_str("ece52657").unwrap(), vec![].into())
-                )
-            ),
-            (
-                from_str!("b511bd5771e47ee27558b1765e87b541668304ec567721c7b880edc0a010da55"),
-                (
-                    vec![],
-                    (FromStr::from_str("871fd295").unwrap(), vec![].into())
-                )
-            )
-        ],
-        "Wrong input tap_key_origins"
+        plan.psbts
+            .iter()
+            .map(|psbt| psbt.unsigned_tx.input.len())
+            .sum::<usize>(),
+        300
     );
-
-    let mut output_key_origins = psbt.outputs[0]
-        .tap_key_origins
-        .clone()
-        .into_iter()
-        .collect::<Vec<_>>();
-    output_key_origins.sort();
-
     assert_eq!(
-        input_key_origins, output_key_origins,
-        "Wrong output tap_key_origins"
+        plan.total_moved + plan.total_fees,
+        Amount::from_sat(300_000)
     );
+    assert_eq!(new_wallet.derivation_index(KeychainKind::External), Some(2));
+
+    for (mut psbt, address) in plan.psbts.into_iter().zip(plan.addresses) {
+        assert_eq!(psbt.unsigned_tx.output.len(), 1);
+        assert_eq!(
+            psbt.unsigned_tx.output[0].script_pubkey,
+            address.script_pubkey()
+        );
+        assert!(new_wallet.is_mine(address.script_pubkey()));
+        assert!(old_wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        let tx = psbt.extract_tx().unwrap();
+        assert!(tx.weight() <= max_weight);
+    }
+
+    // with the standard weight limit a single transaction is enough
+    let plan = plan_migration(&mut old_wallet, &mut new_wallet, fee_rate).unwrap();
+    assert_eq!(plan.psbts.len(), 1);
+    assert_eq!(plan.psbts[0].unsigned_tx.input.len(), 300);
 }
 
 #[test]
-fn test_taproot_psbt_input_tap_tree() {
-    use bitcoin::hex::FromHex;
-    use bitcoin::taproot;
+fn test_plan_migration_errors() {
+    let (mut old_wallet, _) = get_funded_wallet_wpkh();
+    let fee_rate = FeeRate::from_sat_per_vb_u32(1);
 
-    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree());
-    let addr = wallet.next_unused_address(KeychainKind::External);
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut new_wallet = Wallet::create(desc, change_desc)
+        .network(Network::Testnet)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert_matches!(
+        plan_migration(&mut old_wallet, &mut new_wallet, fee_rate),
+        Err(MigrationError::NetworkMismatch {
+            old: Network::Regtest,
+            new: Network::Testnet,
+        })
+    );
 
-    let mut builder = wallet.build_tx();
-    builder.drain_to(addr.script_pubkey()).drain_wallet();
-    let psbt = builder.finish().unwrap();
-
-    assert_eq!(
-        psbt.inputs[0].tap_merkle_root,
-        Some(
-            TapNodeHash::from_str(
-                "61f81509635053e52d9d1217545916167394490da2287aca4693606e43851986"
-            )
-            .unwrap()
+    let mut new_wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert_matches!(
+        plan_migration_with_weight_limit(
+            &mut old_wallet,
+            &mut new_wallet,
+            fee_rate,
+            Weight::from_wu(400)
         ),
+        Err(MigrationError::WeightLimitTooLow { .. })
     );
-    assert_eq!(
-        psbt.inputs[0].tap_scripts.clone().into_iter().collect::<Vec<_>>(),
-        vec![
-            (taproot::ControlBlock::decode(&Vec::<u8>::from_hex("c0b511bd5771e47ee27558b1765e87b541668304ec567721c7b880edc0a010da55b7ef769a745e625ed4b9a4982a4dc08274c59187e73e6f07171108f455081cb2").unwrap()).unwrap(), (ScriptBuf::from_hex("208aee2b8120a5f157f1223f72b5e62b825831a27a9fdf427db7cc697494d4a642ac").unwrap(), taproot::LeafVersion::TapScript)),
-            (taproot::ControlBlock::decode(&Vec::<u8>::from_hex("c0b511bd5771e47ee27558b1765e87b541668304ec567721c7b880edc0a010da55b9a515f7be31a70186e3c5937ee4a70cc4b4e1efe876c1d38e408222ffc64834").unwrap()).unwrap(), (ScriptBuf::from_hex("2051494dc22e24a32fe9dcfbd7e85faf345fa1df296fb49d156e859ef345201295ac").unwrap(), taproot::LeafVersion::TapScript)),
-        ],
+    assert_eq!(new_wallet.derivation_index(KeychainKind::External), None);
+
+    // a later transaction failing reveals none of the addresses of the new wallet
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut old_wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    let addr = old_wallet
+        .reveal_next_address(KeychainKind::External)
+        .address;
+    let mut tx = new_tx(0);
+    tx.output = [10_000, 10_000, 100]
+        .into_iter()
+        .map(|value| TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: addr.script_pubkey(),
+        })
+        .collect();
+    insert_tx(&mut old_wallet, tx);
+    assert_matches!(
+        plan_migration_with_weight_limit(
+            &mut old_wallet,
+            &mut new_wallet,
+            FeeRate::from_sat_per_vb_u32(2),
+            Weight::from_wu(600)
+        ),
+        Err(MigrationError::CreateTx(_))
     );
+    assert_eq!(new_wallet.derivation_index(KeychainKind::External), None);
+}
+
+#[test]
+fn test_account_xpub() {
+    // test vectors from BIP84
+    let descriptor = "wpkh(xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu/84'/0'/0'/0/*)";
+    let change_descriptor = "wpkh(xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu/84'/0'/0'/1/*)";
+    let wallet = Wallet::create(descriptor, change_descriptor)
+        .network(Network::Bitcoin)
+        .create_wallet_no_persist()
+        .unwrap();
+
+    for keychain in [KeychainKind::External, KeychainKind::Internal] {
+        let account_xpub = wallet.account_xpub(keychain).unwrap();
+        assert_eq!(account_xpub.origin.0.to_string(), "73c5da0a");
+        assert_eq!(account_xpub.origin.1.to_string(), "84'/0'/0'");
+        assert_eq!(account_xpub.script_type, ScriptType::P2wpkh);
+        assert_eq!(
+            account_xpub.to_xpub_string(),
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V"
+        );
+        assert_eq!(
+            account_xpub.to_slip132_string(),
+            "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs"
+        );
+
+        let account_xprvs = wallet.account_xprvs(keychain);
+        assert_eq!(account_xprvs.len(), 1);
+        assert_eq!(account_xprvs[0].origin, account_xpub.origin);
+        assert_eq!(
+            account_xprvs[0].to_slip132_string(),
+            "zprvAdG4iTXWBoARxkkzNpNh8r6Qag3irQB8PzEMkAFeTRXxHpbF9z4QgEvBRmfvqWvGp42t42nvgGpNgYSJA9iefm1yYNZKEm7z6qUWCroSQnE"
+        );
+    }
+}
+
+#[test]
+fn test_account_xpub_watch_only() {
+    let descriptor = "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)";
+    let wallet = Wallet::create_single(descriptor)
+        .network(Network::Bitcoin)
+        .create_wallet_no_persist()
+        .unwrap();
+
+    let account_xpub = wallet.account_xpub(KeychainKind::External).unwrap();
     assert_eq!(
-        psbt.inputs[0].tap_internal_key,
-        Some(from_str!(
-            "b511bd5771e47ee27558b1765e87b541668304ec567721c7b880edc0a010da55"
-        ))
+        account_xpub.to_slip132_string(),
+        "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs"
     );
-
-    // Since we are creating an output to the same address as the input, assert that the
-    // internal_key is the same
+    // a single descriptor wallet uses the same key for change
     assert_eq!(
-        psbt.inputs[0].tap_internal_key,
-        psbt.outputs[0].tap_internal_key
+        wallet.account_xpub(KeychainKind::Internal),
+        Some(account_xpub)
     );
-
-    let tap_tree: bitcoin::taproot::TapTree = serde_json::from_str(r#"[1,{"Script":["2051494dc22e24a32fe9dcfbd7e85faf345fa1df296fb49d156e859ef345201295ac",192]},1,{"Script":["208aee2b8120a5f157f1223f72b5e62b825831a27a9fdf427db7cc697494d4a642ac",192]}]"#).unwrap();
-    assert_eq!(psbt.outputs[0].tap_tree, Some(tap_tree));
+    assert!(wallet.account_xprvs(KeychainKind::External).is_empty());
 }
 
 #[test]
-fn test_taproot_sign_missing_witness_utxo() {
-    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
-    let addr = wallet.next_unused_address(KeychainKind::External);
-    let mut builder = wallet.build_tx();
-    builder.drain_to(addr.script_pubkey()).drain_wallet();
-    let mut psbt = builder.finish().unwrap();
-    let witness_utxo = psbt.inputs[0].witness_utxo.take();
+fn test_create_watch_only() {
+    use bdk_wallet::descriptor::DescriptorError;
+    use bdk_wallet::CreateParams;
 
-    let result = wallet.sign(
-        &mut psbt,
-        SignOptions {
-            allow_all_sighashes: true,
-            ..Default::default()
-        },
+    let descriptor = "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)";
+    let wallet = Wallet::create_watch_only(descriptor)
+        .network(Network::Bitcoin)
+        .create_wallet_no_persist()
+        .unwrap();
+    for keychain in [KeychainKind::External, KeychainKind::Internal] {
+        assert!(wallet.get_signers(keychain).signers().is_empty());
+    }
+
+    // an xprv descriptor is rejected
+    assert_matches!(
+        Wallet::create_watch_only(get_test_tr_single_sig_xprv())
+            .network(Network::Testnet)
+            .create_wallet_no_persist(),
+        Err(DescriptorError::SecretKeysNotAllowed)
     );
+    // so is a single secret key in the change descriptor
+    let (_, change_descriptor) = get_test_tr_single_sig_xprv_and_change_desc();
     assert_matches!(
-        result,
-        Err(SignerError::MissingWitnessUtxo),
-        "Signing should have failed with the correct error because the witness_utxo is missing"
+        CreateParams::new(
+            "wpkh(tpubDDks68wKK1xKaVVVbNmXUAx68K1K817M6KwjvjEyCrjdU7xMvjKnfYAtZjfZcrfPfGFzqmibuVqMzKJGbBnK7mo7WSJri8Y9QgM7aNQ3fCp/0/*)",
+            change_descriptor,
+        )
+            .watch_only()
+            .network(Network::Testnet)
+            .create_wallet_no_persist(),
+        Err(DescriptorError::SecretKeysNotAllowed)
     );
+}
 
-    // restore the witness_utxo
-    psbt.inputs[0].witness_utxo = witness_utxo;
+#[test]
+fn test_account_xpub_taproot() {
+    let (descriptor, change_descriptor) = get_test_tr_single_sig_xprv_and_change_desc();
+    let wallet = Wallet::create(descriptor, change_descriptor)
+        .network(Network::Testnet)
+        .create_wallet_no_persist()
+        .unwrap();
 
-    let result = wallet.sign(
-        &mut psbt,
-        SignOptions {
-            allow_all_sighashes: true,
-            ..Default::default()
-        },
+    // taproot keys have no SLIP-0132 version bytes
+    let account_xpub = wallet.account_xpub(KeychainKind::External).unwrap();
+    assert_eq!(account_xpub.script_type, ScriptType::P2tr);
+    assert!(account_xpub.to_slip132_string().starts_with("tpub"));
+    assert_eq!(
+        account_xpub.to_slip132_string(),
+        account_xpub.to_xpub_string()
     );
+    assert_eq!(wallet.account_xprvs(KeychainKind::External).len(), 1);
+}
 
-    assert_matches!(
-        result,
-        Ok(true),
-        "Should finalize the input since we can produce signatures"
-    );
+#[test]
+fn test_account_xpubs_multisig() {
+    let descriptor = "wsh(multi(2,tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq/0/*,[9a6a2580/48'/1'/0'/2']tpubDDnGNapGEY6AZAdQbfRJgMg9fvz8pUBrLwvyvUqEgcUfgzM6zc2eVK4vY9x9L5FJWdX8WumXuLEDV5zDZnTfbn87vLe9XceCFwTu9so9Kks/0/*))";
+    let wallet = Wallet::create_single(descriptor)
+        .network(Network::Testnet)
+        .create_wallet_no_persist()
+        .unwrap();
+
+    let account_xpubs = wallet.account_xpubs(KeychainKind::External);
+    assert_eq!(account_xpubs.len(), 2);
+    assert!(account_xpubs[0].origin.1.is_master());
+    assert_eq!(account_xpubs[1].origin.1.to_string(), "48'/1'/0'/2'");
+    for account_xpub in &account_xpubs {
+        assert_eq!(account_xpub.script_type, ScriptType::P2wsh);
+        assert!(account_xpub.to_slip132_string().starts_with("Vpub"));
+    }
+    assert!(wallet.account_xpub(KeychainKind::External).is_none());
 }
 
 #[test]
-fn test_taproot_sign_using_non_witness_utxo() {
-    let (mut wallet, prev_txid) = get_funded_wallet_single(get_test_tr_single_sig());
-    let addr = wallet.next_unused_address(KeychainKind::External);
-    let mut builder = wallet.build_tx();
-    builder.drain_to(addr.script_pubkey()).drain_wallet();
-    let mut psbt = builder.finish().unwrap();
+fn test_account_xpub_single_key() {
+    let wallet = Wallet::create_single(get_test_wpkh())
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert!(wallet.account_xpubs(KeychainKind::External).is_empty());
+    assert!(wallet.account_xpub(KeychainKind::External).is_none());
+    assert!(wallet.account_xprvs(KeychainKind::External).is_empty());
+}
 
-    psbt.inputs[0].witness_utxo = None;
-    psbt.inputs[0].non_witness_utxo =
-        Some(wallet.get_tx(prev_txid).unwrap().tx_node.as_ref().clone());
-    assert!(
-        psbt.inputs[0].non_witness_utxo.is_some(),
-        "Previous tx should be present in the database"
-    );
+#[test]
+fn test_account_xprvs_mismatched_keymap() {
+    use bdk_wallet::miniscript::descriptor::{
+        DescriptorPublicKey, DescriptorSecretKey, DescriptorXKey, KeyMap, Wildcard,
+    };
+    use bitcoin::bip32::DerivationPath;
+
+    // the private key has a shorter derivation path than the descriptor's public key
+    let descriptor = "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)";
+    let pk = DescriptorPublicKey::from_str(&descriptor[5..descriptor.len() - 1]).unwrap();
+    let sk = DescriptorSecretKey::XPrv(DescriptorXKey {
+        origin: None,
+        xkey: Xpriv::from_str("xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu").unwrap(),
+        derivation_path: DerivationPath::master(),
+        wildcard: Wildcard::Unhardened,
+    });
+    let wallet = Wallet::create_single(descriptor)
+        .keymap(KeychainKind::External, KeyMap::from_iter([(pk, sk)]))
+        .network(Network::Bitcoin)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert!(wallet.account_xprvs(KeychainKind::External).is_empty());
+}
 
-    let result = wallet.sign(&mut psbt, Default::default());
-    assert!(result.is_ok(), "Signing should have worked");
-    assert!(
-        result.unwrap(),
-        "Should finalize the input since we can produce signatures"
+#[test]
+fn test_address_type() {
+    let cases = [
+        (get_test_pkh(), AddressType::P2pkh),
+        (get_test_wpkh(), AddressType::P2wpkh),
+        (get_test_single_sig_csv(), AddressType::P2wsh),
+        (get_test_tr_single_sig(), AddressType::P2tr),
+        (get_test_tr_with_taptree(), AddressType::P2tr),
+        (
+            "sh(wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW))",
+            AddressType::P2shP2wpkh,
+        ),
+        (
+            "sh(wsh(pk(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)))",
+            AddressType::P2shP2wsh,
+        ),
+        (
+            "sh(pk(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW))",
+            AddressType::P2sh,
+        ),
+    ];
+    for (descriptor, address_type) in cases {
+        let wallet = Wallet::create_single(descriptor)
+            .network(Network::Regtest)
+            .create_wallet_no_persist()
+            .unwrap();
+        for keychain in [KeychainKind::External, KeychainKind::Internal] {
+            assert_eq!(wallet.address_type(keychain), address_type, "{descriptor}");
+        }
+    }
+
+    let (_, change_descriptor) = get_test_tr_single_sig_xprv_and_change_desc();
+    let wallet = Wallet::create(get_test_wpkh_and_change_desc().0, change_descriptor)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert_eq!(
+        wallet.address_type(KeychainKind::External),
+        AddressType::P2wpkh
     );
+    assert_eq!(
+        wallet.address_type(KeychainKind::Internal),
+        AddressType::P2tr
+    );
+    assert_eq!(AddressType::P2shP2wpkh.to_string(), "P2SH-P2WPKH");
+    assert!(AddressType::P2shP2wpkh.is_witness());
+    assert!(!AddressType::P2pkh.is_witness());
 }
 
-fn test_spend_from_wallet(mut wallet: Wallet) {
-    let addr = wallet.next_unused_address(KeychainKind::External);
+#[test]
+fn test_dust_value() {
+    let cases = [
+        (get_test_pkh(), 546),
+        (get_test_wpkh(), 294),
+        (get_test_single_sig_csv(), 330),
+        (get_test_tr_single_sig(), 330),
+        (
+            "sh(wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW))",
+            540,
+        ),
+        (
+            "sh(wsh(pk(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)))",
+            540,
+        ),
+        (
+            "pk(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)",
+            576,
+        ),
+    ];
+    for (descriptor, dust) in cases {
+        let mut wallet = Wallet::create_single(descriptor)
+            .network(Network::Regtest)
+            .create_wallet_no_persist()
+            .unwrap();
+        for keychain in [KeychainKind::External, KeychainKind::Internal] {
+            assert_eq!(
+                wallet.dust_value(keychain),
+                Amount::from_sat(dust),
+                "{descriptor}"
+            );
+        }
+        // a higher minimum relay fee raises the dust limit
+        wallet.set_min_relay_fee(FeeRate::from_sat_per_kwu(1500));
+        assert_eq!(
+            wallet.dust_value(KeychainKind::External),
+            Amount::from_sat(dust * 2),
+            "{descriptor}"
+        );
+    }
 
+    // a recipient below the dust value of its script type is rejected
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let script_pubkey = wallet
+        .next_unused_address(KeychainKind::External)
+        .script_pubkey();
+    let dust = wallet.dust_value(KeychainKind::External);
     let mut builder = wallet.build_tx();
-    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
-    let mut psbt = builder.finish().unwrap();
-
-    assert_eq!(psbt.unsigned_tx.version.0, 2);
-    assert!(
-        wallet.sign(&mut psbt, Default::default()).unwrap(),
-        "Unable to finalize tx"
+    builder.add_recipient(script_pubkey.clone(), dust - Amount::ONE_SAT);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::OutputBelowDustLimit(0))
     );
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(script_pubkey, dust);
+    assert!(builder.finish().is_ok());
 }
 
-//     #[test]
-//     fn test_taproot_key_spend() {
-//         let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
-//         test_spend_from_wallet(wallet);
+#[test]
+fn test_create_wallet_rebase_hardened() {
+    use bdk_wallet::keys::{DescriptorPublicKey, DescriptorSecretKey, KeyMap};
+    use bitcoin::bip32::{Xpriv, Xpub};
+
+    let secp = bitcoin::secp256k1::Secp256k1::new();
+    let xprv = Xpriv::from_str("tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS").unwrap();
+    let xpub = Xpub::from_priv(&secp, &xprv);
+    let keymap = |path: &str| {
+        KeyMap::from_iter([(
+            DescriptorPublicKey::from_str(&format!("{xpub}/{path}")).unwrap(),
+            DescriptorSecretKey::from_str(&format!("{xprv}/{path}")).unwrap(),
+        )])
+    };
+    let descriptor = format!("wpkh({xpub}/84'/1'/0'/0/*)");
+    let change_descriptor = format!("wpkh({xpub}/84'/1'/0'/1/*)");
+    let params = || {
+        Wallet::create(descriptor.clone(), change_descriptor.clone())
+            .network(Network::Regtest)
+            .keymap(KeychainKind::External, keymap("84'/1'/0'/0/*"))
+            .keymap(KeychainKind::Internal, keymap("84'/1'/0'/1/*"))
+    };
 
-//         let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig_xprv());
-//         test_spend_from_wallet(wallet);
-//     }
+    // hardened steps are rejected unless the wallet opts in
+    assert_matches!(
+        params().create_wallet_no_persist(),
+        Err(DescriptorError::HardenedDerivationXpub)
+    );
 
-#[test]
-fn test_taproot_no_key_spend() {
-    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
-    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut wallet = params()
+        .rebase_hardened(true)
+        .create_wallet_no_persist()
+        .unwrap();
+    let private_wallet = Wallet::create(
+        format!("wpkh({xprv}/84'/1'/0'/0/*)"),
+        format!("wpkh({xprv}/84'/1'/0'/1/*)"),
+    )
+    .network(Network::Regtest)
+    .create_wallet_no_persist()
+    .unwrap();
+    for keychain in [KeychainKind::External, KeychainKind::Internal] {
+        assert_eq!(
+            wallet.public_descriptor(keychain),
+            private_wallet.public_descriptor(keychain)
+        );
+    }
+    assert!(wallet
+        .public_descriptor(KeychainKind::External)
+        .to_string()
+        .starts_with(&format!("wpkh([{}/84'/1'/0']tpub", xprv.fingerprint(&secp))));
 
+    // the rebased keys can still sign
+    insert_checkpoint(
+        &mut wallet,
+        BlockId {
+            height: 1,
+            hash: BlockHash::all_zeros(),
+        },
+    );
+    receive_output_in_latest_block(&mut wallet, Amount::from_sat(50_000));
+    let addr = wallet.peek_address(KeychainKind::External, 0);
     let mut builder = wallet.build_tx();
-    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    builder.drain_to(addr.script_pubkey());
+    builder.drain_wallet();
     let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
 
-    assert!(
-        wallet
-            .sign(
-                &mut psbt,
-                SignOptions {
-                    sign_with_tap_internal_key: false,
-                    ..Default::default()
-                },
-            )
-            .unwrap(),
-        "Unable to finalize tx"
+    // without the private keys the hardened steps can't be applied
+    let err = Wallet::create(descriptor.clone(), change_descriptor.clone())
+        .network(Network::Regtest)
+        .rebase_hardened(true)
+        .create_wallet_no_persist()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        DescriptorError::HardenedStepWithoutPrivateKey {
+            key: format!("{xpub}/84'/1'/0'/0/*"),
+            index: 0,
+            step: bitcoin::bip32::ChildNumber::from_hardened_idx(84).unwrap(),
+        }
     );
-
-    assert!(psbt.inputs.iter().all(|i| i.tap_key_sig.is_none()));
 }
 
 #[test]
-fn test_taproot_script_spend() {
-    let (wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree());
-    test_spend_from_wallet(wallet);
+fn test_get_utxo() {
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let outpoint = OutPoint::new(txid, 0);
+    let utxo = wallet.get_utxo(outpoint).expect("must be a wallet utxo");
+    assert_eq!(utxo.outpoint, outpoint);
+    assert!(wallet.is_mine_utxo(outpoint));
 
-    let (wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_xprv());
-    test_spend_from_wallet(wallet);
+    // unknown outpoints are not utxos
+    assert!(wallet.get_utxo(OutPoint::null()).is_none());
+    assert!(!wallet.is_mine_utxo(OutPoint::null()));
+
+    // spent outpoints are not utxos
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(10_000));
+    let tx = builder.finish().unwrap().unsigned_tx;
+    let spending_txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+    assert!(wallet.get_utxo(outpoint).is_none());
+    assert!(!wallet.is_mine_utxo(outpoint));
+    assert!(wallet
+        .list_unspent()
+        .all(|utxo| utxo.outpoint.txid == spending_txid && wallet.is_mine_utxo(utxo.outpoint)));
 }
 
 #[test]
-fn test_taproot_script_spend_sign_all_leaves() {
-    use bdk_wallet::signer::TapLeavesOptions;
-    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
-    let addr = wallet.next_unused_address(KeychainKind::External);
+fn test_get_address_no_reuse() {
+    use bdk_wallet::descriptor::template::Bip84;
+    use std::collections::HashSet;
 
-    let mut builder = wallet.build_tx();
-    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
-    let mut psbt = builder.finish().unwrap();
+    let key = bitcoin::bip32::Xpriv::from_str("tprv8ZgxMBicQKsPcx5nBGsR63Pe8KnRUqmbJNENAfGftF3yuXoMMoVJJcYeUw5eVkm9WBPjWYt6HMWYJNesB5HaNVBaFc1M6dRjWSYnmewUMYy").unwrap();
+    let mut wallet = Wallet::create(
+        Bip84(key, KeychainKind::External),
+        Bip84(key, KeychainKind::Internal),
+    )
+    .network(Network::Regtest)
+    .create_wallet_no_persist()
+    .unwrap();
 
-    assert!(
-        wallet
-            .sign(
-                &mut psbt,
-                SignOptions {
-                    tap_leaves_options: TapLeavesOptions::All,
-                    ..Default::default()
-                },
-            )
-            .unwrap(),
-        "Unable to finalize tx"
-    );
+    let mut used_set = HashSet::new();
 
-    assert!(psbt
-        .inputs
-        .iter()
-        .all(|i| i.tap_script_sigs.len() == i.tap_scripts.len()));
+    (0..3).for_each(|_| {
+        let external_addr = wallet.reveal_next_address(KeychainKind::External).address;
+        assert!(used_set.insert(external_addr));
+
+        let internal_addr = wallet.reveal_next_address(KeychainKind::Internal).address;
+        assert!(used_set.insert(internal_addr));
+    });
 }
 
 #[test]
-fn test_taproot_script_spend_sign_include_some_leaves() {
-    use bdk_wallet::signer::TapLeavesOptions;
-    use bitcoin::taproot::TapLeafHash;
-
-    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
-    let addr = wallet.next_unused_address(KeychainKind::External);
+fn test_taproot_psbt_populate_tap_key_origins() {
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let (mut wallet, _) = get_funded_wallet(desc, change_desc);
+    let addr = wallet.reveal_next_address(KeychainKind::External);
 
     let mut builder = wallet.build_tx();
-    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
-    let mut psbt = builder.finish().unwrap();
-    let mut script_leaves: Vec<_> = psbt.inputs[0]
-        .tap_scripts
-        .clone()
-        .values()
-        .map(|(script, version)| TapLeafHash::from_script(script, *version))
-        .collect();
-    let included_script_leaves = vec![script_leaves.pop().unwrap()];
-    let excluded_script_leaves = script_leaves;
+    builder.drain_to(addr.script_pubkey()).drain_wallet();
+    let psbt = builder.finish().unwrap();
 
-    assert!(
-        wallet
-            .sign(
-                &mut psbt,
-                SignOptions {
-                    tap_leaves_options: TapLeavesOptions::Include(included_script_leaves.clone()),
-                    ..Default::default()
-                },
-            )
-            .unwrap(),
-        "Unable to finalize tx"
+    assert_eq!(
+        psbt.inputs[0]
+            .tap_key_origins
+            .clone()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![(
+            from_str!("0841db1dbaf949dbbda893e01a18f2cca9179cf8ea2d8e667857690502b06483"),
+            (vec![], (from_str!("f6a5cb8b"), from_str!("m/0/0")))
+        )],
+        "Wrong input tap_key_origins"
+    );
+    assert_eq!(
+        psbt.outputs[0]
+            .tap_key_origins
+            .clone()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![(
+            from_str!("9187c1e80002d19ddde9c5c7f5394e9a063cee8695867b58815af0562695ca21"),
+            (vec![], (from_str!("f6a5cb8b"), from_str!("m/0/1")))
+        )],
+        "Wrong output tap_key_origins"
     );
-
-    assert!(psbt.inputs[0]
-        .tap_script_sigs
-        .iter()
-        .all(|s| included_script_leaves.contains(&s.0 .1)
-            && !excluded_script_leaves.contains(&s.0 .1)));
 }
 
 #[test]
-fn test_taproot_script_spend_sign_exclude_some_leaves() {
-    use bdk_wallet::signer::TapLeavesOptions;
-    use bitcoin::taproot::TapLeafHash;
+fn test_taproot_psbt_populate_tap_key_origins_repeated_key() {
+    let (mut wallet, _) = get_funded_wallet(get_test_tr_repeated_key(), get_test_tr_single_sig());
+    let addr = wallet.reveal_next_address(KeychainKind::External);
 
-    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
-    let addr = wallet.next_unused_address(KeychainKind::External);
+    let path = vec![("rn4nre9c".to_string(), vec![0])]
+        .into_iter()
+        .collect();
 
     let mut builder = wallet.build_tx();
-    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
-    let mut psbt = builder.finish().unwrap();
-    let mut script_leaves: Vec<_> = psbt.inputs[0]
-        .tap_scripts
+    builder
+        .drain_to(addr.script_pubkey())
+        .drain_wallet()
+        .policy_path(path, KeychainKind::External);
+    let psbt = builder.finish().unwrap();
+
+    let mut input_key_origins = psbt.inputs[0]
+        .tap_key_origins
         .clone()
-        .values()
-        .map(|(script, version)| TapLeafHash::from_script(script, *version))
-        .collect();
-    let included_script_leaves = [script_leaves.pop().unwrap()];
-    let excluded_script_leaves = script_leaves;
+        .into_iter()
+        .collect::<Vec<_>>();
+    input_key_origins.sort();
 
-    assert!(
-        wallet
-            .sign(
-                &mut psbt,
-                SignOptions {
-                    tap_leaves_options: TapLeavesOptions::Exclude(excluded_script_leaves.clone()),
-                    ..Default::default()
-                },
+    assert_eq!(
+        input_key_origins,
+        vec![
+            (
+                from_str!("2b0558078bec38694a84933d659303e2575dae7e91685911454115bfd64487e3"),
+                (
+                    vec![
+                        from_str!(
+                            "858ad7a7d7f270e2c490c4d6ba00c499e46b18fdd59ea3c2c47d20347110271e"
+                        ),
+                        from_str!(
+                            "f6e927ad4492c051fe325894a4f5f14538333b55a35f099876be42009ec8f903"
+                        ),
+                    ],
+                    (FromStr::from_str("ece52657").unwrap(), vec![].into())
+                )
+            ),
+            (
+                from_str!("b511bd5771e47ee27558b1765e87b541668304ec567721c7b880edc0a010da55"),
+                (
+                    vec![],
+                    (FromStr::from_str("871fd295").unwrap(), vec![].into())
+                )
             )
-            .unwrap(),
-        "Unable to finalize tx"
+        ],
+        "Wrong input tap_key_origins"
     );
 
-    assert!(psbt.inputs[0]
-        .tap_script_sigs
-        .iter()
-        .all(|s| included_script_leaves.contains(&s.0 .1)
-            && !excluded_script_leaves.contains(&s.0 .1)));
+    let mut output_key_origins = psbt.outputs[0]
+        .tap_key_origins
+        .clone()
+        .into_iter()
+        .collect::<Vec<_>>();
+    output_key_origins.sort();
+
+    assert_eq!(
+        input_key_origins, output_key_origins,
+        "Wrong output tap_key_origins"
+    );
 }
 
 #[test]
-fn test_taproot_script_spend_sign_no_leaves() {
-    use bdk_wallet::signer::TapLeavesOptions;
-    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
-    let addr = wallet.next_unused_address(KeychainKind::External);
-
-    let mut builder = wallet.build_tx();
-    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
-    let mut psbt = builder.finish().unwrap();
+fn test_taproot_output_info() {
+    use bitcoin::key::TapTweak;
+    use bitcoin::taproot::{LeafVersion, TapLeafHash};
+    use bitcoin::{PrivateKey, XOnlyPublicKey};
 
-    wallet
-        .sign(
-            &mut psbt,
-            SignOptions {
-                tap_leaves_options: TapLeavesOptions::None,
-                ..Default::default()
-            },
-        )
+    let secp = bitcoin::secp256k1::Secp256k1::new();
+    let wallet = Wallet::create_single(get_test_tr_with_taptree())
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
         .unwrap();
 
-    assert!(psbt.inputs.iter().all(|i| i.tap_script_sigs.is_empty()));
-}
-
-#[test]
-fn test_taproot_sign_derive_index_from_psbt() {
-    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig_xprv());
-
-    let addr = wallet.next_unused_address(KeychainKind::External);
+    let internal_key = XOnlyPublicKey::from_str(
+        "b511bd5771e47ee27558b1765e87b541668304ec567721c7b880edc0a010da55",
+    )
+    .unwrap();
+    let key_a = PrivateKey::from_wif("cPZzKuNmpuUjD1e8jUU4PVzy2b5LngbSip8mBsxf4e7rSFZVb4Uh")
+        .unwrap()
+        .public_key(&secp);
+    let key_b = XOnlyPublicKey::from_str(
+        "8aee2b8120a5f157f1223f72b5e62b825831a27a9fdf427db7cc697494d4a642",
+    )
+    .unwrap();
+    let leaf_hash = |key: XOnlyPublicKey| {
+        let script = bitcoin::script::Builder::new()
+            .push_x_only_key(&key)
+            .push_opcode(bitcoin::opcodes::all::OP_CHECKSIG)
+            .into_script();
+        TapLeafHash::from_script(&script, LeafVersion::TapScript)
+    };
+    let merkle_root =
+        TapNodeHash::from_node_hashes(leaf_hash(key_a.into()).into(), leaf_hash(key_b).into());
+    assert_eq!(
+        merkle_root.to_string(),
+        "61f81509635053e52d9d1217545916167394490da2287aca4693606e43851986"
+    );
+    let (output_key, _) = internal_key.tap_tweak(&secp, Some(merkle_root));
 
-    let mut builder = wallet.build_tx();
-    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
-    let mut psbt = builder.finish().unwrap();
+    let (internal, root, output) = wallet
+        .taproot_output_info(KeychainKind::External, 0)
+        .unwrap();
+    assert_eq!(internal, internal_key);
+    assert_eq!(root, Some(merkle_root));
+    assert_eq!(output, output_key.to_x_only_public_key());
+    assert_eq!(
+        ScriptBuf::new_p2tr_tweaked(output_key),
+        wallet
+            .peek_address(KeychainKind::External, 0)
+            .script_pubkey()
+    );
 
-    // re-create the wallet with an empty db
-    let wallet_empty = Wallet::create(get_test_tr_single_sig_xprv(), get_test_tr_single_sig())
+    // key-path only descriptors have no merkle root, and the output key changes with the index
+    let (descriptor, change_descriptor) = get_test_tr_single_sig_xprv_and_change_desc();
+    let wallet = Wallet::create(descriptor, change_descriptor)
         .network(Network::Regtest)
         .create_wallet_no_persist()
         .unwrap();
+    for index in 0..3 {
+        let (internal, root, output) = wallet
+            .taproot_output_info(KeychainKind::Internal, index)
+            .unwrap();
+        assert_eq!(root, None);
+        let (tweaked, _) = internal.tap_tweak(&secp, None);
+        assert_eq!(output, tweaked.to_x_only_public_key());
+        assert_eq!(
+            ScriptBuf::new_p2tr_tweaked(tweaked),
+            wallet
+                .peek_address(KeychainKind::Internal, index)
+                .script_pubkey()
+        );
+    }
+    assert!(wallet
+        .taproot_output_info(KeychainKind::External, 1 << 31)
+        .is_none());
 
-    // signing with an empty db means that we will only look at the psbt to infer the
-    // derivation index
-    assert!(
-        wallet_empty.sign(&mut psbt, Default::default()).unwrap(),
-        "Unable to finalize tx"
-    );
+    let wallet = Wallet::create_single(get_test_wpkh())
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert!(wallet
+        .taproot_output_info(KeychainKind::External, 0)
+        .is_none());
 }
 
 #[test]
-fn test_taproot_sign_explicit_sighash_all() {
-    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
+fn test_taproot_psbt_input_tap_tree() {
+    use bitcoin::hex::FromHex;
+    use bitcoin::taproot;
+
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree());
     let addr = wallet.next_unused_address(KeychainKind::External);
+
     let mut builder = wallet.build_tx();
-    builder
-        .drain_to(addr.script_pubkey())
-        .sighash(TapSighashType::All.into())
-        .drain_wallet();
-    let mut psbt = builder.finish().unwrap();
+    builder.drain_to(addr.script_pubkey()).drain_wallet();
+    let psbt = builder.finish().unwrap();
 
-    let result = wallet.sign(&mut psbt, Default::default());
-    assert!(
-        result.is_ok(),
-        "Signing should work because SIGHASH_ALL is safe"
-    )
+    assert_eq!(
+        psbt.inputs[0].tap_merkle_root,
+        Some(
+            TapNodeHash::from_str(
+                "61f81509635053e52d9d1217545916167394490da2287aca4693606e43851986"
+            )
+            .unwrap()
+        ),
+    );
+    assert_eq!(
+        psbt.inputs[0].tap_scripts.clone().into_iter().collect::<Vec<_>>(),
+        vec![
+            (taproot::ControlBlock::decode(&Vec::<u8>::from_hex("c0b511bd5771e47ee27558b1765e87b541668304ec567721c7b880edc0a010da55b7ef769a745e625ed4b9a4982a4dc08274c59187e73e6f07171108f455081cb2").unwrap()).unwrap(), (ScriptBuf::from_hex("208aee2b8120a5f157f1223f72b5e62b825831a27a9fdf427db7cc697494d4a642ac").unwrap(), taproot::LeafVersion::TapScript)),
+            (taproot::ControlBlock::decode(&Vec::<u8>::from_hex("c0b511bd5771e47ee27558b1765e87b541668304ec567721c7b880edc0a010da55b9a515f7be31a70186e3c5937ee4a70cc4b4e1efe876c1d38e408222ffc64834").unwrap()).unwrap(), (ScriptBuf::from_hex("2051494dc22e24a32fe9dcfbd7e85faf345fa1df296fb49d156e859ef345201295ac").unwrap(), taproot::LeafVersion::TapScript)),
+        ],
+    );
+    assert_eq!(
+        psbt.inputs[0].tap_internal_key,
+        Some(from_str!(
+            "b511bd5771e47ee27558b1765e87b541668304ec567721c7b880edc0a010da55"
+        ))
+    );
+
+    // Since we are creating an output to the same address as the input, assert that the
+    // internal_key is the same
+    assert_eq!(
+        psbt.inputs[0].tap_internal_key,
+        psbt.outputs[0].tap_internal_key
+    );
+
+    let tap_tree: bitcoin::taproot::TapTree = serde_json::from_str(r#"[1,{"Script":["2051494dc22e24a32fe9dcfbd7e85faf345fa1df296fb49d156e859ef345201295ac",192]},1,{"Script":["208aee2b8120a5f157f1223f72b5e62b825831a27a9fdf427db7cc697494d4a642ac",192]}]"#).unwrap();
+    assert_eq!(psbt.outputs[0].tap_tree, Some(tap_tree));
 }
 
 #[test]
-fn test_taproot_sign_non_default_sighash() {
-    let sighash = TapSighashType::NonePlusAnyoneCanPay;
-
+fn test_taproot_sign_missing_witness_utxo() {
     let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
     let addr = wallet.next_unused_address(KeychainKind::External);
     let mut builder = wallet.build_tx();
-    builder
-        .drain_to(addr.script_pubkey())
-        .sighash(sighash.into())
-        .drain_wallet();
+    builder.drain_to(addr.script_pubkey()).drain_wallet();
     let mut psbt = builder.finish().unwrap();
-
     let witness_utxo = psbt.inputs[0].witness_utxo.take();
 
-    let result = wallet.sign(&mut psbt, Default::default());
-    assert!(
-        result.is_err(),
-        "Signing should have failed because the TX uses non-standard sighashes"
-    );
-    assert_matches!(
-        result,
-        Err(SignerError::NonStandardSighash),
-        "Signing failed with the wrong error type"
-    );
-
-    // try again after opting-in
     let result = wallet.sign(
         &mut psbt,
         SignOptions {
@@ -2471,14 +4138,10 @@ fn test_taproot_sign_non_default_sighash() {
             ..Default::default()
         },
     );
-    assert!(
-        result.is_err(),
-        "Signing should have failed because the witness_utxo is missing"
-    );
     assert_matches!(
         result,
         Err(SignerError::MissingWitnessUtxo),
-        "Signing failed with the wrong error type"
+        "Signing should have failed with the correct error because the witness_utxo is missing"
     );
 
     // restore the witness_utxo
@@ -2492,570 +4155,2631 @@ fn test_taproot_sign_non_default_sighash() {
         },
     );
 
-    assert!(result.is_ok(), "Signing should have worked");
-    assert!(
-        result.unwrap(),
+    assert_matches!(
+        result,
+        Ok(true),
         "Should finalize the input since we can produce signatures"
     );
-
-    let extracted = psbt.extract_tx().expect("failed to extract tx");
-    assert_eq!(
-        *extracted.input[0].witness.to_vec()[0].last().unwrap(),
-        sighash as u8,
-        "The signature should have been made with the right sighash"
-    );
 }
 
 #[test]
-fn test_spend_coinbase() {
-    let (desc, change_desc) = get_test_wpkh_and_change_desc();
-    let mut wallet = Wallet::create(desc, change_desc)
-        .network(Network::Regtest)
-        .create_wallet_no_persist()
-        .unwrap();
-
-    let confirmation_height = 5;
-    let confirmation_block_id = BlockId {
-        height: confirmation_height,
-        hash: BlockHash::all_zeros(),
-    };
-    insert_checkpoint(&mut wallet, confirmation_block_id);
-    let coinbase_tx = Transaction {
-        version: transaction::Version::ONE,
-        lock_time: absolute::LockTime::ZERO,
-        input: vec![TxIn {
-            previous_output: OutPoint::null(),
-            ..Default::default()
-        }],
-        output: vec![TxOut {
-            script_pubkey: wallet
-                .next_unused_address(KeychainKind::External)
-                .script_pubkey(),
-            value: Amount::from_sat(25_000),
-        }],
-    };
-    let txid = coinbase_tx.compute_txid();
-
-    let anchor = ConfirmationBlockTime {
-        block_id: confirmation_block_id,
-        confirmation_time: 30_000,
-    };
-
-    // Insert coinbase transaction into the local view, and also simulates confirming tx by applying
-    // the update with an `anchor`.
-    let mut tx_update = bdk_chain::TxUpdate::default();
-    tx_update.txs = vec![Arc::new(coinbase_tx)];
-    tx_update.anchors = [(anchor, txid)].into();
-    wallet
-        .apply_update(Update {
-            tx_update,
-            ..Default::default()
-        })
-        .unwrap();
-
-    // NOTE: A transaction spending an output coming from the coinbase tx at height h, is eligible
-    // to be included in block h + [100 = COINBASE_MATURITY] or higher.
-    // Tx elibible to be included in the next block will be accepted in the mempool, used in block
-    // templates and relayed on the network.
-    // Miners may include such tx in a block when their chaintip is at h + [99 = COINBASE_MATURITY -
-    // 1]. This means these coins are available for selection at height h + 99.
-    //
-    // By https://bitcoin.stackexchange.com/a/119017
-    let not_yet_mature_time = confirmation_height + COINBASE_MATURITY - 2;
-    let maturity_time = confirmation_height + COINBASE_MATURITY - 1;
-
-    let balance = wallet.balance();
-    assert_eq!(
-        balance,
-        Balance {
-            immature: Amount::from_sat(25_000),
-            trusted_pending: Amount::ZERO,
-            untrusted_pending: Amount::ZERO,
-            confirmed: Amount::ZERO
-        }
-    );
-
-    // We try to create a transaction, only to notice that all
-    // our funds are unspendable
-    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
-        .unwrap()
-        .assume_checked();
+fn test_taproot_sign_using_non_witness_utxo() {
+    let (mut wallet, prev_txid) = get_funded_wallet_single(get_test_tr_single_sig());
+    let addr = wallet.next_unused_address(KeychainKind::External);
     let mut builder = wallet.build_tx();
-    builder
-        .add_recipient(addr.script_pubkey(), balance.immature / 2)
-        .current_height(confirmation_height);
-    assert!(matches!(
-        builder.finish(),
-        Err(CreateTxError::CoinSelection(
-            coin_selection::InsufficientFunds {
-                needed: _,
-                available: Amount::ZERO
-            }
-        ))
-    ));
+    builder.drain_to(addr.script_pubkey()).drain_wallet();
+    let mut psbt = builder.finish().unwrap();
 
-    // Still unspendable...
-    let mut builder = wallet.build_tx();
-    builder
-        .add_recipient(addr.script_pubkey(), balance.immature / 2)
-        .current_height(not_yet_mature_time);
-    assert_matches!(
-        builder.finish(),
-        Err(CreateTxError::CoinSelection(
-            coin_selection::InsufficientFunds {
-                needed: _,
-                available: Amount::ZERO
-            }
-        ))
+    psbt.inputs[0].witness_utxo = None;
+    psbt.inputs[0].non_witness_utxo =
+        Some(wallet.get_tx(prev_txid).unwrap().tx_node.as_ref().clone());
+    assert!(
+        psbt.inputs[0].non_witness_utxo.is_some(),
+        "Previous tx should be present in the database"
     );
 
-    insert_checkpoint(
-        &mut wallet,
-        BlockId {
-            height: maturity_time,
-            hash: BlockHash::all_zeros(),
-        },
-    );
-    let balance = wallet.balance();
-    assert_eq!(
-        balance,
-        Balance {
-            immature: Amount::ZERO,
-            trusted_pending: Amount::ZERO,
-            untrusted_pending: Amount::ZERO,
-            confirmed: Amount::from_sat(25_000)
-        }
+    let result = wallet.sign(&mut psbt, Default::default());
+    assert!(result.is_ok(), "Signing should have worked");
+    assert!(
+        result.unwrap(),
+        "Should finalize the input since we can produce signatures"
     );
-    let mut builder = wallet.build_tx();
-    builder
-        .add_recipient(addr.script_pubkey(), balance.confirmed / 2)
-        .current_height(maturity_time);
-    builder.finish().unwrap();
 }
 
-#[test]
-fn test_allow_dust_limit() {
-    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_cltv());
-
+fn test_spend_from_wallet(mut wallet: Wallet) {
     let addr = wallet.next_unused_address(KeychainKind::External);
 
     let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let mut psbt = builder.finish().unwrap();
 
-    builder.add_recipient(addr.script_pubkey(), Amount::ZERO);
-
-    assert_matches!(
-        builder.finish(),
-        Err(CreateTxError::OutputBelowDustLimit(0))
+    assert_eq!(psbt.unsigned_tx.version.0, 2);
+    assert!(
+        wallet.sign(&mut psbt, Default::default()).unwrap(),
+        "Unable to finalize tx"
     );
+}
 
-    let mut builder = wallet.build_tx();
-
-    builder
-        .allow_dust(true)
-        .add_recipient(addr.script_pubkey(), Amount::ZERO);
+//     #[test]
+//     fn test_taproot_key_spend() {
+//         let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
+//         test_spend_from_wallet(wallet);
 
-    assert!(builder.finish().is_ok());
-}
+//         let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig_xprv());
+//         test_spend_from_wallet(wallet);
+//     }
 
 #[test]
-fn test_fee_rate_sign_no_grinding_high_r() {
-    // Our goal is to obtain a transaction with a signature with high-R (71 bytes
-    // instead of 70). We then check that our fee rate and fee calculation is
-    // alright.
-    let (mut wallet, _) = get_funded_wallet_single("wpkh(tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS/*)");
+fn test_taproot_no_key_spend() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
     let addr = wallet.next_unused_address(KeychainKind::External);
-    let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+
     let mut builder = wallet.build_tx();
-    let mut data = PushBytesBuf::try_from(vec![0]).unwrap();
-    builder
-        .drain_to(addr.script_pubkey())
-        .drain_wallet()
-        .fee_rate(fee_rate)
-        .add_data(&data);
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
     let mut psbt = builder.finish().unwrap();
-    let fee = check_fee!(wallet, psbt);
-    let (op_return_vout, _) = psbt
-        .unsigned_tx
-        .output
-        .iter()
-        .enumerate()
-        .find(|(_n, i)| i.script_pubkey.is_op_return())
-        .unwrap();
 
-    let mut sig_len: usize = 0;
-    // We try to sign many different times until we find a longer signature (71 bytes)
-    while sig_len < 71 {
-        // Changing the OP_RETURN data will make the signature change (but not the fee, until
-        // data[0] is small enough)
-        data.as_mut_bytes()[0] += 1;
-        psbt.unsigned_tx.output[op_return_vout].script_pubkey = ScriptBuf::new_op_return(&data);
-        // Clearing the previous signature
-        psbt.inputs[0].partial_sigs.clear();
-        // Signing
+    assert!(
         wallet
             .sign(
                 &mut psbt,
                 SignOptions {
-                    try_finalize: false,
-                    allow_grinding: false,
+                    sign_with_tap_internal_key: false,
                     ..Default::default()
                 },
             )
-            .unwrap();
-        // We only have one key in the partial_sigs map, this is a trick to retrieve it
-        let key = psbt.inputs[0].partial_sigs.keys().next().unwrap();
-        sig_len = psbt.inputs[0].partial_sigs[key]
-            .signature
-            .serialize_der()
-            .len();
-    }
-    // Actually finalizing the transaction...
-    wallet
-        .sign(
-            &mut psbt,
-            SignOptions {
-                allow_grinding: false,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-    // ...and checking that everything is fine
-    assert_fee_rate!(psbt, fee, fee_rate);
+            .unwrap(),
+        "Unable to finalize tx"
+    );
+
+    assert!(psbt.inputs.iter().all(|i| i.tap_key_sig.is_none()));
 }
 
 #[test]
-fn test_fee_rate_sign_grinding_low_r() {
-    // Our goal is to obtain a transaction with a signature with low-R (70 bytes)
-    // by setting the `allow_grinding` signing option as true.
-    // We then check that our fee rate and fee calculation is alright and that our
-    // signature is 70 bytes.
-    let (mut wallet, _) = get_funded_wallet_single("wpkh(tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS/*)");
-    let addr = wallet.next_unused_address(KeychainKind::External);
-    let fee_rate = FeeRate::from_sat_per_vb_u32(1);
-    let mut builder = wallet.build_tx();
-    builder
-        .drain_to(addr.script_pubkey())
-        .drain_wallet()
-        .fee_rate(fee_rate);
-    let mut psbt = builder.finish().unwrap();
-    let fee = check_fee!(wallet, psbt);
-
-    wallet
-        .sign(
-            &mut psbt,
-            SignOptions {
-                try_finalize: false,
-                allow_grinding: true,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+fn test_taproot_script_spend() {
+    let (wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree());
+    test_spend_from_wallet(wallet);
 
-    let key = psbt.inputs[0].partial_sigs.keys().next().unwrap();
-    let sig_len = psbt.inputs[0].partial_sigs[key]
-        .signature
-        .serialize_der()
-        .len();
-    assert_eq!(sig_len, 70);
-    assert_fee_rate!(psbt, fee, fee_rate);
+    let (wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_xprv());
+    test_spend_from_wallet(wallet);
 }
 
 #[test]
-fn test_taproot_load_descriptor_duplicated_keys() {
-    // Added after issue https://github.com/bitcoindevkit/bdk/issues/760
-    //
-    // Having the same key in multiple taproot leaves is safe and should be accepted by BDK
+fn test_taproot_script_spend_sign_all_leaves() {
+    use bdk_wallet::signer::TapLeavesOptions;
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
+    let addr = wallet.next_unused_address(KeychainKind::External);
 
-    let (wallet, _) = get_funded_wallet_single(get_test_tr_dup_keys());
-    let addr = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let mut psbt = builder.finish().unwrap();
 
-    assert_eq!(
-        addr.to_string(),
-        "bcrt1pvysh4nmh85ysrkpwtrr8q8gdadhgdejpy6f9v424a8v9htjxjhyqw9c5s5"
+    assert!(
+        wallet
+            .sign(
+                &mut psbt,
+                SignOptions {
+                    tap_leaves_options: TapLeavesOptions::All,
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        "Unable to finalize tx"
     );
+
+    assert!(psbt
+        .inputs
+        .iter()
+        .all(|i| i.tap_script_sigs.len() == i.tap_scripts.len()));
 }
 
-/// In dev mode this test panics, but in release mode, or if the `debug_panic` in
-/// `TxOutIndex::replenish_inner_index` is commented out, there is no panic and the balance is
-/// calculated correctly. See issue [#1483] and PR [#1486] for discussion on mixing non-wildcard and
-/// wildcard descriptors.
-///
-/// [#1483]: https://github.com/bitcoindevkit/bdk/issues/1483
-/// [#1486]: https://github.com/bitcoindevkit/bdk/pull/1486
 #[test]
-#[cfg(debug_assertions)]
-#[should_panic(
-    expected = "replenish lookahead: must not have existing spk: keychain=Internal, lookahead=25, next_index=0"
-)]
-fn test_keychains_with_overlapping_spks() {
-    // this can happen if a non-wildcard descriptor keychain derives an spk that a
-    // wildcard descriptor keychain in the same wallet also derives.
+fn test_taproot_script_spend_sign_include_some_leaves() {
+    use bdk_wallet::signer::TapLeavesOptions;
+    use bitcoin::taproot::TapLeafHash;
 
-    // index 1 spk overlaps with non-wildcard change descriptor
-    let wildcard_keychain = "wpkh(tprv8ZgxMBicQKsPdDArR4xSAECuVxeX1jwwSXR4ApKbkYgZiziDc4LdBy2WvJeGDfUSE4UT4hHhbgEwbdq8ajjUHiKDegkwrNU6V55CxcxonVN/*)";
-    let non_wildcard_keychain = "wpkh(tprv8ZgxMBicQKsPdDArR4xSAECuVxeX1jwwSXR4ApKbkYgZiziDc4LdBy2WvJeGDfUSE4UT4hHhbgEwbdq8ajjUHiKDegkwrNU6V55CxcxonVN/1)";
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
+    let addr = wallet.next_unused_address(KeychainKind::External);
 
-    let (mut wallet, _) = get_funded_wallet(wildcard_keychain, non_wildcard_keychain);
-    assert_eq!(wallet.balance().confirmed, Amount::from_sat(50000));
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let mut psbt = builder.finish().unwrap();
+    let mut script_leaves: Vec<_> = psbt.inputs[0]
+        .tap_scripts
+        .clone()
+        .values()
+        .map(|(script, version)| TapLeafHash::from_script(script, *version))
+        .collect();
+    let included_script_leaves = vec![script_leaves.pop().unwrap()];
+    let excluded_script_leaves = script_leaves;
 
-    let addr = wallet
-        .reveal_addresses_to(KeychainKind::External, 1)
-        .last()
-        .unwrap()
-        .address;
-    let anchor = ConfirmationBlockTime {
-        block_id: BlockId {
-            height: 2000,
-            hash: BlockHash::all_zeros(),
-        },
-        confirmation_time: 0,
-    };
-    let _outpoint = receive_output_to_address(&mut wallet, addr, Amount::from_sat(8000), anchor);
-    assert_eq!(wallet.balance().confirmed, Amount::from_sat(58000));
-}
+    assert!(
+        wallet
+            .sign(
+                &mut psbt,
+                SignOptions {
+                    tap_leaves_options: TapLeavesOptions::Include(included_script_leaves.clone()),
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        "Unable to finalize tx"
+    );
 
-#[test]
-fn test_thread_safety() {
-    fn thread_safe<T: Send + Sync>() {}
-    thread_safe::<Wallet>(); // compiles only if true
-    thread_safe::<PersistedWallet<bdk_chain::rusqlite::Connection>>();
+    assert!(psbt.inputs[0]
+        .tap_script_sigs
+        .iter()
+        .all(|s| included_script_leaves.contains(&s.0 .1)
+            && !excluded_script_leaves.contains(&s.0 .1)));
 }
 
 #[test]
-fn single_descriptor_wallet_can_create_tx_and_receive_change() {
-    // create single descriptor wallet and fund it
-    let mut wallet = Wallet::create_single(get_test_tr_single_sig_xprv())
-        .network(Network::Testnet)
-        .create_wallet_no_persist()
-        .unwrap();
-    assert_eq!(wallet.keychains().count(), 1);
-    let amount = Amount::from_sat(5_000);
-    receive_output(&mut wallet, amount * 2, ReceiveTo::Mempool(2));
-    // create spend tx that produces a change output
-    let addr = Address::from_str("bcrt1qc6fweuf4xjvz4x3gx3t9e0fh4hvqyu2qw4wvxm")
-        .unwrap()
-        .assume_checked();
+fn test_taproot_script_spend_sign_exclude_some_leaves() {
+    use bdk_wallet::signer::TapLeavesOptions;
+    use bitcoin::taproot::TapLeafHash;
+
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
     let mut builder = wallet.build_tx();
-    builder.add_recipient(addr.script_pubkey(), amount);
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
     let mut psbt = builder.finish().unwrap();
-    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
-    let tx = psbt.extract_tx().unwrap();
-    let _txid = tx.compute_txid();
-    insert_tx(&mut wallet, tx);
-    let unspent: Vec<_> = wallet.list_unspent().collect();
-    assert_eq!(unspent.len(), 1);
-    let utxo = unspent.first().unwrap();
-    assert!(utxo.txout.value < amount);
-    assert_eq!(
-        utxo.keychain,
-        KeychainKind::External,
-        "tx change should go to external keychain"
+    let mut script_leaves: Vec<_> = psbt.inputs[0]
+        .tap_scripts
+        .clone()
+        .values()
+        .map(|(script, version)| TapLeafHash::from_script(script, *version))
+        .collect();
+    let included_script_leaves = [script_leaves.pop().unwrap()];
+    let excluded_script_leaves = script_leaves;
+
+    assert!(
+        wallet
+            .sign(
+                &mut psbt,
+                SignOptions {
+                    tap_leaves_options: TapLeavesOptions::Exclude(excluded_script_leaves.clone()),
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        "Unable to finalize tx"
+    );
+
+    assert!(psbt.inputs[0]
+        .tap_script_sigs
+        .iter()
+        .all(|s| included_script_leaves.contains(&s.0 .1)
+            && !excluded_script_leaves.contains(&s.0 .1)));
+}
+
+#[test]
+fn test_taproot_multi_a() {
+    use bdk_wallet::descriptor::policy::{Satisfaction, SatisfiableItem};
+
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_multi_a());
+
+    // the leaf is a 2-of-3 multisig, to which the wallet contributes two keys
+    let policy = wallet.policies(KeychainKind::External).unwrap().unwrap();
+    let leaf = match policy.item {
+        SatisfiableItem::Thresh { items, threshold } => {
+            assert_eq!((items.len(), threshold), (2, 1));
+            items[1].clone()
+        }
+        item => panic!("unexpected policy item: {item:?}"),
+    };
+    assert_matches!(
+        &leaf.item,
+        SatisfiableItem::Multisig { keys, threshold: 2 } if keys.len() == 3
+    );
+    assert_matches!(
+        &leaf.contribution,
+        Satisfaction::PartialComplete { n: 3, m: 2, .. }
+    );
+
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let psbt = builder.finish().unwrap();
+
+    let mut signed = psbt.clone();
+    assert!(
+        wallet.sign(&mut signed, SignOptions::default()).unwrap(),
+        "Unable to finalize tx"
+    );
+    assert!(signed.inputs[0].final_script_witness.is_some());
+
+    // with only one of the keys the leaf is signed but cannot be finalized
+    let key_b = "cNaQCDwmmh4dS9LzCgVtyy1e1xjCJ21GUDHe9K98nzb689JvinGV";
+    let pubkey_b = bitcoin::PrivateKey::from_wif(key_b)
+        .unwrap()
+        .public_key(&bitcoin::secp256k1::Secp256k1::new());
+    let one_key = get_test_tr_multi_a().replace(key_b, &pubkey_b.to_string());
+    let (mut wallet, _) = get_funded_wallet_single(&one_key);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let mut psbt = builder.finish().unwrap();
+    assert!(!wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    assert_eq!(psbt.inputs[0].tap_script_sigs.len(), 1);
+}
+
+#[test]
+fn test_sign_with_sha256_preimage() {
+    use bitcoin::hashes::sha256;
+
+    let preimage = [42u8; 32];
+    let hash = sha256::Hash::hash(&preimage);
+    let desc = format!(
+        "wsh(and_v(v:sha256({hash}),pk(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)))"
+    );
+    let (mut wallet, _) = get_funded_wallet_single(&desc);
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.drain_to(addr.script_pubkey()).drain_wallet();
+    let psbt = builder.finish().unwrap();
+
+    // without the preimage the input is signed but cannot be finalized
+    let mut signed = psbt.clone();
+    assert!(!wallet.sign(&mut signed, SignOptions::default()).unwrap());
+    assert!(signed.inputs[0].final_script_witness.is_none());
+
+    let mut wrong_preimage = psbt.clone();
+    let sign_options = SignOptions {
+        preimages: vec![(hash, [43u8; 32].to_vec())],
+        ..Default::default()
+    };
+    assert!(!wallet.sign(&mut wrong_preimage, sign_options).unwrap());
+    assert!(wrong_preimage.inputs[0].final_script_witness.is_none());
+
+    let mut signed = psbt;
+    let sign_options = SignOptions {
+        preimages: vec![(hash, preimage.to_vec())],
+        ..Default::default()
+    };
+    assert!(
+        wallet.sign(&mut signed, sign_options).unwrap(),
+        "Unable to finalize tx"
+    );
+    let witness = signed.inputs[0].final_script_witness.as_ref().unwrap();
+    assert!(witness.iter().any(|item| item == preimage));
+    let tx = signed.extract_tx().expect("fee rate is sane");
+    assert_eq!(tx.input[0].witness.len(), 3);
+}
+
+#[test]
+fn test_taproot_script_spend_sign_no_leaves() {
+    use bdk_wallet::signer::TapLeavesOptions;
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_with_taptree_both_priv());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let mut psbt = builder.finish().unwrap();
+
+    wallet
+        .sign(
+            &mut psbt,
+            SignOptions {
+                tap_leaves_options: TapLeavesOptions::None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    assert!(psbt.inputs.iter().all(|i| i.tap_script_sigs.is_empty()));
+}
+
+#[test]
+fn test_taproot_script_spend_sign_foreign_psbt() {
+    use bitcoin::taproot::ControlBlock;
+
+    // The internal key is unknown, but we hold the key of one of the leaves
+    let (mut owner, _) = get_funded_wallet_single(get_test_tr_with_taptree());
+    let addr = owner.next_unused_address(KeychainKind::External);
+    let mut builder = owner.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let mut psbt = builder.finish().unwrap();
+
+    // A foreign PSBT only provides the leaf scripts and their control blocks
+    for input in &mut psbt.inputs {
+        input.tap_key_origins.clear();
+    }
+    let tap_scripts = psbt.inputs[0].tap_scripts.clone();
+
+    let signer = Wallet::create_single("tr(cPZzKuNmpuUjD1e8jUU4PVzy2b5LngbSip8mBsxf4e7rSFZVb4Uh)")
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+
+    let mut ignored = psbt.clone();
+    assert!(!signer.sign(&mut ignored, SignOptions::default()).unwrap());
+    assert!(ignored.inputs[0].tap_script_sigs.is_empty());
+
+    let finalized = signer
+        .sign(
+            &mut psbt,
+            SignOptions {
+                trust_tap_scripts: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert!(finalized, "Unable to finalize tx");
+
+    let witness = psbt.inputs[0].final_script_witness.as_ref().unwrap();
+    assert_eq!(witness.len(), 3);
+    let control_block = ControlBlock::decode(witness.last().unwrap()).unwrap();
+    let (script, _) = tap_scripts.get(&control_block).unwrap();
+    assert_eq!(witness[1], *script.as_bytes());
+}
+
+#[test]
+fn test_taproot_sign_derive_index_from_psbt() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig_xprv());
+
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let mut psbt = builder.finish().unwrap();
+
+    // re-create the wallet with an empty db
+    let wallet_empty = Wallet::create(get_test_tr_single_sig_xprv(), get_test_tr_single_sig())
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+
+    // signing with an empty db means that we will only look at the psbt to infer the
+    // derivation index
+    assert!(
+        wallet_empty.sign(&mut psbt, Default::default()).unwrap(),
+        "Unable to finalize tx"
+    );
+}
+
+#[test]
+fn test_taproot_sign_with_aux_rand() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig_xprv());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.drain_to(addr.script_pubkey()).drain_wallet();
+    let psbt = builder.finish().unwrap();
+
+    let sign_options = SignOptions {
+        try_finalize: false,
+        ..Default::default()
+    };
+    let sign = |seed: u8| {
+        let mut psbt = psbt.clone();
+        let mut rng = StdRng::from_seed([seed; 32]);
+        wallet
+            .sign_with_aux_rand(&mut psbt, sign_options.clone(), &mut rng)
+            .unwrap();
+        psbt.inputs[0].tap_key_sig.expect("input must be signed")
+    };
+
+    // the same rng produces the same signature, another one a different signature
+    assert_eq!(sign(1), sign(1));
+    assert_ne!(sign(1), sign(2));
+
+    // the aux randomness of the options is used by the signer as given
+    let mut rng = StdRng::from_seed([1; 32]);
+    let mut aux_rand = [0_u8; 32];
+    rng.fill_bytes(&mut aux_rand);
+    let mut signed = psbt.clone();
+    wallet
+        .sign(
+            &mut signed,
+            SignOptions {
+                aux_rand: Some(aux_rand),
+                ..sign_options.clone()
+            },
+        )
+        .unwrap();
+    assert_eq!(signed.inputs[0].tap_key_sig, Some(sign(1)));
+
+    // otherwise the system rng is used
+    let mut first = psbt.clone();
+    wallet.sign(&mut first, sign_options.clone()).unwrap();
+    let mut second = psbt.clone();
+    wallet.sign(&mut second, sign_options).unwrap();
+    assert_ne!(first.inputs[0].tap_key_sig, second.inputs[0].tap_key_sig);
+}
+
+#[test]
+fn test_taproot_sign_explicit_sighash_all() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .drain_to(addr.script_pubkey())
+        .sighash(TapSighashType::All.into())
+        .drain_wallet();
+    let mut psbt = builder.finish().unwrap();
+
+    let result = wallet.sign(&mut psbt, Default::default());
+    assert!(
+        result.is_ok(),
+        "Signing should work because SIGHASH_ALL is safe"
+    )
+}
+
+#[test]
+fn test_taproot_sign_non_default_sighash() {
+    let sighash = TapSighashType::NonePlusAnyoneCanPay;
+
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .drain_to(addr.script_pubkey())
+        .sighash(sighash.into())
+        .drain_wallet();
+    let mut psbt = builder.finish().unwrap();
+
+    let witness_utxo = psbt.inputs[0].witness_utxo.take();
+
+    let result = wallet.sign(&mut psbt, Default::default());
+    assert!(
+        result.is_err(),
+        "Signing should have failed because the TX uses non-standard sighashes"
+    );
+    assert_matches!(
+        result,
+        Err(SignerError::NonStandardSighash),
+        "Signing failed with the wrong error type"
+    );
+
+    // try again after opting-in
+    let result = wallet.sign(
+        &mut psbt,
+        SignOptions {
+            allow_all_sighashes: true,
+            ..Default::default()
+        },
+    );
+    assert!(
+        result.is_err(),
+        "Signing should have failed because the witness_utxo is missing"
+    );
+    assert_matches!(
+        result,
+        Err(SignerError::MissingWitnessUtxo),
+        "Signing failed with the wrong error type"
+    );
+
+    // restore the witness_utxo
+    psbt.inputs[0].witness_utxo = witness_utxo;
+
+    let result = wallet.sign(
+        &mut psbt,
+        SignOptions {
+            allow_all_sighashes: true,
+            ..Default::default()
+        },
+    );
+
+    assert!(result.is_ok(), "Signing should have worked");
+    assert!(
+        result.unwrap(),
+        "Should finalize the input since we can produce signatures"
+    );
+
+    let extracted = psbt.extract_tx().expect("failed to extract tx");
+    assert_eq!(
+        *extracted.input[0].witness.to_vec()[0].last().unwrap(),
+        sighash as u8,
+        "The signature should have been made with the right sighash"
+    );
+}
+
+#[test]
+fn test_spend_coinbase() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+
+    let confirmation_height = 5;
+    let confirmation_block_id = BlockId {
+        height: confirmation_height,
+        hash: BlockHash::all_zeros(),
+    };
+    insert_checkpoint(&mut wallet, confirmation_block_id);
+    let coinbase_tx = Transaction {
+        version: transaction::Version::ONE,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            script_pubkey: wallet
+                .next_unused_address(KeychainKind::External)
+                .script_pubkey(),
+            value: Amount::from_sat(25_000),
+        }],
+    };
+    let txid = coinbase_tx.compute_txid();
+
+    let anchor = ConfirmationBlockTime {
+        block_id: confirmation_block_id,
+        confirmation_time: 30_000,
+    };
+
+    // Insert coinbase transaction into the local view, and also simulates confirming tx by applying
+    // the update with an `anchor`.
+    let mut tx_update = bdk_chain::TxUpdate::default();
+    tx_update.txs = vec![Arc::new(coinbase_tx)];
+    tx_update.anchors = [(anchor, txid)].into();
+    wallet
+        .apply_update(Update {
+            tx_update,
+            ..Default::default()
+        })
+        .unwrap();
+
+    // NOTE: A transaction spending an output coming from the coinbase tx at height h, is eligible
+    // to be included in block h + [100 = COINBASE_MATURITY] or higher.
+    // Tx elibible to be included in the next block will be accepted in the mempool, used in block
+    // templates and relayed on the network.
+    // Miners may include such tx in a block when their chaintip is at h + [99 = COINBASE_MATURITY -
+    // 1]. This means these coins are available for selection at height h + 99.
+    //
+    // By https://bitcoin.stackexchange.com/a/119017
+    let not_yet_mature_time = confirmation_height + COINBASE_MATURITY - 2;
+    let maturity_time = confirmation_height + COINBASE_MATURITY - 1;
+
+    let balance = wallet.balance();
+    assert_eq!(
+        balance,
+        Balance {
+            immature: Amount::from_sat(25_000),
+            trusted_pending: Amount::ZERO,
+            untrusted_pending: Amount::ZERO,
+            confirmed: Amount::ZERO
+        }
+    );
+
+    // We try to create a transaction, only to notice that all
+    // our funds are unspendable
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), balance.immature / 2)
+        .current_height(confirmation_height);
+    assert!(matches!(
+        builder.finish(),
+        Err(CreateTxError::CoinSelection(
+            coin_selection::InsufficientFunds {
+                needed: _,
+                available: Amount::ZERO
+            }
+        ))
+    ));
+
+    // Still unspendable...
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), balance.immature / 2)
+        .current_height(not_yet_mature_time);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::CoinSelection(
+            coin_selection::InsufficientFunds {
+                needed: _,
+                available: Amount::ZERO
+            }
+        ))
+    );
+
+    insert_checkpoint(
+        &mut wallet,
+        BlockId {
+            height: maturity_time,
+            hash: BlockHash::all_zeros(),
+        },
+    );
+    let balance = wallet.balance();
+    assert_eq!(
+        balance,
+        Balance {
+            immature: Amount::ZERO,
+            trusted_pending: Amount::ZERO,
+            untrusted_pending: Amount::ZERO,
+            confirmed: Amount::from_sat(25_000)
+        }
+    );
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), balance.confirmed / 2)
+        .current_height(maturity_time);
+    builder.finish().unwrap();
+}
+
+#[test]
+fn test_allow_dust_limit() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_cltv());
+
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    let mut builder = wallet.build_tx();
+
+    builder.add_recipient(addr.script_pubkey(), Amount::ZERO);
+
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::OutputBelowDustLimit(0))
+    );
+
+    let mut builder = wallet.build_tx();
+
+    builder
+        .allow_dust(true)
+        .add_recipient(addr.script_pubkey(), Amount::ZERO);
+
+    assert!(builder.finish().is_ok());
+}
+
+#[test]
+fn test_fee_rate_sign_no_grinding_high_r() {
+    // Our goal is to obtain a transaction with a signature with high-R (71 bytes
+    // instead of 70). We then check that our fee rate and fee calculation is
+    // alright.
+    let (mut wallet, _) = get_funded_wallet_single("wpkh(tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS/*)");
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+    let mut builder = wallet.build_tx();
+    let mut data = PushBytesBuf::try_from(vec![0]).unwrap();
+    builder
+        .drain_to(addr.script_pubkey())
+        .drain_wallet()
+        .fee_rate(fee_rate)
+        .add_data(&data);
+    let mut psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+    let (op_return_vout, _) = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .enumerate()
+        .find(|(_n, i)| i.script_pubkey.is_op_return())
+        .unwrap();
+
+    let mut sig_len: usize = 0;
+    // We try to sign many different times until we find a longer signature (71 bytes)
+    while sig_len < 71 {
+        // Changing the OP_RETURN data will make the signature change (but not the fee, until
+        // data[0] is small enough)
+        data.as_mut_bytes()[0] += 1;
+        psbt.unsigned_tx.output[op_return_vout].script_pubkey = ScriptBuf::new_op_return(&data);
+        // Clearing the previous signature
+        psbt.inputs[0].partial_sigs.clear();
+        // Signing
+        wallet
+            .sign(
+                &mut psbt,
+                SignOptions {
+                    try_finalize: false,
+                    allow_grinding: false,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        // We only have one key in the partial_sigs map, this is a trick to retrieve it
+        let key = psbt.inputs[0].partial_sigs.keys().next().unwrap();
+        sig_len = psbt.inputs[0].partial_sigs[key]
+            .signature
+            .serialize_der()
+            .len();
+    }
+    // Actually finalizing the transaction...
+    wallet
+        .sign(
+            &mut psbt,
+            SignOptions {
+                allow_grinding: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    // ...and checking that everything is fine
+    assert_fee_rate!(psbt, fee, fee_rate);
+}
+
+#[test]
+fn test_fee_rate_sign_grinding_low_r() {
+    // Our goal is to obtain a transaction with a signature with low-R (70 bytes)
+    // by setting the `allow_grinding` signing option as true.
+    // We then check that our fee rate and fee calculation is alright and that our
+    // signature is 70 bytes.
+    let (mut wallet, _) = get_funded_wallet_single("wpkh(tprv8ZgxMBicQKsPd3EupYiPRhaMooHKUHJxNsTfYuScep13go8QFfHdtkG9nRkFGb7busX4isf6X9dURGCoKgitaApQ6MupRhZMcELAxTBRJgS/*)");
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+    let mut builder = wallet.build_tx();
+    builder
+        .drain_to(addr.script_pubkey())
+        .drain_wallet()
+        .fee_rate(fee_rate);
+    let mut psbt = builder.finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+
+    wallet
+        .sign(
+            &mut psbt,
+            SignOptions {
+                try_finalize: false,
+                allow_grinding: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let key = psbt.inputs[0].partial_sigs.keys().next().unwrap();
+    let sig_len = psbt.inputs[0].partial_sigs[key]
+        .signature
+        .serialize_der()
+        .len();
+    assert_eq!(sig_len, 70);
+    assert_fee_rate!(psbt, fee, fee_rate);
+}
+
+#[test]
+fn test_taproot_load_descriptor_duplicated_keys() {
+    // Added after issue https://github.com/bitcoindevkit/bdk/issues/760
+    //
+    // Having the same key in multiple taproot leaves is safe and should be accepted by BDK
+
+    let (wallet, _) = get_funded_wallet_single(get_test_tr_dup_keys());
+    let addr = wallet.peek_address(KeychainKind::External, 0);
+
+    assert_eq!(
+        addr.to_string(),
+        "bcrt1pvysh4nmh85ysrkpwtrr8q8gdadhgdejpy6f9v424a8v9htjxjhyqw9c5s5"
+    );
+}
+
+/// In dev mode this test panics, but in release mode, or if the `debug_panic` in
+/// `TxOutIndex::replenish_inner_index` is commented out, there is no panic and the balance is
+/// calculated correctly. See issue [#1483] and PR [#1486] for discussion on mixing non-wildcard and
+/// wildcard descriptors.
+///
+/// [#1483]: https://github.com/bitcoindevkit/bdk/issues/1483
+/// [#1486]: https://github.com/bitcoindevkit/bdk/pull/1486
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(
+    expected = "replenish lookahead: must not have existing spk: keychain=Internal, lookahead=25, next_index=0"
+)]
+fn test_keychains_with_overlapping_spks() {
+    // this can happen if a non-wildcard descriptor keychain derives an spk that a
+    // wildcard descriptor keychain in the same wallet also derives.
+
+    // index 1 spk overlaps with non-wildcard change descriptor
+    let wildcard_keychain = "wpkh(tprv8ZgxMBicQKsPdDArR4xSAECuVxeX1jwwSXR4ApKbkYgZiziDc4LdBy2WvJeGDfUSE4UT4hHhbgEwbdq8ajjUHiKDegkwrNU6V55CxcxonVN/*)";
+    let non_wildcard_keychain = "wpkh(tprv8ZgxMBicQKsPdDArR4xSAECuVxeX1jwwSXR4ApKbkYgZiziDc4LdBy2WvJeGDfUSE4UT4hHhbgEwbdq8ajjUHiKDegkwrNU6V55CxcxonVN/1)";
+
+    let (mut wallet, _) = get_funded_wallet(wildcard_keychain, non_wildcard_keychain);
+    assert_eq!(wallet.balance().confirmed, Amount::from_sat(50000));
+
+    let addr = wallet
+        .reveal_addresses_to(KeychainKind::External, 1)
+        .last()
+        .unwrap()
+        .address;
+    let anchor = ConfirmationBlockTime {
+        block_id: BlockId {
+            height: 2000,
+            hash: BlockHash::all_zeros(),
+        },
+        confirmation_time: 0,
+    };
+    let _outpoint = receive_output_to_address(&mut wallet, addr, Amount::from_sat(8000), anchor);
+    assert_eq!(wallet.balance().confirmed, Amount::from_sat(58000));
+}
+
+#[test]
+fn test_thread_safety() {
+    fn thread_safe<T: Send + Sync>() {}
+    thread_safe::<Wallet>(); // compiles only if true
+    thread_safe::<PersistedWallet<bdk_chain::rusqlite::Connection>>();
+}
+
+#[test]
+fn single_descriptor_wallet_can_create_tx_and_receive_change() {
+    // create single descriptor wallet and fund it
+    let mut wallet = Wallet::create_single(get_test_tr_single_sig_xprv())
+        .network(Network::Testnet)
+        .create_wallet_no_persist()
+        .unwrap();
+    assert_eq!(wallet.keychains().count(), 1);
+    let amount = Amount::from_sat(5_000);
+    receive_output(&mut wallet, amount * 2, ReceiveTo::Mempool(2));
+    // create spend tx that produces a change output
+    let addr = Address::from_str("bcrt1qc6fweuf4xjvz4x3gx3t9e0fh4hvqyu2qw4wvxm")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), amount);
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().unwrap();
+    let _txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+    let unspent: Vec<_> = wallet.list_unspent().collect();
+    assert_eq!(unspent.len(), 1);
+    let utxo = unspent.first().unwrap();
+    assert!(utxo.txout.value < amount);
+    assert_eq!(
+        utxo.keychain,
+        KeychainKind::External,
+        "tx change should go to external keychain"
     );
 }
 
 #[test]
 fn test_transactions_sort_by() {
     let (mut wallet, _txid) = get_funded_wallet_wpkh();
-    receive_output(&mut wallet, Amount::from_sat(25_000), ReceiveTo::Mempool(0));
+    receive_output(&mut wallet, Amount::from_sat(25_000), ReceiveTo::Mempool(0));
+
+    // sort by chain position, unconfirmed then confirmed by descending block height
+    let sorted_txs: Vec<WalletTx> =
+        wallet.transactions_sort_by(|t1, t2| t2.chain_position.cmp(&t1.chain_position));
+    let conf_heights: Vec<Option<u32>> = sorted_txs
+        .iter()
+        .map(|tx| tx.chain_position.confirmation_height_upper_bound())
+        .collect();
+    assert_eq!([None, Some(2000), Some(1000)], conf_heights.as_slice());
+}
+
+#[test]
+fn test_transactions_sorted() {
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    // `receive_output` marks transactions as seen now, so set the last seen times explicitly
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut unconfirmed = |nonce, last_seen| {
+        let mut tx = new_tx(nonce);
+        tx.output.push(TxOut {
+            value: Amount::from_sat(25_000),
+            script_pubkey: addr.script_pubkey(),
+        });
+        let txid = tx.compute_txid();
+        wallet.apply_unconfirmed_txs([(tx, last_seen)]);
+        txid
+    };
+    let seen_at_20 = unconfirmed(1, 20);
+    let mut seen_at_10 = [unconfirmed(2, 10), unconfirmed(3, 10)];
+    seen_at_10.sort();
+    let evicted = unconfirmed(4, 30);
+    wallet.apply_evicted_txs([(evicted, 40)]);
+    let mut in_tip = [
+        txid,
+        receive_output_in_latest_block(&mut wallet, Amount::from_sat(25_000)).txid,
+        receive_output_in_latest_block(&mut wallet, Amount::from_sat(25_000)).txid,
+    ];
+    in_tip.sort();
+    let funding = wallet.get_tx(txid).unwrap().tx_node.input[0]
+        .previous_output
+        .txid;
+
+    let sorted = wallet
+        .transactions_sorted()
+        .into_iter()
+        .map(|tx| tx.tx_node.txid)
+        .collect::<Vec<_>>();
+    let expected = [seen_at_20]
+        .into_iter()
+        .chain(seen_at_10)
+        .chain(in_tip)
+        .chain([funding])
+        .collect::<Vec<_>>();
+    assert_eq!(sorted, expected);
+    assert!(!sorted.contains(&evicted));
+
+    // the order doesn't depend on the order the transactions are listed in
+    let mut txs = wallet.transactions().collect::<Vec<_>>();
+    txs.reverse();
+    txs.sort_by(|tx1, tx2| tx1.display_ord(tx2));
+    assert_eq!(
+        txs.iter().map(|tx| tx.tx_node.txid).collect::<Vec<_>>(),
+        expected
+    );
+
+    // pages follow the same order, including between transactions seen at the same time or
+    // confirmed in the same block
+    for limit in 1..=expected.len() {
+        let pages = (0..expected.len())
+            .step_by(limit)
+            .flat_map(|offset| {
+                wallet.transactions_paginated(offset, limit, HistoryOrder::NewestFirst)
+            })
+            .map(|tx| tx.tx_node.txid)
+            .collect::<Vec<_>>();
+        assert_eq!(pages, expected, "limit {limit}");
+    }
+    let mut oldest_first = expected.clone();
+    oldest_first.reverse();
+    assert_eq!(
+        wallet
+            .transactions_paginated(0, usize::MAX, HistoryOrder::OldestFirst)
+            .into_iter()
+            .map(|tx| tx.tx_node.txid)
+            .collect::<Vec<_>>(),
+        oldest_first
+    );
+}
+
+#[test]
+fn test_transactions_paginated() {
+    let (mut wallet, _txid) = get_funded_wallet_wpkh();
+    for seen_at in 0..5 {
+        receive_output(
+            &mut wallet,
+            Amount::from_sat(25_000),
+            ReceiveTo::Mempool(seen_at),
+        );
+    }
+    let txids = |txs: Vec<WalletTx>| {
+        txs.into_iter()
+            .map(|tx| tx.tx_node.txid)
+            .collect::<Vec<_>>()
+    };
+    let newest_first = txids(wallet.transactions_sorted());
+    assert_eq!(newest_first.len(), 7);
+
+    let pages = (0..3)
+        .flat_map(|page| {
+            txids(wallet.transactions_paginated(page * 3, 3, HistoryOrder::NewestFirst))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(pages, newest_first);
+
+    let oldest_first = newest_first.iter().rev().cloned().collect::<Vec<_>>();
+    assert_eq!(
+        txids(wallet.transactions_paginated(2, 4, HistoryOrder::OldestFirst)),
+        oldest_first[2..6]
+    );
+    assert_eq!(
+        txids(wallet.transactions_paginated(0, 2, HistoryOrder::OldestFirst))
+            .iter()
+            .map(|txid| wallet.get_tx(*txid).unwrap().chain_position.is_confirmed())
+            .collect::<Vec<_>>(),
+        [true, true]
+    );
+
+    assert!(wallet
+        .transactions_paginated(7, 10, HistoryOrder::NewestFirst)
+        .is_empty());
+    assert!(wallet
+        .transactions_paginated(0, 0, HistoryOrder::NewestFirst)
+        .is_empty());
+    assert_eq!(
+        wallet
+            .transactions_paginated(5, usize::MAX, HistoryOrder::NewestFirst)
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn test_utxo_stats() {
+    // 50_000 sats of change confirmed at height 2000
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let block_1000 = wallet.latest_checkpoint().get(1000).unwrap().block_id();
+    receive_output(
+        &mut wallet,
+        Amount::from_sat(30_000),
+        ConfirmationBlockTime {
+            block_id: block_1000,
+            confirmation_time: 0,
+        },
+    );
+    receive_output_in_latest_block(&mut wallet, Amount::from_sat(20_000));
+    // below the 294 sats dust limit of p2wpkh outputs
+    receive_output(&mut wallet, Amount::from_sat(100), ReceiveTo::Mempool(0));
+
+    let stats = wallet.utxo_stats(2_100);
+    assert_eq!(
+        stats,
+        UtxoStats {
+            count: 4,
+            total_value: Amount::from_sat(100_100),
+            dust_count: 1,
+            dust_value: Amount::from_sat(100),
+            unconfirmed_count: 1,
+            age_distribution: [0, 2, 0, 1, 0, 0],
+            median_age: Some(101),
+            max_age: Some(1_101),
+        }
+    );
+
+    let (wallet, _) = get_funded_wallet_wpkh();
+    let stats = wallet.utxo_stats(2_000);
+    assert_eq!(stats.median_age, Some(1));
+    assert_eq!(stats.age_distribution, [1, 0, 0, 0, 0, 0]);
+    let stats = Wallet::create_single(get_test_wpkh())
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap()
+        .utxo_stats(2_000);
+    assert_eq!(stats, UtxoStats::default());
+}
+
+#[test]
+fn test_balance_at() {
+    // 76_000 sats received at height 1000, then spent at height 2000 with 50_000 sats of change
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let confirmed = |sats| Balance {
+        confirmed: Amount::from_sat(sats),
+        ..Default::default()
+    };
+    let block = |height| BlockId {
+        height,
+        hash: BlockHash::all_zeros(),
+    };
+    let anchor = |height| ConfirmationBlockTime {
+        block_id: block(height),
+        confirmation_time: height as u64,
+    };
+
+    insert_checkpoint(&mut wallet, block(3000));
+    receive_output(&mut wallet, Amount::from_sat(10_000), anchor(3000));
+
+    let spend = Transaction {
+        input: vec![TxIn {
+            previous_output: OutPoint { txid, vout: 0 },
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(49_000),
+            script_pubkey: ScriptBuf::new_op_return([]),
+        }],
+        ..new_tx(0)
+    };
+    let spend_txid = spend.compute_txid();
+    insert_tx(&mut wallet, spend);
+    insert_checkpoint(&mut wallet, block(4000));
+    insert_anchor(&mut wallet, spend_txid, anchor(4000));
+
+    receive_output(&mut wallet, Amount::from_sat(5_000), ReceiveTo::Mempool(1));
+
+    assert_eq!(wallet.balance_at(0), Balance::default());
+    assert_eq!(wallet.balance_at(999), Balance::default());
+    assert_eq!(wallet.balance_at(1000), confirmed(76_000));
+    assert_eq!(wallet.balance_at(1999), confirmed(76_000));
+    assert_eq!(wallet.balance_at(2000), confirmed(50_000));
+    assert_eq!(wallet.balance_at(3000), confirmed(60_000));
+    assert_eq!(wallet.balance_at(3999), confirmed(60_000));
+    assert_eq!(wallet.balance_at(4000), confirmed(10_000));
+
+    // unconfirmed transactions are ignored
+    assert_eq!(wallet.balance_at(u32::MAX), confirmed(10_000));
+    assert_eq!(wallet.balance().untrusted_pending, Amount::from_sat(5_000));
+}
+
+/// Mock of a chain source full scan, stopping on each keychain after `stop_gap` consecutive
+/// unused spks.
+fn mock_full_scan(
+    mut request: FullScanRequest<KeychainKind>,
+    stop_gap: usize,
+    used_spks: &[ScriptBuf],
+) -> FullScanResponse<KeychainKind> {
+    let mut response = FullScanResponse::default();
+    for keychain in request.keychains() {
+        let mut unused = 0;
+        for (index, spk) in request.iter_spks(keychain) {
+            if used_spks.contains(&spk) {
+                response.last_active_indices.insert(keychain, index);
+                unused = 0;
+            } else {
+                unused += 1;
+                if unused >= stop_gap {
+                    break;
+                }
+            }
+        }
+    }
+    response
+}
+
+#[test]
+fn test_full_scan_for_keychain() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    let spk = |keychain, index| wallet.peek_address(keychain, index).script_pubkey();
+    let used_spks = [
+        spk(KeychainKind::External, 0),
+        spk(KeychainKind::External, 30),
+        spk(KeychainKind::External, 90),
+        spk(KeychainKind::Internal, 2),
+    ];
+
+    // a single stop gap for both keychains misses the sparse external usage
+    let response = mock_full_scan(wallet.start_full_scan().build(), 50, &used_spks);
+    assert_eq!(
+        response.last_active_indices,
+        [(KeychainKind::External, 30), (KeychainKind::Internal, 2)].into()
+    );
+
+    let request = wallet
+        .start_full_scan_for_keychain(KeychainKind::External)
+        .build();
+    assert_eq!(request.keychains(), [KeychainKind::External]);
+    let external = mock_full_scan(request, 100, &used_spks);
+    assert_eq!(
+        external.last_active_indices,
+        [(KeychainKind::External, 90)].into()
+    );
+    let request = wallet
+        .start_full_scan_for_keychain_at(KeychainKind::Internal, 42)
+        .build();
+    assert_eq!(request.keychains(), [KeychainKind::Internal]);
+    assert_eq!(request.start_time(), 42);
+    let internal = mock_full_scan(request, 5, &used_spks);
+    assert_eq!(
+        internal.last_active_indices,
+        [(KeychainKind::Internal, 2)].into()
+    );
+
+    wallet.apply_update(external).unwrap();
+    wallet.apply_update(internal).unwrap();
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(90));
+    assert_eq!(wallet.derivation_index(KeychainKind::Internal), Some(2));
+
+    // the last revealed index is carried over to the request
+    let request = wallet
+        .start_full_scan_for_keychain(KeychainKind::External)
+        .build();
+    assert_eq!(request.last_revealed(&KeychainKind::External), Some(90));
+
+    // without a change descriptor the internal keychain maps to the external one
+    let wallet = Wallet::create_single(desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+    let request = wallet
+        .start_full_scan_for_keychain(KeychainKind::Internal)
+        .build();
+    assert_eq!(request.keychains(), [KeychainKind::External]);
+}
+
+#[test]
+fn test_resumable_full_scan() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let new_wallet = || {
+        Wallet::create(desc, change_desc)
+            .network(Network::Regtest)
+            .create_wallet_no_persist()
+            .unwrap()
+    };
+    let mut wallet = new_wallet();
+    let spk = |keychain, index| wallet.peek_address(keychain, index).script_pubkey();
+    let used_spks = [
+        spk(KeychainKind::External, 0),
+        spk(KeychainKind::External, 15),
+        spk(KeychainKind::External, 33),
+        spk(KeychainKind::External, 52),
+        // beyond the stop gap
+        spk(KeychainKind::External, 80),
+        spk(KeychainKind::Internal, 4),
+    ];
+
+    let uninterrupted = mock_full_scan(wallet.start_full_scan().build(), 20, &used_spks);
+    assert_eq!(
+        uninterrupted.last_active_indices,
+        [(KeychainKind::External, 52), (KeychainKind::Internal, 4)].into()
+    );
+
+    // scan 3 batches, then persist the wallet and the cursor
+    let mut cursor = wallet.scan_cursor(20, 7);
+    for _ in 0..3 {
+        let request = wallet.start_full_scan_batch(&cursor).build();
+        let response = mock_full_scan(request, cursor.batch_size() as usize, &used_spks);
+        cursor.advance(&response);
+        wallet.apply_update(response).unwrap();
+    }
+    assert!(!cursor.is_finished());
+    assert_eq!(cursor.next_index(KeychainKind::External), Some(21));
+    assert_eq!(cursor.last_active(KeychainKind::External), Some(15));
+    assert_eq!(cursor.next_index(KeychainKind::Internal), Some(21));
+    let changeset = wallet.staged().unwrap().clone();
+    let cursor = serde_json::to_string(&cursor).unwrap();
+
+    // resume the scan with a reloaded wallet
+    let mut wallet = Wallet::load()
+        .load_wallet_no_persist(changeset)
+        .unwrap()
+        .unwrap();
+    let mut cursor: ScanCursor = serde_json::from_str(&cursor).unwrap();
+    let mut requested = Vec::new();
+    while !cursor.is_finished() {
+        let mut request = wallet.start_full_scan_batch(&cursor).build();
+        for keychain in request.keychains() {
+            requested.extend(
+                request
+                    .iter_spks(keychain)
+                    .map(|(index, _)| (keychain, index)),
+            );
+        }
+        let request = wallet.start_full_scan_batch(&cursor).build();
+        let response = mock_full_scan(request, cursor.batch_size() as usize, &used_spks);
+        cursor.advance(&response);
+        wallet.apply_update(response).unwrap();
+    }
+    assert!(!requested.contains(&(KeychainKind::External, 20)));
+    assert!(requested.contains(&(KeychainKind::External, 21)));
+    assert_eq!(cursor.next_index(KeychainKind::External), Some(73));
+    assert_eq!(cursor.last_active(KeychainKind::External), Some(52));
+    assert_eq!(cursor.last_active(KeychainKind::Internal), Some(4));
+
+    let mut expected = new_wallet();
+    expected.apply_update(uninterrupted).unwrap();
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::External),
+        expected.derivation_index(KeychainKind::External)
+    );
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::Internal),
+        expected.derivation_index(KeychainKind::Internal)
+    );
+    assert!(wallet
+        .start_full_scan_batch(&cursor)
+        .build()
+        .keychains()
+        .is_empty());
+}
+
+#[test]
+fn test_build_utxo_refresh() {
+    use bdk_wallet::tx_builder::{AddForeignUtxoError, AddUtxoError};
+
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let other = receive_output(&mut wallet, Amount::from_sat(25_000), ReceiveTo::Mempool(1));
+    let outpoint = OutPoint { txid, vout: 0 };
+    let fee_rate = FeeRate::from_sat_per_vb_u32(5);
+
+    let mut builder = wallet.build_utxo_refresh(outpoint, fee_rate).unwrap();
+    assert_matches!(builder.add_utxo(other), Err(AddUtxoError::FixedInputs));
+    assert_matches!(
+        builder.add_foreign_utxo(other, Default::default(), Weight::ZERO),
+        Err(AddForeignUtxoError::FixedInputs)
+    );
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    assert_eq!(psbt.unsigned_tx.input[0].previous_output, outpoint);
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    let output = &psbt.unsigned_tx.output[0];
+    assert_eq!(
+        output.value + psbt.fee_amount().unwrap(),
+        Amount::from_sat(50_000)
+    );
+    assert!(psbt.fee_rate().unwrap() >= fee_rate);
+    assert_eq!(
+        wallet.derivation_of_spk(output.script_pubkey.clone()),
+        Some((KeychainKind::Internal, 0))
+    );
+
+    // the destination keychain can be overridden
+    let mut builder = wallet.build_utxo_refresh(outpoint, fee_rate).unwrap();
+    builder.drain_to_keychain(KeychainKind::External);
+    let psbt = builder.finish().unwrap();
+    let (keychain, _) = wallet
+        .derivation_of_spk(psbt.unsigned_tx.output[0].script_pubkey.clone())
+        .unwrap();
+    assert_eq!(keychain, KeychainKind::External);
+
+    // spent and unknown outputs can't be refreshed
+    let spent = wallet.get_tx(txid).unwrap().tx_node.input[0].previous_output;
+    assert_matches!(
+        wallet.build_utxo_refresh(spent, fee_rate),
+        Err(AddUtxoError::UnknownUtxo(op)) if op == spent
+    );
+    let unknown = OutPoint {
+        txid: Txid::all_zeros(),
+        vout: 0,
+    };
+    assert_matches!(
+        wallet.build_utxo_refresh(unknown, fee_rate),
+        Err(AddUtxoError::UnknownUtxo(_))
+    );
+}
+
+#[test]
+fn test_change_script_type_preference() {
+    // taproot external keychain and segwit v0 internal keychain
+    let (mut wallet, _) = get_funded_wallet(
+        get_test_tr_single_sig_xprv(),
+        get_test_wpkh_and_change_desc().1,
+    );
+    assert_eq!(
+        wallet.address_type(KeychainKind::External),
+        AddressType::P2tr
+    );
+    assert_eq!(
+        wallet.address_type(KeychainKind::Internal),
+        AddressType::P2wpkh
+    );
+    let tr_recipient = Wallet::create_single(get_test_tr_single_sig())
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap()
+        .peek_address(KeychainKind::External, 0)
+        .script_pubkey();
+    let wpkh_recipient = Address::from_str("tb1q6yn66vajcctph75pvylgkksgpp6nq04ppwct9a")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let change_keychain = |wallet: &mut Wallet, recipients: &[&ScriptBuf], preference| {
+        let mut builder = wallet.build_tx();
+        for recipient in recipients {
+            builder.add_recipient((*recipient).clone(), Amount::from_sat(10_000));
+        }
+        if let Some(preference) = preference {
+            builder.change_script_type_preference(preference);
+        }
+        let psbt = builder.finish().unwrap();
+        let change = psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .filter_map(|txout| wallet.derivation_of_spk(txout.script_pubkey.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(change.len(), 1);
+        change[0].0
+    };
+
+    // by default the change matches the type of the recipients
+    assert_eq!(
+        change_keychain(&mut wallet, &[&tr_recipient], None),
+        KeychainKind::External
+    );
+    assert_eq!(
+        change_keychain(&mut wallet, &[&wpkh_recipient], None),
+        KeychainKind::Internal
+    );
+    assert_eq!(
+        change_keychain(
+            &mut wallet,
+            &[&tr_recipient, &tr_recipient, &wpkh_recipient],
+            None
+        ),
+        KeychainKind::External
+    );
+    // the internal keychain is used if no type is the most common
+    assert_eq!(
+        change_keychain(&mut wallet, &[&tr_recipient, &wpkh_recipient], None),
+        KeychainKind::Internal
+    );
+
+    // the preference overrides the type of the recipients
+    assert_eq!(
+        change_keychain(&mut wallet, &[&tr_recipient], Some(AddressType::P2wpkh)),
+        KeychainKind::Internal
+    );
+    assert_eq!(
+        change_keychain(&mut wallet, &[&wpkh_recipient], Some(AddressType::P2tr)),
+        KeychainKind::External
+    );
+    assert_eq!(
+        change_keychain(&mut wallet, &[&tr_recipient], Some(AddressType::P2pkh)),
+        KeychainKind::Internal
+    );
+
+    // keychains of the same type always use the internal keychain
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    assert_eq!(
+        change_keychain(&mut wallet, &[&tr_recipient], Some(AddressType::P2tr)),
+        KeychainKind::Internal
+    );
+    assert_eq!(
+        change_keychain(&mut wallet, &[&wpkh_recipient], None),
+        KeychainKind::Internal
+    );
+}
+
+#[test]
+fn test_tx_builder_is_send_safe() {
+    let (mut wallet, _txid) = get_funded_wallet_wpkh();
+    let _box: Box<dyn Send + Sync> = Box::new(wallet.build_tx());
+}
+
+#[test]
+fn test_wallet_transactions_relevant() {
+    let (mut test_wallet, _txid) = get_funded_wallet_wpkh();
+    let relevant_tx_count_before = test_wallet.transactions().count();
+    let full_tx_count_before = test_wallet.tx_graph().full_txs().count();
+    let chain_tip = test_wallet.local_chain().tip().block_id();
+    let canonical_tx_count_before = test_wallet
+        .tx_graph()
+        .list_canonical_txs(
+            test_wallet.local_chain(),
+            chain_tip,
+            CanonicalizationParams::default(),
+        )
+        .count();
+
+    // add not relevant transaction to test wallet
+    let (other_external_desc, other_internal_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let (other_wallet, other_txid) = get_funded_wallet(other_internal_desc, other_external_desc);
+    let test_wallet_update = Update {
+        tx_update: other_wallet.tx_graph().clone().into(),
+        ..Default::default()
+    };
+    test_wallet.apply_update(test_wallet_update).unwrap();
+
+    // verify transaction from other wallet was added but is not in relevant transactions list.
+    let relevant_tx_count_after = test_wallet.transactions().count();
+    let full_tx_count_after = test_wallet.tx_graph().full_txs().count();
+    let canonical_tx_count_after = test_wallet
+        .tx_graph()
+        .list_canonical_txs(
+            test_wallet.local_chain(),
+            chain_tip,
+            CanonicalizationParams::default(),
+        )
+        .count();
+
+    assert_eq!(relevant_tx_count_before, relevant_tx_count_after);
+    assert!(!test_wallet
+        .transactions()
+        .any(|wallet_tx| wallet_tx.tx_node.txid == other_txid));
+    assert!(test_wallet
+        .tx_graph()
+        .list_canonical_txs(
+            test_wallet.local_chain(),
+            chain_tip,
+            CanonicalizationParams::default()
+        )
+        .any(|wallet_tx| wallet_tx.tx_node.txid == other_txid));
+    assert!(full_tx_count_before < full_tx_count_after);
+    assert!(canonical_tx_count_before < canonical_tx_count_after);
+}
+
+#[test]
+fn test_tx_details_method() {
+    let (test_wallet, txid_1) = get_funded_wallet_wpkh();
+    let tx_details_1_option = test_wallet.tx_details(txid_1);
+
+    assert!(tx_details_1_option.is_some());
+    let tx_details_1 = tx_details_1_option.unwrap();
+
+    assert_eq!(
+        tx_details_1.txid.to_string(),
+        "f2a03cdfe1bb6a295b0a4bb4385ca42f95e4b2c6d9a7a59355d32911f957a5b3"
+    );
+    assert_eq!(tx_details_1.received, Amount::from_sat(50000));
+    assert_eq!(tx_details_1.sent, Amount::from_sat(76000));
+    assert_eq!(tx_details_1.fee.unwrap(), Amount::from_sat(1000));
+    assert_eq!(tx_details_1.balance_delta, SignedAmount::from_sat(-26000));
 
-    // sort by chain position, unconfirmed then confirmed by descending block height
-    let sorted_txs: Vec<WalletTx> =
-        wallet.transactions_sort_by(|t1, t2| t2.chain_position.cmp(&t1.chain_position));
-    let conf_heights: Vec<Option<u32>> = sorted_txs
+    // Transaction id not part of the TxGraph
+    let txid_2 = Txid::from_raw_hash(Hash::all_zeros());
+    let tx_details_2_option = test_wallet.tx_details(txid_2);
+    assert!(tx_details_2_option.is_none());
+}
+
+#[test]
+fn test_tx_ordering_untouched_preserves_insertion_ordering() {
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let script_pubkey = wallet
+        .next_unused_address(KeychainKind::External)
+        .address
+        .script_pubkey();
+    let tx1 = Transaction {
+        input: vec![TxIn {
+            previous_output: OutPoint { txid, vout: 0 },
+            ..Default::default()
+        }],
+        output: vec![
+            TxOut {
+                value: Amount::from_sat(500),
+                script_pubkey: script_pubkey.clone(),
+            };
+            4
+        ],
+        ..new_tx(0)
+    };
+
+    insert_tx(&mut wallet, tx1);
+    let utxos = wallet
+        .list_unspent()
+        .map(|o| o.outpoint)
+        .take(2)
+        .collect::<Vec<_>>();
+
+    let mut builder = wallet.build_tx();
+    builder
+        .ordering(bdk_wallet::TxOrdering::Untouched)
+        .add_utxos(&utxos)
+        .unwrap()
+        .add_recipient(script_pubkey.clone(), Amount::from_sat(400))
+        .add_recipient(script_pubkey.clone(), Amount::from_sat(300))
+        .add_recipient(script_pubkey.clone(), Amount::from_sat(500));
+
+    let tx = builder.finish().unwrap().unsigned_tx;
+    let txins = tx
+        .input
         .iter()
-        .map(|tx| tx.chain_position.confirmation_height_upper_bound())
-        .collect();
-    assert_eq!([None, Some(2000), Some(1000)], conf_heights.as_slice());
+        .take(2) // First two UTxOs should be manually selected and sorted by insertion
+        .map(|txin| txin.previous_output)
+        .collect::<Vec<_>>();
+
+    assert!(txins == utxos);
+
+    let txouts = tx
+        .output
+        .iter()
+        .take(3) // Exclude possible change output
+        .map(|txout| txout.value.to_sat())
+        .collect::<Vec<_>>();
+
+    // Check vout is sorted by recipient insertion order
+    assert!(txouts == vec![400, 300, 500]);
 }
 
+// BnB coin selection should find a solution using the optional UTXO.
+// This demonstrates that `calculate_cs_result` correctly orders required UTXOs before selected
+// ones.
 #[test]
-fn test_tx_builder_is_send_safe() {
-    let (mut wallet, _txid) = get_funded_wallet_wpkh();
-    let _box: Box<dyn Send + Sync> = Box::new(wallet.build_tx());
+fn test_tx_ordering_untouched_preserves_insertion_ordering_bnb_success() {
+    // Create empty wallet
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(bdk_wallet::bitcoin::Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+
+    // Set up UTXOs with specific values so BnB can find an exact match (avoiding change).
+    // - outpoint_0 (required): 35,000 sat - not enough alone
+    // - outpoint_1 (optional): 25,200 sat
+    // - Target: 60,000 sat
+    // - Expected fee: 200 sat
+
+    let outpoint_0 = receive_output(
+        &mut wallet,
+        Amount::from_sat(35_000),
+        ReceiveTo::Mempool(50),
+    );
+    let outpoint_1 = receive_output(
+        &mut wallet,
+        Amount::from_sat(25_200),
+        ReceiveTo::Mempool(100),
+    );
+
+    let send_to = wallet.next_unused_address(KeychainKind::External).address;
+    let mut tx_builder = wallet.build_tx();
+    tx_builder
+        .add_utxo(outpoint_0)
+        .unwrap()
+        .add_recipient(send_to.script_pubkey(), Amount::from_sat(60_000))
+        .fee_rate(FeeRate::from_sat_per_vb(1).unwrap())
+        .ordering(bdk_wallet::TxOrdering::Untouched);
+    let psbt = tx_builder.finish().unwrap();
+
+    // Verify that both UTXOs are selected in the correct order:
+    // required (outpoint_0) should appear before optional (outpoint_1)
+    assert_eq!(
+        psbt.unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>(),
+        vec![outpoint_0, outpoint_1],
+        "UTXOs should be ordered with required first, then selected"
+    );
 }
 
 #[test]
-fn test_wallet_transactions_relevant() {
-    let (mut test_wallet, _txid) = get_funded_wallet_wpkh();
-    let relevant_tx_count_before = test_wallet.transactions().count();
-    let full_tx_count_before = test_wallet.tx_graph().full_txs().count();
-    let chain_tip = test_wallet.local_chain().tip().block_id();
-    let canonical_tx_count_before = test_wallet
-        .tx_graph()
-        .list_canonical_txs(
-            test_wallet.local_chain(),
-            chain_tip,
-            CanonicalizationParams::default(),
-        )
-        .count();
+fn test_apply_updates_from_multiple_backends() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let block = |height: u32, byte: u8| BlockId {
+        height,
+        hash: BlockHash::from_byte_array([byte; 32]),
+    };
+    let new_receive_tx = |wallet: &mut Wallet, lock_time: u32| {
+        let mut tx = new_tx(lock_time);
+        tx.output.push(TxOut {
+            script_pubkey: wallet
+                .next_unused_address(KeychainKind::External)
+                .script_pubkey(),
+            value: Amount::from_sat(10_000),
+        });
+        Arc::new(tx)
+    };
+    let tip_2000 = wallet.local_chain().get(2000).unwrap().block_id();
 
-    // add not relevant transaction to test wallet
-    let (other_external_desc, other_internal_desc) = get_test_tr_single_sig_xprv_and_change_desc();
-    let (other_wallet, other_txid) = get_funded_wallet(other_internal_desc, other_external_desc);
-    let test_wallet_update = Update {
-        tx_update: other_wallet.tx_graph().clone().into(),
+    // the first backend is ahead
+    let update = Update {
+        chain: CheckPoint::from_block_ids([tip_2000, block(3000, 1)]).ok(),
+        ..Default::default()
+    };
+    wallet.apply_update(update).unwrap();
+    assert_eq!(wallet.latest_checkpoint().block_id(), block(3000, 1));
+
+    // the second backend lags behind, its transactions are still merged
+    let tx = new_receive_tx(&mut wallet, 1);
+    let txid = tx.compute_txid();
+    let mut update = Update {
+        chain: CheckPoint::from_block_ids([tip_2000]).ok(),
+        ..Default::default()
+    };
+    update.tx_update.txs = vec![tx];
+    update.tx_update.anchors = [(
+        ConfirmationBlockTime {
+            block_id: tip_2000,
+            confirmation_time: 200,
+        },
+        txid,
+    )]
+    .into();
+    wallet.apply_update(update).unwrap();
+    assert_eq!(wallet.latest_checkpoint().block_id(), block(3000, 1));
+    assert!(wallet.get_tx(txid).unwrap().chain_position.is_confirmed());
+
+    // a lagging update made of the wallet's own checkpoints doesn't conflict
+    let update = Update {
+        chain: wallet.local_chain().get(2000),
+        ..Default::default()
+    };
+    wallet.apply_update(update).unwrap();
+    assert_eq!(wallet.latest_checkpoint().block_id(), block(3000, 1));
+
+    // a third backend is on a shorter fork from height 1000: its chain is ignored
+    let tx = new_receive_tx(&mut wallet, 2);
+    let txid = tx.compute_txid();
+    let mut update = Update {
+        chain: CheckPoint::from_block_ids([
+            wallet.local_chain().get(1000).unwrap().block_id(),
+            block(2000, 2),
+            block(2500, 2),
+        ])
+        .ok(),
+        ..Default::default()
+    };
+    update.tx_update.txs = vec![tx];
+    update.tx_update.anchors = [(
+        ConfirmationBlockTime {
+            block_id: block(2500, 2),
+            confirmation_time: 250,
+        },
+        txid,
+    )]
+    .into();
+    update.tx_update.seen_ats = [(txid, 260)].into();
+    wallet.apply_update(update).unwrap();
+    assert_eq!(wallet.latest_checkpoint().block_id(), block(3000, 1));
+    assert_eq!(wallet.local_chain().get(2000).unwrap().block_id(), tip_2000);
+    assert!(wallet.local_chain().get(2500).is_none());
+    assert!(!wallet.get_tx(txid).unwrap().chain_position.is_confirmed());
+}
+
+#[test]
+fn test_contains_key() {
+    let secp = bitcoin::secp256k1::Secp256k1::new();
+    let xpub = |tprv: &str| Xpub::from_priv(&secp, &Xpriv::from_str(tprv).unwrap());
+    let own = "tprv8ZgxMBicQKsPdy6LMhUtFHAgpocR8GC6QmwMSFpZs7h6Eziw3SpThFfczTDh5rW2krkqffa11UpX3XkeTTB2FvzZKWXqPY54Y6Rq4AQ5R8L";
+    let cosigner = xpub("tprv8ZgxMBicQKsPdDArR4xSAECuVxeX1jwwSXR4ApKbkYgZiziDc4LdBy2WvJeGDfUSE4UT4hHhbgEwbdq8ajjUHiKDegkwrNU6V55CxcxonVN");
+    let change_cosigner = Xpub::from_str("tpubDDks68wKK1xKaVVVbNmXUAx68K1K817M6KwjvjEyCrjdU7xMvjKnfYAtZjfZcrfPfGFzqmibuVqMzKJGbBnK7mo7WSJri8Y9QgM7aNQ3fCp").unwrap();
+    let desc = format!("wsh(multi(2,{own}/0/*,{cosigner}/0/*))");
+    let change_desc =
+        format!("wsh(multi(2,{own}/1/*,[12345678/48'/1'/0'/2']{change_cosigner}/1/*))");
+    let wallet = Wallet::create(desc, change_desc)
+        .network(Network::Regtest)
+        .create_wallet_no_persist()
+        .unwrap();
+
+    assert!(wallet.contains_xpub(&xpub(own)));
+    assert!(wallet.contains_xpub(&cosigner));
+    assert!(wallet.contains_xpub(&change_cosigner));
+    let child = cosigner
+        .derive_pub(&secp, &[ChildNumber::from_normal_idx(0).unwrap()])
+        .unwrap();
+    assert!(!wallet.contains_xpub(&child));
+
+    assert!(wallet.contains_fingerprint(xpub(own).fingerprint()));
+    assert!(wallet.contains_fingerprint(cosigner.fingerprint()));
+    // the key origin takes precedence over the fingerprint of the key
+    assert!(wallet.contains_fingerprint(Fingerprint::from_str("12345678").unwrap()));
+    assert!(!wallet.contains_fingerprint(change_cosigner.fingerprint()));
+    assert!(!wallet.contains_fingerprint(child.fingerprint()));
+}
+
+/// Answer a sync `request` from the transactions of a mock chain, where transactions without an
+/// anchor are in the mempool.
+///
+/// Like the electrum and esplora chain sources, expected txids missing from the history of a
+/// script pubkey are reported as evicted.
+fn mock_sync<I>(
+    mut request: SyncRequest<I>,
+    chain: &[(Arc<Transaction>, Option<ConfirmationBlockTime>)],
+) -> SyncResponse {
+    let start_time = request.start_time();
+    let mut response = SyncResponse::default();
+    let mut add_tx = |(tx, anchor): &(Arc<Transaction>, Option<ConfirmationBlockTime>)| {
+        let txid = tx.compute_txid();
+        response.tx_update.txs.push(tx.clone());
+        match anchor {
+            Some(anchor) => response.tx_update.anchors.insert((*anchor, txid)),
+            None => response.tx_update.seen_ats.insert((txid, start_time)),
+        };
+    };
+    for spk in request.iter_spks_with_expected_txids() {
+        let history = chain
+            .iter()
+            .filter(|(tx, _)| tx.output.iter().any(|txout| txout.script_pubkey == spk.spk))
+            .collect::<Vec<_>>();
+        for txid in &spk.expected_txids {
+            if !history.iter().any(|(tx, _)| tx.compute_txid() == *txid) {
+                response.tx_update.evicted_ats.insert((*txid, start_time));
+            }
+        }
+        history.into_iter().for_each(&mut add_tx);
+    }
+    for txid in request.iter_txids() {
+        chain
+            .iter()
+            .filter(|(tx, _)| tx.compute_txid() == txid)
+            .for_each(&mut add_tx);
+    }
+    for outpoint in request.iter_outpoints() {
+        chain
+            .iter()
+            .filter(|(tx, _)| {
+                tx.compute_txid() == outpoint.txid
+                    || tx.input.iter().any(|txin| txin.previous_output == outpoint)
+            })
+            .for_each(&mut add_tx);
+    }
+    response
+}
+
+#[test]
+fn test_multi_sync() {
+    let (tr_desc, tr_change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut wallets = [
+        get_funded_wallet_wpkh().0,
+        get_funded_wallet(tr_desc, tr_change_desc).0,
+        get_funded_wallet_single(get_test_pkh()).0,
+    ];
+    let tip = wallets[0].latest_checkpoint();
+    let block = BlockId {
+        height: tip.height() + 1,
+        hash: BlockHash::from_byte_array([1; 32]),
+    };
+    let anchor = ConfirmationBlockTime {
+        block_id: block,
+        confirmation_time: 1_700_000_000,
+    };
+
+    // only the second wallet receives a new transaction
+    let addr = wallets[1].peek_address(KeychainKind::External, 0).address;
+    let mut tx = new_tx(1);
+    tx.output.push(TxOut {
+        value: Amount::from_sat(10_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    let new_txid = tx.compute_txid();
+    let mut chain = vec![(Arc::new(tx), Some(anchor))];
+    for wallet in &wallets {
+        chain.extend(wallet.transactions().map(|tx| {
+            let anchor = match tx.chain_position {
+                ChainPosition::Confirmed { anchor, .. } => Some(anchor),
+                ChainPosition::Unconfirmed { .. } => None,
+            };
+            (tx.tx_node.tx.clone(), anchor)
+        }));
+    }
+
+    let mut multi = MultiSyncRequest::new();
+    for (id, wallet) in wallets.iter().enumerate() {
+        multi.add_wallet(id, wallet);
+    }
+    assert_eq!(multi.wallet_ids().copied().collect::<Vec<_>>(), [0, 1, 2]);
+    let request = multi.start_sync_at(1_700_000_000).build();
+    let revealed = wallets
+        .iter()
+        .map(|wallet| wallet.spk_index().revealed_spks(..).count())
+        .sum::<usize>();
+    assert_eq!(request.progress().total_spks(), revealed);
+    let mut response = mock_sync(request, &chain);
+    response.chain_update = Some(tip.insert(block));
+
+    let updates = multi.split_sync(response);
+    assert_eq!(updates.len(), 3);
+    for (id, update) in updates {
+        let wallet = &mut wallets[id];
+        let tx_count = wallet.transactions().count();
+        // each update only carries the transactions of its wallet
+        assert!(update
+            .tx_update
+            .txs
+            .iter()
+            .all(|tx| tx.compute_txid() == new_txid || wallet.get_tx(tx.compute_txid()).is_some()));
+        let has_new_tx = update
+            .tx_update
+            .txs
+            .iter()
+            .any(|tx| tx.compute_txid() == new_txid);
+        assert_eq!(has_new_tx, id == 1);
+        wallet.apply_update(update).unwrap();
+
+        // and the tip of every wallet advances
+        assert_eq!(wallet.latest_checkpoint().block_id(), block);
+        if id == 1 {
+            assert_eq!(wallet.transactions().count(), tx_count + 1);
+            assert_matches!(
+                wallet.get_tx(new_txid).unwrap().chain_position,
+                ChainPosition::Confirmed { .. }
+            );
+        } else {
+            assert_eq!(wallet.transactions().count(), tx_count);
+        }
+    }
+}
+
+#[test]
+fn test_multi_full_scan() {
+    let (tr_desc, tr_change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut wallets = [
+        get_funded_wallet_wpkh().0,
+        get_funded_wallet(tr_desc, tr_change_desc).0,
+        get_funded_wallet_single(get_test_pkh()).0,
+    ];
+    let mut multi = MultiSyncRequest::new();
+    for (id, wallet) in wallets.iter().enumerate() {
+        multi.add_wallet(id, wallet);
+    }
+    // every keychain of every wallet is scanned separately
+    let request = multi.start_full_scan_at(1_700_000_000).build();
+    assert_eq!(
+        request.keychains(),
+        [
+            (0, KeychainKind::External),
+            (0, KeychainKind::Internal),
+            (1, KeychainKind::External),
+            (1, KeychainKind::Internal),
+            (2, KeychainKind::External),
+        ]
+    );
+
+    // a transaction paying beyond the lookahead of the second wallet
+    let addr = wallets[1].peek_address(KeychainKind::External, 40).address;
+    let mut tx = new_tx(1);
+    tx.output.push(TxOut {
+        value: Amount::from_sat(10_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    let txid = tx.compute_txid();
+    let mut response = FullScanResponse::default();
+    response.tx_update.txs.push(Arc::new(tx));
+    response.tx_update.seen_ats.insert((txid, 1_700_000_000));
+    response
+        .last_active_indices
+        .insert((1, KeychainKind::External), 40);
+
+    let mut updates = multi.split_full_scan(response);
+    let update = updates.remove(&1).unwrap();
+    assert_eq!(update.tx_update.txs.len(), 1);
+    assert_eq!(
+        update.last_active_indices,
+        [(KeychainKind::External, 40)].into()
+    );
+    for update in updates.values() {
+        assert!(update.tx_update.txs.is_empty());
+        assert!(update.tx_update.seen_ats.is_empty());
+        assert!(update.last_active_indices.is_empty());
+    }
+    wallets[1].apply_update(update).unwrap();
+    assert_eq!(
+        wallets[1].derivation_index(KeychainKind::External),
+        Some(40)
+    );
+    assert!(wallets[1].get_tx(txid).is_some());
+}
+
+#[test]
+fn test_targeted_sync() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let block = BlockId {
+        height: 2100,
+        hash: BlockHash::from_byte_array([1; 32]),
+    };
+    insert_checkpoint(&mut wallet, block);
+    let anchor = ConfirmationBlockTime {
+        block_id: block,
+        confirmation_time: 210,
+    };
+
+    // broadcast a transaction
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(
+        Address::from_str("bcrt1qc6fweuf4xjvz4x3gx3t9e0fh4hvqyu2qw4wvxm")
+            .unwrap()
+            .assume_checked(),
+        Amount::from_sat(10_000),
+    );
+    let psbt = builder.finish().unwrap();
+    let outpoint = psbt.unsigned_tx.input[0].previous_output;
+    let sent_tx = Arc::new(psbt.extract_tx().unwrap());
+    let sent_txid = sent_tx.compute_txid();
+    wallet.apply_unconfirmed_txs([(sent_tx.clone(), 200)]);
+
+    // meanwhile an address of the wallet receives funds
+    let mut received_tx = new_tx(0);
+    received_tx.output.push(TxOut {
+        script_pubkey: wallet
+            .reveal_next_address(KeychainKind::External)
+            .script_pubkey(),
+        value: Amount::from_sat(20_000),
+    });
+    let received_tx = Arc::new(received_tx);
+    let chain = [(sent_tx, Some(anchor)), (received_tx.clone(), Some(anchor))];
+
+    let request = wallet.start_sync_for_txids([sent_txid]).build();
+    assert_eq!(request.progress().total_spks(), 0);
+    wallet.apply_update(mock_sync(request, &chain)).unwrap();
+    assert!(wallet
+        .get_tx(sent_txid)
+        .unwrap()
+        .chain_position
+        .is_confirmed());
+    assert!(wallet.get_tx(received_tx.compute_txid()).is_none());
+
+    let request = wallet.start_sync_for_outpoints([outpoint]).build();
+    assert_eq!(request.progress().total_spks(), 0);
+    wallet.apply_update(mock_sync(request, &chain)).unwrap();
+    assert!(wallet.get_tx(received_tx.compute_txid()).is_none());
+    assert_eq!(wallet.tx_graph().outspends(outpoint).len(), 1);
+
+    // a regular sync picks up the received transaction
+    let request = wallet.start_sync_with_revealed_spks().build();
+    wallet.apply_update(mock_sync(request, &chain)).unwrap();
+    assert!(wallet.get_tx(received_tx.compute_txid()).is_some());
+}
+
+#[test]
+fn test_sync_detects_replaced_tx() {
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let utxo = OutPoint { txid, vout: 0 };
+    let total = wallet.balance().total();
+    let drain_spk = wallet
+        .peek_address(KeychainKind::External, 5)
+        .script_pubkey();
+    let build_tx = |wallet: &mut Wallet, fee_rate: u64| {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_utxo(utxo)
+            .unwrap()
+            .manually_selected_only()
+            .drain_to(drain_spk.clone())
+            .fee_rate(FeeRate::from_sat_per_vb(fee_rate).unwrap());
+        Arc::new(builder.finish().unwrap().unsigned_tx)
+    };
+    let original = build_tx(&mut wallet, 1);
+    let original_txid = original.compute_txid();
+    let replacement = build_tx(&mut wallet, 5);
+    let replacement_txid = replacement.compute_txid();
+    let mut chain = wallet
+        .transactions()
+        .map(|wtx| match wtx.chain_position {
+            ChainPosition::Confirmed { anchor, .. } => (wtx.tx_node.tx, Some(anchor)),
+            ChainPosition::Unconfirmed { .. } => unreachable!("all txs are confirmed"),
+        })
+        .collect::<Vec<_>>();
+    wallet.apply_unconfirmed_txs([(original.clone(), 100)]);
+    wallet
+        .reveal_addresses_to(KeychainKind::External, 5)
+        .for_each(drop);
+
+    // the wallet expects the original transaction to still be in the mempool
+    let mut request = wallet.start_sync_with_revealed_spks_at(200).build();
+    let expected = request
+        .iter_spks_with_expected_txids()
+        .flat_map(|spk| spk.expected_txids)
+        .collect::<Vec<_>>();
+    assert!(expected.contains(&original_txid));
+
+    // the backend only knows the replacement
+    chain.push((replacement.clone(), None));
+    let request = wallet.start_sync_with_revealed_spks_at(200).build();
+    let response = mock_sync(request, &chain);
+    assert_eq!(
+        response.tx_update.evicted_ats,
+        [(original_txid, 200)].into()
+    );
+    wallet.apply_update(response).unwrap();
+
+    let canonical = wallet
+        .transactions()
+        .map(|wtx| wtx.tx_node.txid)
+        .collect::<Vec<_>>();
+    assert!(!canonical.contains(&original_txid));
+    assert!(canonical.contains(&replacement_txid));
+    assert_eq!(
+        wallet.balance().total(),
+        total - wallet.calculate_fee(&replacement).unwrap()
+    );
+}
+
+/// Create a block on top of `prev_blockhash`, with a header meeting its target if `valid_pow`.
+fn mine_block(prev_blockhash: BlockHash, bits: u32, valid_pow: bool) -> Block {
+    let mut coinbase = new_tx(0);
+    coinbase.output.push(TxOut {
+        value: Amount::from_sat(50_000),
+        script_pubkey: ScriptBuf::new_op_return([0; 4]),
+    });
+    let mut block = Block {
+        header: block::Header {
+            version: block::Version::TWO,
+            prev_blockhash,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 1_700_000_000,
+            bits: CompactTarget::from_consensus(bits),
+            nonce: 0,
+        },
+        txdata: vec![coinbase],
+    };
+    block.header.merkle_root = block.compute_merkle_root().unwrap();
+    while block.header.validate_pow(block.header.target()).is_ok() != valid_pow {
+        block.header.nonce += 1;
+    }
+    block
+}
+
+#[test]
+fn test_apply_block_validate_pow() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let tip = wallet.latest_checkpoint().block_id();
+    let options = ApplyOptions {
+        validate_pow: true,
         ..Default::default()
     };
-    test_wallet.apply_update(test_wallet_update).unwrap();
+    let apply = |wallet: &mut Wallet, block: &Block| {
+        wallet.apply_block_connected_to_with_options(block, tip.height + 1, tip, &options)
+    };
 
-    // verify transaction from other wallet was added but is not in relevant transactions list.
-    let relevant_tx_count_after = test_wallet.transactions().count();
-    let full_tx_count_after = test_wallet.tx_graph().full_txs().count();
-    let canonical_tx_count_after = test_wallet
-        .tx_graph()
-        .list_canonical_txs(
-            test_wallet.local_chain(),
-            chain_tip,
-            CanonicalizationParams::default(),
-        )
-        .count();
+    let block = mine_block(tip.hash, 0x207fffff, false);
+    assert_matches!(
+        apply(&mut wallet, &block),
+        Err(ApplyError::Validation(ValidationError::InvalidProofOfWork(
+            _
+        )))
+    );
+    assert_eq!(wallet.latest_checkpoint().block_id(), tip);
 
-    assert_eq!(relevant_tx_count_before, relevant_tx_count_after);
-    assert!(!test_wallet
-        .transactions()
-        .any(|wallet_tx| wallet_tx.tx_node.txid == other_txid));
-    assert!(test_wallet
-        .tx_graph()
-        .list_canonical_txs(
-            test_wallet.local_chain(),
-            chain_tip,
-            CanonicalizationParams::default()
+    // the target can't be easier than the network's limit
+    let block = mine_block(tip.hash, 0x2100ffff, true);
+    assert_matches!(
+        apply(&mut wallet, &block),
+        Err(ApplyError::Validation(ValidationError::TargetAboveLimit(_)))
+    );
+
+    let mut block = mine_block(tip.hash, 0x207fffff, true);
+    block.txdata[0].output[0].value = Amount::from_sat(100_000);
+    assert_matches!(
+        apply(&mut wallet, &block),
+        Err(ApplyError::Validation(ValidationError::InvalidMerkleRoot(
+            _
+        )))
+    );
+    assert_eq!(wallet.latest_checkpoint().block_id(), tip);
+
+    let block = mine_block(tip.hash, 0x207fffff, true);
+    apply(&mut wallet, &block).unwrap();
+    assert_eq!(wallet.latest_checkpoint().hash(), block.block_hash());
+
+    // without validation, the block is trusted
+    let tip = wallet.latest_checkpoint().block_id();
+    let block = mine_block(tip.hash, 0x207fffff, false);
+    wallet
+        .apply_block_connected_to_with_options(
+            &block,
+            tip.height + 1,
+            tip,
+            &ApplyOptions::default(),
         )
-        .any(|wallet_tx| wallet_tx.tx_node.txid == other_txid));
-    assert!(full_tx_count_before < full_tx_count_after);
-    assert!(canonical_tx_count_before < canonical_tx_count_after);
+        .unwrap();
+    assert_eq!(wallet.latest_checkpoint().hash(), block.block_hash());
 }
 
 #[test]
-fn test_tx_details_method() {
-    let (test_wallet, txid_1) = get_funded_wallet_wpkh();
-    let tx_details_1_option = test_wallet.tx_details(txid_1);
+fn test_apply_filtered_block() {
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let tip = wallet.latest_checkpoint().block_id();
+    let mut block = mine_block(tip.hash, 0x207fffff, true);
+
+    // transactions of other wallets
+    for i in 1..=100 {
+        let mut tx = new_tx(i);
+        tx.output.push(TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: ScriptBuf::new_op_return(i.to_le_bytes()),
+        });
+        block.txdata.push(tx);
+    }
+    // a payment to the lookahead window of the wallet
+    let addr = wallet.peek_address(KeychainKind::External, 3).address;
+    let mut receive = new_tx(101);
+    receive.output.push(TxOut {
+        value: Amount::from_sat(20_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    // a spend of the funding output, and a spend of the payment later in the block
+    let mut spend = new_tx(102);
+    spend.input.push(TxIn {
+        previous_output: OutPoint::new(txid, 0),
+        ..Default::default()
+    });
+    spend.output.push(TxOut {
+        value: Amount::from_sat(49_000),
+        script_pubkey: ScriptBuf::new_op_return([1; 4]),
+    });
+    let mut spend_receive = new_tx(103);
+    spend_receive.input.push(TxIn {
+        previous_output: OutPoint::new(receive.compute_txid(), 0),
+        ..Default::default()
+    });
+    spend_receive.output.push(TxOut {
+        value: Amount::from_sat(19_000),
+        script_pubkey: ScriptBuf::new_op_return([2; 4]),
+    });
+    block
+        .txdata
+        .extend([receive.clone(), spend.clone(), spend_receive.clone()]);
 
-    assert!(tx_details_1_option.is_some());
-    let tx_details_1 = tx_details_1_option.unwrap();
+    let filtered = wallet.spk_filter().filter_block(&block, tip.height + 1);
+    assert_eq!(filtered.txs, vec![receive, spend, spend_receive]);
+    assert_eq!(filtered.connected_to(), tip);
+
+    // applying the filtered block has the same effect as applying the whole block
+    let (mut full_wallet, _) = get_funded_wallet_wpkh();
+    full_wallet.apply_block(&block, tip.height + 1).unwrap();
+    wallet.apply_filtered_block(&filtered).unwrap();
 
+    assert_eq!(wallet.latest_checkpoint().block_id(), filtered.block_id());
+    assert_eq!(wallet.balance(), full_wallet.balance());
     assert_eq!(
-        tx_details_1.txid.to_string(),
-        "f2a03cdfe1bb6a295b0a4bb4385ca42f95e4b2c6d9a7a59355d32911f957a5b3"
+        wallet.derivation_index(KeychainKind::External),
+        full_wallet.derivation_index(KeychainKind::External)
     );
-    assert_eq!(tx_details_1.received, Amount::from_sat(50000));
-    assert_eq!(tx_details_1.sent, Amount::from_sat(76000));
-    assert_eq!(tx_details_1.fee.unwrap(), Amount::from_sat(1000));
-    assert_eq!(tx_details_1.balance_delta, SignedAmount::from_sat(-26000));
+    let mut txs = wallet
+        .transactions()
+        .map(|tx| (tx.tx_node.txid, tx.chain_position))
+        .collect::<Vec<_>>();
+    let mut full_txs = full_wallet
+        .transactions()
+        .map(|tx| (tx.tx_node.txid, tx.chain_position))
+        .collect::<Vec<_>>();
+    txs.sort();
+    full_txs.sort();
+    assert_eq!(txs, full_txs);
+    assert_eq!(txs.len(), 5);
+}
 
-    // Transaction id not part of the TxGraph
-    let txid_2 = Txid::from_raw_hash(Hash::all_zeros());
-    let tx_details_2_option = test_wallet.tx_details(txid_2);
-    assert!(tx_details_2_option.is_none());
+/// Create a block on top of `prev_blockhash` with the given transactions, `time` makes it unique.
+fn block_with_txs(prev_blockhash: BlockHash, time: u32, txdata: Vec<Transaction>) -> Block {
+    Block {
+        header: block::Header {
+            version: block::Version::TWO,
+            prev_blockhash,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: time,
+        },
+        txdata,
+    }
 }
 
 #[test]
-fn test_tx_ordering_untouched_preserves_insertion_ordering() {
-    let (mut wallet, txid) = get_funded_wallet_wpkh();
-    let script_pubkey = wallet
-        .next_unused_address(KeychainKind::External)
-        .address
-        .script_pubkey();
-    let tx1 = Transaction {
-        input: vec![TxIn {
-            previous_output: OutPoint { txid, vout: 0 },
-            ..Default::default()
-        }],
-        output: vec![
-            TxOut {
-                value: Amount::from_sat(500),
-                script_pubkey: script_pubkey.clone(),
-            };
-            4
-        ],
-        ..new_tx(0)
+fn test_preview_block() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let (mut wallet, _, update) = new_wallet_and_funding_update(desc, Some(change_desc));
+    let genesis_hash = wallet.local_chain().genesis_hash();
+    let funding_tx = (*update.tx_update.txs[0]).clone();
+    let funding_txid = funding_tx.compute_txid();
+    wallet.apply_unconfirmed_txs([(funding_tx.clone(), 100)]);
+    let balance = wallet.balance();
+    let tip = wallet.latest_checkpoint().block_id();
+    let staged = wallet.staged().cloned();
+
+    let block1 = block_with_txs(genesis_hash, 1000, vec![funding_tx]);
+    let delta = wallet.preview_block(&block1, 1).unwrap();
+    assert_eq!(delta.txids, vec![funding_txid]);
+    assert_eq!(delta.before, balance);
+    assert_eq!(delta.after.confirmed, balance.untrusted_pending);
+    assert_eq!(
+        delta.confirmed(),
+        balance.untrusted_pending.to_signed().unwrap()
+    );
+    assert_eq!(delta.untrusted_pending(), -delta.confirmed());
+    assert_eq!(delta.total(), bitcoin::SignedAmount::ZERO);
+
+    // the wallet is unchanged
+    assert_eq!(wallet.latest_checkpoint().block_id(), tip);
+    assert_eq!(wallet.balance(), balance);
+    assert_eq!(wallet.staged().cloned(), staged);
+
+    // blocks that don't connect are rejected
+    let orphan = block_with_txs(block1.block_hash(), 2000, vec![]);
+    assert!(wallet.preview_block(&orphan, 5).is_err());
+}
+
+#[test]
+fn test_block_filter_matches() {
+    use bitcoin::bip158::BlockFilter;
+
+    fn block_with_filter(
+        prev_blockhash: BlockHash,
+        time: u32,
+        outputs: Vec<ScriptBuf>,
+    ) -> (Block, BlockFilter) {
+        let txdata = outputs
+            .into_iter()
+            .map(|script_pubkey| Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: OutPoint::new(Hash::hash(&time.to_le_bytes()), 0),
+                    ..Default::default()
+                }],
+                output: vec![TxOut {
+                    value: Amount::from_sat(10_000),
+                    script_pubkey,
+                }],
+            })
+            .collect();
+        let block = block_with_txs(prev_blockhash, time, txdata);
+        let filter = BlockFilter::new_script_filter(&block, |_| Ok(ScriptBuf::new())).unwrap();
+        (block, filter)
+    }
+
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let (mut wallet, _, _) = new_wallet_and_funding_update(desc, Some(change_desc));
+    let genesis_hash = wallet.local_chain().genesis_hash();
+    let revealed = wallet.reveal_next_address(KeychainKind::External).index;
+    let spk_at = |index| {
+        wallet
+            .peek_address(KeychainKind::External, revealed + index)
+            .script_pubkey()
     };
+    let (spk_20, spk_40) = (spk_at(20), spk_at(40));
 
-    insert_tx(&mut wallet, tx1);
-    let utxos = wallet
-        .list_unspent()
-        .map(|o| o.outpoint)
-        .take(2)
-        .collect::<Vec<_>>();
+    // a payment to a spk in the lookahead window matches
+    let (block1, filter1) = block_with_filter(genesis_hash, 1000, vec![spk_20]);
+    assert!(wallet
+        .block_filter_matches(&filter1, &block1.block_hash())
+        .unwrap());
+
+    // a payment past the lookahead window only matches once the window has moved
+    let (block2, filter2) = block_with_filter(block1.block_hash(), 2000, vec![spk_40]);
+    assert!(!wallet
+        .block_filter_matches(&filter2, &block2.block_hash())
+        .unwrap());
+    wallet.apply_block(&block1, 1).unwrap();
+    assert!(wallet
+        .block_filter_matches(&filter2, &block2.block_hash())
+        .unwrap());
+
+    // blocks without wallet outputs don't match
+    let (block3, filter3) = block_with_filter(block2.block_hash(), 3000, vec![]);
+    assert!(!wallet
+        .block_filter_matches(&filter3, &block3.block_hash())
+        .unwrap());
+}
 
-    let mut builder = wallet.build_tx();
-    builder
-        .ordering(bdk_wallet::TxOrdering::Untouched)
-        .add_utxos(&utxos)
-        .unwrap()
-        .add_recipient(script_pubkey.clone(), Amount::from_sat(400))
-        .add_recipient(script_pubkey.clone(), Amount::from_sat(300))
-        .add_recipient(script_pubkey.clone(), Amount::from_sat(500));
+#[test]
+fn test_apply_update_header_provider() {
+    struct HeaderChain(std::collections::BTreeMap<u32, BlockHash>);
 
-    let tx = builder.finish().unwrap().unsigned_tx;
-    let txins = tx
-        .input
-        .iter()
-        .take(2) // First two UTxOs should be manually selected and sorted by insertion
-        .map(|txin| txin.previous_output)
-        .collect::<Vec<_>>();
+    impl HeaderProvider for HeaderChain {
+        fn block_hash(&self, height: u32) -> Result<Option<BlockHash>, HeaderProviderError> {
+            Ok(self.0.get(&height).copied())
+        }
+    }
 
-    assert!(txins == utxos);
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let tip = wallet.latest_checkpoint().block_id();
+    let block = BlockId {
+        height: 2100,
+        hash: BlockHash::from_byte_array([1; 32]),
+    };
+    let mut tx = new_tx(0);
+    tx.output.push(TxOut {
+        script_pubkey: wallet
+            .next_unused_address(KeychainKind::External)
+            .script_pubkey(),
+        value: Amount::from_sat(10_000),
+    });
+    let txid = tx.compute_txid();
+    let mut update = Update {
+        chain: CheckPoint::from_block_ids([tip, block]).ok(),
+        ..Default::default()
+    };
+    update.tx_update.txs = vec![Arc::new(tx)];
+    update.tx_update.anchors = [(
+        ConfirmationBlockTime {
+            block_id: block,
+            confirmation_time: 210,
+        },
+        txid,
+    )]
+    .into();
 
-    let txouts = tx
-        .output
-        .iter()
-        .take(3) // Exclude possible change output
-        .map(|txout| txout.value.to_sat())
-        .collect::<Vec<_>>();
+    // the blocks of the wallet's chain aren't looked up
+    let mut headers = HeaderChain([(block.height, BlockHash::from_byte_array([2; 32]))].into());
+    assert_matches!(
+        wallet.apply_update_with_options(update.clone(), &ApplyOptions {
+            header_provider: Some(&headers),
+            ..Default::default()
+        }),
+        Err(ApplyError::Validation(ValidationError::UnknownBlock(unknown))) if unknown == block
+    );
+    assert_eq!(wallet.latest_checkpoint().block_id(), tip);
+    assert!(wallet.get_tx(txid).is_none());
 
-    // Check vout is sorted by recipient insertion order
-    assert!(txouts == vec![400, 300, 500]);
+    headers.0.insert(block.height, block.hash);
+    wallet
+        .apply_update_with_options(
+            update,
+            &ApplyOptions {
+                header_provider: Some(&headers),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(wallet.latest_checkpoint().block_id(), block);
+    assert!(wallet.get_tx(txid).unwrap().chain_position.is_confirmed());
 }
 
-// BnB coin selection should find a solution using the optional UTXO.
-// This demonstrates that `calculate_cs_result` correctly orders required UTXOs before selected
-// ones.
 #[test]
-fn test_tx_ordering_untouched_preserves_insertion_ordering_bnb_success() {
-    // Create empty wallet
+fn test_apply_scantxoutset() {
     let (desc, change_desc) = get_test_wpkh_and_change_desc();
     let mut wallet = Wallet::create(desc, change_desc)
-        .network(bdk_wallet::bitcoin::Network::Regtest)
+        .network(Network::Regtest)
         .create_wallet_no_persist()
         .unwrap();
+    let spk = |keychain, index| wallet.peek_address(keychain, index).script_pubkey();
+    let new_tx = |lock_time, script_pubkey, sats| {
+        let mut tx = new_tx(lock_time);
+        tx.output.push(TxOut {
+            script_pubkey,
+            value: Amount::from_sat(sats),
+        });
+        tx
+    };
+    // the external output is beyond the lookahead of the wallet
+    let external_tx = new_tx(1, spk(KeychainKind::External, 40), 150_000);
+    let internal_tx = new_tx(2, spk(KeychainKind::Internal, 2), 20_000);
+    let foreign_tx = new_tx(3, ScriptBuf::new_op_return([1; 4]), 5_000);
+    let unspent = |tx: &Transaction, height: u32, block: u8| {
+        format!(
+            r#"{{
+                "txid": "{}",
+                "vout": 0,
+                "scriptPubKey": "{}",
+                "desc": "addr(...)#00000000",
+                "amount": {},
+                "coinbase": false,
+                "height": {height},
+                "blockhash": "{}",
+                "confirmations": {}
+            }}"#,
+            tx.compute_txid(),
+            tx.output[0].script_pubkey.to_hex_string(),
+            tx.output[0].value.to_btc(),
+            BlockHash::from_byte_array([block; 32]),
+            1_001 - height,
+        )
+    };
+    let json = format!(
+        r#"{{
+            "success": true,
+            "txouts": 1234,
+            "height": 1000,
+            "bestblock": "{}",
+            "unspents": [{}, {}, {}],
+            "total_amount": 0.00175
+        }}"#,
+        BlockHash::from_byte_array([3; 32]),
+        unspent(&external_tx, 100, 1),
+        unspent(&internal_tx, 200, 2),
+        unspent(&foreign_tx, 200, 2),
+    );
+    let result: ScanTxOutSetResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(result.unspents.len(), 3);
+    assert_eq!(result.unspents[1].amount, Amount::from_sat(20_000));
 
-    // Set up UTXOs with specific values so BnB can find an exact match (avoiding change).
-    // - outpoint_0 (required): 35,000 sat - not enough alone
-    // - outpoint_1 (optional): 25,200 sat
-    // - Target: 60,000 sat
-    // - Expected fee: 200 sat
+    // the transactions creating the outputs of the wallet are needed
+    assert_matches!(
+        wallet.apply_scantxoutset(&result, 1_000, [(external_tx.clone(), 1_000)]),
+        Err(ScanTxOutSetError::MissingTx(txid)) if txid == internal_tx.compute_txid()
+    );
 
-    let outpoint_0 = receive_output(
-        &mut wallet,
-        Amount::from_sat(35_000),
-        ReceiveTo::Mempool(50),
+    wallet
+        .apply_scantxoutset(&result, 1_000, [(external_tx, 1_000), (internal_tx, 2_000)])
+        .unwrap();
+    assert_eq!(
+        wallet.balance().confirmed,
+        Amount::from_sat(150_000 + 20_000)
     );
-    let outpoint_1 = receive_output(
-        &mut wallet,
-        Amount::from_sat(25_200),
-        ReceiveTo::Mempool(100),
+    assert_eq!(wallet.latest_checkpoint().height(), 1000);
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(40));
+    assert_eq!(wallet.derivation_index(KeychainKind::Internal), Some(2));
+    assert!(wallet.get_tx(foreign_tx.compute_txid()).is_none());
+}
+
+#[test]
+fn test_update_builder() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.peek_address(KeychainKind::External, 5).address;
+    let block = BlockId {
+        height: 2_001,
+        hash: BlockHash::from_byte_array([1; 32]),
+    };
+    let anchor = ConfirmationBlockTime {
+        block_id: block,
+        confirmation_time: 1_700_000_000,
+    };
+
+    let mut confirmed = new_tx(1);
+    confirmed.output.push(TxOut {
+        value: Amount::from_sat(10_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    let mut unconfirmed = new_tx(2);
+    unconfirmed.output.push(TxOut {
+        value: Amount::from_sat(5_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    let mut evicted = new_tx(3);
+    evicted.output.push(TxOut {
+        value: Amount::from_sat(1_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    let evicted_txid = evicted.compute_txid();
+    wallet.apply_unconfirmed_txs([(evicted, 100)]);
+
+    // The anchor must point to a block of the update's chain.
+    let mut builder = wallet.update_builder();
+    builder.add_tx(confirmed.clone(), anchor);
+    assert_eq!(
+        builder.build().unwrap_err(),
+        UpdateBuilderError::AnchorNotInChain {
+            txid: confirmed.compute_txid(),
+            block_id: block,
+        }
     );
 
-    let send_to = wallet.next_unused_address(KeychainKind::External).address;
-    let mut tx_builder = wallet.build_tx();
-    tx_builder
-        .add_utxo(outpoint_0)
+    // The genesis block can't be replaced.
+    let genesis = wallet.local_chain().genesis_hash();
+    let other_genesis = BlockHash::from_byte_array([2; 32]);
+    let mut builder = wallet.update_builder();
+    assert_eq!(
+        builder
+            .extend_chain([
+                block,
+                BlockId {
+                    height: 0,
+                    hash: other_genesis,
+                },
+            ])
+            .unwrap_err(),
+        UpdateBuilderError::GenesisMismatch {
+            genesis,
+            got: other_genesis,
+        }
+    );
+    assert!(builder.build().unwrap().chain.is_none());
+
+    let mut builder = wallet.update_builder();
+    builder
+        .extend_chain([block])
         .unwrap()
-        .add_recipient(send_to.script_pubkey(), Amount::from_sat(60_000))
-        .fee_rate(FeeRate::from_sat_per_vb(1).unwrap())
-        .ordering(bdk_wallet::TxOrdering::Untouched);
-    let psbt = tx_builder.finish().unwrap();
+        .add_tx(confirmed.clone(), anchor)
+        .add_unconfirmed(unconfirmed.clone(), 200)
+        .add_evicted(evicted_txid, 300)
+        .set_last_active_index(KeychainKind::External, 5);
+    wallet.apply_update(builder.build().unwrap()).unwrap();
+
+    assert_eq!(wallet.latest_checkpoint().block_id(), block);
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(5));
+    assert_matches!(
+        wallet.get_tx(confirmed.compute_txid()).unwrap().chain_position,
+        ChainPosition::Confirmed { anchor: a, .. } if a == anchor
+    );
+    assert_matches!(
+        wallet
+            .get_tx(unconfirmed.compute_txid())
+            .unwrap()
+            .chain_position,
+        ChainPosition::Unconfirmed { .. }
+    );
+    assert!(wallet.get_tx(evicted_txid).is_none());
+
+    // An anchor may point to a block already in the wallet's chain, and an update without new
+    // blocks leaves the chain untouched.
+    let mut other = new_tx(4);
+    other.output.push(TxOut {
+        value: Amount::from_sat(2_000),
+        script_pubkey: addr.script_pubkey(),
+    });
+    let mut builder = wallet.update_builder();
+    builder.add_tx(other, anchor);
+    let update = builder.build().unwrap();
+    assert!(update.chain.is_none());
+}
 
-    // Verify that both UTXOs are selected in the correct order:
-    // required (outpoint_0) should appear before optional (outpoint_1)
+#[test]
+fn test_canonicalization_policy() {
+    use bdk_wallet::CanonicalizationPolicy;
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(FeeRate::from_sat_per_vb_u32(2));
+    let tx = builder.finish().unwrap().extract_tx().unwrap();
+    let original = tx.compute_txid();
+    wallet.apply_unconfirmed_txs([(tx, 100)]);
+    // a replacement which loses a tie to the original by txid, as outputs are shuffled
+    let (tx, replacement) = (5..)
+        .find_map(|fee_rate| {
+            let mut builder = wallet.build_fee_bump(original).unwrap();
+            builder.fee_rate(FeeRate::from_sat_per_vb_u32(fee_rate));
+            let tx = builder.finish().unwrap().extract_tx().unwrap();
+            let txid = tx.compute_txid();
+            (txid < original).then_some((tx, txid))
+        })
+        .unwrap();
+    wallet.apply_unconfirmed_txs([(tx, 150)]);
+    assert!(wallet.get_tx(replacement).is_some());
+
+    // both are last seen in the same mempool sync, the greatest txid wins by default
+    insert_seen_at(&mut wallet, original, 200);
+    insert_seen_at(&mut wallet, replacement, 200);
     assert_eq!(
-        psbt.unsigned_tx
-            .input
-            .iter()
-            .map(|txin| txin.previous_output)
-            .collect::<Vec<_>>(),
-        vec![outpoint_0, outpoint_1],
-        "UTXOs should be ordered with required first, then selected"
+        wallet.canonicalization_policy(),
+        CanonicalizationPolicy::TxidOrder
     );
+    assert!(wallet.get_tx(original).is_some());
+    assert!(wallet.get_tx(replacement).is_none());
+    let balance = wallet.balance();
+    let fee = |txid| {
+        let tx = wallet.tx_graph().get_tx(txid).unwrap();
+        wallet.calculate_fee(&tx).unwrap()
+    };
+    let fee_increase = fee(replacement) - fee(original);
+
+    for policy in [
+        CanonicalizationPolicy::HigherFeeWins,
+        CanonicalizationPolicy::FirstSeenLatestWins,
+    ] {
+        wallet.set_canonicalization_policy(policy);
+        assert!(wallet.get_tx(original).is_none(), "{policy:?}");
+        assert!(wallet.get_tx(replacement).is_some(), "{policy:?}");
+        assert!(wallet
+            .list_unspent()
+            .all(|utxo| utxo.outpoint.txid != original));
+        assert_eq!(
+            wallet.balance().total(),
+            balance.total() - fee_increase,
+            "{policy:?}"
+        );
+    }
+
+    // the policy doesn't apply once the original is confirmed
+    let anchor = ConfirmationBlockTime {
+        block_id: wallet.latest_checkpoint().block_id(),
+        confirmation_time: 1_000,
+    };
+    insert_anchor(&mut wallet, original, anchor);
+    assert!(wallet.get_tx(original).is_some());
+    assert!(wallet.get_tx(replacement).is_none());
 }
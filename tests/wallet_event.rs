@@ -1,3 +1,4 @@
+use bdk_chain::local_chain::ApplyHeaderError;
 use bdk_chain::{BlockId, CheckPoint, ConfirmationBlockTime};
 use bdk_wallet::test_utils::{get_test_wpkh_and_change_desc, new_wallet_and_funding_update};
 use bdk_wallet::Update;
@@ -69,12 +70,15 @@ fn test_tx_unconfirmed_event() {
     let old_tip1 = wallet.local_chain().tip().block_id();
     let events = wallet.apply_update_events(reorg_update).unwrap();
     let new_tip1 = wallet.local_chain().tip().block_id();
-    assert_eq!(events.len(), 2);
+    assert_eq!(events.len(), 3);
     assert!(
         matches!(events[0], WalletEvent::ChainTipChanged { old_tip, new_tip } if old_tip == old_tip1 && new_tip == new_tip1)
     );
     assert!(
-        matches!(&events[1], WalletEvent::TxUnconfirmed {tx, old_block_time, ..} if tx.output.len() == 2 && old_block_time.is_some())
+        matches!(events[1], WalletEvent::ReorgDetected { depth: 1, old_tip, new_tip } if old_tip == old_tip1 && new_tip == new_tip1)
+    );
+    assert!(
+        matches!(&events[2], WalletEvent::TxUnconfirmed {tx, old_block_time, ..} if tx.output.len() == 2 && old_block_time.is_some())
     );
 }
 
@@ -259,12 +263,15 @@ fn test_tx_confirmed_new_block_event() {
     update.tx_update.anchors = [(reorg_anchor, new_txid)].into();
 
     let events = wallet.apply_update_events(update).unwrap();
-    assert_eq!(events.len(), 2);
+    assert_eq!(events.len(), 3);
     assert!(
         matches!(events[0], WalletEvent::ChainTipChanged { old_tip, new_tip } if old_tip == new_block && new_tip == reorg_block)
     );
     assert!(
-        matches!(events[1], WalletEvent::TxConfirmed { txid, block_time, old_block_time, .. } if txid == new_txid && block_time.block_id == reorg_block && old_block_time.is_some())
+        matches!(events[1], WalletEvent::ReorgDetected { depth: 1, old_tip, new_tip } if old_tip == new_block && new_tip == reorg_block)
+    );
+    assert!(
+        matches!(events[2], WalletEvent::TxConfirmed { txid, block_time, old_block_time, .. } if txid == new_txid && block_time.block_id == reorg_block && old_block_time.is_some())
     );
 }
 
@@ -422,7 +429,7 @@ fn test_apply_block_tx_unconfirmed_event() {
     // apply reorg of spending block without previously confirmed tx
     let reorg_block2 = test_block(block1.block_hash(), 2100, vec![]);
     let events = wallet.apply_block_events(&reorg_block2, 2).unwrap();
-    assert_eq!(events.len(), 2);
+    assert_eq!(events.len(), 3);
     assert!(matches!(
         events[0],
         WalletEvent::ChainTipChanged { old_tip, new_tip }
@@ -430,7 +437,14 @@ fn test_apply_block_tx_unconfirmed_event() {
         && new_tip == (2, reorg_block2.block_hash()).into()
     ));
     assert!(matches!(
-        &events[1],
+        (&events[0], &events[1]),
+        (
+            WalletEvent::ChainTipChanged { old_tip, new_tip },
+            WalletEvent::ReorgDetected { depth: 1, old_tip: reorg_old_tip, new_tip: reorg_new_tip },
+        ) if old_tip == reorg_old_tip && new_tip == reorg_new_tip
+    ));
+    assert!(matches!(
+        &events[2],
         WalletEvent::TxUnconfirmed {tx, old_block_time, ..}
         if tx.output.len() == 2
         && old_block_time.is_some()
@@ -477,7 +491,7 @@ fn test_apply_block_tx_confirmed_new_block_event() {
     // apply reorg of spending block including the original spending tx
     let reorg_block2 = test_block(block1.block_hash(), 2100, vec![spending_tx.clone()]);
     let events = wallet.apply_block_events(&reorg_block2, 2).unwrap();
-    assert_eq!(events.len(), 2);
+    assert_eq!(events.len(), 3);
     assert!(matches!(
         events[0],
         WalletEvent::ChainTipChanged { old_tip, new_tip }
@@ -485,7 +499,14 @@ fn test_apply_block_tx_confirmed_new_block_event() {
         && new_tip == (2, reorg_block2.block_hash()).into()
     ));
     assert!(matches!(
-        events[1],
+        (&events[0], &events[1]),
+        (
+            WalletEvent::ChainTipChanged { old_tip, new_tip },
+            WalletEvent::ReorgDetected { depth: 1, old_tip: reorg_old_tip, new_tip: reorg_new_tip },
+        ) if old_tip == reorg_old_tip && new_tip == reorg_new_tip
+    ));
+    assert!(matches!(
+        events[2],
         WalletEvent::TxConfirmed { txid, block_time, old_block_time, .. }
         if txid == spending_tx.compute_txid()
         && block_time.block_id == (2, reorg_block2.block_hash()).into()
@@ -522,3 +543,158 @@ fn test_apply_evicted_txs_tx_dropped_event() {
         if *txid == tx.compute_txid() && *tx == unconfirmed_tx
     ));
 }
+
+#[test]
+fn test_apply_block_two_block_reorg_events() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let (mut wallet, _, update) = new_wallet_and_funding_update(desc, Some(change_desc));
+    let genesis_hash = wallet.local_chain().genesis_hash();
+    let funding_tx = (*update.tx_update.txs[0]).clone();
+    let spending_tx = (*update.tx_update.txs[1]).clone();
+
+    let block1 = test_block(genesis_hash, 1000, vec![funding_tx]);
+    let block2 = test_block(block1.block_hash(), 2000, vec![spending_tx.clone()]);
+    let block3 = test_block(block2.block_hash(), 3000, vec![]);
+    for (height, block) in [(1, &block1), (2, &block2), (3, &block3)] {
+        wallet.apply_block_events(block, height).unwrap();
+    }
+
+    // the last two blocks are replaced, the spending tx isn't part of the new blocks yet
+    let reorg_block2 = test_block(block1.block_hash(), 2100, vec![]);
+    let events = wallet.apply_block_events(&reorg_block2, 2).unwrap();
+    let old_tip = BlockId::from((3, block3.block_hash()));
+    let new_tip = BlockId::from((2, reorg_block2.block_hash()));
+    assert_eq!(
+        events,
+        vec![
+            WalletEvent::ChainTipChanged { old_tip, new_tip },
+            WalletEvent::ReorgDetected {
+                depth: 2,
+                old_tip,
+                new_tip,
+            },
+            WalletEvent::TxUnconfirmed {
+                txid: spending_tx.compute_txid(),
+                tx: Arc::new(spending_tx.clone()),
+                old_block_time: Some(ConfirmationBlockTime {
+                    block_id: (2, block2.block_hash()).into(),
+                    confirmation_time: 2000,
+                }),
+            },
+        ]
+    );
+
+    // the new chain grows past the old tip and confirms the spending tx again
+    let reorg_block3 = test_block(reorg_block2.block_hash(), 3100, vec![spending_tx.clone()]);
+    let events = wallet.apply_block_events(&reorg_block3, 3).unwrap();
+    let reorg_tip = BlockId::from((3, reorg_block3.block_hash()));
+    assert_eq!(
+        events,
+        vec![
+            WalletEvent::ChainTipChanged {
+                old_tip: new_tip,
+                new_tip: reorg_tip,
+            },
+            WalletEvent::TxConfirmed {
+                txid: spending_tx.compute_txid(),
+                tx: Arc::new(spending_tx),
+                block_time: ConfirmationBlockTime {
+                    block_id: reorg_tip,
+                    confirmation_time: 3100,
+                },
+                old_block_time: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_apply_block_connected_to_reorg() {
+    let (desc, change_desc) = get_test_wpkh_and_change_desc();
+    let (mut wallet, _, update) = new_wallet_and_funding_update(desc, Some(change_desc));
+    let genesis = wallet.local_chain().genesis_hash();
+    let funding_tx = (*update.tx_update.txs[0]).clone();
+    let spending_tx = (*update.tx_update.txs[1]).clone();
+    let spending_txid = spending_tx.compute_txid();
+
+    // chain: 1 (funding tx), 2a (spending tx), 3a
+    let block1 = test_block(genesis, 1000, vec![funding_tx]);
+    let block2a = test_block(block1.block_hash(), 2000, vec![spending_tx]);
+    let block3a = test_block(block2a.block_hash(), 3000, vec![]);
+    for (height, block) in [(1, &block1), (2, &block2a), (3, &block3a)] {
+        let summary = wallet
+            .apply_block_connected_to_reorg(block, height, block_connected_to(block, height))
+            .unwrap();
+        assert_eq!(summary, None, "extending the chain is not a reorg");
+    }
+    assert!(wallet
+        .get_tx(spending_txid)
+        .unwrap()
+        .chain_position
+        .is_confirmed());
+
+    // two-block reorg: 3b connects to 2b, which the wallet doesn't have at height 2
+    let block2b = test_block(block1.block_hash(), 2100, vec![]);
+    let block3b = test_block(block2b.block_hash(), 3100, vec![]);
+    let block2b_id = BlockId {
+        height: 2,
+        hash: block2b.block_hash(),
+    };
+    // without opting in to the reorg the block can't connect
+    let tip = wallet.latest_checkpoint().block_id();
+    assert!(matches!(
+        wallet.apply_block_connected_to(&block3b, 3, block2b_id),
+        Err(ApplyHeaderError::CannotConnect(_))
+    ));
+    assert_eq!(wallet.latest_checkpoint().block_id(), tip);
+
+    let summary = wallet
+        .apply_block_connected_to_reorg(&block3b, 3, block2b_id)
+        .unwrap()
+        .expect("blocks 2a and 3a are disconnected");
+    assert_eq!(summary.fork_height, 1);
+    assert_eq!(summary.disconnected_txs, vec![spending_txid]);
+
+    let chain = wallet
+        .local_chain()
+        .iter_checkpoints()
+        .map(|cp| cp.block_id())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        chain,
+        [
+            (3, block3b.block_hash()),
+            (2, block2b.block_hash()),
+            (1, block1.block_hash()),
+            (0, genesis),
+        ]
+        .map(BlockId::from)
+    );
+    assert!(!wallet
+        .get_tx(spending_txid)
+        .unwrap()
+        .chain_position
+        .is_confirmed());
+
+    // the block at the height of `connected_to` must be its parent
+    let block3c = test_block(block2a.block_hash(), 3200, vec![]);
+    assert!(wallet
+        .apply_block_connected_to_reorg(&block3c, 3, block2b_id)
+        .is_err());
+
+    // the new chain is extended without a reorg
+    let block4b = test_block(block3b.block_hash(), 4100, vec![]);
+    assert_eq!(
+        wallet
+            .apply_block_connected_to_reorg(&block4b, 4, block_connected_to(&block4b, 4))
+            .unwrap(),
+        None
+    );
+}
+
+fn block_connected_to(block: &Block, height: u32) -> BlockId {
+    BlockId {
+        height: height - 1,
+        hash: block.header.prev_blockhash,
+    }
+}